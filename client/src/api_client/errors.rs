@@ -12,6 +12,9 @@ pub enum ApiClientError {
     #[error("Authentication failed or invalid signature")]
     Unauthorized,
 
+    #[error("Server response could not be verified: {0}")]
+    UntrustedResponse(String),
+
     #[error("Unexpected Server response: {0}")]
     Other(StatusCode, String),
 
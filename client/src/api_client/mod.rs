@@ -4,32 +4,53 @@
 // Additionally, this module could be a separate crate for better reusability and maintenance.
 mod errors;
 mod models;
+mod resumable;
 mod retryable;
+mod verifiable;
 
-use std::time::Duration;
+pub use models::{LogHeadResponse, OrphanReportResponse, UploadEventResponse, UsageResponse};
+
+use std::{path::Path, time::Duration};
 
 use chrono::Utc;
-use file_server_library::models::Proof;
+use ed25519_dalek::VerifyingKey;
+use file_server_library::models::{Hash32, Proof};
 use hmac::{Hmac, Mac};
-use reqwest::{Client as HttpClient, StatusCode, Url};
+use reqwest::{Client as HttpClient, RequestBuilder, StatusCode, Url};
 use sha2::Sha256;
+use tokio::{fs::File, io::AsyncWriteExt};
 use tracing::instrument;
 use uuid::Uuid;
 
-use crate::api_client::{
-    errors::ApiClientError,
-    models::{FileMetadataResponse, FinalUploadResponse, InitiateUploadResponse},
-    retryable::{RetrySettings, Retryable},
+use crate::{
+    api_client::{
+        errors::ApiClientError,
+        models::{
+            FileListResponse, FileMetadataResponse, FinalUploadResponse, InitiateUploadResponse,
+            LogConsistencyProofResponse, LogInclusionProofResponse, UploadListResponse,
+            UploadSummaryResponse,
+        },
+        resumable::{Resumable, UploadSessionSettings},
+        retryable::{RetrySettings, Retryable},
+        verifiable::verify_log_head,
+    },
+    archive::ArchiveFormat,
 };
 
 type HmacSha256 = Hmac<Sha256>;
 
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+// The server sends a keep-alive every 15 seconds while an upload is quiet.
+const EVENT_STREAM_READ_TIMEOUT: Duration = Duration::from_secs(60);
+
 #[derive(Clone)]
 pub struct ApiClientArgs {
     pub api_key: String,
     pub api_secret: String,
     pub base_url: Url,
     pub correlation_id: Uuid,
+    /// Public keys the server responses must be signed with, nothing is verified when empty.
+    pub server_keys: Vec<VerifyingKey>,
 }
 
 impl ApiClientArgs {
@@ -47,8 +68,12 @@ impl ApiClientArgs {
 #[derive(Clone)]
 pub struct ApiClient {
     http: HttpClient,
+    // Event streams last as long as the upload does and archives take as long as the upload is
+    // large, they can't have a total timeout.
+    streaming_http: HttpClient,
     args: ApiClientArgs,
     retry_settings: RetrySettings,
+    upload_session_settings: UploadSessionSettings,
 }
 
 impl ApiClient {
@@ -57,11 +82,18 @@ impl ApiClient {
             .timeout(Duration::from_secs(30))
             .build()
             .map_err(|e| ApiClientError::Unexpected(e.to_string()))?;
+        let streaming_http = HttpClient::builder()
+            .connect_timeout(Duration::from_secs(30))
+            .read_timeout(EVENT_STREAM_READ_TIMEOUT)
+            .build()
+            .map_err(|e| ApiClientError::Unexpected(e.to_string()))?;
 
         Ok(Self {
             http,
+            streaming_http,
             args,
             retry_settings: RetrySettings::default(),
+            upload_session_settings: UploadSessionSettings::default(),
         })
     }
 
@@ -78,7 +110,9 @@ impl ApiClient {
     #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id))]
     pub async fn initiate(&self) -> Result<Uuid, ApiClientError> {
         let url = format!("{}api/v1/initiate", self.args.base_url);
-        let resp = self.send_with_retries(self.http.post(url)).await?;
+        let resp = self
+            .send_with_retries(idempotent(self.http.post(url)))
+            .await?;
 
         if resp.status() != StatusCode::CREATED {
             return Err(ApiClientError::from_response(resp).await);
@@ -89,14 +123,33 @@ impl ApiClient {
         Ok(body.id)
     }
 
-    #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id, id = %id, name = name, index = index))]
+    /// Uploads the file at `path`, whose contents hash to `hash`. Files above the session
+    /// threshold are read chunk by chunk as they are sent, smaller ones are sent whole.
+    #[instrument(skip(self, path), fields(correlation_id = %self.args.correlation_id, id = %id, name = name, index = index))]
     pub async fn upload_file(
         &self,
         id: Uuid,
         name: &str,
         index: usize,
-        bytes: Vec<u8>,
+        path: &Path,
+        hash: Hash32,
     ) -> Result<FileMetadataResponse, ApiClientError> {
+        // Contents the server already stores are not sent again. The blob may be collected
+        // between both requests, in which case the contents are uploaded as usual.
+        if self.blob_exists(hash).await? {
+            match self.upload_known_file(id, name, index, hash).await {
+                Err(ApiClientError::NotFound) => {}
+                result => return result,
+            }
+        }
+
+        let unexpected = |e: std::io::Error| ApiClientError::Unexpected(e.to_string());
+        let length = tokio::fs::metadata(path).await.map_err(unexpected)?.len();
+        if length > self.upload_session_settings.threshold_bytes as u64 {
+            return self.upload_file_in_chunks(id, name, index, path).await;
+        }
+        let bytes = tokio::fs::read(path).await.map_err(unexpected)?;
+
         let url = format!(
             "{}api/v1/{}/upload?name={}&index={}",
             self.args.base_url,
@@ -106,7 +159,7 @@ impl ApiClient {
         );
 
         let resp = self
-            .send_with_retries(self.http.post(url).body(bytes))
+            .send_with_retries(idempotent(self.http.post(url).body(bytes)))
             .await?;
 
         match resp.status() {
@@ -115,10 +168,50 @@ impl ApiClient {
         }
     }
 
+    async fn upload_known_file(
+        &self,
+        id: Uuid,
+        name: &str,
+        index: usize,
+        hash: Hash32,
+    ) -> Result<FileMetadataResponse, ApiClientError> {
+        let url = format!(
+            "{}api/v1/{}/upload?name={}&index={}&hash={}",
+            self.args.base_url,
+            id,
+            urlencoding::encode(name),
+            index,
+            hash.to_hex()
+        );
+
+        let resp = self
+            .send_with_retries(idempotent(self.http.post(url)))
+            .await?;
+
+        match resp.status() {
+            StatusCode::OK => Ok(resp.json().await?),
+            _ => Err(ApiClientError::from_response(resp).await),
+        }
+    }
+
+    #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id, hash = %hash.to_hex()))]
+    pub async fn blob_exists(&self, hash: Hash32) -> Result<bool, ApiClientError> {
+        let url = format!("{}api/v1/blobs/{}", self.args.base_url, hash.to_hex());
+        let resp = self.send_with_retries(self.http.head(url)).await?;
+
+        match resp.status() {
+            StatusCode::OK => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            _ => Err(ApiClientError::from_response(resp).await),
+        }
+    }
+
     #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id, id = %id))]
     pub async fn complete(&self, id: Uuid) -> Result<String, ApiClientError> {
         let url = format!("{}api/v1/{}/complete", self.args.base_url, id);
-        let resp = self.send_with_retries(self.http.post(url)).await?;
+        let resp = self
+            .send_with_retries(idempotent(self.http.post(url)))
+            .await?;
 
         if !resp.status().is_success() {
             return Err(ApiClientError::from_response(resp).await);
@@ -128,9 +221,191 @@ impl ApiClient {
         Ok(body.root_hex)
     }
 
+    /// Starts the next version of a completed upload from the files of its latest one.
+    #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id, id = %id))]
+    pub async fn create_version(&self, id: Uuid) -> Result<UploadSummaryResponse, ApiClientError> {
+        let url = format!("{}api/v1/{}/versions", self.args.base_url, id);
+        let resp = self
+            .send_with_retries(idempotent(self.http.post(url)))
+            .await?;
+
+        match resp.status() {
+            StatusCode::CREATED => Ok(resp.json().await?),
+            _ => Err(ApiClientError::from_response(resp).await),
+        }
+    }
+
+    #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id, id = %id))]
+    pub async fn delete_upload(&self, id: Uuid) -> Result<(), ApiClientError> {
+        let url = format!("{}api/v1/{}", self.args.base_url, id);
+        let resp = self
+            .send_with_retries(idempotent(self.http.delete(url)))
+            .await?;
+
+        match resp.status() {
+            StatusCode::NO_CONTENT => Ok(()),
+            _ => Err(ApiClientError::from_response(resp).await),
+        }
+    }
+
+    /// Follows the events of an upload, calling `on_event` with each of them as they happen,
+    /// until the upload is completed. An upload already completed only yields its completion.
+    #[instrument(skip(self, on_event), fields(correlation_id = %self.args.correlation_id, id = %id))]
+    pub async fn watch_upload(
+        &self,
+        id: Uuid,
+        mut on_event: impl FnMut(UploadEventResponse),
+    ) -> Result<(), ApiClientError> {
+        let url = format!("{}api/v1/{}/events", self.args.base_url, id);
+        let mut resp = self
+            .send_with_retries(
+                self.streaming_http
+                    .get(url)
+                    .header(reqwest::header::ACCEPT, "text/event-stream"),
+            )
+            .await?;
+
+        if resp.status() != StatusCode::OK {
+            return Err(ApiClientError::from_response(resp).await);
+        }
+
+        let mut buffer = String::new();
+        while let Some(chunk) = resp.chunk().await? {
+            buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+            while let Some(end) = buffer.find("\n\n") {
+                let block: String = buffer.drain(..end + 2).collect();
+                // Keep-alives are comments and carry no data.
+                let Some(data) = event_data(&block) else {
+                    continue;
+                };
+                let event = serde_json::from_str(&data)
+                    .map_err(|e| ApiClientError::Unexpected(format!("malformed event: {e}")))?;
+                on_event(event);
+            }
+        }
+
+        Ok(())
+    }
+
     #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id, id = %id, index = index))]
-    pub async fn get_proof(&self, id: Uuid, index: usize) -> Result<Proof, ApiClientError> {
-        let url = format!("{}api/v1/{}/proof/{}", self.args.base_url, id, index);
+    pub async fn get_proof(
+        &self,
+        id: Uuid,
+        index: usize,
+        version: Option<u32>,
+    ) -> Result<Proof, ApiClientError> {
+        let url = versioned(
+            format!("{}api/v1/{}/proof/{}", self.args.base_url, id, index),
+            version,
+        );
+        let resp = self.send_with_retries(self.http.get(url)).await?;
+
+        match resp.status() {
+            StatusCode::OK => Ok(resp.json().await?),
+            _ => Err(ApiClientError::from_response(resp).await),
+        }
+    }
+
+    /// Returns what the API key uses along with its quotas.
+    #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id))]
+    pub async fn get_usage(&self) -> Result<UsageResponse, ApiClientError> {
+        let url = format!("{}api/v1/usage", self.args.base_url);
+        let resp = self.send_with_retries(self.http.get(url)).await?;
+
+        match resp.status() {
+            StatusCode::OK => Ok(resp.json().await?),
+            _ => Err(ApiClientError::from_response(resp).await),
+        }
+    }
+
+    /// Looks for orphan objects under the next batch of uploads on the server, deleting them
+    /// unless `dry_run` is set. `cursor` is the `next_cursor` of the previous batch. Only admin
+    /// API keys are allowed to.
+    #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id))]
+    pub async fn collect_orphans(
+        &self,
+        cursor: Option<&str>,
+        limit: usize,
+        dry_run: bool,
+    ) -> Result<OrphanReportResponse, ApiClientError> {
+        let mut url = format!(
+            "{}api/v1/admin/gc/orphans?dry_run={}&limit={}",
+            self.args.base_url, dry_run, limit
+        );
+        if let Some(cursor) = cursor {
+            url.push_str(&format!("&cursor={}", cursor));
+        }
+        let resp = self
+            .send_with_retries(idempotent(self.http.post(url)))
+            .await?;
+
+        match resp.status() {
+            StatusCode::OK => Ok(resp.json().await?),
+            _ => Err(ApiClientError::from_response(resp).await),
+        }
+    }
+
+    /// Returns one page of the uploads initiated with the API key, newest first. `cursor` is the
+    /// `next_cursor` of the previous page.
+    #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id))]
+    pub async fn list_uploads(
+        &self,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<UploadListResponse, ApiClientError> {
+        let mut url = format!("{}api/v1/uploads?limit={}", self.args.base_url, limit);
+        if let Some(cursor) = cursor {
+            url.push_str(&format!("&cursor={}", cursor));
+        }
+        let resp = self.send_with_retries(self.http.get(url)).await?;
+
+        match resp.status() {
+            StatusCode::OK => Ok(resp.json().await?),
+            _ => Err(ApiClientError::from_response(resp).await),
+        }
+    }
+
+    /// Returns one page of the files of an upload, `next_offset` points to the following one.
+    #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id, id = %id))]
+    pub async fn list_files(
+        &self,
+        id: Uuid,
+        offset: usize,
+        limit: usize,
+        version: Option<u32>,
+    ) -> Result<FileListResponse, ApiClientError> {
+        let url = versioned(
+            format!(
+                "{}api/v1/{}/files?offset={}&limit={}",
+                self.args.base_url, id, offset, limit
+            ),
+            version,
+        );
+        let resp = self.send_with_retries(self.http.get(url)).await?;
+
+        match resp.status() {
+            StatusCode::OK => Ok(resp.json().await?),
+            _ => Err(ApiClientError::from_response(resp).await),
+        }
+    }
+
+    #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id, id = %id, name = %name))]
+    pub async fn get_proof_by_name(
+        &self,
+        id: Uuid,
+        name: &str,
+        version: Option<u32>,
+    ) -> Result<Proof, ApiClientError> {
+        let url = versioned(
+            format!(
+                "{}api/v1/{}/files/{}/proof",
+                self.args.base_url,
+                id,
+                urlencoding::encode(name)
+            ),
+            version,
+        );
         let resp = self.send_with_retries(self.http.get(url)).await?;
 
         match resp.status() {
@@ -140,8 +415,76 @@ impl ApiClient {
     }
 
     #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id, id = %id, index = index))]
-    pub async fn download_file(&self, id: Uuid, index: usize) -> Result<Vec<u8>, ApiClientError> {
-        let url = format!("{}api/v1/{}/file/{}", self.args.base_url, id, index);
+    pub async fn download_file(
+        &self,
+        id: Uuid,
+        index: usize,
+        version: Option<u32>,
+    ) -> Result<Vec<u8>, ApiClientError> {
+        let url = versioned(
+            format!("{}api/v1/{}/file/{}", self.args.base_url, id, index),
+            version,
+        );
+        let resp = self.send_with_retries(self.http.get(url)).await?;
+
+        if !resp.status().is_success() {
+            return Err(ApiClientError::from_response(resp).await);
+        }
+
+        Ok(resp.bytes().await?.to_vec())
+    }
+
+    /// Downloads every file of a completed upload as a single archive into `destination`, see
+    /// `crate::archive`. The archive is written as it arrives, it is never held in memory.
+    #[instrument(skip(self, destination), fields(correlation_id = %self.args.correlation_id, id = %id, format = format.as_str()))]
+    pub async fn download_archive(
+        &self,
+        id: Uuid,
+        format: ArchiveFormat,
+        version: Option<u32>,
+        destination: &Path,
+    ) -> Result<(), ApiClientError> {
+        let url = versioned(
+            format!(
+                "{}api/v1/{}/archive?format={}",
+                self.args.base_url,
+                id,
+                format.as_str()
+            ),
+            version,
+        );
+        let mut resp = self.send_with_retries(self.streaming_http.get(url)).await?;
+
+        if !resp.status().is_success() {
+            return Err(ApiClientError::from_response(resp).await);
+        }
+
+        let unexpected = |e: std::io::Error| ApiClientError::Unexpected(e.to_string());
+        let mut file = File::create(destination).await.map_err(unexpected)?;
+        while let Some(chunk) = resp.chunk().await? {
+            file.write_all(&chunk).await.map_err(unexpected)?;
+        }
+        file.flush().await.map_err(unexpected)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id, id = %id, name = %name))]
+    pub async fn download_file_by_name(
+        &self,
+        id: Uuid,
+        name: &str,
+        version: Option<u32>,
+    ) -> Result<Vec<u8>, ApiClientError> {
+        let url = versioned(
+            format!(
+                "{}api/v1/{}/files/{}",
+                self.args.base_url,
+                id,
+                urlencoding::encode(name)
+            ),
+            version,
+        );
         let resp = self.send_with_retries(self.http.get(url)).await?;
 
         if !resp.status().is_success() {
@@ -150,4 +493,75 @@ impl ApiClient {
 
         Ok(resp.bytes().await?.to_vec())
     }
+
+    /// Proof that the latest root of the upload is in the transparency log, along with the
+    /// signed head it was built against.
+    #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id, id = %id))]
+    pub async fn get_log_proof(
+        &self,
+        id: Uuid,
+    ) -> Result<LogInclusionProofResponse, ApiClientError> {
+        let url = format!("{}api/v1/{}/log/proof", self.args.base_url, id);
+        let resp = self.send_with_retries(self.http.get(url)).await?;
+
+        match resp.status() {
+            StatusCode::OK => Ok(resp.json().await?),
+            _ => Err(ApiClientError::from_response(resp).await),
+        }
+    }
+
+    /// Proof that the transparency log of `second` entries extends the log of `first` entries.
+    #[instrument(skip(self), fields(correlation_id = %self.args.correlation_id))]
+    pub async fn get_log_consistency(
+        &self,
+        first: u64,
+        second: u64,
+    ) -> Result<LogConsistencyProofResponse, ApiClientError> {
+        let url = format!(
+            "{}api/v1/log/consistency?first={}&second={}",
+            self.args.base_url, first, second
+        );
+        let resp = self.send_with_retries(self.http.get(url)).await?;
+
+        match resp.status() {
+            StatusCode::OK => Ok(resp.json().await?),
+            _ => Err(ApiClientError::from_response(resp).await),
+        }
+    }
+
+    /// Checks the head was signed by one of the pinned keys, nothing is checked when there are
+    /// none.
+    pub fn verify_log_head(&self, head: &LogHeadResponse) -> Result<(), ApiClientError> {
+        if self.args.server_keys.is_empty() {
+            return Ok(());
+        }
+
+        verify_log_head(&self.args.server_keys, head)
+    }
+}
+
+// Every logical operation gets a key of its own, its retries carry the same one so the server
+// replays the first response instead of running it again.
+fn idempotent(req_builder: RequestBuilder) -> RequestBuilder {
+    req_builder.header(IDEMPOTENCY_KEY_HEADER, Uuid::new_v4().to_string())
+}
+
+// Data of a Server-Sent Event, its lines are joined back when it spans several of them.
+fn event_data(block: &str) -> Option<String> {
+    let lines: Vec<&str> = block
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+        .collect();
+
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+// Responses are signed over the path exactly as requested, so the query is built by hand.
+fn versioned(url: String, version: Option<u32>) -> String {
+    match version {
+        Some(version) if url.contains('?') => format!("{}&version={}", url, version),
+        Some(version) => format!("{}?version={}", url, version),
+        None => url,
+    }
 }
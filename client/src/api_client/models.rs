@@ -3,6 +3,7 @@
 // coupling between client and server and the binary level.
 // There are tools to create DTOs and ApiClients from OpenAPI specification that will simplify the
 // maintenance (e.g. progenitor crate, didn't use it here cause I never tested it before)
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -22,3 +23,133 @@ pub struct FileMetadataResponse {
 pub struct FinalUploadResponse {
     pub root_hex: String,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadSessionResponse {
+    pub session_id: Uuid,
+    pub offset: u64,
+    pub length: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEntryResponse {
+    pub index: usize,
+    pub name: String,
+    pub size: u64,
+    pub leaf_hash: String,
+    pub content_type: String,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileListResponse {
+    pub files: Vec<FileEntryResponse>,
+    pub total: usize,
+    pub next_offset: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadSummaryResponse {
+    pub id: Uuid,
+    pub version: u32,
+    pub previous_version: Option<u32>,
+    pub state: String,
+    pub root_hex: Option<String>,
+    pub file_count: usize,
+    pub total_size: u64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadListResponse {
+    pub uploads: Vec<UploadSummaryResponse>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaLimitsResponse {
+    pub max_stored_bytes: Option<u64>,
+    pub max_files_per_upload: Option<u64>,
+    pub max_file_size_bytes: Option<u64>,
+    pub max_open_uploads: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageResponse {
+    pub stored_bytes: u64,
+    pub open_uploads: u64,
+    pub limits: QuotaLimitsResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrphanObjectResponse {
+    pub upload_id: Uuid,
+    pub name: String,
+    pub size: u64,
+    pub last_modified: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrphanReportResponse {
+    pub dry_run: bool,
+    pub uploads_checked: usize,
+    pub orphans: Vec<OrphanObjectResponse>,
+    pub deleted: usize,
+    pub failed: usize,
+    pub next_cursor: Option<String>,
+}
+
+/// Timestamps are in milliseconds since the epoch, exactly as they were signed or hashed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogHeadResponse {
+    pub size: u64,
+    pub root: String,
+    pub timestamp: i64,
+    pub key_id: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntryResponse {
+    pub index: u64,
+    pub upload_id: Uuid,
+    pub owner: String,
+    pub root: String,
+    pub appended_at: i64,
+    pub leaf_hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogInclusionProofResponse {
+    pub head: LogHeadResponse,
+    pub entry: LogEntryResponse,
+    pub proof: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogConsistencyProofResponse {
+    pub first: LogHeadResponse,
+    pub second: LogHeadResponse,
+    pub proof: Vec<String>,
+}
+
+/// Event of an upload as streamed by the server, tagged with its kind under `event`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum UploadEventResponse {
+    FileReceived {
+        index: usize,
+        name: String,
+        size: u64,
+    },
+    HashComputed {
+        index: usize,
+        name: String,
+        leaf_hash: String,
+    },
+    Completed {
+        version: u32,
+        root: String,
+    },
+}
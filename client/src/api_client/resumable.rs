@@ -0,0 +1,186 @@
+// Large files are sent through an upload session, chunk by chunk, so a dropped connection only
+// costs the chunk in flight. The server is the source of truth for the committed offset: after a
+// failed chunk the client asks for it and continues from there. Chunks are read from the file as
+// they are sent, only one of them is ever in memory.
+use std::{io::SeekFrom, path::Path};
+
+use reqwest::{Response, StatusCode};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+use tracing::{instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    ApiClient,
+    api_client::{
+        errors::ApiClientError,
+        idempotent,
+        models::{FileMetadataResponse, UploadSessionResponse},
+        retryable::Retryable,
+    },
+};
+
+const UPLOAD_OFFSET: &str = "upload-offset";
+const UPLOAD_LENGTH: &str = "upload-length";
+
+#[derive(Clone, Debug)]
+pub struct UploadSessionSettings {
+    /// Files larger than this are uploaded through a session.
+    pub threshold_bytes: usize,
+    pub chunk_size_bytes: usize,
+    /// How many times in a row a chunk may fail before giving up.
+    pub max_resume_attempts: usize,
+}
+
+impl Default for UploadSessionSettings {
+    fn default() -> Self {
+        Self {
+            threshold_bytes: 16 * 1024 * 1024,
+            chunk_size_bytes: 8 * 1024 * 1024,
+            max_resume_attempts: 3,
+        }
+    }
+}
+
+fn upload_offset(resp: &Response) -> Result<u64, ApiClientError> {
+    resp.headers()
+        .get(UPLOAD_OFFSET)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.parse::<u64>().ok())
+        .ok_or_else(|| ApiClientError::Unexpected("missing upload-offset header".to_owned()))
+}
+
+fn unexpected(e: std::io::Error) -> ApiClientError {
+    ApiClientError::Unexpected(e.to_string())
+}
+
+pub trait Resumable {
+    async fn upload_file_in_chunks(
+        &self,
+        id: Uuid,
+        name: &str,
+        index: usize,
+        path: &Path,
+    ) -> Result<FileMetadataResponse, ApiClientError>;
+}
+
+impl Resumable for ApiClient {
+    #[instrument(skip(self, path), fields(correlation_id = %self.args.correlation_id, id = %id, name = name, index = index))]
+    async fn upload_file_in_chunks(
+        &self,
+        id: Uuid,
+        name: &str,
+        index: usize,
+        path: &Path,
+    ) -> Result<FileMetadataResponse, ApiClientError> {
+        let settings = self.upload_session_settings.clone();
+        let mut file = File::open(path).await.map_err(unexpected)?;
+        let length = file.metadata().await.map_err(unexpected)?.len();
+
+        let url = format!(
+            "{}api/v1/{}/sessions?name={}&index={}",
+            self.args.base_url,
+            id,
+            urlencoding::encode(name),
+            index
+        );
+        let resp = self
+            .send_with_retries(idempotent(
+                self.http.post(url).header(UPLOAD_LENGTH, length),
+            ))
+            .await?;
+
+        if resp.status() != StatusCode::CREATED {
+            return Err(ApiClientError::from_response(resp).await);
+        }
+
+        let session: UploadSessionResponse = resp.json().await?;
+        let session_url = format!(
+            "{}api/v1/{}/sessions/{}",
+            self.args.base_url, id, session.session_id
+        );
+
+        let mut offset = session.offset;
+        let mut attempts = 0;
+
+        while offset < length {
+            let chunk = read_chunk(&mut file, offset, length, settings.chunk_size_bytes).await?;
+
+            match self.append_chunk(&session_url, offset, chunk).await {
+                Ok(committed) => {
+                    offset = committed;
+                    attempts = 0;
+                }
+                Err(e) if attempts < settings.max_resume_attempts => {
+                    attempts += 1;
+                    warn!(error = %e, offset, attempts, "chunk failed; resuming from the committed offset");
+                    offset = self.committed_offset(&session_url).await?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let url = format!("{}/finalize", session_url);
+        let resp = self
+            .send_with_retries(idempotent(self.http.post(url)))
+            .await?;
+
+        match resp.status() {
+            StatusCode::OK => Ok(resp.json().await?),
+            _ => Err(ApiClientError::from_response(resp).await),
+        }
+    }
+}
+
+// The chunk starting at `offset`, read from wherever the file was left.
+async fn read_chunk(
+    file: &mut File,
+    offset: u64,
+    length: u64,
+    chunk_size_bytes: usize,
+) -> Result<Vec<u8>, ApiClientError> {
+    let size = (length - offset).min(chunk_size_bytes as u64) as usize;
+    let mut chunk = vec![0; size];
+
+    file.seek(SeekFrom::Start(offset))
+        .await
+        .map_err(unexpected)?;
+    file.read_exact(&mut chunk).await.map_err(unexpected)?;
+
+    Ok(chunk)
+}
+
+impl ApiClient {
+    async fn append_chunk(
+        &self,
+        session_url: &str,
+        offset: u64,
+        chunk: Vec<u8>,
+    ) -> Result<u64, ApiClientError> {
+        let resp = self
+            .send_with_retries(
+                self.http
+                    .patch(session_url)
+                    .header(UPLOAD_OFFSET, offset)
+                    .header("content-type", "application/offset+octet-stream")
+                    .body(chunk),
+            )
+            .await?;
+
+        match resp.status() {
+            StatusCode::NO_CONTENT => upload_offset(&resp),
+            _ => Err(ApiClientError::from_response(resp).await),
+        }
+    }
+
+    async fn committed_offset(&self, session_url: &str) -> Result<u64, ApiClientError> {
+        let resp = self.send_with_retries(self.http.head(session_url)).await?;
+
+        match resp.status() {
+            StatusCode::OK => upload_offset(&resp),
+            _ => Err(ApiClientError::from_response(resp).await),
+        }
+    }
+}
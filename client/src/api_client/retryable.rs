@@ -10,7 +10,13 @@ use tokio_retry::{
 use tracing::Instrument;
 use uuid::Uuid;
 
-use crate::{ApiClient, api_client::errors::ApiClientError};
+use crate::{
+    ApiClient,
+    api_client::{
+        errors::ApiClientError,
+        verifiable::{SentRequest, Verifiable},
+    },
+};
 
 fn is_transient_reqwest(e: &reqwest::Error) -> bool {
     e.is_timeout() || e.is_connect() || e.is_request()
@@ -63,6 +69,10 @@ impl Retryable for ApiClient {
         let request = req_builder.try_clone().unwrap().build().unwrap();
         let method = request.method().as_str().to_string();
         let path = request.url().path().to_string();
+        let signed_path = match request.url().query() {
+            Some(query) => format!("{path}?{query}"),
+            None => path.clone(),
+        };
 
         let request_id = Uuid::new_v4().to_string();
         let url_for_log = format!("{}{}", self.args.base_url, path);
@@ -73,8 +83,21 @@ impl Retryable for ApiClient {
                 .map(jitter)
                 .take(retry_settings.max_retries);
 
-        Retry::spawn(strategy, move || {
+        let (resp, signature) = Retry::spawn(strategy, move || {
             let req_builder = self.sign_request(req_builder.try_clone().unwrap());
+            let signature = req_builder
+                .try_clone()
+                .unwrap()
+                .build()
+                .ok()
+                .and_then(|request| {
+                    request
+                        .headers()
+                        .get("X-AUTH-SIGNATURE")
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string)
+                })
+                .unwrap_or_default();
 
             let span = tracing::info_span!(
                 "http.client",
@@ -112,7 +135,7 @@ impl Retryable for ApiClient {
                                 format!("transient http: {status}"),
                             ))
                         } else {
-                            Ok(resp)
+                            Ok((resp, signature))
                         }
                     }
                     Err(e) if is_transient_reqwest(&e) => {
@@ -126,6 +149,13 @@ impl Retryable for ApiClient {
                 }
             }
         })
-        .await
+        .await?;
+
+        let sent = SentRequest {
+            method: request.method().as_str().to_string(),
+            path: signed_path,
+            signature,
+        };
+        self.verify_response(&sent, resp).await
     }
 }
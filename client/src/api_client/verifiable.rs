@@ -0,0 +1,120 @@
+// Responses are signed by the server with Ed25519 (see `file_server_library::signing`). When
+// server keys are pinned, every response must carry a valid signature from one of them for the
+// request that was sent, and its body must match the signed digest when there is one. Nothing is
+// verified when no key is pinned.
+use ed25519_dalek::{Signature, VerifyingKey};
+use file_server_library::{
+    models::Hash32,
+    signing::{
+        SERVER_DIGEST_HEADER, SERVER_KEY_HEADER, SERVER_SIGNATURE_HEADER, SignedResponse, key_id,
+    },
+    transparency::log_head_message,
+};
+use reqwest::Response;
+
+use crate::{
+    ApiClient,
+    api_client::{errors::ApiClientError, models::LogHeadResponse},
+};
+
+/// The parts of a sent request the response signature is bound to.
+pub struct SentRequest {
+    pub method: String,
+    pub path: String,
+    pub signature: String,
+}
+
+pub trait Verifiable {
+    async fn verify_response(
+        &self,
+        request: &SentRequest,
+        resp: Response,
+    ) -> Result<Response, ApiClientError>;
+}
+
+impl Verifiable for ApiClient {
+    async fn verify_response(
+        &self,
+        request: &SentRequest,
+        resp: Response,
+    ) -> Result<Response, ApiClientError> {
+        if self.args.server_keys.is_empty() {
+            return Ok(resp);
+        }
+
+        let untrusted = |reason: &str| ApiClientError::UntrustedResponse(reason.to_string());
+        let header = |name: &str| {
+            resp.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+
+        let signing_key_id = header(SERVER_KEY_HEADER).ok_or_else(|| untrusted("unsigned"))?;
+        let key = pinned_key(&self.args.server_keys, signing_key_id)
+            .ok_or_else(|| untrusted(&format!("signed by unknown key {signing_key_id}")))?;
+        let signature = header(SERVER_SIGNATURE_HEADER)
+            .and_then(|signature| hex::decode(signature).ok())
+            .and_then(|signature| Signature::from_slice(&signature).ok())
+            .ok_or_else(|| untrusted("malformed signature"))?;
+
+        let message = SignedResponse::new(
+            signing_key_id,
+            &request.method,
+            &request.path,
+            resp.status().as_u16(),
+        )
+        .with_request_signature(&request.signature)
+        .with_headers(header)
+        .message();
+
+        key.verify_strict(&message, &signature)
+            .map_err(|_| untrusted("invalid signature"))?;
+
+        let Some(digest) = header(SERVER_DIGEST_HEADER).map(str::to_string) else {
+            return Ok(resp);
+        };
+
+        // The body has to be read to check it, the response is rebuilt around it.
+        let status = resp.status();
+        let version = resp.version();
+        let headers = resp.headers().clone();
+        let body = resp.bytes().await?;
+
+        if Hash32::hash(&body).to_hex() != digest {
+            return Err(untrusted("body does not match the signed digest"));
+        }
+
+        let mut rebuilt = http::Response::new(body);
+        *rebuilt.status_mut() = status;
+        *rebuilt.version_mut() = version;
+        *rebuilt.headers_mut() = headers;
+
+        Ok(Response::from(rebuilt))
+    }
+}
+
+/// Transparency log heads are signed on their own, so they can be kept and checked later.
+pub(crate) fn verify_log_head(
+    keys: &[VerifyingKey],
+    head: &LogHeadResponse,
+) -> Result<(), ApiClientError> {
+    let untrusted = |reason: &str| ApiClientError::UntrustedResponse(reason.to_string());
+
+    let key = pinned_key(keys, &head.key_id)
+        .ok_or_else(|| untrusted(&format!("log head signed by unknown key {}", head.key_id)))?;
+    let root = Hash32::from_hex(&head.root).map_err(|_| untrusted("malformed log head root"))?;
+    let signature = hex::decode(&head.signature)
+        .ok()
+        .and_then(|signature| Signature::from_slice(&signature).ok())
+        .ok_or_else(|| untrusted("malformed log head signature"))?;
+
+    key.verify_strict(
+        &log_head_message(head.size, &root, head.timestamp),
+        &signature,
+    )
+    .map_err(|_| untrusted("invalid log head signature"))
+}
+
+fn pinned_key<'a>(keys: &'a [VerifyingKey], id: &str) -> Option<&'a VerifyingKey> {
+    keys.iter().find(|key| key_id(key.as_bytes()) == id)
+}
@@ -0,0 +1,220 @@
+// Reading side of the archives served by `GET /{id}/archive`, extracted entry by entry to disk.
+// Zip goes through the zip crate, the server only writes plain ustar and pax path/size records,
+// which is all this tar reader understands.
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use file_server_library::models::{Hash32, Hash32Hasher, Proof};
+use serde::Deserialize;
+use uuid::Uuid;
+
+pub const MANIFEST_PATH: &str = "manifest.json";
+
+#[derive(Clone, Copy, Debug)]
+pub enum ArchiveFormat {
+    Tar,
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+impl std::str::FromStr for ArchiveFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tar" => Ok(ArchiveFormat::Tar),
+            "zip" => Ok(ArchiveFormat::Zip),
+            other => Err(anyhow::anyhow!("unknown archive format {other}")),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ArchiveManifest {
+    pub id: Uuid,
+    pub version: u32,
+    pub root: String,
+    pub files: Vec<ArchiveManifestEntry>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ArchiveManifestEntry {
+    pub index: usize,
+    pub name: String,
+    pub path: String,
+    pub leaf_hash: String,
+    pub proof: Proof,
+}
+
+/// Regular file of an archive, written out to `file` and hashed on the way.
+pub struct ExtractedEntry {
+    pub file: PathBuf,
+    pub leaf: Hash32,
+}
+
+/// Extracts every regular file of the archive into the `into` directory, keyed by their path in
+/// the archive. Entries are copied through a fixed size buffer, so memory does not grow with
+/// the size of the upload.
+pub fn extract_archive(
+    format: ArchiveFormat,
+    archive: &Path,
+    into: &Path,
+) -> anyhow::Result<HashMap<String, ExtractedEntry>> {
+    let archive = File::open(archive)?;
+    match format {
+        ArchiveFormat::Tar => extract_tar(BufReader::new(archive), into),
+        ArchiveFormat::Zip => extract_zip(archive, into),
+    }
+}
+
+const COPY_BUFFER_BYTES: usize = 64 * 1024;
+
+// Entries are named after their position, the names in the archive come from the server.
+fn extract_entry(
+    mut contents: impl Read,
+    into: &Path,
+    position: usize,
+) -> anyhow::Result<(ExtractedEntry, u64)> {
+    let path = into.join(position.to_string());
+    let mut file = BufWriter::new(File::create(&path)?);
+    let mut hasher = Hash32Hasher::new();
+    let mut buffer = vec![0; COPY_BUFFER_BYTES];
+    let mut copied = 0;
+
+    loop {
+        let read = contents.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        file.write_all(&buffer[..read])?;
+        copied += read as u64;
+    }
+    file.flush()?;
+
+    let entry = ExtractedEntry {
+        file: path,
+        leaf: hasher.finalize(),
+    };
+    Ok((entry, copied))
+}
+
+fn extract_zip(archive: File, into: &Path) -> anyhow::Result<HashMap<String, ExtractedEntry>> {
+    let mut zip = zip::ZipArchive::new(archive)?;
+    let mut entries = HashMap::with_capacity(zip.len());
+
+    for i in 0..zip.len() {
+        let file = zip.by_index(i)?;
+        if !file.is_file() {
+            continue;
+        }
+
+        let name = file.name().to_string();
+        let (entry, _) = extract_entry(file, into, i)?;
+        entries.insert(name, entry);
+    }
+
+    Ok(entries)
+}
+
+const TAR_BLOCK: u64 = 512;
+// Pax headers only carry a path and a size, anything larger is not one the server wrote.
+const MAX_PAX_BYTES: u64 = 64 * 1024;
+
+fn extract_tar(
+    mut archive: impl Read,
+    into: &Path,
+) -> anyhow::Result<HashMap<String, ExtractedEntry>> {
+    let mut entries = HashMap::new();
+    let mut pax: HashMap<String, String> = HashMap::new();
+    let mut header = [0; TAR_BLOCK as usize];
+
+    loop {
+        archive
+            .read_exact(&mut header)
+            .map_err(|_| anyhow::anyhow!("truncated tar archive"))?;
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+
+        let size = match pax.remove("size") {
+            Some(size) => size.parse()?,
+            None => parse_octal(&header[124..136])?,
+        };
+        let name = match pax.remove("path") {
+            Some(path) => path,
+            None => String::from_utf8(until_nul(&header[..100]).to_vec())?,
+        };
+
+        let mut data = (&mut archive).take(size);
+        let copied = match header[156] {
+            b'x' if size <= MAX_PAX_BYTES => {
+                let mut records = Vec::with_capacity(size as usize);
+                data.read_to_end(&mut records)?;
+                pax = parse_pax(&records)?;
+                records.len() as u64
+            }
+            b'x' => anyhow::bail!("pax header of {name} is too large"),
+            b'0' | 0 => {
+                let (entry, copied) = extract_entry(data, into, entries.len())?;
+                entries.insert(name.clone(), entry);
+                copied
+            }
+            _ => io::copy(&mut data, &mut io::sink())?,
+        };
+        if copied != size {
+            anyhow::bail!("truncated tar entry {name}");
+        }
+
+        let padding = size.div_ceil(TAR_BLOCK) * TAR_BLOCK - size;
+        io::copy(&mut (&mut archive).take(padding), &mut io::sink())?;
+    }
+
+    Ok(entries)
+}
+
+fn until_nul(field: &[u8]) -> &[u8] {
+    field.split(|&b| b == 0).next().unwrap_or_default()
+}
+
+fn parse_octal(field: &[u8]) -> anyhow::Result<u64> {
+    let digits = std::str::from_utf8(until_nul(field))?.trim();
+    Ok(u64::from_str_radix(digits, 8)?)
+}
+
+// Records look like `<length> <key>=<value>\n`, the length counting the whole record.
+fn parse_pax(data: &[u8]) -> anyhow::Result<HashMap<String, String>> {
+    let mut records = HashMap::new();
+    let mut rest = data;
+
+    while !rest.is_empty() {
+        let space = rest
+            .iter()
+            .position(|&b| b == b' ')
+            .ok_or_else(|| anyhow::anyhow!("malformed pax record"))?;
+        let len: usize = std::str::from_utf8(&rest[..space])?.parse()?;
+        let record = rest
+            .get(space + 1..len)
+            .ok_or_else(|| anyhow::anyhow!("truncated pax record"))?;
+        let record = std::str::from_utf8(record)?.trim_end_matches('\n');
+
+        if let Some((key, value)) = record.split_once('=') {
+            records.insert(key.to_string(), value.to_string());
+        }
+        rest = &rest[len..];
+    }
+
+    Ok(records)
+}
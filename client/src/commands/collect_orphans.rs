@@ -0,0 +1,167 @@
+use async_trait::async_trait;
+use clap::{Arg, ArgAction, ArgMatches};
+use ed25519_dalek::VerifyingKey;
+use reqwest::Url;
+use uuid::Uuid;
+
+use crate::{
+    ApiClient, ApiClientArgs,
+    commands::{
+        Command,
+        helpers::{get_server_keys, server_key_arg},
+    },
+};
+
+struct CollectOrphansCommandArgs {
+    api_key: String,
+    api_secret: String,
+    base_url: String,
+    server_keys: Vec<VerifyingKey>,
+    dry_run: bool,
+}
+
+impl From<&ArgMatches> for CollectOrphansCommandArgs {
+    fn from(args: &ArgMatches) -> Self {
+        let api_key = args
+            .get_one::<String>("api-key")
+            .expect("API-KEY is required")
+            .to_owned();
+
+        let api_secret = args
+            .get_one::<String>("api-secret")
+            .expect("API-SECRET is required")
+            .to_owned();
+
+        let base_url = args
+            .get_one::<String>("base-url")
+            .expect("Base URL is required")
+            .to_owned();
+
+        let server_keys = get_server_keys(args).expect("Failed to parse server keys");
+
+        let dry_run = args.get_flag("dry-run");
+
+        Self {
+            api_key,
+            api_secret,
+            base_url,
+            server_keys,
+            dry_run,
+        }
+    }
+}
+
+impl From<&CollectOrphansCommandArgs> for ApiClientArgs {
+    fn from(val: &CollectOrphansCommandArgs) -> Self {
+        ApiClientArgs {
+            api_key: val.api_key.clone(),
+            api_secret: val.api_secret.clone(),
+            base_url: Url::parse(&val.base_url).expect("Failed to parse base URL"),
+            correlation_id: Uuid::new_v4(),
+            server_keys: val.server_keys.clone(),
+        }
+    }
+}
+
+// Uploads checked per request, so every request stays short however many uploads there are.
+const BATCH_SIZE: usize = 100;
+
+pub struct CollectOrphansCommand;
+
+impl CollectOrphansCommand {
+    async fn collect_orphans(&self, api_client: ApiClient, dry_run: bool) -> anyhow::Result<()> {
+        let mut cursor: Option<String> = None;
+        let (mut uploads_checked, mut orphans, mut orphan_bytes) = (0, 0, 0);
+        let (mut deleted, mut failed) = (0, 0);
+
+        loop {
+            let report = api_client
+                .collect_orphans(cursor.as_deref(), BATCH_SIZE, dry_run)
+                .await?;
+
+            for orphan in &report.orphans {
+                println!(
+                    "- {}/{} ({} bytes, last modified {})",
+                    orphan.upload_id, orphan.name, orphan.size, orphan.last_modified
+                );
+                orphan_bytes += orphan.size;
+            }
+            uploads_checked += report.uploads_checked;
+            orphans += report.orphans.len();
+            deleted += report.deleted;
+            failed += report.failed;
+
+            match report.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        println!("uploads checked: {uploads_checked}");
+        println!("orphans found: {orphans} ({orphan_bytes} bytes)");
+        if dry_run {
+            println!("dry run, nothing was deleted");
+        } else {
+            println!("orphans deleted: {deleted}");
+            println!("orphans failed to delete: {failed}");
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Command for CollectOrphansCommand {
+    fn create(&self) -> clap::Command {
+        clap::Command::new("collect-orphans")
+            .about("This command looks for objects stored on the server that no upload refers to anymore, and deletes them. Requires an admin API key.")
+            .long_flag("collect-orphans")
+            .arg(
+                Arg::new("dry-run")
+                    .long("dry-run")
+                    .action(ArgAction::SetTrue)
+                    .help("Only list the orphans, without deleting them"),
+            )
+            .arg(
+                Arg::new("api-key")
+                    .long("api-key")
+                    .short('k')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Key for authentication"),
+            )
+            .arg(
+                Arg::new("api-secret")
+                    .long("api-secret")
+                    .short('s')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Secret for authentication"),
+            )
+            .arg(
+                Arg::new("base-url")
+                    .long("base-url")
+                    .short('u')
+                    .default_value("http://localhost:8080")
+                    .action(ArgAction::Set)
+                    .help("API Secret for authentication"),
+            )
+            .arg(server_key_arg())
+            .arg_required_else_help(true)
+    }
+
+    fn name(&self) -> String {
+        "collect-orphans".to_owned()
+    }
+
+    async fn execute(&self, args: &ArgMatches) {
+        let commands_args: CollectOrphansCommandArgs = args.into();
+        let api_args: ApiClientArgs = (&commands_args).into();
+
+        let api_cli = ApiClient::new(api_args).expect("Failed to create API client");
+
+        self.collect_orphans(api_cli, commands_args.dry_run)
+            .await
+            .expect("Failed to collect orphans");
+    }
+}
@@ -0,0 +1,195 @@
+use async_trait::async_trait;
+use clap::{Arg, ArgAction, ArgMatches};
+use ed25519_dalek::VerifyingKey;
+use reqwest::Url;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::{
+    ApiClient, ApiClientArgs, FileManager,
+    commands::{
+        Command,
+        helpers::{get_path_from_str, get_server_keys, server_key_arg},
+    },
+    file_manager::FileManagerArgs,
+};
+
+struct DeleteUploadCommandArgs {
+    api_key: String,
+    api_secret: String,
+    base_url: String,
+    server_keys: Vec<VerifyingKey>,
+    files_directory: PathBuf,
+    roots_store_directory: PathBuf,
+    id: Uuid,
+}
+
+impl From<&ArgMatches> for DeleteUploadCommandArgs {
+    fn from(args: &ArgMatches) -> Self {
+        let api_key = args
+            .get_one::<String>("api-key")
+            .expect("API-KEY is required")
+            .to_owned();
+
+        let api_secret = args
+            .get_one::<String>("api-secret")
+            .expect("API-SECRET is required")
+            .to_owned();
+
+        let base_url = args
+            .get_one::<String>("base-url")
+            .expect("Base URL is required")
+            .to_owned();
+
+        let server_keys = get_server_keys(args).expect("Failed to parse server keys");
+
+        let id: Uuid = args
+            .get_one::<String>("id")
+            .expect("Upload ID is required")
+            .parse()
+            .expect("Failed to parse Upload ID");
+
+        let files_directory = args
+            .get_one::<String>("files-directory")
+            .expect("File directory is required");
+        let files_directory =
+            get_path_from_str(files_directory).expect("Failed to parse files directory");
+
+        let roots_store_directory = args
+            .get_one::<String>("roots-store-directory")
+            .expect("File directory is required");
+        let roots_store_directory = get_path_from_str(roots_store_directory)
+            .expect("Failed to parse roots store directory");
+
+        Self {
+            api_key,
+            api_secret,
+            base_url,
+            server_keys,
+            files_directory,
+            roots_store_directory,
+            id,
+        }
+    }
+}
+
+impl From<&DeleteUploadCommandArgs> for ApiClientArgs {
+    fn from(val: &DeleteUploadCommandArgs) -> Self {
+        ApiClientArgs {
+            api_key: val.api_key.clone(),
+            api_secret: val.api_secret.clone(),
+            base_url: Url::parse(&val.base_url).expect("Failed to parse base URL"),
+            correlation_id: Uuid::new_v4(),
+            server_keys: val.server_keys.clone(),
+        }
+    }
+}
+
+impl From<&DeleteUploadCommandArgs> for FileManagerArgs {
+    fn from(val: &DeleteUploadCommandArgs) -> Self {
+        FileManagerArgs {
+            files_storage_path: val.files_directory.clone(),
+            roots_storage_path: val.roots_store_directory.clone(),
+        }
+    }
+}
+
+pub struct DeleteUploadCommand;
+
+impl DeleteUploadCommand {
+    // The local root is removed only once the server confirmed the deletion, otherwise the
+    // client would lose the only way to verify files that are still stored.
+    async fn delete_upload(
+        &self,
+        api_client: ApiClient,
+        file_manager: FileManager,
+        id: Uuid,
+    ) -> anyhow::Result<()> {
+        api_client.delete_upload(id).await?;
+
+        if file_manager.delete_root_file(id).await? {
+            println!("upload deleted. id={}", id);
+        } else {
+            println!("upload deleted, no local root file found. id={}", id);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Command for DeleteUploadCommand {
+    fn create(&self) -> clap::Command {
+        clap::Command::new("delete-upload")
+            .about("This command deletes an upload from the server along with its local root.")
+            .long_flag("delete-upload")
+            .arg(
+                Arg::new("api-key")
+                    .long("api-key")
+                    .short('k')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Key for authentication"),
+            )
+            .arg(
+                Arg::new("api-secret")
+                    .long("api-secret")
+                    .short('s')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Secret for authentication"),
+            )
+            .arg(
+                Arg::new("base-url")
+                    .long("base-url")
+                    .short('u')
+                    .default_value("http://localhost:8080")
+                    .action(ArgAction::Set)
+                    .help("API Secret for authentication"),
+            )
+            .arg(server_key_arg())
+            .arg(
+                Arg::new("files-directory")
+                    .long("files-directory")
+                    .short('f')
+                    .default_value("~/files")
+                    .action(ArgAction::Set)
+                    .help("Local directory containing files to upload"),
+            )
+            .arg(
+                Arg::new("roots-store-directory")
+                    .long("roots-store-directory")
+                    .short('r')
+                    .default_value("~/roots")
+                    .action(ArgAction::Set)
+                    .help("Local directory to persist upload roots"),
+            )
+            .arg(
+                Arg::new("id")
+                    .long("id")
+                    .short('i')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("Upload ID to delete"),
+            )
+            .arg_required_else_help(true)
+    }
+
+    fn name(&self) -> String {
+        "delete-upload".to_owned()
+    }
+
+    async fn execute(&self, args: &ArgMatches) {
+        let commands_args: DeleteUploadCommandArgs = args.into();
+
+        let api_args: ApiClientArgs = (&commands_args).into();
+        let file_manager_args: FileManagerArgs = (&commands_args).into();
+
+        let api_cli = ApiClient::new(api_args).expect("Failed to create API client");
+        let file_manager = FileManager::new(file_manager_args);
+
+        self.delete_upload(api_cli, file_manager, commands_args.id)
+            .await
+            .expect("Failed to delete upload");
+    }
+}
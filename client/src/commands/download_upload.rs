@@ -0,0 +1,324 @@
+use async_trait::async_trait;
+use clap::{Arg, ArgAction, ArgMatches, value_parser};
+use ed25519_dalek::VerifyingKey;
+use file_server_library::{CustomMerkleTree, models::Hash32};
+use reqwest::Url;
+use std::path::{Component, Path, PathBuf};
+use tokio::{fs, task};
+use uuid::Uuid;
+
+use crate::{
+    ApiClient, ApiClientArgs, FileManager,
+    archive::{ArchiveFormat, ArchiveManifest, MANIFEST_PATH, extract_archive},
+    commands::{
+        Command,
+        helpers::{get_path_from_str, get_server_keys, server_key_arg},
+    },
+    file_manager::FileManagerArgs,
+};
+
+struct DownloadUploadCommandArgs {
+    api_key: String,
+    api_secret: String,
+    base_url: String,
+    server_keys: Vec<VerifyingKey>,
+    output_directory: PathBuf,
+    roots_store_directory: PathBuf,
+    format: ArchiveFormat,
+    id: Uuid,
+    version: Option<u32>,
+}
+
+impl From<&ArgMatches> for DownloadUploadCommandArgs {
+    fn from(args: &ArgMatches) -> Self {
+        let api_key = args
+            .get_one::<String>("api-key")
+            .expect("API-KEY is required")
+            .to_owned();
+
+        let api_secret = args
+            .get_one::<String>("api-secret")
+            .expect("API-SECRET is required")
+            .to_owned();
+
+        let base_url = args
+            .get_one::<String>("base-url")
+            .expect("Base URL is required")
+            .to_owned();
+
+        let server_keys = get_server_keys(args).expect("Failed to parse server keys");
+
+        let id: Uuid = args
+            .get_one::<String>("id")
+            .expect("Upload ID is required")
+            .parse()
+            .expect("Failed to parse Upload ID");
+
+        let version = args.get_one::<u32>("version").copied();
+
+        let format = args
+            .get_one::<String>("format")
+            .expect("Format is required")
+            .parse()
+            .expect("Failed to parse archive format");
+
+        let output_directory = args
+            .get_one::<String>("output-directory")
+            .expect("Output directory is required");
+        let output_directory =
+            get_path_from_str(output_directory).expect("Failed to parse output directory");
+
+        let roots_store_directory = args
+            .get_one::<String>("roots-store-directory")
+            .expect("File directory is required");
+        let roots_store_directory = get_path_from_str(roots_store_directory)
+            .expect("Failed to parse roots store directory");
+
+        Self {
+            api_key,
+            api_secret,
+            base_url,
+            server_keys,
+            output_directory,
+            roots_store_directory,
+            format,
+            id,
+            version,
+        }
+    }
+}
+
+impl From<&DownloadUploadCommandArgs> for ApiClientArgs {
+    fn from(val: &DownloadUploadCommandArgs) -> Self {
+        ApiClientArgs {
+            api_key: val.api_key.clone(),
+            api_secret: val.api_secret.clone(),
+            base_url: Url::parse(&val.base_url).expect("Failed to parse base URL"),
+            correlation_id: Uuid::new_v4(),
+            server_keys: val.server_keys.clone(),
+        }
+    }
+}
+
+impl From<&DownloadUploadCommandArgs> for FileManagerArgs {
+    fn from(val: &DownloadUploadCommandArgs) -> Self {
+        FileManagerArgs {
+            files_storage_path: val.output_directory.clone(),
+            roots_storage_path: val.roots_store_directory.clone(),
+        }
+    }
+}
+
+pub struct DownloadUploadCommand;
+
+impl DownloadUploadCommand {
+    // Every file is checked against the root stored when the upload was completed, the one in
+    // the manifest is only compared to it. Files are staged next to the output directory while
+    // they are checked, nothing is moved into place unless the whole upload verifies.
+    async fn download_upload(
+        &self,
+        api_client: ApiClient,
+        file_manager: FileManager,
+        args: DownloadUploadCommandArgs,
+    ) -> anyhow::Result<()> {
+        let staging_dir = file_manager.create_staging_dir().await?;
+        let result = self
+            .download_staged(&api_client, &file_manager, &args, &staging_dir)
+            .await;
+
+        if let Err(e) = file_manager.remove_staging_dir(&staging_dir).await {
+            eprintln!(
+                "Failed to remove staging directory {}: {}",
+                staging_dir.display(),
+                e
+            );
+        }
+
+        result
+    }
+
+    async fn download_staged(
+        &self,
+        api_client: &ApiClient,
+        file_manager: &FileManager,
+        args: &DownloadUploadCommandArgs,
+        staging_dir: &Path,
+    ) -> anyhow::Result<()> {
+        let version = match args.version {
+            Some(version) => version,
+            None => file_manager
+                .latest_root_version(args.id)
+                .await?
+                .unwrap_or(1),
+        };
+        let root_hex = file_manager.load_root_file(args.id, Some(version)).await?;
+        let root = Hash32::from_hex(&root_hex).map_err(|e| anyhow::anyhow!(e))?;
+
+        let archive = staging_dir.join("archive");
+        api_client
+            .download_archive(args.id, args.format, Some(version), &archive)
+            .await?;
+
+        let entries_dir = staging_dir.join("entries");
+        fs::create_dir(&entries_dir).await?;
+        let format = args.format;
+        let extracted_archive = archive.clone();
+        let mut entries =
+            task::spawn_blocking(move || extract_archive(format, &extracted_archive, &entries_dir))
+                .await??;
+        fs::remove_file(&archive).await?;
+
+        let manifest = entries
+            .remove(MANIFEST_PATH)
+            .ok_or_else(|| anyhow::anyhow!("Archive has no {}", MANIFEST_PATH))?;
+        let manifest: ArchiveManifest = serde_json::from_slice(&fs::read(&manifest.file).await?)?;
+
+        if manifest.id != args.id || manifest.version != version || manifest.root != root_hex {
+            anyhow::bail!(
+                "Archive does not belong to upload id={}, version={} with root={}",
+                args.id,
+                version,
+                root_hex
+            );
+        }
+
+        let mut verified = Vec::with_capacity(manifest.files.len());
+        for file in manifest.files {
+            if !is_relative(&file.name) {
+                anyhow::bail!(
+                    "Refusing to write file outside the output directory: {}",
+                    file.name
+                );
+            }
+
+            let entry = entries
+                .remove(&file.path)
+                .ok_or_else(|| anyhow::anyhow!("Archive is missing {}", file.path))?;
+
+            if entry.leaf.to_hex() != file.leaf_hash
+                || !CustomMerkleTree::verify(&entry.leaf, &file.proof, &root)
+            {
+                anyhow::bail!(
+                    "File verification failed for id={}, index={}, name={}",
+                    args.id,
+                    file.index,
+                    file.name
+                );
+            }
+
+            verified.push((file.name, entry.file));
+        }
+
+        for (name, staged) in &verified {
+            let path = file_manager.move_file(name, staged).await?;
+            println!("{}", path.display());
+        }
+
+        println!(
+            "Downloaded and verified {} files for id={}",
+            verified.len(),
+            args.id
+        );
+
+        Ok(())
+    }
+}
+
+// File names come from the server, they must not escape the output directory.
+fn is_relative(name: &str) -> bool {
+    Path::new(name)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+#[async_trait]
+impl Command for DownloadUploadCommand {
+    fn create(&self) -> clap::Command {
+        clap::Command::new("download-upload")
+            .about("This command downloads every file of an upload as an archive and writes them out once verified.")
+            .long_flag("download-upload")
+            .arg(
+                Arg::new("api-key")
+                    .long("api-key")
+                    .short('k')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Key for authentication"),
+            )
+            .arg(
+                Arg::new("api-secret")
+                    .long("api-secret")
+                    .short('s')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Secret for authentication"),
+            )
+            .arg(
+                Arg::new("base-url")
+                    .long("base-url")
+                    .short('u')
+                    .default_value("http://localhost:8080")
+                    .action(ArgAction::Set)
+                    .help("Base URL of the server"),
+            )
+            .arg(server_key_arg())
+            .arg(
+                Arg::new("output-directory")
+                    .long("output-directory")
+                    .short('o')
+                    .default_value("~/downloads")
+                    .action(ArgAction::Set)
+                    .help("Local directory to write the downloaded files to"),
+            )
+            .arg(
+                Arg::new("roots-store-directory")
+                    .long("roots-store-directory")
+                    .short('r')
+                    .default_value("~/roots")
+                    .action(ArgAction::Set)
+                    .help("Local directory to persist upload roots"),
+            )
+            .arg(
+                Arg::new("id")
+                    .long("id")
+                    .short('i')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("Upload ID to download"),
+            )
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .default_value("tar")
+                    .value_parser(["tar", "zip"])
+                    .action(ArgAction::Set)
+                    .help("Archive format to download, tar or zip"),
+            )
+            .arg(
+                Arg::new("version")
+                    .long("version")
+                    .value_parser(value_parser!(u32))
+                    .action(ArgAction::Set)
+                    .help("Version of the upload, the latest one with a local root when missing"),
+            )
+            .arg_required_else_help(true)
+    }
+
+    fn name(&self) -> String {
+        "download-upload".to_owned()
+    }
+
+    async fn execute(&self, args: &ArgMatches) {
+        let commands_args: DownloadUploadCommandArgs = args.into();
+
+        let api_args: ApiClientArgs = (&commands_args).into();
+        let file_manager_args: FileManagerArgs = (&commands_args).into();
+
+        let api_cli = ApiClient::new(api_args).expect("Failed to create API client");
+        let file_manager = FileManager::new(file_manager_args);
+
+        self.download_upload(api_cli, file_manager, commands_args)
+            .await
+            .expect("Failed to download upload");
+    }
+}
@@ -1,3 +1,5 @@
+use clap::{Arg, ArgAction, ArgMatches};
+use ed25519_dalek::VerifyingKey;
 use std::path::PathBuf;
 
 pub fn get_path_from_str(input: &str) -> anyhow::Result<PathBuf> {
@@ -10,3 +12,24 @@ pub fn get_path_from_str(input: &str) -> anyhow::Result<PathBuf> {
 
     Ok(path)
 }
+
+/// Repeatable `--server-key`, shared by every command talking to the server.
+pub fn server_key_arg() -> Arg {
+    Arg::new("server-key")
+        .long("server-key")
+        .action(ArgAction::Append)
+        .help("Pinned Ed25519 public key of the server in hex, responses must be signed by one of them (repeatable)")
+}
+
+pub fn get_server_keys(args: &ArgMatches) -> anyhow::Result<Vec<VerifyingKey>> {
+    args.get_many::<String>("server-key")
+        .unwrap_or_default()
+        .map(|key| {
+            let bytes: [u8; 32] = hex::decode(key)?
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("Server keys must be 32 bytes long"))?;
+
+            Ok(VerifyingKey::from_bytes(&bytes)?)
+        })
+        .collect()
+}
@@ -0,0 +1,161 @@
+use async_trait::async_trait;
+use clap::{Arg, ArgAction, ArgMatches};
+use ed25519_dalek::VerifyingKey;
+use reqwest::Url;
+use uuid::Uuid;
+
+use crate::{
+    ApiClient, ApiClientArgs,
+    commands::{
+        Command,
+        helpers::{get_server_keys, server_key_arg},
+    },
+};
+
+// Big enough to list most uploads in a single request, the server caps it anyway.
+const PAGE_SIZE: usize = 500;
+
+struct ListFilesCommandArgs {
+    api_key: String,
+    api_secret: String,
+    base_url: String,
+    server_keys: Vec<VerifyingKey>,
+    id: Uuid,
+}
+
+impl From<&ArgMatches> for ListFilesCommandArgs {
+    fn from(args: &ArgMatches) -> Self {
+        let api_key = args
+            .get_one::<String>("api-key")
+            .expect("API-KEY is required")
+            .to_owned();
+
+        let api_secret = args
+            .get_one::<String>("api-secret")
+            .expect("API-SECRET is required")
+            .to_owned();
+
+        let base_url = args
+            .get_one::<String>("base-url")
+            .expect("Base URL is required")
+            .to_owned();
+
+        let server_keys = get_server_keys(args).expect("Failed to parse server keys");
+
+        let id: Uuid = args
+            .get_one::<String>("id")
+            .expect("Upload ID is required")
+            .parse()
+            .expect("Failed to parse Upload ID");
+
+        Self {
+            api_key,
+            api_secret,
+            base_url,
+            server_keys,
+            id,
+        }
+    }
+}
+
+impl From<&ListFilesCommandArgs> for ApiClientArgs {
+    fn from(val: &ListFilesCommandArgs) -> Self {
+        ApiClientArgs {
+            api_key: val.api_key.clone(),
+            api_secret: val.api_secret.clone(),
+            base_url: Url::parse(&val.base_url).expect("Failed to parse base URL"),
+            correlation_id: Uuid::new_v4(),
+            server_keys: val.server_keys.clone(),
+        }
+    }
+}
+
+pub struct ListFilesCommand;
+
+impl ListFilesCommand {
+    async fn list_files(&self, api_client: ApiClient, id: Uuid) -> anyhow::Result<()> {
+        let mut offset = Some(0);
+
+        while let Some(current) = offset {
+            let page = api_client.list_files(id, current, PAGE_SIZE, None).await?;
+
+            if page.total == 0 {
+                println!("No files found. id={}", id);
+            }
+
+            for file in page.files {
+                println!(
+                    "{:>5}  {:<40}  {:>12}  {:<24}  {}  {}",
+                    file.index,
+                    file.name,
+                    file.size,
+                    file.content_type,
+                    file.uploaded_at.to_rfc3339(),
+                    file.leaf_hash
+                );
+            }
+
+            offset = page.next_offset;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Command for ListFilesCommand {
+    fn create(&self) -> clap::Command {
+        clap::Command::new("list-files")
+            .about("This command lists the files of an upload stored in the server.")
+            .long_flag("list-files")
+            .arg(
+                Arg::new("api-key")
+                    .long("api-key")
+                    .short('k')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Key for authentication"),
+            )
+            .arg(
+                Arg::new("api-secret")
+                    .long("api-secret")
+                    .short('s')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Secret for authentication"),
+            )
+            .arg(
+                Arg::new("base-url")
+                    .long("base-url")
+                    .short('u')
+                    .default_value("http://localhost:8080")
+                    .action(ArgAction::Set)
+                    .help("Base URL of the server"),
+            )
+            .arg(server_key_arg())
+            .arg(
+                Arg::new("id")
+                    .long("id")
+                    .short('i')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("Upload ID to list files from"),
+            )
+            .arg_required_else_help(true)
+    }
+
+    fn name(&self) -> String {
+        "list-files".to_owned()
+    }
+
+    async fn execute(&self, args: &ArgMatches) {
+        let commands_args: ListFilesCommandArgs = args.into();
+
+        let api_args: ApiClientArgs = (&commands_args).into();
+        let api_cli = ApiClient::new(api_args).expect("Failed to create API client");
+
+        self.list_files(api_cli, commands_args.id)
+            .await
+            .expect("Failed to list files");
+    }
+}
@@ -1,15 +1,31 @@
 use crate::{
-    FileManager,
-    commands::{Command, helpers::get_path_from_str},
+    ApiClient, ApiClientArgs, FileManager,
+    commands::{
+        Command,
+        helpers::{get_path_from_str, get_server_keys, server_key_arg},
+    },
     file_manager::FileManagerArgs,
 };
 use async_trait::async_trait;
 use clap::{Arg, ArgAction, ArgMatches};
-use std::path::PathBuf;
+use ed25519_dalek::VerifyingKey;
+use reqwest::Url;
+use std::{collections::BTreeMap, path::PathBuf};
+use uuid::Uuid;
+
+const PAGE_SIZE: usize = 500;
 
 struct ListUploadIdsCommandArgs {
     files_directory: PathBuf,
     roots_store_directory: PathBuf,
+    remote: Option<RemoteArgs>,
+}
+
+struct RemoteArgs {
+    api_key: String,
+    api_secret: String,
+    base_url: String,
+    server_keys: Vec<VerifyingKey>,
 }
 
 impl From<&ArgMatches> for ListUploadIdsCommandArgs {
@@ -26,9 +42,26 @@ impl From<&ArgMatches> for ListUploadIdsCommandArgs {
         let roots_store_directory = get_path_from_str(roots_store_directory)
             .expect("Failed to parse roots store directory");
 
+        let remote = args.get_flag("remote").then(|| RemoteArgs {
+            api_key: args
+                .get_one::<String>("api-key")
+                .expect("API-KEY is required")
+                .to_owned(),
+            api_secret: args
+                .get_one::<String>("api-secret")
+                .expect("API-SECRET is required")
+                .to_owned(),
+            base_url: args
+                .get_one::<String>("base-url")
+                .expect("Base URL is required")
+                .to_owned(),
+            server_keys: get_server_keys(args).expect("Failed to parse server keys"),
+        });
+
         Self {
             files_directory,
             roots_store_directory,
+            remote,
         }
     }
 }
@@ -42,18 +75,73 @@ impl From<&ListUploadIdsCommandArgs> for FileManagerArgs {
     }
 }
 
+impl From<&RemoteArgs> for ApiClientArgs {
+    fn from(val: &RemoteArgs) -> Self {
+        ApiClientArgs {
+            api_key: val.api_key.clone(),
+            api_secret: val.api_secret.clone(),
+            base_url: Url::parse(&val.base_url).expect("Failed to parse base URL"),
+            correlation_id: Uuid::new_v4(),
+            server_keys: val.server_keys.clone(),
+        }
+    }
+}
+
+/// Where an upload is known from, along with what the server reports about it.
+#[derive(Default)]
+struct UploadListing {
+    local: bool,
+    remote: Option<String>,
+}
+
 pub struct ListUploadIdsCommand;
 
 impl ListUploadIdsCommand {
-    async fn list_upload_ids(&self, file_manager: FileManager) -> anyhow::Result<()> {
-        let upload_ids = file_manager.list_root_files().await?;
+    async fn list_upload_ids(
+        &self,
+        file_manager: FileManager,
+        api_client: Option<ApiClient>,
+    ) -> anyhow::Result<()> {
+        let mut uploads: BTreeMap<Uuid, UploadListing> = BTreeMap::new();
+
+        for id in file_manager.list_root_files().await? {
+            uploads.entry(id).or_default().local = true;
+        }
+
+        if let Some(api_client) = api_client {
+            let mut cursor: Option<String> = None;
+
+            loop {
+                let page = api_client
+                    .list_uploads(cursor.as_deref(), PAGE_SIZE)
+                    .await?;
+
+                for upload in page.uploads {
+                    let details = format!(
+                        "state={} files={} size={}",
+                        upload.state, upload.file_count, upload.total_size
+                    );
+                    uploads.entry(upload.id).or_default().remote = Some(details);
+                }
 
-        if upload_ids.is_empty() {
+                match page.next_cursor {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+        }
+
+        if uploads.is_empty() {
             println!("No upload IDs found.");
         } else {
             println!("Upload IDs:");
-            for id in upload_ids {
-                println!("- {}", id);
+            for (id, listing) in uploads {
+                match (listing.local, listing.remote) {
+                    (true, Some(details)) => println!("- {} [local, remote] {}", id, details),
+                    (false, Some(details)) => println!("- {} [remote] {}", id, details),
+                    (true, None) => println!("- {} [local]", id),
+                    (false, None) => unreachable!("uploads are listed from somewhere"),
+                }
             }
         }
 
@@ -83,6 +171,37 @@ impl Command for ListUploadIdsCommand {
                     .action(ArgAction::Set)
                     .help("Local directory to persist upload roots"),
             )
+            .arg(
+                Arg::new("remote")
+                    .long("remote")
+                    .action(ArgAction::SetTrue)
+                    .help("Merge the uploads the server knows for the API key with the local ones"),
+            )
+            .arg(
+                Arg::new("api-key")
+                    .long("api-key")
+                    .short('k')
+                    .required_if_eq("remote", "true")
+                    .action(ArgAction::Set)
+                    .help("API Key for authentication, required with --remote"),
+            )
+            .arg(
+                Arg::new("api-secret")
+                    .long("api-secret")
+                    .short('s')
+                    .required_if_eq("remote", "true")
+                    .action(ArgAction::Set)
+                    .help("API Secret for authentication, required with --remote"),
+            )
+            .arg(
+                Arg::new("base-url")
+                    .long("base-url")
+                    .short('u')
+                    .default_value("http://localhost:8080")
+                    .action(ArgAction::Set)
+                    .help("Base URL of the server"),
+            )
+            .arg(server_key_arg())
     }
 
     fn name(&self) -> String {
@@ -94,8 +213,12 @@ impl Command for ListUploadIdsCommand {
         let file_manager_args: FileManagerArgs = (&commands_args).into();
 
         let file_manager = FileManager::new(file_manager_args);
+        let api_client = commands_args
+            .remote
+            .as_ref()
+            .map(|remote| ApiClient::new(remote.into()).expect("Failed to create API client"));
 
-        self.list_upload_ids(file_manager)
+        self.list_upload_ids(file_manager, api_client)
             .await
             .expect("Failed to list upload IDs");
     }
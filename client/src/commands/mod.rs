@@ -2,14 +2,30 @@
 // future. This is a custom implementation I had already written in the past ([see here](https://github.com/flarocca/rust_revm_simulations/blob/main/src/commands/mod.rs))
 // It could be argued that using the typed version of commands would be better, but I think it is
 // less flexible and more coupled.
+mod collect_orphans;
+mod delete_upload;
+mod download_upload;
 mod helpers;
+mod list_files;
 mod list_upload_ids;
+mod new_version;
 mod upload_files;
+mod usage;
 mod verify_file;
+mod verify_log;
+mod watch_upload;
 
+pub use collect_orphans::CollectOrphansCommand;
+pub use delete_upload::DeleteUploadCommand;
+pub use download_upload::DownloadUploadCommand;
+pub use list_files::ListFilesCommand;
 pub use list_upload_ids::ListUploadIdsCommand;
+pub use new_version::NewVersionCommand;
 pub use upload_files::UploadFilesCommand;
+pub use usage::UsageCommand;
 pub use verify_file::VerifyFileCommand;
+pub use verify_log::VerifyLogCommand;
+pub use watch_upload::WatchUploadCommand;
 
 use async_trait::async_trait;
 use clap::ArgMatches;
@@ -31,6 +47,14 @@ pub fn get_commands() -> HashMap<String, Box<dyn Command>> {
         Box::new(UploadFilesCommand),
         Box::new(VerifyFileCommand),
         Box::new(ListUploadIdsCommand),
+        Box::new(DeleteUploadCommand),
+        Box::new(ListFilesCommand),
+        Box::new(DownloadUploadCommand),
+        Box::new(VerifyLogCommand),
+        Box::new(NewVersionCommand),
+        Box::new(UsageCommand),
+        Box::new(CollectOrphansCommand),
+        Box::new(WatchUploadCommand),
     ];
 
     for command in commands {
@@ -0,0 +1,275 @@
+use async_trait::async_trait;
+use clap::{Arg, ArgAction, ArgMatches};
+use ed25519_dalek::VerifyingKey;
+use file_server_library::{CustomMerkleTree, models::Hash32};
+use reqwest::Url;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::{
+    ApiClient, ApiClientArgs, FileManager,
+    commands::{
+        Command,
+        helpers::{get_path_from_str, get_server_keys, server_key_arg},
+    },
+    file_manager::FileManagerArgs,
+};
+
+const PAGE_SIZE: usize = 500;
+
+struct NewVersionCommandArgs {
+    api_key: String,
+    api_secret: String,
+    base_url: String,
+    server_keys: Vec<VerifyingKey>,
+    files_directory: PathBuf,
+    roots_store_directory: PathBuf,
+    id: Uuid,
+}
+
+impl From<&ArgMatches> for NewVersionCommandArgs {
+    fn from(args: &ArgMatches) -> Self {
+        let api_key = args
+            .get_one::<String>("api-key")
+            .expect("API-KEY is required")
+            .to_owned();
+
+        let api_secret = args
+            .get_one::<String>("api-secret")
+            .expect("API-SECRET is required")
+            .to_owned();
+
+        let base_url = args
+            .get_one::<String>("base-url")
+            .expect("Base URL is required")
+            .to_owned();
+
+        let server_keys = get_server_keys(args).expect("Failed to parse server keys");
+
+        let id: Uuid = args
+            .get_one::<String>("id")
+            .expect("Upload ID is required")
+            .parse()
+            .expect("Failed to parse Upload ID");
+
+        let files_directory = args
+            .get_one::<String>("files-directory")
+            .expect("File directory is required");
+        let files_directory =
+            get_path_from_str(files_directory).expect("Failed to parse files directory");
+
+        let roots_store_directory = args
+            .get_one::<String>("roots-store-directory")
+            .expect("File directory is required");
+        let roots_store_directory = get_path_from_str(roots_store_directory)
+            .expect("Failed to parse roots store directory");
+
+        Self {
+            api_key,
+            api_secret,
+            base_url,
+            server_keys,
+            files_directory,
+            roots_store_directory,
+            id,
+        }
+    }
+}
+
+impl From<&NewVersionCommandArgs> for ApiClientArgs {
+    fn from(val: &NewVersionCommandArgs) -> Self {
+        ApiClientArgs {
+            api_key: val.api_key.clone(),
+            api_secret: val.api_secret.clone(),
+            base_url: Url::parse(&val.base_url).expect("Failed to parse base URL"),
+            correlation_id: Uuid::new_v4(),
+            server_keys: val.server_keys.clone(),
+        }
+    }
+}
+
+impl From<&NewVersionCommandArgs> for FileManagerArgs {
+    fn from(val: &NewVersionCommandArgs) -> Self {
+        FileManagerArgs {
+            files_storage_path: val.files_directory.clone(),
+            roots_storage_path: val.roots_store_directory.clone(),
+        }
+    }
+}
+
+pub struct NewVersionCommand;
+
+impl NewVersionCommand {
+    /// Creates the next version of a completed upload, replacing the files with the same name as
+    /// a local one and adding the rest after them.
+    async fn new_version(
+        &self,
+        file_manager: FileManager,
+        api_client: ApiClient,
+        id: Uuid,
+    ) -> anyhow::Result<()> {
+        let file_entries = file_manager.load_files().await?;
+        if file_entries.is_empty() {
+            anyhow::bail!("directory has no files");
+        }
+
+        let summary = api_client.create_version(id).await?;
+        let version = summary.version;
+        let previous_version = summary.previous_version.unwrap_or(version - 1);
+
+        // The new version starts with the files of the previous one, which must still match
+        // the root stored when it was completed.
+        let mut leaves = Vec::new();
+        let mut indexes = HashMap::new();
+        let mut offset = Some(0);
+
+        while let Some(current) = offset {
+            let page = api_client
+                .list_files(id, current, PAGE_SIZE, Some(version))
+                .await?;
+
+            for file in page.files {
+                if leaves.len() <= file.index {
+                    leaves.resize(file.index + 1, Hash32::empty());
+                }
+                leaves[file.index] =
+                    Hash32::from_hex(&file.leaf_hash).map_err(|e| anyhow::anyhow!(e))?;
+                indexes.insert(file.name, file.index);
+            }
+
+            offset = page.next_offset;
+        }
+
+        let previous_root = file_manager
+            .load_root_file(id, Some(previous_version))
+            .await?;
+        let inherited_root = CustomMerkleTree::new(leaves.clone()).root().to_hex();
+        if inherited_root != previous_root {
+            anyhow::bail!(
+                "version {} does not start from the files of version {} (server {}, local {})",
+                version,
+                previous_version,
+                inherited_root,
+                previous_root
+            );
+        }
+
+        let mut uploads = Vec::with_capacity(file_entries.len());
+        for entry in &file_entries {
+            let index = match indexes.get(&entry.name) {
+                Some(index) => *index,
+                None => {
+                    leaves.push(Hash32::empty());
+                    leaves.len() - 1
+                }
+            };
+            leaves[index] = entry.leaf;
+            uploads.push((index, entry));
+        }
+
+        let root_hex = CustomMerkleTree::new(leaves).root().to_hex();
+
+        for (index, entry) in uploads {
+            let _ = api_client
+                .upload_file(id, &entry.name, index, &entry.path, entry.leaf)
+                .await?;
+        }
+
+        let server_root = api_client.complete(id).await?;
+        if server_root.to_ascii_lowercase() != root_hex {
+            anyhow::bail!(
+                "server root mismatch (server {}, local {})",
+                server_root,
+                root_hex
+            );
+        }
+
+        file_manager.write_root_file(id, version, &root_hex).await?;
+        file_manager.cleanup_files(file_entries).await?;
+
+        println!(
+            "version complete. id={}, version={}, root={}",
+            id, version, root_hex
+        );
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Command for NewVersionCommand {
+    fn create(&self) -> clap::Command {
+        clap::Command::new("new-version")
+            .about("This command creates a new version of a completed upload with the local files.")
+            .long_flag("new-version")
+            .arg(
+                Arg::new("api-key")
+                    .long("api-key")
+                    .short('k')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Key for authentication"),
+            )
+            .arg(
+                Arg::new("api-secret")
+                    .long("api-secret")
+                    .short('s')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Secret for authentication"),
+            )
+            .arg(
+                Arg::new("base-url")
+                    .long("base-url")
+                    .short('u')
+                    .default_value("http://localhost:8080")
+                    .action(ArgAction::Set)
+                    .help("API Secret for authentication"),
+            )
+            .arg(server_key_arg())
+            .arg(
+                Arg::new("files-directory")
+                    .long("files-directory")
+                    .short('f')
+                    .default_value("~/files")
+                    .action(ArgAction::Set)
+                    .help("Local directory containing files to add or replace"),
+            )
+            .arg(
+                Arg::new("roots-store-directory")
+                    .long("roots-store-directory")
+                    .short('r')
+                    .default_value("~/roots")
+                    .action(ArgAction::Set)
+                    .help("Local directory to persist upload roots"),
+            )
+            .arg(
+                Arg::new("id")
+                    .long("id")
+                    .short('i')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("Upload ID to create a new version of"),
+            )
+            .arg_required_else_help(true)
+    }
+
+    fn name(&self) -> String {
+        "new-version".to_owned()
+    }
+
+    async fn execute(&self, args: &ArgMatches) {
+        let commands_args: NewVersionCommandArgs = args.into();
+
+        let api_args: ApiClientArgs = (&commands_args).into();
+        let file_manager_args: FileManagerArgs = (&commands_args).into();
+
+        let api_cli = ApiClient::new(api_args).expect("Failed to create API client");
+        let file_manager = FileManager::new(file_manager_args);
+
+        self.new_version(file_manager, api_cli, commands_args.id)
+            .await
+            .expect("Failed to create new version");
+    }
+}
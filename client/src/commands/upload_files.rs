@@ -1,13 +1,17 @@
 use async_trait::async_trait;
 use clap::{Arg, ArgAction, ArgMatches};
-use file_server_library::{CustomMerkleTree, models::Hash32};
+use ed25519_dalek::VerifyingKey;
+use file_server_library::CustomMerkleTree;
 use reqwest::Url;
 use std::path::PathBuf;
 use uuid::Uuid;
 
 use crate::{
     ApiClient, ApiClientArgs, FileManager,
-    commands::{Command, helpers::get_path_from_str},
+    commands::{
+        Command,
+        helpers::{get_path_from_str, get_server_keys, server_key_arg},
+    },
     file_manager::FileManagerArgs,
 };
 
@@ -15,6 +19,7 @@ struct UploadFilesCommandArgs {
     api_key: String,
     api_secret: String,
     base_url: String,
+    server_keys: Vec<VerifyingKey>,
     files_directory: PathBuf,
     roots_store_directory: PathBuf,
 }
@@ -36,6 +41,8 @@ impl From<&ArgMatches> for UploadFilesCommandArgs {
             .expect("Base URL is required")
             .to_owned();
 
+        let server_keys = get_server_keys(args).expect("Failed to parse server keys");
+
         let files_directory = args
             .get_one::<String>("files-directory")
             .expect("File directory is required");
@@ -52,6 +59,7 @@ impl From<&ArgMatches> for UploadFilesCommandArgs {
             api_key,
             api_secret,
             base_url,
+            server_keys,
             files_directory,
             roots_store_directory,
         }
@@ -65,6 +73,7 @@ impl From<&UploadFilesCommandArgs> for ApiClientArgs {
             api_secret: val.api_secret.clone(),
             base_url: Url::parse(&val.base_url).expect("Failed to parse base URL"),
             correlation_id: Uuid::new_v4(),
+            server_keys: val.server_keys.clone(),
         }
     }
 }
@@ -93,13 +102,7 @@ impl UploadFilesCommand {
         if file_entries.is_empty() {
             anyhow::bail!("directory has no files");
         }
-        let mut leaves = Vec::with_capacity(file_entries.len());
-        let mut filenames = Vec::with_capacity(file_entries.len());
-
-        for entry in &file_entries {
-            leaves.push(Hash32::hash(&entry.data));
-            filenames.push((entry.name.to_owned(), entry.data.clone()));
-        }
+        let leaves = file_entries.iter().map(|entry| entry.leaf).collect();
 
         let custom_tree = CustomMerkleTree::new(leaves);
         let local_root = custom_tree.root();
@@ -107,8 +110,10 @@ impl UploadFilesCommand {
 
         let id = api_client.initiate().await?;
 
-        for (idx, (name, data)) in filenames.into_iter().enumerate() {
-            let _ = api_client.upload_file(id, &name, idx, data).await?;
+        for (idx, entry) in file_entries.iter().enumerate() {
+            let _ = api_client
+                .upload_file(id, &entry.name, idx, &entry.path, entry.leaf)
+                .await?;
         }
 
         let server_root = api_client.complete(id).await?;
@@ -120,7 +125,7 @@ impl UploadFilesCommand {
             );
         }
 
-        file_manager.write_root_file(id, &root_hex).await?;
+        file_manager.write_root_file(id, 1, &root_hex).await?;
         file_manager.cleanup_files(file_entries).await?;
 
         println!("upload complete. id={}, root={}", id, root_hex);
@@ -162,6 +167,7 @@ impl Command for UploadFilesCommand {
                     .action(ArgAction::Set)
                     .help("API Secret for authentication"),
             )
+            .arg(server_key_arg())
             .arg(
                 Arg::new("files-directory")
                     .long("files-directory")
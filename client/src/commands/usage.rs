@@ -0,0 +1,147 @@
+use async_trait::async_trait;
+use clap::{Arg, ArgAction, ArgMatches};
+use ed25519_dalek::VerifyingKey;
+use reqwest::Url;
+use uuid::Uuid;
+
+use crate::{
+    ApiClient, ApiClientArgs,
+    commands::{
+        Command,
+        helpers::{get_server_keys, server_key_arg},
+    },
+};
+
+struct UsageCommandArgs {
+    api_key: String,
+    api_secret: String,
+    base_url: String,
+    server_keys: Vec<VerifyingKey>,
+}
+
+impl From<&ArgMatches> for UsageCommandArgs {
+    fn from(args: &ArgMatches) -> Self {
+        let api_key = args
+            .get_one::<String>("api-key")
+            .expect("API-KEY is required")
+            .to_owned();
+
+        let api_secret = args
+            .get_one::<String>("api-secret")
+            .expect("API-SECRET is required")
+            .to_owned();
+
+        let base_url = args
+            .get_one::<String>("base-url")
+            .expect("Base URL is required")
+            .to_owned();
+
+        let server_keys = get_server_keys(args).expect("Failed to parse server keys");
+
+        Self {
+            api_key,
+            api_secret,
+            base_url,
+            server_keys,
+        }
+    }
+}
+
+impl From<&UsageCommandArgs> for ApiClientArgs {
+    fn from(val: &UsageCommandArgs) -> Self {
+        ApiClientArgs {
+            api_key: val.api_key.clone(),
+            api_secret: val.api_secret.clone(),
+            base_url: Url::parse(&val.base_url).expect("Failed to parse base URL"),
+            correlation_id: Uuid::new_v4(),
+            server_keys: val.server_keys.clone(),
+        }
+    }
+}
+
+fn format_limit(used: u64, limit: Option<u64>) -> String {
+    match limit {
+        Some(limit) => format!("{used}/{limit}"),
+        None => format!("{used}/unlimited"),
+    }
+}
+
+pub struct UsageCommand;
+
+impl UsageCommand {
+    async fn usage(&self, api_client: ApiClient) -> anyhow::Result<()> {
+        let usage = api_client.get_usage().await?;
+        let limits = usage.limits;
+
+        println!(
+            "stored bytes: {}",
+            format_limit(usage.stored_bytes, limits.max_stored_bytes)
+        );
+        println!(
+            "open uploads: {}",
+            format_limit(usage.open_uploads, limits.max_open_uploads)
+        );
+        println!(
+            "max file size: {}",
+            limits
+                .max_file_size_bytes
+                .map_or("unlimited".to_string(), |limit| limit.to_string())
+        );
+        println!(
+            "max files per upload: {}",
+            limits
+                .max_files_per_upload
+                .map_or("unlimited".to_string(), |limit| limit.to_string())
+        );
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Command for UsageCommand {
+    fn create(&self) -> clap::Command {
+        clap::Command::new("usage")
+            .about("This command shows what the API key uses on the server against its quotas.")
+            .long_flag("usage")
+            .arg(
+                Arg::new("api-key")
+                    .long("api-key")
+                    .short('k')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Key for authentication"),
+            )
+            .arg(
+                Arg::new("api-secret")
+                    .long("api-secret")
+                    .short('s')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Secret for authentication"),
+            )
+            .arg(
+                Arg::new("base-url")
+                    .long("base-url")
+                    .short('u')
+                    .default_value("http://localhost:8080")
+                    .action(ArgAction::Set)
+                    .help("API Secret for authentication"),
+            )
+            .arg(server_key_arg())
+            .arg_required_else_help(true)
+    }
+
+    fn name(&self) -> String {
+        "usage".to_owned()
+    }
+
+    async fn execute(&self, args: &ArgMatches) {
+        let commands_args: UsageCommandArgs = args.into();
+        let api_args: ApiClientArgs = (&commands_args).into();
+
+        let api_cli = ApiClient::new(api_args).expect("Failed to create API client");
+
+        self.usage(api_cli).await.expect("Failed to get usage");
+    }
+}
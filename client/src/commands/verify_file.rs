@@ -1,13 +1,18 @@
 use async_trait::async_trait;
-use clap::{Arg, ArgAction, ArgMatches, value_parser};
+use clap::{Arg, ArgAction, ArgGroup, ArgMatches, value_parser};
+use ed25519_dalek::VerifyingKey;
 use file_server_library::{CustomMerkleTree, models::Hash32};
 use reqwest::Url;
+use std::fmt::Display;
 use std::path::PathBuf;
 use uuid::Uuid;
 
 use crate::{
     ApiClient, ApiClientArgs, FileManager,
-    commands::{Command, helpers::get_path_from_str},
+    commands::{
+        Command,
+        helpers::{get_path_from_str, get_server_keys, server_key_arg},
+    },
     file_manager::FileManagerArgs,
 };
 
@@ -15,10 +20,27 @@ struct VerifyFilesCommandArgs {
     api_key: String,
     api_secret: String,
     base_url: String,
+    server_keys: Vec<VerifyingKey>,
     files_directory: PathBuf,
     roots_store_directory: PathBuf,
-    index: usize,
+    file: FileSelector,
     id: Uuid,
+    version: Option<u32>,
+}
+
+/// Files can be verified either by their index or by their name.
+enum FileSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl Display for FileSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileSelector::Index(index) => write!(f, "index={}", index),
+            FileSelector::Name(name) => write!(f, "name={}", name),
+        }
+    }
 }
 
 impl From<&ArgMatches> for VerifyFilesCommandArgs {
@@ -38,13 +60,24 @@ impl From<&ArgMatches> for VerifyFilesCommandArgs {
             .expect("Base URL is required")
             .to_owned();
 
+        let server_keys = get_server_keys(args).expect("Failed to parse server keys");
+
         let id: Uuid = args
             .get_one::<String>("id")
             .expect("Upload ID is required")
             .parse()
             .expect("Failed to parse Upload ID");
 
-        let index = args.get_one::<usize>("index").expect("Index is required");
+        let version = args.get_one::<u32>("version").copied();
+
+        let file = match args.get_one::<usize>("index") {
+            Some(index) => FileSelector::Index(*index),
+            None => FileSelector::Name(
+                args.get_one::<String>("name")
+                    .expect("Index or name is required")
+                    .to_owned(),
+            ),
+        };
 
         let files_directory = args
             .get_one::<String>("files-directory")
@@ -62,10 +95,12 @@ impl From<&ArgMatches> for VerifyFilesCommandArgs {
             api_key,
             api_secret,
             base_url,
+            server_keys,
             files_directory,
             roots_store_directory,
-            index: *index,
+            file,
             id,
+            version,
         }
     }
 }
@@ -77,6 +112,7 @@ impl From<&VerifyFilesCommandArgs> for ApiClientArgs {
             api_secret: val.api_secret.clone(),
             base_url: Url::parse(&val.base_url).expect("Failed to parse base URL"),
             correlation_id: Uuid::new_v4(),
+            server_keys: val.server_keys.clone(),
         }
     }
 }
@@ -101,25 +137,46 @@ impl VerifyFileCommand {
         file_manager: FileManager,
         args: VerifyFilesCommandArgs,
     ) -> anyhow::Result<()> {
-        let root_hex = file_manager.load_root_file(args.id).await?;
+        let version = match args.version {
+            Some(version) => version,
+            None => file_manager
+                .latest_root_version(args.id)
+                .await?
+                .unwrap_or(1),
+        };
+        let root_hex = file_manager.load_root_file(args.id, Some(version)).await?;
         let root = Hash32::from_hex(&root_hex).map_err(|e| anyhow::anyhow!(e))?;
 
-        let file_bytes = api_client.download_file(args.id, args.index).await?;
+        let (file_bytes, proof) = match &args.file {
+            FileSelector::Index(index) => (
+                api_client
+                    .download_file(args.id, *index, Some(version))
+                    .await?,
+                api_client.get_proof(args.id, *index, Some(version)).await?,
+            ),
+            FileSelector::Name(name) => (
+                api_client
+                    .download_file_by_name(args.id, name, Some(version))
+                    .await?,
+                api_client
+                    .get_proof_by_name(args.id, name, Some(version))
+                    .await?,
+            ),
+        };
 
         let leaf = Hash32::hash(&file_bytes);
 
-        let proof = api_client.get_proof(args.id, args.index).await?;
         let ok = CustomMerkleTree::verify(&leaf, &proof, &root);
 
         if ok {
             println!(
-                "File verification succeeded for id={}, index={}",
-                args.id, args.index
+                "File verification succeeded for id={}, version={}, {}",
+                args.id, version, args.file
             );
         } else {
             eprintln!(
-                "File verification failed for id={}, index={}",
-                args.id, args.index
+                "File verification failed for id={}, version={}, {}",
+                args.id, version, args.file
             );
         }
 
@@ -131,7 +188,7 @@ impl VerifyFileCommand {
 impl Command for VerifyFileCommand {
     fn create(&self) -> clap::Command {
         clap::Command::new("verify-file")
-            .about("This command verifies that a file for a given index is valid.")
+            .about("This command verifies that a file for a given index or name is valid.")
             .long_flag("verify-file")
             .arg(
                 Arg::new("api-key")
@@ -157,6 +214,7 @@ impl Command for VerifyFileCommand {
                     .action(ArgAction::Set)
                     .help("API Secret for authentication"),
             )
+            .arg(server_key_arg())
             .arg(
                 Arg::new("files-directory")
                     .long("files-directory")
@@ -188,6 +246,21 @@ impl Command for VerifyFileCommand {
                     .action(ArgAction::Set)
                     .help("Index of the file to verify"),
             )
+            .arg(
+                Arg::new("name")
+                    .long("name")
+                    .short('n')
+                    .action(ArgAction::Set)
+                    .help("Name of the file to verify, instead of its index"),
+            )
+            .arg(
+                Arg::new("version")
+                    .long("version")
+                    .value_parser(value_parser!(u32))
+                    .action(ArgAction::Set)
+                    .help("Version of the upload, the latest one with a local root when missing"),
+            )
+            .group(ArgGroup::new("file").args(["index", "name"]).required(true))
             .arg_required_else_help(true)
     }
 
@@ -0,0 +1,282 @@
+use async_trait::async_trait;
+use clap::{Arg, ArgAction, ArgMatches};
+use ed25519_dalek::VerifyingKey;
+use file_server_library::{
+    models::Hash32,
+    transparency::{LogLeaf, LogTree},
+};
+use reqwest::Url;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::{
+    ApiClient, ApiClientArgs, FileManager,
+    api_client::LogHeadResponse,
+    commands::{
+        Command,
+        helpers::{get_path_from_str, get_server_keys, server_key_arg},
+    },
+    file_manager::FileManagerArgs,
+};
+
+struct VerifyLogCommandArgs {
+    api_key: String,
+    api_secret: String,
+    base_url: String,
+    server_keys: Vec<VerifyingKey>,
+    files_directory: PathBuf,
+    roots_store_directory: PathBuf,
+    id: Uuid,
+}
+
+impl From<&ArgMatches> for VerifyLogCommandArgs {
+    fn from(args: &ArgMatches) -> Self {
+        let api_key = args
+            .get_one::<String>("api-key")
+            .expect("API-KEY is required")
+            .to_owned();
+
+        let api_secret = args
+            .get_one::<String>("api-secret")
+            .expect("API-SECRET is required")
+            .to_owned();
+
+        let base_url = args
+            .get_one::<String>("base-url")
+            .expect("Base URL is required")
+            .to_owned();
+
+        let server_keys = get_server_keys(args).expect("Failed to parse server keys");
+
+        let id: Uuid = args
+            .get_one::<String>("id")
+            .expect("Upload ID is required")
+            .parse()
+            .expect("Failed to parse Upload ID");
+
+        let files_directory = args
+            .get_one::<String>("files-directory")
+            .expect("File directory is required");
+        let files_directory =
+            get_path_from_str(files_directory).expect("Failed to parse files directory");
+
+        let roots_store_directory = args
+            .get_one::<String>("roots-store-directory")
+            .expect("File directory is required");
+        let roots_store_directory = get_path_from_str(roots_store_directory)
+            .expect("Failed to parse roots store directory");
+
+        Self {
+            api_key,
+            api_secret,
+            base_url,
+            server_keys,
+            files_directory,
+            roots_store_directory,
+            id,
+        }
+    }
+}
+
+impl From<&VerifyLogCommandArgs> for ApiClientArgs {
+    fn from(val: &VerifyLogCommandArgs) -> Self {
+        ApiClientArgs {
+            api_key: val.api_key.clone(),
+            api_secret: val.api_secret.clone(),
+            base_url: Url::parse(&val.base_url).expect("Failed to parse base URL"),
+            correlation_id: Uuid::new_v4(),
+            server_keys: val.server_keys.clone(),
+        }
+    }
+}
+
+impl From<&VerifyLogCommandArgs> for FileManagerArgs {
+    fn from(val: &VerifyLogCommandArgs) -> Self {
+        FileManagerArgs {
+            files_storage_path: val.files_directory.clone(),
+            roots_storage_path: val.roots_store_directory.clone(),
+        }
+    }
+}
+
+pub struct VerifyLogCommand;
+
+impl VerifyLogCommand {
+    // The root stored locally must be in the log, and the log must extend the last head seen,
+    // which is only replaced once both checks pass.
+    async fn verify_log(
+        &self,
+        api_client: ApiClient,
+        file_manager: FileManager,
+        args: VerifyLogCommandArgs,
+    ) -> anyhow::Result<()> {
+        if args.server_keys.is_empty() {
+            println!("No server key pinned, log head signatures are not checked");
+        }
+
+        let root = file_manager.load_root_file(args.id, None).await?;
+        let inclusion = api_client.get_log_proof(args.id).await?;
+        let head = inclusion.head;
+        let entry = inclusion.entry;
+        api_client.verify_log_head(&head)?;
+
+        let leaf = LogLeaf {
+            upload_id: entry.upload_id.to_string(),
+            owner: entry.owner.clone(),
+            root: entry.root.clone(),
+            appended_at: entry.appended_at,
+        };
+        let included = entry.upload_id == args.id
+            && entry.root == root
+            && leaf.leaf_hash().to_hex() == entry.leaf_hash
+            && LogTree::verify_inclusion(
+                &leaf.leaf_hash(),
+                entry.index,
+                head.size,
+                &parse_hashes(&inclusion.proof)?,
+                &parse_hash(&head.root)?,
+            );
+        if !included {
+            eprintln!(
+                "Log verification failed for id={}, root {} is not in the log of size {}",
+                args.id, root, head.size
+            );
+            return Ok(());
+        }
+
+        if let Some(previous) = file_manager.load_log_head().await? {
+            let previous: LogHeadResponse = serde_json::from_str(&previous)?;
+            if !self.is_consistent(&api_client, &previous, &head).await? {
+                eprintln!(
+                    "Log verification failed, the log of size {} does not extend the log of size {} seen before",
+                    head.size, previous.size
+                );
+                return Ok(());
+            }
+        }
+
+        file_manager
+            .write_log_head(&serde_json::to_string(&head)?)
+            .await?;
+
+        println!(
+            "Log verification succeeded for id={}, entry {} in the log of size {}",
+            args.id, entry.index, head.size
+        );
+
+        Ok(())
+    }
+
+    async fn is_consistent(
+        &self,
+        api_client: &ApiClient,
+        previous: &LogHeadResponse,
+        head: &LogHeadResponse,
+    ) -> anyhow::Result<bool> {
+        // The log only grows, a smaller head means entries were dropped.
+        if previous.size > head.size {
+            return Ok(false);
+        }
+
+        let consistency = api_client
+            .get_log_consistency(previous.size, head.size)
+            .await?;
+        api_client.verify_log_head(&consistency.first)?;
+        api_client.verify_log_head(&consistency.second)?;
+
+        Ok(consistency.first.root == previous.root
+            && consistency.second.root == head.root
+            && LogTree::verify_consistency(
+                previous.size,
+                head.size,
+                &parse_hash(&previous.root)?,
+                &parse_hash(&head.root)?,
+                &parse_hashes(&consistency.proof)?,
+            ))
+    }
+}
+
+fn parse_hash(hash: &str) -> anyhow::Result<Hash32> {
+    Hash32::from_hex(hash).map_err(|e| anyhow::anyhow!(e))
+}
+
+fn parse_hashes(hashes: &[String]) -> anyhow::Result<Vec<Hash32>> {
+    hashes.iter().map(|hash| parse_hash(hash)).collect()
+}
+
+#[async_trait]
+impl Command for VerifyLogCommand {
+    fn create(&self) -> clap::Command {
+        clap::Command::new("verify-log")
+            .about("This command verifies that the root of an upload is in the server transparency log.")
+            .long_flag("verify-log")
+            .arg(
+                Arg::new("api-key")
+                    .long("api-key")
+                    .short('k')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Key for authentication"),
+            )
+            .arg(
+                Arg::new("api-secret")
+                    .long("api-secret")
+                    .short('s')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Secret for authentication"),
+            )
+            .arg(
+                Arg::new("base-url")
+                    .long("base-url")
+                    .short('u')
+                    .default_value("http://localhost:8080")
+                    .action(ArgAction::Set)
+                    .help("Base URL of the server"),
+            )
+            .arg(server_key_arg())
+            .arg(
+                Arg::new("files-directory")
+                    .long("files-directory")
+                    .short('f')
+                    .default_value("~/files")
+                    .action(ArgAction::Set)
+                    .help("Local directory containing files to upload"),
+            )
+            .arg(
+                Arg::new("roots-store-directory")
+                    .long("roots-store-directory")
+                    .short('r')
+                    .default_value("~/roots")
+                    .action(ArgAction::Set)
+                    .help("Local directory to persist upload roots and the last log head seen"),
+            )
+            .arg(
+                Arg::new("id")
+                    .long("id")
+                    .short('i')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("Upload ID to look up in the log"),
+            )
+            .arg_required_else_help(true)
+    }
+
+    fn name(&self) -> String {
+        "verify-log".to_owned()
+    }
+
+    async fn execute(&self, args: &ArgMatches) {
+        let commands_args: VerifyLogCommandArgs = args.into();
+
+        let api_args: ApiClientArgs = (&commands_args).into();
+        let file_manager_args: FileManagerArgs = (&commands_args).into();
+
+        let api_cli = ApiClient::new(api_args).expect("Failed to create API client");
+        let file_manager = FileManager::new(file_manager_args);
+
+        self.verify_log(api_cli, file_manager, commands_args)
+            .await
+            .expect("Failed to verify log");
+    }
+}
@@ -0,0 +1,155 @@
+use async_trait::async_trait;
+use clap::{Arg, ArgAction, ArgMatches};
+use ed25519_dalek::VerifyingKey;
+use reqwest::Url;
+use uuid::Uuid;
+
+use crate::{
+    ApiClient, ApiClientArgs,
+    api_client::UploadEventResponse,
+    commands::{
+        Command,
+        helpers::{get_server_keys, server_key_arg},
+    },
+};
+
+struct WatchUploadCommandArgs {
+    api_key: String,
+    api_secret: String,
+    base_url: String,
+    server_keys: Vec<VerifyingKey>,
+    id: Uuid,
+}
+
+impl From<&ArgMatches> for WatchUploadCommandArgs {
+    fn from(args: &ArgMatches) -> Self {
+        let api_key = args
+            .get_one::<String>("api-key")
+            .expect("API-KEY is required")
+            .to_owned();
+
+        let api_secret = args
+            .get_one::<String>("api-secret")
+            .expect("API-SECRET is required")
+            .to_owned();
+
+        let base_url = args
+            .get_one::<String>("base-url")
+            .expect("Base URL is required")
+            .to_owned();
+
+        let server_keys = get_server_keys(args).expect("Failed to parse server keys");
+
+        let id: Uuid = args
+            .get_one::<String>("id")
+            .expect("Upload ID is required")
+            .parse()
+            .expect("Failed to parse Upload ID");
+
+        Self {
+            api_key,
+            api_secret,
+            base_url,
+            server_keys,
+            id,
+        }
+    }
+}
+
+impl From<&WatchUploadCommandArgs> for ApiClientArgs {
+    fn from(val: &WatchUploadCommandArgs) -> Self {
+        ApiClientArgs {
+            api_key: val.api_key.clone(),
+            api_secret: val.api_secret.clone(),
+            base_url: Url::parse(&val.base_url).expect("Failed to parse base URL"),
+            correlation_id: Uuid::new_v4(),
+            server_keys: val.server_keys.clone(),
+        }
+    }
+}
+
+pub struct WatchUploadCommand;
+
+impl WatchUploadCommand {
+    async fn watch(&self, api_client: ApiClient, id: Uuid) -> anyhow::Result<()> {
+        println!("Watching upload. id={}", id);
+
+        api_client
+            .watch_upload(id, |event| match event {
+                UploadEventResponse::FileReceived { index, name, size } => {
+                    println!("file received: index={index} name={name} size={size}");
+                }
+                UploadEventResponse::HashComputed {
+                    index,
+                    name,
+                    leaf_hash,
+                } => {
+                    println!("hash computed: index={index} name={name} leaf_hash={leaf_hash}");
+                }
+                UploadEventResponse::Completed { version, root } => {
+                    println!("completed: version={version} root={root}");
+                }
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Command for WatchUploadCommand {
+    fn create(&self) -> clap::Command {
+        clap::Command::new("watch-upload")
+            .about("This command follows the events of an upload until it is completed.")
+            .long_flag("watch-upload")
+            .arg(
+                Arg::new("api-key")
+                    .long("api-key")
+                    .short('k')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Key for authentication"),
+            )
+            .arg(
+                Arg::new("api-secret")
+                    .long("api-secret")
+                    .short('s')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("API Secret for authentication"),
+            )
+            .arg(
+                Arg::new("base-url")
+                    .long("base-url")
+                    .short('u')
+                    .default_value("http://localhost:8080")
+                    .action(ArgAction::Set)
+                    .help("API Secret for authentication"),
+            )
+            .arg(server_key_arg())
+            .arg(
+                Arg::new("id")
+                    .long("id")
+                    .short('i')
+                    .required(true)
+                    .action(ArgAction::Set)
+                    .help("Upload ID to watch"),
+            )
+            .arg_required_else_help(true)
+    }
+
+    fn name(&self) -> String {
+        "watch-upload".to_owned()
+    }
+
+    async fn execute(&self, args: &ArgMatches) {
+        let commands_args: WatchUploadCommandArgs = args.into();
+        let api_args: ApiClientArgs = (&commands_args).into();
+
+        let api_cli = ApiClient::new(api_args).expect("Failed to create API client");
+
+        self.watch(api_cli, commands_args.id)
+            .await
+            .expect("Failed to watch upload");
+    }
+}
@@ -1,14 +1,25 @@
 // The functionality of this FileManager, suchas as writting or loading files, was delegated to
 // ChatGPT, I then took the functions and refactored them as I liked.
 // One of those big refactors was to replace `fs` with `tokio::fs` to make it async
-use std::path::PathBuf;
-use tokio::fs;
+use file_server_library::models::{Hash32, Hash32Hasher};
+use std::path::{Path, PathBuf};
+use tokio::{
+    fs::{self, File},
+    io::AsyncReadExt,
+};
 use uuid::Uuid;
 
+// Kept next to the roots, it does not end in `.root` so it is never listed as one.
+const LOG_HEAD_FILE_NAME: &str = "log_head.json";
+// Hidden, so a failed download leaving one behind does not look like one of the files.
+const STAGING_DIR_PREFIX: &str = ".staging-";
+const READ_BUFFER_BYTES: usize = 64 * 1024;
+
+/// File to upload, hashed as it was read. Contents stay on disk until they are sent.
 pub struct FileEntry {
     pub path: PathBuf,
     pub name: String,
-    pub data: Vec<u8>,
+    pub leaf: Hash32,
 }
 
 pub struct FileManagerArgs {
@@ -44,8 +55,8 @@ impl FileManager {
         for e in entries {
             let name = e.file_name().to_string_lossy().to_string();
             let path = e.path();
-            let data = fs::read(&path).await?;
-            result.push(FileEntry { path, name, data });
+            let leaf = hash_file(&path).await?;
+            result.push(FileEntry { path, name, leaf });
         }
 
         Ok(result)
@@ -59,8 +70,13 @@ impl FileManager {
         Ok(())
     }
 
-    pub async fn load_root_file(&self, id: Uuid) -> anyhow::Result<String> {
-        let path = self.args.roots_storage_path.join(format!("{}.root", id));
+    /// Root of the given version of the upload, the latest one stored locally when missing.
+    pub async fn load_root_file(&self, id: Uuid, version: Option<u32>) -> anyhow::Result<String> {
+        let version = match version {
+            Some(version) => version,
+            None => self.latest_root_version(id).await?.unwrap_or(1),
+        };
+        let path = self.root_path(id, version);
 
         let s = fs::read_to_string(path).await?;
 
@@ -88,13 +104,159 @@ impl FileManager {
         Ok(result)
     }
 
-    pub async fn write_root_file(&self, id: Uuid, root_hex: &str) -> anyhow::Result<()> {
+    /// Latest version of the upload with a root stored locally, `None` when there is none.
+    pub async fn latest_root_version(&self, id: Uuid) -> anyhow::Result<Option<u32>> {
+        let mut rd = match fs::read_dir(&self.args.roots_storage_path).await {
+            Ok(rd) => rd,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut latest = None;
+
+        while let Some(entry) = rd.next_entry().await? {
+            if let Some(version) = entry
+                .file_name()
+                .to_str()
+                .and_then(|filename| parse_root_file_name(id, filename))
+            {
+                latest = latest.max(Some(version));
+            }
+        }
+
+        Ok(latest)
+    }
+
+    pub async fn write_root_file(
+        &self,
+        id: Uuid,
+        version: u32,
+        root_hex: &str,
+    ) -> anyhow::Result<()> {
         fs::create_dir_all(&self.args.roots_storage_path).await?;
 
-        let path = self.args.roots_storage_path.join(format!("{}.root", id));
+        let path = self.root_path(id, version);
 
         fs::write(path, root_hex).await?;
 
         Ok(())
     }
+
+    /// Writes a file under the files directory, creating the directories in its name.
+    pub async fn write_file(&self, name: &str, data: &[u8]) -> anyhow::Result<PathBuf> {
+        let path = self.args.files_storage_path.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::write(&path, data).await?;
+
+        Ok(path)
+    }
+
+    /// Empty directory under the files directory to put files in until they are verified.
+    /// Being on the same file system, they are moved into place without being copied.
+    pub async fn create_staging_dir(&self) -> anyhow::Result<PathBuf> {
+        let path = self
+            .args
+            .files_storage_path
+            .join(format!("{STAGING_DIR_PREFIX}{}", Uuid::new_v4()));
+        fs::create_dir_all(&path).await?;
+
+        Ok(path)
+    }
+
+    pub async fn remove_staging_dir(&self, path: &Path) -> anyhow::Result<()> {
+        fs::remove_dir_all(path).await?;
+
+        Ok(())
+    }
+
+    /// Moves a file into the files directory, creating the directories in its name.
+    pub async fn move_file(&self, name: &str, from: &Path) -> anyhow::Result<PathBuf> {
+        let path = self.args.files_storage_path.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::rename(from, &path).await?;
+
+        Ok(path)
+    }
+
+    /// Last transparency log head seen by `verify-log`, as it was received.
+    pub async fn load_log_head(&self) -> anyhow::Result<Option<String>> {
+        let path = self.args.roots_storage_path.join(LOG_HEAD_FILE_NAME);
+
+        match fs::read_to_string(path).await {
+            Ok(head) => Ok(Some(head)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn write_log_head(&self, head: &str) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.args.roots_storage_path).await?;
+
+        let path = self.args.roots_storage_path.join(LOG_HEAD_FILE_NAME);
+
+        fs::write(path, head).await?;
+
+        Ok(())
+    }
+
+    /// Deletes the roots of every version of the upload. Returns `false` when there was none.
+    pub async fn delete_root_file(&self, id: Uuid) -> anyhow::Result<bool> {
+        let Some(latest) = self.latest_root_version(id).await? else {
+            return Ok(false);
+        };
+
+        for version in 1..=latest {
+            match fs::remove_file(self.root_path(id, version)).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(true)
+    }
+
+    // The first version keeps the name it had before uploads were versioned, the following
+    // ones do not parse as a `Uuid` so `list_root_files` lists each upload once.
+    fn root_path(&self, id: Uuid, version: u32) -> PathBuf {
+        let name = match version {
+            1 => format!("{}.root", id),
+            _ => format!("{}.v{}.root", id, version),
+        };
+        self.args.roots_storage_path.join(name)
+    }
+}
+
+fn parse_root_file_name(id: Uuid, filename: &str) -> Option<u32> {
+    let rest = filename
+        .strip_prefix(&id.to_string())?
+        .strip_suffix(".root")?;
+
+    match rest.strip_prefix(".v") {
+        Some(version) => version.parse().ok(),
+        None if rest.is_empty() => Some(1),
+        None => None,
+    }
+}
+
+// Read in pieces, files may be larger than the memory at hand.
+async fn hash_file(path: &Path) -> anyhow::Result<Hash32> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Hash32Hasher::new();
+    let mut buffer = vec![0; READ_BUFFER_BYTES];
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize())
 }
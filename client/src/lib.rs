@@ -1,4 +1,5 @@
 mod api_client;
+mod archive;
 mod commands;
 mod file_manager;
 
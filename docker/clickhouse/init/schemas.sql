@@ -2,13 +2,203 @@ CREATE DATABASE IF NOT EXISTS file_server;
 
 CREATE TABLE file_server.files
 (
-  id           UUID,
-  files_order  Array(String),
-  files        Map(String, String),
-  leaf_hashes  Array(String),
-  root         Nullable(String),
-  updated_at   DateTime64(3) DEFAULT now()
+  id                  UUID,
+  version             UInt32 DEFAULT 1,
+  previous_version    Nullable(UInt32),
+  owner               String DEFAULT '',
+  files_order         Array(String),
+  files               Map(String, String),
+  leaf_hashes         Array(String),
+  file_sizes          Array(UInt64),
+  file_content_types  Array(String),
+  file_uploaded_at    Array(DateTime64(3)),
+  root                Nullable(String),
+  state               LowCardinality(String) DEFAULT 'initiated',
+  created_at          DateTime64(3) DEFAULT now(),
+  updated_at          DateTime64(3) DEFAULT now(),
+  recent_writes       Array(String),
+  deleted             Bool DEFAULT false
 )
 ENGINE = ReplacingMergeTree
 PRIMARY KEY id
+ORDER BY id
+SETTINGS non_replicated_deduplication_window = 10000;
+
+CREATE TABLE file_server.file_versions
+(
+  id                  UUID,
+  version             UInt32,
+  previous_version    Nullable(UInt32),
+  owner               String,
+  files_order         Array(String),
+  files               Map(String, String),
+  leaf_hashes         Array(String),
+  file_sizes          Array(UInt64),
+  file_content_types  Array(String),
+  file_uploaded_at    Array(DateTime64(3)),
+  root                Nullable(String),
+  state               LowCardinality(String),
+  created_at          DateTime64(3) DEFAULT now(),
+  updated_at          DateTime64(3) DEFAULT now()
+)
+ENGINE = ReplacingMergeTree(updated_at)
+PRIMARY KEY (id, version)
+ORDER BY (id, version);
+
+CREATE TABLE file_server.upload_sessions
+(
+  id             UUID,
+  upload_id      UUID,
+  name           String,
+  file_index     UInt64,
+  content_type   Nullable(String),
+  length         Nullable(UInt64),
+  offset         UInt64,
+  chunk_offsets  Array(UInt64),
+  chunk_ids      Array(String),
+  created_at     DateTime64(3) DEFAULT now(),
+  updated_at     DateTime64(3) DEFAULT now()
+)
+ENGINE = ReplacingMergeTree
+PRIMARY KEY id
+ORDER BY id
+SETTINGS non_replicated_deduplication_window = 10000;
+
+CREATE TABLE file_server.blobs
+(
+  hash        String,
+  updated_at  DateTime64(3) DEFAULT now()
+)
+ENGINE = ReplacingMergeTree(updated_at)
+PRIMARY KEY hash
+ORDER BY hash;
+
+CREATE TABLE file_server.blob_references
+(
+  hash          String,
+  upload_id     UUID,
+  owner         String,
+  name          String,
+  content_type  String,
+  created_at    DateTime64(3) DEFAULT now()
+)
+ENGINE = ReplacingMergeTree(created_at)
+PRIMARY KEY (upload_id, name)
+ORDER BY (upload_id, name, hash);
+
+CREATE TABLE file_server.scrub_findings
+(
+  upload_id      UUID,
+  file_index     UInt64,
+  name           String,
+  kind           LowCardinality(String),
+  expected_hash  String,
+  actual_hash    Nullable(String),
+  detected_at    DateTime64(3) DEFAULT now()
+)
+ENGINE = MergeTree
+ORDER BY (detected_at, upload_id, file_index);
+
+CREATE TABLE file_server.scrub_state
+(
+  name        String,
+  cursor      Nullable(String),
+  updated_at  DateTime64(3) DEFAULT now()
+)
+ENGINE = ReplacingMergeTree(updated_at)
+PRIMARY KEY name
+ORDER BY name;
+
+CREATE TABLE file_server.transparency_log
+(
+  log_index    UInt64,
+  upload_id    UUID,
+  owner        String,
+  root         String,
+  appended_at  DateTime64(3) DEFAULT now(),
+  leaf_hash    String
+)
+ENGINE = MergeTree
+ORDER BY log_index
+SETTINGS non_replicated_deduplication_window = 10000;
+
+CREATE TABLE file_server.usage
+(
+  owner         String,
+  stored_bytes  Int64,
+  open_uploads  Int64
+)
+ENGINE = SummingMergeTree((stored_bytes, open_uploads))
+ORDER BY owner;
+
+CREATE TABLE file_server.idempotency_keys
+(
+  owner         String,
+  key           String,
+  fingerprint   String,
+  status        UInt16,
+  content_type  Nullable(String),
+  headers       Map(String, String),
+  body          String,
+  expires_at    DateTime64(3)
+)
+ENGINE = ReplacingMergeTree(expires_at)
+ORDER BY (owner, key)
+TTL toDateTime(expires_at);
+
+CREATE TABLE file_server.pending_writes
+(
+  id            UUID,
+  upload_id     UUID,
+  file_index    UInt64,
+  name          String,
+  content_type  String,
+  staging_name  Nullable(String),
+  hash          Nullable(String),
+  size          UInt64,
+  created_at    DateTime64(3) DEFAULT now()
+)
+ENGINE = MergeTree
+PRIMARY KEY id
 ORDER BY id;
+
+CREATE TABLE file_server.webhook_deliveries
+(
+  id               UUID,
+  owner            String,
+  event            LowCardinality(String),
+  upload_id        UUID,
+  url              String,
+  payload          String,
+  state            LowCardinality(String),
+  attempts         UInt32,
+  next_attempt_at  DateTime64(3),
+  last_status      Nullable(UInt16),
+  last_error       Nullable(String),
+  created_at       DateTime64(3) DEFAULT now(),
+  updated_at       DateTime64(3) DEFAULT now()
+)
+ENGINE = MergeTree
+PRIMARY KEY (owner, id)
+ORDER BY (owner, id);
+
+CREATE TABLE file_server.audit_log
+(
+  owner           String,
+  sequence        UInt64,
+  action          LowCardinality(String),
+  upload_id       Nullable(UUID),
+  file_index      Nullable(UInt64),
+  name            Nullable(String),
+  hash            Nullable(String),
+  status          UInt16,
+  request_id      Nullable(String),
+  correlation_id  Nullable(String),
+  occurred_at     DateTime64(3),
+  previous_hash   String,
+  entry_hash      String
+)
+ENGINE = MergeTree
+PRIMARY KEY (owner, sequence)
+ORDER BY (owner, sequence)
+SETTINGS non_replicated_deduplication_window = 10000;
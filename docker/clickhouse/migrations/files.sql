@@ -0,0 +1,14 @@
+-- Brings a files table created before versions, owners and upload states up to date with schemas.sql.
+-- Every statement can be run again. Existing rows get one size, content type and upload date per leaf,
+-- and uploads with a root are completed.
+ALTER TABLE file_server.files ADD COLUMN IF NOT EXISTS version UInt32 DEFAULT 1 AFTER id;
+ALTER TABLE file_server.files ADD COLUMN IF NOT EXISTS previous_version Nullable(UInt32) AFTER version;
+ALTER TABLE file_server.files ADD COLUMN IF NOT EXISTS owner String DEFAULT '' AFTER previous_version;
+ALTER TABLE file_server.files ADD COLUMN IF NOT EXISTS file_sizes Array(UInt64) DEFAULT arrayMap(x -> toUInt64(0), leaf_hashes) AFTER leaf_hashes;
+ALTER TABLE file_server.files ADD COLUMN IF NOT EXISTS file_content_types Array(String) DEFAULT arrayMap(x -> 'application/octet-stream', leaf_hashes) AFTER file_sizes;
+ALTER TABLE file_server.files ADD COLUMN IF NOT EXISTS file_uploaded_at Array(DateTime64(3)) DEFAULT arrayMap(x -> updated_at, leaf_hashes) AFTER file_content_types;
+ALTER TABLE file_server.files ADD COLUMN IF NOT EXISTS state LowCardinality(String) DEFAULT if(root IS NULL, 'initiated', 'completed') AFTER root;
+ALTER TABLE file_server.files ADD COLUMN IF NOT EXISTS created_at DateTime64(3) DEFAULT updated_at AFTER state;
+ALTER TABLE file_server.files ADD COLUMN IF NOT EXISTS recent_writes Array(String) AFTER updated_at;
+ALTER TABLE file_server.files ADD COLUMN IF NOT EXISTS deleted Bool DEFAULT false AFTER recent_writes;
+ALTER TABLE file_server.files MODIFY SETTING non_replicated_deduplication_window = 10000;
@@ -1,4 +1,6 @@
 mod custom_merkle;
 pub mod models;
+pub mod signing;
+pub mod transparency;
 
 pub use custom_merkle::CustomMerkleTree;
@@ -6,7 +6,7 @@ use sha2::{Digest, Sha256};
 // This struct is a wrapper around a 32-byte array representing a SHA-256 hash.
 // The intention is to abstract implementation details behind something more meaningful.
 // TODO: Allow different implementations of hash functions via features
-#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
 pub struct Hash32([u8; 32]);
 
 impl Hash32 {
@@ -36,6 +36,24 @@ impl Hash32 {
     }
 }
 
+/// Incremental counterpart of `Hash32::hash`, for contents that are never fully held in memory.
+#[derive(Clone, Default)]
+pub struct Hash32Hasher(Sha256);
+
+impl Hash32Hasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    pub fn finalize(self) -> Hash32 {
+        Hash32(self.0.finalize().into())
+    }
+}
+
 impl From<(&Hash32, &Hash32)> for Hash32 {
     fn from((left, right): (&Hash32, &Hash32)) -> Self {
         let mut hasher = Sha256::new();
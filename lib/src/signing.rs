@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use crate::models::Hash32;
+
+pub const SERVER_KEY_HEADER: &str = "x-server-key";
+pub const SERVER_SIGNATURE_HEADER: &str = "x-server-signature";
+pub const SERVER_DIGEST_HEADER: &str = "x-server-digest";
+
+/// Response headers covered by the signature. `x-server-digest` is the SHA-256 of the body, it
+/// is missing when the body was streamed and could not be digested upfront.
+pub const SIGNED_HEADERS: [&str; 8] = [
+    "content-type",
+    "content-disposition",
+    "content-encoding",
+    "content-range",
+    "etag",
+    "location",
+    "upload-offset",
+    SERVER_DIGEST_HEADER,
+];
+
+const SIGNATURE_CONTEXT: &str = "file-server-response-v1";
+
+/// Short identifier of an Ed25519 public key: the first 8 bytes of its SHA-256, in hex.
+pub fn key_id(public_key: &[u8; 32]) -> String {
+    let hash: [u8; 32] = Hash32::hash(public_key).into();
+    hex::encode(&hash[..8])
+}
+
+/// What the server signs for every response. Both ends build it from the request they sent or
+/// received and the response headers, so the signature binds the response to its request.
+#[derive(Clone, Debug)]
+pub struct SignedResponse {
+    key_id: String,
+    method: String,
+    path: String,
+    request_signature: String,
+    status: u16,
+    headers: HashMap<&'static str, String>,
+}
+
+impl SignedResponse {
+    /// `path` includes the query string, exactly as it was sent.
+    pub fn new(key_id: &str, method: &str, path: &str, status: u16) -> Self {
+        Self {
+            key_id: key_id.to_string(),
+            method: method.to_uppercase(),
+            path: path.to_string(),
+            request_signature: String::new(),
+            status,
+            headers: HashMap::new(),
+        }
+    }
+
+    /// Signature of the authenticated request, which makes the response useless for any other.
+    pub fn with_request_signature(mut self, signature: &str) -> Self {
+        self.request_signature = signature.to_string();
+        self
+    }
+
+    /// Takes the value of every signed header from `lookup`, which receives lowercase names.
+    pub fn with_headers<'a>(mut self, lookup: impl Fn(&str) -> Option<&'a str>) -> Self {
+        for name in SIGNED_HEADERS {
+            if let Some(value) = lookup(name) {
+                self.headers.insert(name, value.to_string());
+            }
+        }
+        self
+    }
+
+    /// Canonical bytes to sign: one `name:value` line per field, missing headers left empty.
+    pub fn message(&self) -> Vec<u8> {
+        let mut message = format!(
+            "{SIGNATURE_CONTEXT}\nkey:{}\nrequest:{} {}\nrequest-signature:{}\nstatus:{}\n",
+            self.key_id, self.method, self.path, self.request_signature, self.status
+        );
+
+        for name in SIGNED_HEADERS {
+            let value = self
+                .headers
+                .get(name)
+                .map(String::as_str)
+                .unwrap_or_default();
+            message.push_str(&format!("{name}:{value}\n"));
+        }
+
+        message.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response() -> SignedResponse {
+        SignedResponse::new("0011223344556677", "get", "/api/v1/uploads?limit=10", 200)
+            .with_request_signature("abcdef")
+    }
+
+    #[test]
+    fn message_lists_every_signed_header() {
+        // Arrange
+        let response = response().with_headers(|name| match name {
+            "content-type" => Some("application/json"),
+            _ => None,
+        });
+
+        // Act
+        let message = String::from_utf8(response.message()).unwrap();
+
+        // Assert
+        assert_eq!(
+            message,
+            "file-server-response-v1\n\
+             key:0011223344556677\n\
+             request:GET /api/v1/uploads?limit=10\n\
+             request-signature:abcdef\n\
+             status:200\n\
+             content-type:application/json\n\
+             content-disposition:\n\
+             content-encoding:\n\
+             content-range:\n\
+             etag:\n\
+             location:\n\
+             upload-offset:\n\
+             x-server-digest:\n"
+        );
+    }
+
+    #[test]
+    fn message_changes_with_status_and_headers() {
+        // Arrange
+        let original = response().message();
+
+        // Act
+        let other_status =
+            SignedResponse::new("0011223344556677", "GET", "/api/v1/uploads?limit=10", 404)
+                .with_request_signature("abcdef")
+                .message();
+        let other_digest = response()
+            .with_headers(|name| (name == SERVER_DIGEST_HEADER).then_some("00"))
+            .message();
+
+        // Assert
+        assert_ne!(original, other_status);
+        assert_ne!(original, other_digest);
+    }
+
+    #[test]
+    fn key_id_is_short_hash_of_key() {
+        // Act
+        let id = key_id(&[7u8; 32]);
+
+        // Assert
+        assert_eq!(id.len(), 16);
+        assert_eq!(id, Hash32::hash(&[7u8; 32]).to_hex()[..16]);
+    }
+}
@@ -0,0 +1,432 @@
+// Merkle tree of the server-wide transparency log, following RFC 6962 (and its successor RFC
+// 9162) rather than `CustomMerkleTree`: leaves and nodes are hashed with different prefixes and
+// unbalanced trees are split at the largest power of two instead of duplicating the last leaf.
+// That is what makes consistency proofs between two sizes of the log possible.
+use sha2::{Digest, Sha256};
+
+use crate::models::Hash32;
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Everything the log commits to when an upload is completed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogLeaf {
+    pub upload_id: String,
+    pub owner: String,
+    /// Root of the upload, in hex.
+    pub root: String,
+    /// Milliseconds since the epoch.
+    pub appended_at: i64,
+}
+
+impl LogLeaf {
+    pub fn leaf_hash(&self) -> Hash32 {
+        let data = format!(
+            "file-server-log-entry-v1\nupload:{}\nowner:{}\nroot:{}\nappended-at:{}\n",
+            self.upload_id, self.owner, self.root, self.appended_at
+        );
+        LogTree::leaf_hash(data.as_bytes())
+    }
+}
+
+/// Canonical bytes the server signs for a log head of `size` entries.
+pub fn log_head_message(size: u64, root: &Hash32, timestamp: i64) -> Vec<u8> {
+    format!(
+        "file-server-log-head-v1\nsize:{}\nroot:{}\ntimestamp:{}\n",
+        size,
+        root.to_hex(),
+        timestamp
+    )
+    .into_bytes()
+}
+
+/// The log as a list of leaf hashes, in append order.
+pub struct LogTree {
+    leaves: Vec<Hash32>,
+}
+
+impl LogTree {
+    pub fn new(leaves: Vec<Hash32>) -> Self {
+        Self { leaves }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.leaves.len() as u64
+    }
+
+    pub fn leaf_hash(data: &[u8]) -> Hash32 {
+        let mut hasher = Sha256::new();
+        hasher.update([LEAF_PREFIX]);
+        hasher.update(data);
+        Hash32::from(<[u8; 32]>::from(hasher.finalize()))
+    }
+
+    /// Root of the whole log, the hash of nothing when it is empty.
+    pub fn root(&self) -> Hash32 {
+        if self.leaves.is_empty() {
+            return Hash32::from(<[u8; 32]>::from(Sha256::digest([])));
+        }
+        Self::subtree_root(&self.leaves)
+    }
+
+    /// Audit path of the leaf at `index`.
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn inclusion_proof(&self, index: u64) -> Vec<Hash32> {
+        assert!(index < self.size(), "Leaf index out of bounds");
+        Self::path(index as usize, &self.leaves)
+    }
+
+    /// Proof that the first `old_size` leaves are a prefix of this log.
+    ///
+    /// Panics if `old_size` is bigger than the log.
+    pub fn consistency_proof(&self, old_size: u64) -> Vec<Hash32> {
+        assert!(old_size <= self.size(), "Old size out of bounds");
+        if old_size == 0 || old_size == self.size() {
+            return vec![];
+        }
+        Self::subproof(old_size as usize, &self.leaves, true)
+    }
+
+    /// Checks `leaf` sits at `index` in the log of `size` leaves with the given root.
+    pub fn verify_inclusion(
+        leaf: &Hash32,
+        index: u64,
+        size: u64,
+        proof: &[Hash32],
+        root: &Hash32,
+    ) -> bool {
+        if index >= size {
+            return false;
+        }
+
+        let (mut fnode, mut snode) = (index, size - 1);
+        let mut result = *leaf;
+
+        for sibling in proof {
+            if snode == 0 {
+                return false;
+            }
+
+            if fnode & 1 == 1 || fnode == snode {
+                result = Self::node_hash(sibling, &result);
+                while fnode & 1 == 0 && fnode != 0 {
+                    fnode >>= 1;
+                    snode >>= 1;
+                }
+            } else {
+                result = Self::node_hash(&result, sibling);
+            }
+
+            fnode >>= 1;
+            snode >>= 1;
+        }
+
+        snode == 0 && result == *root
+    }
+
+    /// Checks the log of `new_size` leaves extends the log of `old_size` leaves.
+    pub fn verify_consistency(
+        old_size: u64,
+        new_size: u64,
+        old_root: &Hash32,
+        new_root: &Hash32,
+        proof: &[Hash32],
+    ) -> bool {
+        if old_size > new_size {
+            return false;
+        }
+        if old_size == new_size {
+            return proof.is_empty() && old_root == new_root;
+        }
+        if old_size == 0 {
+            return proof.is_empty();
+        }
+
+        // When the old log is a complete subtree, its root is the starting point of the proof.
+        let mut nodes = Vec::with_capacity(proof.len() + 1);
+        if old_size.is_power_of_two() {
+            nodes.push(*old_root);
+        }
+        nodes.extend_from_slice(proof);
+
+        let Some((first, rest)) = nodes.split_first() else {
+            return false;
+        };
+
+        let (mut fnode, mut snode) = (old_size - 1, new_size - 1);
+        while fnode & 1 == 1 {
+            fnode >>= 1;
+            snode >>= 1;
+        }
+
+        let (mut old_result, mut new_result) = (*first, *first);
+        for node in rest {
+            if snode == 0 {
+                return false;
+            }
+
+            if fnode & 1 == 1 || fnode == snode {
+                old_result = Self::node_hash(node, &old_result);
+                new_result = Self::node_hash(node, &new_result);
+                while fnode & 1 == 0 && fnode != 0 {
+                    fnode >>= 1;
+                    snode >>= 1;
+                }
+            } else {
+                new_result = Self::node_hash(&new_result, node);
+            }
+
+            fnode >>= 1;
+            snode >>= 1;
+        }
+
+        snode == 0 && old_result == *old_root && new_result == *new_root
+    }
+}
+
+impl LogTree {
+    fn node_hash(left: &Hash32, right: &Hash32) -> Hash32 {
+        let mut hasher = Sha256::new();
+        hasher.update([NODE_PREFIX]);
+        hasher.update(left);
+        hasher.update(right);
+        Hash32::from(<[u8; 32]>::from(hasher.finalize()))
+    }
+
+    /// Largest power of two strictly smaller than `n`, which must be at least 2.
+    fn split(n: usize) -> usize {
+        1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+    }
+
+    /// # Pre-conditions
+    /// - `leaves` is not empty.
+    fn subtree_root(leaves: &[Hash32]) -> Hash32 {
+        if leaves.len() == 1 {
+            return leaves[0];
+        }
+
+        let k = Self::split(leaves.len());
+        Self::node_hash(
+            &Self::subtree_root(&leaves[..k]),
+            &Self::subtree_root(&leaves[k..]),
+        )
+    }
+
+    fn path(index: usize, leaves: &[Hash32]) -> Vec<Hash32> {
+        if leaves.len() <= 1 {
+            return vec![];
+        }
+
+        let k = Self::split(leaves.len());
+        if index < k {
+            let mut path = Self::path(index, &leaves[..k]);
+            path.push(Self::subtree_root(&leaves[k..]));
+            path
+        } else {
+            let mut path = Self::path(index - k, &leaves[k..]);
+            path.push(Self::subtree_root(&leaves[..k]));
+            path
+        }
+    }
+
+    fn subproof(old_size: usize, leaves: &[Hash32], complete: bool) -> Vec<Hash32> {
+        if old_size == leaves.len() {
+            return if complete {
+                vec![]
+            } else {
+                vec![Self::subtree_root(leaves)]
+            };
+        }
+
+        let k = Self::split(leaves.len());
+        if old_size <= k {
+            let mut proof = Self::subproof(old_size, &leaves[..k], complete);
+            proof.push(Self::subtree_root(&leaves[k..]));
+            proof
+        } else {
+            let mut proof = Self::subproof(old_size - k, &leaves[k..], false);
+            proof.push(Self::subtree_root(&leaves[..k]));
+            proof
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Leaves of the RFC 6962 reference implementation tests.
+    fn reference_leaves() -> Vec<Hash32> {
+        let data: [&[u8]; 8] = [
+            b"",
+            b"\x00",
+            b"\x10",
+            b"\x20\x21",
+            b"\x30\x31",
+            b"\x40\x41\x42\x43",
+            b"\x50\x51\x52\x53\x54\x55\x56\x57",
+            b"\x60\x61\x62\x63\x64\x65\x66\x67\x68\x69\x6a\x6b\x6c\x6d\x6e\x6f",
+        ];
+        data.iter().map(|data| LogTree::leaf_hash(data)).collect()
+    }
+
+    #[test]
+    fn empty_log_root_is_hash_of_nothing() {
+        // Act
+        let root = LogTree::new(vec![]).root();
+
+        // Assert
+        assert_eq!(
+            root.to_hex(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn roots_match_reference_implementation() {
+        // Arrange
+        let leaves = reference_leaves();
+
+        // Act
+        let single = LogTree::new(leaves[..1].to_vec()).root();
+        let full = LogTree::new(leaves).root();
+
+        // Assert
+        assert_eq!(
+            single.to_hex(),
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d"
+        );
+        assert_eq!(
+            full.to_hex(),
+            "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328"
+        );
+    }
+
+    #[test]
+    fn inclusion_proofs_verify_for_every_leaf_and_size() {
+        // Arrange
+        let leaves = reference_leaves();
+
+        for size in 1..=leaves.len() {
+            let tree = LogTree::new(leaves[..size].to_vec());
+            let root = tree.root();
+
+            for (index, leaf) in leaves[..size].iter().enumerate() {
+                // Act
+                let proof = tree.inclusion_proof(index as u64);
+
+                // Assert
+                assert!(LogTree::verify_inclusion(
+                    leaf,
+                    index as u64,
+                    size as u64,
+                    &proof,
+                    &root
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn inclusion_verification_fails_on_wrong_leaf_index_or_proof() {
+        // Arrange
+        let leaves = reference_leaves();
+        let tree = LogTree::new(leaves.clone());
+        let proof = tree.inclusion_proof(2);
+
+        // Act & Assert
+        assert!(!LogTree::verify_inclusion(
+            &leaves[3],
+            2,
+            8,
+            &proof,
+            &tree.root()
+        ));
+        assert!(!LogTree::verify_inclusion(
+            &leaves[2],
+            3,
+            8,
+            &proof,
+            &tree.root()
+        ));
+
+        let mut tampered = proof.clone();
+        tampered[0] = leaves[0];
+        assert!(!LogTree::verify_inclusion(
+            &leaves[2],
+            2,
+            8,
+            &tampered,
+            &tree.root()
+        ));
+    }
+
+    #[test]
+    fn consistency_proofs_verify_for_every_pair_of_sizes() {
+        // Arrange
+        let leaves = reference_leaves();
+
+        for new_size in 1..=leaves.len() {
+            let new_tree = LogTree::new(leaves[..new_size].to_vec());
+
+            for old_size in 1..=new_size {
+                let old_root = LogTree::new(leaves[..old_size].to_vec()).root();
+
+                // Act
+                let proof = new_tree.consistency_proof(old_size as u64);
+
+                // Assert
+                assert!(LogTree::verify_consistency(
+                    old_size as u64,
+                    new_size as u64,
+                    &old_root,
+                    &new_tree.root(),
+                    &proof
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn consistency_verification_fails_when_history_is_rewritten() {
+        // Arrange
+        let leaves = reference_leaves();
+        let old_root = LogTree::new(leaves[..3].to_vec()).root();
+
+        let mut rewritten = leaves.clone();
+        rewritten[1] = LogTree::leaf_hash(b"swapped");
+        let new_tree = LogTree::new(rewritten);
+
+        // Act
+        let proof = new_tree.consistency_proof(3);
+
+        // Assert
+        assert!(!LogTree::verify_consistency(
+            3,
+            8,
+            &old_root,
+            &new_tree.root(),
+            &proof
+        ));
+    }
+
+    #[test]
+    fn leaf_hash_commits_to_every_field() {
+        // Arrange
+        let leaf = LogLeaf {
+            upload_id: "id".to_string(),
+            owner: "client-1".to_string(),
+            root: "00".to_string(),
+            appended_at: 1,
+        };
+        let other = LogLeaf {
+            root: "01".to_string(),
+            ..leaf.clone()
+        };
+
+        // Act & Assert
+        assert_ne!(leaf.leaf_hash(), other.leaf_hash());
+    }
+}
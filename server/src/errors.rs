@@ -1,4 +1,4 @@
-use crate::services::FileServiceError;
+use crate::{models::Quota, services::FileServiceError};
 use axum::{
     Json,
     response::{IntoResponse, Response},
@@ -42,6 +42,75 @@ impl From<FileServiceError> for ServerError {
                 StatusCode::CONFLICT,
                 Some(json!({ "error": "File already exists" })),
             ),
+            FileServiceError::UploadExpired => {
+                ServerError::new(StatusCode::GONE, Some(json!({ "error": "Upload expired" })))
+            }
+            FileServiceError::PayloadTooLarge => ServerError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Some(json!({ "error": "File exceeds the maximum upload size" })),
+            ),
+            FileServiceError::InvalidFileName => ServerError::new(
+                StatusCode::BAD_REQUEST,
+                Some(json!({ "error": "Invalid file name" })),
+            ),
+            FileServiceError::UploadSessionNotFound => ServerError::new(
+                StatusCode::NOT_FOUND,
+                Some(json!({ "error": "Upload session not found" })),
+            ),
+            FileServiceError::UploadOffsetMismatch(offset) => ServerError::new(
+                StatusCode::CONFLICT,
+                Some(json!({ "error": "Upload offset mismatch", "offset": offset })),
+            ),
+            FileServiceError::UploadLengthExceeded => ServerError::new(
+                StatusCode::BAD_REQUEST,
+                Some(json!({ "error": "Chunk exceeds the announced upload length" })),
+            ),
+            FileServiceError::UploadSessionIncomplete => ServerError::new(
+                StatusCode::CONFLICT,
+                Some(json!({ "error": "Upload session is incomplete" })),
+            ),
+            FileServiceError::BlobNotFound => ServerError::new(
+                StatusCode::NOT_FOUND,
+                Some(json!({ "error": "Blob not found" })),
+            ),
+            FileServiceError::UploadNotCompleted => ServerError::new(
+                StatusCode::CONFLICT,
+                Some(json!({ "error": "Upload is not completed" })),
+            ),
+            FileServiceError::UploadAlreadyCompleted => ServerError::new(
+                StatusCode::CONFLICT,
+                Some(json!({ "error": "Upload is completed, create a new version to change it" })),
+            ),
+            FileServiceError::UploadConflict => ServerError::new(
+                StatusCode::CONFLICT,
+                Some(json!({ "error": "Upload kept changing meanwhile, retry" })),
+            ),
+            FileServiceError::VersionNotFound => ServerError::new(
+                StatusCode::NOT_FOUND,
+                Some(json!({ "error": "Version not found" })),
+            ),
+            FileServiceError::LogEntryNotFound => ServerError::new(
+                StatusCode::NOT_FOUND,
+                Some(json!({ "error": "Upload is not in the transparency log" })),
+            ),
+            FileServiceError::InvalidLogSize => ServerError::new(
+                StatusCode::BAD_REQUEST,
+                Some(json!({ "error": "Invalid transparency log size" })),
+            ),
+            // Open uploads free up on their own once completed, the other quotas need the client
+            // to delete something first.
+            FileServiceError::QuotaExceeded(violation) => ServerError::new(
+                match violation.quota {
+                    Quota::OpenUploads => StatusCode::TOO_MANY_REQUESTS,
+                    _ => StatusCode::PAYLOAD_TOO_LARGE,
+                },
+                Some(json!({
+                    "error": "Quota exceeded",
+                    "quota": violation.quota.to_string(),
+                    "limit": violation.limit,
+                    "used": violation.used,
+                })),
+            ),
             FileServiceError::StorageError(msg) => ServerError::new(
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Some(json!({ "error": msg })),
@@ -0,0 +1,84 @@
+use crate::{
+    errors::ServerError,
+    handlers::{
+        requests::{CollectOrphansRequest, ListScrubFindingsRequest},
+        responses::{OrphanReportResponse, ScrubReportResponse},
+    },
+    infrastructure::AdminClient,
+    server::ServerState,
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use tracing::{error, instrument};
+
+#[utoipa::path(
+    get,
+    path = "/admin/scrub/findings",
+    tag = "Administration",
+    description = "List the missing or corrupted contents found by the scrubber, newest first. Only available to admin clients",
+    params(
+        ("offset" = Option<usize>, Query, description = "Number of findings to skip"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of findings to return, defaults to 100 and capped to 1000"),
+    ),
+    responses(
+        (status = 200, description = "Scrub findings", body = ScrubReportResponse),
+        (status = 403, description = "Not an admin client"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, admin, request), fields(admin = %admin.0))]
+pub async fn list_scrub_findings(
+    State(state): State<Arc<ServerState>>,
+    admin: AdminClient,
+    Query(request): Query<ListScrubFindingsRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let report = state
+        .admin_service()
+        .list_scrub_findings(request.offset, request.limit())
+        .await
+        .map_err(|e| {
+            error!("Failed to list scrub findings: {:?}", e);
+            ServerError::from(e)
+        })?;
+
+    Ok(Json(ScrubReportResponse::from(report)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/gc/orphans",
+    tag = "Administration",
+    description = "Look for objects stored under a batch of uploads that nothing refers to anymore, oldest uploads first, and delete them unless on a dry run. Only available to admin clients",
+    params(
+        ("dry_run" = Option<bool>, Query, description = "Only report orphans, without deleting them"),
+        ("cursor" = Option<String>, Query, description = "Cursor returned by the previous batch"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of uploads to check, defaults to 100 and capped to 1000"),
+    ),
+    responses(
+        (status = 200, description = "Orphans found in the batch", body = OrphanReportResponse),
+        (status = 400, description = "Invalid cursor"),
+        (status = 403, description = "Not an admin client"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, admin, request), fields(admin = %admin.0, dry_run = request.dry_run))]
+pub async fn collect_orphans(
+    State(state): State<Arc<ServerState>>,
+    admin: AdminClient,
+    Query(request): Query<CollectOrphansRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let report = state
+        .admin_service()
+        .collect_orphans(request.cursor()?, request.limit(), request.dry_run)
+        .await
+        .map_err(|e| {
+            error!("Failed to collect orphan objects: {:?}", e);
+            ServerError::from(e)
+        })?;
+
+    Ok(Json(OrphanReportResponse::new(report, request.dry_run)))
+}
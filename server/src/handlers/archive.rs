@@ -0,0 +1,70 @@
+use crate::{
+    errors::ServerError,
+    handlers::{headers::content_disposition, requests::ArchiveRequest},
+    infrastructure::AuthenticatedClient,
+    server::ServerState,
+};
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{
+        StatusCode,
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    },
+    response::Response,
+};
+use std::sync::Arc;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+#[utoipa::path(
+    get,
+    path = "/{id}/archive",
+    tag = "Get File from File Tree",
+    description = "Stream every file of a completed File Tree as a single archive. The archive starts with a `manifest.json` holding the root along with the name, index, leaf hash and proof of each file, files follow under `files/`",
+    params(
+        ("id" = Uuid, Path, description = "File Tree ID"),
+        ("version" = Option<u32>, Query, description = "Version to archive, defaults to the latest one"),
+        ("format" = Option<String>, Query, description = "`tar` or `zip`, defaults to `tar`"),
+    ),
+    responses(
+        (status = 200, description = "Archive of the File Tree", body = String, content_type = "application/octet-stream"),
+        (status = 400, description = "Invalid format"),
+        (status = 404, description = "File Tree or version not found"),
+        (status = 409, description = "Upload is not completed"),
+        (status = 410, description = "Upload expired"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client, request), fields(owner = %client.0, id = %id))]
+pub async fn get_archive(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Path(id): Path<Uuid>,
+    Query(request): Query<ArchiveRequest>,
+) -> Result<Response, ServerError> {
+    let format = request.format()?;
+
+    let contents = state
+        .file_service()
+        .get_archive(&client.0, id, request.version, format)
+        .await
+        .map_err(|e| {
+            error!("Failed to get archive: {:?}", e);
+            ServerError::from(e)
+        })?;
+
+    // The length is only known once every entry is written, so the body is chunked.
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, format.content_type())
+        .header(
+            CONTENT_DISPOSITION,
+            content_disposition(&format!("{}.{}", id, format.extension())),
+        )
+        .body(Body::from_stream(contents))
+        .map_err(|e| {
+            error!("Failed to build archive response: {:?}", e);
+            ServerError::new(StatusCode::INTERNAL_SERVER_ERROR, None)
+        })
+}
@@ -0,0 +1,63 @@
+use crate::{
+    errors::ServerError,
+    handlers::{
+        requests::ListAuditEntriesRequest,
+        responses::{AuditEntriesResponse, AuditEntryResponse},
+    },
+    infrastructure::AuthenticatedClient,
+    server::ServerState,
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use tracing::{error, instrument};
+
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "Audit Log",
+    description = "List the audit log of the authenticated API key, oldest first. Entries are chained by hash, each one covering the hash of the one before",
+    params(
+        ("upload_id" = Option<Uuid>, Query, description = "Only entries of this upload"),
+        ("action" = Option<String>, Query, description = "Only entries of this action, e.g. `POST /api/v1/{id}/upload`"),
+        ("occurred_after" = Option<String>, Query, description = "Only entries that occurred at or after this RFC 3339 timestamp"),
+        ("occurred_before" = Option<String>, Query, description = "Only entries that occurred before this RFC 3339 timestamp"),
+        ("after" = Option<u64>, Query, description = "Sequence of the last entry of the previous page"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of entries to return, defaults to 100 and capped to 1000"),
+    ),
+    responses(
+        (status = 200, description = "Audit log of the client", body = AuditEntriesResponse),
+        (status = 400, description = "Invalid filters"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client, request), fields(owner = %client.0))]
+pub async fn list_audit_entries(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Query(request): Query<ListAuditEntriesRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let limit = request.limit();
+    let entries = state
+        .file_service()
+        .list_audit_entries(&client.0, request.filter(), request.after, limit)
+        .await
+        .map_err(|e| {
+            error!("Failed to list audit entries: {:?}", e);
+            ServerError::from(e)
+        })?;
+
+    // A full page may be followed by more entries.
+    let next_after = entries
+        .last()
+        .filter(|_| entries.len() == limit)
+        .map(|entry| entry.sequence);
+
+    Ok(Json(AuditEntriesResponse {
+        entries: entries.into_iter().map(AuditEntryResponse::from).collect(),
+        next_after,
+    }))
+}
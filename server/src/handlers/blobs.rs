@@ -0,0 +1,52 @@
+use crate::{
+    errors::ServerError, handlers::requests::parse_hash, infrastructure::AuthenticatedClient,
+    server::ServerState,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use tracing::{error, instrument};
+
+#[utoipa::path(
+    head,
+    path = "/blobs/{hash}",
+    tag = "Upload File to File Tree",
+    description = "Check whether contents with the given leaf hash are already stored by \
+                   an upload of the authenticated client. If so, files with these contents \
+                   can be uploaded without a body by passing the hash to the upload endpoint",
+    params(
+        ("hash" = String, Path, description = "Hex encoded leaf hash of the contents"),
+    ),
+    responses(
+        (status = 200, description = "Contents are stored"),
+        (status = 400, description = "Invalid hash"),
+        (status = 404, description = "Contents are not stored by the client"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client), fields(owner = %client.0, hash = %hash))]
+pub async fn check_blob(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse, ServerError> {
+    let hash = parse_hash(&hash)?;
+
+    let exists = state
+        .file_service()
+        .blob_exists(&client.0, hash)
+        .await
+        .map_err(|e| {
+            error!("Failed to check blob: {:?}", e);
+            ServerError::from(e)
+        })?;
+
+    Ok(if exists {
+        StatusCode::OK
+    } else {
+        StatusCode::NOT_FOUND
+    })
+}
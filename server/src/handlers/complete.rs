@@ -1,14 +1,19 @@
 use std::sync::Arc;
 
 use axum::{
-    Json,
+    Extension, Json,
     extract::{Path, State},
     response::IntoResponse,
 };
 use tracing::{error, instrument};
 use uuid::Uuid;
 
-use crate::{errors::ServerError, handlers::responses::FinalUploadResponse, server::ServerState};
+use crate::{
+    errors::ServerError,
+    handlers::responses::FinalUploadResponse,
+    infrastructure::{AuditDetails, AuthenticatedClient},
+    server::ServerState,
+};
 
 #[utoipa::path(
     post,
@@ -24,17 +29,30 @@ use crate::{errors::ServerError, handlers::responses::FinalUploadResponse, serve
         (status = 500, description = "Internal Server Error"),
     ),
 )]
-#[instrument(skip(state), fields(id = %id))]
+#[instrument(skip(state, client), fields(owner = %client.0, id = %id))]
 pub async fn complete(
     State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, ServerError> {
-    let encoded_root_hash = state.file_service().complete(id).await.map_err(|e| {
-        error!("Failed to complete upload {}: {:?}", id, e);
-        ServerError::from(e)
-    })?;
+    let encoded_root_hash = state
+        .file_service()
+        .complete(&client.0, id)
+        .await
+        .map_err(|e| {
+            error!("Failed to complete upload {}: {:?}", id, e);
+            ServerError::from(e)
+        })?;
+
+    let details = AuditDetails {
+        hash: Some(encoded_root_hash.clone()),
+        ..Default::default()
+    };
 
-    Ok(Json(FinalUploadResponse {
-        root_hex: encoded_root_hash,
-    }))
+    Ok((
+        Extension(details),
+        Json(FinalUploadResponse {
+            root_hex: encoded_root_hash,
+        }),
+    ))
 }
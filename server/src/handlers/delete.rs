@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use reqwest::StatusCode;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+use crate::{errors::ServerError, infrastructure::AuthenticatedClient, server::ServerState};
+
+#[utoipa::path(
+    delete,
+    path = "/{id}",
+    tag = "Delete File Tree Upload",
+    description = "Delete the File Tree along with every file stored for it",
+    params(
+        ("id" = Uuid, Path, description = "File Tree ID"),
+    ),
+    responses(
+        (status = 204, description = "File Tree deleted"),
+        (status = 404, description = "File Tree not found"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client), fields(owner = %client.0, id = %id))]
+pub async fn delete(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServerError> {
+    state
+        .file_service()
+        .delete(&client.0, id)
+        .await
+        .map_err(|e| {
+            error!("Failed to delete upload {}: {:?}", id, e);
+            ServerError::from(e)
+        })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    response::{
+        IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures::StreamExt;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+use crate::{
+    errors::ServerError, handlers::responses::UploadEventResponse,
+    infrastructure::AuthenticatedClient, server::ServerState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/{id}/events",
+    tag = "Upload Events",
+    description = "Stream the events of the upload as Server-Sent Events, from now on and until it is completed, deleted or expired. Each event is named after its kind and carries it as JSON",
+    params(
+        ("id" = Uuid, Path, description = "File Tree ID"),
+    ),
+    responses(
+        (status = 200, description = "Stream of upload events", body = UploadEventResponse, content_type = "text/event-stream"),
+        (status = 404, description = "File Tree not found"),
+        (status = 410, description = "Upload expired"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client), fields(owner = %client.0, id = %id))]
+pub async fn watch_upload(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServerError> {
+    let events = state
+        .file_service()
+        .watch(&client.0, id)
+        .await
+        .map_err(|e| {
+            error!("Failed to watch upload {}: {:?}", id, e);
+            ServerError::from(e)
+        })?;
+
+    let events = events.map(|event| {
+        let event = UploadEventResponse::from(event);
+        Event::default().event(event.name()).json_data(event)
+    });
+
+    // Proxies tend to drop connections that stay silent for long, uploads can take a while
+    // between events.
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
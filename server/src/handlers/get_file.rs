@@ -1,6 +1,28 @@
-use crate::{errors::ServerError, server::ServerState};
-use axum::extract::{Path, State};
-use bytes::Bytes;
+use crate::{
+    errors::ServerError,
+    handlers::{
+        headers::{
+            RequestedRange, accepts_encoding, content_disposition, entity_tag, if_none_match,
+            requested_range, weak_entity_tag,
+        },
+        requests::VersionRequest,
+    },
+    infrastructure::AuthenticatedClient,
+    repositories::ContentEncoding,
+    server::ServerState,
+};
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{
+        HeaderMap, StatusCode,
+        header::{
+            ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE,
+            CONTENT_TYPE, ETAG, VARY,
+        },
+    },
+    response::{IntoResponse, Response},
+};
 use std::sync::Arc;
 use tracing::{error, instrument};
 use uuid::Uuid;
@@ -9,30 +31,176 @@ use uuid::Uuid;
     get,
     path = "/{id}/file/{index}",
     tag = "Get File from File Tree",
-    description = "Retrieve the raw file bytes from the specified File Tree",
+    description = "Stream the raw file bytes from the specified File Tree. Supports single `Range` requests and `If-None-Match`/`If-Range` with the leaf hash as entity tag. Compressed contents are sent as they are stored, with `Content-Encoding: zstd`, when `Accept-Encoding` lists `zstd` and no range is requested",
     params(
         ("id" = Uuid, Path, description = "File Tree ID"),
         ("index" = usize, Path, description = "File index within the File Tree"),
+        ("Range" = Option<String>, Header, description = "Single byte range, such as `bytes=0-1023`"),
+        ("If-None-Match" = Option<String>, Header, description = "Entity tags the client already has"),
+        ("If-Range" = Option<String>, Header, description = "Entity tag the `Range` applies to"),
+        ("Accept-Encoding" = Option<String>, Header, description = "Encodings the client can decode, only `zstd` is honored"),
+        ("version" = Option<u32>, Query, description = "Version to read from, defaults to the latest one"),
     ),
     responses(
-        (status = 200, description = "File Tree upload initiated", body = String, content_type = "application/octet-stream"),
-        (status = 404, description = "File Tree not found"),
+        (status = 200, description = "File contents", body = String, content_type = "application/octet-stream"),
+        (status = 206, description = "Requested range of the file contents", body = String, content_type = "application/octet-stream"),
+        (status = 304, description = "File not modified"),
+        (status = 404, description = "File Tree or version not found"),
+        (status = 416, description = "Requested range not satisfiable"),
         (status = 500, description = "Internal Server Error"),
     ),
 )]
-#[instrument(skip(state), fields(id = %id, index = %index))]
+#[instrument(skip(state, client, request, headers), fields(owner = %client.0, id = %id, index = %index))]
 pub async fn get_file(
     State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
     Path((id, index)): Path<(Uuid, usize)>,
-) -> Result<Bytes, ServerError> {
-    let contents = state
+    Query(request): Query<VersionRequest>,
+    headers: HeaderMap,
+) -> Result<Response, ServerError> {
+    file_response(&state, &client.0, id, request.version, index, &headers).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/files/{name}",
+    tag = "Get File from File Tree",
+    description = "Stream the raw file bytes of the file with the given name, which must be URL encoded. Behaves like the index based download otherwise",
+    params(
+        ("id" = Uuid, Path, description = "File Tree ID"),
+        ("name" = String, Path, description = "URL encoded file name within the File Tree"),
+        ("Range" = Option<String>, Header, description = "Single byte range, such as `bytes=0-1023`"),
+        ("If-None-Match" = Option<String>, Header, description = "Entity tags the client already has"),
+        ("If-Range" = Option<String>, Header, description = "Entity tag the `Range` applies to"),
+        ("Accept-Encoding" = Option<String>, Header, description = "Encodings the client can decode, only `zstd` is honored"),
+        ("version" = Option<u32>, Query, description = "Version to read from, defaults to the latest one"),
+    ),
+    responses(
+        (status = 200, description = "File contents", body = String, content_type = "application/octet-stream"),
+        (status = 206, description = "Requested range of the file contents", body = String, content_type = "application/octet-stream"),
+        (status = 304, description = "File not modified"),
+        (status = 404, description = "File Tree, version or file not found"),
+        (status = 416, description = "Requested range not satisfiable"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client, request, headers), fields(owner = %client.0, id = %id, name = %name))]
+pub async fn get_file_by_name(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Path((id, name)): Path<(Uuid, String)>,
+    Query(request): Query<VersionRequest>,
+    headers: HeaderMap,
+) -> Result<Response, ServerError> {
+    let index = state
         .file_service()
-        .get_file_content(id, index)
+        .get_file_index(&client.0, id, request.version, &name)
+        .await
+        .map_err(|e| {
+            error!("Failed to find file {}: {:?}", name, e);
+            ServerError::from(e)
+        })?;
+
+    file_response(&state, &client.0, id, request.version, index, &headers).await
+}
+
+async fn file_response(
+    state: &ServerState,
+    owner: &str,
+    id: Uuid,
+    version: Option<u32>,
+    index: usize,
+    headers: &HeaderMap,
+) -> Result<Response, ServerError> {
+    let file_service = state.file_service();
+
+    let descriptor = file_service
+        .get_file_descriptor(owner, id, version, index)
+        .await
+        .map_err(|e| {
+            error!("Failed to get file descriptor for file {}: {:?}", index, e);
+            ServerError::from(e)
+        })?;
+    let etag = entity_tag(&descriptor.hash);
+
+    if if_none_match(headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response());
+    }
+
+    let range = match requested_range(headers, &etag, descriptor.size) {
+        RequestedRange::Full => None,
+        RequestedRange::Partial(range) => Some(range),
+        RequestedRange::Unsatisfiable => {
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(CONTENT_RANGE, format!("bytes */{}", descriptor.size))],
+            )
+                .into_response());
+        }
+    };
+
+    if range.is_none() && accepts_encoding(headers, ContentEncoding::Zstd) {
+        let encoded = file_service
+            .get_encoded_file_content(owner, id, version, index)
+            .await
+            .map_err(|e| {
+                error!("Failed to get encoded file for file {}: {:?}", index, e);
+                ServerError::from(e)
+            })?;
+
+        if let Some(encoded) = encoded {
+            return Response::builder()
+                .status(StatusCode::OK)
+                .header(ETAG, weak_entity_tag(&descriptor.hash))
+                .header(VARY, "accept-encoding")
+                .header(ACCEPT_RANGES, "bytes")
+                .header(CONTENT_TYPE, &descriptor.content_type)
+                .header(CONTENT_DISPOSITION, content_disposition(&descriptor.name))
+                .header(CONTENT_ENCODING, encoded.encoding.as_str())
+                .header(CONTENT_LENGTH, encoded.size)
+                .body(Body::from_stream(encoded.content))
+                .map_err(|e| {
+                    error!("Failed to build response for file {}: {:?}", index, e);
+                    ServerError::new(StatusCode::INTERNAL_SERVER_ERROR, None)
+                });
+        }
+    }
+
+    let contents = file_service
+        .get_file_content(owner, id, version, index, range.clone())
         .await
         .map_err(|e| {
             error!("Failed to get file for file {}: {:?}", index, e);
             ServerError::from(e)
         })?;
 
-    Ok(Bytes::from(contents))
+    let response = Response::builder()
+        .header(ETAG, etag)
+        .header(VARY, "accept-encoding")
+        .header(ACCEPT_RANGES, "bytes")
+        .header(CONTENT_TYPE, &descriptor.content_type)
+        .header(CONTENT_DISPOSITION, content_disposition(&descriptor.name));
+
+    let response = match range {
+        Some(range) => response
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(
+                CONTENT_RANGE,
+                format!(
+                    "bytes {}-{}/{}",
+                    range.start(),
+                    range.end(),
+                    descriptor.size
+                ),
+            )
+            .header(CONTENT_LENGTH, range.end() - range.start() + 1),
+        None => response
+            .status(StatusCode::OK)
+            .header(CONTENT_LENGTH, descriptor.size),
+    };
+
+    response.body(Body::from_stream(contents)).map_err(|e| {
+        error!("Failed to build response for file {}: {:?}", index, e);
+        ServerError::new(StatusCode::INTERNAL_SERVER_ERROR, None)
+    })
 }
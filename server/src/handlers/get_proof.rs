@@ -1,7 +1,12 @@
-use crate::{errors::ServerError, handlers::responses::ProofResponse, server::ServerState};
+use crate::{
+    errors::ServerError,
+    handlers::{requests::VersionRequest, responses::ProofResponse},
+    infrastructure::AuthenticatedClient,
+    server::ServerState,
+};
 use axum::{
     Json,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     response::IntoResponse,
 };
 use std::sync::Arc;
@@ -16,21 +21,24 @@ use uuid::Uuid;
     params(
         ("id" = Uuid, Path, description = "File Tree ID"),
         ("index" = usize, Path, description = "File index within the File Tree"),
+        ("version" = Option<u32>, Query, description = "Version the proof is for, defaults to the latest one"),
     ),
     responses(
         (status = 200, description = "File Tree upload initiated", body = ProofResponse),
-        (status = 404, description = "File Tree not found"),
+        (status = 404, description = "File Tree or version not found"),
         (status = 500, description = "Internal Server Error"),
     ),
 )]
-#[instrument(skip(state), fields(id = %id, index = %index))]
+#[instrument(skip(state, client, request), fields(owner = %client.0, id = %id, index = %index))]
 pub async fn get_proof(
     State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
     Path((id, index)): Path<(Uuid, usize)>,
+    Query(request): Query<VersionRequest>,
 ) -> Result<impl IntoResponse, ServerError> {
     let proof = state
         .file_service()
-        .get_proof(id, index)
+        .get_proof(&client.0, id, request.version, index)
         .await
         .map_err(|e| {
             error!("Failed to get proof for file {}: {:?}", index, e);
@@ -39,3 +47,47 @@ pub async fn get_proof(
 
     Ok(Json(ProofResponse::from(proof)))
 }
+
+#[utoipa::path(
+    get,
+    path = "/{id}/files/{name}/proof",
+    tag = "Get Proof from File Tree",
+    description = "Retrieve the proof for the file with the given name, which must be URL encoded",
+    params(
+        ("id" = Uuid, Path, description = "File Tree ID"),
+        ("name" = String, Path, description = "URL encoded file name within the File Tree"),
+        ("version" = Option<u32>, Query, description = "Version the proof is for, defaults to the latest one"),
+    ),
+    responses(
+        (status = 200, description = "Proof of the file", body = ProofResponse),
+        (status = 404, description = "File Tree, version or file not found"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client, request), fields(owner = %client.0, id = %id, name = %name))]
+pub async fn get_proof_by_name(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Path((id, name)): Path<(Uuid, String)>,
+    Query(request): Query<VersionRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let file_service = state.file_service();
+
+    let index = file_service
+        .get_file_index(&client.0, id, request.version, &name)
+        .await
+        .map_err(|e| {
+            error!("Failed to find file {}: {:?}", name, e);
+            ServerError::from(e)
+        })?;
+
+    let proof = file_service
+        .get_proof(&client.0, id, request.version, index)
+        .await
+        .map_err(|e| {
+            error!("Failed to get proof for file {}: {:?}", name, e);
+            ServerError::from(e)
+        })?;
+
+    Ok(Json(ProofResponse::from(proof)))
+}
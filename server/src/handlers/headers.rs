@@ -0,0 +1,170 @@
+// Helpers to read and write the HTTP headers involved in downloads: entity tags, conditional
+// requests, byte ranges and content encodings. Only what download tools actually send is
+// supported, anything else falls back to a plain `200 OK` with the whole file, as allowed by
+// RFC 9110.
+use axum::http::{
+    HeaderMap, HeaderName, StatusCode,
+    header::{ACCEPT_ENCODING, IF_NONE_MATCH, IF_RANGE, RANGE},
+};
+use file_server_library::models::Hash32;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use serde_json::json;
+use std::ops::RangeInclusive;
+
+use crate::{errors::ServerError, repositories::ContentEncoding};
+
+// Resumable upload headers, named after the tus protocol.
+pub const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+pub const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+
+// `attr-char` from RFC 5987, everything else is percent encoded.
+const ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+pub fn u64_header(headers: &HeaderMap, name: &HeaderName) -> Result<Option<u64>, ServerError> {
+    let Some(value) = headers.get(name) else {
+        return Ok(None);
+    };
+
+    value
+        .to_str()
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Some)
+        .ok_or_else(|| {
+            ServerError::new(
+                StatusCode::BAD_REQUEST,
+                Some(json!({ "error": format!("Invalid {} header", name) })),
+            )
+        })
+}
+
+pub enum RequestedRange {
+    Full,
+    Partial(RangeInclusive<u64>),
+    Unsatisfiable,
+}
+
+/// Leaf hashes identify file contents, so they make natural strong entity tags.
+pub fn entity_tag(hash: &Hash32) -> String {
+    format!("\"{}\"", hash.to_hex())
+}
+
+/// Encoded responses are a different representation of the same contents, their entity tags are
+/// weakened as they are no longer byte for byte identical to the file.
+pub fn weak_entity_tag(hash: &Hash32) -> String {
+    format!("W/{}", entity_tag(hash))
+}
+
+pub fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let Some(value) = headers.get(IF_NONE_MATCH).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+
+    // `If-None-Match` uses weak comparison, so `W/` prefixes are ignored.
+    value
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+pub fn requested_range(headers: &HeaderMap, etag: &str, size: u64) -> RequestedRange {
+    let Some(range) = headers.get(RANGE).and_then(|v| v.to_str().ok()) else {
+        return RequestedRange::Full;
+    };
+
+    // A stale `If-Range` means the client's partial copy is outdated and needs the whole file.
+    // Dates are not supported, entity tags are strong and always available.
+    if let Some(if_range) = headers.get(IF_RANGE)
+        && if_range.to_str().ok() != Some(etag)
+    {
+        return RequestedRange::Full;
+    }
+
+    parse_range(range, size)
+}
+
+/// Only explicitly listed encodings are accepted, `*` is not taken as a request for compressed
+/// bytes.
+pub fn accepts_encoding(headers: &HeaderMap, encoding: ContentEncoding) -> bool {
+    headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|item| {
+            let mut params = item.split(';').map(str::trim);
+            let coding = params.next().unwrap_or_default();
+            let quality = params
+                .find_map(|param| param.strip_prefix("q="))
+                .map(|q| q.parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+
+            coding.eq_ignore_ascii_case(encoding.as_str()) && quality > 0.0
+        })
+}
+
+// Only single ranges are honored, multipart/byteranges responses are not worth the complexity
+// for resumable downloads.
+fn parse_range(range: &str, size: u64) -> RequestedRange {
+    let Some(spec) = range.trim().strip_prefix("bytes=") else {
+        return RequestedRange::Full;
+    };
+    if spec.contains(',') {
+        return RequestedRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RequestedRange::Full;
+    };
+
+    let bounds = match (start.trim(), end.trim()) {
+        ("", suffix) => suffix
+            .parse::<u64>()
+            .ok()
+            .filter(|suffix| *suffix > 0)
+            .map(|suffix| (size.saturating_sub(suffix), size.saturating_sub(1))),
+        (start, "") => start
+            .parse::<u64>()
+            .ok()
+            .map(|start| (start, size.saturating_sub(1))),
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => Some((start, end.min(size.saturating_sub(1)))),
+            _ => return RequestedRange::Full,
+        },
+    };
+
+    match bounds {
+        Some((start, end)) if size > 0 && start < size => RequestedRange::Partial(start..=end),
+        Some(_) => RequestedRange::Unsatisfiable,
+        None => RequestedRange::Full,
+    }
+}
+
+/// Plain `filename` is kept for old clients, replacing whatever cannot be safely quoted, while
+/// `filename*` carries the exact UTF-8 name.
+pub fn content_disposition(name: &str) -> String {
+    let fallback: String = name
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback,
+        utf8_percent_encode(name, ATTR_CHAR)
+    )
+}
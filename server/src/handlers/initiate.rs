@@ -1,7 +1,10 @@
 use crate::{
-    errors::ServerError, handlers::responses::InitiateUploadResponse, server::ServerState,
+    errors::ServerError,
+    handlers::responses::InitiateUploadResponse,
+    infrastructure::{AuditDetails, AuthenticatedClient},
+    server::ServerState,
 };
-use axum::{Json, extract::State, response::IntoResponse};
+use axum::{Extension, Json, extract::State, response::IntoResponse};
 use reqwest::StatusCode;
 use std::sync::Arc;
 use tracing::{error, instrument};
@@ -13,17 +16,32 @@ use tracing::{error, instrument};
     tag = "Initiate File Tree Upload",
     responses(
         (status = 201, description = "File Tree upload initiated", body = InitiateUploadResponse),
+        (status = 429, description = "Too many open uploads"),
         (status = 500, description = "Internal Server Error"),
     ),
 )]
-#[instrument(skip(state))]
+#[instrument(skip(state, client), fields(owner = %client.0))]
 pub async fn initiate(
     State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
 ) -> Result<impl IntoResponse, ServerError> {
-    let id = state.file_service().initiate().await.map_err(|e| {
-        error!("Failed to initiate upload: {:?}", e);
-        ServerError::from(e)
-    })?;
+    let id = state
+        .file_service()
+        .initiate(&client.0)
+        .await
+        .map_err(|e| {
+            error!("Failed to initiate upload: {:?}", e);
+            ServerError::from(e)
+        })?;
 
-    Ok((StatusCode::CREATED, Json(InitiateUploadResponse { id })))
+    let details = AuditDetails {
+        upload_id: Some(id),
+        ..Default::default()
+    };
+
+    Ok((
+        StatusCode::CREATED,
+        Extension(details),
+        Json(InitiateUploadResponse { id }),
+    ))
 }
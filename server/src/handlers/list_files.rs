@@ -0,0 +1,57 @@
+use crate::{
+    errors::ServerError,
+    handlers::{requests::ListFilesRequest, responses::FileListResponse},
+    infrastructure::AuthenticatedClient,
+    server::ServerState,
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+#[utoipa::path(
+    get,
+    path = "/{id}/files",
+    tag = "Get File from File Tree",
+    description = "List the files of the specified File Tree in index order",
+    params(
+        ("id" = Uuid, Path, description = "File Tree ID"),
+        ("version" = Option<u32>, Query, description = "Version to list, defaults to the latest one"),
+        ("offset" = Option<usize>, Query, description = "Number of files to skip, defaults to 0"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of files to return, defaults to 100 and capped to 1000"),
+    ),
+    responses(
+        (status = 200, description = "Files of the File Tree", body = FileListResponse),
+        (status = 404, description = "File Tree or version not found"),
+        (status = 410, description = "Upload expired"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client, pagination), fields(owner = %client.0, id = %id))]
+pub async fn list_files(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Path(id): Path<Uuid>,
+    Query(pagination): Query<ListFilesRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let page = state
+        .file_service()
+        .list_files(
+            &client.0,
+            id,
+            pagination.version,
+            pagination.offset,
+            pagination.limit(),
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to list files: {:?}", e);
+            ServerError::from(e)
+        })?;
+
+    Ok(Json(FileListResponse::new(page, pagination.offset)))
+}
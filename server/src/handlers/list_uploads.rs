@@ -0,0 +1,54 @@
+use crate::{
+    errors::ServerError,
+    handlers::{requests::ListUploadsRequest, responses::UploadListResponse},
+    infrastructure::AuthenticatedClient,
+    server::ServerState,
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use tracing::{error, instrument};
+
+#[utoipa::path(
+    get,
+    path = "/uploads",
+    tag = "List File Tree Uploads",
+    description = "List the uploads initiated with the authenticated API key, newest first",
+    params(
+        ("state" = Option<String>, Query, description = "Only uploads in this state: initiated, completed or expired"),
+        ("created_after" = Option<String>, Query, description = "Only uploads created at or after this RFC 3339 timestamp"),
+        ("created_before" = Option<String>, Query, description = "Only uploads created before this RFC 3339 timestamp"),
+        ("cursor" = Option<String>, Query, description = "Cursor returned by the previous page"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of uploads to return, defaults to 100 and capped to 1000"),
+    ),
+    responses(
+        (status = 200, description = "Uploads of the client", body = UploadListResponse),
+        (status = 400, description = "Invalid filter or cursor"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client, request), fields(owner = %client.0))]
+pub async fn list_uploads(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Query(request): Query<ListUploadsRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let page = state
+        .file_service()
+        .list_uploads(
+            &client.0,
+            request.filter()?,
+            request.cursor()?,
+            request.limit(),
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to list uploads: {:?}", e);
+            ServerError::from(e)
+        })?;
+
+    Ok(Json(UploadListResponse::from(page)))
+}
@@ -6,21 +6,60 @@ use axum::Router;
 use std::sync::Arc;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
+mod admin;
+mod archive;
+mod audit;
+mod blobs;
 mod complete;
+mod delete;
+mod events;
 mod get_file;
 mod get_proof;
+mod headers;
 mod initiate;
+mod list_files;
+mod list_uploads;
 pub mod requests;
 pub mod responses;
+mod sessions;
+mod transparency_log;
 mod upload;
+mod usage;
+mod versions;
+mod webhooks;
+
+const API_PREFIX: &str = "/api/v1";
 
 fn router(state: Arc<ServerState>) -> OpenApiRouter {
     OpenApiRouter::new()
         .routes(routes!(upload::upload))
         .routes(routes!(complete::complete))
+        .routes(routes!(versions::create_version))
         .routes(routes!(initiate::initiate,))
         .routes(routes!(get_proof::get_proof))
+        .routes(routes!(get_proof::get_proof_by_name))
         .routes(routes!(get_file::get_file))
+        .routes(routes!(get_file::get_file_by_name))
+        .routes(routes!(list_files::list_files))
+        .routes(routes!(archive::get_archive))
+        .routes(routes!(list_uploads::list_uploads))
+        .routes(routes!(usage::get_usage))
+        .routes(routes!(webhooks::list_webhook_deliveries))
+        .routes(routes!(audit::list_audit_entries))
+        .routes(routes!(delete::delete))
+        .routes(routes!(events::watch_upload))
+        .routes(routes!(sessions::create_session))
+        .routes(routes!(
+            sessions::get_session_offset,
+            sessions::append_chunk
+        ))
+        .routes(routes!(sessions::finalize_session))
+        .routes(routes!(blobs::check_blob))
+        .routes(routes!(admin::list_scrub_findings))
+        .routes(routes!(admin::collect_orphans))
+        .routes(routes!(transparency_log::get_log_head))
+        .routes(routes!(transparency_log::get_log_proof))
+        .routes(routes!(transparency_log::get_log_consistency))
         .with_state(state)
 }
 
@@ -31,13 +70,13 @@ pub trait RouteExtensions {
 impl RouteExtensions for OpenApiRouter {
     fn with_routes(self, state: Arc<ServerState>) -> Self {
         let routes = router(state);
-        self.nest("/api/v1", routes)
+        self.nest(API_PREFIX, routes)
     }
 }
 
 impl RouteExtensions for Router {
     fn with_routes(self, state: Arc<ServerState>) -> Self {
         let routes = router(state).split_for_parts().0;
-        self.nest("/api/v1", routes)
+        self.nest(API_PREFIX, routes)
     }
 }
@@ -1,8 +1,16 @@
 // This file is part of the template, requests structs are defined here.
 
-use crate::models::FileMetadata;
+use crate::{
+    errors::ServerError,
+    models::{ArchiveFormat, AuditFilter, FileMetadata, UploadCursor, UploadFilter},
+};
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use file_server_library::models::Hash32;
 use serde::Deserialize;
+use serde_json::json;
 use utoipa::ToSchema;
+use uuid::Uuid;
 
 #[derive(Clone, Deserialize, ToSchema)]
 pub struct UploadMetadataRequest {
@@ -24,6 +32,247 @@ impl From<UploadMetadataRequest> for FileMetadata {
         FileMetadata {
             name: val.name,
             index: val.index,
+            content_type: None,
         }
     }
 }
+
+/// Uploads passing the leaf hash of contents the server already stores skip the body.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct UploadContentRequest {
+    pub hash: Option<String>,
+}
+
+impl UploadContentRequest {
+    pub fn hash(&self) -> Result<Option<Hash32>, ServerError> {
+        self.hash.as_deref().map(parse_hash).transpose()
+    }
+}
+
+pub(crate) fn parse_hash(hash: &str) -> Result<Hash32, ServerError> {
+    Hash32::from_hex(hash).map_err(|e| {
+        ServerError::new(
+            StatusCode::BAD_REQUEST,
+            Some(json!({ "error": format!("Invalid hash: {e}") })),
+        )
+    })
+}
+
+/// Version of the upload to read from, the latest one when missing.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct VersionRequest {
+    pub version: Option<u32>,
+}
+
+/// Pagination of file listings, `limit` is capped to keep responses small.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct ListFilesRequest {
+    pub version: Option<u32>,
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default = "ListFilesRequest::default_limit")]
+    pub limit: usize,
+}
+
+impl ListFilesRequest {
+    pub const MAX_LIMIT: usize = 1000;
+
+    pub fn limit(&self) -> usize {
+        self.limit.clamp(1, Self::MAX_LIMIT)
+    }
+
+    fn default_limit() -> usize {
+        100
+    }
+}
+
+/// Filters and pagination of upload listings. `cursor` is the `next_cursor` of the previous
+/// page.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct ListUploadsRequest {
+    pub state: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub cursor: Option<String>,
+    #[serde(default = "ListUploadsRequest::default_limit")]
+    pub limit: usize,
+}
+
+impl ListUploadsRequest {
+    pub const MAX_LIMIT: usize = 1000;
+
+    pub fn filter(&self) -> Result<UploadFilter, ServerError> {
+        let state = self
+            .state
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(|e| bad_request(format!("Invalid state: {e}")))?;
+
+        Ok(UploadFilter {
+            state,
+            created_after: self.created_after,
+            created_before: self.created_before,
+        })
+    }
+
+    pub fn cursor(&self) -> Result<Option<UploadCursor>, ServerError> {
+        self.cursor
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(|e| bad_request(format!("Invalid cursor: {e}")))
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.clamp(1, Self::MAX_LIMIT)
+    }
+
+    fn default_limit() -> usize {
+        100
+    }
+}
+
+/// Pagination of scrub findings, newest first.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct ListScrubFindingsRequest {
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default = "ListScrubFindingsRequest::default_limit")]
+    pub limit: usize,
+}
+
+impl ListScrubFindingsRequest {
+    pub const MAX_LIMIT: usize = 1000;
+
+    pub fn limit(&self) -> usize {
+        self.limit.clamp(1, Self::MAX_LIMIT)
+    }
+
+    fn default_limit() -> usize {
+        100
+    }
+}
+
+/// Pagination of webhook deliveries, newest first.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct ListWebhookDeliveriesRequest {
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default = "ListWebhookDeliveriesRequest::default_limit")]
+    pub limit: usize,
+}
+
+impl ListWebhookDeliveriesRequest {
+    pub const MAX_LIMIT: usize = 1000;
+
+    pub fn limit(&self) -> usize {
+        self.limit.clamp(1, Self::MAX_LIMIT)
+    }
+
+    fn default_limit() -> usize {
+        100
+    }
+}
+
+/// Filters and pagination of the audit log, oldest first. `after` is the sequence of the last
+/// entry of the previous page.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct ListAuditEntriesRequest {
+    pub upload_id: Option<Uuid>,
+    pub action: Option<String>,
+    pub occurred_after: Option<DateTime<Utc>>,
+    pub occurred_before: Option<DateTime<Utc>>,
+    pub after: Option<u64>,
+    #[serde(default = "ListAuditEntriesRequest::default_limit")]
+    pub limit: usize,
+}
+
+impl ListAuditEntriesRequest {
+    pub const MAX_LIMIT: usize = 1000;
+
+    pub fn filter(&self) -> AuditFilter {
+        AuditFilter {
+            upload_id: self.upload_id,
+            action: self.action.clone(),
+            occurred_after: self.occurred_after,
+            occurred_before: self.occurred_before,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.clamp(1, Self::MAX_LIMIT)
+    }
+
+    fn default_limit() -> usize {
+        100
+    }
+}
+
+/// Batch of uploads to look for orphan objects under, oldest first. Orphans are deleted unless
+/// `dry_run` is set.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct CollectOrphansRequest {
+    #[serde(default)]
+    pub dry_run: bool,
+    pub cursor: Option<String>,
+    #[serde(default = "CollectOrphansRequest::default_limit")]
+    pub limit: usize,
+}
+
+impl CollectOrphansRequest {
+    pub const MAX_LIMIT: usize = 1000;
+
+    pub fn cursor(&self) -> Result<Option<UploadCursor>, ServerError> {
+        self.cursor
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(|e| bad_request(format!("Invalid cursor: {e}")))
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.clamp(1, Self::MAX_LIMIT)
+    }
+
+    fn default_limit() -> usize {
+        100
+    }
+}
+
+/// Size of the transparency log the inclusion proof is built against, the current one when
+/// missing.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct LogProofRequest {
+    pub size: Option<u64>,
+}
+
+/// Sizes of the two transparency log heads to prove consistent, `second` defaults to the
+/// current size.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct LogConsistencyRequest {
+    pub first: u64,
+    pub second: Option<u64>,
+}
+
+/// Archives are tar unless asked otherwise.
+#[derive(Clone, Deserialize, ToSchema)]
+pub struct ArchiveRequest {
+    pub version: Option<u32>,
+    pub format: Option<String>,
+}
+
+impl ArchiveRequest {
+    pub fn format(&self) -> Result<ArchiveFormat, ServerError> {
+        self.format
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map(Option::unwrap_or_default)
+            .map_err(|e| bad_request(format!("Invalid format: {e}")))
+    }
+}
+
+fn bad_request(error: String) -> ServerError {
+    ServerError::new(StatusCode::BAD_REQUEST, Some(json!({ "error": error })))
+}
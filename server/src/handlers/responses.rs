@@ -1,5 +1,14 @@
 // This file is part of the template, response structs are defined here.
 
+use crate::{
+    models::{
+        FileEntry, FilePage, LogConsistencyProof, LogHead, LogInclusionProof, OrphanObject,
+        OrphanReport, QuotaLimits, ScrubReport, UploadEvent, UploadPage, UploadSession,
+        UploadSummary, Usage,
+    },
+    repositories::{AuditEntryRow, LogEntryRow, ScrubFindingRow, WebhookDeliveryRow},
+};
+use chrono::{DateTime, Utc};
 use file_server_library::models::{Proof, ProofStep};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
@@ -12,11 +21,409 @@ pub struct FileMetadataResponse {
     pub encoded_hash: String,
 }
 
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct FileEntryResponse {
+    pub index: usize,
+    pub name: String,
+    pub size: u64,
+    pub leaf_hash: String,
+    pub content_type: String,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+impl From<FileEntry> for FileEntryResponse {
+    fn from(entry: FileEntry) -> Self {
+        Self {
+            index: entry.index,
+            name: entry.name,
+            size: entry.size,
+            leaf_hash: entry.hash.to_hex(),
+            content_type: entry.content_type,
+            uploaded_at: entry.uploaded_at,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct FileListResponse {
+    pub files: Vec<FileEntryResponse>,
+    pub total: usize,
+    /// Offset of the next page, missing on the last one.
+    pub next_offset: Option<usize>,
+}
+
+impl FileListResponse {
+    pub fn new(page: FilePage, offset: usize) -> Self {
+        let end = offset + page.files.len();
+
+        Self {
+            next_offset: (end < page.total).then_some(end),
+            files: page
+                .files
+                .into_iter()
+                .map(FileEntryResponse::from)
+                .collect(),
+            total: page.total,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct UploadSummaryResponse {
+    pub id: Uuid,
+    pub version: u32,
+    /// Version this one was derived from, missing on the first one.
+    pub previous_version: Option<u32>,
+    pub state: String,
+    /// Only present once the upload is completed.
+    pub root_hex: Option<String>,
+    pub file_count: usize,
+    pub total_size: u64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<UploadSummary> for UploadSummaryResponse {
+    fn from(summary: UploadSummary) -> Self {
+        Self {
+            id: summary.id,
+            version: summary.version,
+            previous_version: summary.previous_version,
+            state: summary.state.to_string(),
+            root_hex: summary.root.map(|root| root.to_hex()),
+            file_count: summary.file_count,
+            total_size: summary.total_size,
+            created_at: summary.created_at,
+            updated_at: summary.updated_at,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct UploadListResponse {
+    pub uploads: Vec<UploadSummaryResponse>,
+    /// Cursor of the next page, missing on the last one.
+    pub next_cursor: Option<String>,
+}
+
+impl From<UploadPage> for UploadListResponse {
+    fn from(page: UploadPage) -> Self {
+        Self {
+            uploads: page
+                .uploads
+                .into_iter()
+                .map(UploadSummaryResponse::from)
+                .collect(),
+            next_cursor: page.next_cursor.map(|cursor| cursor.to_string()),
+        }
+    }
+}
+
+/// Limits of the client, missing ones are unlimited.
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct QuotaLimitsResponse {
+    pub max_stored_bytes: Option<u64>,
+    pub max_files_per_upload: Option<u64>,
+    pub max_file_size_bytes: Option<u64>,
+    pub max_open_uploads: Option<u64>,
+}
+
+impl From<QuotaLimits> for QuotaLimitsResponse {
+    fn from(limits: QuotaLimits) -> Self {
+        Self {
+            max_stored_bytes: limits.max_stored_bytes,
+            max_files_per_upload: limits.max_files_per_upload,
+            max_file_size_bytes: limits.max_file_size_bytes,
+            max_open_uploads: limits.max_open_uploads,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct UsageResponse {
+    /// Size of the files of every upload of the client.
+    pub stored_bytes: u64,
+    /// Uploads, or versions of them, that are not completed yet.
+    pub open_uploads: u64,
+    pub limits: QuotaLimitsResponse,
+}
+
+impl From<Usage> for UsageResponse {
+    fn from(usage: Usage) -> Self {
+        Self {
+            stored_bytes: usage.stored_bytes,
+            open_uploads: usage.open_uploads,
+            limits: usage.limits.into(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScrubFindingResponse {
+    pub upload_id: Uuid,
+    pub index: usize,
+    pub name: String,
+    /// Either missing or corrupted.
+    pub kind: String,
+    pub expected_hash: String,
+    /// What the stored contents hash to, only present when corrupted.
+    pub actual_hash: Option<String>,
+    pub detected_at: DateTime<Utc>,
+}
+
+impl From<ScrubFindingRow> for ScrubFindingResponse {
+    fn from(row: ScrubFindingRow) -> Self {
+        Self {
+            upload_id: row.upload_id,
+            index: row.index,
+            name: row.name,
+            kind: row.kind.to_string(),
+            expected_hash: row.expected_hash.to_hex(),
+            actual_hash: row.actual_hash.map(|hash| hash.to_hex()),
+            detected_at: row.detected_at,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScrubReportResponse {
+    pub findings: Vec<ScrubFindingResponse>,
+    /// Last upload checked by the current pass, missing between passes.
+    pub cursor: Option<String>,
+}
+
+impl From<ScrubReport> for ScrubReportResponse {
+    fn from(report: ScrubReport) -> Self {
+        Self {
+            findings: report
+                .findings
+                .into_iter()
+                .map(ScrubFindingResponse::from)
+                .collect(),
+            cursor: report.cursor.map(|cursor| cursor.to_string()),
+        }
+    }
+}
+
+/// Event of an upload, tagged with its kind under `event`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum UploadEventResponse {
+    FileReceived {
+        index: usize,
+        name: String,
+        size: u64,
+    },
+    HashComputed {
+        index: usize,
+        name: String,
+        leaf_hash: String,
+    },
+    Completed {
+        version: u32,
+        root: String,
+    },
+    Deleted,
+    Expired,
+}
+
+impl UploadEventResponse {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UploadEventResponse::FileReceived { .. } => "file_received",
+            UploadEventResponse::HashComputed { .. } => "hash_computed",
+            UploadEventResponse::Completed { .. } => "completed",
+            UploadEventResponse::Deleted => "deleted",
+            UploadEventResponse::Expired => "expired",
+        }
+    }
+}
+
+impl From<UploadEvent> for UploadEventResponse {
+    fn from(event: UploadEvent) -> Self {
+        match event {
+            UploadEvent::FileReceived { index, name, size } => {
+                UploadEventResponse::FileReceived { index, name, size }
+            }
+            UploadEvent::HashComputed { index, name, hash } => UploadEventResponse::HashComputed {
+                index,
+                name,
+                leaf_hash: hash.to_hex(),
+            },
+            UploadEvent::Completed { version, root } => UploadEventResponse::Completed {
+                version,
+                root: root.to_hex(),
+            },
+            UploadEvent::Deleted => UploadEventResponse::Deleted,
+            UploadEvent::Expired => UploadEventResponse::Expired,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDeliveryResponse {
+    pub id: Uuid,
+    pub event: String,
+    pub upload_id: Uuid,
+    pub url: String,
+    /// Body sent on every attempt.
+    pub payload: String,
+    /// Either pending, delivered or failed.
+    pub state: String,
+    pub attempts: u32,
+    /// When the delivery is attempted next, only meaningful while pending.
+    pub next_attempt_at: DateTime<Utc>,
+    /// Status the endpoint answered the last attempt with, missing when it could not be reached.
+    pub last_status: Option<u16>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<WebhookDeliveryRow> for WebhookDeliveryResponse {
+    fn from(row: WebhookDeliveryRow) -> Self {
+        Self {
+            id: row.id,
+            event: row.event.to_string(),
+            upload_id: row.upload_id,
+            url: row.url,
+            payload: row.payload,
+            state: row.state.to_string(),
+            attempts: row.attempts,
+            next_attempt_at: row.next_attempt_at,
+            last_status: row.last_status,
+            last_error: row.last_error,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDeliveriesResponse {
+    pub deliveries: Vec<WebhookDeliveryResponse>,
+}
+
+/// An entry of the audit log. `entry_hash` covers every other field along with `previous_hash`,
+/// the `entry_hash` of the entry before, all zeros for the first one.
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntryResponse {
+    pub owner: String,
+    pub sequence: u64,
+    /// Method and route of the request, e.g. `POST /api/v1/{id}/upload`.
+    pub action: String,
+    pub upload_id: Option<Uuid>,
+    pub index: Option<u64>,
+    pub name: Option<String>,
+    /// Leaf hash of the file, or root of the upload once completed, in hex.
+    pub hash: Option<String>,
+    /// Status the request was answered with.
+    pub status: u16,
+    pub request_id: Option<String>,
+    pub correlation_id: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+    pub previous_hash: String,
+    pub entry_hash: String,
+}
+
+impl From<AuditEntryRow> for AuditEntryResponse {
+    fn from(row: AuditEntryRow) -> Self {
+        Self {
+            owner: row.owner,
+            sequence: row.sequence,
+            action: row.action,
+            upload_id: row.upload_id,
+            index: row.index,
+            name: row.name,
+            hash: row.hash,
+            status: row.status,
+            request_id: row.request_id,
+            correlation_id: row.correlation_id,
+            occurred_at: row.occurred_at,
+            previous_hash: row.previous_hash.to_hex(),
+            entry_hash: row.entry_hash.to_hex(),
+        }
+    }
+}
+
+/// Page of the audit log, `next_after` is missing on the last one.
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntriesResponse {
+    pub entries: Vec<AuditEntryResponse>,
+    pub next_after: Option<u64>,
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct OrphanObjectResponse {
+    pub upload_id: Uuid,
+    pub name: String,
+    pub size: u64,
+    pub last_modified: DateTime<Utc>,
+}
+
+impl From<OrphanObject> for OrphanObjectResponse {
+    fn from(orphan: OrphanObject) -> Self {
+        Self {
+            upload_id: orphan.upload_id,
+            name: orphan.name,
+            size: orphan.size,
+            last_modified: orphan.last_modified,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct OrphanReportResponse {
+    pub dry_run: bool,
+    pub uploads_checked: usize,
+    pub orphans: Vec<OrphanObjectResponse>,
+    /// Orphans deleted, always 0 on dry runs.
+    pub deleted: usize,
+    /// Orphans that failed to be deleted, they are found again on the next run.
+    pub failed: usize,
+    /// Cursor of the next batch, missing once every upload was checked.
+    pub next_cursor: Option<String>,
+}
+
+impl OrphanReportResponse {
+    pub fn new(report: OrphanReport, dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            uploads_checked: report.uploads_checked,
+            orphans: report
+                .orphans
+                .into_iter()
+                .map(OrphanObjectResponse::from)
+                .collect(),
+            deleted: report.deleted,
+            failed: report.failed,
+            next_cursor: report.next_cursor.map(|cursor| cursor.to_string()),
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, ToSchema)]
 pub struct InitiateUploadResponse {
     pub id: Uuid,
 }
 
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct UploadSessionResponse {
+    pub session_id: Uuid,
+    pub offset: u64,
+    pub length: Option<u64>,
+}
+
+impl From<&UploadSession> for UploadSessionResponse {
+    fn from(session: &UploadSession) -> Self {
+        Self {
+            session_id: session.id(),
+            offset: session.offset(),
+            length: session.length(),
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, ToSchema)]
 pub struct FinalUploadResponse {
     pub root_hex: String,
@@ -55,3 +462,84 @@ impl From<ProofStep> for ProofStepResponse {
         }
     }
 }
+
+/// Timestamps are in milliseconds since the epoch, exactly as they were signed or hashed.
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct LogHeadResponse {
+    pub size: u64,
+    pub root: String,
+    pub timestamp: i64,
+    pub key_id: String,
+    pub signature: String,
+}
+
+impl From<LogHead> for LogHeadResponse {
+    fn from(head: LogHead) -> Self {
+        Self {
+            size: head.size,
+            root: head.root.to_hex(),
+            timestamp: head.timestamp.timestamp_millis(),
+            key_id: head.key_id,
+            signature: head.signature,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct LogEntryResponse {
+    pub index: u64,
+    pub upload_id: Uuid,
+    pub owner: String,
+    pub root: String,
+    pub appended_at: i64,
+    pub leaf_hash: String,
+}
+
+impl From<LogEntryRow> for LogEntryResponse {
+    fn from(row: LogEntryRow) -> Self {
+        Self {
+            index: row.index,
+            upload_id: row.upload_id,
+            owner: row.owner,
+            root: row.root.to_hex(),
+            appended_at: row.appended_at.timestamp_millis(),
+            leaf_hash: row.leaf_hash.to_hex(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct LogInclusionProofResponse {
+    pub head: LogHeadResponse,
+    pub entry: LogEntryResponse,
+    /// Audit path from the leaf up to the root, as in RFC 9162.
+    pub proof: Vec<String>,
+}
+
+impl From<LogInclusionProof> for LogInclusionProofResponse {
+    fn from(proof: LogInclusionProof) -> Self {
+        Self {
+            head: LogHeadResponse::from(proof.head),
+            entry: LogEntryResponse::from(proof.entry),
+            proof: proof.proof.into_iter().map(|hash| hash.to_hex()).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct LogConsistencyProofResponse {
+    pub first: LogHeadResponse,
+    pub second: LogHeadResponse,
+    /// Consistency proof between both heads, as in RFC 9162.
+    pub proof: Vec<String>,
+}
+
+impl From<LogConsistencyProof> for LogConsistencyProofResponse {
+    fn from(proof: LogConsistencyProof) -> Self {
+        Self {
+            first: LogHeadResponse::from(proof.first),
+            second: LogHeadResponse::from(proof.second),
+            proof: proof.proof.into_iter().map(|hash| hash.to_hex()).collect(),
+        }
+    }
+}
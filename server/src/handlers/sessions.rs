@@ -0,0 +1,246 @@
+use crate::{
+    errors::ServerError,
+    handlers::{
+        API_PREFIX,
+        headers::{UPLOAD_LENGTH, UPLOAD_OFFSET, u64_header},
+        requests::UploadMetadataRequest,
+        responses::{FileMetadataResponse, UploadSessionResponse},
+    },
+    infrastructure::{AuditDetails, AuthenticatedClient, into_file_stream},
+    models::{FileMetadata, UploadSession},
+    server::ServerState,
+};
+use axum::{
+    Extension, Json,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CACHE_CONTROL, CONTENT_TYPE, LOCATION},
+    },
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+#[utoipa::path(
+    post,
+    path = "/{id}/sessions",
+    tag = "Resumable File Upload",
+    description = "Open a resumable upload session for a single file of the File Tree",
+    params(
+        ("id" = Uuid, Path, description = "File Tree ID"),
+        ("name" = String, Query, description = "Name of the file to upload"),
+        ("index" = usize, Query, description = "Index of the file within the File Tree"),
+        ("Upload-Length" = Option<u64>, Header, description = "Total size of the file, when known upfront"),
+    ),
+    responses(
+        (status = 201, description = "Upload session created", body = UploadSessionResponse),
+        (status = 400, description = "Invalid file name or Upload-Length"),
+        (status = 404, description = "File Tree not found"),
+        (status = 409, description = "Another file of the File Tree has the same name"),
+        (status = 410, description = "Upload expired"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client, metadata, headers), fields(owner = %client.0, id = %id))]
+pub async fn create_session(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Query(metadata): Query<UploadMetadataRequest>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ServerError> {
+    let length = u64_header(&headers, &UPLOAD_LENGTH)?;
+
+    let mut file_metadata: FileMetadata = metadata.clone().into();
+    file_metadata.content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+
+    let session = state
+        .upload_session_service()
+        .create(&client.0, id, file_metadata, length)
+        .await
+        .map_err(|e| {
+            error!(
+                "Failed to create upload session for file with index {} and name {}: {:?}",
+                metadata.index, metadata.name, e
+            );
+            ServerError::from(e)
+        })?;
+
+    let location = format!("{}/{}/sessions/{}", API_PREFIX, id, session.id());
+
+    Ok((
+        StatusCode::CREATED,
+        [(LOCATION, location)],
+        Json(UploadSessionResponse::from(&session)),
+    ))
+}
+
+#[utoipa::path(
+    head,
+    path = "/{id}/sessions/{session_id}",
+    tag = "Resumable File Upload",
+    description = "Query the offset committed so far, which is where the next chunk must start",
+    params(
+        ("id" = Uuid, Path, description = "File Tree ID"),
+        ("session_id" = Uuid, Path, description = "Upload session ID"),
+    ),
+    responses(
+        (status = 200, description = "Committed offset in the Upload-Offset header"),
+        (status = 404, description = "Upload session not found"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client), fields(owner = %client.0, id = %id, session_id = %session_id))]
+pub async fn get_session_offset(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Path((id, session_id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, ServerError> {
+    let session = state
+        .upload_session_service()
+        .get(&client.0, id, session_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get upload session: {:?}", e);
+            ServerError::from(e)
+        })?;
+
+    let mut response = offset_response(StatusCode::OK, &session);
+    if let Some(length) = session.length() {
+        response
+            .headers_mut()
+            .insert(UPLOAD_LENGTH, HeaderValue::from(length));
+    }
+
+    Ok(response)
+}
+
+#[utoipa::path(
+    patch,
+    path = "/{id}/sessions/{session_id}",
+    tag = "Resumable File Upload",
+    description = "Append a chunk at the offset given by Upload-Offset, which must match the committed offset",
+    params(
+        ("id" = Uuid, Path, description = "File Tree ID"),
+        ("session_id" = Uuid, Path, description = "Upload session ID"),
+        ("Upload-Offset" = u64, Header, description = "Offset the chunk starts at"),
+    ),
+    request_body(
+        content = inline(String),
+        content_type = "application/offset+octet-stream",
+        description = "Raw chunk bytes"
+    ),
+    responses(
+        (status = 204, description = "Chunk committed, new offset in the Upload-Offset header"),
+        (status = 400, description = "Missing Upload-Offset or chunk exceeds Upload-Length"),
+        (status = 404, description = "Upload session not found"),
+        (status = 409, description = "Upload-Offset does not match the committed offset"),
+        (status = 410, description = "Upload expired"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client, headers, body), fields(owner = %client.0, id = %id, session_id = %session_id))]
+pub async fn append_chunk(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Path((id, session_id)): Path<(Uuid, Uuid)>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, ServerError> {
+    let offset = u64_header(&headers, &UPLOAD_OFFSET)?.ok_or_else(|| {
+        ServerError::new(
+            StatusCode::BAD_REQUEST,
+            Some(json!({ "error": "Missing upload-offset header" })),
+        )
+    })?;
+
+    let session = state
+        .upload_session_service()
+        .append(&client.0, id, session_id, offset, into_file_stream(body))
+        .await
+        .map_err(|e| {
+            error!("Failed to append chunk at offset {}: {:?}", offset, e);
+            ServerError::from(e)
+        })?;
+
+    Ok(offset_response(StatusCode::NO_CONTENT, &session))
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/sessions/{session_id}/finalize",
+    tag = "Resumable File Upload",
+    description = "Assemble the chunks into the file and add its leaf to the File Tree",
+    params(
+        ("id" = Uuid, Path, description = "File Tree ID"),
+        ("session_id" = Uuid, Path, description = "Upload session ID"),
+    ),
+    responses(
+        (status = 200, description = "File stored", body = FileMetadataResponse),
+        (status = 404, description = "Upload session not found"),
+        (status = 409, description = "Upload session is incomplete or another file of the File Tree has the same name"),
+        (status = 410, description = "Upload expired"),
+        (status = 413, description = "File exceeds a quota of the client"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client), fields(owner = %client.0, id = %id, session_id = %session_id))]
+pub async fn finalize_session(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Path((id, session_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, ServerError> {
+    let upload_session_service = state.upload_session_service();
+
+    let metadata = upload_session_service
+        .get(&client.0, id, session_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get upload session: {:?}", e);
+            ServerError::from(e)
+        })?
+        .metadata();
+
+    let encoded_hash = upload_session_service
+        .finalize(&client.0, id, session_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to finalize upload session: {:?}", e);
+            ServerError::from(e)
+        })?;
+
+    // The file is only known to the session, the request does not name it.
+    let details = AuditDetails {
+        index: Some(metadata.index),
+        name: Some(metadata.name.clone()),
+        hash: Some(encoded_hash.clone()),
+        ..Default::default()
+    };
+
+    Ok((
+        Extension(details),
+        Json(FileMetadataResponse {
+            name: metadata.name,
+            index: metadata.index,
+            encoded_hash,
+        }),
+    ))
+}
+
+fn offset_response(status: StatusCode, session: &UploadSession) -> Response {
+    (
+        status,
+        [
+            (UPLOAD_OFFSET, HeaderValue::from(session.offset())),
+            (CACHE_CONTROL, HeaderValue::from_static("no-store")),
+        ],
+    )
+        .into_response()
+}
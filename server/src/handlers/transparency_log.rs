@@ -0,0 +1,106 @@
+use crate::{
+    errors::ServerError,
+    handlers::{
+        requests::{LogConsistencyRequest, LogProofRequest},
+        responses::{LogConsistencyProofResponse, LogHeadResponse, LogInclusionProofResponse},
+    },
+    infrastructure::AuthenticatedClient,
+    server::ServerState,
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+#[utoipa::path(
+    get,
+    path = "/log/head",
+    tag = "Transparency Log",
+    description = "Retrieve the signed head of the transparency log of completed roots",
+    responses(
+        (status = 200, description = "Signed log head", body = LogHeadResponse),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state))]
+pub async fn get_log_head(
+    State(state): State<Arc<ServerState>>,
+) -> Result<impl IntoResponse, ServerError> {
+    let head = state.transparency_log_service().head().await.map_err(|e| {
+        error!("Failed to get transparency log head: {:?}", e);
+        ServerError::from(e)
+    })?;
+
+    Ok(Json(LogHeadResponse::from(head)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/log/proof",
+    tag = "Transparency Log",
+    description = "Retrieve the proof that the latest root of the File Tree is in the transparency log",
+    params(
+        ("id" = Uuid, Path, description = "File Tree ID"),
+        ("size" = Option<u64>, Query, description = "Size of the log to prove inclusion in, defaults to the current size"),
+    ),
+    responses(
+        (status = 200, description = "Inclusion proof and signed log head", body = LogInclusionProofResponse),
+        (status = 400, description = "The log is not that big or the entry is not part of it"),
+        (status = 404, description = "File Tree not in the log"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client, request), fields(owner = %client.0, id = %id))]
+pub async fn get_log_proof(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Path(id): Path<Uuid>,
+    Query(request): Query<LogProofRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let proof = state
+        .transparency_log_service()
+        .inclusion_proof(&client.0, id, request.size)
+        .await
+        .map_err(|e| {
+            error!("Failed to get transparency log proof: {:?}", e);
+            ServerError::from(e)
+        })?;
+
+    Ok(Json(LogInclusionProofResponse::from(proof)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/log/consistency",
+    tag = "Transparency Log",
+    description = "Retrieve the proof that the transparency log of size second extends the log of size first",
+    params(
+        ("first" = u64, Query, description = "Size of the older log head"),
+        ("second" = Option<u64>, Query, description = "Size of the newer log head, defaults to the current size"),
+    ),
+    responses(
+        (status = 200, description = "Consistency proof and both signed log heads", body = LogConsistencyProofResponse),
+        (status = 400, description = "Sizes out of order or bigger than the log"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, request), fields(first = %request.first))]
+pub async fn get_log_consistency(
+    State(state): State<Arc<ServerState>>,
+    Query(request): Query<LogConsistencyRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let proof = state
+        .transparency_log_service()
+        .consistency_proof(request.first, request.second)
+        .await
+        .map_err(|e| {
+            error!("Failed to get transparency log consistency proof: {:?}", e);
+            ServerError::from(e)
+        })?;
+
+    Ok(Json(LogConsistencyProofResponse::from(proof)))
+}
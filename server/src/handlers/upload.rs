@@ -1,14 +1,20 @@
 use crate::{
     errors::ServerError,
-    handlers::{requests::UploadMetadataRequest, responses::FileMetadataResponse},
+    handlers::{
+        requests::{UploadContentRequest, UploadMetadataRequest},
+        responses::FileMetadataResponse,
+    },
+    infrastructure::{AuditDetails, AuthenticatedClient, into_file_stream},
+    models::FileMetadata,
     server::ServerState,
 };
 use axum::{
-    Json,
+    Extension, Json,
+    body::Body,
     extract::{Path, Query, State},
+    http::{HeaderMap, header::CONTENT_TYPE},
     response::IntoResponse,
 };
-use bytes::Bytes;
 use std::sync::Arc;
 use tracing::{error, instrument};
 use uuid::Uuid;
@@ -22,6 +28,7 @@ use uuid::Uuid;
         ("id" = Uuid, Path, description = "File Tree ID"),
         ("name" = String, Query, description = "Name of the file to upload"),
         ("index" = usize, Query, description = "Index of the file within the File Tree"),
+        ("hash" = Option<String>, Query, description = "Leaf hash of contents already stored, see `HEAD /blobs/{hash}`. The body is ignored when present"),
     ),
     request_body(
         content = inline(String),
@@ -30,37 +37,66 @@ use uuid::Uuid;
     ),
     responses(
         (status = 200, description = "File Tree upload initiated", body = FileMetadataResponse),
-        (status = 404, description = "File Tree not found"),
-        (status = 409, description = "File already exists"),
+        (status = 400, description = "Invalid file name or hash"),
+        (status = 404, description = "File Tree or contents for the given hash not found"),
+        (status = 409, description = "Another file of the File Tree has the same name"),
+        (status = 413, description = "File exceeds the maximum upload size or a quota of the client"),
         (status = 500, description = "Internal Server Error"),
     ),
 )]
-#[instrument(skip(state, metadata, body), fields(id = %id))]
+#[instrument(skip(state, client, metadata, content, headers, body), fields(owner = %client.0, id = %id))]
 pub async fn upload(
     State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
     // TODO: Add `validate-rs` crate for better error hanbdling and input validation
     Query(metadata): Query<UploadMetadataRequest>,
+    Query(content): Query<UploadContentRequest>,
     Path(id): Path<Uuid>,
-    body: Bytes,
+    headers: HeaderMap,
+    body: Body,
 ) -> Result<impl IntoResponse, ServerError> {
     // Since each handler (or controller) is responsible for the presentation logic,
     // Once basic input validations were copleted, we delegate the business logic to the service
     // layer. Normnally, the underlying service must expose one method per handler.
-    let encoded_hash = state
-        .file_service()
-        .upload_file(id, metadata.clone().into(), body)
-        .await
-        .map_err(|e| {
-            error!(
-                "Failed to upload file with index {} and name {}: {:?}",
-                metadata.index, metadata.name, e
-            );
-            ServerError::from(e)
-        })?;
+    let mut file_metadata: FileMetadata = metadata.clone().into();
+    file_metadata.content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
 
-    Ok(Json(FileMetadataResponse {
-        name: metadata.name.to_owned(),
-        index: metadata.index,
-        encoded_hash,
-    }))
+    let file_service = state.file_service();
+    let result = match content.hash()? {
+        Some(hash) => {
+            file_service
+                .upload_known_file(&client.0, id, file_metadata, hash)
+                .await
+        }
+        None => {
+            file_service
+                .upload_file(&client.0, id, file_metadata, into_file_stream(body))
+                .await
+        }
+    };
+
+    let encoded_hash = result.map_err(|e| {
+        error!(
+            "Failed to upload file with index {} and name {}: {:?}",
+            metadata.index, metadata.name, e
+        );
+        ServerError::from(e)
+    })?;
+
+    let details = AuditDetails {
+        hash: Some(encoded_hash.clone()),
+        ..Default::default()
+    };
+
+    Ok((
+        Extension(details),
+        Json(FileMetadataResponse {
+            name: metadata.name.to_owned(),
+            index: metadata.index,
+            encoded_hash,
+        }),
+    ))
 }
@@ -0,0 +1,34 @@
+use crate::{
+    errors::ServerError, handlers::responses::UsageResponse, infrastructure::AuthenticatedClient,
+    server::ServerState,
+};
+use axum::{Json, extract::State, response::IntoResponse};
+use std::sync::Arc;
+use tracing::{error, instrument};
+
+#[utoipa::path(
+    get,
+    path = "/usage",
+    tag = "Usage",
+    description = "Usage of the authenticated API key along with its quotas",
+    responses(
+        (status = 200, description = "Usage of the client", body = UsageResponse),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client), fields(owner = %client.0))]
+pub async fn get_usage(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+) -> Result<impl IntoResponse, ServerError> {
+    let usage = state
+        .file_service()
+        .get_usage(&client.0)
+        .await
+        .map_err(|e| {
+            error!("Failed to get usage: {:?}", e);
+            ServerError::from(e)
+        })?;
+
+    Ok(Json(UsageResponse::from(usage)))
+}
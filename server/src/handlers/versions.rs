@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use reqwest::StatusCode;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+use crate::{
+    errors::ServerError, handlers::responses::UploadSummaryResponse,
+    infrastructure::AuthenticatedClient, server::ServerState,
+};
+
+#[utoipa::path(
+    post,
+    path = "/{id}/versions",
+    tag = "Complete File Tree Upload",
+    description = "Create the next version of a completed File Tree. It starts with the files of the latest version, which can be replaced by uploading to their index or extended with new ones, and gets its own root once completed. Previous versions are kept and can be read through `?version=`",
+    params(
+        ("id" = Uuid, Path, description = "File Tree ID"),
+    ),
+    responses(
+        (status = 201, description = "File Tree version created", body = UploadSummaryResponse),
+        (status = 404, description = "File Tree not found"),
+        (status = 409, description = "Upload is not completed"),
+        (status = 410, description = "Upload expired"),
+        (status = 429, description = "Too many open uploads"),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client), fields(owner = %client.0, id = %id))]
+pub async fn create_version(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ServerError> {
+    let summary = state
+        .file_service()
+        .create_version(&client.0, id)
+        .await
+        .map_err(|e| {
+            error!("Failed to create version of upload {}: {:?}", id, e);
+            ServerError::from(e)
+        })?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(UploadSummaryResponse::from(summary)),
+    ))
+}
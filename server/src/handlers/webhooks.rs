@@ -0,0 +1,53 @@
+use crate::{
+    errors::ServerError,
+    handlers::{
+        requests::ListWebhookDeliveriesRequest,
+        responses::{WebhookDeliveriesResponse, WebhookDeliveryResponse},
+    },
+    infrastructure::AuthenticatedClient,
+    server::ServerState,
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use tracing::{error, instrument};
+
+#[utoipa::path(
+    get,
+    path = "/webhooks/deliveries",
+    tag = "Webhooks",
+    description = "List the webhook deliveries of the authenticated API key, newest first",
+    params(
+        ("offset" = Option<usize>, Query, description = "Number of deliveries to skip"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of deliveries to return, defaults to 100 and capped to 1000"),
+    ),
+    responses(
+        (status = 200, description = "Webhook deliveries of the client", body = WebhookDeliveriesResponse),
+        (status = 500, description = "Internal Server Error"),
+    ),
+)]
+#[instrument(skip(state, client, request), fields(owner = %client.0))]
+pub async fn list_webhook_deliveries(
+    State(state): State<Arc<ServerState>>,
+    client: AuthenticatedClient,
+    Query(request): Query<ListWebhookDeliveriesRequest>,
+) -> Result<impl IntoResponse, ServerError> {
+    let deliveries = state
+        .file_service()
+        .list_webhook_deliveries(&client.0, request.offset, request.limit())
+        .await
+        .map_err(|e| {
+            error!("Failed to list webhook deliveries: {:?}", e);
+            ServerError::from(e)
+        })?;
+
+    Ok(Json(WebhookDeliveriesResponse {
+        deliveries: deliveries
+            .into_iter()
+            .map(WebhookDeliveryResponse::from)
+            .collect(),
+    }))
+}
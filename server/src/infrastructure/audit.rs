@@ -0,0 +1,235 @@
+// Authenticated requests are recorded in the audit log of their client once they are answered:
+// what was done, to which upload and file, and how it went. Entries of a client are chained, the
+// hash of each one covers the hash of the one before, so altering or dropping an entry breaks
+// every hash after it. Requests failing authentication have no client and are not recorded.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    Router,
+    extract::{FromRequestParts, MatchedPath, Query, RawPathParams, Request, State},
+    http::{HeaderMap, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use file_server_library::models::Hash32;
+use metrics::counter;
+use serde_json::json;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    infrastructure::{AuthenticatedClient, CORRELATION_ID_HEADER, REQUEST_ID_HEADER},
+    repositories::{AuditEntryRow, AuditRepository},
+};
+
+const AUDIT_ENTRY_VERSION: &str = "file-server-audit-entry-v1";
+
+pub trait AuditExtensions {
+    fn with_audit(self, repository: Arc<dyn AuditRepository>) -> Self;
+}
+
+impl<S> AuditExtensions for Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    // Entries belong to the client that sent the request, so this layer has to be added before
+    // `with_authentication` for it to run after.
+    fn with_audit(self, repository: Arc<dyn AuditRepository>) -> Self {
+        self.layer(axum::middleware::from_fn_with_state(
+            Arc::new(AuditLog::new(repository)),
+            audit_middleware,
+        ))
+    }
+}
+
+/// What a handler knows of the operation that the request does not tell, e.g. the upload it
+/// initiated or the hash of the file it stored. Handlers add it to the response extensions.
+#[derive(Clone, Debug, Default)]
+pub struct AuditDetails {
+    pub upload_id: Option<Uuid>,
+    pub index: Option<usize>,
+    pub name: Option<String>,
+    pub hash: Option<String>,
+}
+
+/// Hash of `entry`, chained to its `previous_hash`: the SHA-256 of the compact JSON array of
+/// `"file-server-audit-entry-v1"` followed by the hex previous hash and the rest of the fields
+/// in declaration order, missing ones as `null` and `occurred_at` in milliseconds.
+pub fn audit_entry_hash(entry: &AuditEntryRow) -> Hash32 {
+    let fields = json!([
+        AUDIT_ENTRY_VERSION,
+        entry.previous_hash.to_hex(),
+        entry.owner,
+        entry.sequence,
+        entry.action,
+        entry.upload_id,
+        entry.index,
+        entry.name,
+        entry.hash,
+        entry.status,
+        entry.request_id,
+        entry.correlation_id,
+        entry.occurred_at.timestamp_millis(),
+    ]);
+
+    Hash32::hash(fields.to_string().as_bytes())
+}
+
+// Appends losing their sequence to another instance are chained again after it, this many times.
+const MAX_APPEND_ATTEMPTS: usize = 10;
+
+type Chain = Arc<tokio::sync::Mutex<()>>;
+
+struct AuditLog {
+    repository: Arc<dyn AuditRepository>,
+    // Entries of a client are numbered and chained. Appends of this instance are serialized per
+    // client so they do not race each other, the repository settles races with other instances.
+    chains: Mutex<HashMap<String, Chain>>,
+}
+
+impl AuditLog {
+    fn new(repository: Arc<dyn AuditRepository>) -> Self {
+        Self {
+            repository,
+            chains: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn chain_of(&self, owner: &str) -> Chain {
+        let mut chains = self.chains.lock().expect("audit chains lock poisoned");
+        Arc::clone(chains.entry(owner.to_string()).or_default())
+    }
+
+    /// Appends `entry` to the chain of its owner, filling its sequence and hashes in. The head
+    /// of the chain is looked up for every entry, other instances may have moved it.
+    async fn record(&self, mut entry: AuditEntryRow) -> anyhow::Result<()> {
+        let chain = self.chain_of(&entry.owner);
+        let _guard = chain.lock().await;
+
+        for _ in 0..MAX_APPEND_ATTEMPTS {
+            let previous = self.repository.latest(&entry.owner).await?;
+            entry.sequence = previous
+                .as_ref()
+                .map_or(0, |previous| previous.sequence + 1);
+            entry.previous_hash = previous.map_or(Hash32::empty(), |previous| previous.entry_hash);
+            entry.entry_hash = audit_entry_hash(&entry);
+
+            if self.repository.append(entry.clone()).await? {
+                return Ok(());
+            }
+
+            counter!("audit_append_conflicts_total").increment(1);
+        }
+
+        anyhow::bail!("kept losing the audit chain head to other instances")
+    }
+}
+
+/// What the request tells of the operation before it runs. Upload ids, indexes, names and
+/// hashes are taken from the path, or the query when the path has none.
+struct Operation {
+    action: String,
+    upload_id: Option<Uuid>,
+    index: Option<u64>,
+    name: Option<String>,
+    hash: Option<String>,
+    occurred_at: DateTime<Utc>,
+}
+
+impl Operation {
+    async fn of(parts: &mut Parts) -> Self {
+        let route = parts
+            .extensions
+            .get::<MatchedPath>()
+            .map(|path| path.as_str().to_string())
+            .unwrap_or_else(|| parts.uri.path().to_string());
+        let action = format!("{} {}", parts.method, route);
+
+        let mut params: HashMap<String, String> = Query::try_from_uri(&parts.uri)
+            .map(|Query(query)| query)
+            .unwrap_or_default();
+        if let Ok(path) = RawPathParams::from_request_parts(parts, &()).await {
+            params.extend(
+                path.iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string())),
+            );
+        }
+
+        Self {
+            action,
+            upload_id: params.get("id").and_then(|id| id.parse().ok()),
+            index: params.get("index").and_then(|index| index.parse().ok()),
+            name: params.remove("name"),
+            hash: params.remove("hash"),
+            occurred_at: now_millis(),
+        }
+    }
+}
+
+// Entries are stored with millisecond precision, which is also what their hash covers.
+fn now_millis() -> DateTime<Utc> {
+    let now = Utc::now();
+    DateTime::from_timestamp_millis(now.timestamp_millis()).unwrap_or(now)
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+async fn audit_middleware(
+    State(audit): State<Arc<AuditLog>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(AuthenticatedClient(owner)) = req.extensions().get::<AuthenticatedClient>().cloned()
+    else {
+        return next.run(req).await;
+    };
+
+    let (mut parts, body) = req.into_parts();
+    let operation = Operation::of(&mut parts).await;
+    let request_headers = parts.headers.clone();
+
+    let response = next.run(Request::from_parts(parts, body)).await;
+
+    // Request ids are generated further in when the client sent none, and sent back.
+    let request_id = header(response.headers(), REQUEST_ID_HEADER)
+        .or_else(|| header(&request_headers, REQUEST_ID_HEADER));
+    let correlation_id = header(&request_headers, CORRELATION_ID_HEADER);
+    let details = response
+        .extensions()
+        .get::<AuditDetails>()
+        .cloned()
+        .unwrap_or_default();
+
+    let entry = AuditEntryRow {
+        owner,
+        sequence: 0,
+        action: operation.action,
+        upload_id: details.upload_id.or(operation.upload_id),
+        index: details.index.map(|index| index as u64).or(operation.index),
+        name: details.name.or(operation.name),
+        hash: details.hash.or(operation.hash),
+        status: response.status().as_u16(),
+        request_id,
+        correlation_id,
+        occurred_at: operation.occurred_at,
+        previous_hash: Hash32::empty(),
+        entry_hash: Hash32::empty(),
+    };
+
+    // The operation already ran, failing to record it does not change its response.
+    if let Err(e) = audit.record(entry).await {
+        warn!(error = %e, "failed to record the audit entry");
+        counter!("audit_failures_total").increment(1);
+    }
+
+    response
+}
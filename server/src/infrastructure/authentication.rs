@@ -4,8 +4,8 @@
 use axum::{
     Router,
     body::Body,
-    extract::State,
-    http::Request,
+    extract::{FromRequestParts, State},
+    http::{Request, request::Parts},
     middleware::Next,
     response::{IntoResponse, Response},
 };
@@ -13,11 +13,14 @@ use chrono::Utc;
 use hmac::{Hmac, Mac};
 use reqwest::StatusCode;
 use sha2::Sha256;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-const AUTH_TS_HEADER_NAME: &str = "X-AUTH-TS";
-const AUTH_SIGNATURE_HEADER_NAME: &str = "X-AUTH-SIGNATURE";
-const AUTH_KEY_HEADER_NAME: &str = "X-AUTH-KEY";
+pub(crate) const AUTH_TS_HEADER_NAME: &str = "X-AUTH-TS";
+pub(crate) const AUTH_SIGNATURE_HEADER_NAME: &str = "X-AUTH-SIGNATURE";
+pub(crate) const AUTH_KEY_HEADER_NAME: &str = "X-AUTH-KEY";
 const FIVE_SECONDS_IN_MILLIS: i64 = 5000;
 
 type HmacSha256 = Hmac<Sha256>;
@@ -39,9 +42,50 @@ where
     }
 }
 
+/// Key of the client that signed the request. The middleware adds it to the request
+/// extensions, so handlers can take it as an extractor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthenticatedClient(pub String);
+
+impl<S> FromRequestParts<S> for AuthenticatedClient
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AuthenticatedClient>()
+            .cloned()
+            .ok_or((StatusCode::UNAUTHORIZED, "Unauthenticated request"))
+    }
+}
+
+/// Added next to `AuthenticatedClient` when the key belongs to an admin. Handlers taking it are
+/// forbidden to everybody else.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdminClient(pub String);
+
+impl<S> FromRequestParts<S> for AdminClient
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AdminClient>()
+            .cloned()
+            .ok_or((StatusCode::FORBIDDEN, "Admin access required"))
+    }
+}
+
 #[derive(Clone)]
 struct AuthState {
     clients: Arc<HashMap<String, String>>,
+    admins: Arc<HashSet<String>>,
     max_skew: i64,
 }
 
@@ -50,6 +94,7 @@ impl AuthState {
         let clients = load_clients();
         Self {
             clients: Arc::new(clients),
+            admins: Arc::new(load_admins()),
             max_skew: FIVE_SECONDS_IN_MILLIS,
         }
     }
@@ -57,7 +102,7 @@ impl AuthState {
 
 async fn authentication_middleware(
     State(auth): State<AuthState>,
-    req: Request<Body>,
+    mut req: Request<Body>,
     next: Next,
 ) -> Response {
     let Some(ts_header) = req.headers().get(AUTH_TS_HEADER_NAME) else {
@@ -103,10 +148,15 @@ async fn authentication_middleware(
         return (StatusCode::UNAUTHORIZED, "Expired timestamp").into_response();
     }
 
+    if auth.admins.contains(&key) {
+        req.extensions_mut().insert(AdminClient(key.clone()));
+    }
+    req.extensions_mut().insert(AuthenticatedClient(key));
+
     next.run(req).await
 }
 
-fn hmac_hex(secret: &[u8], data: &[u8]) -> String {
+pub(crate) fn hmac_hex(secret: &[u8], data: &[u8]) -> String {
     let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC can take key of any size");
     mac.update(data);
     hex::encode(mac.finalize().into_bytes())
@@ -129,11 +179,17 @@ fn constant_time_eq(a_hex: &str, b_hex: &str) -> bool {
 // storage or environment variables. Ideally, not kept in memory neither.
 // Additionally, each client should be able to have more than one secret with
 // expiration, rotation, etc.
-fn load_clients() -> HashMap<String, String> {
+pub(crate) fn load_clients() -> HashMap<String, String> {
     let mut clients = HashMap::new();
 
     clients.insert("client-1".to_string(), "secret-1".to_string());
     clients.insert("client-2".to_string(), "secret-2".to_string());
+    clients.insert("admin".to_string(), "admin-secret".to_string());
 
     clients
 }
+
+// Same as above, roles would come along with the clients from wherever they are stored.
+fn load_admins() -> HashSet<String> {
+    HashSet::from(["admin".to_string()])
+}
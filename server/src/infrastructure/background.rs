@@ -0,0 +1,38 @@
+use std::future::Future;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+/// Keeps track of long running tasks spawned next to the HTTP server (reapers, scrubbers, etc).
+/// Every task receives a `CancellationToken` that is cancelled once `axum` finished its own
+/// graceful shutdown, so background work stops at the same point instead of being killed
+/// in the middle of an iteration when the runtime is dropped.
+#[derive(Default)]
+pub struct BackgroundTasks {
+    shutdown: CancellationToken,
+    handles: Vec<(&'static str, JoinHandle<()>)>,
+}
+
+impl BackgroundTasks {
+    pub fn spawn<F, Fut>(&mut self, name: &'static str, task: F)
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        info!(task = name, "Starting background task");
+        let handle = tokio::spawn(task(self.shutdown.child_token()));
+        self.handles.push((name, handle));
+    }
+
+    pub async fn shutdown(self) {
+        self.shutdown.cancel();
+
+        for (name, handle) in self.handles {
+            if let Err(e) = handle.await {
+                error!(task = name, "Background task did not stop cleanly: {}", e);
+            } else {
+                info!(task = name, "Background task stopped");
+            }
+        }
+    }
+}
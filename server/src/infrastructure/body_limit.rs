@@ -0,0 +1,50 @@
+// Axum's `DefaultBodyLimit` only applies to buffering extractors such as `Bytes`, so streamed
+// bodies are limited with `RequestBodyLimitLayer` instead. Requests announcing a bigger
+// `Content-Length` are rejected upfront, chunked ones fail as soon as the limit is crossed.
+use std::{error::Error, io};
+
+use axum::{Router, body::Body, extract::DefaultBodyLimit};
+use futures::TryStreamExt;
+use http_body_util::LengthLimitError;
+use tower_http::limit::RequestBodyLimitLayer;
+
+use crate::repositories::FileStream;
+
+pub trait BodyLimitExtensions {
+    fn with_body_limit(self, max_bytes: usize) -> Self;
+}
+
+impl<S> BodyLimitExtensions for Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    fn with_body_limit(self, max_bytes: usize) -> Self {
+        self.layer(DefaultBodyLimit::disable())
+            .layer(RequestBodyLimitLayer::new(max_bytes))
+    }
+}
+
+/// Turns a request body into a `FileStream`. Crossing the body limit is reported as
+/// `io::ErrorKind::FileTooLarge` so services can tell it apart from other IO failures.
+pub fn into_file_stream(body: Body) -> FileStream {
+    Box::pin(body.into_data_stream().map_err(|e| {
+        if is_length_limit_error(&e) {
+            io::Error::new(io::ErrorKind::FileTooLarge, e)
+        } else {
+            io::Error::other(e)
+        }
+    }))
+}
+
+fn is_length_limit_error(e: &axum::Error) -> bool {
+    let mut source: Option<&(dyn Error + 'static)> = Some(e);
+
+    while let Some(cause) = source {
+        if cause.is::<LengthLimitError>() {
+            return true;
+        }
+        source = cause.source();
+    }
+
+    false
+}
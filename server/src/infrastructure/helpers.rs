@@ -31,4 +31,3 @@ pub async fn graceful_shutdown_signal() {
         _ = terminate => {},
     }
 }
-
@@ -0,0 +1,336 @@
+// Mutating requests carrying an `Idempotency-Key` header run once per key and client. Their
+// response is recorded for a while and replayed to any retry, so a request that timed out on the
+// client but went through on the server does not run twice. Requests are told apart by their
+// method, path and query, the body is streamed to the handlers and never read here.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    Json, Router,
+    body::{Body, HttpBody},
+    extract::{Request, State},
+    http::{
+        HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+        header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, LAST_MODIFIED, LOCATION},
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use chrono::{Duration, Utc};
+use config::Config;
+use file_server_library::models::Hash32;
+use futures::stream;
+use metrics::counter;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::warn;
+
+use crate::{
+    infrastructure::AuthenticatedClient,
+    repositories::{IdempotencyRepository, IdempotencyRow},
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+const MAX_KEY_LENGTH: usize = 255;
+const ONE_DAY_IN_SECS: u64 = 24 * 60 * 60;
+const SIXTY_FOUR_KIB_IN_BYTES: usize = 64 * 1024;
+
+// Headers describing the outcome of the request, replayed along with the body. The ones added
+// per response, such as the request id or the signature, are added again by their own layers.
+const REPLAYED_HEADERS: [HeaderName; 6] = [
+    LOCATION,
+    ETAG,
+    LAST_MODIFIED,
+    CACHE_CONTROL,
+    HeaderName::from_static("upload-offset"),
+    HeaderName::from_static("upload-length"),
+];
+
+pub trait IdempotencyExtensions {
+    fn with_idempotency(
+        self,
+        repository: Arc<dyn IdempotencyRepository>,
+        config: IdempotencyConfig,
+    ) -> Self;
+}
+
+impl<S> IdempotencyExtensions for Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    // Keys belong to the client that sent them, so this layer has to be added before
+    // `with_authentication` for it to run after.
+    fn with_idempotency(
+        self,
+        repository: Arc<dyn IdempotencyRepository>,
+        config: IdempotencyConfig,
+    ) -> Self {
+        self.layer(axum::middleware::from_fn_with_state(
+            Arc::new(Idempotency::new(repository, config)),
+            idempotency_middleware,
+        ))
+    }
+}
+
+/// `ttl_secs` is how long responses are replayed for. Responses whose body is larger than
+/// `max_response_bytes`, or whose length is not known upfront, are not recorded.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdempotencyConfig {
+    #[serde(default = "IdempotencyConfig::default_ttl_secs")]
+    pub ttl_secs: u64,
+    #[serde(default = "IdempotencyConfig::default_max_response_bytes")]
+    pub max_response_bytes: usize,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: Self::default_ttl_secs(),
+            max_response_bytes: Self::default_max_response_bytes(),
+        }
+    }
+}
+
+impl IdempotencyConfig {
+    const CONFIG_PREFIX: &'static str = "IDEMPOTENCY";
+
+    fn default_ttl_secs() -> u64 {
+        ONE_DAY_IN_SECS
+    }
+
+    fn default_max_response_bytes() -> usize {
+        SIXTY_FOUR_KIB_IN_BYTES
+    }
+
+    pub fn load_from_env() -> anyhow::Result<Self> {
+        Config::builder()
+            .add_source(
+                config::Environment::with_prefix(Self::CONFIG_PREFIX)
+                    .separator("__")
+                    .try_parsing(true),
+            )
+            .build()?
+            .try_deserialize::<IdempotencyConfig>()
+            .map_err(|e| anyhow::anyhow!("failed to load Idempotency Configuration: {}", e))
+    }
+}
+
+type KeyLock = Arc<tokio::sync::Mutex<()>>;
+
+struct Idempotency {
+    repository: Arc<dyn IdempotencyRepository>,
+    config: IdempotencyConfig,
+    // Requests with the same key wait for each other, so a retry arriving while the original is
+    // still running gets its response instead of running again. Only holds within a process.
+    in_flight: Mutex<HashMap<(String, String), KeyLock>>,
+}
+
+impl Idempotency {
+    fn new(repository: Arc<dyn IdempotencyRepository>, config: IdempotencyConfig) -> Self {
+        Self {
+            repository,
+            config,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock_for(&self, owner: &str, key: &str) -> KeyLock {
+        let mut in_flight = self.in_flight.lock().expect("idempotency lock poisoned");
+        Arc::clone(
+            in_flight
+                .entry((owner.to_string(), key.to_string()))
+                .or_default(),
+        )
+    }
+
+    // Dropped once nobody else waits on it.
+    fn release(&self, owner: &str, key: &str, lock: KeyLock) {
+        let mut in_flight = self.in_flight.lock().expect("idempotency lock poisoned");
+        let id = (owner.to_string(), key.to_string());
+        if in_flight
+            .get(&id)
+            .is_some_and(|current| Arc::ptr_eq(current, &lock) && Arc::strong_count(current) == 2)
+        {
+            in_flight.remove(&id);
+        }
+    }
+}
+
+fn is_mutating(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    )
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= MAX_KEY_LENGTH && key.bytes().all(|b| b.is_ascii_graphic())
+}
+
+fn fingerprint(req: &Request) -> String {
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or_else(|| req.uri().path());
+
+    Hash32::hash(format!("{} {}", req.method(), path).as_bytes()).to_hex()
+}
+
+// Retrying is expected to help with these, so they are run again instead of replayed.
+fn is_recordable(status: StatusCode) -> bool {
+    !status.is_server_error() && status != StatusCode::TOO_MANY_REQUESTS
+}
+
+async fn idempotency_middleware(
+    State(idempotency): State<Arc<Idempotency>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !is_mutating(req.method()) {
+        return next.run(req).await;
+    }
+    let Some(key) = req.headers().get(IDEMPOTENCY_KEY_HEADER).cloned() else {
+        return next.run(req).await;
+    };
+    let Some(AuthenticatedClient(owner)) = req.extensions().get::<AuthenticatedClient>().cloned()
+    else {
+        return next.run(req).await;
+    };
+    let Some(key) = key.to_str().ok().filter(|key| is_valid_key(key)) else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "Idempotency key must be up to 255 visible ASCII characters",
+        );
+    };
+    let key = key.to_string();
+
+    let lock = idempotency.lock_for(&owner, &key);
+    let response = {
+        let _guard = lock.lock().await;
+        run_once(&idempotency, &owner, &key, req, next).await
+    };
+    idempotency.release(&owner, &key, lock);
+
+    response
+}
+
+async fn run_once(
+    idempotency: &Idempotency,
+    owner: &str,
+    key: &str,
+    req: Request,
+    next: Next,
+) -> Response {
+    let fingerprint = fingerprint(&req);
+
+    // Without the repository the request runs as if it had no key, rather than failing writes.
+    match idempotency.repository.get(owner, key).await {
+        Ok(Some(row)) if row.fingerprint != fingerprint => {
+            return error_response(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Idempotency key was already used for another request",
+            );
+        }
+        Ok(Some(row)) => {
+            counter!("idempotent_replays_total").increment(1);
+            return replay(row);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            warn!(error = %e, "failed to look the idempotency key up");
+            counter!("idempotency_failures_total").increment(1);
+        }
+    }
+
+    let response = next.run(req).await;
+    if !is_recordable(response.status()) {
+        return response;
+    }
+
+    let max_bytes = idempotency.config.max_response_bytes;
+    match response.body().size_hint().exact() {
+        Some(length) if length as usize <= max_bytes => {}
+        _ => return response,
+    }
+
+    // The request already ran, so a body failing midway is passed on to the client as it is,
+    // cutting the response short, and nothing is recorded.
+    let (parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, max_bytes).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!(error = %e, "failed to read the response to record");
+            let failed = stream::once(async move { Err::<Bytes, _>(e) });
+            return Response::from_parts(parts, Body::from_stream(failed));
+        }
+    };
+
+    // Responses are JSON, anything else is passed through without being recorded.
+    if let Ok(body) = std::str::from_utf8(&bytes) {
+        let row = IdempotencyRow {
+            owner: owner.to_string(),
+            key: key.to_string(),
+            fingerprint,
+            status: parts.status.as_u16(),
+            content_type: parts
+                .headers
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            headers: replayed_headers(&parts.headers),
+            body: body.to_string(),
+            expires_at: Utc::now() + Duration::seconds(idempotency.config.ttl_secs as i64),
+        };
+
+        if let Err(e) = idempotency.repository.insert(row).await {
+            warn!(error = %e, "failed to record the idempotent response");
+            counter!("idempotency_failures_total").increment(1);
+        }
+    }
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+fn replayed_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    REPLAYED_HEADERS
+        .iter()
+        .filter_map(|name| {
+            let value = headers.get(name)?.to_str().ok()?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+fn replay(row: IdempotencyRow) -> Response {
+    let status = StatusCode::from_u16(row.status).unwrap_or(StatusCode::OK);
+    let mut response = (status, row.body).into_response();
+
+    let headers = response.headers_mut();
+    headers.remove(CONTENT_TYPE);
+    if let Some(content_type) = row
+        .content_type
+        .and_then(|value| HeaderValue::from_str(&value).ok())
+    {
+        headers.insert(CONTENT_TYPE, content_type);
+    }
+    for (name, value) in row.headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            headers.insert(name, value);
+        }
+    }
+    headers.insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+
+    response
+}
+
+fn error_response(status: StatusCode, error: &str) -> Response {
+    (status, Json(json!({ "error": error }))).into_response()
+}
@@ -0,0 +1,38 @@
+// Metrics are recorded through the `metrics` facade, so services only depend on the macros
+// (`counter!`, `histogram!`, etc). The Prometheus recorder is installed once at startup and
+// rendered through a plain `/metrics` endpoint that scrapers can reach without authentication.
+use axum::{Router, routing::get};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use std::sync::OnceLock;
+
+static PROMETHEUS_HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+pub trait MetricsExtensions {
+    fn with_metrics(self) -> Self;
+}
+
+pub fn init_metrics() {
+    let handle = PrometheusBuilder::new()
+        .install_recorder()
+        .expect("Failed to install Prometheus recorder");
+
+    PROMETHEUS_HANDLE
+        .set(handle)
+        .expect("Metrics must be initialized only once");
+}
+
+impl<S> MetricsExtensions for Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    fn with_metrics(self) -> Self {
+        self.route("/metrics", get(render_metrics))
+    }
+}
+
+async fn render_metrics() -> String {
+    PROMETHEUS_HANDLE
+        .get()
+        .map(PrometheusHandle::render)
+        .unwrap_or_default()
+}
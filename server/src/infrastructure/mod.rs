@@ -1,14 +1,29 @@
 // This file is part of the template, usually, this does not noeed to be modified.
 
+mod audit;
 mod authentication;
+mod background;
+mod body_limit;
 mod helpers;
+mod idempotency;
+mod metrics;
+mod rate_limit;
+mod response_signing;
 mod tracing;
 
+pub use crate::infrastructure::audit::*;
 pub use crate::infrastructure::authentication::*;
+pub use crate::infrastructure::background::*;
+pub use crate::infrastructure::body_limit::*;
+pub use crate::infrastructure::idempotency::*;
+pub use crate::infrastructure::metrics::*;
+pub use crate::infrastructure::rate_limit::*;
+pub use crate::infrastructure::response_signing::*;
 pub use crate::infrastructure::tracing::*;
 pub use helpers::*;
 
 pub async fn init_infrastructure() {
     dotenv::dotenv().ok();
     init_tracing();
+    init_metrics();
 }
@@ -0,0 +1,327 @@
+// Token buckets per API key, one for the number of requests and one for the bytes uploaded.
+// Buckets live in memory, so every server process limits on its own and a restart starts them
+// full again. Requests over budget are answered `429 Too Many Requests` with `Retry-After`.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Json, Router,
+    body::{Body, HttpBody},
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use config::Config;
+use futures::StreamExt;
+use metrics::counter;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::infrastructure::AuthenticatedClient;
+
+pub trait RateLimitExtensions {
+    fn with_rate_limiting(self, config: RateLimitConfig) -> Self;
+}
+
+impl<S> RateLimitExtensions for Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    // Requests are told apart by the key authentication adds, so this layer has to be added
+    // before `with_authentication` for it to run after.
+    fn with_rate_limiting(self, config: RateLimitConfig) -> Self {
+        self.layer(axum::middleware::from_fn_with_state(
+            Arc::new(RateLimiter::new(config)),
+            rate_limit_middleware,
+        ))
+    }
+}
+
+/// Budgets of a client, missing ones are unlimited. Bursts default to one second worth of the
+/// rate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub struct RateLimits {
+    pub requests_per_sec: Option<f64>,
+    pub request_burst: Option<f64>,
+    pub upload_bytes_per_sec: Option<f64>,
+    pub upload_burst_bytes: Option<f64>,
+}
+
+impl RateLimits {
+    /// Rates and bursts that are set have to be positive and finite, a bucket refilling at no
+    /// rate would never let its client through again.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let limits = [
+            ("requests_per_sec", self.requests_per_sec),
+            ("request_burst", self.request_burst),
+            ("upload_bytes_per_sec", self.upload_bytes_per_sec),
+            ("upload_burst_bytes", self.upload_burst_bytes),
+        ];
+
+        for (name, limit) in limits {
+            if let Some(limit) = limit
+                && !(limit.is_finite() && limit > 0.0)
+            {
+                anyhow::bail!("{name} must be a positive number, got {limit}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Limits missing here are taken from `defaults`.
+    pub fn or(self, defaults: RateLimits) -> Self {
+        Self {
+            requests_per_sec: self.requests_per_sec.or(defaults.requests_per_sec),
+            request_burst: self.request_burst.or(defaults.request_burst),
+            upload_bytes_per_sec: self.upload_bytes_per_sec.or(defaults.upload_bytes_per_sec),
+            upload_burst_bytes: self.upload_burst_bytes.or(defaults.upload_burst_bytes),
+        }
+    }
+}
+
+/// Budgets applied to every client, and per client overrides keyed by API key, e.g.
+/// `RATE_LIMIT__DEFAULT__REQUESTS_PER_SEC` and `RATE_LIMIT__CLIENTS__client-1__REQUESTS_PER_SEC`.
+/// Keys are read lowercased.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RateLimitConfig {
+    #[serde(default)]
+    pub default: RateLimits,
+    #[serde(default)]
+    pub clients: HashMap<String, RateLimits>,
+}
+
+impl RateLimitConfig {
+    const CONFIG_PREFIX: &'static str = "RATE_LIMIT";
+
+    pub fn load_from_env() -> anyhow::Result<Self> {
+        let config = Config::builder()
+            .add_source(
+                config::Environment::with_prefix(Self::CONFIG_PREFIX)
+                    .separator("__")
+                    .try_parsing(true),
+            )
+            .build()?
+            .try_deserialize::<RateLimitConfig>()
+            .map_err(|e| anyhow::anyhow!("failed to load Rate Limit Configuration: {}", e))?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.default
+            .validate()
+            .map_err(|e| anyhow::anyhow!("invalid default rate limits: {}", e))?;
+
+        for (key, limits) in &self.clients {
+            limits
+                .validate()
+                .map_err(|e| anyhow::anyhow!("invalid rate limits for {}: {}", key, e))?;
+        }
+
+        Ok(())
+    }
+
+    fn limits_for(&self, key: &str) -> RateLimits {
+        self.clients
+            .get(key)
+            .copied()
+            .unwrap_or_default()
+            .or(self.default)
+    }
+}
+
+// Longest wait reported to a client, also what a bucket that does not refill asks for.
+const MAX_WAIT: Duration = Duration::from_secs(3600);
+
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, capacity: f64) -> Self {
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            updated_at: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated_at = now;
+    }
+
+    /// Time until `tokens` are available, at most `MAX_WAIT`.
+    fn wait_for(&self, tokens: f64) -> Duration {
+        Duration::try_from_secs_f64(((tokens - self.tokens) / self.rate).max(0.0))
+            .map_or(MAX_WAIT, |wait| wait.min(MAX_WAIT))
+    }
+
+    fn try_take(&mut self, tokens: f64) -> Result<(), Duration> {
+        self.refill();
+        if self.tokens >= tokens {
+            self.tokens -= tokens;
+            Ok(())
+        } else {
+            Err(self.wait_for(tokens))
+        }
+    }
+
+    /// Takes the tokens even when there are not enough, leaving the bucket in debt.
+    fn take(&mut self, tokens: f64) {
+        self.refill();
+        self.tokens -= tokens;
+    }
+}
+
+#[derive(Default)]
+struct ClientBuckets {
+    requests: Option<TokenBucket>,
+    upload_bytes: Option<TokenBucket>,
+}
+
+impl ClientBuckets {
+    fn new(limits: RateLimits) -> Self {
+        Self {
+            requests: limits
+                .requests_per_sec
+                .map(|rate| TokenBucket::new(rate, limits.request_burst.unwrap_or(rate).max(1.0))),
+            upload_bytes: limits.upload_bytes_per_sec.map(|rate| {
+                TokenBucket::new(rate, limits.upload_burst_bytes.unwrap_or(rate).max(1.0))
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Budget {
+    Requests,
+    UploadBytes,
+}
+
+impl Budget {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Budget::Requests => "requests",
+            Budget::UploadBytes => "upload_bytes",
+        }
+    }
+}
+
+struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<String, ClientBuckets>>,
+}
+
+impl RateLimiter {
+    fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn with_buckets<T>(&self, key: &str, f: impl FnOnce(&mut ClientBuckets) -> T) -> T {
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+        let client = buckets
+            .entry(key.to_string())
+            .or_insert_with(|| ClientBuckets::new(self.config.limits_for(key)));
+        f(client)
+    }
+
+    // Uploads are only let in while the bandwidth budget is not in debt, rejected requests are
+    // not counted.
+    fn admit(&self, key: &str, uploads: bool) -> Result<(), (Budget, Duration)> {
+        self.with_buckets(key, |client| {
+            if uploads && let Some(bucket) = client.upload_bytes.as_mut() {
+                bucket.refill();
+                if bucket.tokens <= 0.0 {
+                    return Err((Budget::UploadBytes, bucket.wait_for(1.0)));
+                }
+            }
+
+            match client.requests.as_mut() {
+                Some(bucket) => bucket
+                    .try_take(1.0)
+                    .map_err(|wait| (Budget::Requests, wait)),
+                None => Ok(()),
+            }
+        })
+    }
+
+    fn take_upload_bytes(&self, key: &str, bytes: usize) {
+        self.with_buckets(key, |client| {
+            if let Some(bucket) = client.upload_bytes.as_mut() {
+                bucket.take(bytes as f64);
+            }
+        })
+    }
+
+    fn limits_uploads(&self, key: &str) -> bool {
+        self.with_buckets(key, |client| client.upload_bytes.is_some())
+    }
+}
+
+async fn rate_limit_middleware(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(AuthenticatedClient(key)) = req.extensions().get::<AuthenticatedClient>().cloned()
+    else {
+        return next.run(req).await;
+    };
+
+    let uploads = req.body().size_hint().exact() != Some(0);
+    if let Err((budget, wait)) = limiter.admit(&key, uploads) {
+        counter!("rate_limited_requests_total", "budget" => budget.as_str()).increment(1);
+        return too_many_requests(budget, wait);
+    }
+
+    if !uploads || !limiter.limits_uploads(&key) {
+        return next.run(req).await;
+    }
+
+    // Uploads in flight are neither cut nor slowed down, the bytes they send put the budget in
+    // debt and the next uploads wait until it is paid back.
+    let (parts, body) = req.into_parts();
+    let counted = body.into_data_stream().inspect(move |chunk| {
+        if let Ok(chunk) = chunk {
+            limiter.take_upload_bytes(&key, chunk.len());
+        }
+    });
+
+    next.run(Request::from_parts(parts, Body::from_stream(counted)))
+        .await
+}
+
+// Whole seconds, rounded up so retrying right when told to succeeds.
+fn too_many_requests(budget: Budget, wait: Duration) -> Response {
+    let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+    let mut response = (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(json!({
+            "error": "Rate limit exceeded",
+            "budget": budget.as_str(),
+            "retry_after_secs": retry_after,
+        })),
+    )
+        .into_response();
+
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
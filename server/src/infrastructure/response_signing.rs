@@ -0,0 +1,290 @@
+// Every response is signed with the server's Ed25519 key, so clients pinning the public key can
+// tell they are talking to the legitimate server. The signature covers the request it answers,
+// the status, the headers listed in `file_server_library::signing::SIGNED_HEADERS` and a digest of
+// the body. JSON bodies are always digested, whatever their size. Other bodies only are when their
+// length is known and small enough: streamed downloads are left out, clients verify their
+// contents through the Merkle proofs of the upload instead.
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    body::{Body, HttpBody},
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header::CONTENT_TYPE},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use config::Config;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use file_server_library::{
+    models::Hash32,
+    signing::{
+        SERVER_DIGEST_HEADER, SERVER_KEY_HEADER, SERVER_SIGNATURE_HEADER, SignedResponse, key_id,
+    },
+};
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::infrastructure::AUTH_SIGNATURE_HEADER_NAME;
+
+const KEY_REGISTRY_PATH: &str = "/.well-known/file-server-keys";
+const ONE_MIB_IN_BYTES: usize = 1024 * 1024;
+
+pub trait ResponseSigningExtensions {
+    fn with_response_signing(self, signer: Arc<ServerSigner>) -> Self;
+}
+
+impl<S> ResponseSigningExtensions for Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    // The key registry is added before the layer, so it is signed as well.
+    fn with_response_signing(self, signer: Arc<ServerSigner>) -> Self {
+        let registry = signer.registry();
+
+        self.route(
+            KEY_REGISTRY_PATH,
+            get(move || std::future::ready(Json(registry.clone()))),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            signer,
+            response_signing_middleware,
+        ))
+    }
+}
+
+/// `seed` is the hex encoded secret of the active key, a random one is generated when missing.
+/// `retired_keys` are hex encoded public keys that are still published but sign nothing.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResponseSigningConfig {
+    pub seed: Option<String>,
+    #[serde(default)]
+    pub retired_keys: Vec<String>,
+    #[serde(default = "ResponseSigningConfig::default_max_body_bytes")]
+    pub max_body_bytes: usize,
+}
+
+impl ResponseSigningConfig {
+    const CONFIG_PREFIX: &'static str = "RESPONSE_SIGNING";
+
+    pub fn load_from_env() -> anyhow::Result<Self> {
+        Config::builder()
+            .add_source(
+                config::Environment::with_prefix(Self::CONFIG_PREFIX)
+                    .separator("__")
+                    .list_separator(",")
+                    .with_list_parse_key("retired_keys")
+                    .try_parsing(true),
+            )
+            .build()?
+            .try_deserialize::<ResponseSigningConfig>()
+            .map_err(|e| anyhow::anyhow!("failed to load Response Signing Configuration: {}", e))
+    }
+
+    fn default_max_body_bytes() -> usize {
+        ONE_MIB_IN_BYTES
+    }
+}
+
+/// Holds the active signing key along with the retired public keys published next to it.
+pub struct ServerSigner {
+    signing_key: SigningKey,
+    key_id: String,
+    retired_keys: Vec<VerifyingKey>,
+    max_body_bytes: usize,
+}
+
+impl ServerSigner {
+    pub fn new(signing_key: SigningKey) -> Self {
+        let key_id = key_id(signing_key.verifying_key().as_bytes());
+
+        Self {
+            signing_key,
+            key_id,
+            retired_keys: vec![],
+            max_body_bytes: ResponseSigningConfig::default_max_body_bytes(),
+        }
+    }
+
+    pub fn with_retired_keys(mut self, retired_keys: Vec<VerifyingKey>) -> Self {
+        self.retired_keys = retired_keys;
+        self
+    }
+
+    pub fn with_max_body_bytes(mut self, max_body_bytes: usize) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+
+    pub fn from_config(config: ResponseSigningConfig) -> anyhow::Result<Self> {
+        let signing_key = match config.seed {
+            Some(seed) => SigningKey::from_bytes(&decode_key(&seed)?),
+            None => {
+                let signing_key = SigningKey::generate(&mut OsRng);
+                warn!(
+                    public_key = %hex::encode(signing_key.verifying_key().as_bytes()),
+                    "No RESPONSE_SIGNING__SEED configured, responses are signed with an ephemeral key"
+                );
+                signing_key
+            }
+        };
+
+        let retired_keys = config
+            .retired_keys
+            .iter()
+            .map(|key| {
+                VerifyingKey::from_bytes(&decode_key(key)?)
+                    .map_err(|e| anyhow::anyhow!("invalid retired key {key}: {e}"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self::new(signing_key)
+            .with_retired_keys(retired_keys)
+            .with_max_body_bytes(config.max_body_bytes))
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.signing_key.verifying_key()
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        self.signing_key.sign(message)
+    }
+
+    pub fn registry(&self) -> KeyRegistry {
+        let active = std::iter::once((self.verifying_key(), KeyStatus::Active));
+        let retired = self
+            .retired_keys
+            .iter()
+            .map(|key| (*key, KeyStatus::Retired));
+
+        KeyRegistry {
+            keys: active
+                .chain(retired)
+                .map(|(key, status)| PublishedKey {
+                    key_id: key_id(key.as_bytes()),
+                    algorithm: "ed25519".to_string(),
+                    public_key: hex::encode(key.as_bytes()),
+                    status,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn decode_key(key: &str) -> anyhow::Result<[u8; 32]> {
+    hex::decode(key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("keys must be 32 bytes encoded in hex"))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyStatus {
+    Active,
+    Retired,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PublishedKey {
+    pub key_id: String,
+    pub algorithm: String,
+    pub public_key: String,
+    pub status: KeyStatus,
+}
+
+/// Body of `/.well-known/file-server-keys`, the active key comes first.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyRegistry {
+    pub keys: Vec<PublishedKey>,
+}
+
+async fn response_signing_middleware(
+    State(signer): State<Arc<ServerSigner>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let method = req.method().clone();
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| req.uri().path().to_string());
+    let request_signature = req
+        .headers()
+        .get(AUTH_SIGNATURE_HEADER_NAME)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let (mut parts, body) = next.run(req).await.into_parts();
+
+    // HEAD responses never carry their body, there is nothing to digest.
+    let limit = match body.size_hint().exact() {
+        _ if method == Method::HEAD => None,
+        _ if is_json(&parts.headers) => Some(usize::MAX),
+        Some(len) if len as usize <= signer.max_body_bytes => Some(len as usize),
+        _ => None,
+    };
+    let body = match limit {
+        Some(limit) => {
+            let bytes = match axum::body::to_bytes(body, limit).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    error!("Failed to read response body for signing: {}", e);
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            };
+
+            let digest = Hash32::hash(&bytes).to_hex();
+            parts.headers.insert(
+                SERVER_DIGEST_HEADER,
+                HeaderValue::from_str(&digest).expect("hex is a valid header value"),
+            );
+            Body::from(bytes)
+        }
+        None => body,
+    };
+
+    let mut signed = SignedResponse::new(
+        signer.key_id(),
+        method.as_str(),
+        &path,
+        parts.status.as_u16(),
+    )
+    .with_headers(|name| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    });
+    if let Some(request_signature) = &request_signature {
+        signed = signed.with_request_signature(request_signature);
+    }
+
+    let signature = hex::encode(signer.sign(&signed.message()).to_bytes());
+    parts.headers.insert(
+        SERVER_KEY_HEADER,
+        HeaderValue::from_str(signer.key_id()).expect("hex is a valid header value"),
+    );
+    parts.headers.insert(
+        SERVER_SIGNATURE_HEADER,
+        HeaderValue::from_str(&signature).expect("hex is a valid header value"),
+    );
+
+    Response::from_parts(parts, body)
+}
+
+// Bodies clients parse and act upon, which are built in memory anyway.
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"))
+}
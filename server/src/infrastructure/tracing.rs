@@ -16,8 +16,8 @@ use tracing_log::LogTracer;
 use tracing_subscriber::{EnvFilter, Layer, Registry, fmt, layer::SubscriberExt};
 use uuid::Uuid;
 
-const CORRELATION_ID_HEADER: &str = "x-correlation-id";
-const REQUEST_ID_HEADER: &str = "x-request-id";
+pub(crate) const CORRELATION_ID_HEADER: &str = "x-correlation-id";
+pub(crate) const REQUEST_ID_HEADER: &str = "x-request-id";
 
 // This kind of extension trait pattern is a bias inherited from when I was a C# developer,
 // which literally has the concept of extension methods.
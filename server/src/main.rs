@@ -3,11 +3,14 @@ use file_server_server::{infrastructure, server};
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     infrastructure::init_infrastructure().await;
-    let (server, listener) = server::init_server().await?;
+    let (server, listener, background_tasks) = server::init_server().await?;
 
     axum::serve(listener, server)
         .with_graceful_shutdown(infrastructure::graceful_shutdown_signal())
         .await?;
 
+    // Background tasks are stopped only once `axum` drained in-flight requests.
+    background_tasks.shutdown().await;
+
     Ok(())
 }
@@ -1,33 +1,553 @@
-use crate::repositories::FileMerkleTreeRow;
-use file_server_library::models::Hash32;
-use std::collections::HashMap;
+use crate::repositories::{FileMerkleTreeRow, LogEntryRow, ScrubFindingRow, UploadSessionRow};
+use chrono::{DateTime, Utc};
+use file_server_library::models::{Hash32, Proof};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt::Display, str::FromStr};
 use uuid::Uuid;
 
 pub type FileName = String;
-pub type FileContent = Vec<u8>;
+
+// Objects under these prefixes belong to upload sessions and to uploads in flight, so files
+// cannot be named after them.
+const SESSIONS_PREFIX: &str = ".sessions/";
+const STAGING_PREFIX: &str = ".staging/";
+
+/// Uploads are written to a unique staging object first, since the blob they end up in is only
+/// known once the contents are hashed.
+pub fn staging_name() -> String {
+    format!("{STAGING_PREFIX}{}", Uuid::new_v4())
+}
+
+/// The upload session a stored chunk belongs to, `None` for objects that are not chunks.
+pub fn chunk_session_id(name: &str) -> Option<Uuid> {
+    let (id, _) = name.strip_prefix(SESSIONS_PREFIX)?.split_once('/')?;
+    id.parse().ok()
+}
 
 pub struct FileMetadata {
     pub name: String,
     pub index: usize,
+    /// Media type announced by the uploader, if any.
+    pub content_type: Option<String>,
+}
+
+impl FileMetadata {
+    pub fn has_reserved_name(&self) -> bool {
+        [SESSIONS_PREFIX, STAGING_PREFIX]
+            .iter()
+            .any(|prefix| self.name.starts_with(prefix))
+    }
+}
+
+/// Everything needed to describe a stored file without reading its contents.
+#[derive(Clone, Debug)]
+pub struct FileDescriptor {
+    pub name: FileName,
+    pub hash: Hash32,
+    pub size: u64,
+    pub content_type: String,
+}
+
+/// A file of an upload as listed to clients, gaps left by unused indexes are not files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileEntry {
+    pub index: usize,
+    pub name: FileName,
+    pub hash: Hash32,
+    pub size: u64,
+    pub content_type: String,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+/// Slice of the files of an upload, along with how many there are in total.
+#[derive(Clone, Debug)]
+pub struct FilePage {
+    pub files: Vec<FileEntry>,
+    pub total: usize,
+}
+
+/// Overview of an upload as listed to its owner.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UploadSummary {
+    pub id: Uuid,
+    pub version: u32,
+    pub previous_version: Option<u32>,
+    pub state: UploadState,
+    pub root: Option<Hash32>,
+    pub file_count: usize,
+    pub total_size: u64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Optional filters of upload listings, `created_after` is inclusive and `created_before`
+/// exclusive.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UploadFilter {
+    pub state: Option<UploadState>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+/// Optional filters of audit log listings, `occurred_after` is inclusive and `occurred_before`
+/// exclusive. `action` matches entries of that action only, e.g. `POST /api/v1/{id}/upload`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AuditFilter {
+    pub upload_id: Option<Uuid>,
+    pub action: Option<String>,
+    pub occurred_after: Option<DateTime<Utc>>,
+    pub occurred_before: Option<DateTime<Utc>>,
+}
+
+/// Position in an upload listing. Uploads are listed newest first, so the next page starts
+/// right after the last upload returned, which stays stable while new uploads are created.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UploadCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+// Cursors are opaque to clients, hex keeps them URL safe.
+impl Display for UploadCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let raw = format!("{}:{}", self.created_at.timestamp_millis(), self.id);
+        write!(f, "{}", hex::encode(raw))
+    }
+}
+
+impl FromStr for UploadCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let raw = String::from_utf8(hex::decode(s)?)?;
+        let (millis, id) = raw
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("malformed cursor"))?;
+
+        Ok(Self {
+            created_at: DateTime::from_timestamp_millis(millis.parse()?)
+                .ok_or_else(|| anyhow::anyhow!("bad cursor timestamp {millis}"))?,
+            id: id.parse()?,
+        })
+    }
+}
+
+impl From<&UploadSummary> for UploadCursor {
+    fn from(summary: &UploadSummary) -> Self {
+        Self {
+            created_at: summary.created_at,
+            id: summary.id,
+        }
+    }
+}
+
+/// Slice of the uploads of a client, `next_cursor` is missing on the last one.
+#[derive(Clone, Debug)]
+pub struct UploadPage {
+    pub uploads: Vec<UploadSummary>,
+    pub next_cursor: Option<UploadCursor>,
+}
+
+/// Lifecycle of an upload. Uploads start as `Initiated`, become `Completed` once the root is
+/// computed and end up `Expired` when the reaper collects them after their TTL.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UploadState {
+    Initiated,
+    Completed,
+    Expired,
+}
+
+impl Display for UploadState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UploadState::Initiated => write!(f, "initiated"),
+            UploadState::Completed => write!(f, "completed"),
+            UploadState::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+impl FromStr for UploadState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "initiated" => Ok(UploadState::Initiated),
+            "completed" => Ok(UploadState::Completed),
+            "expired" => Ok(UploadState::Expired),
+            other => Err(anyhow::anyhow!("unknown upload state {other}")),
+        }
+    }
+}
+
+/// Problems the scrubber finds with the stored contents of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScrubFindingKind {
+    /// The blob of the leaf hash is not in the storage.
+    Missing,
+    /// The blob contents do not hash to the leaf hash anymore.
+    Corrupted,
+}
+
+impl Display for ScrubFindingKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScrubFindingKind::Missing => write!(f, "missing"),
+            ScrubFindingKind::Corrupted => write!(f, "corrupted"),
+        }
+    }
+}
+
+impl FromStr for ScrubFindingKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "missing" => Ok(ScrubFindingKind::Missing),
+            "corrupted" => Ok(ScrubFindingKind::Corrupted),
+            other => Err(anyhow::anyhow!("unknown scrub finding kind {other}")),
+        }
+    }
+}
+
+/// Findings of the scrubber, newest first, along with the last upload the current pass checked.
+#[derive(Clone, Debug)]
+pub struct ScrubReport {
+    pub findings: Vec<ScrubFindingRow>,
+    pub cursor: Option<UploadCursor>,
+}
+
+/// An object stored for an upload that nothing refers to anymore.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrphanObject {
+    pub upload_id: Uuid,
+    pub name: String,
+    pub size: u64,
+    pub last_modified: DateTime<Utc>,
+}
+
+/// Orphans found in a batch of uploads. `deleted` and `failed` stay at zero on dry runs,
+/// `next_cursor` is missing once every upload was checked.
+#[derive(Clone, Debug, Default)]
+pub struct OrphanReport {
+    pub uploads_checked: usize,
+    pub orphans: Vec<OrphanObject>,
+    pub deleted: usize,
+    pub failed: usize,
+    pub next_cursor: Option<UploadCursor>,
+}
+
+/// State of the transparency log at `size` entries, signed by the server at `timestamp`. The
+/// signature covers `file_server_library::transparency::log_head_message`.
+#[derive(Clone, Debug)]
+pub struct LogHead {
+    pub size: u64,
+    pub root: Hash32,
+    pub timestamp: DateTime<Utc>,
+    pub key_id: String,
+    pub signature: String,
+}
+
+/// Proof that `entry` is part of the log described by `head`.
+#[derive(Clone, Debug)]
+pub struct LogInclusionProof {
+    pub head: LogHead,
+    pub entry: LogEntryRow,
+    pub proof: Vec<Hash32>,
+}
+
+/// Proof that the log described by `second` extends the one described by `first`.
+#[derive(Clone, Debug)]
+pub struct LogConsistencyProof {
+    pub first: LogHead,
+    pub second: LogHead,
+    pub proof: Vec<Hash32>,
+}
+
+/// Limits of a client, missing ones are unlimited. Stored bytes add up the files of every
+/// upload the client owns, files uploaded twice count twice even though they share a blob.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct QuotaLimits {
+    pub max_stored_bytes: Option<u64>,
+    pub max_files_per_upload: Option<u64>,
+    pub max_file_size_bytes: Option<u64>,
+    pub max_open_uploads: Option<u64>,
+}
+
+impl QuotaLimits {
+    /// Limits missing here are taken from `defaults`.
+    pub fn or(self, defaults: QuotaLimits) -> Self {
+        Self {
+            max_stored_bytes: self.max_stored_bytes.or(defaults.max_stored_bytes),
+            max_files_per_upload: self.max_files_per_upload.or(defaults.max_files_per_upload),
+            max_file_size_bytes: self.max_file_size_bytes.or(defaults.max_file_size_bytes),
+            max_open_uploads: self.max_open_uploads.or(defaults.max_open_uploads),
+        }
+    }
+
+    pub fn get(&self, quota: Quota) -> Option<u64> {
+        match quota {
+            Quota::StoredBytes => self.max_stored_bytes,
+            Quota::FilesPerUpload => self.max_files_per_upload,
+            Quota::FileSize => self.max_file_size_bytes,
+            Quota::OpenUploads => self.max_open_uploads,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quota {
+    StoredBytes,
+    FilesPerUpload,
+    FileSize,
+    OpenUploads,
+}
+
+// Named after the limits, so clients can match them with the usage they are shown.
+impl Display for Quota {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Quota::StoredBytes => write!(f, "max_stored_bytes"),
+            Quota::FilesPerUpload => write!(f, "max_files_per_upload"),
+            Quota::FileSize => write!(f, "max_file_size_bytes"),
+            Quota::OpenUploads => write!(f, "max_open_uploads"),
+        }
+    }
+}
+
+/// A request that would take the usage of `quota` to `used`, past its `limit`. Uploads are cut
+/// as soon as they cross the limit, so `used` only counts what was received until then.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuotaViolation {
+    pub quota: Quota,
+    pub limit: u64,
+    pub used: u64,
+}
+
+impl Display for QuotaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} of {} exceeded ({})",
+            self.quota, self.limit, self.used
+        )
+    }
+}
+
+impl std::error::Error for QuotaViolation {}
+
+/// Where a client stands against its limits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub stored_bytes: u64,
+    pub open_uploads: u64,
+    pub limits: QuotaLimits,
+}
+
+/// Progress of an upload, as it happens. Files whose contents were already stored skip the
+/// transfer, both of their events are sent once they are added.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UploadEvent {
+    /// The contents of a file were received and stored.
+    FileReceived {
+        index: usize,
+        name: String,
+        size: u64,
+    },
+    /// The leaf hash of a file is known.
+    HashComputed {
+        index: usize,
+        name: String,
+        hash: Hash32,
+    },
+    Completed {
+        version: u32,
+        root: Hash32,
+    },
+    Deleted,
+    /// The upload was left unfinished for too long and its contents removed.
+    Expired,
+}
+
+impl UploadEvent {
+    /// Nothing happens to the upload after these.
+    pub fn is_last(&self) -> bool {
+        matches!(
+            self,
+            UploadEvent::Completed { .. } | UploadEvent::Deleted | UploadEvent::Expired
+        )
+    }
+}
+
+/// Upload lifecycle events clients can be notified of through webhooks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WebhookEvent {
+    Initiated,
+    Completed,
+    /// The upload was deleted before being completed.
+    Aborted,
+    Expired,
+    /// The scrubber found the stored contents of a file missing or corrupted.
+    Corrupted,
+}
+
+impl Display for WebhookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebhookEvent::Initiated => write!(f, "upload.initiated"),
+            WebhookEvent::Completed => write!(f, "upload.completed"),
+            WebhookEvent::Aborted => write!(f, "upload.aborted"),
+            WebhookEvent::Expired => write!(f, "upload.expired"),
+            WebhookEvent::Corrupted => write!(f, "upload.corrupted"),
+        }
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "upload.initiated" => Ok(WebhookEvent::Initiated),
+            "upload.completed" => Ok(WebhookEvent::Completed),
+            "upload.aborted" => Ok(WebhookEvent::Aborted),
+            "upload.expired" => Ok(WebhookEvent::Expired),
+            "upload.corrupted" => Ok(WebhookEvent::Corrupted),
+            other => Err(anyhow::anyhow!("unknown webhook event {other}")),
+        }
+    }
+}
+
+/// Deliveries stay `Pending` while they are retried, and end up `Failed` once they run out of
+/// attempts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DeliveryState {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+impl Display for DeliveryState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeliveryState::Pending => write!(f, "pending"),
+            DeliveryState::Delivered => write!(f, "delivered"),
+            DeliveryState::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl FromStr for DeliveryState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(DeliveryState::Pending),
+            "delivered" => Ok(DeliveryState::Delivered),
+            "failed" => Ok(DeliveryState::Failed),
+            other => Err(anyhow::anyhow!("unknown delivery state {other}")),
+        }
+    }
+}
+
+/// Container of upload archives. Both are streamed without compression, since the contents
+/// are already stored as they were uploaded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    #[default]
+    Tar,
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "application/x-tar",
+            ArchiveFormat::Zip => "application/zip",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tar" => Ok(ArchiveFormat::Tar),
+            "zip" => Ok(ArchiveFormat::Zip),
+            other => Err(anyhow::anyhow!("unknown archive format {other}")),
+        }
+    }
+}
+
+/// `manifest.json` of upload archives, everything needed to verify the archived files against
+/// the root without asking the server again.
+#[derive(Clone, Serialize)]
+pub struct ArchiveManifest {
+    pub id: Uuid,
+    pub version: u32,
+    pub root: String,
+    pub files: Vec<ArchiveManifestEntry>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ArchiveManifestEntry {
+    pub index: usize,
+    pub name: FileName,
+    /// Location of the file within the archive.
+    pub path: String,
+    pub leaf_hash: String,
+    pub proof: Proof,
 }
 
+/// Versions start at 1. Every completed version is kept, so the tree of a version is either the
+/// latest one or a snapshot taken when that version was completed.
 #[derive(Clone)]
 pub struct FileMerkleTree {
     id: Uuid,
+    version: u32,
+    previous_version: Option<u32>,
+    /// Key of the client that initiated the upload.
+    owner: String,
     order: Vec<FileName>,
     files: HashMap<FileName, Hash32>,
     leaf_hashes: Vec<Hash32>,
+    // Per file details, indexed like `order`. Trees stored before they were recorded have
+    // shorter vectors.
+    sizes: Vec<u64>,
+    content_types: Vec<String>,
+    uploaded_at: Vec<DateTime<Utc>>,
     root: Option<Hash32>,
+    state: UploadState,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
 }
 
 impl Default for FileMerkleTree {
     fn default() -> Self {
+        let now = Utc::now();
         Self {
             id: Uuid::new_v4(),
+            version: 1,
+            previous_version: None,
+            owner: String::new(),
             order: Vec::new(),
             files: HashMap::new(),
             leaf_hashes: Vec::new(),
+            sizes: Vec::new(),
+            content_types: Vec::new(),
+            uploaded_at: Vec::new(),
             root: None,
+            state: UploadState::Initiated,
+            created_at: now,
+            updated_at: now,
         }
     }
 }
@@ -36,10 +556,19 @@ impl From<FileMerkleTreeRow> for FileMerkleTree {
     fn from(row: FileMerkleTreeRow) -> Self {
         Self {
             id: row.id,
+            version: row.version,
+            previous_version: row.previous_version,
+            owner: row.owner,
             order: row.order,
             files: row.files,
             leaf_hashes: row.leaf_hashes,
+            sizes: row.sizes,
+            content_types: row.content_types,
+            uploaded_at: row.uploaded_at,
             root: row.root,
+            state: row.state,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
         }
     }
 }
@@ -48,44 +577,332 @@ impl From<FileMerkleTree> for FileMerkleTreeRow {
     fn from(val: FileMerkleTree) -> Self {
         FileMerkleTreeRow {
             id: val.id,
+            version: val.version,
+            previous_version: val.previous_version,
+            owner: val.owner,
             order: val.order,
             files: val.files,
             leaf_hashes: val.leaf_hashes,
+            sizes: val.sizes,
+            content_types: val.content_types,
+            uploaded_at: val.uploaded_at,
             root: val.root,
+            state: val.state,
+            created_at: val.created_at,
+            updated_at: val.updated_at,
         }
     }
 }
 
 impl FileMerkleTree {
+    pub fn new(owner: &str) -> Self {
+        Self {
+            owner: owner.to_owned(),
+            ..Default::default()
+        }
+    }
+
     pub fn id(&self) -> Uuid {
         self.id
     }
 
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Version this one was derived from, `None` for the first one.
+    pub fn previous_version(&self) -> Option<u32> {
+        self.previous_version
+    }
+
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
     pub fn leafs(&self) -> Vec<Hash32> {
         self.leaf_hashes.clone()
     }
 
+    pub fn state(&self) -> UploadState {
+        self.state
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    /// Only set while the upload is completed, adding files clears it.
+    pub fn root(&self) -> Option<Hash32> {
+        self.root
+    }
+
     pub fn get_file_name_by_index(&self, index: usize) -> Option<String> {
         self.order.get(index).cloned()
     }
 
+    /// Unused indexes have empty names, so they never match.
+    pub fn get_index_by_file_name(&self, name: &str) -> Option<usize> {
+        if name.is_empty() {
+            return None;
+        }
+
+        self.order.iter().position(|file_name| file_name == name)
+    }
+
+    pub fn get_leaf_hash_by_index(&self, index: usize) -> Option<Hash32> {
+        self.leaf_hashes.get(index).copied()
+    }
+
     pub fn contains_file(&self, name: &str) -> bool {
         self.files.contains_key(name)
     }
 
-    pub fn add(&mut self, index: usize, name: &str, hash: &Hash32) {
+    /// Whether a file named `name` is stored at another index than `index`. Names address files
+    /// as well, so each one can only be held by one index.
+    pub fn holds_name_elsewhere(&self, index: usize, name: &str) -> bool {
+        self.get_index_by_file_name(name)
+            .is_some_and(|existing| existing != index)
+    }
+
+    /// Files in index order. Details missing from older trees fall back to defaults.
+    pub fn files(&self) -> Vec<FileEntry> {
+        self.order
+            .iter()
+            .zip(&self.leaf_hashes)
+            .enumerate()
+            .filter(|(_, (name, _))| !name.is_empty())
+            .map(|(index, (name, hash))| FileEntry {
+                index,
+                name: name.clone(),
+                hash: *hash,
+                size: self.sizes.get(index).copied().unwrap_or_default(),
+                content_type: self
+                    .content_types
+                    .get(index)
+                    .cloned()
+                    .unwrap_or_else(|| mime::APPLICATION_OCTET_STREAM.to_string()),
+                uploaded_at: self
+                    .uploaded_at
+                    .get(index)
+                    .copied()
+                    .unwrap_or(self.created_at),
+            })
+            .collect()
+    }
+
+    pub fn summary(&self) -> UploadSummary {
+        let files = self.files();
+
+        UploadSummary {
+            id: self.id,
+            version: self.version,
+            previous_version: self.previous_version,
+            state: self.state,
+            root: self.root,
+            file_count: files.len(),
+            total_size: files.iter().map(|file| file.size).sum(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+
+    pub fn add(&mut self, index: usize, name: &str, hash: &Hash32, size: u64, content_type: &str) {
+        let now = Utc::now();
+
         if self.order.len() <= index {
             self.order.resize(index + 1, String::new());
             self.leaf_hashes.resize(index + 1, Hash32::empty());
         }
+        // Resized on their own, since they can be shorter than `order` on older trees.
+        if self.sizes.len() <= index {
+            self.sizes.resize(index + 1, 0);
+            self.content_types.resize(index + 1, String::new());
+            self.uploaded_at.resize(index + 1, self.created_at);
+        }
+
+        // The file previously stored at the index goes away along with its name.
+        let replaced = std::mem::replace(&mut self.order[index], name.to_owned());
+        if replaced != name {
+            self.files.remove(&replaced);
+        }
+        self.files.insert(name.to_owned(), *hash);
 
-        self.order[index] = name.to_owned();
         self.leaf_hashes[index] = *hash;
+        self.sizes[index] = size;
+        self.content_types[index] = content_type.to_owned();
+        self.uploaded_at[index] = now;
 
         self.root = None;
+        self.updated_at = now;
     }
 
     pub fn complete(&mut self, root: Hash32) {
         self.root = Some(root);
+        self.state = UploadState::Completed;
+        self.updated_at = Utc::now();
+    }
+
+    /// Next version of a completed tree, starting with the same files and waiting to be
+    /// completed again.
+    pub fn next_version(&self) -> Self {
+        Self {
+            version: self.version + 1,
+            previous_version: Some(self.version),
+            root: None,
+            state: UploadState::Initiated,
+            updated_at: Utc::now(),
+            ..self.clone()
+        }
+    }
+
+    pub fn expire(&mut self) {
+        self.state = UploadState::Expired;
+        self.updated_at = Utc::now();
+    }
+}
+
+/// Resumable upload of a single file. Every `append` stores a chunk of the file, the file itself
+/// is only assembled (and hashed) once the session is finalized.
+#[derive(Clone, Debug)]
+pub struct UploadSession {
+    id: Uuid,
+    upload_id: Uuid,
+    name: FileName,
+    index: usize,
+    content_type: Option<String>,
+    length: Option<u64>,
+    offset: u64,
+    chunk_offsets: Vec<u64>,
+    chunk_ids: Vec<Uuid>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<UploadSessionRow> for UploadSession {
+    fn from(row: UploadSessionRow) -> Self {
+        Self {
+            id: row.id,
+            upload_id: row.upload_id,
+            name: row.name,
+            index: row.index,
+            content_type: row.content_type,
+            length: row.length,
+            offset: row.offset,
+            chunk_offsets: row.chunk_offsets,
+            chunk_ids: row.chunk_ids,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+impl From<UploadSession> for UploadSessionRow {
+    fn from(val: UploadSession) -> Self {
+        UploadSessionRow {
+            id: val.id,
+            upload_id: val.upload_id,
+            name: val.name,
+            index: val.index,
+            content_type: val.content_type,
+            length: val.length,
+            offset: val.offset,
+            chunk_offsets: val.chunk_offsets,
+            chunk_ids: val.chunk_ids,
+            created_at: val.created_at,
+            updated_at: val.updated_at,
+        }
+    }
+}
+
+impl UploadSession {
+    pub fn new(upload_id: Uuid, metadata: FileMetadata, length: Option<u64>) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            upload_id,
+            name: metadata.name,
+            index: metadata.index,
+            content_type: metadata.content_type,
+            length,
+            offset: 0,
+            chunk_offsets: Vec::new(),
+            chunk_ids: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn upload_id(&self) -> Uuid {
+        self.upload_id
+    }
+
+    pub fn length(&self) -> Option<u64> {
+        self.length
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn metadata(&self) -> FileMetadata {
+        FileMetadata {
+            name: self.name.clone(),
+            index: self.index,
+            content_type: self.content_type.clone(),
+        }
+    }
+
+    /// Bytes that can still be appended, `None` when the length was not announced.
+    pub fn remaining(&self) -> Option<u64> {
+        self.length.map(|length| length.saturating_sub(self.offset))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.length.is_none_or(|length| length == self.offset)
+    }
+
+    /// Name the chunk `chunk_id` is stored under when appended at the current offset.
+    pub fn next_chunk_name(&self, chunk_id: Uuid) -> String {
+        self.chunk_name(self.offset, chunk_id)
+    }
+
+    pub fn chunk_names(&self) -> Vec<String> {
+        self.chunk_offsets
+            .iter()
+            .enumerate()
+            .map(|(position, offset)| {
+                let chunk_id = self.chunk_ids.get(position).copied().unwrap_or_default();
+                self.chunk_name(*offset, chunk_id)
+            })
+            .collect()
+    }
+
+    /// Whether the chunk `chunk_id` was appended to the session.
+    pub fn holds_chunk(&self, chunk_id: Uuid) -> bool {
+        self.chunk_ids.contains(&chunk_id)
+    }
+
+    pub fn append(&mut self, chunk_id: Uuid, size: u64) {
+        // Chunks appended before they had ids keep the nil one.
+        self.chunk_ids.resize(self.chunk_offsets.len(), Uuid::nil());
+        self.chunk_ids.push(chunk_id);
+        self.chunk_offsets.push(self.offset);
+        self.offset += size;
+        self.updated_at = Utc::now();
+    }
+
+    // Chunks live next to the files of the upload, under a prefix clients cannot name files with.
+    // Every append stores its chunk under a new id, so appends racing at the same offset never
+    // write over each other and the one that loses can drop its own.
+    fn chunk_name(&self, offset: u64, chunk_id: Uuid) -> String {
+        if chunk_id.is_nil() {
+            format!("{SESSIONS_PREFIX}{}/{:020}", self.id, offset)
+        } else {
+            format!("{SESSIONS_PREFIX}{}/{:020}-{}", self.id, offset, chunk_id)
+        }
     }
 }
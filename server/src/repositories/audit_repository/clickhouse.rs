@@ -0,0 +1,244 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use clickhouse::{Client, Row};
+use file_server_library::models::Hash32;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    models::AuditFilter,
+    repositories::{AuditEntryRow, AuditRepository, ClickhouseConfig},
+};
+
+const AUDIT_LOG_TABLE_NAME: &str = "audit_log";
+
+// Entries are only ever inserted. Every insert carries its owner and sequence as deduplication
+// token, so of several instances appending to the same chain at the same time only the first one
+// is stored, the table drops the others (see `non_replicated_deduplication_window` in the
+// schema). Reading the sequence back tells which one it was.
+pub struct ClickhouseAuditRepository {
+    client: Client,
+}
+
+impl ClickhouseAuditRepository {
+    pub fn new(config: ClickhouseConfig) -> Self {
+        let client = Client::default()
+            .with_url(config.database_url.to_owned())
+            .with_user(config.username.to_owned())
+            .with_password(config.password.to_owned())
+            .with_database(config.database_name.to_owned())
+            .with_option("send_progress_in_http_headers", "1");
+
+        Self::new_from_client(client)
+    }
+
+    pub fn new_from_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[derive(Serialize, Deserialize, Row)]
+struct ClickhouseAuditEntryRow {
+    owner: String,
+    sequence: u64,
+    action: String,
+    #[serde(with = "clickhouse::serde::uuid::option")]
+    upload_id: Option<Uuid>,
+    file_index: Option<u64>,
+    name: Option<String>,
+    hash: Option<String>,
+    status: u16,
+    request_id: Option<String>,
+    correlation_id: Option<String>,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    occurred_at: DateTime<Utc>,
+    previous_hash: String,
+    entry_hash: String,
+}
+
+impl From<AuditEntryRow> for ClickhouseAuditEntryRow {
+    fn from(x: AuditEntryRow) -> Self {
+        Self {
+            owner: x.owner,
+            sequence: x.sequence,
+            action: x.action,
+            upload_id: x.upload_id,
+            file_index: x.index,
+            name: x.name,
+            hash: x.hash,
+            status: x.status,
+            request_id: x.request_id,
+            correlation_id: x.correlation_id,
+            occurred_at: x.occurred_at,
+            previous_hash: x.previous_hash.to_hex(),
+            entry_hash: x.entry_hash.to_hex(),
+        }
+    }
+}
+
+impl TryFrom<ClickhouseAuditEntryRow> for AuditEntryRow {
+    type Error = anyhow::Error;
+
+    fn try_from(x: ClickhouseAuditEntryRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            owner: x.owner,
+            sequence: x.sequence,
+            action: x.action,
+            upload_id: x.upload_id,
+            index: x.file_index,
+            name: x.name,
+            hash: x.hash,
+            status: x.status,
+            request_id: x.request_id,
+            correlation_id: x.correlation_id,
+            occurred_at: x.occurred_at,
+            previous_hash: parse_hash(&x.previous_hash)?,
+            entry_hash: parse_hash(&x.entry_hash)?,
+        })
+    }
+}
+
+fn parse_hash(hash: &str) -> anyhow::Result<Hash32> {
+    Hash32::from_hex(hash).map_err(|e| anyhow::anyhow!("bad audit hash {hash}: {e}"))
+}
+
+#[async_trait]
+impl AuditRepository for ClickhouseAuditRepository {
+    async fn append(&self, entry: AuditEntryRow) -> anyhow::Result<bool> {
+        let owner = entry.owner.clone();
+        let sequence = entry.sequence;
+        let entry_hash = entry.entry_hash.to_hex();
+
+        let mut insert = self
+            .client
+            .clone()
+            .with_option(
+                "insert_deduplication_token",
+                format!("{AUDIT_LOG_TABLE_NAME}-{owner}-{sequence}"),
+            )
+            .insert::<ClickhouseAuditEntryRow>(AUDIT_LOG_TABLE_NAME)
+            .await?;
+
+        insert.write(&entry.into()).await?;
+        insert.end().await?;
+
+        let sql = format!(
+            "SELECT entry_hash
+               FROM {AUDIT_LOG_TABLE_NAME}
+              WHERE owner = ? AND sequence = ?",
+        );
+        let stored = self
+            .client
+            .query(&sql)
+            .bind(&owner)
+            .bind(sequence)
+            .fetch_all::<String>()
+            .await?;
+
+        Ok(stored == [entry_hash])
+    }
+
+    async fn latest(&self, owner: &str) -> anyhow::Result<Option<AuditEntryRow>> {
+        let sql = format!(
+            "SELECT
+                 owner,
+                 sequence,
+                 action,
+                 upload_id,
+                 file_index,
+                 name,
+                 hash,
+                 status,
+                 request_id,
+                 correlation_id,
+                 occurred_at,
+                 previous_hash,
+                 entry_hash
+               FROM {AUDIT_LOG_TABLE_NAME}
+              WHERE owner = ?
+              ORDER BY sequence DESC
+              LIMIT 1",
+        );
+
+        let row = self
+            .client
+            .query(&sql)
+            .bind(owner)
+            .fetch_optional::<ClickhouseAuditEntryRow>()
+            .await?;
+
+        row.map(TryInto::try_into).transpose()
+    }
+
+    async fn list(
+        &self,
+        owner: &str,
+        filter: &AuditFilter,
+        after: Option<u64>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<AuditEntryRow>> {
+        let mut conditions = vec!["owner = ?"];
+        if filter.upload_id.is_some() {
+            conditions.push("upload_id = ?");
+        }
+        if filter.action.is_some() {
+            conditions.push("action = ?");
+        }
+        if filter.occurred_after.is_some() {
+            conditions.push("occurred_at >= fromUnixTimestamp64Milli(?)");
+        }
+        if filter.occurred_before.is_some() {
+            conditions.push("occurred_at < fromUnixTimestamp64Milli(?)");
+        }
+        if after.is_some() {
+            conditions.push("sequence > ?");
+        }
+
+        let sql = format!(
+            "SELECT
+                 owner,
+                 sequence,
+                 action,
+                 upload_id,
+                 file_index,
+                 name,
+                 hash,
+                 status,
+                 request_id,
+                 correlation_id,
+                 occurred_at,
+                 previous_hash,
+                 entry_hash
+               FROM {AUDIT_LOG_TABLE_NAME}
+              WHERE {}
+              ORDER BY sequence
+              LIMIT ?",
+            conditions.join(" AND "),
+        );
+
+        // Binds follow the order in which conditions were added.
+        let mut query = self.client.query(&sql).bind(owner);
+        if let Some(upload_id) = filter.upload_id {
+            query = query.bind(upload_id);
+        }
+        if let Some(action) = &filter.action {
+            query = query.bind(action);
+        }
+        if let Some(occurred_after) = filter.occurred_after {
+            query = query.bind(occurred_after.timestamp_millis());
+        }
+        if let Some(occurred_before) = filter.occurred_before {
+            query = query.bind(occurred_before.timestamp_millis());
+        }
+        if let Some(after) = after {
+            query = query.bind(after);
+        }
+
+        let rows = query
+            .bind(limit as u64)
+            .fetch_all::<ClickhouseAuditEntryRow>()
+            .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+}
@@ -0,0 +1,77 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+use crate::{
+    models::AuditFilter,
+    repositories::{AuditEntryRow, AuditRepository},
+};
+
+#[derive(Default)]
+pub struct InMemoryAuditRepository {
+    entries: Mutex<HashMap<String, Vec<AuditEntryRow>>>,
+}
+
+#[async_trait]
+impl AuditRepository for InMemoryAuditRepository {
+    async fn append(&self, entry: AuditEntryRow) -> anyhow::Result<bool> {
+        let mut entries = self.entries.lock().await;
+        let chain = entries.entry(entry.owner.clone()).or_default();
+        if chain.iter().any(|stored| stored.sequence == entry.sequence) {
+            return Ok(false);
+        }
+
+        chain.push(entry);
+        Ok(true)
+    }
+
+    async fn latest(&self, owner: &str) -> anyhow::Result<Option<AuditEntryRow>> {
+        let entries = self.entries.lock().await;
+        Ok(entries
+            .get(owner)
+            .and_then(|entries| entries.iter().max_by_key(|entry| entry.sequence))
+            .cloned())
+    }
+
+    async fn list(
+        &self,
+        owner: &str,
+        filter: &AuditFilter,
+        after: Option<u64>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<AuditEntryRow>> {
+        let entries = self.entries.lock().await;
+        let mut rows: Vec<_> = entries
+            .get(owner)
+            .into_iter()
+            .flatten()
+            .filter(|entry| after.is_none_or(|after| entry.sequence > after))
+            .filter(|entry| {
+                filter
+                    .upload_id
+                    .is_none_or(|id| entry.upload_id == Some(id))
+            })
+            .filter(|entry| {
+                filter
+                    .action
+                    .as_deref()
+                    .is_none_or(|action| entry.action == action)
+            })
+            .filter(|entry| {
+                filter
+                    .occurred_after
+                    .is_none_or(|occurred_after| entry.occurred_at >= occurred_after)
+            })
+            .filter(|entry| {
+                filter
+                    .occurred_before
+                    .is_none_or(|occurred_before| entry.occurred_at < occurred_before)
+            })
+            .cloned()
+            .collect();
+
+        rows.sort_by_key(|entry| entry.sequence);
+        rows.truncate(limit);
+        Ok(rows)
+    }
+}
@@ -0,0 +1,55 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use file_server_library::models::Hash32;
+use uuid::Uuid;
+
+use crate::models::AuditFilter;
+
+/// An operation of a client, as recorded in its audit log. Entries of a client are numbered by
+/// `sequence` from zero, and `entry_hash` covers the other fields along with the `entry_hash` of
+/// the previous entry, see `crate::infrastructure::audit_entry_hash`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditEntryRow {
+    pub owner: String,
+    pub sequence: u64,
+    pub action: String,
+    pub upload_id: Option<Uuid>,
+    pub index: Option<u64>,
+    pub name: Option<String>,
+    pub hash: Option<String>,
+    pub status: u16,
+    pub request_id: Option<String>,
+    pub correlation_id: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+    pub previous_hash: Hash32,
+    pub entry_hash: Hash32,
+}
+
+/// Append-only storage of the audit log. Entries are never updated nor deleted.
+#[async_trait]
+pub trait AuditRepository: Send + Sync {
+    /// Stores `entry`, whose sequence must follow the latest one of its owner, and returns
+    /// whether it was stored. It is not when another entry took the sequence first.
+    async fn append(&self, entry: AuditEntryRow) -> anyhow::Result<bool>;
+    /// Returns the entry of `owner` with the highest sequence, if any.
+    async fn latest(&self, owner: &str) -> anyhow::Result<Option<AuditEntryRow>>;
+    /// Returns up to `limit` entries of `owner` matching `filter` in sequence order, starting
+    /// right after the `after` one.
+    async fn list(
+        &self,
+        owner: &str,
+        filter: &AuditFilter,
+        after: Option<u64>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<AuditEntryRow>>;
+}
+
+#[cfg(feature = "in-memory")]
+mod in_memory;
+#[cfg(feature = "in-memory")]
+pub use in_memory::InMemoryAuditRepository;
+
+#[cfg(feature = "persistent")]
+mod clickhouse;
+#[cfg(feature = "persistent")]
+pub use clickhouse::ClickhouseAuditRepository;
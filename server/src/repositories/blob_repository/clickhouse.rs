@@ -0,0 +1,290 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use clickhouse::{Client, Row};
+use file_server_library::models::Hash32;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::repositories::{BlobReferenceRow, BlobRepository, BlobRow, ClickhouseConfig};
+
+const BLOB_TABLE_NAME: &str = "blobs";
+const BLOB_REFERENCE_TABLE_NAME: &str = "blob_references";
+
+// Touching a blob inserts a new row, the table keeps the latest one per hash. References are
+// rows of their own, so counting them never needs a read-modify-write cycle. Adding a reference
+// again inserts another row until the table merges them, so they are counted by file.
+pub struct ClickhouseBlobRepository {
+    client: Client,
+}
+
+impl ClickhouseBlobRepository {
+    pub fn new(config: ClickhouseConfig) -> Self {
+        let client = Client::default()
+            .with_url(config.database_url.to_owned())
+            .with_user(config.username.to_owned())
+            .with_password(config.password.to_owned())
+            .with_database(config.database_name.to_owned())
+            .with_option("send_progress_in_http_headers", "1");
+
+        Self::new_from_client(client)
+    }
+
+    pub fn new_from_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[derive(Serialize, Deserialize, Row)]
+struct ClickhouseBlobTouchRow {
+    hash: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Row)]
+struct ClickhouseBlobRow {
+    hash: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    last_touched_at: DateTime<Utc>,
+    reference_count: u64,
+}
+
+impl TryFrom<ClickhouseBlobRow> for BlobRow {
+    type Error = anyhow::Error;
+
+    fn try_from(x: ClickhouseBlobRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            hash: Hash32::from_hex(&x.hash)
+                .map_err(|e| anyhow::anyhow!("bad blob hash {}: {e}", x.hash))?,
+            references: x.reference_count,
+            updated_at: x.last_touched_at,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Row)]
+struct ClickhouseBlobReferenceRow {
+    hash: String,
+    #[serde(with = "clickhouse::serde::uuid")]
+    upload_id: Uuid,
+    owner: String,
+    name: String,
+    content_type: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    created_at: DateTime<Utc>,
+}
+
+impl From<BlobReferenceRow> for ClickhouseBlobReferenceRow {
+    fn from(x: BlobReferenceRow) -> Self {
+        Self {
+            hash: x.hash.to_hex(),
+            upload_id: x.upload_id,
+            owner: x.owner,
+            name: x.name,
+            content_type: x.content_type,
+            created_at: x.created_at,
+        }
+    }
+}
+
+impl TryFrom<ClickhouseBlobReferenceRow> for BlobReferenceRow {
+    type Error = anyhow::Error;
+
+    fn try_from(x: ClickhouseBlobReferenceRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            hash: Hash32::from_hex(&x.hash)
+                .map_err(|e| anyhow::anyhow!("bad blob hash {}: {e}", x.hash))?,
+            upload_id: x.upload_id,
+            owner: x.owner,
+            name: x.name,
+            content_type: x.content_type,
+            created_at: x.created_at,
+        })
+    }
+}
+
+#[async_trait]
+impl BlobRepository for ClickhouseBlobRepository {
+    async fn get(&self, hash: Hash32) -> anyhow::Result<Option<BlobRow>> {
+        let sql = format!(
+            "SELECT
+                 hash,
+                 max(updated_at) AS last_touched_at,
+                 (SELECT uniqExact(upload_id, name)
+                    FROM {BLOB_REFERENCE_TABLE_NAME}
+                   WHERE hash = ?) AS reference_count
+               FROM {BLOB_TABLE_NAME}
+              WHERE hash = ?
+              GROUP BY hash",
+        );
+
+        let row = self
+            .client
+            .query(&sql)
+            .bind(hash.to_hex())
+            .bind(hash.to_hex())
+            .fetch_optional::<ClickhouseBlobRow>()
+            .await?;
+
+        row.map(TryInto::try_into).transpose()
+    }
+
+    async fn touch(&self, hash: Hash32) -> anyhow::Result<()> {
+        let mut insert = self
+            .client
+            .insert::<ClickhouseBlobTouchRow>(BLOB_TABLE_NAME)
+            .await?;
+
+        insert
+            .write(&ClickhouseBlobTouchRow {
+                hash: hash.to_hex(),
+                updated_at: Utc::now(),
+            })
+            .await?;
+        insert.end().await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, hash: Hash32) -> anyhow::Result<()> {
+        let sql = format!("ALTER TABLE {BLOB_TABLE_NAME} DELETE WHERE hash = ?");
+
+        self.client
+            .clone()
+            .with_option("mutations_sync", "1")
+            .query(&sql)
+            .bind(hash.to_hex())
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_reference(
+        &self,
+        upload_id: Uuid,
+        name: &str,
+        hash: Hash32,
+    ) -> anyhow::Result<Option<BlobReferenceRow>> {
+        let sql = format!(
+            "SELECT
+                 hash,
+                 upload_id,
+                 owner,
+                 name,
+                 content_type,
+                 created_at
+               FROM {BLOB_REFERENCE_TABLE_NAME}
+              WHERE upload_id = ?
+                AND name = ?
+                AND hash = ?
+              ORDER BY created_at DESC
+              LIMIT 1",
+        );
+
+        let row = self
+            .client
+            .query(&sql)
+            .bind(upload_id)
+            .bind(name)
+            .bind(hash.to_hex())
+            .fetch_optional::<ClickhouseBlobReferenceRow>()
+            .await?;
+
+        row.map(TryInto::try_into).transpose()
+    }
+
+    async fn add_reference(&self, reference: BlobReferenceRow) -> anyhow::Result<()> {
+        let mut insert = self
+            .client
+            .insert::<ClickhouseBlobReferenceRow>(BLOB_REFERENCE_TABLE_NAME)
+            .await?;
+
+        insert.write(&reference.into()).await?;
+        insert.end().await?;
+
+        Ok(())
+    }
+
+    async fn is_referenced_by(&self, hash: Hash32, owner: &str) -> anyhow::Result<bool> {
+        let sql =
+            format!("SELECT count() FROM {BLOB_REFERENCE_TABLE_NAME} WHERE hash = ? AND owner = ?");
+
+        let references = self
+            .client
+            .query(&sql)
+            .bind(hash.to_hex())
+            .bind(owner)
+            .fetch_one::<u64>()
+            .await?;
+
+        Ok(references > 0)
+    }
+
+    async fn remove_reference(
+        &self,
+        upload_id: Uuid,
+        name: &str,
+        hash: Hash32,
+    ) -> anyhow::Result<()> {
+        let sql = format!(
+            "ALTER TABLE {BLOB_REFERENCE_TABLE_NAME} DELETE
+              WHERE upload_id = ? AND name = ? AND hash = ?"
+        );
+
+        self.client
+            .clone()
+            .with_option("mutations_sync", "1")
+            .query(&sql)
+            .bind(upload_id)
+            .bind(name)
+            .bind(hash.to_hex())
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_references(&self, upload_id: Uuid) -> anyhow::Result<()> {
+        let sql = format!("ALTER TABLE {BLOB_REFERENCE_TABLE_NAME} DELETE WHERE upload_id = ?");
+
+        self.client
+            .clone()
+            .with_option("mutations_sync", "1")
+            .query(&sql)
+            .bind(upload_id)
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_unreferenced(
+        &self,
+        updated_before: DateTime<Utc>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<BlobRow>> {
+        let sql = format!(
+            "SELECT
+                 hash,
+                 max(updated_at) AS last_touched_at,
+                 toUInt64(0) AS reference_count
+               FROM {BLOB_TABLE_NAME}
+              WHERE hash NOT IN (SELECT hash FROM {BLOB_REFERENCE_TABLE_NAME})
+              GROUP BY hash
+             HAVING last_touched_at < fromUnixTimestamp64Milli(?)
+              ORDER BY last_touched_at
+              LIMIT ?",
+        );
+
+        let rows = self
+            .client
+            .query(&sql)
+            .bind(updated_before.timestamp_millis())
+            .bind(limit as u64)
+            .fetch_all::<ClickhouseBlobRow>()
+            .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+}
@@ -0,0 +1,126 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use file_server_library::models::Hash32;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::repositories::{BlobReferenceRow, BlobRepository, BlobRow};
+
+#[derive(Default)]
+struct Blobs {
+    updated_at: HashMap<Hash32, DateTime<Utc>>,
+    references: Vec<BlobReferenceRow>,
+}
+
+impl Blobs {
+    fn row(&self, hash: Hash32, updated_at: DateTime<Utc>) -> BlobRow {
+        BlobRow {
+            hash,
+            references: self.references.iter().filter(|r| r.hash == hash).count() as u64,
+            updated_at,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct InMemoryBlobRepository {
+    blobs: Mutex<Blobs>,
+}
+
+#[async_trait]
+impl BlobRepository for InMemoryBlobRepository {
+    async fn get(&self, hash: Hash32) -> anyhow::Result<Option<BlobRow>> {
+        let blobs = self.blobs.lock().await;
+        Ok(blobs
+            .updated_at
+            .get(&hash)
+            .map(|updated_at| blobs.row(hash, *updated_at)))
+    }
+
+    async fn touch(&self, hash: Hash32) -> anyhow::Result<()> {
+        let mut blobs = self.blobs.lock().await;
+        blobs.updated_at.insert(hash, Utc::now());
+        Ok(())
+    }
+
+    async fn delete(&self, hash: Hash32) -> anyhow::Result<()> {
+        let mut blobs = self.blobs.lock().await;
+        blobs.updated_at.remove(&hash);
+        Ok(())
+    }
+
+    async fn get_reference(
+        &self,
+        upload_id: Uuid,
+        name: &str,
+        hash: Hash32,
+    ) -> anyhow::Result<Option<BlobReferenceRow>> {
+        let blobs = self.blobs.lock().await;
+        Ok(blobs
+            .references
+            .iter()
+            .find(|r| r.upload_id == upload_id && r.name == name && r.hash == hash)
+            .cloned())
+    }
+
+    // Same as ClickHouse, the reference added last replaces any previous one of the same file.
+    async fn add_reference(&self, reference: BlobReferenceRow) -> anyhow::Result<()> {
+        let mut blobs = self.blobs.lock().await;
+        blobs.references.retain(|r| {
+            !(r.upload_id == reference.upload_id
+                && r.name == reference.name
+                && r.hash == reference.hash)
+        });
+        blobs.references.push(reference);
+        Ok(())
+    }
+
+    async fn is_referenced_by(&self, hash: Hash32, owner: &str) -> anyhow::Result<bool> {
+        let blobs = self.blobs.lock().await;
+        Ok(blobs
+            .references
+            .iter()
+            .any(|r| r.hash == hash && r.owner == owner))
+    }
+
+    async fn remove_reference(
+        &self,
+        upload_id: Uuid,
+        name: &str,
+        hash: Hash32,
+    ) -> anyhow::Result<()> {
+        let mut blobs = self.blobs.lock().await;
+        blobs
+            .references
+            .retain(|r| !(r.upload_id == upload_id && r.name == name && r.hash == hash));
+        Ok(())
+    }
+
+    async fn remove_references(&self, upload_id: Uuid) -> anyhow::Result<()> {
+        let mut blobs = self.blobs.lock().await;
+        blobs.references.retain(|r| r.upload_id != upload_id);
+        Ok(())
+    }
+
+    async fn list_unreferenced(
+        &self,
+        updated_before: DateTime<Utc>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<BlobRow>> {
+        let blobs = self.blobs.lock().await;
+
+        let mut rows: Vec<BlobRow> = blobs
+            .updated_at
+            .iter()
+            .filter(|(_, updated_at)| **updated_at < updated_before)
+            .map(|(hash, updated_at)| blobs.row(*hash, *updated_at))
+            .filter(|row| row.references == 0)
+            .collect();
+
+        rows.sort_by_key(|row| row.updated_at);
+        rows.truncate(limit);
+
+        Ok(rows)
+    }
+}
@@ -0,0 +1,70 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use file_server_library::models::Hash32;
+use uuid::Uuid;
+
+/// A stored blob along with the number of files referencing it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlobRow {
+    pub hash: Hash32,
+    pub references: u64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A file of an upload pointing at a blob. The content type belongs to the reference since the
+/// same contents can be uploaded under different names. The owner is the client that uploaded
+/// the file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlobReferenceRow {
+    pub hash: Hash32,
+    pub upload_id: Uuid,
+    pub owner: String,
+    pub name: String,
+    pub content_type: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait BlobRepository: Send + Sync {
+    async fn get(&self, hash: Hash32) -> anyhow::Result<Option<BlobRow>>;
+    /// Inserts the blob, or refreshes its `updated_at` when it already exists.
+    async fn touch(&self, hash: Hash32) -> anyhow::Result<()>;
+    async fn delete(&self, hash: Hash32) -> anyhow::Result<()>;
+    /// Versions of an upload can hold different contents under the same name, hence the hash.
+    async fn get_reference(
+        &self,
+        upload_id: Uuid,
+        name: &str,
+        hash: Hash32,
+    ) -> anyhow::Result<Option<BlobReferenceRow>>;
+    /// Adding the reference of the same file again replaces it, so it is only counted once.
+    async fn add_reference(&self, reference: BlobReferenceRow) -> anyhow::Result<()>;
+    /// Whether any upload of `owner` references the blob.
+    async fn is_referenced_by(&self, hash: Hash32, owner: &str) -> anyhow::Result<bool>;
+    /// Drops the reference of a single file of the upload.
+    async fn remove_reference(
+        &self,
+        upload_id: Uuid,
+        name: &str,
+        hash: Hash32,
+    ) -> anyhow::Result<()>;
+    /// Drops every reference held by the given upload.
+    async fn remove_references(&self, upload_id: Uuid) -> anyhow::Result<()>;
+    /// Returns up to `limit` blobs without references that have not been touched since
+    /// `updated_before`, oldest first.
+    async fn list_unreferenced(
+        &self,
+        updated_before: DateTime<Utc>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<BlobRow>>;
+}
+
+#[cfg(feature = "in-memory")]
+mod in_memory;
+#[cfg(feature = "in-memory")]
+pub use in_memory::InMemoryBlobRepository;
+
+#[cfg(feature = "persistent")]
+mod clickhouse;
+#[cfg(feature = "persistent")]
+pub use clickhouse::ClickhouseBlobRepository;
@@ -4,8 +4,15 @@
 // maintainable (migrations can be painful without a framework, sdk or crate).
 // Having SQL queries in code as it is right now is less than ideal, so I very desirable todo would
 // be to try a custom implementation.
+//
+// Trees are updated in place by mutations, except when the update is conditional. Mutations
+// cannot be made conditional on the row they update, so those updates insert a new row instead,
+// which the ReplacingMergeTree keeps over the previous ones. Racing inserts made against the same
+// `updated_at` share a deduplication token, only the first of them is stored, and each insert
+// carries an id of its own so the row read back tells which one it was. Conditional deletions
+// take part in the race by inserting a tombstone, which hides the tree until it is dropped.
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use clickhouse::{Client, Row};
 use config::Config;
 use file_server_library::models::Hash32;
@@ -13,9 +20,16 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 
-use crate::repositories::{FileMerkleTreeRow, FileRepository};
+use crate::{
+    models::{UploadCursor, UploadFilter, UploadState},
+    repositories::{FileMerkleTreeRow, FileRepository},
+};
 
 const FILE_TABLE_NAME: &str = "files";
+const FILE_VERSION_TABLE_NAME: &str = "file_versions";
+// Ids of the latest conditional updates kept on the row, enough for a writer to find its own
+// after a few others followed it.
+const RECENT_WRITES: usize = 16;
 
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
@@ -58,6 +72,120 @@ impl ClickhouseFileRepository {
     pub fn new_from_client(client: Client) -> Self {
         Self { client }
     }
+
+    // Trees in `state`, or in every state, oldest first.
+    async fn list_oldest_first(
+        &self,
+        state: Option<UploadState>,
+        after: Option<UploadCursor>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<FileMerkleTreeRow>> {
+        let mut conditions = vec!["NOT deleted"];
+        if state.is_some() {
+            conditions.push("state = ?");
+        }
+        if after.is_some() {
+            conditions.push("(created_at, id) > (fromUnixTimestamp64Milli(?), ?)");
+        }
+
+        let sql = format!(
+            "SELECT
+                 id,
+                 version,
+                 previous_version,
+                 owner,
+                 files_order,
+                 files,
+                 leaf_hashes,
+                 file_sizes,
+                 file_content_types,
+                 file_uploaded_at,
+                 root,
+                 state,
+                 created_at,
+                 updated_at
+               FROM {FILE_TABLE_NAME} FINAL
+              WHERE {}
+              ORDER BY created_at, id
+              LIMIT ?",
+            conditions.join(" AND "),
+        );
+
+        let mut query = self.client.query(&sql);
+        if let Some(state) = state {
+            query = query.bind(state.to_string());
+        }
+        if let Some(after) = after {
+            query = query
+                .bind(after.created_at.timestamp_millis())
+                .bind(after.id);
+        }
+
+        let rows = query
+            .bind(limit as u64)
+            .fetch_all::<ClickhouseFileRow>()
+            .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    // Inserts `row` over the tree last updated at `updated_at`, see the top of the file.
+    async fn write_if_unchanged(
+        &self,
+        row: FileMerkleTreeRow,
+        updated_at: DateTime<Utc>,
+        deleted: bool,
+    ) -> anyhow::Result<bool> {
+        let id = row.id;
+        let expected = updated_at.timestamp_millis();
+
+        let current = self
+            .client
+            .query(&format!(
+                "SELECT updated_at, recent_writes FROM {FILE_TABLE_NAME} FINAL WHERE id = ? AND NOT deleted"
+            ))
+            .bind(id)
+            .fetch_optional::<ClickhouseWriteStamp>()
+            .await?;
+        let Some(current) = current.filter(|c| c.updated_at.timestamp_millis() == expected) else {
+            return Ok(false);
+        };
+
+        // The next update is made against this one's `updated_at`, which must differ from the
+        // one it was made against for their tokens to differ too.
+        let write_id = Uuid::new_v4().to_string();
+        let mut recent_writes = current.recent_writes;
+        recent_writes.push(write_id.clone());
+        recent_writes.drain(..recent_writes.len().saturating_sub(RECENT_WRITES));
+        let mut item = ClickhouseFileWriteRow::new(row.into(), recent_writes, deleted);
+        if item.updated_at.timestamp_millis() <= expected {
+            item.updated_at = updated_at + chrono::Duration::milliseconds(1);
+        }
+
+        let mut insert = self
+            .client
+            .clone()
+            .with_option(
+                "insert_deduplication_token",
+                format!("{FILE_TABLE_NAME}-{id}-{expected}"),
+            )
+            .insert::<ClickhouseFileWriteRow>(FILE_TABLE_NAME)
+            .await?;
+        insert.write(&item).await?;
+        insert.end().await?;
+
+        let sql =
+            format!("SELECT count() FROM {FILE_TABLE_NAME} WHERE id = ? AND has(recent_writes, ?)");
+        let stored = self
+            .client
+            .query(&sql)
+            .bind(id)
+            .bind(write_id)
+            .fetch_one::<u64>()
+            .await?;
+
+        Ok(stored > 0)
+    }
 }
 
 // This DTO is required to handle HashMaps, which are not supported natively by Clickhouse crate.
@@ -68,10 +196,78 @@ impl ClickhouseFileRepository {
 struct ClickhouseFileRow {
     #[serde(with = "clickhouse::serde::uuid")]
     id: Uuid,
+    version: u32,
+    previous_version: Option<u32>,
+    owner: String,
+    files_order: Vec<String>,
+    files: Vec<(String, String)>,
+    leaf_hashes: Vec<String>,
+    file_sizes: Vec<u64>,
+    file_content_types: Vec<String>,
+    // DateTime64(3) values, the serde helpers of the crate do not cover arrays.
+    file_uploaded_at: Vec<i64>,
+    root: Option<String>,
+    state: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    created_at: DateTime<Utc>,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    updated_at: DateTime<Utc>,
+}
+
+// What a conditional update needs to know of the stored row.
+#[derive(Deserialize, Row)]
+struct ClickhouseWriteStamp {
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    updated_at: DateTime<Utc>,
+    recent_writes: Vec<String>,
+}
+
+// Same as `ClickhouseFileRow`, plus the ids of the latest conditional updates and whether the
+// row is a tombstone. Versions keep neither, and the crate cannot flatten one row into another.
+#[derive(Serialize, Row)]
+struct ClickhouseFileWriteRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    id: Uuid,
+    version: u32,
+    previous_version: Option<u32>,
+    owner: String,
     files_order: Vec<String>,
     files: Vec<(String, String)>,
     leaf_hashes: Vec<String>,
+    file_sizes: Vec<u64>,
+    file_content_types: Vec<String>,
+    file_uploaded_at: Vec<i64>,
     root: Option<String>,
+    state: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    created_at: DateTime<Utc>,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    updated_at: DateTime<Utc>,
+    recent_writes: Vec<String>,
+    deleted: bool,
+}
+
+impl ClickhouseFileWriteRow {
+    fn new(row: ClickhouseFileRow, recent_writes: Vec<String>, deleted: bool) -> Self {
+        Self {
+            id: row.id,
+            version: row.version,
+            previous_version: row.previous_version,
+            owner: row.owner,
+            files_order: row.files_order,
+            files: row.files,
+            leaf_hashes: row.leaf_hashes,
+            file_sizes: row.file_sizes,
+            file_content_types: row.file_content_types,
+            file_uploaded_at: row.file_uploaded_at,
+            root: row.root,
+            state: row.state,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            recent_writes,
+            deleted,
+        }
+    }
 }
 
 // ChatGPT snippet
@@ -82,10 +278,23 @@ impl From<FileMerkleTreeRow> for ClickhouseFileRow {
         let root = x.root.map(|h| h.to_hex());
         Self {
             id: x.id,
+            version: x.version,
+            previous_version: x.previous_version,
+            owner: x.owner,
             files_order: x.order,
             files,
             leaf_hashes,
+            file_sizes: x.sizes,
+            file_content_types: x.content_types,
+            file_uploaded_at: x
+                .uploaded_at
+                .iter()
+                .map(DateTime::timestamp_millis)
+                .collect(),
             root,
+            state: x.state.to_string(),
+            created_at: x.created_at,
+            updated_at: x.updated_at,
         }
     }
 }
@@ -111,6 +320,15 @@ impl TryFrom<ClickhouseFileRow> for FileMerkleTreeRow {
             .map(|v| Hash32::from_hex(&v).map_err(|e| anyhow::anyhow!("bad leaf hash {v}: {e}")))
             .collect::<anyhow::Result<Vec<_>>>()?;
 
+        let uploaded_at = row
+            .file_uploaded_at
+            .into_iter()
+            .map(|millis| {
+                DateTime::from_timestamp_millis(millis)
+                    .ok_or_else(|| anyhow::anyhow!("bad upload timestamp {millis}"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
         let root = match row.root {
             Some(s) => Some(Hash32::from_hex(&s).map_err(|_| anyhow::anyhow!("bad root"))?),
             None => None,
@@ -118,10 +336,19 @@ impl TryFrom<ClickhouseFileRow> for FileMerkleTreeRow {
 
         Ok(FileMerkleTreeRow {
             id: row.id,
+            version: row.version,
+            previous_version: row.previous_version,
+            owner: row.owner,
             order: row.files_order,
             files,
             leaf_hashes,
+            sizes: row.file_sizes,
+            content_types: row.file_content_types,
+            uploaded_at,
             root,
+            state: row.state.parse()?,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
         })
     }
 }
@@ -130,14 +357,24 @@ impl TryFrom<ClickhouseFileRow> for FileMerkleTreeRow {
 impl FileRepository for ClickhouseFileRepository {
     async fn get(&self, id: Uuid) -> anyhow::Result<Option<FileMerkleTreeRow>> {
         let sql = format!(
-            "SELECT 
+            "SELECT
                  id,
+                 version,
+                 previous_version,
+                 owner,
                  files_order,
                  files,
                  leaf_hashes,
-                 root
-               FROM {FILE_TABLE_NAME}
-              WHERE id = ?",
+                 file_sizes,
+                 file_content_types,
+                 file_uploaded_at,
+                 root,
+                 state,
+                 created_at,
+                 updated_at
+               FROM {FILE_TABLE_NAME} FINAL
+              WHERE id = ?
+                AND NOT deleted",
         );
         let mut cursor = self
             .client
@@ -173,11 +410,17 @@ impl FileRepository for ClickhouseFileRepository {
 
         let sql = format!(
             "ALTER TABLE {FILE_TABLE_NAME} UPDATE
+                version = ?,
+                previous_version = ?,
                 files_order = ?,
                 files = ?,
                 leaf_hashes = ?,
+                file_sizes = ?,
+                file_content_types = ?,
+                file_uploaded_at = arrayMap(x -> fromUnixTimestamp64Milli(x), CAST(? AS Array(Int64))),
                 root = ?,
-                updated_at = ?
+                state = ?,
+                updated_at = fromUnixTimestamp64Milli(?)
              WHERE id = ?",
         );
 
@@ -185,15 +428,238 @@ impl FileRepository for ClickhouseFileRepository {
             .clone()
             .with_option("mutations_sync", "1")
             .query(&sql)
+            .bind(item.version)
+            .bind(item.previous_version)
             .bind(&item.files_order)
             .bind(item.files)
             .bind(item.leaf_hashes)
+            .bind(item.file_sizes)
+            .bind(item.file_content_types)
+            .bind(item.file_uploaded_at)
             .bind(&item.root)
-            .bind(Utc::now().timestamp_millis().to_string())
+            .bind(&item.state)
+            .bind(item.updated_at.timestamp_millis())
             .bind(item.id)
             .execute()
             .await?;
 
         Ok(())
     }
+
+    async fn update_if_unchanged(
+        &self,
+        row: FileMerkleTreeRow,
+        updated_at: DateTime<Utc>,
+    ) -> anyhow::Result<bool> {
+        self.write_if_unchanged(row, updated_at, false).await
+    }
+
+    // Once the tombstone is in, the tree is gone for every read and conditional write, failing
+    // to drop its rows afterwards only leaves them around hidden.
+    async fn delete_if_unchanged(
+        &self,
+        id: Uuid,
+        updated_at: DateTime<Utc>,
+    ) -> anyhow::Result<bool> {
+        let Some(row) = self.get(id).await? else {
+            return Ok(false);
+        };
+        if !self.write_if_unchanged(row, updated_at, true).await? {
+            return Ok(false);
+        }
+
+        for table in [FILE_VERSION_TABLE_NAME, FILE_TABLE_NAME] {
+            let sql = format!("ALTER TABLE {table} DELETE WHERE id = ?");
+
+            self.client
+                .clone()
+                .with_option("mutations_sync", "1")
+                .query(&sql)
+                .bind(id)
+                .execute()
+                .await?;
+        }
+
+        Ok(true)
+    }
+
+    async fn insert_version(&self, tree: FileMerkleTreeRow) -> anyhow::Result<()> {
+        let mut insert = self
+            .client
+            .insert::<ClickhouseFileRow>(FILE_VERSION_TABLE_NAME)
+            .await?;
+
+        insert.write(&tree.into()).await?;
+        insert.end().await?;
+
+        Ok(())
+    }
+
+    async fn get_version(
+        &self,
+        id: Uuid,
+        version: u32,
+    ) -> anyhow::Result<Option<FileMerkleTreeRow>> {
+        // Completing a version again inserts a new snapshot, the latest one wins.
+        let sql = format!(
+            "SELECT
+                 id,
+                 version,
+                 previous_version,
+                 owner,
+                 files_order,
+                 files,
+                 leaf_hashes,
+                 file_sizes,
+                 file_content_types,
+                 file_uploaded_at,
+                 root,
+                 state,
+                 created_at,
+                 updated_at
+               FROM {FILE_VERSION_TABLE_NAME}
+              WHERE id = ?
+                AND version = ?
+              ORDER BY updated_at DESC
+              LIMIT 1",
+        );
+
+        let row = self
+            .client
+            .query(&sql)
+            .bind(id)
+            .bind(version)
+            .fetch_optional::<ClickhouseFileRow>()
+            .await?;
+
+        row.map(TryInto::try_into).transpose()
+    }
+
+    async fn list_stale(
+        &self,
+        state: UploadState,
+        updated_before: DateTime<Utc>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<FileMerkleTreeRow>> {
+        let sql = format!(
+            "SELECT
+                 id,
+                 version,
+                 previous_version,
+                 owner,
+                 files_order,
+                 files,
+                 leaf_hashes,
+                 file_sizes,
+                 file_content_types,
+                 file_uploaded_at,
+                 root,
+                 state,
+                 created_at,
+                 updated_at
+               FROM {FILE_TABLE_NAME} FINAL
+              WHERE state = ?
+                AND updated_at < fromUnixTimestamp64Milli(?)
+                AND NOT deleted
+              ORDER BY updated_at
+              LIMIT ?",
+        );
+
+        let rows = self
+            .client
+            .query(&sql)
+            .bind(state.to_string())
+            .bind(updated_before.timestamp_millis())
+            .bind(limit as u64)
+            .fetch_all::<ClickhouseFileRow>()
+            .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    async fn list(
+        &self,
+        owner: &str,
+        filter: &UploadFilter,
+        after: Option<UploadCursor>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<FileMerkleTreeRow>> {
+        let mut conditions = vec!["owner = ?", "NOT deleted"];
+        if filter.state.is_some() {
+            conditions.push("state = ?");
+        }
+        if filter.created_after.is_some() {
+            conditions.push("created_at >= fromUnixTimestamp64Milli(?)");
+        }
+        if filter.created_before.is_some() {
+            conditions.push("created_at < fromUnixTimestamp64Milli(?)");
+        }
+        if after.is_some() {
+            conditions.push("(created_at, id) < (fromUnixTimestamp64Milli(?), ?)");
+        }
+
+        let sql = format!(
+            "SELECT
+                 id,
+                 version,
+                 previous_version,
+                 owner,
+                 files_order,
+                 files,
+                 leaf_hashes,
+                 file_sizes,
+                 file_content_types,
+                 file_uploaded_at,
+                 root,
+                 state,
+                 created_at,
+                 updated_at
+               FROM {FILE_TABLE_NAME} FINAL
+              WHERE {}
+              ORDER BY created_at DESC, id DESC
+              LIMIT ?",
+            conditions.join(" AND "),
+        );
+
+        // Binds follow the order in which conditions were added.
+        let mut query = self.client.query(&sql).bind(owner);
+        if let Some(state) = filter.state {
+            query = query.bind(state.to_string());
+        }
+        if let Some(created_after) = filter.created_after {
+            query = query.bind(created_after.timestamp_millis());
+        }
+        if let Some(created_before) = filter.created_before {
+            query = query.bind(created_before.timestamp_millis());
+        }
+        if let Some(after) = after {
+            query = query
+                .bind(after.created_at.timestamp_millis())
+                .bind(after.id);
+        }
+
+        let rows = query
+            .bind(limit as u64)
+            .fetch_all::<ClickhouseFileRow>()
+            .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    async fn list_completed(
+        &self,
+        after: Option<UploadCursor>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<FileMerkleTreeRow>> {
+        self.list_oldest_first(Some(UploadState::Completed), after, limit)
+            .await
+    }
+
+    async fn list_all(
+        &self,
+        after: Option<UploadCursor>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<FileMerkleTreeRow>> {
+        self.list_oldest_first(None, after, limit).await
+    }
 }
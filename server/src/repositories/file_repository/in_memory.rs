@@ -1,13 +1,42 @@
 use async_trait::async_trait;
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use std::{cmp::Reverse, collections::HashMap};
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
-use crate::repositories::{FileMerkleTreeRow, FileRepository};
+use crate::{
+    models::{UploadCursor, UploadFilter, UploadState},
+    repositories::{FileMerkleTreeRow, FileRepository},
+};
 
 #[derive(Default)]
 pub struct InMemoryFileRepository {
     file_trees: Mutex<HashMap<Uuid, FileMerkleTreeRow>>,
+    versions: Mutex<HashMap<(Uuid, u32), FileMerkleTreeRow>>,
+}
+
+impl InMemoryFileRepository {
+    async fn list_oldest_first(
+        &self,
+        state: Option<UploadState>,
+        after: Option<UploadCursor>,
+        limit: usize,
+    ) -> Vec<FileMerkleTreeRow> {
+        let file_trees = self.file_trees.lock().await;
+
+        let mut trees: Vec<_> = file_trees
+            .values()
+            .filter(|tree| state.is_none_or(|state| tree.state == state))
+            .filter(|tree| {
+                after.is_none_or(|after| (tree.created_at, tree.id) > (after.created_at, after.id))
+            })
+            .cloned()
+            .collect();
+        trees.sort_by_key(|tree| (tree.created_at, tree.id));
+        trees.truncate(limit);
+
+        trees
+    }
 }
 
 #[async_trait]
@@ -23,9 +52,125 @@ impl FileRepository for InMemoryFileRepository {
         Ok(())
     }
 
+    // ClickHouse mutations match no row once the tree is gone, failing tells callers it is.
     async fn update(&self, tree: FileMerkleTreeRow) -> anyhow::Result<()> {
         let mut file_trees = self.file_trees.lock().await;
-        file_trees.insert(tree.id, tree);
+        let Some(current) = file_trees.get_mut(&tree.id) else {
+            anyhow::bail!("file tree {} not found", tree.id);
+        };
+        *current = tree;
+        Ok(())
+    }
+
+    async fn update_if_unchanged(
+        &self,
+        tree: FileMerkleTreeRow,
+        updated_at: DateTime<Utc>,
+    ) -> anyhow::Result<bool> {
+        let mut file_trees = self.file_trees.lock().await;
+        match file_trees.get_mut(&tree.id) {
+            Some(current) if current.updated_at == updated_at => {
+                *current = tree;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete_if_unchanged(
+        &self,
+        id: Uuid,
+        updated_at: DateTime<Utc>,
+    ) -> anyhow::Result<bool> {
+        let mut file_trees = self.file_trees.lock().await;
+        if file_trees
+            .get(&id)
+            .is_none_or(|current| current.updated_at != updated_at)
+        {
+            return Ok(false);
+        }
+        file_trees.remove(&id);
+
+        let mut versions = self.versions.lock().await;
+        versions.retain(|(tree_id, _), _| *tree_id != id);
+        Ok(true)
+    }
+
+    async fn insert_version(&self, tree: FileMerkleTreeRow) -> anyhow::Result<()> {
+        let mut versions = self.versions.lock().await;
+        versions.insert((tree.id, tree.version), tree);
         Ok(())
     }
+
+    async fn get_version(
+        &self,
+        id: Uuid,
+        version: u32,
+    ) -> anyhow::Result<Option<FileMerkleTreeRow>> {
+        let versions = self.versions.lock().await;
+        Ok(versions.get(&(id, version)).cloned())
+    }
+
+    async fn list_stale(
+        &self,
+        state: UploadState,
+        updated_before: DateTime<Utc>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<FileMerkleTreeRow>> {
+        let file_trees = self.file_trees.lock().await;
+
+        let mut stale: Vec<_> = file_trees
+            .values()
+            .filter(|tree| tree.state == state && tree.updated_at < updated_before)
+            .cloned()
+            .collect();
+        stale.sort_by_key(|tree| tree.updated_at);
+        stale.truncate(limit);
+
+        Ok(stale)
+    }
+
+    async fn list(
+        &self,
+        owner: &str,
+        filter: &UploadFilter,
+        after: Option<UploadCursor>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<FileMerkleTreeRow>> {
+        let file_trees = self.file_trees.lock().await;
+
+        let mut trees: Vec<_> = file_trees
+            .values()
+            .filter(|tree| tree.owner == owner)
+            .filter(|tree| filter.state.is_none_or(|state| tree.state == state))
+            .filter(|tree| filter.created_after.is_none_or(|at| tree.created_at >= at))
+            .filter(|tree| filter.created_before.is_none_or(|at| tree.created_at < at))
+            .filter(|tree| {
+                after.is_none_or(|after| (tree.created_at, tree.id) < (after.created_at, after.id))
+            })
+            .cloned()
+            .collect();
+        trees.sort_by_key(|tree| Reverse((tree.created_at, tree.id)));
+        trees.truncate(limit);
+
+        Ok(trees)
+    }
+
+    async fn list_completed(
+        &self,
+        after: Option<UploadCursor>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<FileMerkleTreeRow>> {
+        Ok(self
+            .list_oldest_first(Some(UploadState::Completed), after, limit)
+            .await)
+    }
+
+    async fn list_all(
+        &self,
+        after: Option<UploadCursor>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<FileMerkleTreeRow>> {
+        Ok(self.list_oldest_first(None, after, limit).await)
+    }
 }
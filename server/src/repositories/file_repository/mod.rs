@@ -1,22 +1,86 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use file_server_library::models::Hash32;
 use std::collections::HashMap;
 use uuid::Uuid;
 
+use crate::models::{UploadCursor, UploadFilter, UploadState};
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct FileMerkleTreeRow {
     pub id: Uuid,
+    pub version: u32,
+    pub previous_version: Option<u32>,
+    pub owner: String,
     pub order: Vec<String>,
     pub files: HashMap<String, Hash32>,
     pub leaf_hashes: Vec<Hash32>,
+    pub sizes: Vec<u64>,
+    pub content_types: Vec<String>,
+    pub uploaded_at: Vec<DateTime<Utc>>,
     pub root: Option<Hash32>,
+    pub state: UploadState,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[async_trait]
 pub trait FileRepository: Send + Sync {
     async fn get(&self, id: Uuid) -> anyhow::Result<Option<FileMerkleTreeRow>>;
     async fn insert(&self, tree: FileMerkleTreeRow) -> anyhow::Result<()>;
+    /// Never brings back a tree that is gone.
     async fn update(&self, tree: FileMerkleTreeRow) -> anyhow::Result<()>;
+    /// Updates the tree only when it was last updated at `updated_at`, so changes made since it
+    /// was read are not overwritten. Returns whether it was updated.
+    async fn update_if_unchanged(
+        &self,
+        tree: FileMerkleTreeRow,
+        updated_at: DateTime<Utc>,
+    ) -> anyhow::Result<bool>;
+    /// Drops the tree along with every version kept for it, only when it was last updated at
+    /// `updated_at` like `update_if_unchanged`. Returns whether it was dropped.
+    async fn delete_if_unchanged(
+        &self,
+        id: Uuid,
+        updated_at: DateTime<Utc>,
+    ) -> anyhow::Result<bool>;
+    /// Keeps a snapshot of a completed version, replacing any previous snapshot of it.
+    async fn insert_version(&self, tree: FileMerkleTreeRow) -> anyhow::Result<()>;
+    async fn get_version(
+        &self,
+        id: Uuid,
+        version: u32,
+    ) -> anyhow::Result<Option<FileMerkleTreeRow>>;
+    /// Returns up to `limit` trees in `state` that have not been updated since `updated_before`,
+    /// oldest first.
+    async fn list_stale(
+        &self,
+        state: UploadState,
+        updated_before: DateTime<Utc>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<FileMerkleTreeRow>>;
+    /// Returns up to `limit` trees of `owner` matching `filter`, newest first, starting right
+    /// after `after` when given.
+    async fn list(
+        &self,
+        owner: &str,
+        filter: &UploadFilter,
+        after: Option<UploadCursor>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<FileMerkleTreeRow>>;
+    /// Returns up to `limit` completed trees of every owner, oldest first, starting right after
+    /// `after` when given.
+    async fn list_completed(
+        &self,
+        after: Option<UploadCursor>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<FileMerkleTreeRow>>;
+    /// Same as `list_completed`, in every state.
+    async fn list_all(
+        &self,
+        after: Option<UploadCursor>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<FileMerkleTreeRow>>;
 }
 
 #[cfg(feature = "in-memory")]
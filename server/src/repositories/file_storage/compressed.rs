@@ -0,0 +1,418 @@
+// Transparent zstd compression of stored contents. Objects reaching `min_size_bytes` are compressed
+// while they are streamed to the inner storage, which records `zstd` as their content encoding,
+// smaller ones are stored as they are. Leaf hashes are still computed over the original bytes by
+// the services, clients see no difference unless they ask for the compressed bytes.
+//
+// The original size is appended to compressed objects as a zstd skippable frame: `SIZE_FRAME_MAGIC`
+// and the frame length (4 bytes each) followed by the size (8 bytes), all little endian. Metadata
+// is then read without decompressing anything, and objects stay valid zstd streams that any
+// decoder handles as they are.
+use std::{
+    io,
+    ops::RangeInclusive,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use async_compression::{
+    Level,
+    tokio::bufread::{ZstdDecoder, ZstdEncoder},
+};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use config::Config;
+use file_server_library::models::Hash32;
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::Deserialize;
+use tokio_util::io::{ReaderStream, StreamReader};
+use tracing::info;
+use uuid::Uuid;
+
+use super::Object;
+use crate::repositories::{
+    ContentEncoding, EncodedContent, FileStorage, FileStream, StoredFileMetadata, StoredObject,
+};
+
+const SIZE_FRAME_MAGIC: u32 = 0x184D_2A50;
+const SIZE_FRAME_LEN: u32 = 8;
+const SIZE_FRAME_SIZE: u64 = 16;
+
+/// Compression only applies to new objects, those already compressed are read back either way.
+/// `level` is the zstd level, from 1 (fastest) to 22 (smallest).
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompressionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "CompressionConfig::default_level")]
+    pub level: i32,
+    #[serde(default = "CompressionConfig::default_min_size_bytes")]
+    pub min_size_bytes: u64,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            level: Self::default_level(),
+            min_size_bytes: Self::default_min_size_bytes(),
+        }
+    }
+}
+
+impl CompressionConfig {
+    const CONFIG_PREFIX: &'static str = "COMPRESSION";
+
+    pub fn load_from_env() -> anyhow::Result<Self> {
+        Config::builder()
+            .add_source(
+                config::Environment::with_prefix(Self::CONFIG_PREFIX)
+                    .separator("__")
+                    .try_parsing(true),
+            )
+            .build()?
+            .try_deserialize::<CompressionConfig>()
+            .map_err(|e| anyhow::anyhow!("failed to load Compression Configuration: {}", e))
+    }
+
+    fn default_level() -> i32 {
+        3
+    }
+
+    fn default_min_size_bytes() -> u64 {
+        4 * 1024
+    }
+}
+
+/// `FileStorage` decorator compressing contents before they reach the inner storage and
+/// decompressing them on the way out. Metadata sizes are the sizes of the original contents.
+pub struct CompressedFileStorage {
+    inner: Arc<dyn FileStorage>,
+    config: CompressionConfig,
+}
+
+impl CompressedFileStorage {
+    pub fn new(inner: Arc<dyn FileStorage>, config: CompressionConfig) -> Self {
+        Self { inner, config }
+    }
+
+    /// `inner` is always wrapped, objects compressed while compression was enabled still have to
+    /// be decompressed after disabling it.
+    pub fn load_from_env(inner: Arc<dyn FileStorage>) -> anyhow::Result<Arc<dyn FileStorage>> {
+        let config = CompressionConfig::load_from_env()?;
+        if config.enabled {
+            info!(
+                level = config.level,
+                min_size_bytes = config.min_size_bytes,
+                "Compression of stored contents is enabled"
+            );
+        }
+
+        Ok(Arc::new(Self::new(inner, config)))
+    }
+
+    fn compress(&self, head: Bytes, rest: FileStream) -> FileStream {
+        let size = Arc::new(AtomicU64::new(0));
+        let counted_size = Arc::clone(&size);
+        let content = stream::once(async { Ok(head) })
+            .chain(rest)
+            .inspect_ok(move |chunk| {
+                counted_size.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+            });
+
+        let encoder = ZstdEncoder::with_quality(
+            StreamReader::new(content),
+            Level::Precise(self.config.level),
+        );
+        // Only polled once the encoder is done, when the whole content has been counted.
+        let size_frame = stream::once(async move { Ok(size_frame(size.load(Ordering::Relaxed))) });
+
+        Box::pin(ReaderStream::new(encoder).chain(size_frame))
+    }
+
+    async fn metadata(&self, object: &Object<'_>) -> anyhow::Result<Option<StoredFileMetadata>> {
+        let Some(stored) = object.metadata(self.inner.as_ref()).await? else {
+            return Ok(None);
+        };
+
+        match stored.content_encoding {
+            Some(ContentEncoding::Zstd) => {
+                Ok(self
+                    .original_size(object, stored.size)
+                    .await?
+                    .map(|size| StoredFileMetadata {
+                        size,
+                        content_type: stored.content_type,
+                        content_encoding: None,
+                    }))
+            }
+            None => Ok(Some(stored)),
+        }
+    }
+
+    async fn original_size(
+        &self,
+        object: &Object<'_>,
+        stored_size: u64,
+    ) -> anyhow::Result<Option<u64>> {
+        if stored_size < SIZE_FRAME_SIZE {
+            anyhow::bail!(corrupted(object));
+        }
+
+        let Some(frame) = object
+            .content(
+                self.inner.as_ref(),
+                Some(stored_size - SIZE_FRAME_SIZE..=stored_size - 1),
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+        let frame: Vec<u8> = frame.map_ok(|chunk| chunk.to_vec()).try_concat().await?;
+
+        parse_size_frame(&frame)
+            .map(Some)
+            .ok_or_else(|| anyhow::anyhow!(corrupted(object)))
+    }
+
+    async fn read(
+        &self,
+        object: Object<'_>,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>> {
+        let Some(stored) = object.metadata(self.inner.as_ref()).await? else {
+            return Ok(None);
+        };
+
+        if stored.content_encoding.is_none() {
+            return object.content(self.inner.as_ref(), range).await;
+        }
+
+        let Some(content) = object.content(self.inner.as_ref(), None).await? else {
+            return Ok(None);
+        };
+
+        let mut decoder = ZstdDecoder::new(StreamReader::new(content));
+        // The size frame follows the compressed frame.
+        decoder.multiple_members(true);
+        let content = ReaderStream::new(decoder);
+
+        Ok(Some(match range {
+            Some(range) => {
+                let state = SliceState {
+                    content: Box::pin(content),
+                    skip: *range.start(),
+                    remaining: range.end() - range.start() + 1,
+                };
+                Box::pin(stream::unfold(state, SliceState::next))
+            }
+            None => Box::pin(content),
+        }))
+    }
+}
+
+#[async_trait]
+impl FileStorage for CompressedFileStorage {
+    async fn get_file_metadata(
+        &self,
+        id: Uuid,
+        name: &str,
+    ) -> anyhow::Result<Option<StoredFileMetadata>> {
+        self.metadata(&Object::File(id, name)).await
+    }
+
+    async fn get_file_content(
+        &self,
+        id: Uuid,
+        name: &str,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>> {
+        self.read(Object::File(id, name), range).await
+    }
+
+    // Contents already encoded by the caller are stored as they are.
+    async fn insert_file_content(
+        &self,
+        id: Uuid,
+        name: &str,
+        content_type: &str,
+        content_encoding: Option<ContentEncoding>,
+        content: FileStream,
+    ) -> anyhow::Result<()> {
+        if !self.config.enabled || content_encoding.is_some() {
+            return self
+                .inner
+                .insert_file_content(id, name, content_type, content_encoding, content)
+                .await;
+        }
+
+        let (head, rest) = read_head(content, self.config.min_size_bytes).await?;
+        match rest {
+            Some(rest) => {
+                self.inner
+                    .insert_file_content(
+                        id,
+                        name,
+                        content_type,
+                        Some(ContentEncoding::Zstd),
+                        self.compress(head, rest),
+                    )
+                    .await
+            }
+            None => {
+                self.inner
+                    .insert_file_content(
+                        id,
+                        name,
+                        content_type,
+                        None,
+                        Box::pin(stream::once(async { Ok(head) })),
+                    )
+                    .await
+            }
+        }
+    }
+
+    async fn delete_file_content(&self, id: Uuid, name: &str) -> anyhow::Result<()> {
+        self.inner.delete_file_content(id, name).await
+    }
+
+    async fn delete_prefix(&self, id: Uuid) -> anyhow::Result<()> {
+        self.inner.delete_prefix(id).await
+    }
+
+    async fn list(&self, id: Uuid) -> anyhow::Result<Vec<StoredObject>> {
+        self.inner.list(id).await
+    }
+
+    async fn get_blob_metadata(&self, hash: Hash32) -> anyhow::Result<Option<StoredFileMetadata>> {
+        self.metadata(&Object::Blob(hash)).await
+    }
+
+    async fn get_blob_content(
+        &self,
+        hash: Hash32,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>> {
+        self.read(Object::Blob(hash), range).await
+    }
+
+    // The content encoding is recorded along with the object, it moves with it.
+    async fn move_to_blob(&self, id: Uuid, name: &str, hash: Hash32) -> anyhow::Result<()> {
+        self.inner.move_to_blob(id, name, hash).await
+    }
+
+    async fn delete_blob(&self, hash: Hash32) -> anyhow::Result<()> {
+        self.inner.delete_blob(hash).await
+    }
+
+    async fn get_encoded_blob_content(
+        &self,
+        hash: Hash32,
+    ) -> anyhow::Result<Option<EncodedContent>> {
+        let Some(stored) = self.inner.get_blob_metadata(hash).await? else {
+            return Ok(None);
+        };
+        let Some(encoding) = stored.content_encoding else {
+            return Ok(None);
+        };
+
+        Ok(self
+            .inner
+            .get_blob_content(hash, None)
+            .await?
+            .map(|content| EncodedContent {
+                encoding,
+                size: stored.size,
+                content,
+            }))
+    }
+}
+
+/// Reads until `min_size_bytes` are buffered. The rest of the content is `None` when it ended
+/// before, so it is not worth compressing.
+async fn read_head(
+    mut content: FileStream,
+    min_size_bytes: u64,
+) -> io::Result<(Bytes, Option<FileStream>)> {
+    let mut head = BytesMut::new();
+
+    while (head.len() as u64) < min_size_bytes.max(1) {
+        match content.next().await {
+            Some(chunk) => head.extend_from_slice(&chunk?),
+            None => return Ok((head.freeze(), None)),
+        }
+    }
+
+    Ok((head.freeze(), Some(content)))
+}
+
+fn size_frame(size: u64) -> Bytes {
+    let mut frame = BytesMut::with_capacity(SIZE_FRAME_SIZE as usize);
+    frame.extend_from_slice(&SIZE_FRAME_MAGIC.to_le_bytes());
+    frame.extend_from_slice(&SIZE_FRAME_LEN.to_le_bytes());
+    frame.extend_from_slice(&size.to_le_bytes());
+    frame.freeze()
+}
+
+fn parse_size_frame(frame: &[u8]) -> Option<u64> {
+    let (magic, rest) = frame.split_first_chunk::<4>()?;
+    let (len, size) = rest.split_first_chunk::<4>()?;
+
+    if u32::from_le_bytes(*magic) != SIZE_FRAME_MAGIC || u32::from_le_bytes(*len) != SIZE_FRAME_LEN
+    {
+        return None;
+    }
+
+    Some(u64::from_le_bytes(size.try_into().ok()?))
+}
+
+fn corrupted(object: &Object<'_>) -> String {
+    format!("compressed object {object} is corrupted: missing size frame")
+}
+
+/// Ranges of compressed contents are served by decompressing them from the start, skipping what
+/// comes before the range.
+struct SliceState {
+    content: FileStream,
+    skip: u64,
+    remaining: u64,
+}
+
+impl SliceState {
+    async fn next(mut self) -> Option<(io::Result<Bytes>, Self)> {
+        while self.remaining > 0 {
+            match self.content.next().await {
+                Some(Ok(mut chunk)) => {
+                    let skip = self.skip.min(chunk.len() as u64);
+                    self.skip -= skip;
+                    let _ = chunk.split_to(skip as usize);
+                    chunk.truncate(self.remaining.min(chunk.len() as u64) as usize);
+                    if chunk.is_empty() {
+                        continue;
+                    }
+
+                    self.remaining -= chunk.len() as u64;
+                    return Some((Ok(chunk), self));
+                }
+                Some(Err(e)) => {
+                    self.remaining = 0;
+                    return Some((Err(e), self));
+                }
+                None => {
+                    self.remaining = 0;
+                    return Some((
+                        Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "compressed contents are shorter than their recorded size",
+                        )),
+                        self,
+                    ));
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,670 @@
+// Envelope encryption of stored contents. Every object gets its own random data key, wrapped by
+// the active master key and stored in the object header along with the id of that master key,
+// so master keys can be rotated without rewriting what is already stored. Contents are encrypted
+// with AES-256-GCM in fixed size segments, which keeps uploads streamed and lets ranges be served
+// by decrypting only the segments they cover. Leaf hashes are still computed over the plaintext
+// by the services, clients see no difference.
+//
+// Objects are laid out as:
+// - `FSE1`, the id length (1 byte), the id of the master key,
+// - the nonce (12 bytes) and the data key wrapped by the master key (48 bytes),
+// - the segments, `SEGMENT_SIZE_BYTES` of plaintext each (the last one can be shorter) followed
+//   by their tag. Segment nonces are the segment index plus a flag set on the last one, so
+//   segments cannot be reordered, dropped or appended without failing authentication.
+//
+// Objects not starting with `FSE1` were stored before encryption was enabled. They are only read
+// as they are when plaintext reads are allowed, otherwise anything able to write to the storage
+// could have its contents served as if they had been encrypted by the server. Without master
+// keys contents are stored as they are, and objects starting with `FSE1` fail to be read instead
+// of being served as ciphertext.
+use std::{collections::HashMap, io, ops::RangeInclusive, path::Path, sync::Arc};
+
+use aes_gcm::{
+    Aes256Gcm, Key, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng, Payload},
+};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use config::Config;
+use file_server_library::models::Hash32;
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use super::Object;
+use crate::repositories::{
+    ContentEncoding, FileStorage, FileStream, StoredFileMetadata, StoredObject,
+};
+
+const MAGIC: &[u8; 4] = b"FSE1";
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: u64 = 16;
+const WRAPPED_KEY_SIZE: usize = 32 + TAG_SIZE as usize;
+const MAX_KEY_ID_LEN: usize = u8::MAX as usize;
+const MAX_HEADER_SIZE: u64 =
+    (MAGIC.len() + 1 + MAX_KEY_ID_LEN + NONCE_SIZE + WRAPPED_KEY_SIZE) as u64;
+const SEGMENT_SIZE_BYTES: u64 = 64 * 1024;
+const ENCRYPTED_SEGMENT_SIZE: u64 = SEGMENT_SIZE_BYTES + TAG_SIZE;
+
+/// Master keys are `key_id:hex` pairs, given as a comma separated list or one per line in a key
+/// file (empty lines and lines starting with `#` are skipped). New objects are encrypted with
+/// `active_key_id`, the first key when missing. Contents are stored as they are when no key is
+/// configured. `allow_plaintext_reads` reads objects stored before encryption was enabled as they
+/// are.
+#[derive(Clone, Default, Deserialize)]
+pub struct EncryptionConfig {
+    #[serde(default)]
+    pub master_keys: Vec<String>,
+    pub key_file: Option<String>,
+    pub active_key_id: Option<String>,
+    #[serde(default)]
+    pub allow_plaintext_reads: bool,
+}
+
+impl EncryptionConfig {
+    const CONFIG_PREFIX: &'static str = "ENCRYPTION";
+
+    pub fn load_from_env() -> anyhow::Result<Self> {
+        Config::builder()
+            .add_source(
+                config::Environment::with_prefix(Self::CONFIG_PREFIX)
+                    .separator("__")
+                    .list_separator(",")
+                    .with_list_parse_key("master_keys")
+                    .try_parsing(true),
+            )
+            .build()?
+            .try_deserialize::<EncryptionConfig>()
+            .map_err(|e| anyhow::anyhow!("failed to load Encryption Configuration: {}", e))
+    }
+}
+
+/// Master keys by id. The active one wraps the data keys of new objects, the others are only
+/// kept to unwrap the data keys they wrapped before being rotated.
+pub struct MasterKeys {
+    active_key_id: String,
+    keys: HashMap<String, Aes256Gcm>,
+}
+
+impl MasterKeys {
+    /// Panics if `key_id` is empty or longer than 255 bytes.
+    pub fn new(key_id: &str, key: [u8; 32]) -> Self {
+        assert!(
+            !key_id.is_empty() && key_id.len() <= MAX_KEY_ID_LEN,
+            "Key ids must be between 1 and 255 bytes long"
+        );
+
+        Self {
+            active_key_id: key_id.to_string(),
+            keys: HashMap::from([(key_id.to_string(), cipher(&key))]),
+        }
+    }
+
+    /// Panics if `key_id` is empty or longer than 255 bytes.
+    pub fn with_retired_key(mut self, key_id: &str, key: [u8; 32]) -> Self {
+        assert!(
+            !key_id.is_empty() && key_id.len() <= MAX_KEY_ID_LEN,
+            "Key ids must be between 1 and 255 bytes long"
+        );
+
+        self.keys.insert(key_id.to_string(), cipher(&key));
+        self
+    }
+
+    /// `None` when no key is configured.
+    pub fn from_config(config: EncryptionConfig) -> anyhow::Result<Option<Self>> {
+        let mut entries = config.master_keys;
+        if let Some(key_file) = &config.key_file {
+            let contents = std::fs::read_to_string(Path::new(key_file))
+                .map_err(|e| anyhow::anyhow!("failed to read key file {key_file}: {e}"))?;
+            entries.extend(
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_string),
+            );
+        }
+
+        let mut keys: Vec<(String, [u8; 32])> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let (key_id, key) = parse_master_key(&entry)?;
+            if keys.iter().any(|(id, _)| *id == key_id) {
+                anyhow::bail!("master key {key_id} is configured more than once");
+            }
+            keys.push((key_id, key));
+        }
+
+        let Some((first_key_id, _)) = keys.first() else {
+            return Ok(None);
+        };
+        let active_key_id = config.active_key_id.unwrap_or_else(|| first_key_id.clone());
+        let Some((_, active_key)) = keys.iter().find(|(id, _)| *id == active_key_id) else {
+            anyhow::bail!("active master key {active_key_id} is not configured");
+        };
+
+        let master_keys = keys.iter().filter(|(id, _)| *id != active_key_id).fold(
+            Self::new(&active_key_id, *active_key),
+            |master_keys, (id, key)| master_keys.with_retired_key(id, *key),
+        );
+
+        Ok(Some(master_keys))
+    }
+
+    pub fn active_key_id(&self) -> &str {
+        &self.active_key_id
+    }
+}
+
+/// `FileStorage` decorator encrypting contents before they reach the inner storage and
+/// decrypting them on the way out. Metadata sizes are the sizes of the plaintext.
+pub struct EncryptedFileStorage {
+    inner: Arc<dyn FileStorage>,
+    master_keys: Option<MasterKeys>,
+    allow_plaintext_reads: bool,
+}
+
+impl EncryptedFileStorage {
+    pub fn new(inner: Arc<dyn FileStorage>, master_keys: MasterKeys) -> Self {
+        Self {
+            inner,
+            master_keys: Some(master_keys),
+            allow_plaintext_reads: false,
+        }
+    }
+
+    /// Stores contents as they are, failing to read encrypted objects.
+    pub fn unencrypted(inner: Arc<dyn FileStorage>) -> Self {
+        Self {
+            inner,
+            master_keys: None,
+            allow_plaintext_reads: true,
+        }
+    }
+
+    /// Objects stored before encryption was enabled are read as they are.
+    pub fn with_plaintext_reads(mut self) -> Self {
+        self.allow_plaintext_reads = true;
+        self
+    }
+
+    /// `inner` is always wrapped, objects encrypted while master keys were configured must not be
+    /// served as they are after removing them.
+    pub fn load_from_env(inner: Arc<dyn FileStorage>) -> anyhow::Result<Arc<dyn FileStorage>> {
+        let config = EncryptionConfig::load_from_env()?;
+        let allow_plaintext_reads = config.allow_plaintext_reads;
+
+        let Some(master_keys) = MasterKeys::from_config(config)? else {
+            info!("No ENCRYPTION__MASTER_KEYS configured, contents are stored unencrypted");
+            return Ok(Arc::new(Self::unencrypted(inner)));
+        };
+
+        info!(
+            active_key_id = master_keys.active_key_id(),
+            allow_plaintext_reads, "Encryption at rest is enabled"
+        );
+        let storage = Self::new(inner, master_keys);
+        if allow_plaintext_reads {
+            return Ok(Arc::new(storage.with_plaintext_reads()));
+        }
+        Ok(Arc::new(storage))
+    }
+
+    // Contents are passed through as they are without master keys.
+    fn encrypt(&self, content: FileStream) -> anyhow::Result<FileStream> {
+        let Some(master_keys) = &self.master_keys else {
+            return Ok(content);
+        };
+        let data_key = Aes256Gcm::generate_key(OsRng);
+        let header = header(master_keys, &data_key)?;
+
+        let state = EncryptState {
+            content,
+            buffer: BytesMut::new(),
+            header: Some(header),
+            cipher: Aes256Gcm::new(&data_key),
+            index: 0,
+            done: false,
+        };
+
+        Ok(Box::pin(stream::unfold(state, EncryptState::next)))
+    }
+
+    async fn open(&self, object: &Object<'_>) -> anyhow::Result<Option<OpenedObject>> {
+        let Some(stored) = object.metadata(self.inner.as_ref()).await? else {
+            return Ok(None);
+        };
+        if stored.size < MAGIC.len() as u64 {
+            return self.plaintext(object, stored).map(Some);
+        }
+
+        let Some(header) = object
+            .content(
+                self.inner.as_ref(),
+                Some(0..=stored.size.min(MAX_HEADER_SIZE) - 1),
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+        let header: Vec<u8> = header.map_ok(|chunk| chunk.to_vec()).try_concat().await?;
+        if !header.starts_with(MAGIC) {
+            return self.plaintext(object, stored).map(Some);
+        }
+
+        let (header_size, cipher) = self
+            .parse_header(&header)
+            .map_err(|e| anyhow::anyhow!(corrupted(object, &e)))?;
+        let body_size = stored.size - header_size;
+        let segments = body_size.div_ceil(ENCRYPTED_SEGMENT_SIZE);
+        if body_size < TAG_SIZE || body_size - segments * TAG_SIZE > segments * SEGMENT_SIZE_BYTES {
+            anyhow::bail!(corrupted(object, "truncated contents"));
+        }
+
+        Ok(Some(OpenedObject::Encrypted(Box::new(EncryptedObject {
+            metadata: StoredFileMetadata {
+                size: body_size - segments * TAG_SIZE,
+                content_type: stored.content_type,
+                content_encoding: stored.content_encoding,
+            },
+            header_size,
+            body_size,
+            cipher,
+        }))))
+    }
+
+    fn plaintext(
+        &self,
+        object: &Object<'_>,
+        stored: StoredFileMetadata,
+    ) -> anyhow::Result<OpenedObject> {
+        if !self.allow_plaintext_reads {
+            anyhow::bail!("object {object} is not encrypted and plaintext reads are not allowed");
+        }
+
+        Ok(OpenedObject::Plaintext(stored))
+    }
+
+    fn parse_header(&self, header: &[u8]) -> Result<(u64, Aes256Gcm), String> {
+        let invalid = || "invalid header".to_string();
+        let master_keys = self
+            .master_keys
+            .as_ref()
+            .ok_or_else(|| "no master key is configured".to_string())?;
+
+        let rest = header.strip_prefix(MAGIC).ok_or_else(invalid)?;
+        let (key_id_len, rest) = rest.split_first().ok_or_else(invalid)?;
+        let key_id_len = *key_id_len as usize;
+        if rest.len() < key_id_len + NONCE_SIZE + WRAPPED_KEY_SIZE {
+            return Err(invalid());
+        }
+
+        let key_id = String::from_utf8_lossy(&rest[..key_id_len]);
+        let master_key = master_keys
+            .keys
+            .get(key_id.as_ref())
+            .ok_or_else(|| format!("unknown master key {key_id}"))?;
+
+        let nonce: [u8; NONCE_SIZE] = rest[key_id_len..key_id_len + NONCE_SIZE]
+            .try_into()
+            .map_err(|_| invalid())?;
+        let wrapped_key =
+            &rest[key_id_len + NONCE_SIZE..key_id_len + NONCE_SIZE + WRAPPED_KEY_SIZE];
+        let aad = &header[..MAGIC.len() + 1 + key_id_len];
+        let data_key = master_key
+            .decrypt(
+                &Nonce::from(nonce),
+                Payload {
+                    msg: wrapped_key,
+                    aad,
+                },
+            )
+            .map_err(|_| format!("failed to unwrap data key with master key {key_id}"))?
+            .try_into()
+            .map_err(|_| invalid())?;
+
+        let header_size = (aad.len() + NONCE_SIZE + WRAPPED_KEY_SIZE) as u64;
+        Ok((header_size, cipher(&data_key)))
+    }
+
+    async fn read(
+        &self,
+        object: Object<'_>,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>> {
+        let encrypted = match self.open(&object).await? {
+            Some(OpenedObject::Encrypted(encrypted)) => encrypted,
+            Some(OpenedObject::Plaintext(_)) => {
+                return object.content(self.inner.as_ref(), range).await;
+            }
+            None => return Ok(None),
+        };
+
+        let (start, len) = match range {
+            Some(range) => (*range.start(), range.end() - range.start() + 1),
+            None => (0, encrypted.metadata.size),
+        };
+        let first_segment = start / SEGMENT_SIZE_BYTES;
+        let last_segment = (start + len.max(1) - 1) / SEGMENT_SIZE_BYTES;
+
+        let inner_start = encrypted.header_size + first_segment * ENCRYPTED_SEGMENT_SIZE;
+        let inner_end = (encrypted.header_size + (last_segment + 1) * ENCRYPTED_SEGMENT_SIZE)
+            .min(encrypted.header_size + encrypted.body_size)
+            - 1;
+        let Some(content) = object
+            .content(self.inner.as_ref(), Some(inner_start..=inner_end))
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let state = DecryptState {
+            content,
+            buffer: BytesMut::new(),
+            cipher: encrypted.cipher,
+            index: first_segment,
+            last_segment,
+            final_segment: encrypted.body_size.div_ceil(ENCRYPTED_SEGMENT_SIZE) - 1,
+            skip: (start - first_segment * SEGMENT_SIZE_BYTES) as usize,
+            remaining: len,
+            ended: false,
+            done: false,
+        };
+
+        Ok(Some(Box::pin(stream::unfold(state, DecryptState::next))))
+    }
+}
+
+#[async_trait]
+impl FileStorage for EncryptedFileStorage {
+    async fn get_file_metadata(
+        &self,
+        id: Uuid,
+        name: &str,
+    ) -> anyhow::Result<Option<StoredFileMetadata>> {
+        Ok(self
+            .open(&Object::File(id, name))
+            .await?
+            .map(OpenedObject::into_metadata))
+    }
+
+    async fn get_file_content(
+        &self,
+        id: Uuid,
+        name: &str,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>> {
+        self.read(Object::File(id, name), range).await
+    }
+
+    async fn insert_file_content(
+        &self,
+        id: Uuid,
+        name: &str,
+        content_type: &str,
+        content_encoding: Option<ContentEncoding>,
+        content: FileStream,
+    ) -> anyhow::Result<()> {
+        self.inner
+            .insert_file_content(
+                id,
+                name,
+                content_type,
+                content_encoding,
+                self.encrypt(content)?,
+            )
+            .await
+    }
+
+    async fn delete_file_content(&self, id: Uuid, name: &str) -> anyhow::Result<()> {
+        self.inner.delete_file_content(id, name).await
+    }
+
+    async fn delete_prefix(&self, id: Uuid) -> anyhow::Result<()> {
+        self.inner.delete_prefix(id).await
+    }
+
+    // Names are not encrypted, only contents are.
+    async fn list(&self, id: Uuid) -> anyhow::Result<Vec<StoredObject>> {
+        self.inner.list(id).await
+    }
+
+    async fn get_blob_metadata(&self, hash: Hash32) -> anyhow::Result<Option<StoredFileMetadata>> {
+        Ok(self
+            .open(&Object::Blob(hash))
+            .await?
+            .map(OpenedObject::into_metadata))
+    }
+
+    async fn get_blob_content(
+        &self,
+        hash: Hash32,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>> {
+        self.read(Object::Blob(hash), range).await
+    }
+
+    // Objects carry their own wrapped data key, so they are moved as they are.
+    async fn move_to_blob(&self, id: Uuid, name: &str, hash: Hash32) -> anyhow::Result<()> {
+        self.inner.move_to_blob(id, name, hash).await
+    }
+
+    async fn delete_blob(&self, hash: Hash32) -> anyhow::Result<()> {
+        self.inner.delete_blob(hash).await
+    }
+}
+
+enum OpenedObject {
+    /// Stored before encryption was enabled, or without master keys.
+    Plaintext(StoredFileMetadata),
+    Encrypted(Box<EncryptedObject>),
+}
+
+impl OpenedObject {
+    fn into_metadata(self) -> StoredFileMetadata {
+        match self {
+            Self::Plaintext(metadata) => metadata,
+            Self::Encrypted(encrypted) => encrypted.metadata,
+        }
+    }
+}
+
+struct EncryptedObject {
+    metadata: StoredFileMetadata,
+    header_size: u64,
+    body_size: u64,
+    cipher: Aes256Gcm,
+}
+
+struct EncryptState {
+    content: FileStream,
+    buffer: BytesMut,
+    header: Option<Bytes>,
+    cipher: Aes256Gcm,
+    index: u64,
+    done: bool,
+}
+
+impl EncryptState {
+    // A full segment is only encrypted once more plaintext follows it, otherwise it could be the
+    // last one.
+    async fn next(mut self) -> Option<(io::Result<Bytes>, Self)> {
+        if let Some(header) = self.header.take() {
+            return Some((Ok(header), self));
+        }
+
+        loop {
+            if self.done {
+                return None;
+            }
+
+            if self.buffer.len() as u64 > SEGMENT_SIZE_BYTES {
+                let segment = self.buffer.split_to(SEGMENT_SIZE_BYTES as usize);
+                let encrypted = self.seal(&segment, false);
+                return Some((encrypted, self));
+            }
+
+            match self.content.next().await {
+                Some(Ok(chunk)) => self.buffer.extend_from_slice(&chunk),
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some((Err(e), self));
+                }
+                None => {
+                    self.done = true;
+                    let segment = self.buffer.split();
+                    let encrypted = self.seal(&segment, true);
+                    return Some((encrypted, self));
+                }
+            }
+        }
+    }
+
+    fn seal(&mut self, segment: &[u8], last: bool) -> io::Result<Bytes> {
+        let nonce = segment_nonce(self.index, last);
+        self.index += 1;
+
+        self.cipher
+            .encrypt(&Nonce::from(nonce), segment)
+            .map(Bytes::from)
+            .map_err(|_| io::Error::other("failed to encrypt segment"))
+    }
+}
+
+struct DecryptState {
+    content: FileStream,
+    buffer: BytesMut,
+    cipher: Aes256Gcm,
+    index: u64,
+    /// Last segment covered by the range being read.
+    last_segment: u64,
+    /// Last segment of the object.
+    final_segment: u64,
+    skip: usize,
+    remaining: u64,
+    ended: bool,
+    done: bool,
+}
+
+impl DecryptState {
+    async fn next(mut self) -> Option<(io::Result<Bytes>, Self)> {
+        loop {
+            if self.done {
+                return None;
+            }
+
+            if self.buffer.len() as u64 >= ENCRYPTED_SEGMENT_SIZE
+                || (self.ended && !self.buffer.is_empty())
+            {
+                let len = (self.buffer.len() as u64).min(ENCRYPTED_SEGMENT_SIZE) as usize;
+                let segment = self.buffer.split_to(len);
+                let plaintext = self.open(&segment);
+                return Some((plaintext, self));
+            }
+
+            if self.ended {
+                self.done = true;
+                return Some((
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "encrypted contents are truncated",
+                    )),
+                    self,
+                ));
+            }
+
+            match self.content.next().await {
+                Some(Ok(chunk)) => self.buffer.extend_from_slice(&chunk),
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some((Err(e), self));
+                }
+                None => self.ended = true,
+            }
+        }
+    }
+
+    fn open(&mut self, segment: &[u8]) -> io::Result<Bytes> {
+        let nonce = segment_nonce(self.index, self.index == self.final_segment);
+        self.done = self.index == self.last_segment;
+        self.index += 1;
+
+        let plaintext = self
+            .cipher
+            .decrypt(&Nonce::from(nonce), segment)
+            .map_err(|_| {
+                self.done = true;
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "failed to decrypt stored contents",
+                )
+            })?;
+
+        let mut plaintext = Bytes::from(plaintext);
+        let skip = std::mem::take(&mut self.skip).min(plaintext.len());
+        let _ = plaintext.split_to(skip);
+        plaintext.truncate(self.remaining.min(plaintext.len() as u64) as usize);
+        self.remaining -= plaintext.len() as u64;
+
+        Ok(plaintext)
+    }
+}
+
+fn segment_nonce(index: u64, last: bool) -> [u8; NONCE_SIZE] {
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce[..8].copy_from_slice(&index.to_be_bytes());
+    nonce[NONCE_SIZE - 1] = last as u8;
+    nonce
+}
+
+fn cipher(key: &[u8; 32]) -> Aes256Gcm {
+    Aes256Gcm::new(&Key::<Aes256Gcm>::from(*key))
+}
+
+fn header(master_keys: &MasterKeys, data_key: &Key<Aes256Gcm>) -> anyhow::Result<Bytes> {
+    let key_id = master_keys.active_key_id.as_bytes();
+    let master_key = &master_keys.keys[&master_keys.active_key_id];
+
+    let mut header = BytesMut::with_capacity(MAX_HEADER_SIZE as usize);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&[key_id.len() as u8]);
+    header.extend_from_slice(key_id);
+
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let wrapped_key = master_key
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: data_key,
+                aad: &header,
+            },
+        )
+        .map_err(|_| anyhow::anyhow!("failed to wrap data key"))?;
+    header.extend_from_slice(&nonce);
+    header.extend_from_slice(&wrapped_key);
+
+    Ok(header.freeze())
+}
+
+fn corrupted(object: &Object<'_>, reason: &str) -> String {
+    format!("encrypted object {object} is corrupted: {reason}")
+}
+
+fn parse_master_key(entry: &str) -> anyhow::Result<(String, [u8; 32])> {
+    let (key_id, key) = entry
+        .trim()
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("master keys must be given as key_id:hex"))?;
+
+    if key_id.is_empty() || key_id.len() > MAX_KEY_ID_LEN {
+        anyhow::bail!("master key ids must be between 1 and 255 bytes long");
+    }
+
+    let key: [u8; 32] = hex::decode(key)
+        .map_err(|e| anyhow::anyhow!("invalid master key {key_id}: {e}"))?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("master key {key_id} must be 32 bytes long"))?;
+
+    Ok((key_id.to_string(), key))
+}
@@ -1,12 +1,48 @@
-use crate::repositories::FileStorage;
+use crate::repositories::{
+    ContentEncoding, FileStorage, FileStream, StoredFileMetadata, StoredObject,
+};
 use async_trait::async_trait;
-use std::collections::HashMap;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use file_server_library::models::Hash32;
+use futures::{TryStreamExt, stream};
+use std::{collections::HashMap, ops::RangeInclusive};
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
+struct StoredFile {
+    content: Bytes,
+    content_type: String,
+    content_encoding: Option<ContentEncoding>,
+    stored_at: DateTime<Utc>,
+}
+
+impl StoredFile {
+    fn metadata(&self) -> StoredFileMetadata {
+        StoredFileMetadata {
+            size: self.content.len() as u64,
+            content_type: self.content_type.clone(),
+            content_encoding: self.content_encoding,
+        }
+    }
+
+    // `Bytes` slices are cheap, they share the same buffer.
+    fn content(&self, range: Option<RangeInclusive<u64>>) -> FileStream {
+        let content = match range {
+            Some(range) => self
+                .content
+                .slice(*range.start() as usize..=*range.end() as usize),
+            None => self.content.clone(),
+        };
+
+        Box::pin(stream::iter([Ok(content)]))
+    }
+}
+
 #[derive(Default)]
 pub struct InMemoryFileStorage {
-    file_tree_contents: Mutex<HashMap<(Uuid, String), Vec<u8>>>,
+    file_tree_contents: Mutex<HashMap<(Uuid, String), StoredFile>>,
+    blobs: Mutex<HashMap<Hash32, StoredFile>>,
 }
 
 #[async_trait]
@@ -15,17 +51,113 @@ impl FileStorage for InMemoryFileStorage {
         &self,
         id: Uuid,
         name: &str,
-        content: &[u8],
+        content_type: &str,
+        content_encoding: Option<ContentEncoding>,
+        content: FileStream,
     ) -> anyhow::Result<()> {
+        // The stream is drained before taking the lock so slow uploads do not block each other.
+        let content = content
+            .try_fold(Vec::new(), |mut acc, chunk| async move {
+                acc.extend_from_slice(&chunk);
+                Ok(acc)
+            })
+            .await?;
+
         let mut file_tree_contents = self.file_tree_contents.lock().await;
 
-        file_tree_contents.insert((id, name.to_string()), content.to_vec());
+        file_tree_contents.insert(
+            (id, name.to_string()),
+            StoredFile {
+                content: Bytes::from(content),
+                content_type: content_type.to_owned(),
+                content_encoding,
+                stored_at: Utc::now(),
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn get_file_metadata(
+        &self,
+        id: Uuid,
+        name: &str,
+    ) -> anyhow::Result<Option<StoredFileMetadata>> {
+        let file_tree_contents = self.file_tree_contents.lock().await;
+
+        Ok(file_tree_contents
+            .get(&(id, name.to_string()))
+            .map(StoredFile::metadata))
+    }
+
+    async fn get_file_content(
+        &self,
+        id: Uuid,
+        name: &str,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>> {
+        let file_tree_contents = self.file_tree_contents.lock().await;
+
+        Ok(file_tree_contents
+            .get(&(id, name.to_string()))
+            .map(|file| file.content(range)))
+    }
+
+    async fn delete_file_content(&self, id: Uuid, name: &str) -> anyhow::Result<()> {
+        let mut file_tree_contents = self.file_tree_contents.lock().await;
+        file_tree_contents.remove(&(id, name.to_string()));
+        Ok(())
+    }
 
+    async fn delete_prefix(&self, id: Uuid) -> anyhow::Result<()> {
+        let mut file_tree_contents = self.file_tree_contents.lock().await;
+        file_tree_contents.retain(|(tree_id, _), _| *tree_id != id);
         Ok(())
     }
 
-    async fn get_file_content(&self, id: Uuid, name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    async fn list(&self, id: Uuid) -> anyhow::Result<Vec<StoredObject>> {
         let file_tree_contents = self.file_tree_contents.lock().await;
-        Ok(file_tree_contents.get(&(id, name.to_string())).cloned())
+
+        Ok(file_tree_contents
+            .iter()
+            .filter(|((tree_id, _), _)| *tree_id == id)
+            .map(|((_, name), file)| StoredObject {
+                name: name.clone(),
+                size: file.content.len() as u64,
+                last_modified: file.stored_at,
+            })
+            .collect())
+    }
+
+    async fn get_blob_metadata(&self, hash: Hash32) -> anyhow::Result<Option<StoredFileMetadata>> {
+        let blobs = self.blobs.lock().await;
+        Ok(blobs.get(&hash).map(StoredFile::metadata))
+    }
+
+    async fn get_blob_content(
+        &self,
+        hash: Hash32,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>> {
+        let blobs = self.blobs.lock().await;
+        Ok(blobs.get(&hash).map(|blob| blob.content(range)))
+    }
+
+    async fn move_to_blob(&self, id: Uuid, name: &str, hash: Hash32) -> anyhow::Result<()> {
+        let mut file_tree_contents = self.file_tree_contents.lock().await;
+        let file = file_tree_contents
+            .remove(&(id, name.to_string()))
+            .ok_or_else(|| anyhow::anyhow!("missing object {id}/{name}"))?;
+
+        let mut blobs = self.blobs.lock().await;
+        blobs.entry(hash).or_insert(file);
+
+        Ok(())
+    }
+
+    async fn delete_blob(&self, hash: Hash32) -> anyhow::Result<()> {
+        let mut blobs = self.blobs.lock().await;
+        blobs.remove(&hash);
+        Ok(())
     }
 }
@@ -1,13 +1,162 @@
+use std::{io, ops::RangeInclusive, pin::Pin, str::FromStr};
+
 use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use file_server_library::models::Hash32;
+use futures::Stream;
 use uuid::Uuid;
 
+/// File contents are moved around as a stream of chunks so that uploads never need to be fully
+/// buffered in memory.
+pub type FileStream = Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send>>;
+
+/// Encoding stored contents are kept in, recorded along with them so they can be decoded on the
+/// way out. Names follow the HTTP `Content-Encoding` tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Zstd,
+}
+
+impl ContentEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Zstd => "zstd",
+        }
+    }
+}
+
+impl FromStr for ContentEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "zstd" => Ok(ContentEncoding::Zstd),
+            other => Err(anyhow::anyhow!("unsupported content encoding {other}")),
+        }
+    }
+}
+
+/// `content_encoding` is `None` when the contents are stored as they were given.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredFileMetadata {
+    pub size: u64,
+    pub content_type: String,
+    pub content_encoding: Option<ContentEncoding>,
+}
+
+/// An object stored for an upload, as listed by `FileStorage::list`. `size` is the stored size,
+/// which differs from the size of the contents when they are encoded or encrypted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredObject {
+    pub name: String,
+    pub size: u64,
+    pub last_modified: DateTime<Utc>,
+}
+
+/// Blob contents as they are stored, see `FileStorage::get_encoded_blob_content`.
+pub struct EncodedContent {
+    pub encoding: ContentEncoding,
+    pub size: u64,
+    pub content: FileStream,
+}
+
 #[async_trait]
 pub trait FileStorage: Send + Sync {
-    async fn get_file_content(&self, id: Uuid, name: &str) -> anyhow::Result<Option<Vec<u8>>>;
-    async fn insert_file_content(&self, id: Uuid, name: &str, content: &[u8])
-    -> anyhow::Result<()>;
+    async fn get_file_metadata(
+        &self,
+        id: Uuid,
+        name: &str,
+    ) -> anyhow::Result<Option<StoredFileMetadata>>;
+    /// `range` is inclusive on both ends and must be within the stored file size.
+    async fn get_file_content(
+        &self,
+        id: Uuid,
+        name: &str,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>>;
+    /// The stream must be consumed until the end, callers hash the content while it is stored.
+    async fn insert_file_content(
+        &self,
+        id: Uuid,
+        name: &str,
+        content_type: &str,
+        content_encoding: Option<ContentEncoding>,
+        content: FileStream,
+    ) -> anyhow::Result<()>;
+    /// Deleting a missing object is not an error.
+    async fn delete_file_content(&self, id: Uuid, name: &str) -> anyhow::Result<()>;
+    /// Deletes every object stored for the given upload.
+    async fn delete_prefix(&self, id: Uuid) -> anyhow::Result<()>;
+    /// Every object stored for the given upload, blobs are not listed.
+    async fn list(&self, id: Uuid) -> anyhow::Result<Vec<StoredObject>>;
+    /// File contents are stored once per hash, see `move_to_blob`.
+    async fn get_blob_metadata(&self, hash: Hash32) -> anyhow::Result<Option<StoredFileMetadata>>;
+    /// `range` is inclusive on both ends and must be within the stored blob size.
+    async fn get_blob_content(
+        &self,
+        hash: Hash32,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>>;
+    /// Moves an object stored for the given upload into the blob of its hash. When the blob
+    /// already exists the object is just deleted, its contents are the same.
+    async fn move_to_blob(&self, id: Uuid, name: &str, hash: Hash32) -> anyhow::Result<()>;
+    /// Deleting a missing blob is not an error.
+    async fn delete_blob(&self, hash: Hash32) -> anyhow::Result<()>;
+    /// The encoded contents of the blob, without decoding them, so they can be sent as they are
+    /// to clients accepting their encoding. `None` when the blob is missing or not encoded.
+    async fn get_encoded_blob_content(
+        &self,
+        _hash: Hash32,
+    ) -> anyhow::Result<Option<EncodedContent>> {
+        Ok(None)
+    }
+}
+
+/// Objects are read the same way whether they belong to an upload or are blobs.
+enum Object<'a> {
+    File(Uuid, &'a str),
+    Blob(Hash32),
+}
+
+impl Object<'_> {
+    async fn metadata(
+        &self,
+        inner: &dyn FileStorage,
+    ) -> anyhow::Result<Option<StoredFileMetadata>> {
+        match self {
+            Object::File(id, name) => inner.get_file_metadata(*id, name).await,
+            Object::Blob(hash) => inner.get_blob_metadata(*hash).await,
+        }
+    }
+
+    async fn content(
+        &self,
+        inner: &dyn FileStorage,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>> {
+        match self {
+            Object::File(id, name) => inner.get_file_content(*id, name, range).await,
+            Object::Blob(hash) => inner.get_blob_content(*hash, range).await,
+        }
+    }
+}
+
+impl std::fmt::Display for Object<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Object::File(id, name) => write!(f, "{id}/{name}"),
+            Object::Blob(hash) => write!(f, "blob {}", hash.to_hex()),
+        }
+    }
 }
 
+mod compressed;
+pub use compressed::{CompressedFileStorage, CompressionConfig};
+
+mod encrypted;
+pub use encrypted::{EncryptedFileStorage, EncryptionConfig, MasterKeys};
+
 #[cfg(feature = "in-memory")]
 mod in_memory;
 #[cfg(feature = "in-memory")]
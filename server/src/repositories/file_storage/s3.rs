@@ -1,15 +1,60 @@
-use crate::repositories::FileStorage;
+use crate::repositories::{
+    ContentEncoding, FileStorage, FileStream, StoredFileMetadata, StoredObject,
+};
 use anyhow::Context;
 use async_trait::async_trait;
-use aws_sdk_s3::{Client, config::Builder, error::SdkError, primitives::ByteStream};
+use aws_sdk_s3::{
+    Client,
+    config::Builder,
+    error::SdkError,
+    primitives::ByteStream,
+    types::{CompletedMultipartUpload, CompletedPart, Delete, ObjectIdentifier},
+};
+use bytes::{Bytes, BytesMut};
+use chrono::DateTime;
 use config::Config;
+use file_server_library::models::Hash32;
+use futures::TryStreamExt;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
 use serde::Deserialize;
+use std::ops::RangeInclusive;
+use tokio_util::io::ReaderStream;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Default)]
+// S3 rejects multipart uploads whose parts (except the last one) are smaller than 5 MiB.
+const MIN_PART_SIZE_BYTES: usize = 5 * 1024 * 1024;
+const DEFAULT_PART_SIZE_BYTES: usize = 8 * 1024 * 1024;
+// `copy_object` is limited to 5 GiB, bigger objects have to be copied part by part.
+const MAX_COPY_OBJECT_BYTES: u64 = 5 * 1024 * 1024 * 1024;
+const COPY_PART_SIZE_BYTES: u64 = 512 * 1024 * 1024;
+
+const BLOBS_PREFIX: &str = "blobs/";
+
+// Copy sources are sent as `{bucket}/{key}` in a header, so keys must be URL-encoded.
+const COPY_SOURCE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// Files up to `part_size_bytes` are stored with a single `put_object`, bigger ones are sent as
+/// a multipart upload with parts of that size. This also bounds the memory used per upload.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub struct S3Config {
     pub bucket: String,
+    #[serde(default = "S3Config::default_part_size_bytes")]
+    pub part_size_bytes: usize,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            bucket: String::default(),
+            part_size_bytes: Self::default_part_size_bytes(),
+        }
+    }
 }
 
 impl S3Config {
@@ -23,17 +68,31 @@ impl S3Config {
             .try_deserialize::<S3Config>()
             .map_err(|e| anyhow::anyhow!("failed to load S3 Configuration: {}", e))
     }
+
+    fn default_part_size_bytes() -> usize {
+        DEFAULT_PART_SIZE_BYTES
+    }
 }
 
 #[derive(Clone)]
 pub struct S3FileStorage {
     client: Client,
     bucket: String,
+    part_size_bytes: usize,
 }
 
 impl S3FileStorage {
     pub fn from_client(client: Client, bucket: String) -> Self {
-        Self { client, bucket }
+        Self {
+            client,
+            bucket,
+            part_size_bytes: DEFAULT_PART_SIZE_BYTES,
+        }
+    }
+
+    pub fn with_part_size(mut self, part_size_bytes: usize) -> Self {
+        self.part_size_bytes = part_size_bytes.max(MIN_PART_SIZE_BYTES);
+        self
     }
 
     pub async fn load_from_env() -> anyhow::Result<Self> {
@@ -44,42 +103,75 @@ impl S3FileStorage {
         let conf = Builder::from(&aws_config).force_path_style(true).build();
         let client = Client::from_conf(conf);
 
-        Ok(Self {
-            client,
-            bucket: config.bucket,
-        })
+        Ok(Self::from_client(client, config.bucket).with_part_size(config.part_size_bytes))
     }
 
     fn key(&self, id: Uuid, name: &str) -> String {
         format!("{id}/{name}")
     }
-}
 
-#[async_trait]
-#[async_trait]
-impl FileStorage for S3FileStorage {
-    async fn get_file_content(&self, id: Uuid, name: &str) -> anyhow::Result<Option<Vec<u8>>> {
-        let key = self.key(id, name);
+    fn prefix(&self, id: Uuid) -> String {
+        format!("{id}/")
+    }
+
+    fn blob_key(&self, hash: Hash32) -> String {
+        format!("{BLOBS_PREFIX}{}", hash.to_hex())
+    }
+
+    fn copy_source(&self, key: &str) -> String {
+        format!("{}/{}", self.bucket, utf8_percent_encode(key, COPY_SOURCE))
+    }
 
+    async fn head_object(&self, key: &str) -> anyhow::Result<Option<StoredFileMetadata>> {
         match self
             .client
-            .get_object()
+            .head_object()
             .bucket(&self.bucket)
-            .key(&key)
+            .key(key)
             .send()
             .await
         {
-            Ok(out) => {
-                let bytes = out
-                    .body
-                    .collect()
-                    .await
-                    .with_context(|| "failed to read S3 body")?
-                    .into_bytes()
-                    .to_vec();
-
-                Ok(Some(bytes))
+            Ok(out) => Ok(Some(StoredFileMetadata {
+                size: out.content_length().unwrap_or_default().max(0) as u64,
+                content_type: out
+                    .content_type()
+                    .unwrap_or(mime::APPLICATION_OCTET_STREAM.as_ref())
+                    .to_owned(),
+                content_encoding: out
+                    .content_encoding()
+                    .map(str::parse)
+                    .transpose()
+                    .with_context(|| format!("head_object {}/{}", self.bucket, key))?,
+            })),
+            Err(e) => {
+                if let SdkError::ServiceError(se) = &e
+                    && se.raw().status().as_u16() == 404
+                {
+                    return Ok(None);
+                }
+                Err(anyhow::anyhow!(e))
+                    .with_context(|| format!("head_object {}/{}", self.bucket, key))
             }
+        }
+    }
+
+    async fn get_object(
+        &self,
+        key: &str,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>> {
+        match self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .set_range(range.map(|r| format!("bytes={}-{}", r.start(), r.end())))
+            .send()
+            .await
+        {
+            Ok(out) => Ok(Some(Box::pin(ReaderStream::new(
+                out.body.into_async_read(),
+            )))),
             Err(e) => {
                 if let SdkError::ServiceError(se) = &e
                     && se.raw().status().as_u16() == 404
@@ -92,19 +184,49 @@ impl FileStorage for S3FileStorage {
         }
     }
 
-    async fn insert_file_content(
+    async fn delete_object(&self, key: &str) -> anyhow::Result<()> {
+        // S3 answers 204 for missing keys as well, so there is no need to special case 404 here.
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+            .with_context(|| format!("delete_object {}/{}", self.bucket, key))?;
+
+        Ok(())
+    }
+
+    // Reads from the stream until a full part is buffered or the stream ends. The returned part
+    // is only shorter than `part_size_bytes` when it is the last one.
+    async fn read_part(&self, content: &mut FileStream) -> anyhow::Result<Bytes> {
+        let mut part = BytesMut::new();
+
+        while part.len() < self.part_size_bytes {
+            match content.try_next().await? {
+                Some(chunk) => part.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+
+        Ok(part.freeze())
+    }
+
+    async fn put_object(
         &self,
-        id: Uuid,
-        name: &str,
-        content: &[u8],
+        key: &str,
+        content_type: &str,
+        content_encoding: Option<ContentEncoding>,
+        part: Bytes,
     ) -> anyhow::Result<()> {
-        let key = self.key(id, name);
-
         self.client
             .put_object()
             .bucket(&self.bucket)
-            .key(&key)
-            .body(ByteStream::from(content.to_vec()))
+            .key(key)
+            .content_type(content_type)
+            .set_content_encoding(content_encoding.map(|encoding| encoding.as_str().to_owned()))
+            .body(ByteStream::from(part))
             .send()
             .await
             .map_err(|e| anyhow::anyhow!(e))
@@ -112,4 +234,381 @@ impl FileStorage for S3FileStorage {
 
         Ok(())
     }
+
+    async fn multipart_upload(
+        &self,
+        key: &str,
+        content_type: &str,
+        content_encoding: Option<ContentEncoding>,
+        first_part: Bytes,
+        content: FileStream,
+    ) -> anyhow::Result<()> {
+        let upload_id = self
+            .create_multipart_upload(key, content_type, content_encoding)
+            .await?;
+        let parts = self
+            .upload_parts(key, &upload_id, first_part, content)
+            .await;
+
+        self.finish_multipart_upload(key, &upload_id, parts).await
+    }
+
+    async fn create_multipart_upload(
+        &self,
+        key: &str,
+        content_type: &str,
+        content_encoding: Option<ContentEncoding>,
+    ) -> anyhow::Result<String> {
+        let upload = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .set_content_encoding(content_encoding.map(|encoding| encoding.as_str().to_owned()))
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+            .with_context(|| format!("create_multipart_upload {}/{}", self.bucket, key))?;
+        let upload_id = upload
+            .upload_id()
+            .with_context(|| format!("missing upload id for {}/{}", self.bucket, key))?;
+
+        Ok(upload_id.to_owned())
+    }
+
+    async fn finish_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: anyhow::Result<Vec<CompletedPart>>,
+    ) -> anyhow::Result<()> {
+        let result = match parts {
+            Ok(parts) => self
+                .client
+                .complete_multipart_upload()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .multipart_upload(
+                    CompletedMultipartUpload::builder()
+                        .set_parts(Some(parts))
+                        .build(),
+                )
+                .send()
+                .await
+                .map(|_| ())
+                .map_err(|e| anyhow::anyhow!(e))
+                .with_context(|| format!("complete_multipart_upload {}/{}", self.bucket, key)),
+            Err(e) => Err(e),
+        };
+
+        // Parts of an unfinished multipart upload are billed until the upload is aborted.
+        if result.is_err() {
+            let _ = self
+                .client
+                .abort_multipart_upload()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .send()
+                .await;
+        }
+
+        result
+    }
+
+    async fn upload_parts(
+        &self,
+        key: &str,
+        upload_id: &str,
+        first_part: Bytes,
+        mut content: FileStream,
+    ) -> anyhow::Result<Vec<CompletedPart>> {
+        let mut parts = Vec::new();
+        let mut part = first_part;
+
+        while !part.is_empty() {
+            let part_number = parts.len() as i32 + 1;
+
+            let output = self
+                .client
+                .upload_part()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(ByteStream::from(part))
+                .send()
+                .await
+                .map_err(|e| anyhow::anyhow!(e))
+                .with_context(|| format!("upload_part {} {}/{}", part_number, self.bucket, key))?;
+
+            parts.push(
+                CompletedPart::builder()
+                    .set_e_tag(output.e_tag().map(str::to_owned))
+                    .part_number(part_number)
+                    .build(),
+            );
+
+            part = self.read_part(&mut content).await?;
+        }
+
+        Ok(parts)
+    }
+
+    async fn copy_object(
+        &self,
+        source_key: &str,
+        key: &str,
+        source: &StoredFileMetadata,
+    ) -> anyhow::Result<()> {
+        if source.size > MAX_COPY_OBJECT_BYTES {
+            return self.multipart_copy(source_key, key, source).await;
+        }
+
+        self.client
+            .copy_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .copy_source(self.copy_source(source_key))
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!(e))
+            .with_context(|| format!("copy_object {} to {}/{}", source_key, self.bucket, key))?;
+
+        Ok(())
+    }
+
+    async fn multipart_copy(
+        &self,
+        source_key: &str,
+        key: &str,
+        source: &StoredFileMetadata,
+    ) -> anyhow::Result<()> {
+        let upload_id = self
+            .create_multipart_upload(key, &source.content_type, source.content_encoding)
+            .await?;
+        let parts = self
+            .copy_parts(source_key, key, &upload_id, source.size)
+            .await;
+
+        self.finish_multipart_upload(key, &upload_id, parts).await
+    }
+
+    async fn copy_parts(
+        &self,
+        source_key: &str,
+        key: &str,
+        upload_id: &str,
+        size: u64,
+    ) -> anyhow::Result<Vec<CompletedPart>> {
+        let mut parts = Vec::new();
+        let mut start = 0;
+
+        while start < size {
+            let end = (start + COPY_PART_SIZE_BYTES).min(size) - 1;
+            let part_number = parts.len() as i32 + 1;
+
+            let output = self
+                .client
+                .upload_part_copy()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .copy_source(self.copy_source(source_key))
+                .copy_source_range(format!("bytes={start}-{end}"))
+                .send()
+                .await
+                .map_err(|e| anyhow::anyhow!(e))
+                .with_context(|| {
+                    format!("upload_part_copy {} {}/{}", part_number, self.bucket, key)
+                })?;
+
+            parts.push(
+                CompletedPart::builder()
+                    .set_e_tag(
+                        output
+                            .copy_part_result()
+                            .and_then(|result| result.e_tag())
+                            .map(str::to_owned),
+                    )
+                    .part_number(part_number)
+                    .build(),
+            );
+
+            start = end + 1;
+        }
+
+        Ok(parts)
+    }
+}
+
+#[async_trait]
+impl FileStorage for S3FileStorage {
+    async fn get_file_metadata(
+        &self,
+        id: Uuid,
+        name: &str,
+    ) -> anyhow::Result<Option<StoredFileMetadata>> {
+        self.head_object(&self.key(id, name)).await
+    }
+
+    async fn get_file_content(
+        &self,
+        id: Uuid,
+        name: &str,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>> {
+        self.get_object(&self.key(id, name), range).await
+    }
+
+    async fn insert_file_content(
+        &self,
+        id: Uuid,
+        name: &str,
+        content_type: &str,
+        content_encoding: Option<ContentEncoding>,
+        mut content: FileStream,
+    ) -> anyhow::Result<()> {
+        let key = self.key(id, name);
+        let first_part = self.read_part(&mut content).await?;
+
+        if first_part.len() < self.part_size_bytes {
+            return self
+                .put_object(&key, content_type, content_encoding, first_part)
+                .await;
+        }
+
+        self.multipart_upload(&key, content_type, content_encoding, first_part, content)
+            .await
+    }
+
+    async fn delete_file_content(&self, id: Uuid, name: &str) -> anyhow::Result<()> {
+        self.delete_object(&self.key(id, name)).await
+    }
+
+    // Each `list_objects_v2` page holds up to 1000 keys, which is also the maximum accepted by
+    // `delete_objects`, so every page is deleted with a single request.
+    async fn delete_prefix(&self, id: Uuid) -> anyhow::Result<()> {
+        let prefix = self.prefix(id);
+        let mut continuation_token = None;
+
+        loop {
+            let page = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(&prefix)
+                .set_continuation_token(continuation_token)
+                .send()
+                .await
+                .map_err(|e| anyhow::anyhow!(e))
+                .with_context(|| format!("list_objects_v2 {}/{}", self.bucket, prefix))?;
+
+            let objects = page
+                .contents()
+                .iter()
+                .filter_map(|object| object.key())
+                .map(|key| ObjectIdentifier::builder().key(key).build())
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if !objects.is_empty() {
+                let delete = Delete::builder().set_objects(Some(objects)).build()?;
+
+                self.client
+                    .delete_objects()
+                    .bucket(&self.bucket)
+                    .delete(delete)
+                    .send()
+                    .await
+                    .map_err(|e| anyhow::anyhow!(e))
+                    .with_context(|| format!("delete_objects {}/{}", self.bucket, prefix))?;
+            }
+
+            match page.next_continuation_token() {
+                Some(token) => continuation_token = Some(token.to_owned()),
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn list(&self, id: Uuid) -> anyhow::Result<Vec<StoredObject>> {
+        let prefix = self.prefix(id);
+        let mut continuation_token = None;
+        let mut objects = Vec::new();
+
+        loop {
+            let page = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(&prefix)
+                .set_continuation_token(continuation_token)
+                .send()
+                .await
+                .map_err(|e| anyhow::anyhow!(e))
+                .with_context(|| format!("list_objects_v2 {}/{}", self.bucket, prefix))?;
+
+            for object in page.contents() {
+                let (Some(key), Some(last_modified)) = (object.key(), object.last_modified())
+                else {
+                    continue;
+                };
+
+                objects.push(StoredObject {
+                    name: key.strip_prefix(&prefix).unwrap_or(key).to_owned(),
+                    size: object.size().unwrap_or_default().max(0) as u64,
+                    last_modified: DateTime::from_timestamp(
+                        last_modified.secs(),
+                        last_modified.subsec_nanos(),
+                    )
+                    .with_context(|| format!("bad last modified date for {key}"))?,
+                });
+            }
+
+            match page.next_continuation_token() {
+                Some(token) => continuation_token = Some(token.to_owned()),
+                None => break,
+            }
+        }
+
+        Ok(objects)
+    }
+
+    async fn get_blob_metadata(&self, hash: Hash32) -> anyhow::Result<Option<StoredFileMetadata>> {
+        self.head_object(&self.blob_key(hash)).await
+    }
+
+    async fn get_blob_content(
+        &self,
+        hash: Hash32,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>> {
+        self.get_object(&self.blob_key(hash), range).await
+    }
+
+    // S3 has no rename, so the object is copied into the blob and then deleted.
+    async fn move_to_blob(&self, id: Uuid, name: &str, hash: Hash32) -> anyhow::Result<()> {
+        let source_key = self.key(id, name);
+        let key = self.blob_key(hash);
+
+        if self.head_object(&key).await?.is_none() {
+            let source = self
+                .head_object(&source_key)
+                .await?
+                .with_context(|| format!("missing object {}/{}", self.bucket, source_key))?;
+
+            self.copy_object(&source_key, &key, &source).await?;
+        }
+
+        self.delete_object(&source_key).await
+    }
+
+    async fn delete_blob(&self, hash: Hash32) -> anyhow::Result<()> {
+        self.delete_object(&self.blob_key(hash)).await
+    }
 }
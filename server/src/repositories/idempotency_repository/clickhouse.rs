@@ -0,0 +1,116 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use clickhouse::{Client, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::repositories::{ClickhouseConfig, IdempotencyRepository, IdempotencyRow};
+
+const IDEMPOTENCY_TABLE_NAME: &str = "idempotency_keys";
+
+// Rows are dropped by the table TTL once they expire, reads skip the ones not dropped yet.
+pub struct ClickhouseIdempotencyRepository {
+    client: Client,
+}
+
+impl ClickhouseIdempotencyRepository {
+    pub fn new(config: ClickhouseConfig) -> Self {
+        let client = Client::default()
+            .with_url(config.database_url.to_owned())
+            .with_user(config.username.to_owned())
+            .with_password(config.password.to_owned())
+            .with_database(config.database_name.to_owned())
+            .with_option("send_progress_in_http_headers", "1");
+
+        Self::new_from_client(client)
+    }
+
+    pub fn new_from_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[derive(Serialize, Deserialize, Row)]
+struct ClickhouseIdempotencyRow {
+    owner: String,
+    key: String,
+    fingerprint: String,
+    status: u16,
+    content_type: Option<String>,
+    headers: Vec<(String, String)>,
+    body: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    expires_at: DateTime<Utc>,
+}
+
+impl From<IdempotencyRow> for ClickhouseIdempotencyRow {
+    fn from(x: IdempotencyRow) -> Self {
+        Self {
+            owner: x.owner,
+            key: x.key,
+            fingerprint: x.fingerprint,
+            status: x.status,
+            content_type: x.content_type,
+            headers: x.headers,
+            body: x.body,
+            expires_at: x.expires_at,
+        }
+    }
+}
+
+impl From<ClickhouseIdempotencyRow> for IdempotencyRow {
+    fn from(x: ClickhouseIdempotencyRow) -> Self {
+        Self {
+            owner: x.owner,
+            key: x.key,
+            fingerprint: x.fingerprint,
+            status: x.status,
+            content_type: x.content_type,
+            headers: x.headers,
+            body: x.body,
+            expires_at: x.expires_at,
+        }
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepository for ClickhouseIdempotencyRepository {
+    async fn get(&self, owner: &str, key: &str) -> anyhow::Result<Option<IdempotencyRow>> {
+        let sql = format!(
+            "SELECT
+                 owner,
+                 key,
+                 fingerprint,
+                 status,
+                 content_type,
+                 headers,
+                 body,
+                 expires_at
+               FROM {IDEMPOTENCY_TABLE_NAME}
+              WHERE owner = ? AND key = ? AND expires_at > now64(3)
+              ORDER BY expires_at DESC
+              LIMIT 1",
+        );
+
+        let row = self
+            .client
+            .query(&sql)
+            .bind(owner)
+            .bind(key)
+            .fetch_optional::<ClickhouseIdempotencyRow>()
+            .await?;
+
+        Ok(row.map(IdempotencyRow::from))
+    }
+
+    async fn insert(&self, row: IdempotencyRow) -> anyhow::Result<()> {
+        let mut insert = self
+            .client
+            .insert::<ClickhouseIdempotencyRow>(IDEMPOTENCY_TABLE_NAME)
+            .await?;
+
+        insert.write(&row.into()).await?;
+        insert.end().await?;
+
+        Ok(())
+    }
+}
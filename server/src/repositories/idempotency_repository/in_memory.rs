@@ -0,0 +1,31 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+use crate::repositories::{IdempotencyRepository, IdempotencyRow};
+
+#[derive(Default)]
+pub struct InMemoryIdempotencyRepository {
+    responses: Mutex<HashMap<(String, String), IdempotencyRow>>,
+}
+
+#[async_trait]
+impl IdempotencyRepository for InMemoryIdempotencyRepository {
+    async fn get(&self, owner: &str, key: &str) -> anyhow::Result<Option<IdempotencyRow>> {
+        let responses = self.responses.lock().await;
+        Ok(responses
+            .get(&(owner.to_string(), key.to_string()))
+            .filter(|row| row.expires_at > Utc::now())
+            .cloned())
+    }
+
+    // Expired responses are dropped here, nothing else would.
+    async fn insert(&self, row: IdempotencyRow) -> anyhow::Result<()> {
+        let mut responses = self.responses.lock().await;
+        let now = Utc::now();
+        responses.retain(|_, row| row.expires_at > now);
+        responses.insert((row.owner.clone(), row.key.clone()), row);
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+/// Response a mutating request was answered with, replayed to retries carrying the same
+/// idempotency key. `fingerprint` identifies the request the key was first used for, `headers`
+/// are the response headers replayed along with the body, such as `Location`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdempotencyRow {
+    pub owner: String,
+    pub key: String,
+    pub fingerprint: String,
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait IdempotencyRepository: Send + Sync {
+    /// Response recorded for the key of `owner`, expired ones are never returned.
+    async fn get(&self, owner: &str, key: &str) -> anyhow::Result<Option<IdempotencyRow>>;
+    /// Records the response, replacing the one recorded for the same key if any.
+    async fn insert(&self, row: IdempotencyRow) -> anyhow::Result<()>;
+}
+
+#[cfg(feature = "in-memory")]
+mod in_memory;
+#[cfg(feature = "in-memory")]
+pub use in_memory::InMemoryIdempotencyRepository;
+
+#[cfg(feature = "persistent")]
+mod clickhouse;
+#[cfg(feature = "persistent")]
+pub use clickhouse::ClickhouseIdempotencyRepository;
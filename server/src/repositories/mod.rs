@@ -1,43 +1,148 @@
+mod audit_repository;
+mod blob_repository;
 mod file_repository;
 mod file_storage;
+mod idempotency_repository;
+mod pending_write_repository;
+mod scrub_repository;
+mod transparency_log_repository;
+mod upload_session_repository;
+mod usage_repository;
+mod webhook_repository;
 
 use std::sync::Arc;
 
+#[cfg(feature = "persistent")]
+pub use audit_repository::ClickhouseAuditRepository;
+pub use audit_repository::{AuditEntryRow, AuditRepository};
+#[cfg(feature = "persistent")]
+pub use blob_repository::ClickhouseBlobRepository;
+pub use blob_repository::{BlobReferenceRow, BlobRepository, BlobRow};
 #[cfg(feature = "persistent")]
 pub use file_repository::{ClickhouseConfig, ClickhouseFileRepository};
 pub use file_repository::{FileMerkleTreeRow, FileRepository};
-pub use file_storage::FileStorage;
+pub use file_storage::{
+    CompressedFileStorage, CompressionConfig, ContentEncoding, EncodedContent,
+    EncryptedFileStorage, EncryptionConfig, FileStorage, FileStream, MasterKeys,
+    StoredFileMetadata, StoredObject,
+};
 #[cfg(feature = "persistent")]
 pub use file_storage::{S3Config, S3FileStorage};
+#[cfg(feature = "persistent")]
+pub use idempotency_repository::ClickhouseIdempotencyRepository;
+pub use idempotency_repository::{IdempotencyRepository, IdempotencyRow};
+#[cfg(feature = "persistent")]
+pub use pending_write_repository::ClickhousePendingWriteRepository;
+pub use pending_write_repository::{PendingWriteRepository, PendingWriteRow};
+#[cfg(feature = "persistent")]
+pub use scrub_repository::ClickhouseScrubRepository;
+pub use scrub_repository::{ScrubFindingRow, ScrubRepository};
+#[cfg(feature = "persistent")]
+pub use transparency_log_repository::ClickhouseTransparencyLogRepository;
+pub use transparency_log_repository::{LogEntryRow, TransparencyLogRepository};
+#[cfg(feature = "persistent")]
+pub use upload_session_repository::ClickhouseUploadSessionRepository;
+pub use upload_session_repository::{UploadSessionRepository, UploadSessionRow};
+#[cfg(feature = "persistent")]
+pub use usage_repository::ClickhouseUsageRepository;
+pub use usage_repository::{UsageRepository, UsageRow};
+#[cfg(feature = "persistent")]
+pub use webhook_repository::ClickhouseWebhookRepository;
+pub use webhook_repository::{WebhookDeliveryRow, WebhookRepository};
+
+pub struct Repositories {
+    pub audit_repository: Arc<dyn AuditRepository>,
+    pub blob_repository: Arc<dyn BlobRepository>,
+    pub file_repository: Arc<dyn FileRepository>,
+    pub file_storage: Arc<dyn FileStorage>,
+    pub idempotency_repository: Arc<dyn IdempotencyRepository>,
+    pub pending_write_repository: Arc<dyn PendingWriteRepository>,
+    pub scrub_repository: Arc<dyn ScrubRepository>,
+    pub transparency_log_repository: Arc<dyn TransparencyLogRepository>,
+    pub upload_session_repository: Arc<dyn UploadSessionRepository>,
+    pub usage_repository: Arc<dyn UsageRepository>,
+    pub webhook_repository: Arc<dyn WebhookRepository>,
+}
 
 // Template function to initialize repositories.
-pub async fn init_repositories() -> anyhow::Result<(Arc<dyn FileRepository>, Arc<dyn FileStorage>)>
-{
+pub async fn init_repositories() -> anyhow::Result<Repositories> {
     // This is not part of the template repository, it just shows the advantages of the repository
     // structure and traits implemented in here. Since we can dockerize everything using clickhouse
     // images and loccalstack, it is unlikely that the in-memory version is needed.
     #[cfg(feature = "in-memory")]
     {
         use crate::repositories::{
+            audit_repository::InMemoryAuditRepository, blob_repository::InMemoryBlobRepository,
             file_repository::InMemoryFileRepository, file_storage::InMemoryFileStorage,
+            idempotency_repository::InMemoryIdempotencyRepository,
+            pending_write_repository::InMemoryPendingWriteRepository,
+            scrub_repository::InMemoryScrubRepository,
+            transparency_log_repository::InMemoryTransparencyLogRepository,
+            upload_session_repository::InMemoryUploadSessionRepository,
+            usage_repository::InMemoryUsageRepository,
+            webhook_repository::InMemoryWebhookRepository,
         };
 
-        let file_repository = Arc::new(InMemoryFileRepository::default());
-        let file_storage = Arc::new(InMemoryFileStorage::default());
-
-        Ok((file_repository, file_storage))
+        Ok(Repositories {
+            audit_repository: Arc::new(InMemoryAuditRepository::default()),
+            blob_repository: Arc::new(InMemoryBlobRepository::default()),
+            file_repository: Arc::new(InMemoryFileRepository::default()),
+            // Contents are compressed before being encrypted, ciphertext does not compress.
+            file_storage: CompressedFileStorage::load_from_env(
+                EncryptedFileStorage::load_from_env(Arc::new(InMemoryFileStorage::default()))?,
+            )?,
+            idempotency_repository: Arc::new(InMemoryIdempotencyRepository::default()),
+            pending_write_repository: Arc::new(InMemoryPendingWriteRepository::default()),
+            scrub_repository: Arc::new(InMemoryScrubRepository::default()),
+            transparency_log_repository: Arc::new(InMemoryTransparencyLogRepository::default()),
+            upload_session_repository: Arc::new(InMemoryUploadSessionRepository::default()),
+            usage_repository: Arc::new(InMemoryUsageRepository::default()),
+            webhook_repository: Arc::new(InMemoryWebhookRepository::default()),
+        })
     }
     #[cfg(feature = "persistent")]
     {
         use crate::repositories::{
+            audit_repository::ClickhouseAuditRepository,
+            blob_repository::ClickhouseBlobRepository,
             file_repository::{ClickhouseConfig, ClickhouseFileRepository},
             file_storage::S3FileStorage,
+            idempotency_repository::ClickhouseIdempotencyRepository,
+            pending_write_repository::ClickhousePendingWriteRepository,
+            scrub_repository::ClickhouseScrubRepository,
+            transparency_log_repository::ClickhouseTransparencyLogRepository,
+            upload_session_repository::ClickhouseUploadSessionRepository,
+            usage_repository::ClickhouseUsageRepository,
+            webhook_repository::ClickhouseWebhookRepository,
         };
 
         let clickhouse_config = ClickhouseConfig::load_from_env()?;
-        let file_repository = Arc::new(ClickhouseFileRepository::new(clickhouse_config));
-        let file_storage = Arc::new(S3FileStorage::load_from_env().await?);
 
-        Ok((file_repository, file_storage))
+        Ok(Repositories {
+            audit_repository: Arc::new(ClickhouseAuditRepository::new(clickhouse_config.clone())),
+            blob_repository: Arc::new(ClickhouseBlobRepository::new(clickhouse_config.clone())),
+            file_repository: Arc::new(ClickhouseFileRepository::new(clickhouse_config.clone())),
+            // Contents are compressed before being encrypted, ciphertext does not compress.
+            file_storage: CompressedFileStorage::load_from_env(
+                EncryptedFileStorage::load_from_env(Arc::new(
+                    S3FileStorage::load_from_env().await?,
+                ))?,
+            )?,
+            idempotency_repository: Arc::new(ClickhouseIdempotencyRepository::new(
+                clickhouse_config.clone(),
+            )),
+            pending_write_repository: Arc::new(ClickhousePendingWriteRepository::new(
+                clickhouse_config.clone(),
+            )),
+            scrub_repository: Arc::new(ClickhouseScrubRepository::new(clickhouse_config.clone())),
+            transparency_log_repository: Arc::new(ClickhouseTransparencyLogRepository::new(
+                clickhouse_config.clone(),
+            )),
+            upload_session_repository: Arc::new(ClickhouseUploadSessionRepository::new(
+                clickhouse_config.clone(),
+            )),
+            usage_repository: Arc::new(ClickhouseUsageRepository::new(clickhouse_config.clone())),
+            webhook_repository: Arc::new(ClickhouseWebhookRepository::new(clickhouse_config)),
+        })
     }
 }
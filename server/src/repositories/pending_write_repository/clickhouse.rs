@@ -0,0 +1,171 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use clickhouse::{Client, Row};
+use file_server_library::models::Hash32;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::repositories::{ClickhouseConfig, PendingWriteRepository, PendingWriteRow};
+
+const PENDING_WRITE_TABLE_NAME: &str = "pending_writes";
+
+pub struct ClickhousePendingWriteRepository {
+    client: Client,
+}
+
+impl ClickhousePendingWriteRepository {
+    pub fn new(config: ClickhouseConfig) -> Self {
+        let client = Client::default()
+            .with_url(config.database_url.to_owned())
+            .with_user(config.username.to_owned())
+            .with_password(config.password.to_owned())
+            .with_database(config.database_name.to_owned())
+            .with_option("send_progress_in_http_headers", "1");
+
+        Self::new_from_client(client)
+    }
+
+    pub fn new_from_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[derive(Serialize, Deserialize, Row)]
+struct ClickhousePendingWriteRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    id: Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    upload_id: Uuid,
+    file_index: u64,
+    name: String,
+    content_type: String,
+    staging_name: Option<String>,
+    hash: Option<String>,
+    size: u64,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    created_at: DateTime<Utc>,
+}
+
+impl From<PendingWriteRow> for ClickhousePendingWriteRow {
+    fn from(x: PendingWriteRow) -> Self {
+        Self {
+            id: x.id,
+            upload_id: x.upload_id,
+            file_index: x.index as u64,
+            name: x.name,
+            content_type: x.content_type,
+            staging_name: x.staging_name,
+            hash: x.hash.map(|hash| hash.to_hex()),
+            size: x.size,
+            created_at: x.created_at,
+        }
+    }
+}
+
+impl TryFrom<ClickhousePendingWriteRow> for PendingWriteRow {
+    type Error = anyhow::Error;
+
+    fn try_from(x: ClickhousePendingWriteRow) -> Result<Self, Self::Error> {
+        let hash = x
+            .hash
+            .map(|hash| {
+                Hash32::from_hex(&hash).map_err(|e| anyhow::anyhow!("bad blob hash {hash}: {e}"))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            id: x.id,
+            upload_id: x.upload_id,
+            index: x.file_index as usize,
+            name: x.name,
+            content_type: x.content_type,
+            staging_name: x.staging_name,
+            hash,
+            size: x.size,
+            created_at: x.created_at,
+        })
+    }
+}
+
+#[async_trait]
+impl PendingWriteRepository for ClickhousePendingWriteRepository {
+    async fn insert(&self, row: PendingWriteRow) -> anyhow::Result<()> {
+        let mut insert = self
+            .client
+            .insert::<ClickhousePendingWriteRow>(PENDING_WRITE_TABLE_NAME)
+            .await?;
+
+        insert.write(&row.into()).await?;
+        insert.end().await?;
+
+        Ok(())
+    }
+
+    async fn mark_stored(&self, id: Uuid, hash: Hash32, size: u64) -> anyhow::Result<()> {
+        let sql = format!(
+            "ALTER TABLE {PENDING_WRITE_TABLE_NAME} UPDATE
+                hash = ?,
+                size = ?
+             WHERE id = ?",
+        );
+
+        self.client
+            .clone()
+            .with_option("mutations_sync", "1")
+            .query(&sql)
+            .bind(hash.to_hex())
+            .bind(size)
+            .bind(id)
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: Uuid) -> anyhow::Result<()> {
+        let sql = format!("ALTER TABLE {PENDING_WRITE_TABLE_NAME} DELETE WHERE id = ?");
+
+        self.client
+            .clone()
+            .with_option("mutations_sync", "1")
+            .query(&sql)
+            .bind(id)
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_before(
+        &self,
+        created_before: DateTime<Utc>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<PendingWriteRow>> {
+        let sql = format!(
+            "SELECT
+                 id,
+                 upload_id,
+                 file_index,
+                 name,
+                 content_type,
+                 staging_name,
+                 hash,
+                 size,
+                 created_at
+               FROM {PENDING_WRITE_TABLE_NAME}
+              WHERE created_at < fromUnixTimestamp64Milli(?)
+              ORDER BY created_at
+              LIMIT ?",
+        );
+
+        let rows = self
+            .client
+            .query(&sql)
+            .bind(created_before.timestamp_millis())
+            .bind(limit as u64)
+            .fetch_all::<ClickhousePendingWriteRow>()
+            .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+}
@@ -0,0 +1,54 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use file_server_library::models::Hash32;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::repositories::{PendingWriteRepository, PendingWriteRow};
+
+#[derive(Default)]
+pub struct InMemoryPendingWriteRepository {
+    writes: Mutex<HashMap<Uuid, PendingWriteRow>>,
+}
+
+#[async_trait]
+impl PendingWriteRepository for InMemoryPendingWriteRepository {
+    async fn insert(&self, row: PendingWriteRow) -> anyhow::Result<()> {
+        let mut writes = self.writes.lock().await;
+        writes.insert(row.id, row);
+        Ok(())
+    }
+
+    async fn mark_stored(&self, id: Uuid, hash: Hash32, size: u64) -> anyhow::Result<()> {
+        let mut writes = self.writes.lock().await;
+        if let Some(row) = writes.get_mut(&id) {
+            row.hash = Some(hash);
+            row.size = size;
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: Uuid) -> anyhow::Result<()> {
+        let mut writes = self.writes.lock().await;
+        writes.remove(&id);
+        Ok(())
+    }
+
+    async fn list_before(
+        &self,
+        created_before: DateTime<Utc>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<PendingWriteRow>> {
+        let writes = self.writes.lock().await;
+        let mut rows: Vec<_> = writes
+            .values()
+            .filter(|row| row.created_at < created_before)
+            .cloned()
+            .collect();
+
+        rows.sort_by_key(|row| row.created_at);
+        rows.truncate(limit);
+        Ok(rows)
+    }
+}
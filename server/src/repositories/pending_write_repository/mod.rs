@@ -0,0 +1,45 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use file_server_library::models::Hash32;
+use uuid::Uuid;
+
+/// A file being added to an upload, journaled before anything is written so a write cut halfway
+/// can be finished or rolled back later. `hash` is only set once the contents are in their blob,
+/// until then they may sit in the `staging_name` object of the upload. Files whose contents were
+/// already stored have no staging object.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingWriteRow {
+    pub id: Uuid,
+    pub upload_id: Uuid,
+    pub index: usize,
+    pub name: String,
+    pub content_type: String,
+    pub staging_name: Option<String>,
+    pub hash: Option<Hash32>,
+    pub size: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait PendingWriteRepository: Send + Sync {
+    async fn insert(&self, row: PendingWriteRow) -> anyhow::Result<()>;
+    /// Records that the contents of the write are in the blob of `hash`.
+    async fn mark_stored(&self, id: Uuid, hash: Hash32, size: u64) -> anyhow::Result<()>;
+    async fn delete(&self, id: Uuid) -> anyhow::Result<()>;
+    /// Returns up to `limit` writes started before `created_before`, oldest first.
+    async fn list_before(
+        &self,
+        created_before: DateTime<Utc>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<PendingWriteRow>>;
+}
+
+#[cfg(feature = "in-memory")]
+mod in_memory;
+#[cfg(feature = "in-memory")]
+pub use in_memory::InMemoryPendingWriteRepository;
+
+#[cfg(feature = "persistent")]
+mod clickhouse;
+#[cfg(feature = "persistent")]
+pub use clickhouse::ClickhousePendingWriteRepository;
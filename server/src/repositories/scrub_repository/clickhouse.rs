@@ -0,0 +1,182 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use clickhouse::{Client, Row};
+use file_server_library::models::Hash32;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    models::UploadCursor,
+    repositories::{ClickhouseConfig, ScrubFindingRow, ScrubRepository},
+};
+
+const SCRUB_FINDINGS_TABLE_NAME: &str = "scrub_findings";
+const SCRUB_STATE_TABLE_NAME: &str = "scrub_state";
+// There is a single scrubber, its state is kept in a single row.
+const SCRUBBER_NAME: &str = "scrubber";
+
+// Findings are only ever appended. Moving the cursor inserts a new state row, the table keeps
+// the latest one.
+pub struct ClickhouseScrubRepository {
+    client: Client,
+}
+
+impl ClickhouseScrubRepository {
+    pub fn new(config: ClickhouseConfig) -> Self {
+        let client = Client::default()
+            .with_url(config.database_url.to_owned())
+            .with_user(config.username.to_owned())
+            .with_password(config.password.to_owned())
+            .with_database(config.database_name.to_owned())
+            .with_option("send_progress_in_http_headers", "1");
+
+        Self::new_from_client(client)
+    }
+
+    pub fn new_from_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[derive(Serialize, Deserialize, Row)]
+struct ClickhouseScrubFindingRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    upload_id: Uuid,
+    file_index: u64,
+    name: String,
+    kind: String,
+    expected_hash: String,
+    actual_hash: Option<String>,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    detected_at: DateTime<Utc>,
+}
+
+impl From<ScrubFindingRow> for ClickhouseScrubFindingRow {
+    fn from(x: ScrubFindingRow) -> Self {
+        Self {
+            upload_id: x.upload_id,
+            file_index: x.index as u64,
+            name: x.name,
+            kind: x.kind.to_string(),
+            expected_hash: x.expected_hash.to_hex(),
+            actual_hash: x.actual_hash.map(Hash32::to_hex),
+            detected_at: x.detected_at,
+        }
+    }
+}
+
+impl TryFrom<ClickhouseScrubFindingRow> for ScrubFindingRow {
+    type Error = anyhow::Error;
+
+    fn try_from(x: ClickhouseScrubFindingRow) -> Result<Self, Self::Error> {
+        let parse_hash = |hash: &str| {
+            Hash32::from_hex(hash).map_err(|e| anyhow::anyhow!("bad scrubbed hash {hash}: {e}"))
+        };
+
+        Ok(Self {
+            upload_id: x.upload_id,
+            index: x.file_index as usize,
+            name: x.name,
+            kind: x.kind.parse()?,
+            expected_hash: parse_hash(&x.expected_hash)?,
+            actual_hash: x.actual_hash.as_deref().map(parse_hash).transpose()?,
+            detected_at: x.detected_at,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Row)]
+struct ClickhouseScrubStateRow {
+    name: String,
+    cursor: Option<String>,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    updated_at: DateTime<Utc>,
+}
+
+#[async_trait]
+impl ScrubRepository for ClickhouseScrubRepository {
+    async fn add_finding(&self, finding: ScrubFindingRow) -> anyhow::Result<()> {
+        let mut insert = self
+            .client
+            .insert::<ClickhouseScrubFindingRow>(SCRUB_FINDINGS_TABLE_NAME)
+            .await?;
+
+        insert.write(&finding.into()).await?;
+        insert.end().await?;
+
+        Ok(())
+    }
+
+    async fn list_findings(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> anyhow::Result<Vec<ScrubFindingRow>> {
+        let sql = format!(
+            "SELECT
+                 upload_id,
+                 file_index,
+                 name,
+                 kind,
+                 expected_hash,
+                 actual_hash,
+                 detected_at
+               FROM {SCRUB_FINDINGS_TABLE_NAME}
+              ORDER BY detected_at DESC, upload_id, file_index
+              LIMIT ?
+             OFFSET ?",
+        );
+
+        let rows = self
+            .client
+            .query(&sql)
+            .bind(limit as u64)
+            .bind(offset as u64)
+            .fetch_all::<ClickhouseScrubFindingRow>()
+            .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    async fn get_cursor(&self) -> anyhow::Result<Option<UploadCursor>> {
+        let sql = format!(
+            "SELECT
+                 name,
+                 cursor,
+                 updated_at
+               FROM {SCRUB_STATE_TABLE_NAME}
+              WHERE name = ?
+              ORDER BY updated_at DESC
+              LIMIT 1",
+        );
+
+        let row = self
+            .client
+            .query(&sql)
+            .bind(SCRUBBER_NAME)
+            .fetch_optional::<ClickhouseScrubStateRow>()
+            .await?;
+
+        row.and_then(|row| row.cursor)
+            .map(|cursor| cursor.parse())
+            .transpose()
+    }
+
+    async fn set_cursor(&self, cursor: Option<UploadCursor>) -> anyhow::Result<()> {
+        let mut insert = self
+            .client
+            .insert::<ClickhouseScrubStateRow>(SCRUB_STATE_TABLE_NAME)
+            .await?;
+
+        insert
+            .write(&ClickhouseScrubStateRow {
+                name: SCRUBBER_NAME.to_string(),
+                cursor: cursor.map(|cursor| cursor.to_string()),
+                updated_at: Utc::now(),
+            })
+            .await?;
+        insert.end().await?;
+
+        Ok(())
+    }
+}
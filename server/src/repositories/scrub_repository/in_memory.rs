@@ -0,0 +1,42 @@
+use async_trait::async_trait;
+use std::cmp::Reverse;
+use tokio::sync::Mutex;
+
+use crate::{
+    models::UploadCursor,
+    repositories::{ScrubFindingRow, ScrubRepository},
+};
+
+#[derive(Default)]
+pub struct InMemoryScrubRepository {
+    findings: Mutex<Vec<ScrubFindingRow>>,
+    cursor: Mutex<Option<UploadCursor>>,
+}
+
+#[async_trait]
+impl ScrubRepository for InMemoryScrubRepository {
+    async fn add_finding(&self, finding: ScrubFindingRow) -> anyhow::Result<()> {
+        self.findings.lock().await.push(finding);
+        Ok(())
+    }
+
+    async fn list_findings(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> anyhow::Result<Vec<ScrubFindingRow>> {
+        let mut findings = self.findings.lock().await.clone();
+        findings.sort_by_key(|finding| Reverse(finding.detected_at));
+
+        Ok(findings.into_iter().skip(offset).take(limit).collect())
+    }
+
+    async fn get_cursor(&self) -> anyhow::Result<Option<UploadCursor>> {
+        Ok(*self.cursor.lock().await)
+    }
+
+    async fn set_cursor(&self, cursor: Option<UploadCursor>) -> anyhow::Result<()> {
+        *self.cursor.lock().await = cursor;
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use file_server_library::models::Hash32;
+use uuid::Uuid;
+
+use crate::models::{ScrubFindingKind, UploadCursor};
+
+/// A file whose stored contents failed verification. `actual_hash` is only known for
+/// corrupted contents.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScrubFindingRow {
+    pub upload_id: Uuid,
+    pub index: usize,
+    pub name: String,
+    pub kind: ScrubFindingKind,
+    pub expected_hash: Hash32,
+    pub actual_hash: Option<Hash32>,
+    pub detected_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait ScrubRepository: Send + Sync {
+    async fn add_finding(&self, finding: ScrubFindingRow) -> anyhow::Result<()>;
+    /// Returns up to `limit` findings, newest first, skipping the first `offset`.
+    async fn list_findings(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> anyhow::Result<Vec<ScrubFindingRow>>;
+    /// Last upload checked by the current pass, `None` when the next pass starts over.
+    async fn get_cursor(&self) -> anyhow::Result<Option<UploadCursor>>;
+    async fn set_cursor(&self, cursor: Option<UploadCursor>) -> anyhow::Result<()>;
+}
+
+#[cfg(feature = "in-memory")]
+mod in_memory;
+#[cfg(feature = "in-memory")]
+pub use in_memory::InMemoryScrubRepository;
+
+#[cfg(feature = "persistent")]
+mod clickhouse;
+#[cfg(feature = "persistent")]
+pub use clickhouse::ClickhouseScrubRepository;
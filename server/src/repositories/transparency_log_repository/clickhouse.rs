@@ -0,0 +1,163 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use clickhouse::{Client, Row};
+use file_server_library::models::Hash32;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::repositories::{ClickhouseConfig, LogEntryRow, TransparencyLogRepository};
+
+const TRANSPARENCY_LOG_TABLE_NAME: &str = "transparency_log";
+
+// Entries are only ever inserted, the index is their position in the log. Every insert carries
+// its index as deduplication token, so of several instances appending at the same index only the
+// first one is stored, the table drops the others (see `non_replicated_deduplication_window` in
+// the schema). Reading the index back tells which one it was.
+pub struct ClickhouseTransparencyLogRepository {
+    client: Client,
+}
+
+impl ClickhouseTransparencyLogRepository {
+    pub fn new(config: ClickhouseConfig) -> Self {
+        let client = Client::default()
+            .with_url(config.database_url.to_owned())
+            .with_user(config.username.to_owned())
+            .with_password(config.password.to_owned())
+            .with_database(config.database_name.to_owned())
+            .with_option("send_progress_in_http_headers", "1");
+
+        Self::new_from_client(client)
+    }
+
+    pub fn new_from_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[derive(Serialize, Deserialize, Row)]
+struct ClickhouseLogEntryRow {
+    log_index: u64,
+    #[serde(with = "clickhouse::serde::uuid")]
+    upload_id: Uuid,
+    owner: String,
+    root: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    appended_at: DateTime<Utc>,
+    leaf_hash: String,
+}
+
+impl From<LogEntryRow> for ClickhouseLogEntryRow {
+    fn from(x: LogEntryRow) -> Self {
+        Self {
+            log_index: x.index,
+            upload_id: x.upload_id,
+            owner: x.owner,
+            root: x.root.to_hex(),
+            appended_at: x.appended_at,
+            leaf_hash: x.leaf_hash.to_hex(),
+        }
+    }
+}
+
+impl TryFrom<ClickhouseLogEntryRow> for LogEntryRow {
+    type Error = anyhow::Error;
+
+    fn try_from(x: ClickhouseLogEntryRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            index: x.log_index,
+            upload_id: x.upload_id,
+            owner: x.owner,
+            root: parse_hash(&x.root)?,
+            appended_at: x.appended_at,
+            leaf_hash: parse_hash(&x.leaf_hash)?,
+        })
+    }
+}
+
+fn parse_hash(hash: &str) -> anyhow::Result<Hash32> {
+    Hash32::from_hex(hash).map_err(|e| anyhow::anyhow!("bad log hash {hash}: {e}"))
+}
+
+#[async_trait]
+impl TransparencyLogRepository for ClickhouseTransparencyLogRepository {
+    async fn append(&self, entry: LogEntryRow) -> anyhow::Result<bool> {
+        let index = entry.index;
+        let leaf_hash = entry.leaf_hash.to_hex();
+
+        let mut insert = self
+            .client
+            .clone()
+            .with_option(
+                "insert_deduplication_token",
+                format!("{TRANSPARENCY_LOG_TABLE_NAME}-{index}"),
+            )
+            .insert::<ClickhouseLogEntryRow>(TRANSPARENCY_LOG_TABLE_NAME)
+            .await?;
+
+        insert.write(&entry.into()).await?;
+        insert.end().await?;
+
+        let sql = format!(
+            "SELECT leaf_hash
+               FROM {TRANSPARENCY_LOG_TABLE_NAME}
+              WHERE log_index = ?",
+        );
+        let stored = self
+            .client
+            .query(&sql)
+            .bind(index)
+            .fetch_all::<String>()
+            .await?;
+
+        Ok(stored == [leaf_hash])
+    }
+
+    async fn size(&self) -> anyhow::Result<u64> {
+        let sql = format!("SELECT count() FROM {TRANSPARENCY_LOG_TABLE_NAME}");
+
+        Ok(self.client.query(&sql).fetch_one::<u64>().await?)
+    }
+
+    async fn latest_for_upload(&self, upload_id: Uuid) -> anyhow::Result<Option<LogEntryRow>> {
+        let sql = format!(
+            "SELECT
+                 log_index,
+                 upload_id,
+                 owner,
+                 root,
+                 appended_at,
+                 leaf_hash
+               FROM {TRANSPARENCY_LOG_TABLE_NAME}
+              WHERE upload_id = ?
+              ORDER BY log_index DESC
+              LIMIT 1",
+        );
+
+        let row = self
+            .client
+            .query(&sql)
+            .bind(upload_id)
+            .fetch_optional::<ClickhouseLogEntryRow>()
+            .await?;
+
+        row.map(TryInto::try_into).transpose()
+    }
+
+    async fn leaf_hashes(&self, size: u64) -> anyhow::Result<Vec<Hash32>> {
+        let sql = format!(
+            "SELECT leaf_hash
+               FROM {TRANSPARENCY_LOG_TABLE_NAME}
+              WHERE log_index < ?
+              ORDER BY log_index",
+        );
+
+        let hashes = self
+            .client
+            .query(&sql)
+            .bind(size)
+            .fetch_all::<String>()
+            .await?;
+
+        hashes.iter().map(|hash| parse_hash(hash)).collect()
+    }
+}
@@ -0,0 +1,57 @@
+use async_trait::async_trait;
+use file_server_library::models::Hash32;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::repositories::{LogEntryRow, TransparencyLogRepository};
+
+#[derive(Default)]
+pub struct InMemoryTransparencyLogRepository {
+    entries: Mutex<Vec<LogEntryRow>>,
+}
+
+#[async_trait]
+impl TransparencyLogRepository for InMemoryTransparencyLogRepository {
+    async fn append(&self, entry: LogEntryRow) -> anyhow::Result<bool> {
+        let mut entries = self.entries.lock().await;
+        if entry.index < entries.len() as u64 {
+            return Ok(false);
+        }
+        if entry.index > entries.len() as u64 {
+            anyhow::bail!(
+                "log entry {} appended to a log of {} entries",
+                entry.index,
+                entries.len()
+            );
+        }
+
+        entries.push(entry);
+        Ok(true)
+    }
+
+    async fn size(&self) -> anyhow::Result<u64> {
+        Ok(self.entries.lock().await.len() as u64)
+    }
+
+    async fn latest_for_upload(&self, upload_id: Uuid) -> anyhow::Result<Option<LogEntryRow>> {
+        Ok(self
+            .entries
+            .lock()
+            .await
+            .iter()
+            .rev()
+            .find(|entry| entry.upload_id == upload_id)
+            .cloned())
+    }
+
+    async fn leaf_hashes(&self, size: u64) -> anyhow::Result<Vec<Hash32>> {
+        Ok(self
+            .entries
+            .lock()
+            .await
+            .iter()
+            .take(size as usize)
+            .map(|entry| entry.leaf_hash)
+            .collect())
+    }
+}
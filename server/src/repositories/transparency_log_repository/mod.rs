@@ -0,0 +1,39 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use file_server_library::models::Hash32;
+use uuid::Uuid;
+
+/// An entry of the transparency log. `leaf_hash` is derived from the other fields and kept
+/// alongside them so the tree can be rebuilt without rehashing every entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogEntryRow {
+    pub index: u64,
+    pub upload_id: Uuid,
+    pub owner: String,
+    pub root: Hash32,
+    pub appended_at: DateTime<Utc>,
+    pub leaf_hash: Hash32,
+}
+
+/// Append-only storage of the transparency log. Entries are never updated nor deleted.
+#[async_trait]
+pub trait TransparencyLogRepository: Send + Sync {
+    /// Stores `entry` at its index, which must be at most the current size of the log, and
+    /// returns whether it was stored. It is not when another entry took the index first.
+    async fn append(&self, entry: LogEntryRow) -> anyhow::Result<bool>;
+    async fn size(&self) -> anyhow::Result<u64>;
+    /// Returns the entry with the highest index for the upload, if any.
+    async fn latest_for_upload(&self, upload_id: Uuid) -> anyhow::Result<Option<LogEntryRow>>;
+    /// Returns the leaf hashes of the first `size` entries, in index order.
+    async fn leaf_hashes(&self, size: u64) -> anyhow::Result<Vec<Hash32>>;
+}
+
+#[cfg(feature = "in-memory")]
+mod in_memory;
+#[cfg(feature = "in-memory")]
+pub use in_memory::InMemoryTransparencyLogRepository;
+
+#[cfg(feature = "persistent")]
+mod clickhouse;
+#[cfg(feature = "persistent")]
+pub use clickhouse::ClickhouseTransparencyLogRepository;
@@ -0,0 +1,210 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use clickhouse::{Client, Row};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::repositories::{ClickhouseConfig, UploadSessionRepository, UploadSessionRow};
+
+const UPLOAD_SESSION_TABLE_NAME: &str = "upload_sessions";
+
+// Appends insert the session again with its new offset, the latest row is the one with the
+// highest offset. Every such insert carries the session and the offset it started from as
+// deduplication token, so of several appends racing at the same offset only the first one is
+// stored, the table drops the others (see `non_replicated_deduplication_window` in the schema).
+// Reading the chunks back tells which one it was.
+pub struct ClickhouseUploadSessionRepository {
+    client: Client,
+}
+
+impl ClickhouseUploadSessionRepository {
+    pub fn new(config: ClickhouseConfig) -> Self {
+        let client = Client::default()
+            .with_url(config.database_url.to_owned())
+            .with_user(config.username.to_owned())
+            .with_password(config.password.to_owned())
+            .with_database(config.database_name.to_owned())
+            .with_option("send_progress_in_http_headers", "1");
+
+        Self::new_from_client(client)
+    }
+
+    pub fn new_from_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[derive(Serialize, Deserialize, Row)]
+struct ClickhouseUploadSessionRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    id: Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    upload_id: Uuid,
+    name: String,
+    file_index: u64,
+    content_type: Option<String>,
+    length: Option<u64>,
+    offset: u64,
+    chunk_offsets: Vec<u64>,
+    chunk_ids: Vec<String>,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    created_at: DateTime<Utc>,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    updated_at: DateTime<Utc>,
+}
+
+impl From<UploadSessionRow> for ClickhouseUploadSessionRow {
+    fn from(x: UploadSessionRow) -> Self {
+        Self {
+            id: x.id,
+            upload_id: x.upload_id,
+            name: x.name,
+            file_index: x.index as u64,
+            content_type: x.content_type,
+            length: x.length,
+            offset: x.offset,
+            chunk_offsets: x.chunk_offsets,
+            chunk_ids: x.chunk_ids.iter().map(Uuid::to_string).collect(),
+            created_at: x.created_at,
+            updated_at: x.updated_at,
+        }
+    }
+}
+
+impl TryFrom<ClickhouseUploadSessionRow> for UploadSessionRow {
+    type Error = anyhow::Error;
+
+    fn try_from(x: ClickhouseUploadSessionRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: x.id,
+            upload_id: x.upload_id,
+            name: x.name,
+            index: x.file_index as usize,
+            content_type: x.content_type,
+            length: x.length,
+            offset: x.offset,
+            chunk_offsets: x.chunk_offsets,
+            chunk_ids: x
+                .chunk_ids
+                .iter()
+                .map(|chunk_id| Uuid::parse_str(chunk_id))
+                .collect::<Result<_, _>>()?,
+            created_at: x.created_at,
+            updated_at: x.updated_at,
+        })
+    }
+}
+
+#[async_trait]
+impl UploadSessionRepository for ClickhouseUploadSessionRepository {
+    async fn get(&self, id: Uuid) -> anyhow::Result<Option<UploadSessionRow>> {
+        let sql = format!(
+            "SELECT
+                 id,
+                 upload_id,
+                 name,
+                 file_index,
+                 content_type,
+                 length,
+                 offset,
+                 chunk_offsets,
+                 chunk_ids,
+                 created_at,
+                 updated_at
+               FROM {UPLOAD_SESSION_TABLE_NAME}
+              WHERE id = ?
+              ORDER BY offset DESC
+              LIMIT 1",
+        );
+
+        let row = self
+            .client
+            .query(&sql)
+            .bind(id)
+            .fetch_optional::<ClickhouseUploadSessionRow>()
+            .await?;
+
+        row.map(TryInto::try_into).transpose()
+    }
+
+    async fn insert(&self, session: UploadSessionRow) -> anyhow::Result<()> {
+        let mut insert = self
+            .client
+            .clone()
+            .with_option("mutations_sync", "1")
+            .insert::<ClickhouseUploadSessionRow>(UPLOAD_SESSION_TABLE_NAME)
+            .await?;
+
+        insert.write(&session.into()).await?;
+        insert.end().await?;
+
+        Ok(())
+    }
+
+    async fn update_if_offset(
+        &self,
+        session: UploadSessionRow,
+        offset: u64,
+    ) -> anyhow::Result<bool> {
+        let id = session.id;
+        let item: ClickhouseUploadSessionRow = session.into();
+        let chunk_ids = item.chunk_ids.clone();
+
+        let mut insert = self
+            .client
+            .clone()
+            .with_option(
+                "insert_deduplication_token",
+                format!("{UPLOAD_SESSION_TABLE_NAME}-{id}-{offset}"),
+            )
+            .insert::<ClickhouseUploadSessionRow>(UPLOAD_SESSION_TABLE_NAME)
+            .await?;
+
+        insert.write(&item).await?;
+        insert.end().await?;
+
+        // Later appends keep the chunks of this one, so it is found even once they replaced it.
+        let sql = format!(
+            "SELECT count()
+               FROM {UPLOAD_SESSION_TABLE_NAME}
+              WHERE id = ? AND hasSubstr(chunk_ids, ?)",
+        );
+        let stored = self
+            .client
+            .query(&sql)
+            .bind(id)
+            .bind(chunk_ids)
+            .fetch_one::<u64>()
+            .await?;
+
+        Ok(stored > 0)
+    }
+
+    async fn delete(&self, id: Uuid) -> anyhow::Result<()> {
+        let sql = format!("ALTER TABLE {UPLOAD_SESSION_TABLE_NAME} DELETE WHERE id = ?");
+
+        self.client
+            .clone()
+            .with_option("mutations_sync", "1")
+            .query(&sql)
+            .bind(id)
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_by_upload(&self, upload_id: Uuid) -> anyhow::Result<()> {
+        let sql = format!("ALTER TABLE {UPLOAD_SESSION_TABLE_NAME} DELETE WHERE upload_id = ?");
+
+        self.client
+            .clone()
+            .with_option("mutations_sync", "1")
+            .query(&sql)
+            .bind(upload_id)
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+}
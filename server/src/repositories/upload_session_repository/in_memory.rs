@@ -0,0 +1,52 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::repositories::{UploadSessionRepository, UploadSessionRow};
+
+#[derive(Default)]
+pub struct InMemoryUploadSessionRepository {
+    sessions: Mutex<HashMap<Uuid, UploadSessionRow>>,
+}
+
+#[async_trait]
+impl UploadSessionRepository for InMemoryUploadSessionRepository {
+    async fn get(&self, id: Uuid) -> anyhow::Result<Option<UploadSessionRow>> {
+        let sessions = self.sessions.lock().await;
+        Ok(sessions.get(&id).cloned())
+    }
+
+    async fn insert(&self, session: UploadSessionRow) -> anyhow::Result<()> {
+        let mut sessions = self.sessions.lock().await;
+        sessions.insert(session.id, session);
+        Ok(())
+    }
+
+    async fn update_if_offset(
+        &self,
+        session: UploadSessionRow,
+        offset: u64,
+    ) -> anyhow::Result<bool> {
+        let mut sessions = self.sessions.lock().await;
+        match sessions.get_mut(&session.id) {
+            Some(stored) if stored.offset == offset => {
+                *stored = session;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete(&self, id: Uuid) -> anyhow::Result<()> {
+        let mut sessions = self.sessions.lock().await;
+        sessions.remove(&id);
+        Ok(())
+    }
+
+    async fn delete_by_upload(&self, upload_id: Uuid) -> anyhow::Result<()> {
+        let mut sessions = self.sessions.lock().await;
+        sessions.retain(|_, session| session.upload_id != upload_id);
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UploadSessionRow {
+    pub id: Uuid,
+    pub upload_id: Uuid,
+    pub name: String,
+    pub index: usize,
+    pub content_type: Option<String>,
+    pub length: Option<u64>,
+    pub offset: u64,
+    pub chunk_offsets: Vec<u64>,
+    /// Parallel to `chunk_offsets`, shorter for sessions whose first chunks had no ids.
+    pub chunk_ids: Vec<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait UploadSessionRepository: Send + Sync {
+    async fn get(&self, id: Uuid) -> anyhow::Result<Option<UploadSessionRow>>;
+    async fn insert(&self, session: UploadSessionRow) -> anyhow::Result<()>;
+    /// Stores the session only when its committed offset is still `offset`, so of several
+    /// appends racing at the same offset only one is committed. Returns whether it was stored.
+    async fn update_if_offset(
+        &self,
+        session: UploadSessionRow,
+        offset: u64,
+    ) -> anyhow::Result<bool>;
+    async fn delete(&self, id: Uuid) -> anyhow::Result<()>;
+    /// Deletes every session opened for the given upload.
+    async fn delete_by_upload(&self, upload_id: Uuid) -> anyhow::Result<()>;
+}
+
+#[cfg(feature = "in-memory")]
+mod in_memory;
+#[cfg(feature = "in-memory")]
+pub use in_memory::InMemoryUploadSessionRepository;
+
+#[cfg(feature = "persistent")]
+mod clickhouse;
+#[cfg(feature = "persistent")]
+pub use clickhouse::ClickhouseUploadSessionRepository;
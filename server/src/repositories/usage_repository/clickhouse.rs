@@ -0,0 +1,96 @@
+use async_trait::async_trait;
+use clickhouse::{Client, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::repositories::{ClickhouseConfig, UsageRepository, UsageRow};
+
+const USAGE_TABLE_NAME: &str = "usage";
+
+// Every change is inserted as it is, the table sums the rows of each owner in the background and
+// reads add up whatever was not merged yet.
+pub struct ClickhouseUsageRepository {
+    client: Client,
+}
+
+impl ClickhouseUsageRepository {
+    pub fn new(config: ClickhouseConfig) -> Self {
+        let client = Client::default()
+            .with_url(config.database_url.to_owned())
+            .with_user(config.username.to_owned())
+            .with_password(config.password.to_owned())
+            .with_database(config.database_name.to_owned())
+            .with_option("send_progress_in_http_headers", "1");
+
+        Self::new_from_client(client)
+    }
+
+    pub fn new_from_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[derive(Serialize, Deserialize, Row)]
+struct ClickhouseUsageRow {
+    owner: String,
+    stored_bytes: i64,
+    open_uploads: i64,
+}
+
+impl From<UsageRow> for ClickhouseUsageRow {
+    fn from(x: UsageRow) -> Self {
+        Self {
+            owner: x.owner,
+            stored_bytes: x.stored_bytes,
+            open_uploads: x.open_uploads,
+        }
+    }
+}
+
+impl From<ClickhouseUsageRow> for UsageRow {
+    fn from(x: ClickhouseUsageRow) -> Self {
+        Self {
+            owner: x.owner,
+            stored_bytes: x.stored_bytes,
+            open_uploads: x.open_uploads,
+        }
+    }
+}
+
+#[async_trait]
+impl UsageRepository for ClickhouseUsageRepository {
+    async fn add(&self, change: UsageRow) -> anyhow::Result<()> {
+        let mut insert = self
+            .client
+            .insert::<ClickhouseUsageRow>(USAGE_TABLE_NAME)
+            .await?;
+
+        insert.write(&change.into()).await?;
+        insert.end().await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, owner: &str) -> anyhow::Result<UsageRow> {
+        let sql = format!(
+            "SELECT
+                 owner,
+                 sum(stored_bytes) AS stored_bytes,
+                 sum(open_uploads) AS open_uploads
+               FROM {USAGE_TABLE_NAME}
+              WHERE owner = ?
+              GROUP BY owner",
+        );
+
+        let row = self
+            .client
+            .query(&sql)
+            .bind(owner)
+            .fetch_optional::<ClickhouseUsageRow>()
+            .await?;
+
+        Ok(row.map(UsageRow::from).unwrap_or_else(|| UsageRow {
+            owner: owner.to_string(),
+            ..Default::default()
+        }))
+    }
+}
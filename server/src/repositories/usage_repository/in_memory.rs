@@ -0,0 +1,35 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+use crate::repositories::{UsageRepository, UsageRow};
+
+#[derive(Default)]
+pub struct InMemoryUsageRepository {
+    usage: Mutex<HashMap<String, UsageRow>>,
+}
+
+#[async_trait]
+impl UsageRepository for InMemoryUsageRepository {
+    async fn add(&self, change: UsageRow) -> anyhow::Result<()> {
+        let mut usage = self.usage.lock().await;
+        let row = usage
+            .entry(change.owner.clone())
+            .or_insert_with(|| UsageRow {
+                owner: change.owner,
+                ..Default::default()
+            });
+
+        row.stored_bytes += change.stored_bytes;
+        row.open_uploads += change.open_uploads;
+        Ok(())
+    }
+
+    async fn get(&self, owner: &str) -> anyhow::Result<UsageRow> {
+        let usage = self.usage.lock().await;
+        Ok(usage.get(owner).cloned().unwrap_or_else(|| UsageRow {
+            owner: owner.to_string(),
+            ..Default::default()
+        }))
+    }
+}
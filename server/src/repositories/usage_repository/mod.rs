@@ -0,0 +1,28 @@
+use async_trait::async_trait;
+
+/// Usage of a client. Changes are recorded as rows of their own, the usage of a client is what
+/// its rows add up to, so concurrent changes never overwrite each other.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UsageRow {
+    pub owner: String,
+    pub stored_bytes: i64,
+    pub open_uploads: i64,
+}
+
+#[async_trait]
+pub trait UsageRepository: Send + Sync {
+    /// Adds the change to the usage of its owner.
+    async fn add(&self, change: UsageRow) -> anyhow::Result<()>;
+    /// Usage of `owner`, zero when nothing was recorded for it.
+    async fn get(&self, owner: &str) -> anyhow::Result<UsageRow>;
+}
+
+#[cfg(feature = "in-memory")]
+mod in_memory;
+#[cfg(feature = "in-memory")]
+pub use in_memory::InMemoryUsageRepository;
+
+#[cfg(feature = "persistent")]
+mod clickhouse;
+#[cfg(feature = "persistent")]
+pub use clickhouse::ClickhouseUsageRepository;
@@ -0,0 +1,222 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use clickhouse::{Client, Row};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    models::DeliveryState,
+    repositories::{ClickhouseConfig, WebhookDeliveryRow, WebhookRepository},
+};
+
+const WEBHOOK_DELIVERY_TABLE_NAME: &str = "webhook_deliveries";
+
+pub struct ClickhouseWebhookRepository {
+    client: Client,
+}
+
+impl ClickhouseWebhookRepository {
+    pub fn new(config: ClickhouseConfig) -> Self {
+        let client = Client::default()
+            .with_url(config.database_url.to_owned())
+            .with_user(config.username.to_owned())
+            .with_password(config.password.to_owned())
+            .with_database(config.database_name.to_owned())
+            .with_option("send_progress_in_http_headers", "1");
+
+        Self::new_from_client(client)
+    }
+
+    pub fn new_from_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[derive(Serialize, Deserialize, Row)]
+struct ClickhouseWebhookDeliveryRow {
+    #[serde(with = "clickhouse::serde::uuid")]
+    id: Uuid,
+    owner: String,
+    event: String,
+    #[serde(with = "clickhouse::serde::uuid")]
+    upload_id: Uuid,
+    url: String,
+    payload: String,
+    state: String,
+    attempts: u32,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    next_attempt_at: DateTime<Utc>,
+    last_status: Option<u16>,
+    last_error: Option<String>,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    created_at: DateTime<Utc>,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    updated_at: DateTime<Utc>,
+}
+
+impl From<WebhookDeliveryRow> for ClickhouseWebhookDeliveryRow {
+    fn from(x: WebhookDeliveryRow) -> Self {
+        Self {
+            id: x.id,
+            owner: x.owner,
+            event: x.event.to_string(),
+            upload_id: x.upload_id,
+            url: x.url,
+            payload: x.payload,
+            state: x.state.to_string(),
+            attempts: x.attempts,
+            next_attempt_at: x.next_attempt_at,
+            last_status: x.last_status,
+            last_error: x.last_error,
+            created_at: x.created_at,
+            updated_at: x.updated_at,
+        }
+    }
+}
+
+impl TryFrom<ClickhouseWebhookDeliveryRow> for WebhookDeliveryRow {
+    type Error = anyhow::Error;
+
+    fn try_from(x: ClickhouseWebhookDeliveryRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: x.id,
+            owner: x.owner,
+            event: x.event.parse()?,
+            upload_id: x.upload_id,
+            url: x.url,
+            payload: x.payload,
+            state: x.state.parse()?,
+            attempts: x.attempts,
+            next_attempt_at: x.next_attempt_at,
+            last_status: x.last_status,
+            last_error: x.last_error,
+            created_at: x.created_at,
+            updated_at: x.updated_at,
+        })
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for ClickhouseWebhookRepository {
+    async fn insert(&self, delivery: WebhookDeliveryRow) -> anyhow::Result<()> {
+        let mut insert = self
+            .client
+            .insert::<ClickhouseWebhookDeliveryRow>(WEBHOOK_DELIVERY_TABLE_NAME)
+            .await?;
+
+        insert.write(&delivery.into()).await?;
+        insert.end().await?;
+
+        Ok(())
+    }
+
+    async fn update(&self, delivery: WebhookDeliveryRow) -> anyhow::Result<()> {
+        let item: ClickhouseWebhookDeliveryRow = delivery.into();
+
+        let sql = format!(
+            "ALTER TABLE {WEBHOOK_DELIVERY_TABLE_NAME} UPDATE
+                state = ?,
+                attempts = ?,
+                next_attempt_at = fromUnixTimestamp64Milli(?),
+                last_status = ?,
+                last_error = ?,
+                updated_at = fromUnixTimestamp64Milli(?)
+             WHERE id = ?",
+        );
+
+        self.client
+            .clone()
+            .with_option("mutations_sync", "1")
+            .query(&sql)
+            .bind(item.state)
+            .bind(item.attempts)
+            .bind(item.next_attempt_at.timestamp_millis())
+            .bind(item.last_status)
+            .bind(item.last_error)
+            .bind(item.updated_at.timestamp_millis())
+            .bind(item.id)
+            .execute()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_due(
+        &self,
+        now: DateTime<Utc>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<WebhookDeliveryRow>> {
+        let sql = format!(
+            "SELECT
+                 id,
+                 owner,
+                 event,
+                 upload_id,
+                 url,
+                 payload,
+                 state,
+                 attempts,
+                 next_attempt_at,
+                 last_status,
+                 last_error,
+                 created_at,
+                 updated_at
+               FROM {WEBHOOK_DELIVERY_TABLE_NAME}
+              WHERE state = ?
+                AND next_attempt_at <= fromUnixTimestamp64Milli(?)
+              ORDER BY next_attempt_at
+              LIMIT ?",
+        );
+
+        let rows = self
+            .client
+            .query(&sql)
+            .bind(DeliveryState::Pending.to_string())
+            .bind(now.timestamp_millis())
+            .bind(limit as u64)
+            .fetch_all::<ClickhouseWebhookDeliveryRow>()
+            .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    async fn list_by_owner(
+        &self,
+        owner: &str,
+        offset: usize,
+        limit: usize,
+    ) -> anyhow::Result<Vec<WebhookDeliveryRow>> {
+        let sql = format!(
+            "SELECT
+                 id,
+                 owner,
+                 event,
+                 upload_id,
+                 url,
+                 payload,
+                 state,
+                 attempts,
+                 next_attempt_at,
+                 last_status,
+                 last_error,
+                 created_at,
+                 updated_at
+               FROM {WEBHOOK_DELIVERY_TABLE_NAME}
+              WHERE owner = ?
+              ORDER BY created_at DESC, id
+              LIMIT ?
+             OFFSET ?",
+        );
+
+        let rows = self
+            .client
+            .query(&sql)
+            .bind(owner)
+            .bind(limit as u64)
+            .bind(offset as u64)
+            .fetch_all::<ClickhouseWebhookDeliveryRow>()
+            .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+}
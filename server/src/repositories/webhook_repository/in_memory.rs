@@ -0,0 +1,66 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{cmp::Reverse, collections::HashMap};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{
+    models::DeliveryState,
+    repositories::{WebhookDeliveryRow, WebhookRepository},
+};
+
+#[derive(Default)]
+pub struct InMemoryWebhookRepository {
+    deliveries: Mutex<HashMap<Uuid, WebhookDeliveryRow>>,
+}
+
+#[async_trait]
+impl WebhookRepository for InMemoryWebhookRepository {
+    async fn insert(&self, delivery: WebhookDeliveryRow) -> anyhow::Result<()> {
+        let mut deliveries = self.deliveries.lock().await;
+        deliveries.insert(delivery.id, delivery);
+        Ok(())
+    }
+
+    async fn update(&self, delivery: WebhookDeliveryRow) -> anyhow::Result<()> {
+        let mut deliveries = self.deliveries.lock().await;
+        if let Some(row) = deliveries.get_mut(&delivery.id) {
+            *row = delivery;
+        }
+        Ok(())
+    }
+
+    async fn list_due(
+        &self,
+        now: DateTime<Utc>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<WebhookDeliveryRow>> {
+        let deliveries = self.deliveries.lock().await;
+        let mut rows: Vec<_> = deliveries
+            .values()
+            .filter(|row| row.state == DeliveryState::Pending && row.next_attempt_at <= now)
+            .cloned()
+            .collect();
+
+        rows.sort_by_key(|row| row.next_attempt_at);
+        rows.truncate(limit);
+        Ok(rows)
+    }
+
+    async fn list_by_owner(
+        &self,
+        owner: &str,
+        offset: usize,
+        limit: usize,
+    ) -> anyhow::Result<Vec<WebhookDeliveryRow>> {
+        let deliveries = self.deliveries.lock().await;
+        let mut rows: Vec<_> = deliveries
+            .values()
+            .filter(|row| row.owner == owner)
+            .cloned()
+            .collect();
+
+        rows.sort_by_key(|row| Reverse(row.created_at));
+        Ok(rows.into_iter().skip(offset).take(limit).collect())
+    }
+}
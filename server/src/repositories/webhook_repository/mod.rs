@@ -0,0 +1,55 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::models::{DeliveryState, WebhookEvent};
+
+/// A webhook notification, queued until it reaches the endpoint of its owner or runs out of
+/// attempts. `payload` is the body sent on every attempt, `last_status` and `last_error` tell how
+/// the last attempt went.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WebhookDeliveryRow {
+    pub id: Uuid,
+    pub owner: String,
+    pub event: WebhookEvent,
+    pub upload_id: Uuid,
+    pub url: String,
+    pub payload: String,
+    pub state: DeliveryState,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status: Option<u16>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait WebhookRepository: Send + Sync {
+    async fn insert(&self, delivery: WebhookDeliveryRow) -> anyhow::Result<()>;
+    /// Records the outcome of an attempt: state, attempts, next attempt and last response.
+    async fn update(&self, delivery: WebhookDeliveryRow) -> anyhow::Result<()>;
+    /// Returns up to `limit` pending deliveries due at `now`, the ones due first first.
+    async fn list_due(
+        &self,
+        now: DateTime<Utc>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<WebhookDeliveryRow>>;
+    /// Returns up to `limit` deliveries of `owner`, newest first, skipping the first `offset`.
+    async fn list_by_owner(
+        &self,
+        owner: &str,
+        offset: usize,
+        limit: usize,
+    ) -> anyhow::Result<Vec<WebhookDeliveryRow>>;
+}
+
+#[cfg(feature = "in-memory")]
+mod in_memory;
+#[cfg(feature = "in-memory")]
+pub use in_memory::InMemoryWebhookRepository;
+
+#[cfg(feature = "persistent")]
+mod clickhouse;
+#[cfg(feature = "persistent")]
+pub use clickhouse::ClickhouseWebhookRepository;
@@ -1,6 +1,11 @@
 use crate::handlers::RouteExtensions;
-use crate::infrastructure::{AuthenticationExtensions, TracingExtensions};
-use crate::services::FileService;
+use crate::infrastructure::{
+    AuditExtensions, AuthenticationExtensions, BackgroundTasks, BodyLimitExtensions,
+    IdempotencyConfig, IdempotencyExtensions, MetricsExtensions, RateLimitConfig,
+    RateLimitExtensions, ResponseSigningConfig, ResponseSigningExtensions, ServerSigner,
+    TracingExtensions,
+};
+use crate::services::{AdminService, FileService, TransparencyLogService, UploadSessionService};
 use crate::{
     apidoc::ApiDoc,
     repositories,
@@ -18,26 +23,59 @@ use utoipa_swagger_ui::SwaggerUi;
 #[derive(Clone)]
 pub struct ServerState {
     file_service: Arc<dyn FileService>,
+    upload_session_service: Arc<dyn UploadSessionService>,
+    admin_service: Arc<dyn AdminService>,
+    transparency_log_service: Arc<dyn TransparencyLogService>,
 }
 
 impl ServerState {
-    pub fn new(file_service: Arc<dyn FileService>) -> Self {
-        Self { file_service }
+    pub fn new(
+        file_service: Arc<dyn FileService>,
+        upload_session_service: Arc<dyn UploadSessionService>,
+        admin_service: Arc<dyn AdminService>,
+        transparency_log_service: Arc<dyn TransparencyLogService>,
+    ) -> Self {
+        Self {
+            file_service,
+            upload_session_service,
+            admin_service,
+            transparency_log_service,
+        }
     }
 
     pub fn file_service(&self) -> Arc<dyn FileService> {
         Arc::clone(&self.file_service)
     }
+
+    pub fn upload_session_service(&self) -> Arc<dyn UploadSessionService> {
+        Arc::clone(&self.upload_session_service)
+    }
+
+    pub fn admin_service(&self) -> Arc<dyn AdminService> {
+        Arc::clone(&self.admin_service)
+    }
+
+    pub fn transparency_log_service(&self) -> Arc<dyn TransparencyLogService> {
+        Arc::clone(&self.transparency_log_service)
+    }
 }
 
+const TEN_GIB_IN_BYTES: usize = 10 * 1024 * 1024 * 1024;
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct ServerConfig {
     pub port: u16,
+    #[serde(default = "ServerConfig::default_max_upload_bytes")]
+    pub max_upload_bytes: usize,
 }
 
 impl ServerConfig {
     const CONFIG_PREFIX: &'static str = "SERVER_CONFIG";
 
+    fn default_max_upload_bytes() -> usize {
+        TEN_GIB_IN_BYTES
+    }
+
     pub fn load_from_env() -> anyhow::Result<Self> {
         let config = Config::builder()
             .add_source(config::Environment::with_prefix(Self::CONFIG_PREFIX).separator("__"))
@@ -48,25 +86,104 @@ impl ServerConfig {
     }
 }
 
-pub async fn init_server() -> anyhow::Result<(Router, TcpListener)> {
+pub async fn init_server() -> anyhow::Result<(Router, TcpListener, BackgroundTasks)> {
     let config = ServerConfig::load_from_env()?;
+    let rate_limit_config = RateLimitConfig::load_from_env()?;
+    let idempotency_config = IdempotencyConfig::load_from_env()?;
+    let signer = Arc::new(ServerSigner::from_config(
+        ResponseSigningConfig::load_from_env()?,
+    )?);
+
+    let repositories = repositories::init_repositories().await?;
+    let quotas = Arc::new(services::init_quotas(Arc::clone(
+        &repositories.usage_repository,
+    ))?);
+    let webhooks = Arc::new(services::init_webhooks(Arc::clone(
+        &repositories.webhook_repository,
+    ))?);
+    // Shared so watchers see uploads expiring as well as everything the file service does.
+    let upload_events = services::UploadEvents::default();
+    let upload_reaper = services::init_upload_reaper(
+        Arc::clone(&repositories.file_repository),
+        Arc::clone(&repositories.file_storage),
+        Arc::clone(&repositories.upload_session_repository),
+        Arc::clone(&repositories.blob_repository),
+        Arc::clone(&quotas),
+        Arc::clone(&webhooks),
+        upload_events.clone(),
+    )?;
+    let write_recovery = services::init_write_recovery(
+        Arc::clone(&repositories.pending_write_repository),
+        Arc::clone(&repositories.file_repository),
+        Arc::clone(&repositories.file_storage),
+        Arc::clone(&repositories.blob_repository),
+        Arc::clone(&quotas),
+    )?;
+    let legacy_migration = services::init_legacy_migration(
+        Arc::clone(&repositories.file_repository),
+        Arc::clone(&repositories.file_storage),
+        Arc::clone(&repositories.blob_repository),
+        Arc::clone(&quotas),
+    )?;
+    let blob_collector = services::init_blob_collector(
+        Arc::clone(&repositories.blob_repository),
+        Arc::clone(&repositories.file_storage),
+    )?;
+    let scrubber = services::init_scrubber(
+        Arc::clone(&repositories.file_repository),
+        Arc::clone(&repositories.file_storage),
+        Arc::clone(&repositories.scrub_repository),
+        Arc::clone(&webhooks),
+    )?;
+    let webhook_dispatcher =
+        services::init_webhook_dispatcher(Arc::clone(&repositories.webhook_repository))?;
+    let idempotency_repository = Arc::clone(&repositories.idempotency_repository);
+    let audit_repository = Arc::clone(&repositories.audit_repository);
+    let services = services::init_services(
+        &repositories,
+        Arc::clone(&signer),
+        quotas,
+        webhooks,
+        upload_events,
+    )
+    .await?;
+    let state = ServerState::new(
+        services.file_service,
+        services.upload_session_service,
+        services.admin_service,
+        services.transparency_log_service,
+    );
 
-    let (file_repository, file_storage) = repositories::init_repositories().await?;
-    let file_service = services::init_services(file_repository, file_storage).await?;
-    let state = ServerState::new(file_service);
+    let mut background_tasks = BackgroundTasks::default();
+    background_tasks.spawn("write-recovery", |shutdown| write_recovery.run(shutdown));
+    background_tasks.spawn("legacy-migration", |shutdown| {
+        legacy_migration.run(shutdown)
+    });
+    background_tasks.spawn("upload-reaper", |shutdown| upload_reaper.run(shutdown));
+    background_tasks.spawn("blob-collector", |shutdown| blob_collector.run(shutdown));
+    background_tasks.spawn("scrubber", |shutdown| scrubber.run(shutdown));
+    background_tasks.spawn("webhook-dispatcher", |shutdown| {
+        webhook_dispatcher.run(shutdown)
+    });
 
     let (server, api) = OpenApiRouter::with_openapi(ApiDoc::openapi())
         .with_routes(Arc::new(state))
         .split_for_parts();
 
     let server = server
+        .with_body_limit(config.max_upload_bytes)
         .with_tracing()
         .with_request_id()
         .with_correlation_id()
+        .with_idempotency(idempotency_repository, idempotency_config)
+        .with_rate_limiting(rate_limit_config)
+        .with_audit(audit_repository)
         .with_authentication()
+        .with_metrics()
+        .with_response_signing(signer)
         .merge(SwaggerUi::new("/swagger-ui").url("/apidoc/openapi.json", api));
 
     let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, config.port)).await?;
 
-    Ok((server, listener))
+    Ok((server, listener, background_tasks))
 }
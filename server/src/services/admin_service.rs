@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tracing::error;
+
+use crate::{
+    models::{OrphanReport, ScrubReport, UploadCursor},
+    repositories::ScrubRepository,
+    services::{FileServiceError, OrphanCollector},
+};
+
+/// Operations reserved to admin clients, which look at the server as a whole rather than at
+/// their own uploads.
+#[async_trait]
+pub trait AdminService: Send + Sync {
+    /// Findings of the scrubber, newest first, skipping the first `offset`.
+    async fn list_scrub_findings(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<ScrubReport, FileServiceError>;
+    /// Looks for orphan objects under the next `limit` uploads after `after`, deleting them
+    /// unless `dry_run` is set.
+    async fn collect_orphans(
+        &self,
+        after: Option<UploadCursor>,
+        limit: usize,
+        dry_run: bool,
+    ) -> Result<OrphanReport, FileServiceError>;
+}
+
+pub struct AdminServiceImpl {
+    scrub_repository: Arc<dyn ScrubRepository>,
+    orphan_collector: Arc<OrphanCollector>,
+}
+
+impl AdminServiceImpl {
+    pub fn new(
+        scrub_repository: Arc<dyn ScrubRepository>,
+        orphan_collector: Arc<OrphanCollector>,
+    ) -> Self {
+        Self {
+            scrub_repository,
+            orphan_collector,
+        }
+    }
+}
+
+#[async_trait]
+impl AdminService for AdminServiceImpl {
+    async fn list_scrub_findings(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<ScrubReport, FileServiceError> {
+        let findings = self
+            .scrub_repository
+            .list_findings(offset, limit)
+            .await
+            .map_err(|e| {
+                error!("Failed to list scrub findings: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })?;
+
+        let cursor = self.scrub_repository.get_cursor().await.map_err(|e| {
+            error!("Failed to get scrub cursor: {}", e);
+            FileServiceError::StorageError(e.to_string())
+        })?;
+
+        Ok(ScrubReport { findings, cursor })
+    }
+
+    async fn collect_orphans(
+        &self,
+        after: Option<UploadCursor>,
+        limit: usize,
+        dry_run: bool,
+    ) -> Result<OrphanReport, FileServiceError> {
+        self.orphan_collector
+            .collect(after, limit, dry_run)
+            .await
+            .map_err(|e| {
+                error!("Failed to collect orphan objects: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })
+    }
+}
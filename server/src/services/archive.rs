@@ -0,0 +1,477 @@
+// Tar and zip are simple enough to be written by hand, which keeps archives streaming: entries
+// are emitted as their contents are read from storage and nothing is buffered besides the zip
+// central directory.
+use std::{collections::VecDeque, io, sync::Arc};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use file_server_library::models::Hash32;
+use futures::{StreamExt, stream};
+
+use crate::{
+    models::ArchiveFormat,
+    repositories::{FileStorage, FileStream},
+};
+
+/// File of an upload to be archived, its contents are read from the blob of its leaf hash.
+pub(crate) struct ArchiveFile {
+    pub path: String,
+    pub hash: Hash32,
+    pub modified: DateTime<Utc>,
+}
+
+/// Streams an archive holding `manifest` as `manifest.json` followed by every file.
+pub(crate) fn archive_stream(
+    format: ArchiveFormat,
+    manifest: Bytes,
+    files: Vec<ArchiveFile>,
+    file_storage: Arc<dyn FileStorage>,
+) -> FileStream {
+    let encoder: Box<dyn ArchiveEncoder> = match format {
+        ArchiveFormat::Tar => Box::new(TarEncoder::default()),
+        ArchiveFormat::Zip => Box::new(ZipEncoder::default()),
+    };
+
+    let mut pending = VecDeque::with_capacity(files.len() + 1);
+    pending.push_back(ArchiveItem::Inline {
+        path: MANIFEST_PATH.to_string(),
+        modified: Utc::now(),
+        contents: manifest,
+    });
+    pending.extend(files.into_iter().map(ArchiveItem::Blob));
+
+    let state = ArchiveState {
+        encoder,
+        pending,
+        current: None,
+        file_storage,
+        done: false,
+    };
+
+    Box::pin(stream::unfold(state, |mut state| async move {
+        state.next_chunk().await.map(|chunk| (chunk, state))
+    }))
+}
+
+pub(crate) const MANIFEST_PATH: &str = "manifest.json";
+
+enum ArchiveItem {
+    Inline {
+        path: String,
+        modified: DateTime<Utc>,
+        contents: Bytes,
+    },
+    Blob(ArchiveFile),
+}
+
+struct CurrentEntry {
+    path: String,
+    contents: FileStream,
+    remaining: u64,
+}
+
+struct ArchiveState {
+    encoder: Box<dyn ArchiveEncoder>,
+    pending: VecDeque<ArchiveItem>,
+    current: Option<CurrentEntry>,
+    file_storage: Arc<dyn FileStorage>,
+    done: bool,
+}
+
+impl ArchiveState {
+    // Errors end the stream, the archive is truncated and clients notice it is unreadable.
+    async fn next_chunk(&mut self) -> Option<io::Result<Bytes>> {
+        if self.done {
+            return None;
+        }
+
+        let result = match self.current.as_mut() {
+            Some(current) => match current.contents.next().await {
+                Some(Ok(chunk)) => match current.remaining.checked_sub(chunk.len() as u64) {
+                    Some(remaining) => {
+                        current.remaining = remaining;
+                        self.encoder.update(&chunk);
+                        Ok(chunk)
+                    }
+                    None => Err(size_mismatch(&current.path)),
+                },
+                Some(Err(e)) => Err(e),
+                None if current.remaining == 0 => {
+                    self.current = None;
+                    Ok(self.encoder.end())
+                }
+                None => Err(size_mismatch(&current.path)),
+            },
+            None => match self.pending.pop_front() {
+                Some(item) => self.open(item).await,
+                None => {
+                    self.done = true;
+                    Ok(self.encoder.finish())
+                }
+            },
+        };
+
+        if result.is_err() {
+            self.done = true;
+        }
+        Some(result)
+    }
+
+    async fn open(&mut self, item: ArchiveItem) -> io::Result<Bytes> {
+        let (path, modified, size, contents) = match item {
+            ArchiveItem::Inline {
+                path,
+                modified,
+                contents,
+            } => {
+                let size = contents.len() as u64;
+                let contents: FileStream = Box::pin(stream::once(async { Ok(contents) }));
+                (path, modified, size, contents)
+            }
+            ArchiveItem::Blob(file) => {
+                let missing = || {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("blob of {} not found", file.path),
+                    )
+                };
+                // Sizes go into the headers, so they come from the stored blob rather than the
+                // upload row.
+                let size = self
+                    .file_storage
+                    .get_blob_metadata(file.hash)
+                    .await
+                    .map_err(io::Error::other)?
+                    .ok_or_else(missing)?
+                    .size;
+                let contents = self
+                    .file_storage
+                    .get_blob_content(file.hash, None)
+                    .await
+                    .map_err(io::Error::other)?
+                    .ok_or_else(missing)?;
+                (file.path, file.modified, size, contents)
+            }
+        };
+
+        let header = self.encoder.begin(&path, size, modified);
+        self.current = Some(CurrentEntry {
+            path,
+            contents,
+            remaining: size,
+        });
+
+        Ok(header)
+    }
+}
+
+fn size_mismatch(path: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("contents of {path} do not match the stored size"),
+    )
+}
+
+/// Framing of archive entries. Contents are passed through untouched, encoders only produce
+/// what goes around them.
+trait ArchiveEncoder: Send {
+    fn begin(&mut self, path: &str, size: u64, modified: DateTime<Utc>) -> Bytes;
+    fn update(&mut self, chunk: &[u8]);
+    fn end(&mut self) -> Bytes;
+    fn finish(&mut self) -> Bytes;
+}
+
+const TAR_BLOCK: usize = 512;
+// Largest size the 11 octal digits of a ustar header can hold.
+const TAR_MAX_SIZE: u64 = 0o77777777777;
+
+/// POSIX ustar, with pax extended headers for names over 100 bytes and files over 8 GiB.
+#[derive(Default)]
+struct TarEncoder {
+    size: u64,
+}
+
+impl ArchiveEncoder for TarEncoder {
+    fn begin(&mut self, path: &str, size: u64, modified: DateTime<Utc>) -> Bytes {
+        let mut buf = BytesMut::new();
+        let mtime = modified.timestamp().max(0) as u64;
+
+        if path.len() > 100 || size > TAR_MAX_SIZE {
+            let mut records = String::new();
+            if path.len() > 100 {
+                records.push_str(&pax_record("path", path));
+            }
+            if size > TAR_MAX_SIZE {
+                records.push_str(&pax_record("size", &size.to_string()));
+            }
+            buf.put_slice(&tar_header(
+                "././@PaxHeader",
+                records.len() as u64,
+                mtime,
+                b'x',
+            ));
+            buf.put_slice(records.as_bytes());
+            buf.put_bytes(0, tar_padding(records.len() as u64));
+        }
+
+        buf.put_slice(&tar_header(
+            truncate(path, 100),
+            size.min(TAR_MAX_SIZE),
+            mtime,
+            b'0',
+        ));
+        self.size = size;
+
+        buf.freeze()
+    }
+
+    fn update(&mut self, _chunk: &[u8]) {}
+
+    fn end(&mut self) -> Bytes {
+        Bytes::from(vec![0; tar_padding(self.size)])
+    }
+
+    // Two empty blocks mark the end of the archive.
+    fn finish(&mut self) -> Bytes {
+        Bytes::from(vec![0; 2 * TAR_BLOCK])
+    }
+}
+
+fn tar_header(name: &str, size: u64, mtime: u64, typeflag: u8) -> [u8; TAR_BLOCK] {
+    let mut header = [0u8; TAR_BLOCK];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+    header[136..148].copy_from_slice(format!("{:011o}\0", mtime.min(TAR_MAX_SIZE)).as_bytes());
+    header[148..156].copy_from_slice(b"        ");
+    header[156] = typeflag;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+    header
+}
+
+fn tar_padding(size: u64) -> usize {
+    (TAR_BLOCK - (size % TAR_BLOCK as u64) as usize) % TAR_BLOCK
+}
+
+// Records are prefixed with their own length, digits included.
+fn pax_record(key: &str, value: &str) -> String {
+    let body = format!(" {key}={value}\n");
+    let mut len = body.len() + 1;
+    while len.to_string().len() + body.len() != len {
+        len = len.to_string().len() + body.len();
+    }
+    format!("{len}{body}")
+}
+
+fn truncate(s: &str, max: usize) -> &str {
+    let mut end = s.len().min(max);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+const ZIP_VERSION: u16 = 20;
+const ZIP64_VERSION: u16 = 45;
+// Sizes follow the contents in a data descriptor and names are UTF-8.
+const ZIP_FLAGS: u16 = 0x0808;
+const ZIP64_LIMIT: u64 = 0xFFFF_FFFF;
+
+struct ZipEntry {
+    name: String,
+    crc: u32,
+    size: u64,
+    offset: u64,
+    time: u16,
+    date: u16,
+}
+
+impl ZipEntry {
+    fn zip64(&self) -> bool {
+        self.size >= ZIP64_LIMIT
+    }
+}
+
+/// Stored (uncompressed) zip with data descriptors, switching to zip64 records only where the
+/// sizes or offsets need them.
+#[derive(Default)]
+struct ZipEncoder {
+    entries: Vec<ZipEntry>,
+    hasher: crc32fast::Hasher,
+    offset: u64,
+}
+
+impl ArchiveEncoder for ZipEncoder {
+    fn begin(&mut self, path: &str, size: u64, modified: DateTime<Utc>) -> Bytes {
+        let (time, date) = dos_date_time(modified);
+        let entry = ZipEntry {
+            name: path.to_string(),
+            crc: 0,
+            size,
+            offset: self.offset,
+            time,
+            date,
+        };
+
+        let mut buf = BytesMut::new();
+        buf.put_u32_le(0x0403_4b50);
+        buf.put_u16_le(if entry.zip64() {
+            ZIP64_VERSION
+        } else {
+            ZIP_VERSION
+        });
+        buf.put_u16_le(ZIP_FLAGS);
+        buf.put_u16_le(0);
+        buf.put_u16_le(time);
+        buf.put_u16_le(date);
+        buf.put_u32_le(0);
+        if entry.zip64() {
+            buf.put_u32_le(ZIP64_LIMIT as u32);
+            buf.put_u32_le(ZIP64_LIMIT as u32);
+        } else {
+            buf.put_u32_le(0);
+            buf.put_u32_le(0);
+        }
+        buf.put_u16_le(entry.name.len() as u16);
+        buf.put_u16_le(if entry.zip64() { 20 } else { 0 });
+        buf.put_slice(entry.name.as_bytes());
+        if entry.zip64() {
+            buf.put_u16_le(0x0001);
+            buf.put_u16_le(16);
+            buf.put_u64_le(0);
+            buf.put_u64_le(0);
+        }
+
+        self.offset += buf.len() as u64 + size;
+        self.hasher = crc32fast::Hasher::new();
+        self.entries.push(entry);
+
+        buf.freeze()
+    }
+
+    fn update(&mut self, chunk: &[u8]) {
+        self.hasher.update(chunk);
+    }
+
+    fn end(&mut self) -> Bytes {
+        let entry = self.entries.last_mut().expect("entry was begun");
+        entry.crc = std::mem::take(&mut self.hasher).finalize();
+
+        let mut buf = BytesMut::new();
+        buf.put_u32_le(0x0807_4b50);
+        buf.put_u32_le(entry.crc);
+        if entry.zip64() {
+            buf.put_u64_le(entry.size);
+            buf.put_u64_le(entry.size);
+        } else {
+            buf.put_u32_le(entry.size as u32);
+            buf.put_u32_le(entry.size as u32);
+        }
+
+        self.offset += buf.len() as u64;
+        buf.freeze()
+    }
+
+    fn finish(&mut self) -> Bytes {
+        let mut buf = BytesMut::new();
+        let directory_offset = self.offset;
+
+        for entry in &self.entries {
+            let large_offset = entry.offset >= ZIP64_LIMIT;
+            let mut extra = BytesMut::new();
+            if entry.zip64() {
+                extra.put_u64_le(entry.size);
+                extra.put_u64_le(entry.size);
+            }
+            if large_offset {
+                extra.put_u64_le(entry.offset);
+            }
+
+            buf.put_u32_le(0x0201_4b50);
+            buf.put_u16_le(ZIP64_VERSION);
+            buf.put_u16_le(if entry.zip64() || large_offset {
+                ZIP64_VERSION
+            } else {
+                ZIP_VERSION
+            });
+            buf.put_u16_le(ZIP_FLAGS);
+            buf.put_u16_le(0);
+            buf.put_u16_le(entry.time);
+            buf.put_u16_le(entry.date);
+            buf.put_u32_le(entry.crc);
+            buf.put_u32_le(entry.size.min(ZIP64_LIMIT) as u32);
+            buf.put_u32_le(entry.size.min(ZIP64_LIMIT) as u32);
+            buf.put_u16_le(entry.name.len() as u16);
+            buf.put_u16_le(if extra.is_empty() {
+                0
+            } else {
+                extra.len() as u16 + 4
+            });
+            buf.put_u16_le(0);
+            buf.put_u16_le(0);
+            buf.put_u16_le(0);
+            buf.put_u32_le(0o100644 << 16);
+            buf.put_u32_le(entry.offset.min(ZIP64_LIMIT) as u32);
+            buf.put_slice(entry.name.as_bytes());
+            if !extra.is_empty() {
+                buf.put_u16_le(0x0001);
+                buf.put_u16_le(extra.len() as u16);
+                buf.put_slice(&extra);
+            }
+        }
+
+        let count = self.entries.len() as u64;
+        let directory_size = buf.len() as u64;
+
+        if count >= 0xFFFF || directory_offset >= ZIP64_LIMIT || directory_size >= ZIP64_LIMIT {
+            let record_offset = directory_offset + directory_size;
+
+            buf.put_u32_le(0x0606_4b50);
+            buf.put_u64_le(44);
+            buf.put_u16_le(ZIP64_VERSION);
+            buf.put_u16_le(ZIP64_VERSION);
+            buf.put_u32_le(0);
+            buf.put_u32_le(0);
+            buf.put_u64_le(count);
+            buf.put_u64_le(count);
+            buf.put_u64_le(directory_size);
+            buf.put_u64_le(directory_offset);
+
+            buf.put_u32_le(0x0706_4b50);
+            buf.put_u32_le(0);
+            buf.put_u64_le(record_offset);
+            buf.put_u32_le(1);
+        }
+
+        buf.put_u32_le(0x0605_4b50);
+        buf.put_u16_le(0);
+        buf.put_u16_le(0);
+        buf.put_u16_le(count.min(0xFFFF) as u16);
+        buf.put_u16_le(count.min(0xFFFF) as u16);
+        buf.put_u32_le(directory_size.min(ZIP64_LIMIT) as u32);
+        buf.put_u32_le(directory_offset.min(ZIP64_LIMIT) as u32);
+        buf.put_u16_le(0);
+
+        self.offset += buf.len() as u64;
+        buf.freeze()
+    }
+}
+
+// MS-DOS timestamps start in 1980 and have a two seconds resolution.
+fn dos_date_time(modified: DateTime<Utc>) -> (u16, u16) {
+    if modified.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+
+    let time = (modified.hour() << 11) | (modified.minute() << 5) | (modified.second() / 2);
+    let date = ((modified.year() as u32 - 1980) << 9) | (modified.month() << 5) | modified.day();
+
+    (time as u16, date as u16)
+}
@@ -0,0 +1,151 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use config::Config;
+use metrics::counter;
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument, warn};
+
+use crate::repositories::{BlobRepository, BlobRow, FileStorage};
+
+const ONE_HOUR_IN_SECONDS: u64 = 60 * 60;
+const FIVE_MINUTES_IN_SECONDS: u64 = 5 * 60;
+
+/// Blobs are only collected once they went unreferenced and untouched for `grace_period_secs`.
+/// Uploads touch a blob before referencing it, so the grace period must be longer than the
+/// slowest upload takes to go from storing the contents to adding the reference.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlobGcConfig {
+    #[serde(default = "BlobGcConfig::default_grace_period_secs")]
+    pub grace_period_secs: u64,
+    #[serde(default = "BlobGcConfig::default_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "BlobGcConfig::default_batch_size")]
+    pub batch_size: usize,
+}
+
+impl Default for BlobGcConfig {
+    fn default() -> Self {
+        Self {
+            grace_period_secs: Self::default_grace_period_secs(),
+            interval_secs: Self::default_interval_secs(),
+            batch_size: Self::default_batch_size(),
+        }
+    }
+}
+
+impl BlobGcConfig {
+    const CONFIG_PREFIX: &'static str = "BLOB_GC";
+
+    pub fn load_from_env() -> anyhow::Result<Self> {
+        Config::builder()
+            .add_source(config::Environment::with_prefix(Self::CONFIG_PREFIX).separator("__"))
+            .build()?
+            .try_deserialize::<BlobGcConfig>()
+            .map_err(|e| anyhow::anyhow!("failed to load Blob GC Configuration: {}", e))
+    }
+
+    fn default_grace_period_secs() -> u64 {
+        ONE_HOUR_IN_SECONDS
+    }
+
+    fn default_interval_secs() -> u64 {
+        FIVE_MINUTES_IN_SECONDS
+    }
+
+    fn default_batch_size() -> usize {
+        100
+    }
+}
+
+/// Periodically removes blobs no file references anymore, which happens once every upload
+/// pointing at them was deleted or expired.
+pub struct BlobCollector {
+    blob_repository: Arc<dyn BlobRepository>,
+    file_storage: Arc<dyn FileStorage>,
+    config: BlobGcConfig,
+}
+
+impl BlobCollector {
+    pub fn new(
+        blob_repository: Arc<dyn BlobRepository>,
+        file_storage: Arc<dyn FileStorage>,
+        config: BlobGcConfig,
+    ) -> Self {
+        Self {
+            blob_repository,
+            file_storage,
+            config,
+        }
+    }
+
+    pub async fn run(self, shutdown: CancellationToken) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = interval.tick() => {
+                    if let Err(e) = self.collect().await {
+                        counter!("blob_collector_failures_total").increment(1);
+                        error!("Blob collector iteration failed: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Runs a single pass and returns how many blobs were removed.
+    #[instrument(skip(self))]
+    pub async fn collect(&self) -> anyhow::Result<usize> {
+        let updated_before = Utc::now() - Duration::from_secs(self.config.grace_period_secs);
+        let unreferenced = self
+            .blob_repository
+            .list_unreferenced(updated_before, self.config.batch_size)
+            .await?;
+
+        let mut collected = 0;
+
+        for blob in unreferenced {
+            let hash = blob.hash.to_hex();
+
+            match self.remove(blob).await {
+                Ok(true) => {
+                    counter!("blobs_collected_total").increment(1);
+                    info!(%hash, "Blob collected");
+                    collected += 1;
+                }
+                Ok(false) => info!(%hash, "Blob was referenced again, skipping"),
+                Err(e) => {
+                    counter!("blob_collector_failures_total").increment(1);
+                    warn!(%hash, "Failed to collect blob: {}", e);
+                }
+            }
+        }
+
+        Ok(collected)
+    }
+
+    // The blob is read again right before deleting it, an upload may have referenced or touched
+    // it since it was listed. The contents are deleted before the row so a failed deletion is
+    // picked up again in the next iteration.
+    async fn remove(&self, blob: BlobRow) -> anyhow::Result<bool> {
+        let still_unreferenced =
+            self.blob_repository
+                .get(blob.hash)
+                .await?
+                .is_some_and(|current| {
+                    current.references == 0 && current.updated_at <= blob.updated_at
+                });
+
+        if !still_unreferenced {
+            return Ok(false);
+        }
+
+        self.file_storage.delete_blob(blob.hash).await?;
+        self.blob_repository.delete(blob.hash).await?;
+
+        Ok(true)
+    }
+}
@@ -1,184 +1,515 @@
-use std::sync::Arc;
+use std::{
+    io,
+    ops::RangeInclusive,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
 
 use async_trait::async_trait;
-use bytes::Bytes;
+use chrono::Utc;
 use file_server_library::{
     CustomMerkleTree,
-    models::{Hash32, Proof},
+    models::{Hash32, Hash32Hasher, Proof},
 };
-use tracing::error;
+use futures::{TryStreamExt, stream};
+use metrics::counter;
+use serde_json::json;
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
 use crate::{
-    models::{FileContent, FileMerkleTree, FileMetadata},
-    repositories::{FileRepository, FileStorage},
+    models::{
+        ArchiveFormat, ArchiveManifest, ArchiveManifestEntry, AuditFilter, FileDescriptor,
+        FileMerkleTree, FileMetadata, FilePage, QuotaViolation, UploadCursor, UploadEvent,
+        UploadFilter, UploadPage, UploadState, UploadSummary, Usage, WebhookEvent, staging_name,
+    },
+    repositories::{
+        AuditEntryRow, AuditRepository, BlobReferenceRow, BlobRepository, EncodedContent,
+        FileRepository, FileStorage, FileStream, PendingWriteRepository, PendingWriteRow,
+        StoredFileMetadata, UploadSessionRepository, WebhookDeliveryRow,
+    },
+    services::{
+        Allowance, Quotas, TransparencyLogService, TreeCache, UploadEventStream, UploadEvents,
+        Webhooks,
+        archive::{ArchiveFile, archive_stream},
+        write_recovery::{held_by_versions, holds},
+    },
 };
 
+// Times an operation starts over on an upload changing meanwhile before giving up.
+const UPLOAD_CHANGE_ATTEMPTS: usize = 3;
+
 #[derive(Debug)]
 pub enum FileServiceError {
     FileNotFound,
     FileIndexNotFound,
     FileAlreadyExists,
+    UploadExpired,
+    PayloadTooLarge,
+    InvalidFileName,
+    UploadSessionNotFound,
+    UploadOffsetMismatch(u64),
+    UploadLengthExceeded,
+    UploadSessionIncomplete,
+    BlobNotFound,
+    UploadNotCompleted,
+    UploadAlreadyCompleted,
+    UploadConflict,
+    VersionNotFound,
+    LogEntryNotFound,
+    InvalidLogSize,
+    QuotaExceeded(QuotaViolation),
     StorageError(String),
 }
 
+/// Operations on a single upload take the client performing them, uploads of other clients are
+/// not found. Reads take the version of the upload to read from, the latest one when missing.
 #[async_trait]
 pub trait FileService: Send + Sync {
+    async fn get_file_descriptor(
+        &self,
+        owner: &str,
+        id: Uuid,
+        version: Option<u32>,
+        index: usize,
+    ) -> Result<FileDescriptor, FileServiceError>;
     async fn get_file_content(
         &self,
+        owner: &str,
+        id: Uuid,
+        version: Option<u32>,
+        index: usize,
+        range: Option<RangeInclusive<u64>>,
+    ) -> Result<FileStream, FileServiceError>;
+    /// The file contents as they are stored when they are encoded, so they can be sent without
+    /// decoding them. `None` when they are stored as they are.
+    async fn get_encoded_file_content(
+        &self,
+        owner: &str,
+        id: Uuid,
+        version: Option<u32>,
+        index: usize,
+    ) -> Result<Option<EncodedContent>, FileServiceError>;
+    async fn get_proof(
+        &self,
+        owner: &str,
         id: Uuid,
+        version: Option<u32>,
         index: usize,
-    ) -> Result<FileContent, FileServiceError>;
-    async fn get_proof(&self, id: Uuid, index: usize) -> Result<Proof, FileServiceError>;
-    async fn initiate(&self) -> Result<Uuid, FileServiceError>;
+    ) -> Result<Proof, FileServiceError>;
+    /// Index of the file named `name`, so files can be addressed by name as well.
+    async fn get_file_index(
+        &self,
+        owner: &str,
+        id: Uuid,
+        version: Option<u32>,
+        name: &str,
+    ) -> Result<usize, FileServiceError>;
+    /// Files of the upload in index order, skipping the first `offset` and returning at most
+    /// `limit`.
+    async fn list_files(
+        &self,
+        owner: &str,
+        id: Uuid,
+        version: Option<u32>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<FilePage, FileServiceError>;
+    /// Every file of a completed upload along with a manifest of their proofs, streamed as a
+    /// single archive.
+    async fn get_archive(
+        &self,
+        owner: &str,
+        id: Uuid,
+        version: Option<u32>,
+        format: ArchiveFormat,
+    ) -> Result<FileStream, FileServiceError>;
+    /// Uploads initiated by `owner` matching `filter`, newest first.
+    async fn list_uploads(
+        &self,
+        owner: &str,
+        filter: UploadFilter,
+        cursor: Option<UploadCursor>,
+        limit: usize,
+    ) -> Result<UploadPage, FileServiceError>;
+    async fn initiate(&self, owner: &str) -> Result<Uuid, FileServiceError>;
     async fn upload_file(
         &self,
+        owner: &str,
         id: Uuid,
         metadata: FileMetadata,
-        content: Bytes,
+        content: FileStream,
     ) -> Result<String, FileServiceError>;
-    async fn complete(&self, id: Uuid) -> Result<String, FileServiceError>;
+    /// Adds a file whose contents are already stored, without transferring them again. Only
+    /// contents another file of the same owner references can be added this way.
+    async fn upload_known_file(
+        &self,
+        owner: &str,
+        id: Uuid,
+        metadata: FileMetadata,
+        hash: Hash32,
+    ) -> Result<String, FileServiceError>;
+    /// Whether `owner` references contents with this leaf hash. Contents stored by other clients
+    /// are not disclosed, they can only be found by uploading them.
+    async fn blob_exists(&self, owner: &str, hash: Hash32) -> Result<bool, FileServiceError>;
+    async fn complete(&self, owner: &str, id: Uuid) -> Result<String, FileServiceError>;
+    /// Starts the next version of a completed upload with the files of the latest one, files
+    /// are then added or replaced as usual until it is completed again.
+    async fn create_version(
+        &self,
+        owner: &str,
+        id: Uuid,
+    ) -> Result<UploadSummary, FileServiceError>;
+    async fn delete(&self, owner: &str, id: Uuid) -> Result<(), FileServiceError>;
+    /// Events of the upload from now on, the stream ends once it is completed, deleted or
+    /// expired. A completed upload only gets its completion.
+    async fn watch(&self, owner: &str, id: Uuid) -> Result<UploadEventStream, FileServiceError>;
+    async fn get_usage(&self, owner: &str) -> Result<Usage, FileServiceError>;
+    /// Webhook deliveries of `owner`, newest first, skipping the first `offset`.
+    async fn list_webhook_deliveries(
+        &self,
+        owner: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<WebhookDeliveryRow>, FileServiceError>;
+    /// Up to `limit` entries of the audit log of `owner` matching `filter`, oldest first,
+    /// starting right after the `after` one.
+    async fn list_audit_entries(
+        &self,
+        owner: &str,
+        filter: AuditFilter,
+        after: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<AuditEntryRow>, FileServiceError>;
 }
 
 pub struct FileServiceImpl {
     file_repository: Arc<dyn FileRepository>,
     file_storage: Arc<dyn FileStorage>,
+    upload_session_repository: Arc<dyn UploadSessionRepository>,
+    blob_repository: Arc<dyn BlobRepository>,
+    tree_cache: TreeCache,
+    transparency_log: Option<Arc<dyn TransparencyLogService>>,
+    quotas: Option<Arc<Quotas>>,
+    pending_write_repository: Option<Arc<dyn PendingWriteRepository>>,
+    webhooks: Option<Arc<Webhooks>>,
+    audit_repository: Option<Arc<dyn AuditRepository>>,
+    upload_events: UploadEvents,
 }
 
 impl FileServiceImpl {
     pub fn new(
         file_repository: Arc<dyn FileRepository>,
         file_storage: Arc<dyn FileStorage>,
+        upload_session_repository: Arc<dyn UploadSessionRepository>,
+        blob_repository: Arc<dyn BlobRepository>,
     ) -> Self {
         Self {
             file_repository,
             file_storage,
+            upload_session_repository,
+            blob_repository,
+            tree_cache: TreeCache::default(),
+            transparency_log: None,
+            quotas: None,
+            pending_write_repository: None,
+            webhooks: None,
+            audit_repository: None,
+            upload_events: UploadEvents::default(),
         }
     }
 
-    pub async fn get_file_content(
+    pub fn with_tree_cache(mut self, tree_cache: TreeCache) -> Self {
+        self.tree_cache = tree_cache;
+        self
+    }
+
+    /// Completed roots are appended to the log, completing fails when they cannot be.
+    pub fn with_transparency_log(
+        mut self,
+        transparency_log: Arc<dyn TransparencyLogService>,
+    ) -> Self {
+        self.transparency_log = Some(transparency_log);
+        self
+    }
+
+    /// Usage is only tracked, and limits only enforced, along with quotas.
+    pub fn with_quotas(mut self, quotas: Arc<Quotas>) -> Self {
+        self.quotas = Some(quotas);
+        self
+    }
+
+    /// Files are journaled before being written, so the ones cut halfway can be finished or
+    /// rolled back by `WriteRecovery`.
+    pub fn with_pending_writes(
+        mut self,
+        pending_write_repository: Arc<dyn PendingWriteRepository>,
+    ) -> Self {
+        self.pending_write_repository = Some(pending_write_repository);
+        self
+    }
+
+    /// Owners are notified of uploads being initiated, completed and aborted.
+    pub fn with_webhooks(mut self, webhooks: Arc<Webhooks>) -> Self {
+        self.webhooks = Some(webhooks);
+        self
+    }
+
+    /// Clients can only read their audit log along with the repository it is recorded in.
+    pub fn with_audit_log(mut self, audit_repository: Arc<dyn AuditRepository>) -> Self {
+        self.audit_repository = Some(audit_repository);
+        self
+    }
+
+    /// Events are published to `upload_events`, shared with whatever else changes uploads so
+    /// watchers see all of their events.
+    pub fn with_upload_events(mut self, upload_events: UploadEvents) -> Self {
+        self.upload_events = upload_events;
+        self
+    }
+}
+
+#[async_trait]
+impl FileService for FileServiceImpl {
+    async fn get_file_descriptor(
         &self,
+        owner: &str,
         id: Uuid,
+        version: Option<u32>,
         index: usize,
-    ) -> Result<FileContent, FileServiceError> {
-        let file_tree = self.get_file_tree(id).await?;
+    ) -> Result<FileDescriptor, FileServiceError> {
+        let file_tree = self.get_file_tree_version(owner, id, version).await?;
 
-        let file_name = file_tree
+        let (name, hash) = file_tree
             .get_file_name_by_index(index)
+            .zip(file_tree.get_leaf_hash_by_index(index))
             .ok_or(FileServiceError::FileNotFound)?;
 
-        let content = self
+        let stored = self
             .file_storage
-            .get_file_content(id, &file_name)
+            .get_blob_metadata(hash)
             .await
-            .map_err(|e| FileServiceError::StorageError(e.to_string()))?
+            .map_err(|e| {
+                error!("Failed to get file metadata: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })?
             .ok_or(FileServiceError::FileIndexNotFound)?;
 
-        Ok(content)
-    }
-
-    pub async fn get_proof(&self, id: Uuid, index: usize) -> Result<Proof, FileServiceError> {
-        let file_tree = self.get_file_tree(id).await?;
-
-        let tree = CustomMerkleTree::new(file_tree.leafs());
-        let proof = tree.proof(index);
+        // The blob keeps the content type of whoever stored it first, the reference the one of
+        // this file.
+        let reference = self
+            .blob_repository
+            .get_reference(id, &name, hash)
+            .await
+            .map_err(|e| {
+                error!("Failed to get blob reference: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })?;
 
-        Ok(proof)
+        Ok(FileDescriptor {
+            name,
+            hash,
+            size: stored.size,
+            content_type: reference
+                .map(|reference| reference.content_type)
+                .unwrap_or(stored.content_type),
+        })
     }
 
-    pub async fn initiate(&self) -> Result<Uuid, FileServiceError> {
-        let file_tree = FileMerkleTree::default();
+    async fn get_file_content(
+        &self,
+        owner: &str,
+        id: Uuid,
+        version: Option<u32>,
+        index: usize,
+        range: Option<RangeInclusive<u64>>,
+    ) -> Result<FileStream, FileServiceError> {
+        let file_tree = self.get_file_tree_version(owner, id, version).await?;
 
-        self.file_repository
-            .insert(file_tree.clone().into())
+        let hash = file_tree
+            .get_leaf_hash_by_index(index)
+            .ok_or(FileServiceError::FileNotFound)?;
+
+        let content = self
+            .file_storage
+            .get_blob_content(hash, range)
             .await
-            .map_err(|e| FileServiceError::StorageError(e.to_string()))?;
+            .map_err(|e| {
+                error!("Failed to get file content: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })?
+            .ok_or(FileServiceError::FileIndexNotFound)?;
 
-        Ok(file_tree.id())
+        Ok(content)
     }
 
-    pub async fn upload_file(
+    async fn get_encoded_file_content(
         &self,
+        owner: &str,
         id: Uuid,
-        metadata: FileMetadata,
-        content: Bytes,
-    ) -> Result<String, FileServiceError> {
-        let mut file_tree = self.get_file_tree(id).await?;
+        version: Option<u32>,
+        index: usize,
+    ) -> Result<Option<EncodedContent>, FileServiceError> {
+        let file_tree = self.get_file_tree_version(owner, id, version).await?;
 
-        if file_tree.contains_file(&metadata.name) {
-            return Err(FileServiceError::FileAlreadyExists);
-        }
+        let hash = file_tree
+            .get_leaf_hash_by_index(index)
+            .ok_or(FileServiceError::FileNotFound)?;
 
         self.file_storage
-            .insert_file_content(id, &metadata.name, &content)
+            .get_encoded_blob_content(hash)
             .await
-            .map_err(|e| FileServiceError::StorageError(e.to_string()))?;
+            .map_err(|e| {
+                error!("Failed to get encoded file content: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })
+    }
 
-        let hash = Hash32::hash(&content);
-        file_tree.add(metadata.index, &metadata.name, &hash);
+    async fn get_proof(
+        &self,
+        owner: &str,
+        id: Uuid,
+        version: Option<u32>,
+        index: usize,
+    ) -> Result<Proof, FileServiceError> {
+        let file_tree = self.get_file_tree_version(owner, id, version).await?;
 
-        self.file_repository
-            .update(file_tree.clone().into())
-            .await
-            .map_err(|e| FileServiceError::StorageError(e.to_string()))?;
+        // Trees of uploads still in progress change with every file, only completed ones are
+        // worth caching.
+        let proof = match file_tree.root() {
+            Some(root) => self
+                .tree_cache
+                .get_or_build(id, root, || file_tree.leafs())
+                .proof(index),
+            None => CustomMerkleTree::new(file_tree.leafs()).proof(index),
+        };
 
-        Ok(hex::encode(hash))
+        Ok(proof)
     }
 
-    pub async fn complete(&self, id: Uuid) -> Result<String, FileServiceError> {
-        println!("Completing file tree with id: {}", id);
-        let mut file_tree = self.get_file_tree(id).await?;
-
-        let tree = CustomMerkleTree::new(file_tree.leafs());
-        let root_hash = tree.root();
-        file_tree.complete(root_hash);
+    async fn get_file_index(
+        &self,
+        owner: &str,
+        id: Uuid,
+        version: Option<u32>,
+        name: &str,
+    ) -> Result<usize, FileServiceError> {
+        self.get_file_tree_version(owner, id, version)
+            .await?
+            .get_index_by_file_name(name)
+            .ok_or(FileServiceError::FileNotFound)
+    }
 
-        self.file_repository
-            .update(file_tree.into())
-            .await
-            .map_err(|e| FileServiceError::StorageError(e.to_string()))?;
+    async fn list_files(
+        &self,
+        owner: &str,
+        id: Uuid,
+        version: Option<u32>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<FilePage, FileServiceError> {
+        let files = self
+            .get_file_tree_version(owner, id, version)
+            .await?
+            .files();
+        let total = files.len();
 
-        Ok(root_hash.to_hex())
+        Ok(FilePage {
+            files: files.into_iter().skip(offset).take(limit).collect(),
+            total,
+        })
     }
-}
 
-#[async_trait]
-impl FileService for FileServiceImpl {
-    async fn get_file_content(
+    async fn get_archive(
         &self,
+        owner: &str,
         id: Uuid,
-        index: usize,
-    ) -> Result<FileContent, FileServiceError> {
-        let file_tree = self.get_file_tree(id).await?;
+        version: Option<u32>,
+        format: ArchiveFormat,
+    ) -> Result<FileStream, FileServiceError> {
+        let file_tree = self.get_file_tree_version(owner, id, version).await?;
+        let root = file_tree
+            .root()
+            .ok_or(FileServiceError::UploadNotCompleted)?;
+        let tree = self.tree_cache.get_or_build(id, root, || file_tree.leafs());
 
-        let file_name = file_tree
-            .get_file_name_by_index(index)
-            .ok_or(FileServiceError::FileNotFound)?;
+        let files = file_tree.files();
+        let manifest = ArchiveManifest {
+            id,
+            version: file_tree.version(),
+            root: root.to_hex(),
+            files: files
+                .iter()
+                .map(|file| ArchiveManifestEntry {
+                    index: file.index,
+                    name: file.name.clone(),
+                    path: archive_path(&file.name),
+                    leaf_hash: file.hash.to_hex(),
+                    proof: tree.proof(file.index),
+                })
+                .collect(),
+        };
+        let manifest = serde_json::to_vec_pretty(&manifest)
+            .map_err(|e| FileServiceError::StorageError(e.to_string()))?;
 
-        let content = self
-            .file_storage
-            .get_file_content(id, &file_name)
+        let files = files
+            .into_iter()
+            .map(|file| ArchiveFile {
+                path: archive_path(&file.name),
+                hash: file.hash,
+                modified: file.uploaded_at,
+            })
+            .collect();
+
+        Ok(archive_stream(
+            format,
+            manifest.into(),
+            files,
+            Arc::clone(&self.file_storage),
+        ))
+    }
+
+    async fn list_uploads(
+        &self,
+        owner: &str,
+        filter: UploadFilter,
+        cursor: Option<UploadCursor>,
+        limit: usize,
+    ) -> Result<UploadPage, FileServiceError> {
+        // One extra row tells whether there is a next page without counting.
+        let mut rows = self
+            .file_repository
+            .list(owner, &filter, cursor, limit + 1)
             .await
             .map_err(|e| {
-                error!("Failed to insert file tree: {}", e);
+                error!("Failed to list file trees: {}", e);
                 FileServiceError::StorageError(e.to_string())
-            })?
-            .ok_or(FileServiceError::FileIndexNotFound)?;
-
-        Ok(content)
-    }
+            })?;
 
-    async fn get_proof(&self, id: Uuid, index: usize) -> Result<Proof, FileServiceError> {
-        let file_tree = self.get_file_tree(id).await?;
+        let has_more = rows.len() > limit;
+        rows.truncate(limit);
 
-        let tree = CustomMerkleTree::new(file_tree.leafs());
-        let proof = tree.proof(index);
+        let uploads: Vec<_> = rows
+            .into_iter()
+            .map(|row| FileMerkleTree::from(row).summary())
+            .collect();
+        let next_cursor = if has_more {
+            uploads.last().map(UploadCursor::from)
+        } else {
+            None
+        };
 
-        Ok(proof)
+        Ok(UploadPage {
+            uploads,
+            next_cursor,
+        })
     }
 
-    async fn initiate(&self) -> Result<Uuid, FileServiceError> {
-        let file_tree = FileMerkleTree::default();
+    async fn initiate(&self, owner: &str) -> Result<Uuid, FileServiceError> {
+        self.check_open_uploads(owner).await?;
+        let file_tree = FileMerkleTree::new(owner);
 
         println!(
             "Initiating new file tree upload with id: {}",
@@ -193,73 +524,832 @@ impl FileService for FileServiceImpl {
                 FileServiceError::StorageError(e.to_string())
             })?;
 
+        self.record_usage(owner, 0, 1).await;
+        self.notify(owner, WebhookEvent::Initiated, file_tree.id(), json!({}))
+            .await;
+
         Ok(file_tree.id())
     }
 
     async fn upload_file(
         &self,
+        owner: &str,
         id: Uuid,
         metadata: FileMetadata,
-        content: Bytes,
+        content: FileStream,
     ) -> Result<String, FileServiceError> {
-        let mut file_tree = self.get_file_tree(id).await?;
+        if metadata.has_reserved_name() {
+            return Err(FileServiceError::InvalidFileName);
+        }
 
-        if file_tree.contains_file(&metadata.name) {
+        let file_tree = self.get_active_file_tree(owner, id).await?;
+
+        // Storing a file at the index already holding its name replaces it.
+        if file_tree.holds_name_elsewhere(metadata.index, &metadata.name) {
             return Err(FileServiceError::FileAlreadyExists);
         }
 
+        self.check_files_per_upload(&file_tree, metadata.index)?;
+        let content = match self.allowance(file_tree.owner()).await? {
+            Some(allowance) => allowance.limit(content),
+            None => content,
+        };
+
+        // The content is hashed while the storage consumes it, so it is never held in memory.
+        let hasher = Arc::new(Mutex::new(Hash32Hasher::new()));
+        let size = Arc::new(AtomicU64::new(0));
+        let tracked_hasher = Arc::clone(&hasher);
+        let tracked_size = Arc::clone(&size);
+        let content: FileStream = Box::pin(content.inspect_ok(move |chunk| {
+            tracked_size.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+            tracked_hasher
+                .lock()
+                .expect("hasher lock poisoned")
+                .update(chunk)
+        }));
+
+        let content_type = resolve_content_type(&metadata);
+        let staging_name = staging_name();
+        let pending_write = self
+            .begin_write(PendingWriteRow {
+                id: Uuid::new_v4(),
+                upload_id: id,
+                index: metadata.index,
+                name: metadata.name.clone(),
+                content_type: content_type.clone(),
+                staging_name: Some(staging_name.clone()),
+                hash: None,
+                size: 0,
+                created_at: Utc::now(),
+            })
+            .await?;
+
         self.file_storage
-            .insert_file_content(id, &metadata.name, &content)
+            .insert_file_content(id, &staging_name, &content_type, None, content)
             .await
             .map_err(|e| {
-                error!("Failed to insert file tree: {}", e);
-                FileServiceError::StorageError(e.to_string())
+                error!("Failed to insert file content: {}", e);
+                storage_error(e)
             })?;
 
-        let hash = Hash32::hash(&content);
-        file_tree.add(metadata.index, &metadata.name, &hash);
+        let size = size.load(Ordering::Relaxed);
+        self.publish(
+            id,
+            UploadEvent::FileReceived {
+                index: metadata.index,
+                name: metadata.name.clone(),
+                size,
+            },
+        );
 
-        self.file_repository
-            .update(file_tree.clone().into())
+        let hash = hasher
+            .lock()
+            .expect("hasher lock poisoned")
+            .clone()
+            .finalize();
+        self.publish(
+            id,
+            UploadEvent::HashComputed {
+                index: metadata.index,
+                name: metadata.name.clone(),
+                hash,
+            },
+        );
+
+        // The blob is touched before the move so the collector leaves it alone while the
+        // reference is being added.
+        self.touch_blob(hash).await?;
+        self.file_storage
+            .move_to_blob(id, &staging_name, hash)
             .await
             .map_err(|e| {
-                error!("Failed to insert file tree: {}", e);
+                error!("Failed to move file content into blob: {}", e);
                 FileServiceError::StorageError(e.to_string())
             })?;
 
-        Ok(hex::encode(hash))
+        self.mark_stored(pending_write, hash, size).await?;
+
+        let encoded_hash = self
+            .add_file(file_tree, metadata, hash, size, content_type, pending_write)
+            .await?;
+
+        Ok(encoded_hash)
+    }
+
+    async fn upload_known_file(
+        &self,
+        owner: &str,
+        id: Uuid,
+        metadata: FileMetadata,
+        hash: Hash32,
+    ) -> Result<String, FileServiceError> {
+        if metadata.has_reserved_name() {
+            return Err(FileServiceError::InvalidFileName);
+        }
+
+        let file_tree = self.get_active_file_tree(owner, id).await?;
+
+        // Storing a file at the index already holding its name replaces it.
+        if file_tree.holds_name_elsewhere(metadata.index, &metadata.name) {
+            return Err(FileServiceError::FileAlreadyExists);
+        }
+
+        self.check_files_per_upload(&file_tree, metadata.index)?;
+
+        if !self.is_referenced_by(hash, owner).await? {
+            return Err(FileServiceError::BlobNotFound);
+        }
+
+        self.touch_blob(hash).await?;
+        let stored = self
+            .get_blob_metadata(hash)
+            .await?
+            .ok_or(FileServiceError::BlobNotFound)?;
+
+        if let Some(allowance) = self.allowance(file_tree.owner()).await?
+            && stored.size > allowance.remaining
+        {
+            return Err(FileServiceError::QuotaExceeded(
+                allowance.violation(stored.size),
+            ));
+        }
+
+        let content_type = resolve_content_type(&metadata);
+        let pending_write = self
+            .begin_write(PendingWriteRow {
+                id: Uuid::new_v4(),
+                upload_id: id,
+                index: metadata.index,
+                name: metadata.name.clone(),
+                content_type: content_type.clone(),
+                staging_name: None,
+                hash: Some(hash),
+                size: stored.size,
+                created_at: Utc::now(),
+            })
+            .await?;
+
+        let (index, name) = (metadata.index, metadata.name.clone());
+        let encoded_hash = self
+            .add_file(
+                file_tree,
+                metadata,
+                hash,
+                stored.size,
+                content_type,
+                pending_write,
+            )
+            .await?;
+
+        self.publish(
+            id,
+            UploadEvent::FileReceived {
+                index,
+                name: name.clone(),
+                size: stored.size,
+            },
+        );
+        self.publish(id, UploadEvent::HashComputed { index, name, hash });
+
+        Ok(encoded_hash)
+    }
+
+    async fn blob_exists(&self, owner: &str, hash: Hash32) -> Result<bool, FileServiceError> {
+        Ok(self.is_referenced_by(hash, owner).await?
+            && self.get_blob_metadata(hash).await?.is_some())
     }
 
-    async fn complete(&self, id: Uuid) -> Result<String, FileServiceError> {
+    async fn complete(&self, owner: &str, id: Uuid) -> Result<String, FileServiceError> {
         println!("Completing file tree with id: {}", id);
-        let mut file_tree = self.get_file_tree(id).await?;
+        let mut file_tree = self.get_file_tree(owner, id).await?;
 
-        let tree = CustomMerkleTree::new(file_tree.leafs());
+        let tree = Arc::new(CustomMerkleTree::new(file_tree.leafs()));
         let root_hash = tree.root();
+        let was_open = file_tree.state() == UploadState::Initiated;
         file_tree.complete(root_hash);
+        let owner = file_tree.owner().to_string();
+        let version = file_tree.version();
 
         self.file_repository
-            .update(file_tree.into())
+            .update(file_tree.clone().into())
             .await
             .map_err(|e| {
                 error!("Failed to insert file tree: {}", e);
                 FileServiceError::StorageError(e.to_string())
             })?;
 
+        // The latest version keeps changing once the next one is created, so every completed
+        // version is kept as it is now.
+        self.file_repository
+            .insert_version(file_tree.into())
+            .await
+            .map_err(|e| {
+                error!("Failed to insert file tree version: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })?;
+
+        if was_open {
+            self.record_usage(&owner, 0, -1).await;
+        }
+
+        // Proofs are usually requested right after completing, so the tree is kept around.
+        self.tree_cache.insert(id, tree);
+
+        // Appending again with the same root is a no-op, so a failure here is fixed by
+        // completing again.
+        if let Some(transparency_log) = &self.transparency_log {
+            transparency_log.append(id, &owner, root_hash).await?;
+        }
+
+        if was_open {
+            self.publish(
+                id,
+                UploadEvent::Completed {
+                    version,
+                    root: root_hash,
+                },
+            );
+            let data = json!({ "root": root_hash.to_hex(), "version": version });
+            self.notify(&owner, WebhookEvent::Completed, id, data).await;
+        }
+
         Ok(root_hash.to_hex())
     }
+
+    async fn create_version(
+        &self,
+        owner: &str,
+        id: Uuid,
+    ) -> Result<UploadSummary, FileServiceError> {
+        let file_tree = self.get_file_tree(owner, id).await?;
+        if file_tree.state() != UploadState::Completed {
+            return Err(FileServiceError::UploadNotCompleted);
+        }
+
+        self.check_open_uploads(file_tree.owner()).await?;
+
+        // Files of the new version still point at the blobs the previous one references, so no
+        // reference needs to be added for them.
+        let next = file_tree.next_version();
+        self.file_repository
+            .update(next.clone().into())
+            .await
+            .map_err(|e| {
+                error!("Failed to update file tree: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })?;
+
+        self.record_usage(next.owner(), 0, 1).await;
+
+        counter!("upload_versions_created_total").increment(1);
+        info!(%id, version = next.version(), "Created upload version");
+
+        Ok(next.summary())
+    }
+
+    // Contents are removed before the row so a failed deletion can simply be retried.
+    // Expired uploads can be deleted as well, which is why the row is read directly.
+    // Blobs are shared between uploads, dropping the references is enough for the collector to
+    // remove the ones nobody else uses.
+    async fn delete(&self, owner: &str, id: Uuid) -> Result<(), FileServiceError> {
+        // Files added meanwhile change the upload, deleting it then starts over so their
+        // references go as well.
+        let mut deleted = None;
+        for _ in 0..UPLOAD_CHANGE_ATTEMPTS {
+            let file_tree = self.get_owned_file_tree(owner, id).await?;
+
+            self.file_storage.delete_prefix(id).await.map_err(|e| {
+                error!("Failed to delete file contents: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })?;
+
+            self.blob_repository
+                .remove_references(id)
+                .await
+                .map_err(|e| {
+                    error!("Failed to remove blob references: {}", e);
+                    FileServiceError::StorageError(e.to_string())
+                })?;
+
+            self.upload_session_repository
+                .delete_by_upload(id)
+                .await
+                .map_err(|e| {
+                    error!("Failed to delete upload sessions: {}", e);
+                    FileServiceError::StorageError(e.to_string())
+                })?;
+
+            let dropped = self
+                .file_repository
+                .delete_if_unchanged(id, file_tree.updated_at())
+                .await
+                .map_err(|e| {
+                    error!("Failed to delete file tree: {}", e);
+                    FileServiceError::StorageError(e.to_string())
+                })?;
+            if dropped {
+                deleted = Some(file_tree);
+                break;
+            }
+        }
+        let Some(file_tree) = deleted else {
+            warn!(%id, "Upload kept changing while deleting it");
+            return Err(FileServiceError::UploadConflict);
+        };
+
+        // Expired uploads were already taken off the usage of their owner.
+        let (stored_bytes, open_uploads) = match file_tree.state() {
+            UploadState::Initiated => (file_tree.summary().total_size, 1),
+            UploadState::Completed => (file_tree.summary().total_size, 0),
+            UploadState::Expired => (0, 0),
+        };
+        self.record_usage(file_tree.owner(), -(stored_bytes as i64), -open_uploads)
+            .await;
+
+        if file_tree.state() == UploadState::Initiated {
+            let data = json!({ "version": file_tree.version() });
+            self.notify(file_tree.owner(), WebhookEvent::Aborted, id, data)
+                .await;
+        }
+
+        self.publish(id, UploadEvent::Deleted);
+
+        Ok(())
+    }
+
+    async fn watch(&self, owner: &str, id: Uuid) -> Result<UploadEventStream, FileServiceError> {
+        // Subscribing before reading the upload, so nothing happening in between is missed.
+        let events = self.upload_events.subscribe(id);
+        let file_tree = self.get_file_tree(owner, id).await?;
+
+        if let Some(root) = file_tree.root()
+            && file_tree.state() == UploadState::Completed
+        {
+            let completed = UploadEvent::Completed {
+                version: file_tree.version(),
+                root,
+            };
+            return Ok(Box::pin(stream::once(async move { completed })));
+        }
+
+        Ok(events)
+    }
+
+    async fn get_usage(&self, owner: &str) -> Result<Usage, FileServiceError> {
+        let Some(quotas) = &self.quotas else {
+            return Ok(Usage {
+                stored_bytes: 0,
+                open_uploads: 0,
+                limits: Default::default(),
+            });
+        };
+
+        quotas.usage(owner).await.map_err(|e| {
+            error!("Failed to get usage: {}", e);
+            FileServiceError::StorageError(e.to_string())
+        })
+    }
+
+    async fn list_webhook_deliveries(
+        &self,
+        owner: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<WebhookDeliveryRow>, FileServiceError> {
+        let Some(webhooks) = &self.webhooks else {
+            return Ok(vec![]);
+        };
+
+        webhooks
+            .deliveries(owner, offset, limit)
+            .await
+            .map_err(|e| {
+                error!("Failed to list webhook deliveries: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })
+    }
+
+    async fn list_audit_entries(
+        &self,
+        owner: &str,
+        filter: AuditFilter,
+        after: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<AuditEntryRow>, FileServiceError> {
+        let Some(audit_repository) = &self.audit_repository else {
+            return Ok(vec![]);
+        };
+
+        audit_repository
+            .list(owner, &filter, after, limit)
+            .await
+            .map_err(|e| {
+                error!("Failed to list audit entries: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })
+    }
 }
 
 impl FileServiceImpl {
-    async fn get_file_tree(&self, id: Uuid) -> Result<FileMerkleTree, FileServiceError> {
-        let file_tree: FileMerkleTree = self
-            .file_repository
+    /// Uploads of other clients are not found, rather than forbidden, so their ids are not
+    /// disclosed either.
+    async fn get_owned_file_tree(
+        &self,
+        owner: &str,
+        id: Uuid,
+    ) -> Result<FileMerkleTree, FileServiceError> {
+        self.file_repository
             .get(id)
             .await
             .map_err(|e| FileServiceError::StorageError(e.to_string()))?
-            .ok_or(FileServiceError::FileNotFound)?
-            .into();
+            .map(FileMerkleTree::from)
+            .filter(|file_tree| file_tree.owner() == owner)
+            .ok_or(FileServiceError::FileNotFound)
+    }
+
+    async fn get_file_tree(
+        &self,
+        owner: &str,
+        id: Uuid,
+    ) -> Result<FileMerkleTree, FileServiceError> {
+        let file_tree = self.get_owned_file_tree(owner, id).await?;
+
+        if file_tree.state() == UploadState::Expired {
+            return Err(FileServiceError::UploadExpired);
+        }
+
+        Ok(file_tree)
+    }
+
+    /// Older versions are snapshots, the latest one is read as it is, completed or not.
+    async fn get_file_tree_version(
+        &self,
+        owner: &str,
+        id: Uuid,
+        version: Option<u32>,
+    ) -> Result<FileMerkleTree, FileServiceError> {
+        let file_tree = self.get_file_tree(owner, id).await?;
+
+        match version {
+            None => Ok(file_tree),
+            Some(version) if version == file_tree.version() => Ok(file_tree),
+            Some(version) => self
+                .file_repository
+                .get_version(id, version)
+                .await
+                .map_err(|e| FileServiceError::StorageError(e.to_string()))?
+                .map(FileMerkleTree::from)
+                .ok_or(FileServiceError::VersionNotFound),
+        }
+    }
+
+    /// Completed versions never change, files can only be added to a new one.
+    async fn get_active_file_tree(
+        &self,
+        owner: &str,
+        id: Uuid,
+    ) -> Result<FileMerkleTree, FileServiceError> {
+        let file_tree = self.get_file_tree(owner, id).await?;
+
+        if file_tree.state() == UploadState::Completed {
+            return Err(FileServiceError::UploadAlreadyCompleted);
+        }
 
         Ok(file_tree)
     }
+
+    async fn get_blob_metadata(
+        &self,
+        hash: Hash32,
+    ) -> Result<Option<StoredFileMetadata>, FileServiceError> {
+        self.file_storage
+            .get_blob_metadata(hash)
+            .await
+            .map_err(|e| {
+                error!("Failed to get blob metadata: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })
+    }
+
+    async fn is_referenced_by(&self, hash: Hash32, owner: &str) -> Result<bool, FileServiceError> {
+        self.blob_repository
+            .is_referenced_by(hash, owner)
+            .await
+            .map_err(|e| {
+                error!("Failed to get blob references: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })
+    }
+
+    async fn touch_blob(&self, hash: Hash32) -> Result<(), FileServiceError> {
+        self.blob_repository.touch(hash).await.map_err(|e| {
+            error!("Failed to touch blob: {}", e);
+            FileServiceError::StorageError(e.to_string())
+        })
+    }
+
+    // The write is finished once the file is added. Files the upload cannot take are abandoned
+    // right away, storage failures leave the write to the recovery, which can tell whether the
+    // file made it.
+    async fn add_file(
+        &self,
+        file_tree: FileMerkleTree,
+        metadata: FileMetadata,
+        hash: Hash32,
+        size: u64,
+        content_type: String,
+        pending_write: Option<Uuid>,
+    ) -> Result<String, FileServiceError> {
+        let (id, name) = (file_tree.id(), metadata.name.clone());
+        self.blob_repository
+            .add_reference(BlobReferenceRow {
+                hash,
+                upload_id: id,
+                owner: file_tree.owner().to_string(),
+                name: name.clone(),
+                content_type: content_type.clone(),
+                created_at: Utc::now(),
+            })
+            .await
+            .map_err(|e| {
+                error!("Failed to add blob reference: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })?;
+
+        match self
+            .store_file(file_tree, metadata, hash, size, &content_type)
+            .await
+        {
+            Ok(replaced) => {
+                self.end_write(pending_write).await;
+                if let Some((replaced_name, replaced_hash)) = replaced {
+                    self.drop_replaced_reference(id, &replaced_name, replaced_hash)
+                        .await;
+                }
+                Ok(hex::encode(hash))
+            }
+            Err(FileServiceError::StorageError(e)) => Err(FileServiceError::StorageError(e)),
+            Err(e) => {
+                self.abandon_write(pending_write, id, &name, hash).await;
+                Err(e)
+            }
+        }
+    }
+
+    // The upload was read before the file was received, it may have changed since, expired by
+    // the reaper or deleted among others. It is read again whenever it did, so nothing written
+    // meanwhile is overwritten and nothing gone is brought back. Returns the name and hash of the
+    // file replaced at the index, if any other.
+    async fn store_file(
+        &self,
+        mut file_tree: FileMerkleTree,
+        metadata: FileMetadata,
+        hash: Hash32,
+        size: u64,
+        content_type: &str,
+    ) -> Result<Option<(String, Hash32)>, FileServiceError> {
+        let owner = file_tree.owner().to_string();
+        for attempt in 0..UPLOAD_CHANGE_ATTEMPTS {
+            if attempt > 0 {
+                file_tree = self.get_active_file_tree(&owner, file_tree.id()).await?;
+                if file_tree.holds_name_elsewhere(metadata.index, &metadata.name) {
+                    return Err(FileServiceError::FileAlreadyExists);
+                }
+                self.check_files_per_upload(&file_tree, metadata.index)?;
+            }
+
+            // Files can replace the one previously stored at the same index.
+            let replaced = file_tree
+                .get_file_name_by_index(metadata.index)
+                .filter(|name| !name.is_empty())
+                .zip(file_tree.get_leaf_hash_by_index(metadata.index))
+                .filter(|(name, replaced_hash)| *name != metadata.name || *replaced_hash != hash);
+            let read_at = file_tree.updated_at();
+            let mut updated = file_tree.clone();
+            let before = updated.summary().total_size;
+            updated.add(metadata.index, &metadata.name, &hash, size, content_type);
+            let after = updated.summary().total_size;
+
+            let stored = self
+                .file_repository
+                .update_if_unchanged(updated.into(), read_at)
+                .await
+                .map_err(|e| {
+                    error!("Failed to insert file tree: {}", e);
+                    FileServiceError::StorageError(e.to_string())
+                })?;
+
+            if stored {
+                self.record_usage(&owner, after as i64 - before as i64, 0)
+                    .await;
+                return Ok(replaced);
+            }
+        }
+
+        warn!(id = %file_tree.id(), "Upload kept changing while adding a file");
+        Err(FileServiceError::UploadConflict)
+    }
+
+    // Same as a rollback of the recovery, the reference goes along with the journal entry. Both
+    // are left to the recovery on failure.
+    async fn abandon_write(&self, pending_write: Option<Uuid>, id: Uuid, name: &str, hash: Hash32) {
+        match self.drop_reference_unless_held(id, name, hash).await {
+            Ok(()) => self.end_write(pending_write).await,
+            Err(e) => {
+                counter!("pending_write_failures_total").increment(1);
+                warn!(%id, "Failed to roll back abandoned write: {}", e);
+            }
+        }
+    }
+
+    // The file is replaced already, failing to drop its reference only keeps its blob around.
+    async fn drop_replaced_reference(&self, id: Uuid, name: &str, hash: Hash32) {
+        if let Err(e) = self.drop_reference_unless_held(id, name, hash).await {
+            counter!("blob_reference_failures_total").increment(1);
+            warn!(%id, name, "Failed to remove reference of replaced file: {}", e);
+        }
+    }
+
+    // References are shared by every version of the upload, so they stay while the upload or
+    // any of its completed versions holds the file.
+    async fn drop_reference_unless_held(
+        &self,
+        id: Uuid,
+        name: &str,
+        hash: Hash32,
+    ) -> anyhow::Result<()> {
+        let held = self
+            .file_repository
+            .get(id)
+            .await?
+            .map(FileMerkleTree::from)
+            .is_some_and(|file_tree| holds(&file_tree, name, hash));
+        if !held && !held_by_versions(self.file_repository.as_ref(), id, name, hash).await? {
+            self.blob_repository
+                .remove_reference(id, name, hash)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Journals the write, returning its id when writes are journaled.
+    async fn begin_write(&self, row: PendingWriteRow) -> Result<Option<Uuid>, FileServiceError> {
+        let Some(pending_write_repository) = &self.pending_write_repository else {
+            return Ok(None);
+        };
+
+        let id = row.id;
+        pending_write_repository.insert(row).await.map_err(|e| {
+            error!("Failed to journal pending write: {}", e);
+            FileServiceError::StorageError(e.to_string())
+        })?;
+
+        Ok(Some(id))
+    }
+
+    // Failing here leaves a write recovery cannot tell from one that never stored its contents,
+    // so the blob is left to the collector and the upload fails.
+    async fn mark_stored(
+        &self,
+        pending_write: Option<Uuid>,
+        hash: Hash32,
+        size: u64,
+    ) -> Result<(), FileServiceError> {
+        let (Some(pending_write_repository), Some(id)) =
+            (&self.pending_write_repository, pending_write)
+        else {
+            return Ok(());
+        };
+
+        pending_write_repository
+            .mark_stored(id, hash, size)
+            .await
+            .map_err(|e| {
+                error!("Failed to mark pending write as stored: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })
+    }
+
+    // The write is done at this point, recovery drops the entry if it cannot be removed now.
+    async fn end_write(&self, pending_write: Option<Uuid>) {
+        let (Some(pending_write_repository), Some(id)) =
+            (&self.pending_write_repository, pending_write)
+        else {
+            return;
+        };
+
+        if let Err(e) = pending_write_repository.delete(id).await {
+            counter!("pending_write_failures_total").increment(1);
+            warn!(%id, "Failed to remove finished pending write: {}", e);
+        }
+    }
+
+    async fn check_open_uploads(&self, owner: &str) -> Result<(), FileServiceError> {
+        let Some(quotas) = &self.quotas else {
+            return Ok(());
+        };
+
+        match quotas.check_open_uploads(owner).await.map_err(|e| {
+            error!("Failed to get usage: {}", e);
+            FileServiceError::StorageError(e.to_string())
+        })? {
+            Some(violation) => Err(FileServiceError::QuotaExceeded(violation)),
+            None => Ok(()),
+        }
+    }
+
+    // Replacing the file stored at `index` does not add one.
+    fn check_files_per_upload(
+        &self,
+        file_tree: &FileMerkleTree,
+        index: usize,
+    ) -> Result<(), FileServiceError> {
+        let Some(quotas) = &self.quotas else {
+            return Ok(());
+        };
+        if file_tree
+            .get_file_name_by_index(index)
+            .is_some_and(|name| !name.is_empty())
+        {
+            return Ok(());
+        }
+
+        let files = file_tree.summary().file_count as u64;
+        match quotas.check_files_per_upload(file_tree.owner(), files) {
+            Some(violation) => Err(FileServiceError::QuotaExceeded(violation)),
+            None => Ok(()),
+        }
+    }
+
+    async fn allowance(&self, owner: &str) -> Result<Option<Allowance>, FileServiceError> {
+        let Some(quotas) = &self.quotas else {
+            return Ok(None);
+        };
+
+        quotas.allowance(owner).await.map_err(|e| {
+            error!("Failed to get usage: {}", e);
+            FileServiceError::StorageError(e.to_string())
+        })
+    }
+
+    // Usage is recorded once the change it describes is done, failing the request at that point
+    // would only make clients retry something that already happened.
+    async fn record_usage(&self, owner: &str, stored_bytes: i64, open_uploads: i64) {
+        if let Some(quotas) = &self.quotas
+            && let Err(e) = quotas.record(owner, stored_bytes, open_uploads).await
+        {
+            counter!("usage_record_failures_total").increment(1);
+            warn!(owner, "Failed to record usage: {}", e);
+        }
+    }
+
+    fn publish(&self, id: Uuid, event: UploadEvent) {
+        self.upload_events.publish(id, event);
+    }
+
+    async fn notify(&self, owner: &str, event: WebhookEvent, id: Uuid, data: serde_json::Value) {
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.notify(owner, event, id, data).await;
+        }
+    }
+}
+
+// Files go into their own directory so that no name can clash with the manifest.
+fn archive_path(name: &str) -> String {
+    format!("files/{name}")
+}
+
+// Most clients send `application/octet-stream` no matter what they upload, in which case the
+// file name is a better hint.
+fn resolve_content_type(metadata: &FileMetadata) -> String {
+    metadata
+        .content_type
+        .clone()
+        .filter(|content_type| content_type != mime::APPLICATION_OCTET_STREAM.as_ref())
+        .unwrap_or_else(|| {
+            mime_guess::from_path(&metadata.name)
+                .first_or_octet_stream()
+                .to_string()
+        })
+}
+
+// Body limits and quotas are enforced while streaming, so exceeding them surfaces as an IO error
+// somewhere down the storage error chain. Quotas leave the violation inside.
+pub(crate) fn storage_error(e: anyhow::Error) -> FileServiceError {
+    let too_large = e
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .find(|e| e.kind() == io::ErrorKind::FileTooLarge);
+
+    match too_large {
+        Some(e) => match e
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<QuotaViolation>())
+        {
+            Some(violation) => FileServiceError::QuotaExceeded(*violation),
+            None => FileServiceError::PayloadTooLarge,
+        },
+        None => FileServiceError::StorageError(e.to_string()),
+    }
 }
@@ -0,0 +1,210 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use config::Config;
+use metrics::counter;
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument, warn};
+
+use crate::{
+    models::{FileMerkleTree, UploadCursor, UploadFilter, UploadState},
+    repositories::{
+        BlobReferenceRow, BlobRepository, FileMerkleTreeRow, FileRepository, FileStorage,
+    },
+    services::Quotas,
+};
+
+/// Uploads stored before they belonged to a client are handed to `owner`. Nothing is migrated
+/// while it is missing, those uploads stay out of reach of every client.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LegacyMigrationConfig {
+    pub owner: Option<String>,
+    #[serde(default = "LegacyMigrationConfig::default_batch_size")]
+    pub batch_size: usize,
+}
+
+impl Default for LegacyMigrationConfig {
+    fn default() -> Self {
+        Self {
+            owner: None,
+            batch_size: Self::default_batch_size(),
+        }
+    }
+}
+
+impl LegacyMigrationConfig {
+    const CONFIG_PREFIX: &'static str = "LEGACY_MIGRATION";
+
+    pub fn load_from_env() -> anyhow::Result<Self> {
+        Config::builder()
+            .add_source(config::Environment::with_prefix(Self::CONFIG_PREFIX).separator("__"))
+            .build()?
+            .try_deserialize::<LegacyMigrationConfig>()
+            .map_err(|e| anyhow::anyhow!("failed to load Legacy Migration Configuration: {}", e))
+    }
+
+    fn default_batch_size() -> usize {
+        100
+    }
+}
+
+/// What a migration pass did with the legacy uploads it found.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MigrationReport {
+    pub migrated: usize,
+    pub failed: usize,
+}
+
+/// Brings uploads stored before deduplication and ownership in line on startup. Their rows have
+/// no owner and their contents still live under `{id}/{name}`: contents are moved into their
+/// blob and referenced, then the upload is handed to the configured owner.
+pub struct LegacyMigration {
+    file_repository: Arc<dyn FileRepository>,
+    file_storage: Arc<dyn FileStorage>,
+    blob_repository: Arc<dyn BlobRepository>,
+    config: LegacyMigrationConfig,
+    quotas: Option<Arc<Quotas>>,
+}
+
+impl LegacyMigration {
+    pub fn new(
+        file_repository: Arc<dyn FileRepository>,
+        file_storage: Arc<dyn FileStorage>,
+        blob_repository: Arc<dyn BlobRepository>,
+        config: LegacyMigrationConfig,
+    ) -> Self {
+        Self {
+            file_repository,
+            file_storage,
+            blob_repository,
+            config,
+            quotas: None,
+        }
+    }
+
+    /// Migrated uploads are added to the usage of their new owner.
+    pub fn with_quotas(mut self, quotas: Arc<Quotas>) -> Self {
+        self.quotas = Some(quotas);
+        self
+    }
+
+    /// Migrates once and stops, uploads failing to migrate are retried on the next start.
+    pub async fn run(self, shutdown: CancellationToken) {
+        tokio::select! {
+            _ = shutdown.cancelled() => {}
+            result = self.migrate() => match result {
+                Ok(report) => info!(?report, "Legacy migration finished"),
+                Err(e) => {
+                    counter!("legacy_migration_failures_total").increment(1);
+                    error!("Legacy migration failed: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Goes through every upload without owner, batch by batch.
+    #[instrument(skip(self))]
+    pub async fn migrate(&self) -> anyhow::Result<MigrationReport> {
+        let mut report = MigrationReport::default();
+        let mut after: Option<UploadCursor> = None;
+
+        loop {
+            let legacy = self
+                .file_repository
+                .list("", &UploadFilter::default(), after, self.config.batch_size)
+                .await?;
+
+            let Some(last) = legacy.last() else {
+                return Ok(report);
+            };
+            after = Some(UploadCursor {
+                created_at: last.created_at,
+                id: last.id,
+            });
+
+            let Some(owner) = &self.config.owner else {
+                warn!("Uploads without owner found, set LEGACY_MIGRATION__OWNER to migrate them");
+                return Ok(report);
+            };
+
+            for row in legacy {
+                let id = row.id;
+
+                match self.migrate_upload(row, owner).await {
+                    Ok(()) => {
+                        counter!("legacy_uploads_migrated_total").increment(1);
+                        info!(%id, owner, "Legacy upload migrated");
+                        report.migrated += 1;
+                    }
+                    Err(e) => {
+                        counter!("legacy_migration_failures_total").increment(1);
+                        warn!(%id, "Failed to migrate legacy upload: {}", e);
+                        report.failed += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    // Every step can be repeated, so an upload failing halfway is simply migrated again. The
+    // owner is assigned last, which is what takes the upload out of the next listings.
+    async fn migrate_upload(&self, mut row: FileMerkleTreeRow, owner: &str) -> anyhow::Result<()> {
+        let id = row.id;
+        let file_tree = FileMerkleTree::from(row.clone());
+
+        for file in file_tree.files() {
+            let stored = self.file_storage.get_file_metadata(id, &file.name).await?;
+            if stored.is_some() {
+                self.blob_repository.touch(file.hash).await?;
+                self.file_storage
+                    .move_to_blob(id, &file.name, file.hash)
+                    .await?;
+            }
+
+            // Contents moved by a previous attempt may still lack their reference.
+            if self
+                .blob_repository
+                .get_reference(id, &file.name, file.hash)
+                .await?
+                .is_none()
+            {
+                self.blob_repository
+                    .add_reference(BlobReferenceRow {
+                        hash: file.hash,
+                        upload_id: id,
+                        owner: owner.to_string(),
+                        name: file.name.clone(),
+                        content_type: stored
+                            .map_or(file.content_type, |stored| stored.content_type),
+                        created_at: Utc::now(),
+                    })
+                    .await?;
+            }
+        }
+
+        row.owner = owner.to_string();
+        self.file_repository.update(row).await?;
+
+        // Same accounting as deleting or expiring the upload, which later takes it off again.
+        let (stored_bytes, open_uploads) = match file_tree.state() {
+            UploadState::Initiated => (file_tree.summary().total_size, 1),
+            UploadState::Completed => (file_tree.summary().total_size, 0),
+            UploadState::Expired => (0, 0),
+        };
+        self.record_usage(owner, stored_bytes as i64, open_uploads)
+            .await;
+
+        Ok(())
+    }
+
+    // Same as in `FileServiceImpl`, the upload is migrated already so failing would not help.
+    async fn record_usage(&self, owner: &str, stored_bytes: i64, open_uploads: i64) {
+        if let Some(quotas) = &self.quotas
+            && let Err(e) = quotas.record(owner, stored_bytes, open_uploads).await
+        {
+            counter!("usage_record_failures_total").increment(1);
+            warn!(owner, "Failed to record usage: {}", e);
+        }
+    }
+}
@@ -1,17 +1,200 @@
+mod admin_service;
+mod archive;
+mod blob_collector;
 mod file_service;
+mod legacy_migration;
+mod orphan_collector;
+mod quotas;
+mod scrubber;
+mod transparency_log;
+mod tree_cache;
+mod upload_events;
+mod upload_reaper;
+mod upload_session_service;
+mod webhooks;
+mod write_recovery;
+pub use admin_service::{AdminService, AdminServiceImpl};
+pub use blob_collector::{BlobCollector, BlobGcConfig};
 pub use file_service::{FileService, FileServiceError, FileServiceImpl};
+pub use legacy_migration::{LegacyMigration, LegacyMigrationConfig, MigrationReport};
+pub use orphan_collector::{OrphanCollector, OrphanGcConfig};
+pub use quotas::{Allowance, QuotaConfig, Quotas};
+pub use scrubber::{Scrubber, ScrubberConfig};
+pub use transparency_log::{TransparencyLogService, TransparencyLogServiceImpl};
+pub use tree_cache::{TreeCache, TreeCacheConfig};
+pub use upload_events::{UploadEventStream, UploadEvents};
+pub use upload_reaper::{ExpiryConfig, UploadReaper};
+pub use upload_session_service::{UploadSessionService, UploadSessionServiceImpl};
+pub use webhooks::{WebhookConfig, WebhookDispatcher, WebhookEndpoint, Webhooks};
+pub use write_recovery::{RecoveryReport, WriteRecovery, WriteRecoveryConfig};
 
-use crate::repositories::{FileRepository, FileStorage};
+use crate::{
+    infrastructure::{ServerSigner, load_clients},
+    repositories::{
+        BlobRepository, FileRepository, FileStorage, PendingWriteRepository, Repositories,
+        ScrubRepository, UploadSessionRepository, UsageRepository, WebhookRepository,
+    },
+};
 use std::sync::Arc;
 
+pub struct Services {
+    pub file_service: Arc<dyn FileService>,
+    pub upload_session_service: Arc<dyn UploadSessionService>,
+    pub admin_service: Arc<dyn AdminService>,
+    pub transparency_log_service: Arc<dyn TransparencyLogService>,
+}
+
 // Template function to initialize services.
 // In a more complex project, this should return a IoC container instead with
 // all services registered.
 pub async fn init_services(
+    repositories: &Repositories,
+    signer: Arc<ServerSigner>,
+    quotas: Arc<Quotas>,
+    webhooks: Arc<Webhooks>,
+    upload_events: UploadEvents,
+) -> anyhow::Result<Services> {
+    let tree_cache_config = TreeCacheConfig::load_from_env()?;
+
+    let transparency_log_service = Arc::new(TransparencyLogServiceImpl::new(
+        Arc::clone(&repositories.transparency_log_repository),
+        signer,
+    )) as Arc<dyn TransparencyLogService>;
+
+    let file_service = Arc::new(
+        FileServiceImpl::new(
+            Arc::clone(&repositories.file_repository),
+            Arc::clone(&repositories.file_storage),
+            Arc::clone(&repositories.upload_session_repository),
+            Arc::clone(&repositories.blob_repository),
+        )
+        .with_tree_cache(TreeCache::new(tree_cache_config.capacity))
+        .with_transparency_log(Arc::clone(&transparency_log_service))
+        .with_quotas(Arc::clone(&quotas))
+        .with_pending_writes(Arc::clone(&repositories.pending_write_repository))
+        .with_webhooks(webhooks)
+        .with_audit_log(Arc::clone(&repositories.audit_repository))
+        .with_upload_events(upload_events),
+    ) as Arc<dyn FileService>;
+
+    let upload_session_service = Arc::new(
+        UploadSessionServiceImpl::new(
+            Arc::clone(&file_service),
+            Arc::clone(&repositories.file_repository),
+            Arc::clone(&repositories.file_storage),
+            Arc::clone(&repositories.upload_session_repository),
+        )
+        .with_quotas(Arc::clone(&quotas)),
+    ) as Arc<dyn UploadSessionService>;
+
+    let orphan_collector = OrphanCollector::new(
+        Arc::clone(&repositories.file_repository),
+        Arc::clone(&repositories.file_storage),
+        Arc::clone(&repositories.upload_session_repository),
+        OrphanGcConfig::load_from_env()?,
+    );
+
+    let admin_service = Arc::new(AdminServiceImpl::new(
+        Arc::clone(&repositories.scrub_repository),
+        Arc::new(orphan_collector),
+    )) as Arc<dyn AdminService>;
+
+    Ok(Services {
+        file_service,
+        upload_session_service,
+        admin_service,
+        transparency_log_service,
+    })
+}
+
+pub fn init_quotas(usage_repository: Arc<dyn UsageRepository>) -> anyhow::Result<Quotas> {
+    let config = QuotaConfig::load_from_env()?;
+    Ok(Quotas::new(config, usage_repository))
+}
+
+pub fn init_webhooks(webhook_repository: Arc<dyn WebhookRepository>) -> anyhow::Result<Webhooks> {
+    let config = WebhookConfig::load_from_env()?;
+    Ok(Webhooks::new(config, webhook_repository))
+}
+
+pub fn init_upload_reaper(
+    file_repository: Arc<dyn FileRepository>,
+    file_storage: Arc<dyn FileStorage>,
+    upload_session_repository: Arc<dyn UploadSessionRepository>,
+    blob_repository: Arc<dyn BlobRepository>,
+    quotas: Arc<Quotas>,
+    webhooks: Arc<Webhooks>,
+    upload_events: UploadEvents,
+) -> anyhow::Result<UploadReaper> {
+    let config = ExpiryConfig::load_from_env()?;
+    Ok(UploadReaper::new(
+        file_repository,
+        file_storage,
+        upload_session_repository,
+        blob_repository,
+        config,
+    )
+    .with_quotas(quotas)
+    .with_webhooks(webhooks)
+    .with_upload_events(upload_events))
+}
+
+pub fn init_blob_collector(
+    blob_repository: Arc<dyn BlobRepository>,
+    file_storage: Arc<dyn FileStorage>,
+) -> anyhow::Result<BlobCollector> {
+    let config = BlobGcConfig::load_from_env()?;
+    Ok(BlobCollector::new(blob_repository, file_storage, config))
+}
+
+pub fn init_scrubber(
+    file_repository: Arc<dyn FileRepository>,
+    file_storage: Arc<dyn FileStorage>,
+    scrub_repository: Arc<dyn ScrubRepository>,
+    webhooks: Arc<Webhooks>,
+) -> anyhow::Result<Scrubber> {
+    let config = ScrubberConfig::load_from_env()?;
+    Ok(
+        Scrubber::new(file_repository, file_storage, scrub_repository, config)
+            .with_webhooks(webhooks),
+    )
+}
+
+// Deliveries are signed with the same secrets requests are authenticated with.
+pub fn init_webhook_dispatcher(
+    webhook_repository: Arc<dyn WebhookRepository>,
+) -> anyhow::Result<WebhookDispatcher> {
+    let config = WebhookConfig::load_from_env()?;
+    WebhookDispatcher::new(webhook_repository, config, load_clients())
+}
+
+pub fn init_write_recovery(
+    pending_write_repository: Arc<dyn PendingWriteRepository>,
+    file_repository: Arc<dyn FileRepository>,
+    file_storage: Arc<dyn FileStorage>,
+    blob_repository: Arc<dyn BlobRepository>,
+    quotas: Arc<Quotas>,
+) -> anyhow::Result<WriteRecovery> {
+    let config = WriteRecoveryConfig::load_from_env()?;
+    Ok(WriteRecovery::new(
+        pending_write_repository,
+        file_repository,
+        file_storage,
+        blob_repository,
+        config,
+    )
+    .with_quotas(quotas))
+}
+
+pub fn init_legacy_migration(
     file_repository: Arc<dyn FileRepository>,
     file_storage: Arc<dyn FileStorage>,
-) -> anyhow::Result<Arc<dyn FileService>> {
-    let file_service =
-        Arc::new(FileServiceImpl::new(file_repository, file_storage)) as Arc<dyn FileService>;
-    Ok(file_service)
+    blob_repository: Arc<dyn BlobRepository>,
+    quotas: Arc<Quotas>,
+) -> anyhow::Result<LegacyMigration> {
+    let config = LegacyMigrationConfig::load_from_env()?;
+    Ok(
+        LegacyMigration::new(file_repository, file_storage, blob_repository, config)
+            .with_quotas(quotas),
+    )
 }
@@ -0,0 +1,187 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use config::Config;
+use metrics::counter;
+use serde::Deserialize;
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    models::{OrphanObject, OrphanReport, UploadCursor, UploadState, chunk_session_id},
+    repositories::{
+        FileMerkleTreeRow, FileRepository, FileStorage, StoredObject, UploadSessionRepository,
+    },
+};
+
+const ONE_HOUR_IN_SECONDS: u64 = 60 * 60;
+
+/// Objects younger than `min_age_secs` are never orphans, they may belong to a write still
+/// running. It must be longer than the slowest upload takes to store its file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OrphanGcConfig {
+    #[serde(default = "OrphanGcConfig::default_min_age_secs")]
+    pub min_age_secs: u64,
+}
+
+impl Default for OrphanGcConfig {
+    fn default() -> Self {
+        Self {
+            min_age_secs: Self::default_min_age_secs(),
+        }
+    }
+}
+
+impl OrphanGcConfig {
+    const CONFIG_PREFIX: &'static str = "ORPHAN_GC";
+
+    pub fn load_from_env() -> anyhow::Result<Self> {
+        Config::builder()
+            .add_source(config::Environment::with_prefix(Self::CONFIG_PREFIX).separator("__"))
+            .build()?
+            .try_deserialize::<OrphanGcConfig>()
+            .map_err(|e| anyhow::anyhow!("failed to load Orphan GC Configuration: {}", e))
+    }
+
+    fn default_min_age_secs() -> u64 {
+        ONE_HOUR_IN_SECONDS
+    }
+}
+
+/// Cross-checks the objects stored under each upload against the upload, and reports or deletes
+/// the ones nothing refers to anymore: staging objects of writes that never made it to their
+/// blob, chunks of sessions that are gone, and anything left under expired uploads. Objects named
+/// after a file of the upload are kept. Blobs are left to the blob collector.
+pub struct OrphanCollector {
+    file_repository: Arc<dyn FileRepository>,
+    file_storage: Arc<dyn FileStorage>,
+    upload_session_repository: Arc<dyn UploadSessionRepository>,
+    config: OrphanGcConfig,
+}
+
+impl OrphanCollector {
+    pub fn new(
+        file_repository: Arc<dyn FileRepository>,
+        file_storage: Arc<dyn FileStorage>,
+        upload_session_repository: Arc<dyn UploadSessionRepository>,
+        config: OrphanGcConfig,
+    ) -> Self {
+        Self {
+            file_repository,
+            file_storage,
+            upload_session_repository,
+            config,
+        }
+    }
+
+    /// Checks up to `limit` uploads, oldest first, starting right after `after` when given.
+    /// Orphans are only reported when `dry_run` is set.
+    #[instrument(skip(self))]
+    pub async fn collect(
+        &self,
+        after: Option<UploadCursor>,
+        limit: usize,
+        dry_run: bool,
+    ) -> anyhow::Result<OrphanReport> {
+        let uploads = self.file_repository.list_all(after, limit).await?;
+        let mut report = OrphanReport::default();
+
+        for upload in &uploads {
+            for orphan in self.find_orphans(upload).await? {
+                counter!("orphans_found_total").increment(1);
+                if !dry_run {
+                    self.delete(&orphan, &mut report).await;
+                }
+                report.orphans.push(orphan);
+            }
+            report.uploads_checked += 1;
+        }
+
+        if uploads.len() == limit {
+            report.next_cursor = uploads.last().map(|upload| UploadCursor {
+                created_at: upload.created_at,
+                id: upload.id,
+            });
+        }
+
+        Ok(report)
+    }
+
+    async fn find_orphans(&self, upload: &FileMerkleTreeRow) -> anyhow::Result<Vec<OrphanObject>> {
+        let modified_before = Utc::now() - Duration::from_secs(self.config.min_age_secs);
+        // Sessions are looked up once per upload, they hold many chunks.
+        let mut live_sessions: HashMap<Uuid, bool> = HashMap::new();
+        let mut orphans = Vec::new();
+
+        for object in self.file_storage.list(upload.id).await? {
+            if object.last_modified > modified_before {
+                continue;
+            }
+
+            if upload.state != UploadState::Expired
+                && self
+                    .is_referenced(upload, &object, &mut live_sessions)
+                    .await?
+            {
+                continue;
+            }
+
+            orphans.push(OrphanObject {
+                upload_id: upload.id,
+                name: object.name,
+                size: object.size,
+                last_modified: object.last_modified,
+            });
+        }
+
+        Ok(orphans)
+    }
+
+    async fn is_referenced(
+        &self,
+        upload: &FileMerkleTreeRow,
+        object: &StoredObject,
+        live_sessions: &mut HashMap<Uuid, bool>,
+    ) -> anyhow::Result<bool> {
+        if upload.order.contains(&object.name) {
+            return Ok(true);
+        }
+
+        let Some(session_id) = chunk_session_id(&object.name) else {
+            return Ok(false);
+        };
+
+        if let Some(live) = live_sessions.get(&session_id) {
+            return Ok(*live);
+        }
+
+        let live = self
+            .upload_session_repository
+            .get(session_id)
+            .await?
+            .is_some_and(|session| session.upload_id == upload.id);
+        live_sessions.insert(session_id, live);
+
+        Ok(live)
+    }
+
+    // One failed deletion does not stop the others, it is reported and retried on the next run.
+    async fn delete(&self, orphan: &OrphanObject, report: &mut OrphanReport) {
+        match self
+            .file_storage
+            .delete_file_content(orphan.upload_id, &orphan.name)
+            .await
+        {
+            Ok(()) => {
+                counter!("orphans_deleted_total").increment(1);
+                info!(upload_id = %orphan.upload_id, name = %orphan.name, size = orphan.size, "Deleted orphan object");
+                report.deleted += 1;
+            }
+            Err(e) => {
+                counter!("orphan_gc_failures_total").increment(1);
+                warn!(upload_id = %orphan.upload_id, name = %orphan.name, "Failed to delete orphan object: {}", e);
+                report.failed += 1;
+            }
+        }
+    }
+}
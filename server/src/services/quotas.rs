@@ -0,0 +1,185 @@
+use std::{collections::HashMap, io, sync::Arc};
+
+use config::Config;
+use futures::{TryStreamExt, future};
+use serde::Deserialize;
+
+use crate::{
+    models::{Quota, QuotaLimits, QuotaViolation, Usage},
+    repositories::{FileStream, UsageRepository, UsageRow},
+};
+
+/// Limits applied to every client, and per client overrides keyed by API key. Overrides only
+/// replace the limits they set, e.g. `QUOTAS__DEFAULT__MAX_STORED_BYTES` for everyone and
+/// `QUOTAS__CLIENTS__client-1__MAX_FILE_SIZE_BYTES` for `client-1`. Keys are read lowercased.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct QuotaConfig {
+    #[serde(default)]
+    pub default: QuotaLimits,
+    #[serde(default)]
+    pub clients: HashMap<String, QuotaLimits>,
+}
+
+impl QuotaConfig {
+    const CONFIG_PREFIX: &'static str = "QUOTAS";
+
+    pub fn load_from_env() -> anyhow::Result<Self> {
+        Config::builder()
+            .add_source(
+                config::Environment::with_prefix(Self::CONFIG_PREFIX)
+                    .separator("__")
+                    .try_parsing(true),
+            )
+            .build()?
+            .try_deserialize::<QuotaConfig>()
+            .map_err(|e| anyhow::anyhow!("failed to load Quota Configuration: {}", e))
+    }
+}
+
+/// Room left for the contents of a single file, taken from whichever quota runs out first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Allowance {
+    pub quota: Quota,
+    pub limit: u64,
+    /// Usage of the quota before the file.
+    pub used: u64,
+    pub remaining: u64,
+}
+
+impl Allowance {
+    pub fn violation(&self, size: u64) -> QuotaViolation {
+        QuotaViolation {
+            quota: self.quota,
+            limit: self.limit,
+            used: self.used + size,
+        }
+    }
+
+    /// Fails the stream with a `FileTooLarge` IO error wrapping the violation as soon as it goes
+    /// past the allowance, like body limits do.
+    pub fn limit(self, content: FileStream) -> FileStream {
+        let mut received = 0u64;
+
+        Box::pin(content.and_then(move |chunk| {
+            received += chunk.len() as u64;
+
+            future::ready(if received > self.remaining {
+                Err(io::Error::new(
+                    io::ErrorKind::FileTooLarge,
+                    self.violation(received),
+                ))
+            } else {
+                Ok(chunk)
+            })
+        }))
+    }
+}
+
+/// Tracks what clients use and checks it against their limits. Checks and updates are not
+/// atomic, concurrent requests of the same client can go slightly past a limit.
+pub struct Quotas {
+    config: QuotaConfig,
+    usage_repository: Arc<dyn UsageRepository>,
+}
+
+impl Quotas {
+    pub fn new(config: QuotaConfig, usage_repository: Arc<dyn UsageRepository>) -> Self {
+        Self {
+            config,
+            usage_repository,
+        }
+    }
+
+    pub fn limits_for(&self, owner: &str) -> QuotaLimits {
+        self.config
+            .clients
+            .get(owner)
+            .copied()
+            .unwrap_or_default()
+            .or(self.config.default)
+    }
+
+    pub async fn usage(&self, owner: &str) -> anyhow::Result<Usage> {
+        let row = self.usage_repository.get(owner).await?;
+
+        // Rows are only ever added, a lost one can leave the sums slightly off.
+        Ok(Usage {
+            stored_bytes: row.stored_bytes.max(0) as u64,
+            open_uploads: row.open_uploads.max(0) as u64,
+            limits: self.limits_for(owner),
+        })
+    }
+
+    pub async fn check_open_uploads(&self, owner: &str) -> anyhow::Result<Option<QuotaViolation>> {
+        let Some(limit) = self.limits_for(owner).max_open_uploads else {
+            return Ok(None);
+        };
+
+        let used = self.usage(owner).await?.open_uploads + 1;
+        Ok((used > limit).then_some(QuotaViolation {
+            quota: Quota::OpenUploads,
+            limit,
+            used,
+        }))
+    }
+
+    /// `files` is how many files the upload has before adding one more.
+    pub fn check_files_per_upload(&self, owner: &str, files: u64) -> Option<QuotaViolation> {
+        let limit = self.limits_for(owner).max_files_per_upload?;
+
+        (files + 1 > limit).then_some(QuotaViolation {
+            quota: Quota::FilesPerUpload,
+            limit,
+            used: files + 1,
+        })
+    }
+
+    /// Room left for a new file of `owner`, `None` when neither its size nor the stored bytes
+    /// are limited.
+    pub async fn allowance(&self, owner: &str) -> anyhow::Result<Option<Allowance>> {
+        let limits = self.limits_for(owner);
+
+        let file_size = limits.max_file_size_bytes.map(|limit| Allowance {
+            quota: Quota::FileSize,
+            limit,
+            used: 0,
+            remaining: limit,
+        });
+        let stored_bytes = match limits.max_stored_bytes {
+            Some(limit) => {
+                let used = self.usage(owner).await?.stored_bytes;
+                Some(Allowance {
+                    quota: Quota::StoredBytes,
+                    limit,
+                    used,
+                    remaining: limit.saturating_sub(used),
+                })
+            }
+            None => None,
+        };
+
+        Ok([file_size, stored_bytes]
+            .into_iter()
+            .flatten()
+            .min_by_key(|allowance| allowance.remaining))
+    }
+
+    pub async fn record(
+        &self,
+        owner: &str,
+        stored_bytes: i64,
+        open_uploads: i64,
+    ) -> anyhow::Result<()> {
+        if stored_bytes == 0 && open_uploads == 0 {
+            return Ok(());
+        }
+
+        self.usage_repository
+            .add(UsageRow {
+                owner: owner.to_string(),
+                stored_bytes,
+                open_uploads,
+            })
+            .await
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    collections::HashMap,
+    io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use config::Config;
+use file_server_library::models::{Hash32, Hash32Hasher};
+use futures::StreamExt;
+use metrics::counter;
+use serde::Deserialize;
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument};
+
+use crate::{
+    models::{FileEntry, FileMerkleTree, ScrubFindingKind, UploadCursor, WebhookEvent},
+    repositories::{FileRepository, FileStorage, ScrubFindingRow, ScrubRepository},
+    services::Webhooks,
+};
+
+const ONE_HOUR_IN_SECONDS: u64 = 60 * 60;
+const EIGHT_MIB_IN_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Every iteration checks up to `batch_size` completed uploads, reading contents at no more than
+/// `max_bytes_per_sec`. A pass over every upload spans as many iterations as needed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScrubberConfig {
+    #[serde(default = "ScrubberConfig::default_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "ScrubberConfig::default_batch_size")]
+    pub batch_size: usize,
+    #[serde(default = "ScrubberConfig::default_max_bytes_per_sec")]
+    pub max_bytes_per_sec: u64,
+}
+
+impl Default for ScrubberConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: Self::default_interval_secs(),
+            batch_size: Self::default_batch_size(),
+            max_bytes_per_sec: Self::default_max_bytes_per_sec(),
+        }
+    }
+}
+
+impl ScrubberConfig {
+    const CONFIG_PREFIX: &'static str = "SCRUBBER";
+
+    pub fn load_from_env() -> anyhow::Result<Self> {
+        Config::builder()
+            .add_source(config::Environment::with_prefix(Self::CONFIG_PREFIX).separator("__"))
+            .build()?
+            .try_deserialize::<ScrubberConfig>()
+            .map_err(|e| anyhow::anyhow!("failed to load Scrubber Configuration: {}", e))
+    }
+
+    fn default_interval_secs() -> u64 {
+        ONE_HOUR_IN_SECONDS
+    }
+
+    fn default_batch_size() -> usize {
+        10
+    }
+
+    fn default_max_bytes_per_sec() -> u64 {
+        EIGHT_MIB_IN_BYTES
+    }
+}
+
+/// Periodically reads back the contents of completed uploads and checks they still hash to their
+/// leaf hashes. Problems are recorded as findings and reported through logs and metrics, nothing
+/// is repaired.
+pub struct Scrubber {
+    file_repository: Arc<dyn FileRepository>,
+    file_storage: Arc<dyn FileStorage>,
+    scrub_repository: Arc<dyn ScrubRepository>,
+    config: ScrubberConfig,
+    webhooks: Option<Arc<Webhooks>>,
+}
+
+impl Scrubber {
+    pub fn new(
+        file_repository: Arc<dyn FileRepository>,
+        file_storage: Arc<dyn FileStorage>,
+        scrub_repository: Arc<dyn ScrubRepository>,
+        config: ScrubberConfig,
+    ) -> Self {
+        Self {
+            file_repository,
+            file_storage,
+            scrub_repository,
+            config,
+            webhooks: None,
+        }
+    }
+
+    /// Owners are notified of every finding in their uploads.
+    pub fn with_webhooks(mut self, webhooks: Arc<Webhooks>) -> Self {
+        self.webhooks = Some(webhooks);
+        self
+    }
+
+    // Iterations can take long while throttled, so shutdown does not wait for them. The cursor
+    // only moves past fully checked uploads, an interrupted one is checked again.
+    pub async fn run(self, shutdown: CancellationToken) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = interval.tick() => {
+                    tokio::select! {
+                        _ = shutdown.cancelled() => break,
+                        result = self.scrub() => {
+                            if let Err(e) = result {
+                                counter!("scrubber_failures_total").increment(1);
+                                error!("Scrubber iteration failed: {}", e);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks the next batch of uploads and returns how many findings were recorded.
+    #[instrument(skip(self))]
+    pub async fn scrub(&self) -> anyhow::Result<usize> {
+        let cursor = self.scrub_repository.get_cursor().await?;
+        let uploads = self
+            .file_repository
+            .list_completed(cursor, self.config.batch_size)
+            .await?;
+
+        if uploads.is_empty() {
+            if cursor.is_some() {
+                info!("Scrub pass completed");
+                self.scrub_repository.set_cursor(None).await?;
+            }
+            return Ok(0);
+        }
+
+        let mut throttle = Throttle::new(self.config.max_bytes_per_sec);
+        // Blobs are shared between uploads, each one is read once per iteration.
+        let mut checked: HashMap<Hash32, Verdict> = HashMap::new();
+        let mut findings = 0;
+
+        for upload in uploads {
+            let tree = FileMerkleTree::from(upload);
+
+            for file in tree.files() {
+                let (kind, actual_hash) = match checked.get(&file.hash) {
+                    Some(verdict) => *verdict,
+                    None => {
+                        let verdict = self.check(&file, &mut throttle).await?;
+                        checked.insert(file.hash, verdict);
+                        verdict
+                    }
+                };
+
+                if let Some(kind) = kind {
+                    self.report(&tree, &file, kind, actual_hash).await?;
+                    findings += 1;
+                } else {
+                    counter!("scrub_verified_files_total").increment(1);
+                }
+            }
+
+            let summary = tree.summary();
+            self.scrub_repository
+                .set_cursor(Some(UploadCursor::from(&summary)))
+                .await?;
+        }
+
+        Ok(findings)
+    }
+
+    async fn check(&self, file: &FileEntry, throttle: &mut Throttle) -> anyhow::Result<Verdict> {
+        let Some(mut contents) = self.file_storage.get_blob_content(file.hash, None).await? else {
+            return Ok((Some(ScrubFindingKind::Missing), None));
+        };
+
+        let mut hasher = Hash32Hasher::new();
+        while let Some(chunk) = contents.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                // Encrypted contents that fail authentication were changed in the storage.
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    return Ok((Some(ScrubFindingKind::Corrupted), None));
+                }
+                Err(e) => return Err(e.into()),
+            };
+            hasher.update(&chunk);
+            counter!("scrub_bytes_read_total").increment(chunk.len() as u64);
+            throttle.consume(chunk.len() as u64).await;
+        }
+
+        let actual = hasher.finalize();
+        if actual == file.hash {
+            Ok((None, None))
+        } else {
+            Ok((Some(ScrubFindingKind::Corrupted), Some(actual)))
+        }
+    }
+
+    async fn report(
+        &self,
+        tree: &FileMerkleTree,
+        file: &FileEntry,
+        kind: ScrubFindingKind,
+        actual_hash: Option<Hash32>,
+    ) -> anyhow::Result<()> {
+        let upload_id = tree.id();
+        let expected = file.hash.to_hex();
+
+        match kind {
+            ScrubFindingKind::Missing => {
+                counter!("scrub_missing_files_total").increment(1);
+                error!(%upload_id, index = file.index, name = %file.name, %expected, "Stored contents are missing");
+            }
+            ScrubFindingKind::Corrupted => {
+                counter!("scrub_corrupted_files_total").increment(1);
+                error!(%upload_id, index = file.index, name = %file.name, %expected, "Stored contents do not match their leaf hash");
+            }
+        }
+
+        self.scrub_repository
+            .add_finding(ScrubFindingRow {
+                upload_id,
+                index: file.index,
+                name: file.name.clone(),
+                kind,
+                expected_hash: file.hash,
+                actual_hash,
+                detected_at: Utc::now(),
+            })
+            .await?;
+
+        if let Some(webhooks) = &self.webhooks {
+            let data = json!({
+                "index": file.index,
+                "name": file.name,
+                "kind": kind.to_string(),
+                "expected_hash": expected,
+                "actual_hash": actual_hash.map(Hash32::to_hex),
+            });
+            webhooks
+                .notify(tree.owner(), WebhookEvent::Corrupted, upload_id, data)
+                .await;
+        }
+
+        Ok(())
+    }
+}
+
+/// What is wrong with some contents, if anything, and what they hash to when corrupted.
+type Verdict = (Option<ScrubFindingKind>, Option<Hash32>);
+
+/// Sleeps whenever more bytes were read than `max_bytes_per_sec` allows for the time elapsed.
+struct Throttle {
+    max_bytes_per_sec: u64,
+    started_at: Instant,
+    bytes: u64,
+}
+
+impl Throttle {
+    fn new(max_bytes_per_sec: u64) -> Self {
+        Self {
+            max_bytes_per_sec: max_bytes_per_sec.max(1),
+            started_at: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    async fn consume(&mut self, bytes: u64) {
+        self.bytes += bytes;
+
+        let expected = Duration::from_secs_f64(self.bytes as f64 / self.max_bytes_per_sec as f64);
+        let elapsed = self.started_at.elapsed();
+        if expected > elapsed {
+            tokio::time::sleep(expected - elapsed).await;
+        }
+    }
+}
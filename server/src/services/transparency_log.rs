@@ -0,0 +1,245 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use file_server_library::{
+    models::Hash32,
+    transparency::{LogLeaf, LogTree, log_head_message},
+};
+use metrics::counter;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    infrastructure::ServerSigner,
+    models::{LogConsistencyProof, LogHead, LogInclusionProof},
+    repositories::{LogEntryRow, TransparencyLogRepository},
+    services::FileServiceError,
+};
+
+/// Server-wide append-only log of completed roots. Heads are signed with the response signing
+/// key, so clients pinning it can hold the server to every head it ever handed out.
+#[async_trait]
+pub trait TransparencyLogService: Send + Sync {
+    /// Records that the upload was completed with `root`. Completing again with the same root
+    /// returns the existing entry instead of appending a new one.
+    async fn append(
+        &self,
+        upload_id: Uuid,
+        owner: &str,
+        root: Hash32,
+    ) -> Result<LogEntryRow, FileServiceError>;
+    /// Signed head of the log as it currently is.
+    async fn head(&self) -> Result<LogHead, FileServiceError>;
+    /// Proof of the latest entry of the upload in the log of `size` entries, the current
+    /// one when missing. Entries of uploads of other owners are not found.
+    async fn inclusion_proof(
+        &self,
+        owner: &str,
+        upload_id: Uuid,
+        size: Option<u64>,
+    ) -> Result<LogInclusionProof, FileServiceError>;
+    /// Proof that the log of `second` entries, the current one when missing, extends the log
+    /// of `first` entries.
+    async fn consistency_proof(
+        &self,
+        first: u64,
+        second: Option<u64>,
+    ) -> Result<LogConsistencyProof, FileServiceError>;
+}
+
+pub struct TransparencyLogServiceImpl {
+    repository: Arc<dyn TransparencyLogRepository>,
+    signer: Arc<ServerSigner>,
+    // Entries are indexed by the size of the log. Appends of this instance are serialized so they
+    // do not race each other, the repository settles races with other instances.
+    append_lock: Mutex<()>,
+}
+
+impl TransparencyLogServiceImpl {
+    pub fn new(repository: Arc<dyn TransparencyLogRepository>, signer: Arc<ServerSigner>) -> Self {
+        Self {
+            repository,
+            signer,
+            append_lock: Mutex::new(()),
+        }
+    }
+
+    async fn size(&self) -> Result<u64, FileServiceError> {
+        self.repository.size().await.map_err(|e| {
+            error!("Failed to get transparency log size: {}", e);
+            FileServiceError::StorageError(e.to_string())
+        })
+    }
+
+    async fn leaf_hashes(&self, size: u64) -> Result<Vec<Hash32>, FileServiceError> {
+        let leaves = self.repository.leaf_hashes(size).await.map_err(|e| {
+            error!("Failed to get transparency log leaves: {}", e);
+            FileServiceError::StorageError(e.to_string())
+        })?;
+
+        // Proofs are built assuming every entry below `size` is there.
+        if leaves.len() as u64 != size {
+            error!(
+                size,
+                found = leaves.len(),
+                "Transparency log has missing entries"
+            );
+            return Err(FileServiceError::StorageError(
+                "transparency log has missing entries".to_string(),
+            ));
+        }
+
+        Ok(leaves)
+    }
+
+    async fn tree(&self, size: u64) -> Result<LogTree, FileServiceError> {
+        Ok(LogTree::new(self.leaf_hashes(size).await?))
+    }
+
+    fn sign(&self, size: u64, root: Hash32) -> LogHead {
+        let timestamp = now_millis();
+        let message = log_head_message(size, &root, timestamp.timestamp_millis());
+
+        LogHead {
+            size,
+            root,
+            timestamp,
+            key_id: self.signer.key_id().to_string(),
+            signature: hex::encode(self.signer.sign(&message).to_bytes()),
+        }
+    }
+}
+
+// Appends losing their index to another instance are retried at the next one, this many times.
+const MAX_APPEND_ATTEMPTS: usize = 10;
+
+// Entries are stored with millisecond precision, which is also what the leaf hash covers.
+fn now_millis() -> DateTime<Utc> {
+    let now = Utc::now();
+    DateTime::from_timestamp_millis(now.timestamp_millis()).unwrap_or(now)
+}
+
+#[async_trait]
+impl TransparencyLogService for TransparencyLogServiceImpl {
+    async fn append(
+        &self,
+        upload_id: Uuid,
+        owner: &str,
+        root: Hash32,
+    ) -> Result<LogEntryRow, FileServiceError> {
+        let _guard = self.append_lock.lock().await;
+
+        let latest = self
+            .repository
+            .latest_for_upload(upload_id)
+            .await
+            .map_err(|e| {
+                error!("Failed to get transparency log entry: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })?;
+        if let Some(latest) = latest.filter(|latest| latest.root == root) {
+            return Ok(latest);
+        }
+
+        let appended_at = now_millis();
+        let leaf = LogLeaf {
+            upload_id: upload_id.to_string(),
+            owner: owner.to_string(),
+            root: root.to_hex(),
+            appended_at: appended_at.timestamp_millis(),
+        };
+
+        for _ in 0..MAX_APPEND_ATTEMPTS {
+            let entry = LogEntryRow {
+                index: self.size().await?,
+                upload_id,
+                owner: owner.to_string(),
+                root,
+                appended_at,
+                leaf_hash: leaf.leaf_hash(),
+            };
+
+            let appended = self.repository.append(entry.clone()).await.map_err(|e| {
+                error!("Failed to append to the transparency log: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })?;
+
+            if appended {
+                counter!("transparency_log_entries_total").increment(1);
+                info!(%upload_id, index = entry.index, "Appended root to the transparency log");
+                return Ok(entry);
+            }
+
+            counter!("transparency_log_append_conflicts_total").increment(1);
+        }
+
+        error!(%upload_id, "Kept losing the transparency log index to other instances");
+        Err(FileServiceError::StorageError(
+            "transparency log is too busy".to_string(),
+        ))
+    }
+
+    async fn head(&self) -> Result<LogHead, FileServiceError> {
+        let size = self.size().await?;
+        let tree = self.tree(size).await?;
+
+        Ok(self.sign(tree.size(), tree.root()))
+    }
+
+    async fn inclusion_proof(
+        &self,
+        owner: &str,
+        upload_id: Uuid,
+        size: Option<u64>,
+    ) -> Result<LogInclusionProof, FileServiceError> {
+        let entry = self
+            .repository
+            .latest_for_upload(upload_id)
+            .await
+            .map_err(|e| {
+                error!("Failed to get transparency log entry: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })?
+            .filter(|entry| entry.owner == owner)
+            .ok_or(FileServiceError::LogEntryNotFound)?;
+
+        let current = self.size().await?;
+        let size = size.unwrap_or(current);
+        if size > current || size <= entry.index {
+            return Err(FileServiceError::InvalidLogSize);
+        }
+
+        let tree = self.tree(size).await?;
+        let proof = tree.inclusion_proof(entry.index);
+
+        Ok(LogInclusionProof {
+            head: self.sign(size, tree.root()),
+            entry,
+            proof,
+        })
+    }
+
+    async fn consistency_proof(
+        &self,
+        first: u64,
+        second: Option<u64>,
+    ) -> Result<LogConsistencyProof, FileServiceError> {
+        let current = self.size().await?;
+        let second = second.unwrap_or(current);
+        if first > second || second > current {
+            return Err(FileServiceError::InvalidLogSize);
+        }
+
+        let leaves = self.leaf_hashes(second).await?;
+        let first_root = LogTree::new(leaves[..first as usize].to_vec()).root();
+        let tree = LogTree::new(leaves);
+
+        Ok(LogConsistencyProof {
+            first: self.sign(first, first_root),
+            second: self.sign(second, tree.root()),
+            proof: tree.consistency_proof(first),
+        })
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+
+use config::Config;
+use file_server_library::{CustomMerkleTree, models::Hash32};
+use lru::LruCache;
+use metrics::counter;
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TreeCacheConfig {
+    #[serde(default = "TreeCacheConfig::default_capacity")]
+    pub capacity: usize,
+}
+
+impl Default for TreeCacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: Self::default_capacity(),
+        }
+    }
+}
+
+impl TreeCacheConfig {
+    const CONFIG_PREFIX: &'static str = "TREE_CACHE";
+
+    pub fn load_from_env() -> anyhow::Result<Self> {
+        Config::builder()
+            .add_source(config::Environment::with_prefix(Self::CONFIG_PREFIX).separator("__"))
+            .build()?
+            .try_deserialize::<TreeCacheConfig>()
+            .map_err(|e| anyhow::anyhow!("failed to load Tree Cache Configuration: {}", e))
+    }
+
+    fn default_capacity() -> usize {
+        1024
+    }
+}
+
+/// Bounded cache of built Merkle trees, so proofs do not rehash every leaf on each request.
+///
+/// Trees are keyed by upload id and root. Any change to the leaves of an upload changes its
+/// root as well, so a stale tree can never be returned and entries never need to be evicted
+/// explicitly, the LRU policy takes care of the ones nobody asks for anymore.
+pub struct TreeCache {
+    trees: Mutex<LruCache<(Uuid, Hash32), Arc<CustomMerkleTree>>>,
+}
+
+impl TreeCache {
+    /// A capacity of `0` is bumped to `1`.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+
+        Self {
+            trees: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Returns the cached tree, or builds it from `leaves` and caches it.
+    pub fn get_or_build(
+        &self,
+        id: Uuid,
+        root: Hash32,
+        leaves: impl FnOnce() -> Vec<Hash32>,
+    ) -> Arc<CustomMerkleTree> {
+        if let Some(tree) = self.lock().get(&(id, root)) {
+            counter!("merkle_tree_cache_hits_total").increment(1);
+            return Arc::clone(tree);
+        }
+
+        counter!("merkle_tree_cache_misses_total").increment(1);
+
+        // Building the tree is the expensive part, so it happens without holding the lock.
+        let tree = Arc::new(CustomMerkleTree::new(leaves()));
+        self.insert(id, Arc::clone(&tree));
+
+        tree
+    }
+
+    pub fn insert(&self, id: Uuid, tree: Arc<CustomMerkleTree>) {
+        self.lock().put((id, tree.root()), tree);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LruCache<(Uuid, Hash32), Arc<CustomMerkleTree>>> {
+        self.trees.lock().expect("tree cache lock poisoned")
+    }
+}
+
+impl Default for TreeCache {
+    fn default() -> Self {
+        Self::new(TreeCacheConfig::default_capacity())
+    }
+}
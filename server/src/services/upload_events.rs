@@ -0,0 +1,60 @@
+use std::pin::Pin;
+
+use futures::{Stream, stream};
+use metrics::counter;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::models::UploadEvent;
+
+// Watchers falling further behind than this miss events.
+const UPLOAD_EVENTS_CAPACITY: usize = 1024;
+
+pub type UploadEventStream = Pin<Box<dyn Stream<Item = UploadEvent> + Send>>;
+
+/// In-process broadcast of the events of every upload. It is shared by everything changing
+/// uploads, the file service and the upload reaper, so watchers see all of their events.
+#[derive(Clone)]
+pub struct UploadEvents {
+    sender: broadcast::Sender<(Uuid, UploadEvent)>,
+}
+
+impl Default for UploadEvents {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(UPLOAD_EVENTS_CAPACITY).0,
+        }
+    }
+}
+
+impl UploadEvents {
+    // Sending fails when nobody watches, which is fine.
+    pub fn publish(&self, id: Uuid, event: UploadEvent) {
+        let _ = self.sender.send((id, event));
+    }
+
+    /// Events of the upload `id` published from now on, until one of them ends the upload.
+    pub fn subscribe(&self, id: Uuid) -> UploadEventStream {
+        let receiver = self.sender.subscribe();
+
+        Box::pin(stream::unfold(Some(receiver), move |receiver| async move {
+            let mut receiver = receiver?;
+
+            loop {
+                match receiver.recv().await {
+                    Ok((upload_id, event)) if upload_id == id => {
+                        let last = event.is_last();
+                        return Some((event, (!last).then_some(receiver)));
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(missed)) => {
+                        counter!("upload_events_missed_total").increment(missed);
+                        warn!(%id, missed, "Upload watcher fell behind, events were missed");
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }))
+    }
+}
@@ -0,0 +1,319 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use config::Config;
+use metrics::counter;
+use serde::Deserialize;
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    models::{FileMerkleTree, UploadEvent, UploadState, WebhookEvent},
+    repositories::{BlobRepository, FileRepository, FileStorage, UploadSessionRepository},
+    services::{Quotas, UploadEvents, Webhooks},
+};
+
+const ONE_DAY_IN_SECONDS: u64 = 24 * 60 * 60;
+const FIVE_MINUTES_IN_SECONDS: u64 = 5 * 60;
+
+/// TTLs are measured from the last time an upload was modified. A TTL of `0` means uploads in
+/// that state never expire, which is the default for completed uploads.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExpiryConfig {
+    #[serde(default = "ExpiryConfig::default_initiated_ttl_secs")]
+    pub initiated_ttl_secs: u64,
+    #[serde(default)]
+    pub completed_ttl_secs: u64,
+    #[serde(default = "ExpiryConfig::default_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "ExpiryConfig::default_batch_size")]
+    pub batch_size: usize,
+}
+
+impl Default for ExpiryConfig {
+    fn default() -> Self {
+        Self {
+            initiated_ttl_secs: Self::default_initiated_ttl_secs(),
+            completed_ttl_secs: 0,
+            interval_secs: Self::default_interval_secs(),
+            batch_size: Self::default_batch_size(),
+        }
+    }
+}
+
+impl ExpiryConfig {
+    const CONFIG_PREFIX: &'static str = "EXPIRY";
+
+    pub fn load_from_env() -> anyhow::Result<Self> {
+        Config::builder()
+            .add_source(config::Environment::with_prefix(Self::CONFIG_PREFIX).separator("__"))
+            .build()?
+            .try_deserialize::<ExpiryConfig>()
+            .map_err(|e| anyhow::anyhow!("failed to load Expiry Configuration: {}", e))
+    }
+
+    fn ttls(&self) -> Vec<(UploadState, Duration)> {
+        [
+            (UploadState::Initiated, self.initiated_ttl_secs),
+            (UploadState::Completed, self.completed_ttl_secs),
+        ]
+        .into_iter()
+        .filter(|(_, ttl)| *ttl > 0)
+        .map(|(state, ttl)| (state, Duration::from_secs(ttl)))
+        .collect()
+    }
+
+    fn default_initiated_ttl_secs() -> u64 {
+        ONE_DAY_IN_SECONDS
+    }
+
+    fn default_interval_secs() -> u64 {
+        FIVE_MINUTES_IN_SECONDS
+    }
+
+    fn default_batch_size() -> usize {
+        100
+    }
+}
+
+/// Periodically marks uploads whose TTL elapsed as `Expired` and removes their contents from
+/// the storage. The repository row is kept (in `Expired` state) so clients get a meaningful
+/// `410 Gone` instead of a `404` for an id they used to know.
+pub struct UploadReaper {
+    file_repository: Arc<dyn FileRepository>,
+    file_storage: Arc<dyn FileStorage>,
+    upload_session_repository: Arc<dyn UploadSessionRepository>,
+    blob_repository: Arc<dyn BlobRepository>,
+    config: ExpiryConfig,
+    quotas: Option<Arc<Quotas>>,
+    webhooks: Option<Arc<Webhooks>>,
+    upload_events: Option<UploadEvents>,
+}
+
+impl UploadReaper {
+    pub fn new(
+        file_repository: Arc<dyn FileRepository>,
+        file_storage: Arc<dyn FileStorage>,
+        upload_session_repository: Arc<dyn UploadSessionRepository>,
+        blob_repository: Arc<dyn BlobRepository>,
+        config: ExpiryConfig,
+    ) -> Self {
+        Self {
+            file_repository,
+            file_storage,
+            upload_session_repository,
+            blob_repository,
+            config,
+            quotas: None,
+            webhooks: None,
+            upload_events: None,
+        }
+    }
+
+    /// Expired uploads and discarded versions are taken off the usage of their owners.
+    pub fn with_quotas(mut self, quotas: Arc<Quotas>) -> Self {
+        self.quotas = Some(quotas);
+        self
+    }
+
+    /// Owners are notified of their uploads expiring.
+    pub fn with_webhooks(mut self, webhooks: Arc<Webhooks>) -> Self {
+        self.webhooks = Some(webhooks);
+        self
+    }
+
+    /// Watchers of the uploads expiring are told so, which ends their streams.
+    pub fn with_upload_events(mut self, upload_events: UploadEvents) -> Self {
+        self.upload_events = Some(upload_events);
+        self
+    }
+
+    pub async fn run(self, shutdown: CancellationToken) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = interval.tick() => {
+                    if let Err(e) = self.reap().await {
+                        counter!("upload_reaper_failures_total").increment(1);
+                        error!("Upload reaper iteration failed: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Runs a single pass over every state with a TTL and returns how many uploads were expired.
+    #[instrument(skip(self))]
+    pub async fn reap(&self) -> anyhow::Result<usize> {
+        let mut expired = 0;
+
+        for (state, ttl) in self.config.ttls() {
+            let updated_before = Utc::now() - ttl;
+            let stale = self
+                .file_repository
+                .list_stale(state, updated_before, self.config.batch_size)
+                .await?;
+
+            for row in stale {
+                let listed_at = row.updated_at;
+                let mut file_tree: FileMerkleTree = row.into();
+                let id = file_tree.id();
+
+                // Abandoning a new version leaves the completed ones alone, the upload goes back
+                // to the version it was derived from.
+                if state == UploadState::Initiated
+                    && let Some(previous) = file_tree.previous_version()
+                {
+                    match self.discard_version(&file_tree, previous, listed_at).await {
+                        Ok(Some(true)) => {
+                            counter!("upload_versions_discarded_total").increment(1);
+                            info!(%id, version = file_tree.version(), "Upload version discarded");
+                            continue;
+                        }
+                        Ok(Some(false)) => {
+                            info!(%id, "Upload changed since it was listed, keeping it");
+                            continue;
+                        }
+                        Ok(None) => {
+                            warn!(%id, previous, "Previous version is missing, expiring upload");
+                        }
+                        Err(e) => {
+                            counter!("upload_reaper_failures_total").increment(1);
+                            warn!(%id, "Failed to discard upload version: {}", e);
+                            continue;
+                        }
+                    }
+                }
+
+                match self.expire(&mut file_tree, listed_at).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        info!(%id, "Upload changed since it was listed, keeping it");
+                        continue;
+                    }
+                    Err(e) => {
+                        counter!("upload_reaper_failures_total").increment(1);
+                        warn!(%id, "Failed to expire upload: {}", e);
+                        continue;
+                    }
+                }
+
+                counter!("uploads_expired_total", "state" => state.to_string()).increment(1);
+                info!(%id, %state, "Upload expired");
+                if let Some(webhooks) = &self.webhooks {
+                    let data = json!({ "state": state.to_string() });
+                    webhooks
+                        .notify(file_tree.owner(), WebhookEvent::Expired, id, data)
+                        .await;
+                }
+                if let Some(upload_events) = &self.upload_events {
+                    upload_events.publish(id, UploadEvent::Expired);
+                }
+                expired += 1;
+            }
+        }
+
+        Ok(expired)
+    }
+
+    // Files only the discarded version added keep their blob references until the upload is
+    // deleted or expires. `None` when the previous version is missing, otherwise whether it was
+    // restored, which it is not when the upload changed since `listed_at`.
+    async fn discard_version(
+        &self,
+        file_tree: &FileMerkleTree,
+        previous: u32,
+        listed_at: DateTime<Utc>,
+    ) -> anyhow::Result<Option<bool>> {
+        let Some(row) = self
+            .file_repository
+            .get_version(file_tree.id(), previous)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let restored = FileMerkleTree::from(row.clone()).summary().total_size;
+        if !self
+            .file_repository
+            .update_if_unchanged(row, listed_at)
+            .await?
+        {
+            return Ok(Some(false));
+        }
+
+        let discarded = file_tree.summary().total_size;
+        self.record_usage(file_tree.owner(), restored as i64 - discarded as i64, -1)
+            .await;
+        Ok(Some(true))
+    }
+
+    // The row is expired first, and only when nothing changed it since `listed_at`: a file or a
+    // completion arriving after the upload was listed keeps it alive, and its contents with it.
+    // Returns whether it was expired.
+    async fn expire(
+        &self,
+        file_tree: &mut FileMerkleTree,
+        listed_at: DateTime<Utc>,
+    ) -> anyhow::Result<bool> {
+        let open_uploads = match file_tree.state() {
+            UploadState::Initiated => 1,
+            _ => 0,
+        };
+        file_tree.expire();
+        if !self
+            .file_repository
+            .update_if_unchanged(file_tree.clone().into(), listed_at)
+            .await?
+        {
+            return Ok(false);
+        }
+
+        let stored_bytes = file_tree.summary().total_size as i64;
+        self.record_usage(file_tree.owner(), -stored_bytes, -open_uploads)
+            .await;
+
+        self.remove_contents(file_tree.id()).await;
+        Ok(true)
+    }
+
+    // The whole prefix is deleted so chunks of abandoned upload sessions go away as well, objects
+    // failing to be deleted are left to the orphan collector, which takes every object of an
+    // expired upload for an orphan. File contents live in shared blobs, which the blob collector
+    // removes once nothing references them. Sessions left open would keep appending to an upload
+    // that is gone.
+    async fn remove_contents(&self, id: Uuid) {
+        let removals = [
+            ("objects", self.file_storage.delete_prefix(id).await),
+            (
+                "blob references",
+                self.blob_repository.remove_references(id).await,
+            ),
+            (
+                "upload sessions",
+                self.upload_session_repository.delete_by_upload(id).await,
+            ),
+        ];
+
+        for (contents, result) in removals {
+            if let Err(e) = result {
+                counter!("upload_reaper_failures_total").increment(1);
+                warn!(%id, "Failed to remove {} of expired upload: {}", contents, e);
+            }
+        }
+    }
+
+    // Same as in `FileServiceImpl`, the upload is gone already so failing would not bring it back.
+    async fn record_usage(&self, owner: &str, stored_bytes: i64, open_uploads: i64) {
+        if let Some(quotas) = &self.quotas
+            && let Err(e) = quotas.record(owner, stored_bytes, open_uploads).await
+        {
+            counter!("usage_record_failures_total").increment(1);
+            warn!(owner, "Failed to record usage: {}", e);
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use std::{
+    io,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt, future, stream};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::{
+    models::{FileMerkleTree, FileMetadata, UploadSession, UploadState},
+    repositories::{FileRepository, FileStorage, FileStream, UploadSessionRepository},
+    services::{Allowance, FileService, FileServiceError, Quotas, file_service::storage_error},
+};
+
+/// Resumable uploads for flaky links. A session receives a file in chunks, each one appended at
+/// the offset the server committed so far, and is finalized once every chunk landed. Sessions are
+/// meant to be driven by a single client at a time, the one owning the upload.
+#[async_trait]
+pub trait UploadSessionService: Send + Sync {
+    async fn create(
+        &self,
+        owner: &str,
+        upload_id: Uuid,
+        metadata: FileMetadata,
+        length: Option<u64>,
+    ) -> Result<UploadSession, FileServiceError>;
+    async fn get(
+        &self,
+        owner: &str,
+        upload_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<UploadSession, FileServiceError>;
+    async fn append(
+        &self,
+        owner: &str,
+        upload_id: Uuid,
+        session_id: Uuid,
+        offset: u64,
+        content: FileStream,
+    ) -> Result<UploadSession, FileServiceError>;
+    async fn finalize(
+        &self,
+        owner: &str,
+        upload_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<String, FileServiceError>;
+}
+
+pub struct UploadSessionServiceImpl {
+    file_service: Arc<dyn FileService>,
+    file_repository: Arc<dyn FileRepository>,
+    file_storage: Arc<dyn FileStorage>,
+    upload_session_repository: Arc<dyn UploadSessionRepository>,
+    quotas: Option<Arc<Quotas>>,
+}
+
+impl UploadSessionServiceImpl {
+    pub fn new(
+        file_service: Arc<dyn FileService>,
+        file_repository: Arc<dyn FileRepository>,
+        file_storage: Arc<dyn FileStorage>,
+        upload_session_repository: Arc<dyn UploadSessionRepository>,
+    ) -> Self {
+        Self {
+            file_service,
+            file_repository,
+            file_storage,
+            upload_session_repository,
+            quotas: None,
+        }
+    }
+
+    /// Sessions are held to the file size and stored bytes quotas of their owner while chunks
+    /// arrive, instead of only once finalized.
+    pub fn with_quotas(mut self, quotas: Arc<Quotas>) -> Self {
+        self.quotas = Some(quotas);
+        self
+    }
+}
+
+#[async_trait]
+impl UploadSessionService for UploadSessionServiceImpl {
+    async fn create(
+        &self,
+        owner: &str,
+        upload_id: Uuid,
+        metadata: FileMetadata,
+        length: Option<u64>,
+    ) -> Result<UploadSession, FileServiceError> {
+        if metadata.has_reserved_name() {
+            return Err(FileServiceError::InvalidFileName);
+        }
+
+        // Checked again once finalized, failing now saves sending the whole file first.
+        let file_tree = self.ensure_upload_is_active(owner, upload_id).await?;
+        if file_tree.holds_name_elsewhere(metadata.index, &metadata.name) {
+            return Err(FileServiceError::FileAlreadyExists);
+        }
+        if let Some(length) = length
+            && let Some(allowance) = self.allowance(owner).await?
+            && length > allowance.remaining
+        {
+            return Err(FileServiceError::QuotaExceeded(allowance.violation(length)));
+        }
+
+        let session = UploadSession::new(upload_id, metadata, length);
+
+        self.upload_session_repository
+            .insert(session.clone().into())
+            .await
+            .map_err(|e| {
+                error!("Failed to insert upload session: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })?;
+
+        Ok(session)
+    }
+
+    async fn get(
+        &self,
+        owner: &str,
+        upload_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<UploadSession, FileServiceError> {
+        self.ensure_upload_is_owned(owner, upload_id).await?;
+        self.get_session(upload_id, session_id).await
+    }
+
+    async fn append(
+        &self,
+        owner: &str,
+        upload_id: Uuid,
+        session_id: Uuid,
+        offset: u64,
+        content: FileStream,
+    ) -> Result<UploadSession, FileServiceError> {
+        self.ensure_upload_is_active(owner, upload_id).await?;
+        let mut session = self.get_session(upload_id, session_id).await?;
+
+        if offset != session.offset() {
+            return Err(FileServiceError::UploadOffsetMismatch(session.offset()));
+        }
+
+        // Chunks crossing the announced length are rejected as soon as the extra byte arrives
+        // instead of being stored and discarded afterwards.
+        let remaining = session.remaining();
+        let received = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&received);
+        let content: FileStream = Box::pin(content.and_then(move |chunk| {
+            let size = chunk.len() as u64;
+            let total = counter.fetch_add(size, Ordering::Relaxed) + size;
+
+            future::ready(match remaining {
+                Some(remaining) if total > remaining => Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "chunk exceeds the announced upload length",
+                )),
+                _ => Ok(chunk),
+            })
+        }));
+
+        // The chunks already committed are part of the file, they count against the allowance.
+        let content = match self.allowance(owner).await? {
+            Some(allowance) => Allowance {
+                used: allowance.used + offset,
+                remaining: allowance.remaining.saturating_sub(offset),
+                ..allowance
+            }
+            .limit(content),
+            None => content,
+        };
+
+        // A retried request may still be appending at the same offset, every chunk is stored
+        // under its own name so they never write over each other.
+        let chunk_id = Uuid::new_v4();
+        let chunk_name = session.next_chunk_name(chunk_id);
+        let stored = self
+            .file_storage
+            .insert_file_content(
+                upload_id,
+                &chunk_name,
+                mime::APPLICATION_OCTET_STREAM.as_ref(),
+                None,
+                content,
+            )
+            .await;
+        let received = received.load(Ordering::Relaxed);
+
+        // A chunk that fails half way is not committed, the client resumes from the same offset.
+        if let Err(e) = stored {
+            self.discard_chunk(upload_id, &chunk_name).await;
+
+            if remaining.is_some_and(|remaining| received > remaining) {
+                return Err(FileServiceError::UploadLengthExceeded);
+            }
+
+            error!("Failed to store upload session chunk: {}", e);
+            return Err(storage_error(e));
+        }
+
+        if received == 0 {
+            self.discard_chunk(upload_id, &chunk_name).await;
+            return Ok(session);
+        }
+
+        session.append(chunk_id, received);
+
+        let committed = self
+            .upload_session_repository
+            .update_if_offset(session.clone().into(), offset)
+            .await
+            .map_err(|e| {
+                error!("Failed to update upload session: {}", e);
+                FileServiceError::StorageError(e.to_string())
+            })?;
+
+        // Another append at the same offset was committed first.
+        if !committed {
+            self.discard_chunk(upload_id, &chunk_name).await;
+            let session = self.get_session(upload_id, session_id).await?;
+            return Err(FileServiceError::UploadOffsetMismatch(session.offset()));
+        }
+
+        Ok(session)
+    }
+
+    async fn finalize(
+        &self,
+        owner: &str,
+        upload_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<String, FileServiceError> {
+        self.ensure_upload_is_owned(owner, upload_id).await?;
+        let session = self.get_session(upload_id, session_id).await?;
+
+        if !session.is_complete() {
+            return Err(FileServiceError::UploadSessionIncomplete);
+        }
+
+        // Chunks are opened one at a time while the file is being stored, so finalizing has the
+        // same memory footprint as a regular streamed upload. The leaf is hashed along the way.
+        let file_storage = Arc::clone(&self.file_storage);
+        let content: FileStream = Box::pin(
+            stream::iter(session.chunk_names())
+                .then(move |name| {
+                    let file_storage = Arc::clone(&file_storage);
+                    async move {
+                        file_storage
+                            .get_file_content(upload_id, &name, None)
+                            .await
+                            .map_err(io::Error::other)?
+                            .ok_or_else(|| {
+                                io::Error::new(io::ErrorKind::NotFound, format!("missing {name}"))
+                            })
+                    }
+                })
+                .try_flatten(),
+        );
+
+        let encoded_hash = self
+            .file_service
+            .upload_file(owner, upload_id, session.metadata(), content)
+            .await?;
+
+        // The file is already stored at this point, leftovers are collected along with the upload.
+        for name in session.chunk_names() {
+            if let Err(e) = self
+                .file_storage
+                .delete_file_content(upload_id, &name)
+                .await
+            {
+                warn!(%upload_id, %session_id, "Failed to delete chunk {}: {}", name, e);
+            }
+        }
+        if let Err(e) = self.upload_session_repository.delete(session_id).await {
+            warn!(%upload_id, %session_id, "Failed to delete upload session: {}", e);
+        }
+
+        Ok(encoded_hash)
+    }
+}
+
+impl UploadSessionServiceImpl {
+    // Same as for `FileService`, uploads of other clients are not found.
+    async fn ensure_upload_is_owned(
+        &self,
+        owner: &str,
+        upload_id: Uuid,
+    ) -> Result<FileMerkleTree, FileServiceError> {
+        self.file_repository
+            .get(upload_id)
+            .await
+            .map_err(|e| FileServiceError::StorageError(e.to_string()))?
+            .map(FileMerkleTree::from)
+            .filter(|file_tree| file_tree.owner() == owner)
+            .ok_or(FileServiceError::FileNotFound)
+    }
+
+    async fn ensure_upload_is_active(
+        &self,
+        owner: &str,
+        upload_id: Uuid,
+    ) -> Result<FileMerkleTree, FileServiceError> {
+        let file_tree = self.ensure_upload_is_owned(owner, upload_id).await?;
+
+        match file_tree.state() {
+            UploadState::Expired => Err(FileServiceError::UploadExpired),
+            UploadState::Completed => Err(FileServiceError::UploadAlreadyCompleted),
+            UploadState::Initiated => Ok(file_tree),
+        }
+    }
+
+    async fn allowance(&self, owner: &str) -> Result<Option<Allowance>, FileServiceError> {
+        let Some(quotas) = &self.quotas else {
+            return Ok(None);
+        };
+
+        quotas.allowance(owner).await.map_err(|e| {
+            error!("Failed to get usage: {}", e);
+            FileServiceError::StorageError(e.to_string())
+        })
+    }
+
+    async fn get_session(
+        &self,
+        upload_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<UploadSession, FileServiceError> {
+        let session: UploadSession = self
+            .upload_session_repository
+            .get(session_id)
+            .await
+            .map_err(|e| FileServiceError::StorageError(e.to_string()))?
+            .ok_or(FileServiceError::UploadSessionNotFound)?
+            .into();
+
+        if session.upload_id() != upload_id {
+            return Err(FileServiceError::UploadSessionNotFound);
+        }
+
+        Ok(session)
+    }
+
+    // Chunks that are not committed are never read, failing to delete one only leaves it to the
+    // orphan collector once the session is gone.
+    async fn discard_chunk(&self, upload_id: Uuid, name: &str) {
+        if let Err(e) = self.file_storage.delete_file_content(upload_id, name).await {
+            warn!(%upload_id, "Failed to delete chunk {}: {}", name, e);
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::Utc;
+use config::Config;
+use metrics::counter;
+use serde::Deserialize;
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    infrastructure::{
+        AUTH_KEY_HEADER_NAME, AUTH_SIGNATURE_HEADER_NAME, AUTH_TS_HEADER_NAME, hmac_hex,
+    },
+    models::{DeliveryState, WebhookEvent},
+    repositories::{WebhookDeliveryRow, WebhookRepository},
+};
+
+const ONE_HOUR_IN_SECONDS: u64 = 60 * 60;
+// Responses are only kept to tell what went wrong, long bodies are cut.
+const MAX_ERROR_LENGTH: usize = 512;
+
+/// Where a client is notified, and of what. `events` is a comma separated list of event names,
+/// e.g. `upload.completed,upload.expired`, every event is sent when it is missing.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookEndpoint {
+    pub url: String,
+    #[serde(default)]
+    pub events: Option<String>,
+}
+
+impl WebhookEndpoint {
+    pub fn events(&self) -> anyhow::Result<Option<HashSet<WebhookEvent>>> {
+        self.events
+            .as_deref()
+            .map(|events| {
+                events
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|event| !event.is_empty())
+                    .map(str::parse)
+                    .collect()
+            })
+            .transpose()
+    }
+
+    fn subscribes_to(&self, event: WebhookEvent) -> bool {
+        match self.events() {
+            Ok(Some(events)) => events.contains(&event),
+            Ok(None) => true,
+            Err(_) => false,
+        }
+    }
+}
+
+/// Endpoints keyed by API key, e.g. `WEBHOOKS__CLIENTS__client-1__URL`. Keys are read
+/// lowercased. Failed deliveries are retried up to `max_attempts` times, waiting twice as long
+/// after each attempt starting at `base_delay_secs`, and never longer than `max_delay_secs`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookConfig {
+    #[serde(default)]
+    pub clients: HashMap<String, WebhookEndpoint>,
+    #[serde(default = "WebhookConfig::default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "WebhookConfig::default_base_delay_secs")]
+    pub base_delay_secs: u64,
+    #[serde(default = "WebhookConfig::default_max_delay_secs")]
+    pub max_delay_secs: u64,
+    #[serde(default = "WebhookConfig::default_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "WebhookConfig::default_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "WebhookConfig::default_batch_size")]
+    pub batch_size: usize,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            clients: HashMap::new(),
+            max_attempts: Self::default_max_attempts(),
+            base_delay_secs: Self::default_base_delay_secs(),
+            max_delay_secs: Self::default_max_delay_secs(),
+            timeout_secs: Self::default_timeout_secs(),
+            interval_secs: Self::default_interval_secs(),
+            batch_size: Self::default_batch_size(),
+        }
+    }
+}
+
+impl WebhookConfig {
+    const CONFIG_PREFIX: &'static str = "WEBHOOKS";
+
+    pub fn load_from_env() -> anyhow::Result<Self> {
+        let config = Config::builder()
+            .add_source(
+                config::Environment::with_prefix(Self::CONFIG_PREFIX)
+                    .separator("__")
+                    .try_parsing(true),
+            )
+            .build()?
+            .try_deserialize::<WebhookConfig>()
+            .map_err(|e| anyhow::anyhow!("failed to load Webhook Configuration: {}", e))?;
+
+        for (client, endpoint) in &config.clients {
+            endpoint
+                .events()
+                .map_err(|e| anyhow::anyhow!("bad webhook events of {client}: {e}"))?;
+        }
+
+        Ok(config)
+    }
+
+    /// How long to wait after the given number of failed attempts.
+    pub fn retry_delay(&self, attempts: u32) -> Duration {
+        let exponent = attempts.saturating_sub(1).min(32);
+        let delay = self.base_delay_secs.saturating_mul(1 << exponent);
+        Duration::from_secs(delay.min(self.max_delay_secs))
+    }
+
+    fn default_max_attempts() -> u32 {
+        8
+    }
+
+    fn default_base_delay_secs() -> u64 {
+        10
+    }
+
+    fn default_max_delay_secs() -> u64 {
+        ONE_HOUR_IN_SECONDS
+    }
+
+    fn default_timeout_secs() -> u64 {
+        10
+    }
+
+    fn default_interval_secs() -> u64 {
+        5
+    }
+
+    fn default_batch_size() -> usize {
+        100
+    }
+}
+
+/// Queues notifications of upload lifecycle events for the clients that subscribed to them. The
+/// `WebhookDispatcher` sends them later, so notifying never waits on the endpoints.
+pub struct Webhooks {
+    config: WebhookConfig,
+    webhook_repository: Arc<dyn WebhookRepository>,
+}
+
+impl Webhooks {
+    pub fn new(config: WebhookConfig, webhook_repository: Arc<dyn WebhookRepository>) -> Self {
+        Self {
+            config,
+            webhook_repository,
+        }
+    }
+
+    /// `data` goes along with the event in the payload. Failing to queue the notification only
+    /// warns, the event happened either way.
+    pub async fn notify(
+        &self,
+        owner: &str,
+        event: WebhookEvent,
+        upload_id: Uuid,
+        data: serde_json::Value,
+    ) {
+        let Some(endpoint) = self.config.clients.get(owner) else {
+            return;
+        };
+        if !endpoint.subscribes_to(event) {
+            return;
+        }
+
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+        let payload = json!({
+            "id": id,
+            "event": event.to_string(),
+            "upload_id": upload_id,
+            "occurred_at": now,
+            "data": data,
+        });
+
+        let delivery = WebhookDeliveryRow {
+            id,
+            owner: owner.to_string(),
+            event,
+            upload_id,
+            url: endpoint.url.clone(),
+            payload: payload.to_string(),
+            state: DeliveryState::Pending,
+            attempts: 0,
+            next_attempt_at: now,
+            last_status: None,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        if let Err(e) = self.webhook_repository.insert(delivery).await {
+            counter!("webhook_enqueue_failures_total").increment(1);
+            warn!(owner, %event, %upload_id, "Failed to queue webhook delivery: {}", e);
+        }
+    }
+
+    /// Deliveries of `owner`, newest first.
+    pub async fn deliveries(
+        &self,
+        owner: &str,
+        offset: usize,
+        limit: usize,
+    ) -> anyhow::Result<Vec<WebhookDeliveryRow>> {
+        self.webhook_repository
+            .list_by_owner(owner, offset, limit)
+            .await
+    }
+}
+
+/// Periodically sends the deliveries that are due. Payloads are signed like requests are, with
+/// the API secret of the client: `X-AUTH-SIGNATURE` is the hex HMAC-SHA256 of `X-AUTH-TS`, a dot
+/// and the body. Any response other than 2xx counts as a failed attempt.
+pub struct WebhookDispatcher {
+    webhook_repository: Arc<dyn WebhookRepository>,
+    config: WebhookConfig,
+    secrets: HashMap<String, String>,
+    client: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    /// `secrets` maps API keys to their secrets, deliveries of clients missing from it fail.
+    pub fn new(
+        webhook_repository: Arc<dyn WebhookRepository>,
+        config: WebhookConfig,
+        secrets: HashMap<String, String>,
+    ) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+
+        Ok(Self {
+            webhook_repository,
+            config,
+            secrets,
+            client,
+        })
+    }
+
+    pub async fn run(self, shutdown: CancellationToken) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = interval.tick() => {
+                    if let Err(e) = self.dispatch().await {
+                        counter!("webhook_dispatcher_failures_total").increment(1);
+                        error!("Webhook dispatcher iteration failed: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Attempts every delivery due and returns how many of them were delivered.
+    #[instrument(skip(self))]
+    pub async fn dispatch(&self) -> anyhow::Result<usize> {
+        let due = self
+            .webhook_repository
+            .list_due(Utc::now(), self.config.batch_size)
+            .await?;
+        let mut delivered = 0;
+
+        for mut delivery in due {
+            let (status, error) = self.attempt(&delivery).await;
+            let now = Utc::now();
+            delivery.attempts += 1;
+            delivery.last_status = status;
+            delivery.last_error = error;
+            delivery.updated_at = now;
+
+            let outcome = if status.is_some_and(|status| (200..300).contains(&status)) {
+                delivery.state = DeliveryState::Delivered;
+                info!(id = %delivery.id, owner = %delivery.owner, event = %delivery.event, "Webhook delivered");
+                delivered += 1;
+                "delivered"
+            } else if delivery.attempts >= self.config.max_attempts {
+                delivery.state = DeliveryState::Failed;
+                warn!(id = %delivery.id, owner = %delivery.owner, event = %delivery.event, attempts = delivery.attempts, "Webhook delivery failed");
+                "failed"
+            } else {
+                delivery.next_attempt_at = now + self.config.retry_delay(delivery.attempts);
+                "retried"
+            };
+            counter!("webhook_deliveries_total", "outcome" => outcome).increment(1);
+
+            // The attempt is made again if this fails, endpoints may see a delivery twice and
+            // can tell by its id.
+            self.webhook_repository.update(delivery).await?;
+        }
+
+        Ok(delivered)
+    }
+
+    async fn attempt(&self, delivery: &WebhookDeliveryRow) -> (Option<u16>, Option<String>) {
+        let Some(secret) = self.secrets.get(&delivery.owner) else {
+            return (None, Some("client has no secret to sign with".to_string()));
+        };
+
+        let timestamp = Utc::now().timestamp_millis().to_string();
+        let signature = hmac_hex(
+            secret.as_bytes(),
+            format!("{timestamp}.{}", delivery.payload).as_bytes(),
+        );
+
+        let response = self
+            .client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(AUTH_KEY_HEADER_NAME, &delivery.owner)
+            .header(AUTH_TS_HEADER_NAME, timestamp)
+            .header(AUTH_SIGNATURE_HEADER_NAME, signature)
+            .body(delivery.payload.clone())
+            .send()
+            .await;
+
+        match response {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status().as_u16()), None)
+            }
+            Ok(response) => {
+                let status = response.status().as_u16();
+                let mut body = response.text().await.unwrap_or_default();
+                body.truncate(body.floor_char_boundary(MAX_ERROR_LENGTH));
+                (Some(status), Some(body).filter(|body| !body.is_empty()))
+            }
+            Err(e) => (None, Some(e.to_string())),
+        }
+    }
+}
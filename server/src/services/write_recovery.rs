@@ -0,0 +1,347 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use config::Config;
+use file_server_library::models::Hash32;
+use metrics::counter;
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    models::{FileMerkleTree, UploadState},
+    repositories::{
+        BlobReferenceRow, BlobRepository, FileRepository, FileStorage, PendingWriteRepository,
+        PendingWriteRow,
+    },
+    services::Quotas,
+};
+
+const ONE_HOUR_IN_SECONDS: u64 = 60 * 60;
+const FIVE_MINUTES_IN_SECONDS: u64 = 5 * 60;
+
+/// Writes are only recovered once they are `min_age_secs` old, younger ones may still be running
+/// on another instance. It must be longer than the slowest upload takes to store its file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WriteRecoveryConfig {
+    #[serde(default = "WriteRecoveryConfig::default_min_age_secs")]
+    pub min_age_secs: u64,
+    #[serde(default = "WriteRecoveryConfig::default_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "WriteRecoveryConfig::default_batch_size")]
+    pub batch_size: usize,
+}
+
+impl Default for WriteRecoveryConfig {
+    fn default() -> Self {
+        Self {
+            min_age_secs: Self::default_min_age_secs(),
+            interval_secs: Self::default_interval_secs(),
+            batch_size: Self::default_batch_size(),
+        }
+    }
+}
+
+impl WriteRecoveryConfig {
+    const CONFIG_PREFIX: &'static str = "WRITE_RECOVERY";
+
+    pub fn load_from_env() -> anyhow::Result<Self> {
+        Config::builder()
+            .add_source(config::Environment::with_prefix(Self::CONFIG_PREFIX).separator("__"))
+            .build()?
+            .try_deserialize::<WriteRecoveryConfig>()
+            .map_err(|e| anyhow::anyhow!("failed to load Write Recovery Configuration: {}", e))
+    }
+
+    fn default_min_age_secs() -> u64 {
+        ONE_HOUR_IN_SECONDS
+    }
+
+    fn default_interval_secs() -> u64 {
+        FIVE_MINUTES_IN_SECONDS
+    }
+
+    fn default_batch_size() -> usize {
+        100
+    }
+}
+
+/// What a recovery pass did with the pending writes it found.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    pub finished: usize,
+    pub rolled_back: usize,
+    pub failed: usize,
+}
+
+enum Outcome {
+    Finished,
+    RolledBack,
+}
+
+/// Periodically reconciles the storage with the repository, going through the writes left in the
+/// journal by uploads cut halfway, whether by a crash or by a failure the server survived. Writes
+/// whose contents made it to their blob are finished when the upload can still take the file,
+/// everything else is rolled back: staging objects are deleted and references dropped, leaving
+/// unreferenced blobs to the blob collector.
+pub struct WriteRecovery {
+    pending_write_repository: Arc<dyn PendingWriteRepository>,
+    file_repository: Arc<dyn FileRepository>,
+    file_storage: Arc<dyn FileStorage>,
+    blob_repository: Arc<dyn BlobRepository>,
+    config: WriteRecoveryConfig,
+    quotas: Option<Arc<Quotas>>,
+}
+
+impl WriteRecovery {
+    pub fn new(
+        pending_write_repository: Arc<dyn PendingWriteRepository>,
+        file_repository: Arc<dyn FileRepository>,
+        file_storage: Arc<dyn FileStorage>,
+        blob_repository: Arc<dyn BlobRepository>,
+        config: WriteRecoveryConfig,
+    ) -> Self {
+        Self {
+            pending_write_repository,
+            file_repository,
+            file_storage,
+            blob_repository,
+            config,
+            quotas: None,
+        }
+    }
+
+    /// Finished writes are added to the usage of their owners.
+    pub fn with_quotas(mut self, quotas: Arc<Quotas>) -> Self {
+        self.quotas = Some(quotas);
+        self
+    }
+
+    /// Recovers right away and then on every interval, writes failing to recover are retried on
+    /// the next pass.
+    pub async fn run(self, shutdown: CancellationToken) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = interval.tick() => match self.recover().await {
+                    Ok(report) if report != RecoveryReport::default() => {
+                        info!(?report, "Write recovery finished")
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        counter!("write_recovery_failures_total").increment(1);
+                        error!("Write recovery failed: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Goes through every pending write old enough, batch by batch.
+    #[instrument(skip(self))]
+    pub async fn recover(&self) -> anyhow::Result<RecoveryReport> {
+        let created_before = Utc::now() - Duration::from_secs(self.config.min_age_secs);
+        let mut report = RecoveryReport::default();
+
+        loop {
+            // Writes failing to recover stay in the journal, so they are skipped past.
+            let pending = self
+                .pending_write_repository
+                .list_before(created_before, self.config.batch_size + report.failed)
+                .await?;
+            let pending: Vec<_> = pending.into_iter().skip(report.failed).collect();
+
+            if pending.is_empty() {
+                return Ok(report);
+            }
+
+            for write in pending {
+                let id = write.id;
+                let upload_id = write.upload_id;
+
+                match self.recover_write(write).await {
+                    Ok(Outcome::Finished) => {
+                        counter!("pending_writes_finished_total").increment(1);
+                        info!(%id, %upload_id, "Pending write finished");
+                        report.finished += 1;
+                    }
+                    Ok(Outcome::RolledBack) => {
+                        counter!("pending_writes_rolled_back_total").increment(1);
+                        info!(%id, %upload_id, "Pending write rolled back");
+                        report.rolled_back += 1;
+                    }
+                    Err(e) => {
+                        counter!("write_recovery_failures_total").increment(1);
+                        warn!(%id, %upload_id, "Failed to recover pending write: {}", e);
+                        report.failed += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn recover_write(&self, write: PendingWriteRow) -> anyhow::Result<Outcome> {
+        let Some(hash) = write.hash else {
+            self.roll_back(&write, None).await?;
+            return Ok(Outcome::RolledBack);
+        };
+
+        let Some(mut file_tree) = self
+            .file_repository
+            .get(write.upload_id)
+            .await?
+            .map(FileMerkleTree::from)
+        else {
+            self.roll_back(&write, Some(hash)).await?;
+            return Ok(Outcome::RolledBack);
+        };
+
+        if holds(&file_tree, &write.name, hash) {
+            self.ensure_reference(&file_tree, &write, hash).await?;
+            self.pending_write_repository.delete(write.id).await?;
+            return Ok(Outcome::Finished);
+        }
+
+        if !self.can_finish(&file_tree, &write, hash).await? {
+            self.roll_back(&write, Some(hash)).await?;
+            return Ok(Outcome::RolledBack);
+        }
+
+        self.ensure_reference(&file_tree, &write, hash).await?;
+        let before = file_tree.summary().total_size;
+        file_tree.add(
+            write.index,
+            &write.name,
+            &hash,
+            write.size,
+            &write.content_type,
+        );
+        let after = file_tree.summary().total_size;
+        self.file_repository
+            .update(file_tree.clone().into())
+            .await?;
+        self.record_usage(file_tree.owner(), after as i64 - before as i64)
+            .await;
+
+        self.pending_write_repository.delete(write.id).await?;
+        Ok(Outcome::Finished)
+    }
+
+    // Only open uploads take files, and only where no other file was written since.
+    async fn can_finish(
+        &self,
+        file_tree: &FileMerkleTree,
+        write: &PendingWriteRow,
+        hash: Hash32,
+    ) -> anyhow::Result<bool> {
+        let index_is_free = file_tree
+            .get_file_name_by_index(write.index)
+            .is_none_or(|name| name.is_empty());
+
+        Ok(file_tree.state() == UploadState::Initiated
+            && index_is_free
+            && file_tree.get_index_by_file_name(&write.name).is_none()
+            && self.file_storage.get_blob_metadata(hash).await?.is_some())
+    }
+
+    async fn ensure_reference(
+        &self,
+        file_tree: &FileMerkleTree,
+        write: &PendingWriteRow,
+        hash: Hash32,
+    ) -> anyhow::Result<()> {
+        let existing = self
+            .blob_repository
+            .get_reference(write.upload_id, &write.name, hash)
+            .await?;
+
+        if existing.is_none() {
+            self.blob_repository
+                .add_reference(BlobReferenceRow {
+                    hash,
+                    upload_id: write.upload_id,
+                    owner: file_tree.owner().to_string(),
+                    name: write.name.clone(),
+                    content_type: write.content_type.clone(),
+                    created_at: Utc::now(),
+                })
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // The staging object is deleted before the journal entry so a failed rollback is retried
+    // as a whole. References shared with earlier versions of the upload are kept.
+    async fn roll_back(&self, write: &PendingWriteRow, hash: Option<Hash32>) -> anyhow::Result<()> {
+        if let Some(staging_name) = &write.staging_name {
+            self.file_storage
+                .delete_file_content(write.upload_id, staging_name)
+                .await?;
+        }
+
+        if let Some(hash) = hash
+            && !held_by_versions(
+                self.file_repository.as_ref(),
+                write.upload_id,
+                &write.name,
+                hash,
+            )
+            .await?
+        {
+            self.blob_repository
+                .remove_reference(write.upload_id, &write.name, hash)
+                .await?;
+        }
+
+        self.pending_write_repository.delete(write.id).await
+    }
+
+    // Same as in `FileServiceImpl`, the file is stored already so failing would not help.
+    async fn record_usage(&self, owner: &str, stored_bytes: i64) {
+        if let Some(quotas) = &self.quotas
+            && let Err(e) = quotas.record(owner, stored_bytes, 0).await
+        {
+            counter!("usage_record_failures_total").increment(1);
+            warn!(owner, "Failed to record usage: {}", e);
+        }
+    }
+}
+
+pub(crate) fn holds(file_tree: &FileMerkleTree, name: &str, hash: Hash32) -> bool {
+    file_tree
+        .get_index_by_file_name(name)
+        .and_then(|index| file_tree.get_leaf_hash_by_index(index))
+        == Some(hash)
+}
+
+/// Whether a completed version of the upload holds the file named `name` with `hash`, whose
+/// reference must then be kept.
+pub(crate) async fn held_by_versions(
+    file_repository: &dyn FileRepository,
+    upload_id: Uuid,
+    name: &str,
+    hash: Hash32,
+) -> anyhow::Result<bool> {
+    let Some(file_tree) = file_repository.get(upload_id).await? else {
+        return Ok(false);
+    };
+
+    let mut previous = file_tree.previous_version;
+    while let Some(version) = previous {
+        let Some(row) = file_repository.get_version(upload_id, version).await? else {
+            break;
+        };
+
+        let snapshot = FileMerkleTree::from(row);
+        if holds(&snapshot, name, hash) {
+            return Ok(true);
+        }
+        previous = snapshot.previous_version();
+    }
+
+    Ok(false)
+}
@@ -0,0 +1,107 @@
+mod helpers;
+
+use crate::helpers::mocks::{MockBlobRepositoryImpl, MockFileStorageImpl};
+use chrono::{Duration, Utc};
+use file_server_library::models::Hash32;
+use file_server_server::{
+    repositories::BlobRow,
+    services::{BlobCollector, BlobGcConfig},
+};
+use mockall::predicate::eq;
+use std::sync::Arc;
+
+fn unreferenced_blob() -> BlobRow {
+    BlobRow {
+        hash: Hash32::hash(b"contents_file_1"),
+        references: 0,
+        updated_at: Utc::now() - Duration::days(1),
+    }
+}
+
+fn listing(blob: BlobRow, current: BlobRow) -> MockBlobRepositoryImpl {
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_list_unreferenced()
+        .times(1)
+        .returning(move |_, _| Ok(vec![blob.clone()]));
+    blob_repository
+        .expect_get()
+        .returning(move |_| Ok(Some(current.clone())));
+    blob_repository
+}
+
+#[tokio::test]
+async fn test_collector_removes_unreferenced_blobs() {
+    let blob = unreferenced_blob();
+    let hash = blob.hash;
+
+    let mut blob_repository = listing(blob.clone(), blob);
+    blob_repository
+        .expect_delete()
+        .with(eq(hash))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_delete_blob()
+        .with(eq(hash))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let collector = BlobCollector::new(
+        Arc::new(blob_repository),
+        Arc::new(file_storage),
+        BlobGcConfig::default(),
+    );
+
+    let collected = collector.collect().await.unwrap();
+    assert_eq!(collected, 1);
+}
+
+#[tokio::test]
+async fn test_collector_skips_blobs_referenced_since_listed() {
+    let blob = unreferenced_blob();
+    let current = BlobRow {
+        references: 1,
+        updated_at: Utc::now(),
+        ..blob.clone()
+    };
+
+    let mut blob_repository = listing(blob, current);
+    blob_repository.expect_delete().times(0);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_delete_blob().times(0);
+
+    let collector = BlobCollector::new(
+        Arc::new(blob_repository),
+        Arc::new(file_storage),
+        BlobGcConfig::default(),
+    );
+
+    let collected = collector.collect().await.unwrap();
+    assert_eq!(collected, 0);
+}
+
+#[tokio::test]
+async fn test_collector_keeps_row_when_storage_delete_fails() {
+    let blob = unreferenced_blob();
+
+    let mut blob_repository = listing(blob.clone(), blob);
+    blob_repository.expect_delete().times(0);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_delete_blob()
+        .returning(|_| Err(anyhow::anyhow!("storage unavailable")));
+
+    let collector = BlobCollector::new(
+        Arc::new(blob_repository),
+        Arc::new(file_storage),
+        BlobGcConfig::default(),
+    );
+
+    let collected = collector.collect().await.unwrap();
+    assert_eq!(collected, 0);
+}
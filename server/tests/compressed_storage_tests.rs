@@ -0,0 +1,259 @@
+mod helpers;
+
+use crate::helpers::{
+    memory_storage::MemoryFileStorage,
+    storage_fixtures::{chunked, log_lines, read},
+};
+use async_compression::tokio::bufread::ZstdDecoder;
+use file_server_library::models::Hash32;
+use file_server_server::repositories::{
+    CompressedFileStorage, CompressionConfig, ContentEncoding, EncryptedFileStorage, FileStorage,
+    FileStream, MasterKeys,
+};
+use futures::stream;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+use tokio_util::io::StreamReader;
+use uuid::Uuid;
+
+fn config() -> CompressionConfig {
+    CompressionConfig {
+        enabled: true,
+        ..Default::default()
+    }
+}
+
+fn compressed(inner: Arc<dyn FileStorage>, config: CompressionConfig) -> CompressedFileStorage {
+    CompressedFileStorage::new(inner, config)
+}
+
+async fn decompress(content: FileStream) -> Vec<u8> {
+    let mut decoder = ZstdDecoder::new(StreamReader::new(content));
+    decoder.multiple_members(true);
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed).await.unwrap();
+    decompressed
+}
+
+#[tokio::test]
+async fn test_contents_are_compressed_and_read_back() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let storage = compressed(inner.clone(), config());
+    let id = Uuid::new_v4();
+    let contents = log_lines();
+
+    storage
+        .insert_file_content(id, "log.json", "application/json", None, chunked(&contents))
+        .await
+        .unwrap();
+
+    let stored = inner
+        .get_file_metadata(id, "log.json")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.content_encoding, Some(ContentEncoding::Zstd));
+    assert!(stored.size < contents.len() as u64 / 4);
+    let raw = inner.raw_file(id, "log.json").unwrap();
+    assert_eq!(
+        decompress(Box::pin(stream::iter([Ok(raw.into())]))).await,
+        contents
+    );
+
+    let metadata = storage
+        .get_file_metadata(id, "log.json")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(metadata.size, contents.len() as u64);
+    assert_eq!(metadata.content_type, "application/json");
+    assert_eq!(metadata.content_encoding, None);
+    assert_eq!(
+        read(&storage, id, "log.json", None).await.unwrap(),
+        contents
+    );
+}
+
+#[tokio::test]
+async fn test_ranges_only_return_the_requested_bytes() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let storage = compressed(inner, config());
+    let id = Uuid::new_v4();
+    let contents = log_lines();
+    let last = contents.len() as u64 - 1;
+
+    storage
+        .insert_file_content(id, "log.json", "application/json", None, chunked(&contents))
+        .await
+        .unwrap();
+
+    for range in [
+        0..=0,
+        10..=99,
+        9_999..=10_000,
+        100..=last - 100,
+        last..=last,
+        0..=last,
+    ] {
+        let expected = &contents[*range.start() as usize..=*range.end() as usize];
+        let actual = read(&storage, id, "log.json", Some(range.clone()))
+            .await
+            .unwrap();
+        assert_eq!(actual, expected, "range {:?}", range);
+    }
+}
+
+#[tokio::test]
+async fn test_contents_below_the_threshold_are_stored_as_they_are() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let storage = compressed(
+        inner.clone(),
+        CompressionConfig {
+            min_size_bytes: 1024,
+            ..config()
+        },
+    );
+    let id = Uuid::new_v4();
+
+    for (name, contents) in [("small.txt", &b"small contents"[..]), ("empty.txt", b"")] {
+        storage
+            .insert_file_content(id, name, "text/plain", None, chunked(contents))
+            .await
+            .unwrap();
+
+        assert_eq!(inner.raw_file(id, name).unwrap(), contents);
+        let metadata = storage.get_file_metadata(id, name).await.unwrap().unwrap();
+        assert_eq!(metadata.size, contents.len() as u64);
+        assert_eq!(metadata.content_encoding, None);
+        assert_eq!(read(&storage, id, name, None).await.unwrap(), contents);
+    }
+}
+
+#[tokio::test]
+async fn test_compressed_contents_are_read_back_after_disabling_compression() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let id = Uuid::new_v4();
+    let contents = log_lines();
+
+    compressed(inner.clone(), config())
+        .insert_file_content(id, "old.json", "application/json", None, chunked(&contents))
+        .await
+        .unwrap();
+
+    let disabled = compressed(inner.clone(), CompressionConfig::default());
+    disabled
+        .insert_file_content(id, "new.json", "application/json", None, chunked(&contents))
+        .await
+        .unwrap();
+
+    assert_eq!(inner.raw_file(id, "new.json").unwrap(), contents);
+    assert_eq!(
+        read(&disabled, id, "old.json", None).await.unwrap(),
+        contents
+    );
+    assert_eq!(
+        read(&disabled, id, "new.json", None).await.unwrap(),
+        contents
+    );
+}
+
+#[tokio::test]
+async fn test_encoded_blob_contents_are_returned_as_they_are_stored() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let storage = compressed(inner.clone(), config());
+    let id = Uuid::new_v4();
+    let contents = log_lines();
+    let hash = Hash32::hash(&contents);
+    let small_hash = Hash32::hash(b"small");
+
+    storage
+        .insert_file_content(id, "log.json", "application/json", None, chunked(&contents))
+        .await
+        .unwrap();
+    storage.move_to_blob(id, "log.json", hash).await.unwrap();
+    storage
+        .insert_file_content(id, "small.txt", "text/plain", None, chunked(b"small"))
+        .await
+        .unwrap();
+    storage
+        .move_to_blob(id, "small.txt", small_hash)
+        .await
+        .unwrap();
+
+    let encoded = storage
+        .get_encoded_blob_content(hash)
+        .await
+        .unwrap()
+        .unwrap();
+    let stored = inner.get_blob_metadata(hash).await.unwrap().unwrap();
+    assert_eq!(encoded.encoding, ContentEncoding::Zstd);
+    assert_eq!(encoded.size, stored.size);
+    assert_eq!(decompress(encoded.content).await, contents);
+
+    assert!(
+        storage
+            .get_encoded_blob_content(small_hash)
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        storage
+            .get_encoded_blob_content(Hash32::hash(b"missing"))
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn test_contents_are_compressed_before_being_encrypted() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let encrypted = Arc::new(EncryptedFileStorage::new(
+        inner.clone(),
+        MasterKeys::new("key-1", [1; 32]),
+    ));
+    let storage = compressed(encrypted, config());
+    let id = Uuid::new_v4();
+    let contents = log_lines();
+
+    storage
+        .insert_file_content(id, "log.json", "application/json", None, chunked(&contents))
+        .await
+        .unwrap();
+
+    let raw = inner.raw_file(id, "log.json").unwrap();
+    assert!(raw.starts_with(b"FSE1"));
+    assert!(raw.len() < contents.len() / 4);
+
+    let metadata = storage
+        .get_file_metadata(id, "log.json")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(metadata.size, contents.len() as u64);
+    assert_eq!(
+        read(&storage, id, "log.json", Some(1000..=1999))
+            .await
+            .unwrap(),
+        &contents[1000..=1999]
+    );
+}
+
+#[tokio::test]
+async fn test_missing_size_frame_is_reported_as_corrupted() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let storage = compressed(inner.clone(), config());
+    let id = Uuid::new_v4();
+    let contents = log_lines();
+
+    storage
+        .insert_file_content(id, "log.json", "application/json", None, chunked(&contents))
+        .await
+        .unwrap();
+    let raw = inner.raw_file(id, "log.json").unwrap();
+    inner.replace_raw_file(id, "log.json", raw[..raw.len() - 16].to_vec());
+
+    let error = storage.get_file_metadata(id, "log.json").await.unwrap_err();
+    assert!(error.to_string().contains("is corrupted"));
+}
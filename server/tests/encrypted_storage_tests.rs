@@ -0,0 +1,343 @@
+mod helpers;
+
+use crate::helpers::{
+    memory_storage::MemoryFileStorage,
+    storage_fixtures::{chunked, read, segmented_contents},
+};
+use file_server_library::models::Hash32;
+use file_server_server::repositories::{
+    EncryptedFileStorage, EncryptionConfig, FileStorage, MasterKeys,
+};
+use futures::TryStreamExt;
+use std::{io, sync::Arc};
+use uuid::Uuid;
+
+const SEGMENT_SIZE_BYTES: usize = 64 * 1024;
+
+fn encrypted(inner: &Arc<MemoryFileStorage>, master_keys: MasterKeys) -> EncryptedFileStorage {
+    EncryptedFileStorage::new(Arc::clone(inner) as Arc<dyn FileStorage>, master_keys)
+}
+
+#[tokio::test]
+async fn test_contents_are_encrypted_and_read_back() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let storage = encrypted(&inner, MasterKeys::new("key-1", [1; 32]));
+    let id = Uuid::new_v4();
+    let contents = segmented_contents(SEGMENT_SIZE_BYTES);
+
+    storage
+        .insert_file_content(
+            id,
+            "file.bin",
+            "application/octet-stream",
+            None,
+            chunked(&contents),
+        )
+        .await
+        .unwrap();
+
+    let raw = inner.raw_file(id, "file.bin").unwrap();
+    assert!(raw.starts_with(b"FSE1"));
+    assert!(!raw.windows(64).any(|window| window == &contents[..64]));
+
+    let metadata = storage
+        .get_file_metadata(id, "file.bin")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(metadata.size, contents.len() as u64);
+    assert_eq!(metadata.content_type, "application/octet-stream");
+    assert_eq!(
+        read(&storage, id, "file.bin", None).await.unwrap(),
+        contents
+    );
+}
+
+#[tokio::test]
+async fn test_ranges_only_return_the_requested_plaintext() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let storage = encrypted(&inner, MasterKeys::new("key-1", [1; 32]));
+    let id = Uuid::new_v4();
+    let contents = segmented_contents(SEGMENT_SIZE_BYTES);
+    let last = contents.len() as u64 - 1;
+    let segment = SEGMENT_SIZE_BYTES as u64;
+
+    storage
+        .insert_file_content(
+            id,
+            "file.bin",
+            "application/octet-stream",
+            None,
+            chunked(&contents),
+        )
+        .await
+        .unwrap();
+
+    for range in [
+        0..=0,
+        10..=99,
+        segment - 1..=segment,
+        segment..=2 * segment - 1,
+        100..=3 * segment + 5,
+        last..=last,
+        0..=last,
+    ] {
+        let expected = &contents[*range.start() as usize..=*range.end() as usize];
+        let actual = read(&storage, id, "file.bin", Some(range.clone()))
+            .await
+            .unwrap();
+        assert_eq!(actual, expected, "range {:?}", range);
+    }
+}
+
+#[tokio::test]
+async fn test_empty_contents_and_blobs_are_encrypted() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let storage = encrypted(&inner, MasterKeys::new("key-1", [1; 32]));
+    let id = Uuid::new_v4();
+    let hash = Hash32::hash(b"");
+
+    storage
+        .insert_file_content(id, "empty.txt", "text/plain", None, chunked(b""))
+        .await
+        .unwrap();
+    storage.move_to_blob(id, "empty.txt", hash).await.unwrap();
+
+    let metadata = storage.get_blob_metadata(hash).await.unwrap().unwrap();
+    assert_eq!(metadata.size, 0);
+
+    let contents: Vec<u8> = storage
+        .get_blob_content(hash, None)
+        .await
+        .unwrap()
+        .unwrap()
+        .map_ok(|chunk| chunk.to_vec())
+        .try_concat()
+        .await
+        .unwrap();
+    assert!(contents.is_empty());
+}
+
+#[tokio::test]
+async fn test_contents_stay_readable_after_rotating_the_master_key() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let id = Uuid::new_v4();
+    let contents = segmented_contents(SEGMENT_SIZE_BYTES);
+
+    let before = encrypted(&inner, MasterKeys::new("key-1", [1; 32]));
+    before
+        .insert_file_content(
+            id,
+            "old.bin",
+            "application/octet-stream",
+            None,
+            chunked(&contents),
+        )
+        .await
+        .unwrap();
+
+    let after = encrypted(
+        &inner,
+        MasterKeys::new("key-2", [2; 32]).with_retired_key("key-1", [1; 32]),
+    );
+    after
+        .insert_file_content(
+            id,
+            "new.bin",
+            "application/octet-stream",
+            None,
+            chunked(&contents),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(read(&after, id, "old.bin", None).await.unwrap(), contents);
+    assert_eq!(read(&after, id, "new.bin", None).await.unwrap(), contents);
+    assert!(before.get_file_metadata(id, "new.bin").await.is_err());
+}
+
+#[tokio::test]
+async fn test_contents_stored_before_encryption_are_read_as_they_are() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let id = Uuid::new_v4();
+    let contents = segmented_contents(SEGMENT_SIZE_BYTES);
+
+    inner
+        .insert_file_content(id, "old.bin", "text/plain", None, chunked(&contents))
+        .await
+        .unwrap();
+    inner
+        .insert_file_content(id, "short.txt", "text/plain", None, chunked(b"ab"))
+        .await
+        .unwrap();
+
+    let storage = encrypted(&inner, MasterKeys::new("key-1", [1; 32])).with_plaintext_reads();
+
+    let metadata = storage
+        .get_file_metadata(id, "old.bin")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(metadata.size, contents.len() as u64);
+    assert_eq!(metadata.content_type, "text/plain");
+    assert_eq!(read(&storage, id, "old.bin", None).await.unwrap(), contents);
+    assert_eq!(
+        read(&storage, id, "old.bin", Some(70_000..=70_099))
+            .await
+            .unwrap(),
+        &contents[70_000..70_100]
+    );
+    assert_eq!(read(&storage, id, "short.txt", None).await.unwrap(), b"ab");
+
+    // Moved into their blob by the legacy migration, still as they are.
+    let hash = Hash32::hash(&contents);
+    storage.move_to_blob(id, "old.bin", hash).await.unwrap();
+    let metadata = storage.get_blob_metadata(hash).await.unwrap().unwrap();
+    assert_eq!(metadata.size, contents.len() as u64);
+}
+
+#[tokio::test]
+async fn test_plaintext_contents_fail_unless_plaintext_reads_are_allowed() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let id = Uuid::new_v4();
+
+    inner
+        .insert_file_content(id, "old.txt", "text/plain", None, chunked(b"plaintext"))
+        .await
+        .unwrap();
+    inner
+        .insert_file_content(id, "short.txt", "text/plain", None, chunked(b"ab"))
+        .await
+        .unwrap();
+
+    let storage = encrypted(&inner, MasterKeys::new("key-1", [1; 32]));
+    assert!(storage.get_file_metadata(id, "old.txt").await.is_err());
+    assert!(storage.get_file_metadata(id, "short.txt").await.is_err());
+    assert!(storage.get_file_content(id, "old.txt", None).await.is_err());
+}
+
+#[tokio::test]
+async fn test_contents_are_stored_as_they_are_without_master_keys() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let storage = EncryptedFileStorage::unencrypted(Arc::clone(&inner) as Arc<dyn FileStorage>);
+    let id = Uuid::new_v4();
+
+    storage
+        .insert_file_content(id, "file.txt", "text/plain", None, chunked(b"plaintext"))
+        .await
+        .unwrap();
+    assert_eq!(inner.raw_file(id, "file.txt").unwrap(), b"plaintext");
+    assert_eq!(
+        read(&storage, id, "file.txt", None).await.unwrap(),
+        b"plaintext"
+    );
+
+    // Encrypted while master keys were configured, never served as ciphertext.
+    encrypted(&inner, MasterKeys::new("key-1", [1; 32]))
+        .insert_file_content(id, "file.bin", "text/plain", None, chunked(b"secret"))
+        .await
+        .unwrap();
+    assert!(storage.get_file_metadata(id, "file.bin").await.is_err());
+    assert!(
+        storage
+            .get_file_content(id, "file.bin", None)
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_tampered_contents_fail_to_decrypt() {
+    let inner = Arc::new(MemoryFileStorage::default());
+    let storage = encrypted(&inner, MasterKeys::new("key-1", [1; 32]));
+    let id = Uuid::new_v4();
+    let contents = segmented_contents(SEGMENT_SIZE_BYTES);
+
+    storage
+        .insert_file_content(
+            id,
+            "file.bin",
+            "application/octet-stream",
+            None,
+            chunked(&contents),
+        )
+        .await
+        .unwrap();
+    let raw = inner.raw_file(id, "file.bin").unwrap();
+
+    let mut flipped = raw.clone();
+    let middle = flipped.len() / 2;
+    flipped[middle] ^= 1;
+    inner.replace_raw_file(id, "file.bin", flipped);
+    let error = read(&storage, id, "file.bin", None).await.unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+    // Dropping the last segment leaves whole segments behind, none of them flagged as the last.
+    let truncated = raw[..raw.len() - (1234 + 16)].to_vec();
+    inner.replace_raw_file(id, "file.bin", truncated);
+    let error = read(&storage, id, "file.bin", None).await.unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_master_keys_are_loaded_from_config_and_key_file() {
+    let key_file = std::env::temp_dir().join(format!("master-keys-{}", Uuid::new_v4()));
+    std::fs::write(
+        &key_file,
+        format!("# rotated\n\nkey-2:{}\n", hex::encode([2u8; 32])),
+    )
+    .unwrap();
+
+    let master_keys = MasterKeys::from_config(EncryptionConfig {
+        master_keys: vec![format!("key-1:{}", hex::encode([1u8; 32]))],
+        key_file: Some(key_file.to_string_lossy().to_string()),
+        active_key_id: Some("key-2".to_string()),
+        ..Default::default()
+    })
+    .unwrap()
+    .unwrap();
+    assert_eq!(master_keys.active_key_id(), "key-2");
+
+    let first_by_default = MasterKeys::from_config(EncryptionConfig {
+        master_keys: vec![format!("key-1:{}", hex::encode([1u8; 32]))],
+        ..Default::default()
+    })
+    .unwrap()
+    .unwrap();
+    assert_eq!(first_by_default.active_key_id(), "key-1");
+
+    std::fs::remove_file(key_file).unwrap();
+}
+
+#[test]
+fn test_master_keys_config_errors() {
+    let key = hex::encode([1u8; 32]);
+
+    assert!(
+        MasterKeys::from_config(EncryptionConfig::default())
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        MasterKeys::from_config(EncryptionConfig {
+            master_keys: vec![format!("key-1:{key}")],
+            active_key_id: Some("key-2".to_string()),
+            ..Default::default()
+        })
+        .is_err()
+    );
+    assert!(
+        MasterKeys::from_config(EncryptionConfig {
+            master_keys: vec![format!("key-1:{key}"), format!("key-1:{key}")],
+            ..Default::default()
+        })
+        .is_err()
+    );
+    assert!(
+        MasterKeys::from_config(EncryptionConfig {
+            master_keys: vec!["key-1:abcd".to_string()],
+            ..Default::default()
+        })
+        .is_err()
+    );
+}
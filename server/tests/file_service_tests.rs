@@ -0,0 +1,2296 @@
+mod helpers;
+
+use crate::helpers::{
+    mocks::{
+        MockBlobRepositoryImpl, MockFileRepositoryImpl, MockFileStorageImpl,
+        MockPendingWriteRepositoryImpl, MockTransparencyLogServiceImpl,
+        MockUploadSessionRepositoryImpl, MockUsageRepositoryImpl, MockWebhookRepositoryImpl,
+    },
+    tree_fixtures::{last_updated_at, tree_row, with_file},
+};
+use bytes::Bytes;
+use chrono::Utc;
+use file_server_library::{CustomMerkleTree, models::Hash32};
+use file_server_server::{
+    models::{
+        ArchiveFormat, DeliveryState, FileEntry, FileMetadata, Quota, QuotaLimits, QuotaViolation,
+        UploadCursor, UploadEvent, UploadFilter, UploadState, WebhookEvent,
+    },
+    repositories::{
+        BlobReferenceRow, FileMerkleTreeRow, FileStream, LogEntryRow, StoredFileMetadata, UsageRow,
+    },
+    services::{
+        FileService, FileServiceError, FileServiceImpl, QuotaConfig, Quotas, TreeCache,
+        UploadEvents, WebhookConfig, WebhookEndpoint, Webhooks,
+    },
+};
+use futures::{StreamExt, TryStreamExt, executor::block_on, stream};
+use mockall::predicate::eq;
+use std::{
+    collections::HashMap,
+    io::{self, Cursor, Read},
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+fn initiated_row(id: Uuid) -> FileMerkleTreeRow {
+    tree_row(id, UploadState::Initiated)
+}
+
+fn file_metadata(name: &str) -> FileMetadata {
+    FileMetadata {
+        name: name.to_string(),
+        index: 0,
+        content_type: None,
+    }
+}
+
+fn chunked(chunks: Vec<io::Result<Bytes>>) -> FileStream {
+    Box::pin(stream::iter(chunks))
+}
+
+fn quotas(limits: QuotaLimits, usage_repository: MockUsageRepositoryImpl) -> Arc<Quotas> {
+    Arc::new(Quotas::new(
+        QuotaConfig {
+            default: limits,
+            ..Default::default()
+        },
+        Arc::new(usage_repository),
+    ))
+}
+
+fn usage(stored_bytes: i64, open_uploads: i64) -> MockUsageRepositoryImpl {
+    let mut usage_repository = MockUsageRepositoryImpl::new();
+    usage_repository.expect_get().returning(move |owner| {
+        Ok(UsageRow {
+            owner: owner.to_string(),
+            stored_bytes,
+            open_uploads,
+        })
+    });
+    usage_repository
+}
+
+#[tokio::test]
+async fn test_upload_file_hashes_streamed_content() {
+    let id = Uuid::new_v4();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .with(eq(id))
+        .returning(move |id| Ok(Some(initiated_row(id))));
+    file_repository
+        .expect_update_if_unchanged()
+        .withf(|row, _| row.sizes == vec![15] && row.content_types == vec!["text/plain"])
+        .times(1)
+        .returning(|_, _| Ok(true));
+
+    let expected_hash = Hash32::hash(b"contents_file_1");
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_insert_file_content()
+        .withf(move |tree_id, name, content_type, content_encoding, _| {
+            *tree_id == id
+                && name.starts_with(".staging/")
+                && content_type == "text/plain"
+                && content_encoding.is_none()
+        })
+        .times(1)
+        .returning(|_, _, _, _, content| {
+            block_on(content.try_collect::<Vec<_>>())?;
+            Ok(())
+        });
+    file_storage
+        .expect_move_to_blob()
+        .withf(move |tree_id, name, hash| {
+            *tree_id == id && name.starts_with(".staging/") && *hash == expected_hash
+        })
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_touch()
+        .with(eq(expected_hash))
+        .times(1)
+        .returning(|_| Ok(()));
+    blob_repository
+        .expect_add_reference()
+        .withf(move |reference| {
+            reference.hash == expected_hash
+                && reference.upload_id == id
+                && reference.name == "file1.txt"
+                && reference.content_type == "text/plain"
+        })
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    );
+
+    let content = chunked(vec![
+        Ok(Bytes::from_static(b"contents_")),
+        Ok(Bytes::from_static(b"file_")),
+        Ok(Bytes::from_static(b"1")),
+    ]);
+    let encoded_hash = service
+        .upload_file("client-1", id, file_metadata("file1.txt"), content)
+        .await
+        .unwrap();
+
+    assert_eq!(encoded_hash, expected_hash.to_hex());
+}
+
+#[tokio::test]
+async fn test_upload_file_exceeding_body_limit_returns_payload_too_large() {
+    let id = Uuid::new_v4();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(initiated_row(id))));
+    file_repository.expect_update_if_unchanged().times(0);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_insert_file_content()
+        .times(1)
+        .returning(|_, _, _, _, content| {
+            block_on(content.try_collect::<Vec<_>>())?;
+            Ok(())
+        });
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository.expect_add_reference().times(0);
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    );
+
+    let content = chunked(vec![
+        Ok(Bytes::from_static(b"contents_")),
+        Err(io::Error::new(
+            io::ErrorKind::FileTooLarge,
+            "length limit exceeded",
+        )),
+    ]);
+    let result = service
+        .upload_file("client-1", id, file_metadata("file1.txt"), content)
+        .await;
+
+    assert!(matches!(result, Err(FileServiceError::PayloadTooLarge)));
+}
+
+#[tokio::test]
+async fn test_upload_file_with_name_held_by_another_index_returns_file_already_exists() {
+    let id = Uuid::new_v4();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(move |id| {
+        let mut row = initiated_row(id);
+        row.order = vec!["file1.txt".to_string()];
+        row.leaf_hashes = vec![Hash32::hash(b"contents_file_1")];
+        Ok(Some(row))
+    });
+    file_repository.expect_update_if_unchanged().times(0);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_insert_file_content().times(0);
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    );
+
+    let metadata = FileMetadata {
+        index: 1,
+        ..file_metadata("file1.txt")
+    };
+    let content = chunked(vec![Ok(Bytes::from_static(b"contents_file_2"))]);
+    let result = service.upload_file("client-1", id, metadata, content).await;
+
+    assert!(matches!(result, Err(FileServiceError::FileAlreadyExists)));
+}
+
+#[tokio::test]
+async fn test_upload_replacing_file_of_index_drops_its_name() {
+    let id = Uuid::new_v4();
+    let previous_hash = Hash32::hash(b"contents_file_1");
+    let hash = Hash32::hash(b"contents_file_2");
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(move |id| {
+        let mut row = initiated_row(id);
+        row.order = vec!["file1.txt".to_string()];
+        row.files = HashMap::from([("file1.txt".to_string(), previous_hash)]);
+        row.leaf_hashes = vec![previous_hash];
+        Ok(Some(row))
+    });
+    file_repository
+        .expect_update_if_unchanged()
+        .withf(move |row, _| {
+            row.order == vec!["file2.txt"]
+                && row.files == HashMap::from([("file2.txt".to_string(), hash)])
+                && row.leaf_hashes == vec![hash]
+        })
+        .times(1)
+        .returning(|_, _| Ok(true));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_get_blob_metadata().returning(|_| {
+        Ok(Some(StoredFileMetadata {
+            size: 15,
+            content_type: "text/plain".to_string(),
+            content_encoding: None,
+        }))
+    });
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_is_referenced_by()
+        .returning(|_, _| Ok(true));
+    blob_repository.expect_touch().returning(|_| Ok(()));
+    blob_repository.expect_add_reference().returning(|_| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    );
+
+    service
+        .upload_known_file("client-1", id, file_metadata("file2.txt"), hash)
+        .await
+        .unwrap();
+}
+
+// Upload holding `file1.txt` with `contents_file_1` at index 0 until `file2.txt` replaces it,
+// read once before the replacement and from then on after it.
+fn replacing_upload(previous_version: Option<u32>) -> MockFileRepositoryImpl {
+    let previous_hash = Hash32::hash(b"contents_file_1");
+    let hash = Hash32::hash(b"contents_file_2");
+    let holding = move |id, name: &str, hash| {
+        let mut row = with_file(initiated_row(id), name, hash);
+        row.files = HashMap::from([(name.to_string(), hash)]);
+        row.version = previous_version.map_or(1, |version| version + 1);
+        row.previous_version = previous_version;
+        row
+    };
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .times(1)
+        .returning(move |id| Ok(Some(holding(id, "file1.txt", previous_hash))));
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(holding(id, "file2.txt", hash))));
+    file_repository
+        .expect_update_if_unchanged()
+        .times(1)
+        .returning(|_, _| Ok(true));
+    file_repository
+}
+
+fn known_blob_references() -> (MockFileStorageImpl, MockBlobRepositoryImpl) {
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_get_blob_metadata().returning(|_| {
+        Ok(Some(StoredFileMetadata {
+            size: 15,
+            content_type: "text/plain".to_string(),
+            content_encoding: None,
+        }))
+    });
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_is_referenced_by()
+        .returning(|_, _| Ok(true));
+    blob_repository.expect_touch().returning(|_| Ok(()));
+    blob_repository.expect_add_reference().returning(|_| Ok(()));
+
+    (file_storage, blob_repository)
+}
+
+#[tokio::test]
+async fn test_upload_replacing_file_drops_reference_of_replaced_blob() {
+    let id = Uuid::new_v4();
+    let previous_hash = Hash32::hash(b"contents_file_1");
+
+    // Without its last reference the replaced blob is left to the collector.
+    let (file_storage, mut blob_repository) = known_blob_references();
+    blob_repository
+        .expect_remove_reference()
+        .with(eq(id), eq("file1.txt"), eq(previous_hash))
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(replacing_upload(None)),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    );
+
+    let hash = Hash32::hash(b"contents_file_2");
+    service
+        .upload_known_file("client-1", id, file_metadata("file2.txt"), hash)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_upload_replacing_file_keeps_reference_held_by_completed_version() {
+    let id = Uuid::new_v4();
+    let previous_hash = Hash32::hash(b"contents_file_1");
+
+    let mut file_repository = replacing_upload(Some(1));
+    file_repository
+        .expect_get_version()
+        .with(eq(id), eq(1))
+        .returning(move |id, _| {
+            let mut row = with_file(
+                tree_row(id, UploadState::Completed),
+                "file1.txt",
+                previous_hash,
+            );
+            row.files = HashMap::from([("file1.txt".to_string(), previous_hash)]);
+            Ok(Some(row))
+        });
+
+    let (file_storage, mut blob_repository) = known_blob_references();
+    blob_repository.expect_remove_reference().times(0);
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    );
+
+    let hash = Hash32::hash(b"contents_file_2");
+    service
+        .upload_known_file("client-1", id, file_metadata("file2.txt"), hash)
+        .await
+        .unwrap();
+}
+
+// Storage and blob references of an upload receiving `contents_file_1` without failing.
+fn receiving_upload(id: Uuid) -> (MockFileStorageImpl, MockBlobRepositoryImpl) {
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_insert_file_content()
+        .returning(|_, _, _, _, content| {
+            block_on(content.try_collect::<Vec<_>>())?;
+            Ok(())
+        });
+    file_storage
+        .expect_move_to_blob()
+        .withf(move |tree_id, _, _| *tree_id == id)
+        .returning(|_, _, _| Ok(()));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository.expect_touch().returning(|_| Ok(()));
+    blob_repository.expect_add_reference().returning(|_| Ok(()));
+
+    (file_storage, blob_repository)
+}
+
+#[tokio::test]
+async fn test_upload_file_to_upload_expired_while_receiving_returns_upload_expired() {
+    let id = Uuid::new_v4();
+    let read_at = Utc::now() - chrono::Duration::minutes(5);
+
+    // The reaper expires the upload once it was read.
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .times(1)
+        .returning(move |id| Ok(Some(last_updated_at(initiated_row(id), read_at))));
+    file_repository
+        .expect_get()
+        .returning(|id| Ok(Some(tree_row(id, UploadState::Expired))));
+    file_repository
+        .expect_update_if_unchanged()
+        .withf(move |_, updated_at| *updated_at == read_at)
+        .times(1)
+        .returning(|_, _| Ok(false));
+
+    let (file_storage, mut blob_repository) = receiving_upload(id);
+    blob_repository
+        .expect_remove_reference()
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    );
+
+    let content = chunked(vec![Ok(Bytes::from_static(b"contents_file_1"))]);
+    let result = service
+        .upload_file("client-1", id, file_metadata("file1.txt"), content)
+        .await;
+
+    assert!(matches!(result, Err(FileServiceError::UploadExpired)));
+}
+
+#[tokio::test]
+async fn test_upload_file_keeps_files_added_while_receiving() {
+    let id = Uuid::new_v4();
+    let read_at = Utc::now() - chrono::Duration::minutes(5);
+    let changed_at = Utc::now() - chrono::Duration::minutes(1);
+    let other_hash = Hash32::hash(b"contents_file_2");
+
+    // Another file is added to the upload once it was read.
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .times(1)
+        .returning(move |id| Ok(Some(last_updated_at(initiated_row(id), read_at))));
+    file_repository.expect_get().returning(move |id| {
+        let mut row = with_file(initiated_row(id), "file2.txt", other_hash);
+        row.files = HashMap::from([("file2.txt".to_string(), other_hash)]);
+        Ok(Some(last_updated_at(row, changed_at)))
+    });
+    file_repository
+        .expect_update_if_unchanged()
+        .withf(move |_, updated_at| *updated_at == read_at)
+        .times(1)
+        .returning(|_, _| Ok(false));
+    file_repository
+        .expect_update_if_unchanged()
+        .withf(move |row, updated_at| {
+            *updated_at == changed_at && row.order == vec!["file2.txt", "file1.txt"]
+        })
+        .times(1)
+        .returning(|_, _| Ok(true));
+
+    let (file_storage, blob_repository) = receiving_upload(id);
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    );
+
+    let metadata = FileMetadata {
+        index: 1,
+        ..file_metadata("file1.txt")
+    };
+    let content = chunked(vec![Ok(Bytes::from_static(b"contents_file_1"))]);
+    let encoded_hash = service
+        .upload_file("client-1", id, metadata, content)
+        .await
+        .unwrap();
+
+    assert_eq!(encoded_hash, Hash32::hash(b"contents_file_1").to_hex());
+}
+
+#[tokio::test]
+async fn test_upload_file_to_upload_deleted_while_receiving_rolls_write_back() {
+    let id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+
+    // The upload is deleted once it was read, references included.
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .times(1)
+        .returning(move |id| Ok(Some(initiated_row(id))));
+    file_repository.expect_get().returning(|_| Ok(None));
+    file_repository
+        .expect_update_if_unchanged()
+        .times(1)
+        .returning(|_, _| Ok(false));
+
+    let (file_storage, mut blob_repository) = receiving_upload(id);
+    blob_repository
+        .expect_remove_reference()
+        .with(eq(id), eq("file1.txt"), eq(hash))
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+
+    let mut pending_write_repository = MockPendingWriteRepositoryImpl::new();
+    pending_write_repository
+        .expect_insert()
+        .times(1)
+        .returning(|_| Ok(()));
+    pending_write_repository
+        .expect_mark_stored()
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+    pending_write_repository
+        .expect_delete()
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    )
+    .with_pending_writes(Arc::new(pending_write_repository));
+
+    let content = chunked(vec![Ok(Bytes::from_static(b"contents_file_1"))]);
+    let result = service
+        .upload_file("client-1", id, file_metadata("file1.txt"), content)
+        .await;
+
+    assert!(matches!(result, Err(FileServiceError::FileNotFound)));
+}
+
+#[tokio::test]
+async fn test_delete_starts_over_when_file_is_added_meanwhile() {
+    let id = Uuid::new_v4();
+    let read_at = Utc::now() - chrono::Duration::minutes(5);
+    let changed_at = Utc::now() - chrono::Duration::minutes(1);
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .times(1)
+        .returning(move |id| Ok(Some(last_updated_at(initiated_row(id), read_at))));
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(last_updated_at(initiated_row(id), changed_at))));
+    file_repository
+        .expect_delete_if_unchanged()
+        .with(eq(id), eq(read_at))
+        .times(1)
+        .returning(|_, _| Ok(false));
+    file_repository
+        .expect_delete_if_unchanged()
+        .with(eq(id), eq(changed_at))
+        .times(1)
+        .returning(|_, _| Ok(true));
+
+    // References are removed again, along with the one of the file added meanwhile.
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_delete_prefix()
+        .times(2)
+        .returning(|_| Ok(()));
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_remove_references()
+        .with(eq(id))
+        .times(2)
+        .returning(|_| Ok(()));
+    let mut upload_session_repository = MockUploadSessionRepositoryImpl::new();
+    upload_session_repository
+        .expect_delete_by_upload()
+        .times(2)
+        .returning(|_| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(upload_session_repository),
+        Arc::new(blob_repository),
+    );
+
+    service.delete("client-1", id).await.unwrap();
+}
+
+// Storage and repositories of an upload whose contents hash to `contents_file_1`, every step
+// succeeding unless `fail` says otherwise.
+fn journaled_upload(
+    id: Uuid,
+    fail_storing: bool,
+    fail_updating: bool,
+) -> (
+    MockFileRepositoryImpl,
+    MockFileStorageImpl,
+    MockBlobRepositoryImpl,
+) {
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(initiated_row(id))));
+    file_repository
+        .expect_update_if_unchanged()
+        .returning(move |_, _| {
+            if fail_updating {
+                Err(anyhow::anyhow!("repository is unavailable"))
+            } else {
+                Ok(true)
+            }
+        });
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_insert_file_content()
+        .returning(move |_, _, _, _, content| {
+            block_on(content.try_collect::<Vec<_>>())?;
+            if fail_storing {
+                anyhow::bail!("storage is unavailable");
+            }
+            Ok(())
+        });
+    file_storage
+        .expect_move_to_blob()
+        .withf(move |tree_id, _, _| *tree_id == id)
+        .returning(|_, _, _| Ok(()));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository.expect_touch().returning(|_| Ok(()));
+    blob_repository.expect_add_reference().returning(|_| Ok(()));
+
+    (file_repository, file_storage, blob_repository)
+}
+
+#[tokio::test]
+async fn test_upload_file_journals_write_until_file_is_added() {
+    let id = Uuid::new_v4();
+    let expected_hash = Hash32::hash(b"contents_file_1");
+    let (file_repository, file_storage, blob_repository) = journaled_upload(id, false, false);
+
+    let journaled = Arc::new(Mutex::new(None));
+    let mut pending_write_repository = MockPendingWriteRepositoryImpl::new();
+    let inserted = Arc::clone(&journaled);
+    pending_write_repository
+        .expect_insert()
+        .withf(move |row| {
+            row.upload_id == id
+                && row.name == "file1.txt"
+                && row.hash.is_none()
+                && row
+                    .staging_name
+                    .as_ref()
+                    .is_some_and(|name| name.starts_with(".staging/"))
+        })
+        .times(1)
+        .returning(move |row| {
+            *inserted.lock().unwrap() = Some(row.id);
+            Ok(())
+        });
+    let marked = Arc::clone(&journaled);
+    pending_write_repository
+        .expect_mark_stored()
+        .withf(move |write_id, hash, size| {
+            Some(*write_id) == *marked.lock().unwrap() && *hash == expected_hash && *size == 15
+        })
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+    let deleted = Arc::clone(&journaled);
+    pending_write_repository
+        .expect_delete()
+        .withf(move |write_id| Some(*write_id) == *deleted.lock().unwrap())
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    )
+    .with_pending_writes(Arc::new(pending_write_repository));
+
+    let content = chunked(vec![Ok(Bytes::from_static(b"contents_file_1"))]);
+    service
+        .upload_file("client-1", id, file_metadata("file1.txt"), content)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_upload_file_failing_to_update_upload_leaves_stored_write_in_journal() {
+    let id = Uuid::new_v4();
+    let (file_repository, file_storage, blob_repository) = journaled_upload(id, false, true);
+
+    let mut pending_write_repository = MockPendingWriteRepositoryImpl::new();
+    pending_write_repository
+        .expect_insert()
+        .times(1)
+        .returning(|_| Ok(()));
+    pending_write_repository
+        .expect_mark_stored()
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+    pending_write_repository.expect_delete().times(0);
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    )
+    .with_pending_writes(Arc::new(pending_write_repository));
+
+    let content = chunked(vec![Ok(Bytes::from_static(b"contents_file_1"))]);
+    let result = service
+        .upload_file("client-1", id, file_metadata("file1.txt"), content)
+        .await;
+
+    assert!(matches!(result, Err(FileServiceError::StorageError(_))));
+}
+
+#[tokio::test]
+async fn test_upload_file_failing_to_store_contents_leaves_unstored_write_in_journal() {
+    let id = Uuid::new_v4();
+    let (file_repository, file_storage, blob_repository) = journaled_upload(id, true, false);
+
+    let mut pending_write_repository = MockPendingWriteRepositoryImpl::new();
+    pending_write_repository
+        .expect_insert()
+        .times(1)
+        .returning(|_| Ok(()));
+    pending_write_repository.expect_mark_stored().times(0);
+    pending_write_repository.expect_delete().times(0);
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    )
+    .with_pending_writes(Arc::new(pending_write_repository));
+
+    let content = chunked(vec![Ok(Bytes::from_static(b"contents_file_1"))]);
+    let result = service
+        .upload_file("client-1", id, file_metadata("file1.txt"), content)
+        .await;
+
+    assert!(matches!(result, Err(FileServiceError::StorageError(_))));
+}
+
+#[tokio::test]
+async fn test_upload_file_failing_to_journal_stores_nothing() {
+    let id = Uuid::new_v4();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(initiated_row(id))));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_insert_file_content().times(0);
+
+    let mut pending_write_repository = MockPendingWriteRepositoryImpl::new();
+    pending_write_repository
+        .expect_insert()
+        .times(1)
+        .returning(|_| Err(anyhow::anyhow!("repository is unavailable")));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    )
+    .with_pending_writes(Arc::new(pending_write_repository));
+
+    let content = chunked(vec![Ok(Bytes::from_static(b"contents_file_1"))]);
+    let result = service
+        .upload_file("client-1", id, file_metadata("file1.txt"), content)
+        .await;
+
+    assert!(matches!(result, Err(FileServiceError::StorageError(_))));
+}
+
+#[tokio::test]
+async fn test_upload_known_file_references_stored_blob() {
+    let id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(initiated_row(id))));
+    file_repository
+        .expect_update_if_unchanged()
+        .withf(move |tree, _| tree.leaf_hashes == vec![hash])
+        .times(1)
+        .returning(|_, _| Ok(true));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_insert_file_content().times(0);
+    file_storage
+        .expect_get_blob_metadata()
+        .with(eq(hash))
+        .returning(|_| {
+            Ok(Some(StoredFileMetadata {
+                size: 15,
+                content_type: "text/plain".to_string(),
+                content_encoding: None,
+            }))
+        });
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_is_referenced_by()
+        .with(eq(hash), eq("client-1"))
+        .times(1)
+        .returning(|_, _| Ok(true));
+    blob_repository
+        .expect_touch()
+        .with(eq(hash))
+        .times(1)
+        .returning(|_| Ok(()));
+    blob_repository
+        .expect_add_reference()
+        .withf(move |reference| {
+            reference.hash == hash && reference.owner == "client-1" && reference.name == "file1.csv"
+        })
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    );
+
+    let encoded_hash = service
+        .upload_known_file("client-1", id, file_metadata("file1.csv"), hash)
+        .await
+        .unwrap();
+
+    assert_eq!(encoded_hash, hash.to_hex());
+}
+
+#[tokio::test]
+async fn test_upload_known_file_with_unknown_blob_returns_blob_not_found() {
+    let id = Uuid::new_v4();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(initiated_row(id))));
+    file_repository.expect_update_if_unchanged().times(0);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_get_blob_metadata()
+        .returning(|_| Ok(None));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_is_referenced_by()
+        .returning(|_, _| Ok(true));
+    blob_repository.expect_touch().returning(|_| Ok(()));
+    blob_repository.expect_add_reference().times(0);
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    );
+
+    let result = service
+        .upload_known_file(
+            "client-1",
+            id,
+            file_metadata("file1.txt"),
+            Hash32::hash(b"unknown"),
+        )
+        .await;
+
+    assert!(matches!(result, Err(FileServiceError::BlobNotFound)));
+}
+
+#[tokio::test]
+async fn test_upload_known_file_with_blob_of_another_client_returns_blob_not_found() {
+    let id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_of_client_2");
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(initiated_row(id))));
+    file_repository.expect_update_if_unchanged().times(0);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_get_blob_metadata().times(0);
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_is_referenced_by()
+        .with(eq(hash), eq("client-1"))
+        .returning(|_, _| Ok(false));
+    blob_repository.expect_touch().times(0);
+    blob_repository.expect_add_reference().times(0);
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    );
+
+    let result = service
+        .upload_known_file("client-1", id, file_metadata("file1.txt"), hash)
+        .await;
+
+    assert!(matches!(result, Err(FileServiceError::BlobNotFound)));
+}
+
+#[tokio::test]
+async fn test_blob_exists_only_for_blobs_referenced_by_owner() {
+    let hash = Hash32::hash(b"contents_file_1");
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_get_blob_metadata().returning(|_| {
+        Ok(Some(StoredFileMetadata {
+            size: 15,
+            content_type: "text/plain".to_string(),
+            content_encoding: None,
+        }))
+    });
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_is_referenced_by()
+        .returning(|_, owner| Ok(owner == "client-1"));
+
+    let service = FileServiceImpl::new(
+        Arc::new(MockFileRepositoryImpl::new()),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    );
+
+    assert!(service.blob_exists("client-1", hash).await.unwrap());
+    assert!(!service.blob_exists("client-2", hash).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_get_file_descriptor_uses_content_type_of_reference() {
+    let id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(move |id| {
+        let mut row = initiated_row(id);
+        row.order = vec!["file1.csv".to_string()];
+        row.leaf_hashes = vec![hash];
+        Ok(Some(row))
+    });
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_get_blob_metadata()
+        .with(eq(hash))
+        .returning(|_| {
+            Ok(Some(StoredFileMetadata {
+                size: 15,
+                content_type: "text/plain".to_string(),
+                content_encoding: None,
+            }))
+        });
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_get_reference()
+        .withf(move |upload_id, name, reference_hash| {
+            *upload_id == id && name == "file1.csv" && *reference_hash == hash
+        })
+        .returning(move |upload_id, name, _| {
+            Ok(Some(BlobReferenceRow {
+                hash,
+                upload_id,
+                owner: "client-1".to_string(),
+                name: name.to_string(),
+                content_type: "text/csv".to_string(),
+                created_at: Utc::now(),
+            }))
+        });
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    );
+
+    let descriptor = service
+        .get_file_descriptor("client-1", id, None, 0)
+        .await
+        .unwrap();
+
+    assert_eq!(descriptor.hash, hash);
+    assert_eq!(descriptor.size, 15);
+    assert_eq!(descriptor.content_type, "text/csv");
+}
+
+#[tokio::test]
+async fn test_delete_removes_blob_references() {
+    let id = Uuid::new_v4();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(initiated_row(id))));
+    file_repository
+        .expect_delete_if_unchanged()
+        .withf(move |tree_id, _| *tree_id == id)
+        .times(1)
+        .returning(|_, _| Ok(true));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_delete_prefix()
+        .with(eq(id))
+        .times(1)
+        .returning(|_| Ok(()));
+    file_storage.expect_delete_blob().times(0);
+
+    let mut upload_session_repository = MockUploadSessionRepositoryImpl::new();
+    upload_session_repository
+        .expect_delete_by_upload()
+        .returning(|_| Ok(()));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_remove_references()
+        .with(eq(id))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(upload_session_repository),
+        Arc::new(blob_repository),
+    );
+
+    service.delete("client-1", id).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_get_proof_of_completed_upload_verifies_against_root() {
+    let id = Uuid::new_v4();
+    let leaves: Vec<Hash32> = (0..3)
+        .map(|i| Hash32::hash(format!("contents_file_{i}").as_bytes()))
+        .collect();
+    let root = CustomMerkleTree::new(leaves.clone()).root();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    let row_leaves = leaves.clone();
+    file_repository.expect_get().times(2).returning(move |id| {
+        let mut row = initiated_row(id);
+        row.leaf_hashes = row_leaves.clone();
+        row.root = Some(root);
+        row.state = UploadState::Completed;
+        Ok(Some(row))
+    });
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    )
+    .with_tree_cache(TreeCache::new(1));
+
+    for (index, leaf) in leaves.iter().enumerate().take(2) {
+        let proof = service
+            .get_proof("client-1", id, None, index)
+            .await
+            .unwrap();
+        assert!(CustomMerkleTree::verify(leaf, &proof, &root));
+    }
+}
+
+#[tokio::test]
+async fn test_complete_appends_root_to_transparency_log() {
+    let id = Uuid::new_v4();
+    let leaves: Vec<Hash32> = (0..3)
+        .map(|i| Hash32::hash(format!("contents_file_{i}").as_bytes()))
+        .collect();
+    let root = CustomMerkleTree::new(leaves.clone()).root();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().times(1).returning(move |id| {
+        let mut row = initiated_row(id);
+        row.leaf_hashes = leaves.clone();
+        Ok(Some(row))
+    });
+    file_repository
+        .expect_update()
+        .withf(move |row| row.root == Some(root) && row.state == UploadState::Completed)
+        .times(1)
+        .returning(|_| Ok(()));
+    file_repository
+        .expect_insert_version()
+        .withf(move |row| row.version == 1 && row.root == Some(root))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut transparency_log = MockTransparencyLogServiceImpl::new();
+    transparency_log
+        .expect_append()
+        .with(eq(id), eq("client-1"), eq(root))
+        .times(1)
+        .returning(move |upload_id, owner, root| {
+            Ok(LogEntryRow {
+                index: 0,
+                upload_id,
+                owner: owner.to_string(),
+                root,
+                appended_at: Utc::now(),
+                leaf_hash: Hash32::empty(),
+            })
+        });
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    )
+    .with_transparency_log(Arc::new(transparency_log));
+
+    let completed = service.complete("client-1", id).await.unwrap();
+
+    assert_eq!(completed, root.to_hex());
+}
+
+#[tokio::test]
+async fn test_upload_file_to_completed_upload_returns_upload_already_completed() {
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(|id| {
+        let mut row = initiated_row(id);
+        row.state = UploadState::Completed;
+        Ok(Some(row))
+    });
+    file_repository.expect_update_if_unchanged().times(0);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_insert_file_content().times(0);
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    );
+
+    let content = chunked(vec![Ok(Bytes::from_static(b"contents_file_1"))]);
+    assert!(matches!(
+        service
+            .upload_file(
+                "client-1",
+                Uuid::new_v4(),
+                file_metadata("file1.txt"),
+                content
+            )
+            .await,
+        Err(FileServiceError::UploadAlreadyCompleted)
+    ));
+}
+
+#[tokio::test]
+async fn test_create_version_starts_from_files_of_completed_version() {
+    let id = Uuid::new_v4();
+    let leaves = vec![Hash32::hash(b"contents_file_1")];
+    let root = CustomMerkleTree::new(leaves.clone()).root();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    let row_leaves = leaves.clone();
+    file_repository.expect_get().times(1).returning(move |id| {
+        let mut row = initiated_row(id);
+        row.order = vec!["file1.txt".to_string()];
+        row.leaf_hashes = row_leaves.clone();
+        row.root = Some(root);
+        row.state = UploadState::Completed;
+        Ok(Some(row))
+    });
+    file_repository
+        .expect_update()
+        .withf(move |row| {
+            row.version == 2
+                && row.previous_version == Some(1)
+                && row.state == UploadState::Initiated
+                && row.root.is_none()
+                && row.leaf_hashes == leaves
+        })
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    );
+
+    let summary = service.create_version("client-1", id).await.unwrap();
+
+    assert_eq!(summary.version, 2);
+    assert_eq!(summary.previous_version, Some(1));
+    assert_eq!(summary.file_count, 1);
+}
+
+#[tokio::test]
+async fn test_create_version_of_initiated_upload_returns_upload_not_completed() {
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(|id| Ok(Some(initiated_row(id))));
+    file_repository.expect_update().times(0);
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    );
+
+    assert!(matches!(
+        service.create_version("client-1", Uuid::new_v4()).await,
+        Err(FileServiceError::UploadNotCompleted)
+    ));
+}
+
+#[tokio::test]
+async fn test_reads_of_previous_version_use_its_snapshot() {
+    let id = Uuid::new_v4();
+    let first_leaves = vec![Hash32::hash(b"contents_file_1")];
+    let first_root = CustomMerkleTree::new(first_leaves.clone()).root();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(|id| {
+        let mut row = initiated_row(id);
+        row.version = 2;
+        row.previous_version = Some(1);
+        row.order = vec!["file1.txt".to_string(), "file2.txt".to_string()];
+        row.leaf_hashes = vec![
+            Hash32::hash(b"contents_file_1_v2"),
+            Hash32::hash(b"contents_file_2"),
+        ];
+        Ok(Some(row))
+    });
+    file_repository
+        .expect_get_version()
+        .with(eq(id), eq(1))
+        .returning(move |id, _| {
+            let mut row = initiated_row(id);
+            row.order = vec!["file1.txt".to_string()];
+            row.leaf_hashes = first_leaves.clone();
+            row.root = Some(first_root);
+            row.state = UploadState::Completed;
+            Ok(Some(row))
+        });
+    file_repository
+        .expect_get_version()
+        .with(eq(id), eq(3))
+        .returning(|_, _| Ok(None));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    );
+
+    let first = service
+        .list_files("client-1", id, Some(1), 0, 10)
+        .await
+        .unwrap();
+    assert_eq!(first.total, 1);
+    assert_eq!(first.files[0].hash, Hash32::hash(b"contents_file_1"));
+
+    let latest = service
+        .list_files("client-1", id, Some(2), 0, 10)
+        .await
+        .unwrap();
+    assert_eq!(latest.total, 2);
+
+    let proof = service.get_proof("client-1", id, Some(1), 0).await.unwrap();
+    assert!(CustomMerkleTree::verify(
+        &Hash32::hash(b"contents_file_1"),
+        &proof,
+        &first_root
+    ));
+
+    assert!(matches!(
+        service.list_files("client-1", id, Some(3), 0, 10).await,
+        Err(FileServiceError::VersionNotFound)
+    ));
+}
+
+#[tokio::test]
+async fn test_list_files_skips_unused_indexes_and_paginates() {
+    let id = Uuid::new_v4();
+    let uploaded_at = Utc::now();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(move |id| {
+        let mut row = initiated_row(id);
+        row.order = vec![
+            "file0.txt".to_string(),
+            String::new(),
+            "file2.csv".to_string(),
+            "file3.txt".to_string(),
+        ];
+        row.leaf_hashes = (0..4)
+            .map(|i| Hash32::hash(format!("contents_file_{i}").as_bytes()))
+            .collect();
+        row.sizes = vec![15, 0, 15, 15];
+        row.content_types = vec![
+            "text/plain".to_string(),
+            String::new(),
+            "text/csv".to_string(),
+            "text/plain".to_string(),
+        ];
+        row.uploaded_at = vec![uploaded_at; 4];
+        Ok(Some(row))
+    });
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    );
+
+    let page = service
+        .list_files("client-1", id, None, 1, 1)
+        .await
+        .unwrap();
+
+    assert_eq!(page.total, 3);
+    assert_eq!(
+        page.files,
+        vec![FileEntry {
+            index: 2,
+            name: "file2.csv".to_string(),
+            hash: Hash32::hash(b"contents_file_2"),
+            size: 15,
+            content_type: "text/csv".to_string(),
+            uploaded_at,
+        }]
+    );
+}
+
+#[tokio::test]
+async fn test_operations_on_upload_of_another_owner_return_file_not_found() {
+    let id = Uuid::new_v4();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(initiated_row(id))));
+    file_repository.expect_update().times(0);
+    file_repository.expect_delete_if_unchanged().times(0);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_delete_prefix().times(0);
+    file_storage.expect_insert_file_content().times(0);
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    );
+
+    let content = chunked(vec![Ok(Bytes::from_static(b"contents"))]);
+    let results = [
+        service
+            .upload_file("client-2", id, file_metadata("file1.txt"), content)
+            .await
+            .map(drop),
+        service
+            .list_files("client-2", id, None, 0, 10)
+            .await
+            .map(drop),
+        service.get_proof("client-2", id, None, 0).await.map(drop),
+        service.complete("client-2", id).await.map(drop),
+        service.create_version("client-2", id).await.map(drop),
+        service.watch("client-2", id).await.map(drop),
+        service
+            .get_archive("client-2", id, None, ArchiveFormat::Tar)
+            .await
+            .map(drop),
+        service.delete("client-2", id).await,
+    ];
+
+    for result in results {
+        assert!(matches!(result, Err(FileServiceError::FileNotFound)));
+    }
+}
+
+#[tokio::test]
+async fn test_list_uploads_returns_cursor_when_more_uploads_exist() {
+    let created_at = Utc::now();
+    let rows: Vec<_> = (0..3)
+        .map(|i| {
+            let mut row = initiated_row(Uuid::new_v4());
+            row.created_at = created_at - chrono::Duration::minutes(i);
+            row.order = vec!["file1.txt".to_string()];
+            row.leaf_hashes = vec![Hash32::hash(b"contents_file_1")];
+            row.sizes = vec![15];
+            row
+        })
+        .collect();
+    let expected_cursor = UploadCursor {
+        created_at: rows[1].created_at,
+        id: rows[1].id,
+    };
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_list()
+        .withf(|owner, filter, after, limit| {
+            owner == "client-1"
+                && *filter == UploadFilter::default()
+                && after.is_none()
+                && *limit == 3
+        })
+        .times(1)
+        .returning(move |_, _, _, _| Ok(rows.clone()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    );
+
+    let page = service
+        .list_uploads("client-1", UploadFilter::default(), None, 2)
+        .await
+        .unwrap();
+
+    assert_eq!(page.uploads.len(), 2);
+    assert_eq!(page.uploads[0].file_count, 1);
+    assert_eq!(page.uploads[0].total_size, 15);
+    assert_eq!(page.next_cursor, Some(expected_cursor));
+}
+
+#[tokio::test]
+async fn test_list_uploads_last_page_has_no_cursor() {
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_list()
+        .returning(|_, _, _, _| Ok(vec![initiated_row(Uuid::new_v4())]));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    );
+
+    let page = service
+        .list_uploads("client-1", UploadFilter::default(), None, 2)
+        .await
+        .unwrap();
+
+    assert_eq!(page.uploads.len(), 1);
+    assert_eq!(page.next_cursor, None);
+}
+
+#[tokio::test]
+async fn test_get_file_index_finds_files_by_name() {
+    let id = Uuid::new_v4();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(move |id| {
+        let mut row = initiated_row(id);
+        row.order = vec![
+            "file0.txt".to_string(),
+            String::new(),
+            "dir/file 2.txt".to_string(),
+        ];
+        row.leaf_hashes = vec![Hash32::empty(); 3];
+        Ok(Some(row))
+    });
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    );
+
+    assert_eq!(
+        service
+            .get_file_index("client-1", id, None, "dir/file 2.txt")
+            .await
+            .unwrap(),
+        2
+    );
+    assert!(matches!(
+        service
+            .get_file_index("client-1", id, None, "missing.txt")
+            .await,
+        Err(FileServiceError::FileNotFound)
+    ));
+    assert!(matches!(
+        service.get_file_index("client-1", id, None, "").await,
+        Err(FileServiceError::FileNotFound)
+    ));
+}
+
+fn archived_upload(names: &[&str]) -> (FileServiceImpl, Hash32) {
+    let leaves: Vec<Hash32> = names
+        .iter()
+        .map(|name| Hash32::hash(format!("contents of {name}").as_bytes()))
+        .collect();
+    let root = CustomMerkleTree::new(leaves.clone()).root();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    let order: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+    file_repository.expect_get().returning(move |id| {
+        let mut row = initiated_row(id);
+        row.order = order.clone();
+        row.leaf_hashes = leaves.clone();
+        row.root = Some(root);
+        row.state = UploadState::Completed;
+        Ok(Some(row))
+    });
+
+    let contents: HashMap<Hash32, Bytes> = names
+        .iter()
+        .map(|name| {
+            let contents = Bytes::from(format!("contents of {name}"));
+            (Hash32::hash(&contents), contents)
+        })
+        .collect();
+    let sizes = contents.clone();
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_get_blob_metadata()
+        .returning(move |hash| {
+            Ok(sizes.get(&hash).map(|contents| StoredFileMetadata {
+                size: contents.len() as u64,
+                content_type: "text/plain".to_string(),
+                content_encoding: None,
+            }))
+        });
+    file_storage
+        .expect_get_blob_content()
+        .returning(move |hash, _| {
+            Ok(contents
+                .get(&hash)
+                .map(|contents| chunked(vec![Ok(contents.clone())])))
+        });
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    );
+
+    (service, root)
+}
+
+// Just enough of a tar reader to check the archives, pax headers only ever carry paths here.
+fn read_tar(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let mut entries = vec![];
+    let mut offset = 0;
+    let mut long_path = None;
+
+    while archive[offset..offset + 512].iter().any(|&b| b != 0) {
+        let header = &archive[offset..offset + 512];
+        let name =
+            String::from_utf8(header[..100].split(|&b| b == 0).next().unwrap().to_vec()).unwrap();
+        let size_field = std::str::from_utf8(&header[124..135]).unwrap();
+        let size = usize::from_str_radix(size_field, 8).unwrap();
+        let data = archive[offset + 512..offset + 512 + size].to_vec();
+        offset += 512 + size.div_ceil(512) * 512;
+
+        if header[156] == b'x' {
+            let record = String::from_utf8(data).unwrap();
+            let path = record.split_once("path=").unwrap().1.trim_end_matches('\n');
+            long_path = Some(path.to_string());
+        } else {
+            entries.push((long_path.take().unwrap_or(name), data));
+        }
+    }
+
+    entries
+}
+
+fn verify_manifest(manifest: &[u8], root: Hash32, names: &[&str]) {
+    let manifest: serde_json::Value = serde_json::from_slice(manifest).unwrap();
+    assert_eq!(manifest["root"], root.to_hex());
+
+    let files = manifest["files"].as_array().unwrap();
+    assert_eq!(files.len(), names.len());
+    for (index, (file, name)) in files.iter().zip(names).enumerate() {
+        assert_eq!(file["index"], index);
+        assert_eq!(file["name"], *name);
+        assert_eq!(file["path"], format!("files/{name}"));
+
+        let leaf = Hash32::from_hex(file["leaf_hash"].as_str().unwrap()).unwrap();
+        let proof = serde_json::from_value(file["proof"].clone()).unwrap();
+        assert!(CustomMerkleTree::verify(&leaf, &proof, &root));
+    }
+}
+
+#[tokio::test]
+async fn test_get_archive_as_tar_holds_manifest_and_files() {
+    let id = Uuid::new_v4();
+    let long_name = format!("{}/report.txt", "nested".repeat(20));
+    let names = ["file0.txt", "dir/file 1.txt", long_name.as_str()];
+    let (service, root) = archived_upload(&names);
+
+    let archive: Vec<u8> = service
+        .get_archive("client-1", id, None, ArchiveFormat::Tar)
+        .await
+        .unwrap()
+        .map_ok(|chunk| chunk.to_vec())
+        .try_concat()
+        .await
+        .unwrap();
+
+    assert_eq!(archive.len() % 512, 0);
+    let entries = read_tar(&archive);
+    assert_eq!(entries.len(), names.len() + 1);
+    assert_eq!(entries[0].0, "manifest.json");
+    verify_manifest(&entries[0].1, root, &names);
+    for ((path, contents), name) in entries[1..].iter().zip(names) {
+        assert_eq!(path, &format!("files/{name}"));
+        assert_eq!(contents, format!("contents of {name}").as_bytes());
+    }
+}
+
+#[tokio::test]
+async fn test_get_archive_as_zip_holds_manifest_and_files() {
+    let id = Uuid::new_v4();
+    let names = ["file0.txt", "dir/résumé 1.txt"];
+    let (service, root) = archived_upload(&names);
+
+    let archive: Vec<u8> = service
+        .get_archive("client-1", id, None, ArchiveFormat::Zip)
+        .await
+        .unwrap()
+        .map_ok(|chunk| chunk.to_vec())
+        .try_concat()
+        .await
+        .unwrap();
+
+    let mut zip = zip::ZipArchive::new(Cursor::new(archive)).unwrap();
+    assert_eq!(zip.len(), names.len() + 1);
+
+    let mut manifest = vec![];
+    zip.by_name("manifest.json")
+        .unwrap()
+        .read_to_end(&mut manifest)
+        .unwrap();
+    verify_manifest(&manifest, root, &names);
+
+    for name in names {
+        // Reading to the end checks the CRC as well.
+        let mut contents = vec![];
+        zip.by_name(&format!("files/{name}"))
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, format!("contents of {name}").as_bytes());
+    }
+}
+
+#[tokio::test]
+async fn test_get_archive_of_initiated_upload_returns_upload_not_completed() {
+    let id = Uuid::new_v4();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(initiated_row(id))));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    );
+
+    assert!(matches!(
+        service
+            .get_archive("client-1", id, None, ArchiveFormat::Tar)
+            .await,
+        Err(FileServiceError::UploadNotCompleted)
+    ));
+}
+
+#[tokio::test]
+async fn test_initiate_beyond_open_uploads_quota_returns_quota_exceeded() {
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_insert().times(0);
+
+    let mut usage_repository = usage(0, 2);
+    usage_repository.expect_add().times(0);
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    )
+    .with_quotas(quotas(
+        QuotaLimits {
+            max_open_uploads: Some(2),
+            ..Default::default()
+        },
+        usage_repository,
+    ));
+
+    let result = service.initiate("client-1").await;
+
+    assert!(matches!(
+        result,
+        Err(FileServiceError::QuotaExceeded(QuotaViolation {
+            quota: Quota::OpenUploads,
+            limit: 2,
+            used: 3,
+        }))
+    ));
+}
+
+#[tokio::test]
+async fn test_upload_file_beyond_stored_bytes_quota_returns_quota_exceeded() {
+    let id = Uuid::new_v4();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(initiated_row(id))));
+    file_repository.expect_update_if_unchanged().times(0);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_insert_file_content()
+        .times(1)
+        .returning(|_, _, _, _, content| {
+            block_on(content.try_collect::<Vec<_>>())?;
+            Ok(())
+        });
+
+    let mut usage_repository = usage(10, 0);
+    usage_repository.expect_add().times(0);
+
+    // The file size limit leaves more room than what is left of the stored bytes.
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    )
+    .with_quotas(quotas(
+        QuotaLimits {
+            max_stored_bytes: Some(20),
+            max_file_size_bytes: Some(100),
+            ..Default::default()
+        },
+        usage_repository,
+    ));
+
+    let content = chunked(vec![
+        Ok(Bytes::from_static(b"contents_")),
+        Ok(Bytes::from_static(b"file_")),
+        Ok(Bytes::from_static(b"1")),
+    ]);
+    let result = service
+        .upload_file("client-1", id, file_metadata("file1.txt"), content)
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(FileServiceError::QuotaExceeded(QuotaViolation {
+            quota: Quota::StoredBytes,
+            limit: 20,
+            used: 24,
+        }))
+    ));
+}
+
+#[tokio::test]
+async fn test_upload_known_file_records_stored_bytes() {
+    let id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(initiated_row(id))));
+    file_repository
+        .expect_update_if_unchanged()
+        .times(1)
+        .returning(|_, _| Ok(true));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_get_blob_metadata().returning(|_| {
+        Ok(Some(StoredFileMetadata {
+            size: 15,
+            content_type: "text/plain".to_string(),
+            content_encoding: None,
+        }))
+    });
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_is_referenced_by()
+        .returning(|_, _| Ok(true));
+    blob_repository.expect_touch().returning(|_| Ok(()));
+    blob_repository.expect_add_reference().returning(|_| Ok(()));
+
+    let mut usage_repository = usage(10, 1);
+    usage_repository
+        .expect_add()
+        .with(eq(UsageRow {
+            owner: "client-1".to_string(),
+            stored_bytes: 15,
+            open_uploads: 0,
+        }))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    )
+    .with_quotas(quotas(
+        QuotaLimits {
+            max_stored_bytes: Some(25),
+            ..Default::default()
+        },
+        usage_repository,
+    ));
+
+    service
+        .upload_known_file("client-1", id, file_metadata("file1.csv"), hash)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_upload_known_file_beyond_files_per_upload_quota_returns_quota_exceeded() {
+    let id = Uuid::new_v4();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(move |id| {
+        let mut row = initiated_row(id);
+        row.order = vec!["file0.txt".to_string()];
+        row.leaf_hashes = vec![Hash32::hash(b"contents_file_0")];
+        Ok(Some(row))
+    });
+    file_repository.expect_update_if_unchanged().times(0);
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository.expect_touch().times(0);
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    )
+    .with_quotas(quotas(
+        QuotaLimits {
+            max_files_per_upload: Some(1),
+            ..Default::default()
+        },
+        MockUsageRepositoryImpl::new(),
+    ));
+
+    let result = service
+        .upload_known_file(
+            "client-1",
+            id,
+            FileMetadata {
+                index: 1,
+                ..file_metadata("file1.txt")
+            },
+            Hash32::hash(b"contents"),
+        )
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(FileServiceError::QuotaExceeded(QuotaViolation {
+            quota: Quota::FilesPerUpload,
+            limit: 1,
+            used: 2,
+        }))
+    ));
+}
+
+#[tokio::test]
+async fn test_upload_known_file_replacing_file_at_files_per_upload_quota_succeeds() {
+    let id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(move |id| {
+        let mut row = initiated_row(id);
+        row.order = vec!["file0.txt".to_string()];
+        row.leaf_hashes = vec![Hash32::hash(b"contents_file_0")];
+        Ok(Some(row))
+    });
+    file_repository
+        .expect_update_if_unchanged()
+        .withf(move |row, _| row.order == vec!["file1.txt"] && row.leaf_hashes == vec![hash])
+        .times(1)
+        .returning(|_, _| Ok(true));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_get_blob_metadata().returning(|_| {
+        Ok(Some(StoredFileMetadata {
+            size: 15,
+            content_type: "text/plain".to_string(),
+            content_encoding: None,
+        }))
+    });
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_is_referenced_by()
+        .returning(|_, _| Ok(true));
+    blob_repository.expect_touch().returning(|_| Ok(()));
+    blob_repository.expect_add_reference().returning(|_| Ok(()));
+
+    let mut usage_repository = usage(0, 1);
+    usage_repository.expect_add().returning(|_| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    )
+    .with_quotas(quotas(
+        QuotaLimits {
+            max_files_per_upload: Some(1),
+            ..Default::default()
+        },
+        usage_repository,
+    ));
+
+    service
+        .upload_known_file("client-1", id, file_metadata("file1.txt"), hash)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_complete_takes_upload_off_open_uploads() {
+    let id = Uuid::new_v4();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(move |id| {
+        let mut row = initiated_row(id);
+        row.leaf_hashes = vec![Hash32::hash(b"contents_file_0")];
+        Ok(Some(row))
+    });
+    file_repository.expect_update().returning(|_| Ok(()));
+    file_repository
+        .expect_insert_version()
+        .returning(|_| Ok(()));
+
+    let mut usage_repository = MockUsageRepositoryImpl::new();
+    usage_repository
+        .expect_add()
+        .with(eq(UsageRow {
+            owner: "client-1".to_string(),
+            stored_bytes: 0,
+            open_uploads: -1,
+        }))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    )
+    .with_quotas(quotas(QuotaLimits::default(), usage_repository));
+
+    service.complete("client-1", id).await.unwrap();
+}
+
+fn webhooks(events: Option<&str>, webhook_repository: MockWebhookRepositoryImpl) -> Arc<Webhooks> {
+    let endpoint = WebhookEndpoint {
+        url: "http://localhost:9000/hooks".to_string(),
+        events: events.map(str::to_string),
+    };
+
+    Arc::new(Webhooks::new(
+        WebhookConfig {
+            clients: HashMap::from([("client-1".to_string(), endpoint)]),
+            ..Default::default()
+        },
+        Arc::new(webhook_repository),
+    ))
+}
+
+#[tokio::test]
+async fn test_complete_queues_webhook_delivery_for_owner() {
+    let id = Uuid::new_v4();
+    let leaf = Hash32::hash(b"contents_file_0");
+    let root = CustomMerkleTree::new(vec![leaf]).root();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(move |id| {
+        let mut row = initiated_row(id);
+        row.leaf_hashes = vec![leaf];
+        Ok(Some(row))
+    });
+    file_repository.expect_update().returning(|_| Ok(()));
+    file_repository
+        .expect_insert_version()
+        .returning(|_| Ok(()));
+
+    let mut webhook_repository = MockWebhookRepositoryImpl::new();
+    webhook_repository
+        .expect_insert()
+        .withf(move |delivery| {
+            let payload: serde_json::Value = serde_json::from_str(&delivery.payload).unwrap();
+
+            delivery.owner == "client-1"
+                && delivery.event == WebhookEvent::Completed
+                && delivery.upload_id == id
+                && delivery.url == "http://localhost:9000/hooks"
+                && delivery.state == DeliveryState::Pending
+                && delivery.attempts == 0
+                && payload["id"] == delivery.id.to_string()
+                && payload["event"] == "upload.completed"
+                && payload["data"]["root"] == root.to_hex()
+        })
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    )
+    .with_webhooks(webhooks(None, webhook_repository));
+
+    service.complete("client-1", id).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_delete_skips_webhook_events_owner_did_not_subscribe_to() {
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(initiated_row(id))));
+    file_repository
+        .expect_delete_if_unchanged()
+        .returning(|_, _| Ok(true));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_delete_prefix().returning(|_| Ok(()));
+
+    let mut upload_session_repository = MockUploadSessionRepositoryImpl::new();
+    upload_session_repository
+        .expect_delete_by_upload()
+        .returning(|_| Ok(()));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_remove_references()
+        .returning(|_| Ok(()));
+
+    let mut webhook_repository = MockWebhookRepositoryImpl::new();
+    webhook_repository.expect_insert().times(0);
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(upload_session_repository),
+        Arc::new(blob_repository),
+    )
+    .with_webhooks(webhooks(
+        Some("upload.completed, upload.expired"),
+        webhook_repository,
+    ));
+
+    service.delete("client-1", Uuid::new_v4()).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_watch_streams_events_of_upload_until_it_is_completed() {
+    let id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+    let root = CustomMerkleTree::new(vec![hash]).root();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(move |id| {
+        let mut row = initiated_row(id);
+        row.leaf_hashes = vec![hash];
+        Ok(Some(row))
+    });
+    file_repository.expect_update().returning(|_| Ok(()));
+    file_repository
+        .expect_update_if_unchanged()
+        .returning(|_, _| Ok(true));
+    file_repository
+        .expect_insert_version()
+        .returning(|_| Ok(()));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_insert_file_content()
+        .returning(|_, _, _, _, content| {
+            block_on(content.try_collect::<Vec<_>>())?;
+            Ok(())
+        });
+    file_storage
+        .expect_move_to_blob()
+        .returning(|_, _, _| Ok(()));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository.expect_touch().returning(|_| Ok(()));
+    blob_repository.expect_add_reference().returning(|_| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(blob_repository),
+    );
+
+    let events = service.watch("client-1", id).await.unwrap();
+
+    let content = chunked(vec![Ok(Bytes::from_static(b"contents_file_1"))]);
+    service
+        .upload_file("client-1", id, file_metadata("file1.txt"), content)
+        .await
+        .unwrap();
+    // Events of other uploads are left out.
+    service.complete("client-1", Uuid::new_v4()).await.unwrap();
+    service.complete("client-1", id).await.unwrap();
+
+    let events: Vec<_> = events.collect().await;
+    assert_eq!(
+        events,
+        vec![
+            UploadEvent::FileReceived {
+                index: 0,
+                name: "file1.txt".to_string(),
+                size: 15,
+            },
+            UploadEvent::HashComputed {
+                index: 0,
+                name: "file1.txt".to_string(),
+                hash,
+            },
+            UploadEvent::Completed { version: 1, root },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_watch_stream_ends_once_upload_is_deleted() {
+    let id = Uuid::new_v4();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(initiated_row(id))));
+    file_repository
+        .expect_delete_if_unchanged()
+        .returning(|_, _| Ok(true));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_delete_prefix().returning(|_| Ok(()));
+
+    let mut upload_session_repository = MockUploadSessionRepositoryImpl::new();
+    upload_session_repository
+        .expect_delete_by_upload()
+        .returning(|_| Ok(()));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_remove_references()
+        .returning(|_| Ok(()));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(upload_session_repository),
+        Arc::new(blob_repository),
+    );
+
+    let events = service.watch("client-1", id).await.unwrap();
+    service.delete("client-1", id).await.unwrap();
+
+    let events: Vec<_> = events.collect().await;
+    assert_eq!(events, vec![UploadEvent::Deleted]);
+}
+
+#[tokio::test]
+async fn test_watch_stream_ends_once_upload_expires() {
+    let upload_events = UploadEvents::default();
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(move |id| Ok(Some(initiated_row(id))));
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    )
+    .with_upload_events(upload_events.clone());
+
+    let id = Uuid::new_v4();
+    let events = service.watch("client-1", id).await.unwrap();
+    // Published by the upload reaper sharing the events.
+    upload_events.publish(id, UploadEvent::Expired);
+
+    let events: Vec<_> = events.collect().await;
+    assert_eq!(events, vec![UploadEvent::Expired]);
+}
+
+#[tokio::test]
+async fn test_watch_completed_upload_returns_its_completion() {
+    let root = Hash32::hash(b"root");
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(move |id| {
+        let mut row = initiated_row(id);
+        row.state = UploadState::Completed;
+        row.root = Some(root);
+        Ok(Some(row))
+    });
+
+    let service = FileServiceImpl::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(MockUploadSessionRepositoryImpl::new()),
+        Arc::new(MockBlobRepositoryImpl::new()),
+    );
+
+    let events: Vec<_> = service
+        .watch("client-1", Uuid::new_v4())
+        .await
+        .unwrap()
+        .collect()
+        .await;
+
+    assert_eq!(events, vec![UploadEvent::Completed { version: 1, root }]);
+}
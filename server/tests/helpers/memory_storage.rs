@@ -0,0 +1,155 @@
+// `InMemoryFileStorage` is only built along with the `in-memory` feature, storage decorators are
+// tested on top of this one instead. Stored bytes can be read and replaced as they are, to check
+// what actually reaches the storage.
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::Utc;
+use file_server_library::models::Hash32;
+use file_server_server::repositories::{
+    self, ContentEncoding, FileStorage, FileStream, StoredFileMetadata,
+};
+use futures::{TryStreamExt, stream};
+use std::{collections::HashMap, ops::RangeInclusive, sync::Mutex};
+use uuid::Uuid;
+
+// Contents, content type and content encoding.
+type StoredObject = (Vec<u8>, String, Option<ContentEncoding>);
+
+#[derive(Default)]
+pub struct MemoryFileStorage {
+    files: Mutex<HashMap<(Uuid, String), StoredObject>>,
+    blobs: Mutex<HashMap<Hash32, StoredObject>>,
+}
+
+impl MemoryFileStorage {
+    pub fn raw_file(&self, id: Uuid, name: &str) -> Option<Vec<u8>> {
+        let files = self.files.lock().unwrap();
+        files
+            .get(&(id, name.to_string()))
+            .map(|(data, _, _)| data.clone())
+    }
+
+    pub fn replace_raw_file(&self, id: Uuid, name: &str, data: Vec<u8>) {
+        let mut files = self.files.lock().unwrap();
+        if let Some(file) = files.get_mut(&(id, name.to_string())) {
+            file.0 = data;
+        }
+    }
+}
+
+fn metadata((data, content_type, content_encoding): &StoredObject) -> StoredFileMetadata {
+    StoredFileMetadata {
+        size: data.len() as u64,
+        content_type: content_type.clone(),
+        content_encoding: *content_encoding,
+    }
+}
+
+fn content((data, _, _): &StoredObject, range: Option<RangeInclusive<u64>>) -> FileStream {
+    let data = match range {
+        Some(range) => data[*range.start() as usize..=*range.end() as usize].to_vec(),
+        None => data.clone(),
+    };
+    Box::pin(stream::iter([Ok(Bytes::from(data))]))
+}
+
+#[async_trait]
+impl FileStorage for MemoryFileStorage {
+    async fn get_file_metadata(
+        &self,
+        id: Uuid,
+        name: &str,
+    ) -> anyhow::Result<Option<StoredFileMetadata>> {
+        let files = self.files.lock().unwrap();
+        Ok(files.get(&(id, name.to_string())).map(metadata))
+    }
+
+    async fn get_file_content(
+        &self,
+        id: Uuid,
+        name: &str,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>> {
+        let files = self.files.lock().unwrap();
+        Ok(files
+            .get(&(id, name.to_string()))
+            .map(|file| content(file, range)))
+    }
+
+    async fn insert_file_content(
+        &self,
+        id: Uuid,
+        name: &str,
+        content_type: &str,
+        content_encoding: Option<ContentEncoding>,
+        content: FileStream,
+    ) -> anyhow::Result<()> {
+        let data: Vec<u8> = content.map_ok(|chunk| chunk.to_vec()).try_concat().await?;
+
+        let mut files = self.files.lock().unwrap();
+        files.insert(
+            (id, name.to_string()),
+            (data, content_type.to_string(), content_encoding),
+        );
+        Ok(())
+    }
+
+    async fn delete_file_content(&self, id: Uuid, name: &str) -> anyhow::Result<()> {
+        let mut files = self.files.lock().unwrap();
+        files.remove(&(id, name.to_string()));
+        Ok(())
+    }
+
+    async fn delete_prefix(&self, id: Uuid) -> anyhow::Result<()> {
+        let mut files = self.files.lock().unwrap();
+        files.retain(|(file_id, _), _| *file_id != id);
+        Ok(())
+    }
+
+    // Nothing tested on top of this storage looks at how old objects are.
+    async fn list(&self, id: Uuid) -> anyhow::Result<Vec<repositories::StoredObject>> {
+        let files = self.files.lock().unwrap();
+        Ok(files
+            .iter()
+            .filter(|((file_id, _), _)| *file_id == id)
+            .map(|((_, name), (data, _, _))| repositories::StoredObject {
+                name: name.clone(),
+                size: data.len() as u64,
+                last_modified: Utc::now(),
+            })
+            .collect())
+    }
+
+    async fn get_blob_metadata(&self, hash: Hash32) -> anyhow::Result<Option<StoredFileMetadata>> {
+        let blobs = self.blobs.lock().unwrap();
+        Ok(blobs.get(&hash).map(metadata))
+    }
+
+    async fn get_blob_content(
+        &self,
+        hash: Hash32,
+        range: Option<RangeInclusive<u64>>,
+    ) -> anyhow::Result<Option<FileStream>> {
+        let blobs = self.blobs.lock().unwrap();
+        Ok(blobs.get(&hash).map(|blob| content(blob, range)))
+    }
+
+    async fn move_to_blob(&self, id: Uuid, name: &str, hash: Hash32) -> anyhow::Result<()> {
+        let file = self
+            .files
+            .lock()
+            .unwrap()
+            .remove(&(id, name.to_string()))
+            .ok_or_else(|| anyhow::anyhow!("missing object {id}/{name}"))?;
+
+        let mut blobs = self.blobs.lock().unwrap();
+        blobs.entry(hash).or_insert(file);
+        Ok(())
+    }
+
+    async fn delete_blob(&self, hash: Hash32) -> anyhow::Result<()> {
+        let mut blobs = self.blobs.lock().unwrap();
+        blobs.remove(&hash);
+        Ok(())
+    }
+}
@@ -1,9 +1,24 @@
-use bytes::Bytes;
-use file_server_library::models::Proof;
-use file_server_server::models::{FileContent, FileMetadata};
-use file_server_server::services::FileService;
-use file_server_server::services::FileServiceError;
+use chrono::{DateTime, Utc};
+use file_server_library::models::{Hash32, Proof};
+use file_server_server::models::{
+    ArchiveFormat, AuditFilter, FileDescriptor, FileMetadata, FilePage, LogConsistencyProof,
+    LogHead, LogInclusionProof, OrphanReport, ScrubReport, UploadCursor, UploadFilter, UploadPage,
+    UploadSession, UploadState, UploadSummary, Usage,
+};
+use file_server_server::repositories::{
+    AuditEntryRow, AuditRepository, BlobReferenceRow, BlobRepository, BlobRow, ContentEncoding,
+    EncodedContent, FileMerkleTreeRow, FileRepository, FileStorage, FileStream,
+    IdempotencyRepository, IdempotencyRow, LogEntryRow, PendingWriteRepository, PendingWriteRow,
+    ScrubFindingRow, ScrubRepository, StoredFileMetadata, StoredObject, TransparencyLogRepository,
+    UploadSessionRepository, UploadSessionRow, UsageRepository, UsageRow, WebhookDeliveryRow,
+    WebhookRepository,
+};
+use file_server_server::services::{
+    AdminService, FileService, TransparencyLogService, UploadSessionService,
+};
+use file_server_server::services::{FileServiceError, UploadEventStream};
 use mockall::mock;
+use std::ops::RangeInclusive;
 use uuid::Uuid;
 
 mock! {
@@ -11,19 +26,426 @@ mock! {
 
     #[async_trait::async_trait]
     impl FileService for FileServiceImpl {
+        async fn get_file_descriptor(
+            &self,
+            owner: &str,
+            id: Uuid,
+            version: Option<u32>,
+            index: usize,
+        ) -> Result<FileDescriptor, FileServiceError>;
         async fn get_file_content(
             &self,
+            owner: &str,
+            id: Uuid,
+            version: Option<u32>,
+            index: usize,
+            range: Option<RangeInclusive<u64>>,
+        ) -> Result<FileStream, FileServiceError>;
+        async fn get_encoded_file_content(
+            &self,
+            owner: &str,
             id: Uuid,
+            version: Option<u32>,
             index: usize,
-        ) -> Result<FileContent, FileServiceError>;
-        async fn get_proof(&self, id: Uuid, index: usize) -> Result<Proof, FileServiceError>;
-        async fn initiate(&self) -> Result<Uuid, FileServiceError>;
+        ) -> Result<Option<EncodedContent>, FileServiceError>;
+        async fn get_proof(
+            &self,
+            owner: &str,
+            id: Uuid,
+            version: Option<u32>,
+            index: usize,
+        ) -> Result<Proof, FileServiceError>;
+        async fn get_file_index(
+            &self,
+            owner: &str,
+            id: Uuid,
+            version: Option<u32>,
+            name: &str,
+        ) -> Result<usize, FileServiceError>;
+        async fn list_files(
+            &self,
+            owner: &str,
+            id: Uuid,
+            version: Option<u32>,
+            offset: usize,
+            limit: usize,
+        ) -> Result<FilePage, FileServiceError>;
+        async fn get_archive(
+            &self,
+            owner: &str,
+            id: Uuid,
+            version: Option<u32>,
+            format: ArchiveFormat,
+        ) -> Result<FileStream, FileServiceError>;
+        async fn list_uploads(
+            &self,
+            owner: &str,
+            filter: UploadFilter,
+            cursor: Option<UploadCursor>,
+            limit: usize,
+        ) -> Result<UploadPage, FileServiceError>;
+        async fn initiate(&self, owner: &str) -> Result<Uuid, FileServiceError>;
         async fn upload_file(
+            &self,
+            owner: &str,
+            id: Uuid,
+            metadata: FileMetadata,
+            content: FileStream,
+        ) -> Result<String, FileServiceError>;
+        async fn upload_known_file(
+            &self,
+            owner: &str,
+            id: Uuid,
+            metadata: FileMetadata,
+            hash: Hash32,
+        ) -> Result<String, FileServiceError>;
+        async fn blob_exists(&self, owner: &str, hash: Hash32) -> Result<bool, FileServiceError>;
+        async fn complete(&self, owner: &str, id: Uuid) -> Result<String, FileServiceError>;
+        async fn create_version(
+            &self,
+            owner: &str,
+            id: Uuid,
+        ) -> Result<UploadSummary, FileServiceError>;
+        async fn delete(&self, owner: &str, id: Uuid) -> Result<(), FileServiceError>;
+        async fn watch(&self, owner: &str, id: Uuid) -> Result<UploadEventStream, FileServiceError>;
+        async fn get_usage(&self, owner: &str) -> Result<Usage, FileServiceError>;
+        async fn list_webhook_deliveries(
+            &self,
+            owner: &str,
+            offset: usize,
+            limit: usize,
+        ) -> Result<Vec<WebhookDeliveryRow>, FileServiceError>;
+        async fn list_audit_entries(
+            &self,
+            owner: &str,
+            filter: AuditFilter,
+            after: Option<u64>,
+            limit: usize,
+        ) -> Result<Vec<AuditEntryRow>, FileServiceError>;
+    }
+}
+
+mock! {
+    pub FileRepositoryImpl {}
+
+    #[async_trait::async_trait]
+    impl FileRepository for FileRepositoryImpl {
+        async fn get(&self, id: Uuid) -> anyhow::Result<Option<FileMerkleTreeRow>>;
+        async fn insert(&self, tree: FileMerkleTreeRow) -> anyhow::Result<()>;
+        async fn update(&self, tree: FileMerkleTreeRow) -> anyhow::Result<()>;
+        async fn update_if_unchanged(
+            &self,
+            tree: FileMerkleTreeRow,
+            updated_at: DateTime<Utc>,
+        ) -> anyhow::Result<bool>;
+        async fn delete_if_unchanged(
+            &self,
+            id: Uuid,
+            updated_at: DateTime<Utc>,
+        ) -> anyhow::Result<bool>;
+        async fn insert_version(&self, tree: FileMerkleTreeRow) -> anyhow::Result<()>;
+        async fn get_version(
             &self,
             id: Uuid,
+            version: u32,
+        ) -> anyhow::Result<Option<FileMerkleTreeRow>>;
+        async fn list_stale(
+            &self,
+            state: UploadState,
+            updated_before: DateTime<Utc>,
+            limit: usize,
+        ) -> anyhow::Result<Vec<FileMerkleTreeRow>>;
+        async fn list(
+            &self,
+            owner: &str,
+            filter: &UploadFilter,
+            after: Option<UploadCursor>,
+            limit: usize,
+        ) -> anyhow::Result<Vec<FileMerkleTreeRow>>;
+        async fn list_completed(
+            &self,
+            after: Option<UploadCursor>,
+            limit: usize,
+        ) -> anyhow::Result<Vec<FileMerkleTreeRow>>;
+        async fn list_all(
+            &self,
+            after: Option<UploadCursor>,
+            limit: usize,
+        ) -> anyhow::Result<Vec<FileMerkleTreeRow>>;
+    }
+}
+
+mock! {
+    pub FileStorageImpl {}
+
+    #[async_trait::async_trait]
+    impl FileStorage for FileStorageImpl {
+        async fn get_file_metadata(
+            &self,
+            id: Uuid,
+            name: &str,
+        ) -> anyhow::Result<Option<StoredFileMetadata>>;
+        async fn get_file_content(
+            &self,
+            id: Uuid,
+            name: &str,
+            range: Option<RangeInclusive<u64>>,
+        ) -> anyhow::Result<Option<FileStream>>;
+        async fn insert_file_content(
+            &self,
+            id: Uuid,
+            name: &str,
+            content_type: &str,
+            content_encoding: Option<ContentEncoding>,
+            content: FileStream,
+        ) -> anyhow::Result<()>;
+        async fn delete_file_content(&self, id: Uuid, name: &str) -> anyhow::Result<()>;
+        async fn delete_prefix(&self, id: Uuid) -> anyhow::Result<()>;
+        async fn list(&self, id: Uuid) -> anyhow::Result<Vec<StoredObject>>;
+        async fn get_blob_metadata(
+            &self,
+            hash: Hash32,
+        ) -> anyhow::Result<Option<StoredFileMetadata>>;
+        async fn get_blob_content(
+            &self,
+            hash: Hash32,
+            range: Option<RangeInclusive<u64>>,
+        ) -> anyhow::Result<Option<FileStream>>;
+        async fn move_to_blob(&self, id: Uuid, name: &str, hash: Hash32) -> anyhow::Result<()>;
+        async fn delete_blob(&self, hash: Hash32) -> anyhow::Result<()>;
+        async fn get_encoded_blob_content(
+            &self,
+            hash: Hash32,
+        ) -> anyhow::Result<Option<EncodedContent>>;
+    }
+}
+
+mock! {
+    pub UploadSessionServiceImpl {}
+
+    #[async_trait::async_trait]
+    impl UploadSessionService for UploadSessionServiceImpl {
+        async fn create(
+            &self,
+            owner: &str,
+            upload_id: Uuid,
             metadata: FileMetadata,
-            content: Bytes,
+            length: Option<u64>,
+        ) -> Result<UploadSession, FileServiceError>;
+        async fn get(
+            &self,
+            owner: &str,
+            upload_id: Uuid,
+            session_id: Uuid,
+        ) -> Result<UploadSession, FileServiceError>;
+        async fn append(
+            &self,
+            owner: &str,
+            upload_id: Uuid,
+            session_id: Uuid,
+            offset: u64,
+            content: FileStream,
+        ) -> Result<UploadSession, FileServiceError>;
+        async fn finalize(
+            &self,
+            owner: &str,
+            upload_id: Uuid,
+            session_id: Uuid,
         ) -> Result<String, FileServiceError>;
-        async fn complete(&self, id: Uuid) -> Result<String, FileServiceError>;
+    }
+}
+
+mock! {
+    pub UploadSessionRepositoryImpl {}
+
+    #[async_trait::async_trait]
+    impl UploadSessionRepository for UploadSessionRepositoryImpl {
+        async fn get(&self, id: Uuid) -> anyhow::Result<Option<UploadSessionRow>>;
+        async fn insert(&self, session: UploadSessionRow) -> anyhow::Result<()>;
+        async fn update_if_offset(
+            &self,
+            session: UploadSessionRow,
+            offset: u64,
+        ) -> anyhow::Result<bool>;
+        async fn delete(&self, id: Uuid) -> anyhow::Result<()>;
+        async fn delete_by_upload(&self, upload_id: Uuid) -> anyhow::Result<()>;
+    }
+}
+
+mock! {
+    pub BlobRepositoryImpl {}
+
+    #[async_trait::async_trait]
+    impl BlobRepository for BlobRepositoryImpl {
+        async fn get(&self, hash: Hash32) -> anyhow::Result<Option<BlobRow>>;
+        async fn touch(&self, hash: Hash32) -> anyhow::Result<()>;
+        async fn delete(&self, hash: Hash32) -> anyhow::Result<()>;
+        async fn get_reference(
+            &self,
+            upload_id: Uuid,
+            name: &str,
+            hash: Hash32,
+        ) -> anyhow::Result<Option<BlobReferenceRow>>;
+        async fn add_reference(&self, reference: BlobReferenceRow) -> anyhow::Result<()>;
+        async fn is_referenced_by(&self, hash: Hash32, owner: &str) -> anyhow::Result<bool>;
+        async fn remove_reference(
+            &self,
+            upload_id: Uuid,
+            name: &str,
+            hash: Hash32,
+        ) -> anyhow::Result<()>;
+        async fn remove_references(&self, upload_id: Uuid) -> anyhow::Result<()>;
+        async fn list_unreferenced(
+            &self,
+            updated_before: DateTime<Utc>,
+            limit: usize,
+        ) -> anyhow::Result<Vec<BlobRow>>;
+    }
+}
+
+mock! {
+    pub ScrubRepositoryImpl {}
+
+    #[async_trait::async_trait]
+    impl ScrubRepository for ScrubRepositoryImpl {
+        async fn add_finding(&self, finding: ScrubFindingRow) -> anyhow::Result<()>;
+        async fn list_findings(
+            &self,
+            offset: usize,
+            limit: usize,
+        ) -> anyhow::Result<Vec<ScrubFindingRow>>;
+        async fn get_cursor(&self) -> anyhow::Result<Option<UploadCursor>>;
+        async fn set_cursor(&self, cursor: Option<UploadCursor>) -> anyhow::Result<()>;
+    }
+}
+
+mock! {
+    pub AdminServiceImpl {}
+
+    #[async_trait::async_trait]
+    impl AdminService for AdminServiceImpl {
+        async fn list_scrub_findings(
+            &self,
+            offset: usize,
+            limit: usize,
+        ) -> Result<ScrubReport, FileServiceError>;
+        async fn collect_orphans(
+            &self,
+            after: Option<UploadCursor>,
+            limit: usize,
+            dry_run: bool,
+        ) -> Result<OrphanReport, FileServiceError>;
+    }
+}
+
+mock! {
+    pub TransparencyLogRepositoryImpl {}
+
+    #[async_trait::async_trait]
+    impl TransparencyLogRepository for TransparencyLogRepositoryImpl {
+        async fn append(&self, entry: LogEntryRow) -> anyhow::Result<bool>;
+        async fn size(&self) -> anyhow::Result<u64>;
+        async fn latest_for_upload(&self, upload_id: Uuid) -> anyhow::Result<Option<LogEntryRow>>;
+        async fn leaf_hashes(&self, size: u64) -> anyhow::Result<Vec<Hash32>>;
+    }
+}
+
+mock! {
+    pub UsageRepositoryImpl {}
+
+    #[async_trait::async_trait]
+    impl UsageRepository for UsageRepositoryImpl {
+        async fn add(&self, change: UsageRow) -> anyhow::Result<()>;
+        async fn get(&self, owner: &str) -> anyhow::Result<UsageRow>;
+    }
+}
+
+mock! {
+    pub PendingWriteRepositoryImpl {}
+
+    #[async_trait::async_trait]
+    impl PendingWriteRepository for PendingWriteRepositoryImpl {
+        async fn insert(&self, row: PendingWriteRow) -> anyhow::Result<()>;
+        async fn mark_stored(&self, id: Uuid, hash: Hash32, size: u64) -> anyhow::Result<()>;
+        async fn delete(&self, id: Uuid) -> anyhow::Result<()>;
+        async fn list_before(
+            &self,
+            created_before: DateTime<Utc>,
+            limit: usize,
+        ) -> anyhow::Result<Vec<PendingWriteRow>>;
+    }
+}
+
+mock! {
+    pub WebhookRepositoryImpl {}
+
+    #[async_trait::async_trait]
+    impl WebhookRepository for WebhookRepositoryImpl {
+        async fn insert(&self, delivery: WebhookDeliveryRow) -> anyhow::Result<()>;
+        async fn update(&self, delivery: WebhookDeliveryRow) -> anyhow::Result<()>;
+        async fn list_due(
+            &self,
+            now: DateTime<Utc>,
+            limit: usize,
+        ) -> anyhow::Result<Vec<WebhookDeliveryRow>>;
+        async fn list_by_owner(
+            &self,
+            owner: &str,
+            offset: usize,
+            limit: usize,
+        ) -> anyhow::Result<Vec<WebhookDeliveryRow>>;
+    }
+}
+
+mock! {
+    pub IdempotencyRepositoryImpl {}
+
+    #[async_trait::async_trait]
+    impl IdempotencyRepository for IdempotencyRepositoryImpl {
+        async fn get(&self, owner: &str, key: &str) -> anyhow::Result<Option<IdempotencyRow>>;
+        async fn insert(&self, row: IdempotencyRow) -> anyhow::Result<()>;
+    }
+}
+
+mock! {
+    pub AuditRepositoryImpl {}
+
+    #[async_trait::async_trait]
+    impl AuditRepository for AuditRepositoryImpl {
+        async fn append(&self, entry: AuditEntryRow) -> anyhow::Result<bool>;
+        async fn latest(&self, owner: &str) -> anyhow::Result<Option<AuditEntryRow>>;
+        async fn list(
+            &self,
+            owner: &str,
+            filter: &AuditFilter,
+            after: Option<u64>,
+            limit: usize,
+        ) -> anyhow::Result<Vec<AuditEntryRow>>;
+    }
+}
+
+mock! {
+    pub TransparencyLogServiceImpl {}
+
+    #[async_trait::async_trait]
+    impl TransparencyLogService for TransparencyLogServiceImpl {
+        async fn append(
+            &self,
+            upload_id: Uuid,
+            owner: &str,
+            root: Hash32,
+        ) -> Result<LogEntryRow, FileServiceError>;
+        async fn head(&self) -> Result<LogHead, FileServiceError>;
+        async fn inclusion_proof(
+            &self,
+            owner: &str,
+            upload_id: Uuid,
+            size: Option<u64>,
+        ) -> Result<LogInclusionProof, FileServiceError>;
+        async fn consistency_proof(
+            &self,
+            first: u64,
+            second: Option<u64>,
+        ) -> Result<LogConsistencyProof, FileServiceError>;
     }
 }
@@ -1,2 +1,10 @@
 pub mod mocks;
+// Every test file compiles its own copy of the helpers and not all of them use every helper.
+#[allow(dead_code)]
+pub mod memory_storage;
+#[allow(dead_code)]
+pub mod storage_fixtures;
+#[allow(dead_code)]
+pub mod tree_fixtures;
+#[allow(dead_code)]
 pub mod web_server_simulator;
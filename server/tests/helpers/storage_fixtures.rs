@@ -0,0 +1,47 @@
+// Contents and stream helpers shared by the tests of the storage decorators.
+use bytes::Bytes;
+use file_server_server::repositories::{FileStorage, FileStream};
+use futures::{TryStreamExt, stream};
+use std::{io, ops::RangeInclusive};
+use uuid::Uuid;
+
+// Log lines compress well.
+pub fn log_lines() -> Vec<u8> {
+    (0..5000)
+        .flat_map(|i| {
+            format!("{{\"line\":{i},\"level\":\"info\",\"message\":\"ok\"}}\n").into_bytes()
+        })
+        .collect()
+}
+
+// Several segments, the last one shorter.
+pub fn segmented_contents(segment_size: usize) -> Vec<u8> {
+    (0..3 * segment_size + 1234)
+        .map(|i| (i % 251) as u8)
+        .collect()
+}
+
+// Sent in chunks unrelated to the block or segment size of the storage.
+pub fn chunked(contents: &[u8]) -> FileStream {
+    let chunks: Vec<io::Result<Bytes>> = contents
+        .chunks(10_000)
+        .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+        .collect();
+    Box::pin(stream::iter(chunks))
+}
+
+pub async fn read(
+    storage: &dyn FileStorage,
+    id: Uuid,
+    name: &str,
+    range: Option<RangeInclusive<u64>>,
+) -> io::Result<Vec<u8>> {
+    storage
+        .get_file_content(id, name, range)
+        .await
+        .unwrap()
+        .unwrap()
+        .map_ok(|chunk| chunk.to_vec())
+        .try_concat()
+        .await
+}
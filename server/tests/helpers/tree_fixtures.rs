@@ -0,0 +1,57 @@
+// Upload rows and listings shared by the tests of the services working on stored uploads.
+use chrono::{DateTime, Utc};
+use file_server_library::models::Hash32;
+use file_server_server::{models::UploadState, repositories::FileMerkleTreeRow};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// An upload of `client-1` without files, created and updated now.
+pub fn tree_row(id: Uuid, state: UploadState) -> FileMerkleTreeRow {
+    let now = Utc::now();
+
+    FileMerkleTreeRow {
+        id,
+        version: 1,
+        previous_version: None,
+        owner: "client-1".to_string(),
+        order: vec![],
+        files: HashMap::new(),
+        leaf_hashes: vec![],
+        sizes: vec![],
+        content_types: vec![],
+        uploaded_at: vec![],
+        root: None,
+        state,
+        created_at: now,
+        updated_at: now,
+    }
+}
+
+// Appends a 15 bytes long text file, the size of every `contents_file_N`.
+pub fn with_file(mut row: FileMerkleTreeRow, name: &str, hash: Hash32) -> FileMerkleTreeRow {
+    row.order.push(name.to_string());
+    row.leaf_hashes.push(hash);
+    row.sizes.push(15);
+    row.content_types.push("text/plain".to_string());
+    row.uploaded_at.push(row.created_at);
+    row
+}
+
+pub fn with_contents(row: FileMerkleTreeRow, name: &str, contents: &[u8]) -> FileMerkleTreeRow {
+    let mut row = with_file(row, name, Hash32::hash(contents));
+    *row.sizes.last_mut().unwrap() = contents.len() as u64;
+    row
+}
+
+pub fn last_updated_at(mut row: FileMerkleTreeRow, at: DateTime<Utc>) -> FileMerkleTreeRow {
+    row.created_at = at;
+    row.updated_at = at;
+    row.uploaded_at.fill(at);
+    row
+}
+
+// Pages of a listing: the items once, nothing afterwards as if they had all been dealt with.
+pub fn listed_once<T: Send + 'static>(items: Vec<T>) -> impl FnMut() -> Vec<T> + Send + 'static {
+    let mut items = Some(items);
+    move || items.take().unwrap_or_default()
+}
@@ -1,19 +1,44 @@
 use axum::Router;
+use ed25519_dalek::{SigningKey, VerifyingKey};
 use file_server_server::{
-    handlers::RouteExtensions, infrastructure::AuthenticationExtensions, server::ServerState,
-    services::FileService,
+    handlers::RouteExtensions,
+    infrastructure::{
+        AuditExtensions, AuthenticationExtensions, BodyLimitExtensions, IdempotencyConfig,
+        IdempotencyExtensions, RateLimitConfig, RateLimitExtensions, ResponseSigningExtensions,
+        ServerSigner,
+    },
+    repositories::{AuditRepository, IdempotencyRepository},
+    server::ServerState,
+    services::{AdminService, FileService, TransparencyLogService, UploadSessionService},
 };
 use std::sync::Arc;
 use tokio::{net::TcpListener, task::JoinHandle};
 
-use crate::helpers::mocks::MockFileServiceImpl;
+use crate::helpers::mocks::{
+    MockAdminServiceImpl, MockAuditRepositoryImpl, MockFileServiceImpl,
+    MockIdempotencyRepositoryImpl, MockTransparencyLogServiceImpl, MockUploadSessionServiceImpl,
+};
 
 type FileServiceType = Arc<dyn FileService + Send + Sync>;
+type UploadSessionServiceType = Arc<dyn UploadSessionService + Send + Sync>;
+type AdminServiceType = Arc<dyn AdminService + Send + Sync>;
+type TransparencyLogServiceType = Arc<dyn TransparencyLogService + Send + Sync>;
+
+const SIGNING_SEED: [u8; 32] = [7; 32];
+const RETIRED_SEED: [u8; 32] = [3; 32];
 
 pub struct WebServerSimulator {
     api_base_url: String,
     listener: TcpListener,
     file_service: MockFileServiceImpl,
+    upload_session_service: MockUploadSessionServiceImpl,
+    admin_service: MockAdminServiceImpl,
+    transparency_log_service: MockTransparencyLogServiceImpl,
+    body_limit: Option<usize>,
+    max_signed_body: Option<usize>,
+    rate_limit: Option<RateLimitConfig>,
+    idempotency: Option<(MockIdempotencyRepositoryImpl, IdempotencyConfig)>,
+    audit: Option<MockAuditRepositoryImpl>,
 }
 
 impl WebServerSimulator {
@@ -26,6 +51,14 @@ impl WebServerSimulator {
             api_base_url,
             listener,
             file_service: MockFileServiceImpl::new(),
+            upload_session_service: MockUploadSessionServiceImpl::new(),
+            admin_service: MockAdminServiceImpl::new(),
+            transparency_log_service: MockTransparencyLogServiceImpl::new(),
+            body_limit: None,
+            max_signed_body: None,
+            rate_limit: None,
+            idempotency: None,
+            audit: None,
         })
     }
 
@@ -33,19 +66,102 @@ impl WebServerSimulator {
         self.api_base_url.clone()
     }
 
+    /// Base URL of the server itself, for the routes living outside the API prefix.
+    pub fn root_url(&self) -> String {
+        self.api_base_url.trim_end_matches("/api/v1").to_string()
+    }
+
+    pub fn signing_key() -> VerifyingKey {
+        SigningKey::from_bytes(&SIGNING_SEED).verifying_key()
+    }
+
+    pub fn retired_key() -> VerifyingKey {
+        SigningKey::from_bytes(&RETIRED_SEED).verifying_key()
+    }
+
     pub fn configure_file_service(&mut self, mut callback: impl FnMut(&mut MockFileServiceImpl)) {
         callback(&mut self.file_service);
     }
 
+    pub fn configure_upload_session_service(
+        &mut self,
+        mut callback: impl FnMut(&mut MockUploadSessionServiceImpl),
+    ) {
+        callback(&mut self.upload_session_service);
+    }
+
+    pub fn configure_admin_service(&mut self, mut callback: impl FnMut(&mut MockAdminServiceImpl)) {
+        callback(&mut self.admin_service);
+    }
+
+    pub fn configure_transparency_log_service(
+        &mut self,
+        mut callback: impl FnMut(&mut MockTransparencyLogServiceImpl),
+    ) {
+        callback(&mut self.transparency_log_service);
+    }
+
+    pub fn configure_body_limit(&mut self, max_bytes: usize) {
+        self.body_limit = Some(max_bytes);
+    }
+
+    /// Largest body digested unless it is JSON.
+    pub fn configure_max_signed_body(&mut self, max_bytes: usize) {
+        self.max_signed_body = Some(max_bytes);
+    }
+
+    pub fn configure_rate_limit(&mut self, config: RateLimitConfig) {
+        self.rate_limit = Some(config);
+    }
+
+    pub fn configure_idempotency(
+        &mut self,
+        config: IdempotencyConfig,
+        mut callback: impl FnMut(&mut MockIdempotencyRepositoryImpl),
+    ) {
+        let mut repository = MockIdempotencyRepositoryImpl::new();
+        callback(&mut repository);
+        self.idempotency = Some((repository, config));
+    }
+
+    pub fn configure_audit(&mut self, mut callback: impl FnMut(&mut MockAuditRepositoryImpl)) {
+        let mut repository = MockAuditRepositoryImpl::new();
+        callback(&mut repository);
+        self.audit = Some(repository);
+    }
+
     pub async fn start(self) -> JoinHandle<()> {
         let state = Arc::new(ServerState::new(
-            Arc::new(self.file_service) as FileServiceType
+            Arc::new(self.file_service) as FileServiceType,
+            Arc::new(self.upload_session_service) as UploadSessionServiceType,
+            Arc::new(self.admin_service) as AdminServiceType,
+            Arc::new(self.transparency_log_service) as TransparencyLogServiceType,
         ));
 
         // For testing purposes, I am taking the router only, which skips some additions
         // like OpenAPI docs, tracing, etc. Depending on the project, that might o r might not be
         // desired. For that reason I need to add authentication manually here.
-        let server = Router::new().with_routes(state).with_authentication();
+        let mut server = Router::new().with_routes(state);
+        if let Some((repository, config)) = self.idempotency {
+            let repository = Arc::new(repository) as Arc<dyn IdempotencyRepository>;
+            server = server.with_idempotency(repository, config);
+        }
+        if let Some(config) = self.rate_limit {
+            server = server.with_rate_limiting(config);
+        }
+        if let Some(repository) = self.audit {
+            server = server.with_audit(Arc::new(repository) as Arc<dyn AuditRepository>);
+        }
+        let mut server = server.with_authentication();
+        if let Some(max_bytes) = self.body_limit {
+            server = server.with_body_limit(max_bytes);
+        }
+        let mut signer = ServerSigner::new(SigningKey::from_bytes(&SIGNING_SEED))
+            .with_retired_keys(vec![Self::retired_key()]);
+        if let Some(max_bytes) = self.max_signed_body {
+            signer = signer.with_max_body_bytes(max_bytes);
+        }
+        let server = server.with_response_signing(Arc::new(signer));
         let server = axum::serve(self.listener, server.into_make_service());
 
         tokio::spawn(async move {
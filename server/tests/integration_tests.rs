@@ -6,21 +6,55 @@ mod helpers;
 
 use crate::helpers::web_server_simulator::WebServerSimulator;
 use bytes::Bytes;
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
+use ed25519_dalek::Signature;
+use file_server_library::{
+    models::{Hash32, Proof},
+    signing::{
+        SERVER_DIGEST_HEADER, SERVER_KEY_HEADER, SERVER_SIGNATURE_HEADER, SignedResponse, key_id,
+    },
+};
 use file_server_server::{
-    handlers::responses::{FileMetadataResponse, FinalUploadResponse, InitiateUploadResponse},
+    handlers::responses::{
+        AuditEntriesResponse, FileListResponse, FileMetadataResponse, FinalUploadResponse,
+        InitiateUploadResponse, LogConsistencyProofResponse, LogHeadResponse,
+        LogInclusionProofResponse, OrphanReportResponse, ProofResponse, ScrubReportResponse,
+        UploadListResponse, UploadSessionResponse, UploadSummaryResponse, UsageResponse,
+        WebhookDeliveriesResponse,
+    },
+    infrastructure::{
+        IdempotencyConfig, KeyRegistry, KeyStatus, RateLimitConfig, RateLimits, audit_entry_hash,
+    },
+    models::{
+        ArchiveFormat, AuditFilter, DeliveryState, FileDescriptor, FileEntry, FileMetadata,
+        FilePage, LogConsistencyProof, LogHead, LogInclusionProof, OrphanObject, OrphanReport,
+        Quota, QuotaLimits, QuotaViolation, ScrubFindingKind, ScrubReport, UploadCursor,
+        UploadEvent, UploadPage, UploadSession, UploadState, UploadSummary, Usage, WebhookEvent,
+    },
+    repositories::{
+        AuditEntryRow, ContentEncoding, EncodedContent, FileStream, IdempotencyRow, LogEntryRow,
+        ScrubFindingRow, WebhookDeliveryRow,
+    },
     services::FileServiceError,
 };
+use futures::{TryStreamExt, stream};
 use hmac::{Hmac, Mac};
 use mockall::predicate::{always, eq};
 use reqwest::StatusCode;
 use sha2::Sha256;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::{runtime::Handle, task::block_in_place};
 use uuid::Uuid;
 
 type HmacSha256 = Hmac<Sha256>;
 
 const TEST_KEY: &str = "client-1";
 const TEST_SECRET: &str = "secret-1";
+const ADMIN_KEY: &str = "admin";
+const ADMIN_SECRET: &str = "admin-secret";
 
 fn create_valid_signature() -> (String, String) {
     let timestamp = Utc::now().timestamp_millis().to_string();
@@ -151,8 +185,9 @@ async fn test_can_initiate_upload() {
 
     simulator.configure_file_service(|srv| {
         srv.expect_initiate()
+            .with(eq(TEST_KEY))
             .times(1)
-            .returning(move || Ok(expected_id));
+            .returning(move |_| Ok(expected_id));
     });
     let server_handle = simulator.start().await;
 
@@ -185,9 +220,9 @@ async fn test_unload_without_initialization_returns_not_found() {
 
     simulator.configure_file_service(|srv| {
         srv.expect_upload_file()
-            .with(eq(invalid_id), always(), always())
+            .with(eq(TEST_KEY), eq(invalid_id), always(), always())
             .times(1)
-            .returning(move |_, _, _| Err(FileServiceError::FileNotFound));
+            .returning(move |_, _, _, _| Err(FileServiceError::FileNotFound));
     });
     let server_handle = simulator.start().await;
 
@@ -220,13 +255,14 @@ async fn test_unload_with_duplicated_file_returns_conflict() {
 
     simulator.configure_file_service(|srv| {
         srv.expect_upload_file()
-            .withf(move |id, metadata, _| {
-                *id == expected_id
+            .withf(move |owner, id, metadata, _| {
+                owner == TEST_KEY
+                    && *id == expected_id
                     && metadata.name == expected_filename
                     && metadata.index == expected_index
             })
             .times(1)
-            .returning(move |_, _, _| Err(FileServiceError::FileAlreadyExists));
+            .returning(move |_, _, _, _| Err(FileServiceError::FileAlreadyExists));
     });
     let server_handle = simulator.start().await;
 
@@ -251,7 +287,9 @@ async fn test_unload_with_duplicated_file_returns_conflict() {
     server_handle.abort();
 }
 
-#[tokio::test]
+// The body is only available as a stream, which the mock drains synchronously. That needs a
+// multi threaded runtime so the server keeps making progress while the test thread blocks.
+#[tokio::test(flavor = "multi_thread")]
 async fn test_upload_with_valid_request_returns_ok() {
     let mut simulator = WebServerSimulator::new().await.unwrap();
     let base_url = simulator.url();
@@ -264,14 +302,20 @@ async fn test_upload_with_valid_request_returns_ok() {
 
     simulator.configure_file_service(|srv| {
         srv.expect_upload_file()
-            .withf(move |id, metadata, body| {
-                *id == expected_id
+            .withf(move |owner, id, metadata, _| {
+                owner == TEST_KEY
+                    && *id == expected_id
                     && metadata.name == expected_filename
                     && metadata.index == expected_index
-                    && body == &Bytes::from_static(expected_filecontent)
             })
             .times(1)
-            .returning(move |_, _, _| Ok(expected_hash.to_string()));
+            .returning(move |_, _, _, body| {
+                let body = body.map_ok(|chunk| chunk.to_vec()).try_concat();
+                let body = block_in_place(|| Handle::current().block_on(body));
+                assert_eq!(body.unwrap(), expected_filecontent.to_vec());
+
+                Ok(expected_hash.to_string())
+            });
     });
 
     let server_handle = simulator.start().await;
@@ -303,23 +347,53 @@ async fn test_upload_with_valid_request_returns_ok() {
 }
 
 #[tokio::test]
-async fn test_complete_returns_ok() {
+async fn test_upload_exceeding_body_limit_returns_payload_too_large() {
     let mut simulator = WebServerSimulator::new().await.unwrap();
     let base_url = simulator.url();
 
     let expected_id = Uuid::new_v4();
-    let expected_root = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
 
+    simulator.configure_body_limit(16);
     simulator.configure_file_service(|srv| {
-        srv.expect_complete()
-            .withf(move |id| *id == expected_id)
-            .times(1)
-            .returning(move |_| Ok(expected_root.to_string()));
+        srv.expect_upload_file().times(0);
     });
+    let server_handle = simulator.start().await;
+
+    let url = format!("{}/{}/upload?name=test.txt&index=0", base_url, expected_id);
+
+    let (signature, timestamp) = create_valid_signature();
+    let resp = reqwest::Client::new()
+        .post(&url)
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature)
+        .body(Bytes::from(vec![0u8; 64]))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    server_handle.abort();
+}
 
+#[tokio::test]
+async fn test_initiate_beyond_open_uploads_quota_returns_too_many_requests() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_initiate().times(1).returning(|_| {
+            Err(FileServiceError::QuotaExceeded(QuotaViolation {
+                quota: Quota::OpenUploads,
+                limit: 2,
+                used: 3,
+            }))
+        });
+    });
     let server_handle = simulator.start().await;
 
-    let url = format!("{}/{}/complete", base_url, expected_id);
+    let url = format!("{}/initiate", base_url);
 
     let (signature, timestamp) = create_valid_signature();
     let resp = reqwest::Client::new()
@@ -331,43 +405,2552 @@ async fn test_complete_returns_ok() {
         .await
         .unwrap();
 
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "error": "Quota exceeded",
+            "quota": "max_open_uploads",
+            "limit": 2,
+            "used": 3,
+        })
+    );
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_usage_returns_usage_of_client() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_get_usage()
+            .with(eq(TEST_KEY))
+            .times(1)
+            .returning(|_| {
+                Ok(Usage {
+                    stored_bytes: 1024,
+                    open_uploads: 1,
+                    limits: QuotaLimits {
+                        max_stored_bytes: Some(4096),
+                        ..Default::default()
+                    },
+                })
+            });
+    });
+    let server_handle = simulator.start().await;
+
+    let url = format!("{}/usage", base_url);
+
+    let (signature, timestamp) = create_valid_signature();
+    let resp = reqwest::Client::new()
+        .get(&url)
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature)
+        .send()
+        .await
+        .unwrap();
+
     assert_eq!(resp.status(), StatusCode::OK);
 
-    let body: FinalUploadResponse = resp.json().await.unwrap();
-    assert_eq!(body.root_hex, expected_root);
+    let usage: UsageResponse = resp.json().await.unwrap();
+    assert_eq!(usage.stored_bytes, 1024);
+    assert_eq!(usage.open_uploads, 1);
+    assert_eq!(usage.limits.max_stored_bytes, Some(4096));
+    assert_eq!(usage.limits.max_file_size_bytes, None);
 
     server_handle.abort();
 }
 
 #[tokio::test]
-async fn test_complete_with_invalid_id_returns_not_found() {
+async fn test_list_webhook_deliveries_returns_deliveries_of_client() {
     let mut simulator = WebServerSimulator::new().await.unwrap();
     let base_url = simulator.url();
+    let upload_id = Uuid::new_v4();
 
-    let id = Uuid::new_v4();
+    simulator.configure_file_service(|srv| {
+        srv.expect_list_webhook_deliveries()
+            .with(eq(TEST_KEY), eq(10), eq(1000))
+            .times(1)
+            .returning(move |owner, _, _| {
+                let now = Utc::now();
+                Ok(vec![WebhookDeliveryRow {
+                    id: Uuid::new_v4(),
+                    owner: owner.to_string(),
+                    event: WebhookEvent::Expired,
+                    upload_id,
+                    url: "http://localhost:9000/hooks".to_string(),
+                    payload: "{}".to_string(),
+                    state: DeliveryState::Pending,
+                    attempts: 2,
+                    next_attempt_at: now + Duration::seconds(40),
+                    last_status: Some(503),
+                    last_error: None,
+                    created_at: now,
+                    updated_at: now,
+                }])
+            });
+    });
+    let server_handle = simulator.start().await;
 
+    let url = format!("{}/webhooks/deliveries?offset=10&limit=5000", base_url);
+
+    let (signature, timestamp) = create_valid_signature();
+    let resp = reqwest::Client::new()
+        .get(&url)
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let deliveries: WebhookDeliveriesResponse = resp.json().await.unwrap();
+    assert_eq!(deliveries.deliveries.len(), 1);
+    assert_eq!(deliveries.deliveries[0].event, "upload.expired");
+    assert_eq!(deliveries.deliveries[0].upload_id, upload_id);
+    assert_eq!(deliveries.deliveries[0].state, "pending");
+    assert_eq!(deliveries.deliveries[0].last_status, Some(503));
+
+    server_handle.abort();
+}
+
+async fn get_usage_as(base_url: &str, key: &str, secret: &str) -> reqwest::Response {
+    let timestamp = Utc::now().timestamp_millis().to_string();
+    let signature = create_signature(secret, &timestamp);
+
+    reqwest::Client::new()
+        .get(format!("{}/usage", base_url))
+        .header("X-AUTH-KEY", key)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_requests_beyond_rate_limit_return_too_many_requests() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_rate_limit(RateLimitConfig {
+        default: RateLimits {
+            requests_per_sec: Some(0.1),
+            request_burst: Some(2.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
     simulator.configure_file_service(|srv| {
-        srv.expect_complete()
-            .withf(move |got_id| *got_id == id)
+        srv.expect_get_usage()
+            .times(3)
+            .returning(|_| Ok(Usage::default()));
+    });
+    let server_handle = simulator.start().await;
+
+    for _ in 0..2 {
+        let resp = get_usage_as(&base_url, TEST_KEY, TEST_SECRET).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let resp = get_usage_as(&base_url, TEST_KEY, TEST_SECRET).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let retry_after: u64 = resp.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=10).contains(&retry_after));
+
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "Rate limit exceeded");
+    assert_eq!(body["budget"], "requests");
+
+    // Every key has its own budget.
+    let resp = get_usage_as(&base_url, ADMIN_KEY, ADMIN_SECRET).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_rate_limit_overrides_apply_to_their_client_only() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_rate_limit(RateLimitConfig {
+        default: RateLimits {
+            requests_per_sec: Some(0.1),
+            request_burst: Some(1.0),
+            ..Default::default()
+        },
+        clients: HashMap::from([(
+            TEST_KEY.to_string(),
+            RateLimits {
+                request_burst: Some(5.0),
+                ..Default::default()
+            },
+        )]),
+    });
+    simulator.configure_file_service(|srv| {
+        srv.expect_get_usage()
+            .times(6)
+            .returning(|_| Ok(Usage::default()));
+    });
+    let server_handle = simulator.start().await;
+
+    for _ in 0..5 {
+        let resp = get_usage_as(&base_url, TEST_KEY, TEST_SECRET).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let resp = get_usage_as(&base_url, ADMIN_KEY, ADMIN_SECRET).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = get_usage_as(&base_url, ADMIN_KEY, ADMIN_SECRET).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    server_handle.abort();
+}
+
+#[test]
+fn test_rate_limits_must_be_positive() {
+    let config = RateLimitConfig {
+        clients: HashMap::from([(
+            TEST_KEY.to_string(),
+            RateLimits {
+                requests_per_sec: Some(0.0),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    };
+    assert!(config.validate().is_err());
+
+    for rate in [-1.0, f64::NAN, f64::INFINITY] {
+        let limits = RateLimits {
+            upload_bytes_per_sec: Some(rate),
+            ..Default::default()
+        };
+        assert!(limits.validate().is_err());
+    }
+
+    let limits = RateLimits {
+        requests_per_sec: Some(0.5),
+        request_burst: Some(2.0),
+        ..Default::default()
+    };
+    assert!(limits.validate().is_ok());
+}
+
+#[tokio::test]
+async fn test_zero_rate_limit_denies_without_taking_the_server_down() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_rate_limit(RateLimitConfig {
+        clients: HashMap::from([(
+            TEST_KEY.to_string(),
+            RateLimits {
+                requests_per_sec: Some(0.0),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    });
+    simulator.configure_file_service(|srv| {
+        srv.expect_get_usage()
+            .times(2)
+            .returning(|_| Ok(Usage::default()));
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = get_usage_as(&base_url, TEST_KEY, TEST_SECRET).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    for _ in 0..2 {
+        let resp = get_usage_as(&base_url, TEST_KEY, TEST_SECRET).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers()["Retry-After"], "3600");
+    }
+
+    // The bucket that never refills does not hold back other clients.
+    let resp = get_usage_as(&base_url, ADMIN_KEY, ADMIN_SECRET).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    server_handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_uploads_beyond_bandwidth_budget_return_too_many_requests() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+
+    simulator.configure_rate_limit(RateLimitConfig {
+        default: RateLimits {
+            upload_bytes_per_sec: Some(1.0),
+            upload_burst_bytes: Some(16.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    simulator.configure_file_service(|srv| {
+        srv.expect_upload_file()
             .times(1)
-            .returning(|_| Err(FileServiceError::FileNotFound));
+            .returning(|_, _, _, body| {
+                let body = body.map_ok(|chunk| chunk.to_vec()).try_concat();
+                let body = block_in_place(|| Handle::current().block_on(body));
+                assert_eq!(body.unwrap().len(), 64);
+
+                Ok("abcd1234deadbeef".to_string())
+            });
+        srv.expect_get_usage()
+            .times(1)
+            .returning(|_| Ok(Usage::default()));
     });
+    let server_handle = simulator.start().await;
+
+    let url = format!("{}/{}/upload?name=test.txt&index=0", base_url, expected_id);
+    let upload = || {
+        let (signature, timestamp) = create_valid_signature();
+        reqwest::Client::new()
+            .post(&url)
+            .header("X-AUTH-KEY", TEST_KEY)
+            .header("X-AUTH-TS", timestamp)
+            .header("X-AUTH-SIGNATURE", signature)
+            .body(Bytes::from(vec![0u8; 64]))
+            .send()
+    };
+
+    // The upload is let through whole, the next one waits for the debt to be paid back.
+    let resp = upload().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = upload().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let retry_after: u64 = resp.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 40);
+
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["budget"], "upload_bytes");
+
+    // Requests without a body are not held back by the bandwidth budget.
+    let resp = get_usage_as(&base_url, TEST_KEY, TEST_SECRET).await;
+    assert_eq!(resp.status(), StatusCode::OK);
 
+    server_handle.abort();
+}
+
+fn initiate_with_key(base_url: &str, key: &str) -> reqwest::RequestBuilder {
+    let (signature, timestamp) = create_valid_signature();
+
+    reqwest::Client::new()
+        .post(format!("{}/initiate", base_url))
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature)
+        .header("Idempotency-Key", key)
+}
+
+#[tokio::test]
+async fn test_retried_initiate_with_idempotency_key_replays_response() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    let recorded: Arc<Mutex<Option<IdempotencyRow>>> = Arc::default();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_initiate()
+            .with(eq(TEST_KEY))
+            .times(1)
+            .returning(move |_| Ok(expected_id));
+    });
+    simulator.configure_idempotency(IdempotencyConfig::default(), |repo| {
+        let recorded_get = Arc::clone(&recorded);
+        repo.expect_get()
+            .with(eq(TEST_KEY), eq("initiate-1"))
+            .times(2)
+            .returning(move |_, _| Ok(recorded_get.lock().unwrap().clone()));
+        let recorded_insert = Arc::clone(&recorded);
+        repo.expect_insert().times(1).returning(move |row| {
+            *recorded_insert.lock().unwrap() = Some(row);
+            Ok(())
+        });
+    });
     let server_handle = simulator.start().await;
 
-    let url = format!("{}/{}/complete", base_url, id);
+    let resp = initiate_with_key(&base_url, "initiate-1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert!(resp.headers().get("Idempotent-Replayed").is_none());
+    let body: InitiateUploadResponse = resp.json().await.unwrap();
+    assert_eq!(body.id, expected_id);
+
+    let resp = initiate_with_key(&base_url, "initiate-1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(resp.headers()["Idempotent-Replayed"], "true");
+    assert_eq!(resp.headers()["Content-Type"], "application/json");
+    let body: InitiateUploadResponse = resp.json().await.unwrap();
+    assert_eq!(body.id, expected_id);
+
+    let row = recorded.lock().unwrap().clone().unwrap();
+    assert_eq!(row.owner, TEST_KEY);
+    assert_eq!(row.status, 201);
+    assert!(row.expires_at > Utc::now() + Duration::hours(23));
 
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_idempotency_key_reused_for_another_request_returns_unprocessable_entity() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_initiate().times(0);
+    });
+    simulator.configure_idempotency(IdempotencyConfig::default(), |repo| {
+        repo.expect_get().times(1).returning(|owner, key| {
+            Ok(Some(IdempotencyRow {
+                owner: owner.to_string(),
+                key: key.to_string(),
+                fingerprint: Hash32::hash(b"DELETE /api/v1/some-upload").to_hex(),
+                status: 204,
+                content_type: None,
+                headers: Vec::new(),
+                body: String::new(),
+                expires_at: Utc::now() + Duration::hours(1),
+            }))
+        });
+        repo.expect_insert().times(0);
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = initiate_with_key(&base_url, "reused").send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let resp = initiate_with_key(&base_url, "not a valid key")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_failed_requests_with_idempotency_key_are_not_recorded() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_initiate().times(1).returning(|_| {
+            Err(FileServiceError::StorageError(
+                "database is down".to_string(),
+            ))
+        });
+    });
+    simulator.configure_idempotency(IdempotencyConfig::default(), |repo| {
+        repo.expect_get().times(1).returning(|_, _| Ok(None));
+        repo.expect_insert().times(0);
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = initiate_with_key(&base_url, "initiate-1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    server_handle.abort();
+}
+
+fn signed_post(url: &str) -> reqwest::RequestBuilder {
     let (signature, timestamp) = create_valid_signature();
-    let resp = reqwest::Client::new()
-        .post(&url)
+
+    reqwest::Client::new()
+        .post(url)
         .header("X-AUTH-KEY", TEST_KEY)
         .header("X-AUTH-TS", timestamp)
         .header("X-AUTH-SIGNATURE", signature)
+}
+
+fn audit_entry(sequence: u64) -> AuditEntryRow {
+    let mut entry = AuditEntryRow {
+        owner: TEST_KEY.to_string(),
+        sequence,
+        action: "POST /api/v1/initiate".to_string(),
+        upload_id: Some(Uuid::new_v4()),
+        index: None,
+        name: None,
+        hash: None,
+        status: 201,
+        request_id: None,
+        correlation_id: None,
+        occurred_at: DateTime::from_timestamp_millis(1_700_000_000_000).unwrap(),
+        previous_hash: Hash32::hash(b"previous entry"),
+        entry_hash: Hash32::empty(),
+    };
+    entry.entry_hash = audit_entry_hash(&entry);
+    entry
+}
+
+#[tokio::test]
+async fn test_audit_records_operations_of_client_chained_by_hash() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    let expected_hash = "abcd1234deadbeef";
+    let recorded: Arc<Mutex<Vec<AuditEntryRow>>> = Arc::default();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_initiate()
+            .times(1)
+            .returning(move |_| Ok(expected_id));
+        srv.expect_upload_file()
+            .times(1)
+            .returning(move |_, _, _, _| Ok(expected_hash.to_string()));
+    });
+    simulator.configure_audit(|repo| {
+        // The head of the chain is looked up for every entry.
+        let stored = Arc::clone(&recorded);
+        repo.expect_latest()
+            .with(eq(TEST_KEY))
+            .times(2)
+            .returning(move |_| Ok(stored.lock().unwrap().last().cloned()));
+        let recorded = Arc::clone(&recorded);
+        repo.expect_append().times(2).returning(move |entry| {
+            recorded.lock().unwrap().push(entry);
+            Ok(true)
+        });
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = signed_post(&format!("{}/initiate", base_url))
+        .header("x-request-id", "request-1")
+        .header("x-correlation-id", "correlation-1")
         .send()
         .await
         .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
 
-    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = signed_post(&format!(
+        "{}/{}/upload?name=file1.txt&index=3",
+        base_url, expected_id
+    ))
+    .body(Bytes::from_static(b"fake data"))
+    .send()
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let entries = recorded.lock().unwrap().clone();
+    assert_eq!(entries.len(), 2);
+
+    let initiated = &entries[0];
+    assert_eq!(initiated.owner, TEST_KEY);
+    assert_eq!(initiated.sequence, 0);
+    assert_eq!(initiated.action, "POST /api/v1/initiate");
+    assert_eq!(initiated.upload_id, Some(expected_id));
+    assert_eq!(initiated.status, 201);
+    assert_eq!(initiated.request_id.as_deref(), Some("request-1"));
+    assert_eq!(initiated.correlation_id.as_deref(), Some("correlation-1"));
+    assert_eq!(initiated.previous_hash, Hash32::empty());
+    assert_eq!(initiated.entry_hash, audit_entry_hash(initiated));
+
+    let uploaded = &entries[1];
+    assert_eq!(uploaded.sequence, 1);
+    assert_eq!(uploaded.action, "POST /api/v1/{id}/upload");
+    assert_eq!(uploaded.upload_id, Some(expected_id));
+    assert_eq!(uploaded.index, Some(3));
+    assert_eq!(uploaded.name.as_deref(), Some("file1.txt"));
+    assert_eq!(uploaded.hash.as_deref(), Some(expected_hash));
+    assert_eq!(uploaded.status, 200);
+    assert_eq!(uploaded.previous_hash, initiated.entry_hash);
+    assert_eq!(uploaded.entry_hash, audit_entry_hash(uploaded));
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_audit_continues_chain_from_latest_recorded_entry() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let latest = audit_entry(41);
+    let latest_hash = latest.entry_hash;
+    let recorded: Arc<Mutex<Vec<AuditEntryRow>>> = Arc::default();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_initiate().times(1).returning(|_| {
+            Err(FileServiceError::StorageError(
+                "database is down".to_string(),
+            ))
+        });
+    });
+    simulator.configure_audit(|repo| {
+        let latest = latest.clone();
+        repo.expect_latest()
+            .times(1)
+            .returning(move |_| Ok(Some(latest.clone())));
+        let recorded = Arc::clone(&recorded);
+        repo.expect_append().times(1).returning(move |entry| {
+            recorded.lock().unwrap().push(entry);
+            Ok(true)
+        });
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = signed_post(&format!("{}/initiate", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    // Failed operations are recorded along with how they failed.
+    let entries = recorded.lock().unwrap().clone();
+    assert_eq!(entries[0].sequence, 42);
+    assert_eq!(entries[0].previous_hash, latest_hash);
+    assert_eq!(entries[0].status, 500);
+    assert_eq!(entries[0].upload_id, None);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_audit_chains_entry_again_after_losing_sequence_to_another_server() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let before = audit_entry(41);
+    let other = audit_entry(42);
+    let other_hash = other.entry_hash;
+    let recorded: Arc<Mutex<Vec<AuditEntryRow>>> = Arc::default();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_initiate()
+            .times(1)
+            .returning(|_| Ok(Uuid::new_v4()));
+    });
+    simulator.configure_audit(|repo| {
+        // Another server records entry 42 between the lookup and the append.
+        let heads = Mutex::new(vec![other.clone(), before.clone()]);
+        repo.expect_latest()
+            .times(2)
+            .returning(move |_| Ok(heads.lock().unwrap().pop()));
+        let recorded = Arc::clone(&recorded);
+        repo.expect_append().times(2).returning(move |entry| {
+            recorded.lock().unwrap().push(entry.clone());
+            Ok(entry.sequence == 43)
+        });
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = signed_post(&format!("{}/initiate", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let entries = recorded.lock().unwrap().clone();
+    assert_eq!(entries[0].sequence, 42);
+    assert_eq!(entries[1].sequence, 43);
+    assert_eq!(entries[1].previous_hash, other_hash);
+    assert_eq!(entries[1].entry_hash, audit_entry_hash(&entries[1]));
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_audit_does_not_record_unauthenticated_requests() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_initiate().times(0);
+    });
+    simulator.configure_audit(|repo| {
+        repo.expect_latest().times(0);
+        repo.expect_append().times(0);
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/initiate", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_list_audit_entries_returns_entries_of_client() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let upload_id = Uuid::new_v4();
+    let expected_filter = AuditFilter {
+        upload_id: Some(upload_id),
+        action: None,
+        occurred_after: None,
+        occurred_before: None,
+    };
+    let entries = vec![audit_entry(5), audit_entry(6)];
+    let expected_hash = entries[1].entry_hash.to_hex();
+
+    simulator.configure_file_service(|srv| {
+        let entries = entries.clone();
+        srv.expect_list_audit_entries()
+            .with(
+                eq(TEST_KEY),
+                eq(expected_filter.clone()),
+                eq(Some(4)),
+                eq(2),
+            )
+            .times(1)
+            .returning(move |_, _, _, _| Ok(entries.clone()));
+    });
+    let server_handle = simulator.start().await;
+
+    let url = format!("{}/audit?upload_id={}&after=4&limit=2", base_url, upload_id);
+
+    let (signature, timestamp) = create_valid_signature();
+    let resp = reqwest::Client::new()
+        .get(&url)
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let page: AuditEntriesResponse = resp.json().await.unwrap();
+    assert_eq!(page.entries.len(), 2);
+    assert_eq!(page.entries[0].sequence, 5);
+    assert_eq!(page.entries[1].entry_hash, expected_hash);
+    assert_eq!(page.entries[1].action, "POST /api/v1/initiate");
+    // The page was full, there may be more entries after it.
+    assert_eq!(page.next_after, Some(6));
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_complete_returns_ok() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    let expected_root = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_complete()
+            .withf(move |owner, id| owner == TEST_KEY && *id == expected_id)
+            .times(1)
+            .returning(move |_, _| Ok(expected_root.to_string()));
+    });
+
+    let server_handle = simulator.start().await;
+
+    let url = format!("{}/{}/complete", base_url, expected_id);
+
+    let (signature, timestamp) = create_valid_signature();
+    let resp = reqwest::Client::new()
+        .post(&url)
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: FinalUploadResponse = resp.json().await.unwrap();
+    assert_eq!(body.root_hex, expected_root);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_create_version_returns_created_with_link_to_previous_version() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_create_version()
+            .with(eq(TEST_KEY), eq(expected_id))
+            .times(1)
+            .returning(|_, id| {
+                Ok(UploadSummary {
+                    id,
+                    version: 2,
+                    previous_version: Some(1),
+                    state: UploadState::Initiated,
+                    root: None,
+                    file_count: 3,
+                    total_size: 45,
+                    created_at: Utc::now(),
+                    updated_at: Utc::now(),
+                })
+            });
+    });
+    let server_handle = simulator.start().await;
+
+    let url = format!("{}/{}/versions", base_url, expected_id);
+
+    let (signature, timestamp) = create_valid_signature();
+    let resp = reqwest::Client::new()
+        .post(&url)
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let body: UploadSummaryResponse = resp.json().await.unwrap();
+    assert_eq!(body.id, expected_id);
+    assert_eq!(body.version, 2);
+    assert_eq!(body.previous_version, Some(1));
+    assert_eq!(body.root_hex, None);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_proof_of_unknown_version_returns_not_found() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_get_proof()
+            .with(eq(TEST_KEY), eq(expected_id), eq(Some(7)), eq(0))
+            .times(1)
+            .returning(|_, _, _, _| Err(FileServiceError::VersionNotFound));
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = download(
+        &format!("{}/{}/proof/0?version=7", base_url, expected_id),
+        &[],
+    )
+    .await;
+
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_complete_with_invalid_id_returns_not_found() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let id = Uuid::new_v4();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_complete()
+            .withf(move |_, got_id| *got_id == id)
+            .times(1)
+            .returning(|_, _| Err(FileServiceError::FileNotFound));
+    });
+
+    let server_handle = simulator.start().await;
+
+    let url = format!("{}/{}/complete", base_url, id);
+
+    let (signature, timestamp) = create_valid_signature();
+    let resp = reqwest::Client::new()
+        .post(&url)
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_delete_returns_no_content() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_delete()
+            .with(eq(TEST_KEY), eq(expected_id))
+            .times(1)
+            .returning(|_, _| Ok(()));
+    });
+
+    let server_handle = simulator.start().await;
+
+    let url = format!("{}/{}", base_url, expected_id);
+
+    let (signature, timestamp) = create_valid_signature();
+    let resp = reqwest::Client::new()
+        .delete(&url)
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_watch_upload_streams_events_as_server_sent_events() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let id = Uuid::new_v4();
+    let root = Hash32::hash(b"root");
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_watch()
+            .with(eq(TEST_KEY), eq(id))
+            .times(1)
+            .returning(move |_, _| {
+                Ok(Box::pin(stream::iter(vec![
+                    UploadEvent::FileReceived {
+                        index: 0,
+                        name: "file1.txt".to_string(),
+                        size: 15,
+                    },
+                    UploadEvent::Completed { version: 1, root },
+                ])))
+            });
+    });
+
+    let server_handle = simulator.start().await;
+
+    let url = format!("{}/{}/events", base_url, id);
+
+    let (signature, timestamp) = create_valid_signature();
+    let resp = reqwest::Client::new()
+        .get(&url)
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "text/event-stream");
+
+    let body = resp.text().await.unwrap();
+    assert_eq!(
+        body,
+        format!(
+            "event: file_received\n\
+             data: {{\"event\":\"file_received\",\"index\":0,\"name\":\"file1.txt\",\"size\":15}}\n\n\
+             event: completed\n\
+             data: {{\"event\":\"completed\",\"version\":1,\"root\":\"{}\"}}\n\n",
+            root.to_hex()
+        )
+    );
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_watch_upload_with_invalid_id_returns_not_found() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let id = Uuid::new_v4();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_watch()
+            .with(eq(TEST_KEY), eq(id))
+            .times(1)
+            .returning(|_, _| Err(FileServiceError::FileNotFound));
+    });
+
+    let server_handle = simulator.start().await;
+
+    let url = format!("{}/{}/events", base_url, id);
+
+    let (signature, timestamp) = create_valid_signature();
+    let resp = reqwest::Client::new()
+        .get(&url)
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_delete_with_invalid_id_returns_not_found() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let id = Uuid::new_v4();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_delete()
+            .with(eq(TEST_KEY), eq(id))
+            .times(1)
+            .returning(|_, _| Err(FileServiceError::FileNotFound));
+    });
+
+    let server_handle = simulator.start().await;
+
+    let url = format!("{}/{}", base_url, id);
+
+    let (signature, timestamp) = create_valid_signature();
+    let resp = reqwest::Client::new()
+        .delete(&url)
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    server_handle.abort();
+}
+
+const DOWNLOAD_CONTENTS: &[u8] = b"contents_file_1";
+
+fn configure_download(simulator: &mut WebServerSimulator, id: Uuid) -> Hash32 {
+    let hash = Hash32::hash(DOWNLOAD_CONTENTS);
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_get_file_descriptor()
+            .with(eq(TEST_KEY), eq(id), eq(None), eq(0))
+            .returning(move |_, _, _, _| {
+                Ok(FileDescriptor {
+                    name: "résumé 1.txt".to_string(),
+                    hash,
+                    size: DOWNLOAD_CONTENTS.len() as u64,
+                    content_type: "text/plain".to_string(),
+                })
+            });
+        srv.expect_get_file_content()
+            .with(eq(TEST_KEY), eq(id), eq(None), eq(0), always())
+            .returning(|_, _, _, _, range| {
+                let contents = match range {
+                    Some(range) => {
+                        &DOWNLOAD_CONTENTS[*range.start() as usize..=*range.end() as usize]
+                    }
+                    None => DOWNLOAD_CONTENTS,
+                };
+                let stream: FileStream = Box::pin(stream::iter([Ok(Bytes::from_static(contents))]));
+                Ok(stream)
+            });
+    });
+
+    hash
+}
+
+async fn download(url: &str, headers: &[(&str, &str)]) -> reqwest::Response {
+    let (signature, timestamp) = create_valid_signature();
+    let mut request = reqwest::Client::new()
+        .get(url)
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature);
+
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+
+    request.send().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_get_file_returns_contents_with_headers() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    let hash = configure_download(&mut simulator, expected_id);
+    let server_handle = simulator.start().await;
+
+    let resp = download(&format!("{}/{}/file/0", base_url, expected_id), &[]).await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let headers = resp.headers();
+    assert_eq!(headers["etag"], format!("\"{}\"", hash.to_hex()));
+    assert_eq!(headers["accept-ranges"], "bytes");
+    assert_eq!(headers["content-type"], "text/plain");
+    assert_eq!(
+        headers["content-length"],
+        DOWNLOAD_CONTENTS.len().to_string()
+    );
+    assert_eq!(
+        headers["content-disposition"],
+        "attachment; filename=\"r_sum_ 1.txt\"; filename*=UTF-8''r%C3%A9sum%C3%A9%201.txt"
+    );
+    assert_eq!(resp.bytes().await.unwrap(), DOWNLOAD_CONTENTS);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_file_by_url_encoded_name_returns_contents() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    configure_download(&mut simulator, expected_id);
+    simulator.configure_file_service(|srv| {
+        srv.expect_get_file_index()
+            .with(
+                eq(TEST_KEY),
+                eq(expected_id),
+                eq(None),
+                eq("reports/résumé 1.txt"),
+            )
+            .times(1)
+            .returning(|_, _, _, _| Ok(0));
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = download(
+        &format!(
+            "{}/{}/files/reports%2Fr%C3%A9sum%C3%A9%201.txt",
+            base_url, expected_id
+        ),
+        &[],
+    )
+    .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.bytes().await.unwrap(), DOWNLOAD_CONTENTS);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_file_by_unknown_name_returns_not_found() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    simulator.configure_file_service(|srv| {
+        srv.expect_get_file_index()
+            .returning(|_, _, _, _| Err(FileServiceError::FileNotFound));
+        srv.expect_get_file_descriptor().times(0);
+        srv.expect_get_proof().times(0);
+    });
+    let server_handle = simulator.start().await;
+
+    for path in ["missing.txt", "missing.txt/proof"] {
+        let resp = download(&format!("{}/{}/files/{}", base_url, expected_id, path), &[]).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_proof_by_name_returns_proof_of_its_index() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    let leaf_hash = Hash32::hash(b"contents_file_2");
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_get_file_index()
+            .with(eq(TEST_KEY), eq(expected_id), eq(None), eq("file 2.txt"))
+            .returning(|_, _, _, _| Ok(2));
+        srv.expect_get_proof()
+            .with(eq(TEST_KEY), eq(expected_id), eq(None), eq(2))
+            .times(1)
+            .returning(move |_, _, _, _| {
+                Ok(Proof {
+                    leaf_hash: leaf_hash.to_hex(),
+                    steps: vec![],
+                })
+            });
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = download(
+        &format!("{}/{}/files/file%202.txt/proof", base_url, expected_id),
+        &[],
+    )
+    .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: ProofResponse = resp.json().await.unwrap();
+    assert_eq!(body.leaf_hash, leaf_hash.to_hex());
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_file_with_range_returns_partial_content() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    configure_download(&mut simulator, expected_id);
+    let server_handle = simulator.start().await;
+
+    let url = format!("{}/{}/file/0", base_url, expected_id);
+
+    let resp = download(&url, &[("Range", "bytes=9-")]).await;
+    assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(resp.headers()["content-range"], "bytes 9-14/15");
+    assert_eq!(resp.headers()["content-length"], "6");
+    assert_eq!(resp.bytes().await.unwrap(), &b"file_1"[..]);
+
+    let resp = download(&url, &[("Range", "bytes=-6")]).await;
+    assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(resp.bytes().await.unwrap(), &b"file_1"[..]);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_file_with_stale_if_range_returns_full_content() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    configure_download(&mut simulator, expected_id);
+    let server_handle = simulator.start().await;
+
+    let resp = download(
+        &format!("{}/{}/file/0", base_url, expected_id),
+        &[("Range", "bytes=0-3"), ("If-Range", "\"outdated\"")],
+    )
+    .await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.bytes().await.unwrap(), DOWNLOAD_CONTENTS);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_file_with_unsatisfiable_range_returns_range_not_satisfiable() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    configure_download(&mut simulator, expected_id);
+    let server_handle = simulator.start().await;
+
+    let resp = download(
+        &format!("{}/{}/file/0", base_url, expected_id),
+        &[("Range", "bytes=100-")],
+    )
+    .await;
+
+    assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(resp.headers()["content-range"], "bytes */15");
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_file_accepting_zstd_returns_compressed_contents_as_stored() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    let hash = configure_download(&mut simulator, expected_id);
+    let compressed = b"compressed contents";
+    simulator.configure_file_service(|srv| {
+        srv.expect_get_encoded_file_content()
+            .with(eq(TEST_KEY), eq(expected_id), eq(None), eq(0))
+            .times(1)
+            .returning(|_, _, _, _| {
+                Ok(Some(EncodedContent {
+                    encoding: ContentEncoding::Zstd,
+                    size: compressed.len() as u64,
+                    content: Box::pin(stream::iter([Ok(Bytes::from_static(compressed))])),
+                }))
+            });
+    });
+    let server_handle = simulator.start().await;
+
+    let url = format!("{}/{}/file/0", base_url, expected_id);
+
+    let resp = download(&url, &[("Accept-Encoding", "gzip, zstd")]).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let headers = resp.headers();
+    assert_eq!(headers["content-encoding"], "zstd");
+    assert_eq!(headers["content-length"], compressed.len().to_string());
+    assert_eq!(headers["etag"], format!("W/\"{}\"", hash.to_hex()));
+    assert_eq!(headers["vary"], "accept-encoding");
+    assert_eq!(resp.bytes().await.unwrap(), &compressed[..]);
+
+    // Ranges and refused encodings are served from the decompressed contents.
+    let resp = download(&url, &[("Accept-Encoding", "zstd"), ("Range", "bytes=9-")]).await;
+    assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+    assert!(resp.headers().get("content-encoding").is_none());
+    assert_eq!(resp.bytes().await.unwrap(), &b"file_1"[..]);
+
+    let resp = download(&url, &[("Accept-Encoding", "zstd;q=0, gzip")]).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get("content-encoding").is_none());
+    assert_eq!(resp.bytes().await.unwrap(), DOWNLOAD_CONTENTS);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_file_with_matching_etag_returns_not_modified() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    let hash = Hash32::hash(DOWNLOAD_CONTENTS);
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_get_file_descriptor()
+            .returning(move |_, _, _, _| {
+                Ok(FileDescriptor {
+                    name: "file1.txt".to_string(),
+                    hash,
+                    size: DOWNLOAD_CONTENTS.len() as u64,
+                    content_type: "text/plain".to_string(),
+                })
+            });
+        srv.expect_get_file_content().times(0);
+    });
+    let server_handle = simulator.start().await;
+
+    let etag = format!("\"{}\"", hash.to_hex());
+    let resp = download(
+        &format!("{}/{}/file/0", base_url, expected_id),
+        &[("If-None-Match", &etag)],
+    )
+    .await;
+
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(resp.headers()["etag"], etag);
+
+    server_handle.abort();
+}
+
+fn upload_session(upload_id: Uuid, length: Option<u64>, offset: u64) -> UploadSession {
+    let metadata = FileMetadata {
+        name: "file1.txt".to_string(),
+        index: 0,
+        content_type: None,
+    };
+
+    let mut session = UploadSession::new(upload_id, metadata, length);
+    if offset > 0 {
+        session.append(Uuid::new_v4(), offset);
+    }
+    session
+}
+
+fn session_request(
+    method: reqwest::Method,
+    url: &str,
+    headers: &[(&str, &str)],
+) -> reqwest::RequestBuilder {
+    let (signature, timestamp) = create_valid_signature();
+    let mut request = reqwest::Client::new()
+        .request(method, url)
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature);
+
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+
+    request
+}
+
+#[tokio::test]
+async fn test_create_session_returns_created_with_location() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    let session = upload_session(expected_id, Some(10), 0);
+    let session_id = session.id();
+
+    simulator.configure_upload_session_service(|srv| {
+        let session = session.clone();
+        srv.expect_create()
+            .withf(move |owner, id, metadata, length| {
+                owner == TEST_KEY
+                    && *id == expected_id
+                    && metadata.name == "file1.txt"
+                    && *length == Some(10)
+            })
+            .times(1)
+            .returning(move |_, _, _, _| Ok(session.clone()));
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(
+        reqwest::Method::POST,
+        &format!(
+            "{}/{}/sessions?name=file1.txt&index=0",
+            base_url, expected_id
+        ),
+        &[("Upload-Length", "10")],
+    )
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(
+        resp.headers()["location"],
+        format!("/api/v1/{}/sessions/{}", expected_id, session_id).as_str()
+    );
+
+    let body: UploadSessionResponse = resp.json().await.unwrap();
+    assert_eq!(body.session_id, session_id);
+    assert_eq!(body.offset, 0);
+    assert_eq!(body.length, Some(10));
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_retried_create_session_with_idempotency_key_replays_location() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    let session = upload_session(expected_id, Some(10), 0);
+    let session_id = session.id();
+    let recorded: Arc<Mutex<Option<IdempotencyRow>>> = Arc::default();
+
+    simulator.configure_upload_session_service(|srv| {
+        let session = session.clone();
+        srv.expect_create()
+            .times(1)
+            .returning(move |_, _, _, _| Ok(session.clone()));
+    });
+    simulator.configure_idempotency(IdempotencyConfig::default(), |repo| {
+        let recorded_get = Arc::clone(&recorded);
+        repo.expect_get()
+            .times(2)
+            .returning(move |_, _| Ok(recorded_get.lock().unwrap().clone()));
+        let recorded_insert = Arc::clone(&recorded);
+        repo.expect_insert().times(1).returning(move |row| {
+            *recorded_insert.lock().unwrap() = Some(row);
+            Ok(())
+        });
+    });
+    let server_handle = simulator.start().await;
+
+    let url = format!(
+        "{}/{}/sessions?name=file1.txt&index=0",
+        base_url, expected_id
+    );
+    let expected_location = format!("/api/v1/{}/sessions/{}", expected_id, session_id);
+
+    for replayed in [false, true] {
+        let resp = session_request(
+            reqwest::Method::POST,
+            &url,
+            &[("Upload-Length", "10"), ("Idempotency-Key", "session-1")],
+        )
+        .send()
+        .await
+        .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(
+            resp.headers().get("Idempotent-Replayed").is_some(),
+            replayed
+        );
+        assert_eq!(resp.headers()["location"], expected_location.as_str());
+
+        let body: UploadSessionResponse = resp.json().await.unwrap();
+        assert_eq!(body.session_id, session_id);
+    }
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_session_offset_is_returned_in_headers() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    let session = upload_session(expected_id, Some(10), 4);
+    let session_id = session.id();
+
+    simulator.configure_upload_session_service(|srv| {
+        let session = session.clone();
+        srv.expect_get()
+            .with(eq(TEST_KEY), eq(expected_id), eq(session_id))
+            .returning(move |_, _, _| Ok(session.clone()));
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(
+        reqwest::Method::HEAD,
+        &format!("{}/{}/sessions/{}", base_url, expected_id, session_id),
+        &[],
+    )
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["upload-offset"], "4");
+    assert_eq!(resp.headers()["upload-length"], "10");
+    assert_eq!(resp.headers()["cache-control"], "no-store");
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_append_chunk_without_offset_returns_bad_request() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_upload_session_service(|srv| {
+        srv.expect_append().times(0);
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(
+        reqwest::Method::PATCH,
+        &format!(
+            "{}/{}/sessions/{}",
+            base_url,
+            Uuid::new_v4(),
+            Uuid::new_v4()
+        ),
+        &[],
+    )
+    .body("01234")
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_append_chunk_with_wrong_offset_returns_conflict() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_upload_session_service(|srv| {
+        srv.expect_append()
+            .returning(|_, _, _, _, _| Err(FileServiceError::UploadOffsetMismatch(5)));
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(
+        reqwest::Method::PATCH,
+        &format!(
+            "{}/{}/sessions/{}",
+            base_url,
+            Uuid::new_v4(),
+            Uuid::new_v4()
+        ),
+        &[("Upload-Offset", "0")],
+    )
+    .body("01234")
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["offset"], 5);
+
+    server_handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_append_chunk_returns_new_offset() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    let session = upload_session(expected_id, None, 0);
+    let session_id = session.id();
+
+    simulator.configure_upload_session_service(|srv| {
+        let mut session = session.clone();
+        srv.expect_append()
+            .with(
+                eq(TEST_KEY),
+                eq(expected_id),
+                eq(session_id),
+                eq(0),
+                always(),
+            )
+            .times(1)
+            .returning(move |_, _, _, _, content: FileStream| {
+                let content =
+                    block_in_place(|| Handle::current().block_on(content.try_collect::<Vec<_>>()))
+                        .unwrap()
+                        .concat();
+                session.append(Uuid::new_v4(), content.len() as u64);
+                Ok(session.clone())
+            });
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(
+        reqwest::Method::PATCH,
+        &format!("{}/{}/sessions/{}", base_url, expected_id, session_id),
+        &[("Upload-Offset", "0")],
+    )
+    .body("01234")
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    assert_eq!(resp.headers()["upload-offset"], "5");
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_finalize_session_returns_file_metadata() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    let session = upload_session(expected_id, Some(5), 5);
+    let session_id = session.id();
+
+    simulator.configure_upload_session_service(|srv| {
+        let session = session.clone();
+        srv.expect_get()
+            .returning(move |_, _, _| Ok(session.clone()));
+        srv.expect_finalize()
+            .with(eq(TEST_KEY), eq(expected_id), eq(session_id))
+            .times(1)
+            .returning(|_, _, _| Ok("encoded_hash".to_string()));
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(
+        reqwest::Method::POST,
+        &format!(
+            "{}/{}/sessions/{}/finalize",
+            base_url, expected_id, session_id
+        ),
+        &[],
+    )
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: FileMetadataResponse = resp.json().await.unwrap();
+    assert_eq!(body.name, "file1.txt");
+    assert_eq!(body.index, 0);
+    assert_eq!(body.encoded_hash, "encoded_hash");
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_check_blob_returns_whether_contents_are_stored() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let stored = Hash32::hash(b"contents_file_1");
+    let unknown = Hash32::hash(b"contents_file_2");
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_blob_exists()
+            .returning(move |owner, hash| Ok(owner == TEST_KEY && hash == stored));
+    });
+    let server_handle = simulator.start().await;
+
+    for (hash, expected_status) in [
+        (stored.to_hex(), StatusCode::OK),
+        (unknown.to_hex(), StatusCode::NOT_FOUND),
+        ("not-a-hash".to_string(), StatusCode::BAD_REQUEST),
+    ] {
+        let resp = session_request(
+            reqwest::Method::HEAD,
+            &format!("{}/blobs/{}", base_url, hash),
+            &[],
+        )
+        .send()
+        .await
+        .unwrap();
+
+        assert_eq!(resp.status(), expected_status);
+    }
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_upload_with_known_hash_skips_body() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let expected_id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_upload_file().times(0);
+        srv.expect_upload_known_file()
+            .withf(move |owner, id, metadata, known_hash| {
+                owner == TEST_KEY
+                    && *id == expected_id
+                    && metadata.name == "file1.txt"
+                    && *known_hash == hash
+            })
+            .times(1)
+            .returning(move |_, _, _, hash| Ok(hash.to_hex()));
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(
+        reqwest::Method::POST,
+        &format!(
+            "{}/{}/upload?name=file1.txt&index=0&hash={}",
+            base_url,
+            expected_id,
+            hash.to_hex()
+        ),
+        &[],
+    )
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: FileMetadataResponse = resp.json().await.unwrap();
+    assert_eq!(body.encoded_hash, hash.to_hex());
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_list_files_returns_page_with_next_offset() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+    let uploaded_at = Utc::now();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_list_files()
+            .with(eq(TEST_KEY), eq(id), eq(None), eq(1), eq(1))
+            .times(1)
+            .returning(move |_, _, _, _, _| {
+                Ok(FilePage {
+                    files: vec![FileEntry {
+                        index: 1,
+                        name: "file1.txt".to_string(),
+                        hash,
+                        size: 15,
+                        content_type: "text/plain".to_string(),
+                        uploaded_at,
+                    }],
+                    total: 3,
+                })
+            });
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(
+        reqwest::Method::GET,
+        &format!("{}/{}/files?offset=1&limit=1", base_url, id),
+        &[],
+    )
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: FileListResponse = resp.json().await.unwrap();
+    assert_eq!(body.total, 3);
+    assert_eq!(body.next_offset, Some(2));
+    assert_eq!(body.files.len(), 1);
+    assert_eq!(body.files[0].name, "file1.txt");
+    assert_eq!(body.files[0].size, 15);
+    assert_eq!(body.files[0].leaf_hash, hash.to_hex());
+    assert_eq!(body.files[0].content_type, "text/plain");
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_list_uploads_is_scoped_to_authenticated_key() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    // Cursors keep timestamps in milliseconds, like the repositories.
+    let now = DateTime::from_timestamp_millis(Utc::now().timestamp_millis()).unwrap();
+    let cursor = UploadCursor {
+        created_at: now - Duration::hours(1),
+        id: Uuid::new_v4(),
+    };
+    let next_cursor = UploadCursor {
+        created_at: now - Duration::hours(2),
+        id: Uuid::new_v4(),
+    };
+    let root = Hash32::hash(b"root");
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_list_uploads()
+            .withf(move |owner, filter, after, limit| {
+                owner == TEST_KEY
+                    && filter.state == Some(UploadState::Completed)
+                    && filter.created_after.is_none()
+                    && *after == Some(cursor)
+                    && *limit == 10
+            })
+            .times(1)
+            .returning(move |_, _, _, _| {
+                Ok(UploadPage {
+                    uploads: vec![UploadSummary {
+                        id: next_cursor.id,
+                        version: 1,
+                        previous_version: None,
+                        state: UploadState::Completed,
+                        root: Some(root),
+                        file_count: 2,
+                        total_size: 30,
+                        created_at: next_cursor.created_at,
+                        updated_at: next_cursor.created_at,
+                    }],
+                    next_cursor: Some(next_cursor),
+                })
+            });
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(
+        reqwest::Method::GET,
+        &format!(
+            "{}/uploads?state=completed&cursor={}&limit=10",
+            base_url, cursor
+        ),
+        &[],
+    )
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: UploadListResponse = resp.json().await.unwrap();
+    assert_eq!(body.uploads.len(), 1);
+    assert_eq!(body.uploads[0].id, next_cursor.id);
+    assert_eq!(body.uploads[0].state, "completed");
+    assert_eq!(body.uploads[0].root_hex, Some(root.to_hex()));
+    assert_eq!(body.uploads[0].file_count, 2);
+    assert_eq!(body.uploads[0].total_size, 30);
+    assert_eq!(body.next_cursor, Some(next_cursor.to_string()));
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_list_uploads_with_invalid_filters_returns_bad_request() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_list_uploads().times(0);
+    });
+    let server_handle = simulator.start().await;
+
+    for query in ["state=unknown", "cursor=not-a-cursor"] {
+        let resp = session_request(
+            reqwest::Method::GET,
+            &format!("{}/uploads?{}", base_url, query),
+            &[],
+        )
+        .send()
+        .await
+        .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_archive_streams_requested_format() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let id = Uuid::new_v4();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_get_archive()
+            .with(eq(TEST_KEY), eq(id), eq(None), eq(ArchiveFormat::Zip))
+            .times(1)
+            .returning(|_, _, _, _| {
+                let content: FileStream =
+                    Box::pin(stream::iter(vec![Ok(Bytes::from_static(b"PK archive"))]));
+                Ok(content)
+            });
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = download(&format!("{}/{}/archive?format=zip", base_url, id), &[]).await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "application/zip");
+    assert_eq!(
+        resp.headers()["content-disposition"],
+        format!("attachment; filename=\"{id}.zip\"; filename*=UTF-8''{id}.zip")
+    );
+    assert_eq!(resp.bytes().await.unwrap(), "PK archive");
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_archive_of_initiated_upload_returns_conflict() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let id = Uuid::new_v4();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_get_archive()
+            .with(eq(TEST_KEY), eq(id), eq(None), eq(ArchiveFormat::Tar))
+            .times(1)
+            .returning(|_, _, _, _| Err(FileServiceError::UploadNotCompleted));
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = download(&format!("{}/{}/archive", base_url, id), &[]).await;
+
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_archive_with_unknown_format_returns_bad_request() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_get_archive().times(0);
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = download(
+        &format!("{}/{}/archive?format=rar", base_url, Uuid::new_v4()),
+        &[],
+    )
+    .await;
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    server_handle.abort();
+}
+
+fn admin_request(method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
+    let timestamp = Utc::now().timestamp_millis().to_string();
+    let signature = create_signature(ADMIN_SECRET, &timestamp);
+
+    reqwest::Client::new()
+        .request(method, url)
+        .header("X-AUTH-KEY", ADMIN_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", signature)
+}
+
+#[tokio::test]
+async fn test_list_scrub_findings_returns_findings_to_admins() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let upload_id = Uuid::new_v4();
+    let expected = Hash32::hash(b"contents_file_1");
+    let actual = Hash32::hash(b"rotten_file_1");
+    let cursor = UploadCursor {
+        created_at: DateTime::from_timestamp_millis(Utc::now().timestamp_millis()).unwrap(),
+        id: upload_id,
+    };
+
+    simulator.configure_admin_service(|srv| {
+        srv.expect_list_scrub_findings()
+            .with(eq(5), eq(20))
+            .times(1)
+            .returning(move |_, _| {
+                Ok(ScrubReport {
+                    findings: vec![ScrubFindingRow {
+                        upload_id,
+                        index: 1,
+                        name: "file_1.txt".to_string(),
+                        kind: ScrubFindingKind::Corrupted,
+                        expected_hash: expected,
+                        actual_hash: Some(actual),
+                        detected_at: Utc::now(),
+                    }],
+                    cursor: Some(cursor),
+                })
+            });
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = admin_request(
+        reqwest::Method::GET,
+        &format!("{}/admin/scrub/findings?offset=5&limit=20", base_url),
+    )
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: ScrubReportResponse = resp.json().await.unwrap();
+    assert_eq!(body.findings.len(), 1);
+    assert_eq!(body.findings[0].upload_id, upload_id);
+    assert_eq!(body.findings[0].kind, "corrupted");
+    assert_eq!(body.findings[0].expected_hash, expected.to_hex());
+    assert_eq!(body.findings[0].actual_hash, Some(actual.to_hex()));
+    assert_eq!(body.cursor, Some(cursor.to_string()));
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_list_scrub_findings_as_regular_client_returns_forbidden() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_admin_service(|srv| {
+        srv.expect_list_scrub_findings().times(0);
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(
+        reqwest::Method::GET,
+        &format!("{}/admin/scrub/findings", base_url),
+        &[],
+    )
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_collect_orphans_reports_orphans_to_admins() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let upload_id = Uuid::new_v4();
+    let last_modified = DateTime::from_timestamp_millis(Utc::now().timestamp_millis()).unwrap();
+    let cursor = UploadCursor {
+        created_at: last_modified,
+        id: Uuid::new_v4(),
+    };
+    let next_cursor = UploadCursor {
+        created_at: last_modified,
+        id: upload_id,
+    };
+
+    simulator.configure_admin_service(|srv| {
+        srv.expect_collect_orphans()
+            .with(eq(Some(cursor)), eq(10), eq(true))
+            .times(1)
+            .returning(move |_, _, _| {
+                Ok(OrphanReport {
+                    uploads_checked: 10,
+                    orphans: vec![OrphanObject {
+                        upload_id,
+                        name: ".staging/1".to_string(),
+                        size: 15,
+                        last_modified,
+                    }],
+                    deleted: 0,
+                    failed: 0,
+                    next_cursor: Some(next_cursor),
+                })
+            });
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = admin_request(
+        reqwest::Method::POST,
+        &format!(
+            "{}/admin/gc/orphans?dry_run=true&cursor={}&limit=10",
+            base_url, cursor
+        ),
+    )
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: OrphanReportResponse = resp.json().await.unwrap();
+    assert!(body.dry_run);
+    assert_eq!(body.uploads_checked, 10);
+    assert_eq!(body.orphans.len(), 1);
+    assert_eq!(body.orphans[0].upload_id, upload_id);
+    assert_eq!(body.orphans[0].name, ".staging/1");
+    assert_eq!(body.orphans[0].size, 15);
+    assert_eq!(body.deleted, 0);
+    assert_eq!(body.next_cursor, Some(next_cursor.to_string()));
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_collect_orphans_with_invalid_cursor_returns_bad_request() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_admin_service(|srv| {
+        srv.expect_collect_orphans().times(0);
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = admin_request(
+        reqwest::Method::POST,
+        &format!("{}/admin/gc/orphans?cursor=not-a-cursor", base_url),
+    )
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    server_handle.abort();
+}
+
+// Checks the response against the simulator key the way a pinning client does, returning its body.
+async fn verify_signed_response(resp: reqwest::Response, request_signature: Option<&str>) -> Bytes {
+    let path = match resp.url().query() {
+        Some(query) => format!("{}?{}", resp.url().path(), query),
+        None => resp.url().path().to_string(),
+    };
+    let status = resp.status().as_u16();
+    let headers = resp.headers().clone();
+    let body = resp.bytes().await.unwrap();
+
+    let key = WebServerSimulator::signing_key();
+    assert_eq!(headers[SERVER_KEY_HEADER], key_id(key.as_bytes()));
+    assert_eq!(
+        headers[SERVER_DIGEST_HEADER],
+        Hash32::hash(&body).to_hex().as_str()
+    );
+
+    let mut signed = SignedResponse::new(&key_id(key.as_bytes()), "GET", &path, status)
+        .with_headers(|name| headers.get(name).and_then(|value| value.to_str().ok()));
+    if let Some(request_signature) = request_signature {
+        signed = signed.with_request_signature(request_signature);
+    }
+
+    let signature = hex::decode(&headers[SERVER_SIGNATURE_HEADER]).unwrap();
+    let signature = Signature::from_slice(&signature).unwrap();
+    key.verify_strict(&signed.message(), &signature).unwrap();
+
+    body
+}
+
+#[tokio::test]
+async fn test_responses_are_signed_and_bound_to_the_request() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_file_service(|srv| {
+        srv.expect_list_uploads().times(1).returning(|_, _, _, _| {
+            Ok(UploadPage {
+                uploads: vec![],
+                next_cursor: None,
+            })
+        });
+    });
+    let server_handle = simulator.start().await;
+
+    let (signature, timestamp) = create_valid_signature();
+    let resp = reqwest::Client::new()
+        .get(format!("{}/uploads?limit=10", base_url))
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", &signature)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body = verify_signed_response(resp, Some(&signature)).await;
+    let body: UploadListResponse = serde_json::from_slice(&body).unwrap();
+    assert!(body.uploads.is_empty());
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_json_responses_are_digested_whatever_their_size() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_max_signed_body(8);
+    simulator.configure_file_service(|srv| {
+        srv.expect_list_uploads().times(1).returning(|_, _, _, _| {
+            Ok(UploadPage {
+                uploads: vec![],
+                next_cursor: None,
+            })
+        });
+    });
+    let server_handle = simulator.start().await;
+
+    let (signature, timestamp) = create_valid_signature();
+    let resp = reqwest::Client::new()
+        .get(format!("{}/uploads", base_url))
+        .header("X-AUTH-KEY", TEST_KEY)
+        .header("X-AUTH-TS", timestamp)
+        .header("X-AUTH-SIGNATURE", &signature)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body = verify_signed_response(resp, Some(&signature)).await;
+    assert!(body.len() > 8);
+
+    server_handle.abort();
+}
+
+// Their contents are verified through the proofs of the upload instead.
+#[tokio::test]
+async fn test_streamed_downloads_are_signed_without_digest() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let id = Uuid::new_v4();
+    configure_download(&mut simulator, id);
+    let server_handle = simulator.start().await;
+
+    let resp = download(&format!("{}/{}/file/0", base_url, id), &[]).await;
+
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get(SERVER_SIGNATURE_HEADER).is_some());
+    assert!(resp.headers().get(SERVER_DIGEST_HEADER).is_none());
+    assert_eq!(resp.bytes().await.unwrap(), DOWNLOAD_CONTENTS);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_rejected_requests_are_signed_as_well() {
+    let simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+    let server_handle = simulator.start().await;
+
+    let resp = reqwest::Client::new()
+        .get(format!("{}/uploads", base_url))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    verify_signed_response(resp, None).await;
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_key_registry_publishes_active_and_retired_keys() {
+    let simulator = WebServerSimulator::new().await.unwrap();
+    let root_url = simulator.root_url();
+    let server_handle = simulator.start().await;
+
+    let resp = reqwest::Client::new()
+        .get(format!("{}/.well-known/file-server-keys", root_url))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body = verify_signed_response(resp, None).await;
+    let registry: KeyRegistry = serde_json::from_slice(&body).unwrap();
+
+    let active = WebServerSimulator::signing_key();
+    let retired = WebServerSimulator::retired_key();
+    let keys: Vec<_> = registry
+        .keys
+        .iter()
+        .map(|key| (key.key_id.clone(), key.public_key.clone(), key.status))
+        .collect();
+    assert_eq!(
+        keys,
+        vec![
+            (
+                key_id(active.as_bytes()),
+                hex::encode(active.as_bytes()),
+                KeyStatus::Active
+            ),
+            (
+                key_id(retired.as_bytes()),
+                hex::encode(retired.as_bytes()),
+                KeyStatus::Retired
+            ),
+        ]
+    );
+
+    server_handle.abort();
+}
+
+fn log_head(size: u64) -> LogHead {
+    LogHead {
+        size,
+        root: Hash32::hash(format!("log_root_{size}").as_bytes()),
+        timestamp: DateTime::from_timestamp_millis(1_700_000_000_000).unwrap(),
+        key_id: "0011223344556677".to_string(),
+        signature: "ab".repeat(64),
+    }
+}
+
+#[tokio::test]
+async fn test_get_log_head_returns_signed_head() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_transparency_log_service(|srv| {
+        srv.expect_head().times(1).returning(|| Ok(log_head(12)));
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(reqwest::Method::GET, &format!("{}/log/head", base_url), &[])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: LogHeadResponse = resp.json().await.unwrap();
+    let expected = log_head(12);
+    assert_eq!(body.size, 12);
+    assert_eq!(body.root, expected.root.to_hex());
+    assert_eq!(body.timestamp, 1_700_000_000_000);
+    assert_eq!(body.key_id, expected.key_id);
+    assert_eq!(body.signature, expected.signature);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_log_proof_returns_entry_and_audit_path() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    let upload_id = Uuid::new_v4();
+    let root = Hash32::hash(b"upload_root");
+    let path = vec![Hash32::hash(b"sibling_1"), Hash32::hash(b"sibling_2")];
+
+    let proof_path = path.clone();
+    simulator.configure_transparency_log_service(|srv| {
+        let proof_path = proof_path.clone();
+        srv.expect_inclusion_proof()
+            .with(eq(TEST_KEY), eq(upload_id), eq(Some(4)))
+            .times(1)
+            .returning(move |_, upload_id, _| {
+                Ok(LogInclusionProof {
+                    head: log_head(4),
+                    entry: LogEntryRow {
+                        index: 2,
+                        upload_id,
+                        owner: TEST_KEY.to_string(),
+                        root,
+                        appended_at: DateTime::from_timestamp_millis(1_600_000_000_000).unwrap(),
+                        leaf_hash: Hash32::hash(b"leaf"),
+                    },
+                    proof: proof_path.clone(),
+                })
+            });
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(
+        reqwest::Method::GET,
+        &format!("{}/{}/log/proof?size=4", base_url, upload_id),
+        &[],
+    )
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: LogInclusionProofResponse = resp.json().await.unwrap();
+    assert_eq!(body.head.size, 4);
+    assert_eq!(body.entry.index, 2);
+    assert_eq!(body.entry.upload_id, upload_id);
+    assert_eq!(body.entry.owner, TEST_KEY);
+    assert_eq!(body.entry.root, root.to_hex());
+    assert_eq!(body.entry.appended_at, 1_600_000_000_000);
+    assert_eq!(body.entry.leaf_hash, Hash32::hash(b"leaf").to_hex());
+    assert_eq!(
+        body.proof,
+        path.iter().map(|hash| hash.to_hex()).collect::<Vec<_>>()
+    );
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_log_proof_of_unlogged_upload_returns_not_found() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_transparency_log_service(|srv| {
+        srv.expect_inclusion_proof()
+            .times(1)
+            .returning(|_, _, _| Err(FileServiceError::LogEntryNotFound));
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(
+        reqwest::Method::GET,
+        &format!("{}/{}/log/proof", base_url, Uuid::new_v4()),
+        &[],
+    )
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_log_consistency_returns_both_heads() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_transparency_log_service(|srv| {
+        srv.expect_consistency_proof()
+            .with(eq(3), eq(None))
+            .times(1)
+            .returning(|first, _| {
+                Ok(LogConsistencyProof {
+                    first: log_head(first),
+                    second: log_head(8),
+                    proof: vec![Hash32::hash(b"node")],
+                })
+            });
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(
+        reqwest::Method::GET,
+        &format!("{}/log/consistency?first=3", base_url),
+        &[],
+    )
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: LogConsistencyProofResponse = resp.json().await.unwrap();
+    assert_eq!(body.first.size, 3);
+    assert_eq!(body.second.size, 8);
+    assert_eq!(body.proof, vec![Hash32::hash(b"node").to_hex()]);
+
+    server_handle.abort();
+}
+
+#[tokio::test]
+async fn test_get_log_consistency_with_invalid_sizes_returns_bad_request() {
+    let mut simulator = WebServerSimulator::new().await.unwrap();
+    let base_url = simulator.url();
+
+    simulator.configure_transparency_log_service(|srv| {
+        srv.expect_consistency_proof()
+            .with(eq(5), eq(Some(2)))
+            .times(1)
+            .returning(|_, _| Err(FileServiceError::InvalidLogSize));
+    });
+    let server_handle = simulator.start().await;
+
+    let resp = session_request(
+        reqwest::Method::GET,
+        &format!("{}/log/consistency?first=5&second=2", base_url),
+        &[],
+    )
+    .send()
+    .await
+    .unwrap();
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 
     server_handle.abort();
 }
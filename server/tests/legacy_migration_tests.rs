@@ -0,0 +1,254 @@
+mod helpers;
+
+use crate::helpers::{
+    mocks::{
+        MockBlobRepositoryImpl, MockFileRepositoryImpl, MockFileStorageImpl,
+        MockUsageRepositoryImpl,
+    },
+    tree_fixtures::{listed_once, tree_row},
+};
+use file_server_library::models::Hash32;
+use file_server_server::{
+    models::UploadState,
+    repositories::{FileMerkleTreeRow, StoredFileMetadata, UsageRow},
+    services::{LegacyMigration, LegacyMigrationConfig, MigrationReport, QuotaConfig, Quotas},
+};
+use mockall::predicate::eq;
+use std::sync::Arc;
+use uuid::Uuid;
+
+// Stored before uploads had an owner or per-file sizes and content types.
+fn legacy_row(id: Uuid, state: UploadState, name: &str, hash: Hash32) -> FileMerkleTreeRow {
+    let mut row = tree_row(id, state);
+    row.owner = String::new();
+    row.order = vec![name.to_string()];
+    row.leaf_hashes = vec![hash];
+    row
+}
+
+// None are left without owner once listed.
+fn listing(rows: Vec<FileMerkleTreeRow>) -> MockFileRepositoryImpl {
+    let mut next_page = listed_once(rows);
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_list()
+        .withf(|owner, _, _, limit| owner.is_empty() && *limit == 100)
+        .returning(move |_, _, _, _| Ok(next_page()));
+    file_repository
+}
+
+fn migration(
+    file_repository: MockFileRepositoryImpl,
+    file_storage: MockFileStorageImpl,
+    blob_repository: MockBlobRepositoryImpl,
+    owner: Option<&str>,
+) -> LegacyMigration {
+    LegacyMigration::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(blob_repository),
+        LegacyMigrationConfig {
+            owner: owner.map(str::to_string),
+            ..Default::default()
+        },
+    )
+}
+
+fn stored_metadata() -> StoredFileMetadata {
+    StoredFileMetadata {
+        size: 15,
+        content_type: "text/plain".to_string(),
+        content_encoding: None,
+    }
+}
+
+#[tokio::test]
+async fn test_migration_without_owner_leaves_legacy_uploads_alone() {
+    let row = legacy_row(
+        Uuid::new_v4(),
+        UploadState::Completed,
+        "file1.txt",
+        Hash32::hash(b"contents_file_1"),
+    );
+
+    let mut file_repository = listing(vec![row]);
+    file_repository.expect_update().never();
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_move_to_blob().never();
+
+    let migration = migration(
+        file_repository,
+        file_storage,
+        MockBlobRepositoryImpl::new(),
+        None,
+    );
+
+    assert_eq!(
+        migration.migrate().await.unwrap(),
+        MigrationReport::default()
+    );
+}
+
+#[tokio::test]
+async fn test_migration_moves_files_into_blobs_and_assigns_owner() {
+    let upload_id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+    let row = legacy_row(upload_id, UploadState::Initiated, "file1.txt", hash);
+
+    let mut file_repository = listing(vec![row]);
+    file_repository
+        .expect_update()
+        .withf(move |row| row.id == upload_id && row.owner == "client-1")
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_get_file_metadata()
+        .withf(move |id, name| *id == upload_id && name == "file1.txt")
+        .returning(|_, _| Ok(Some(stored_metadata())));
+    file_storage
+        .expect_move_to_blob()
+        .withf(move |id, name, blob| *id == upload_id && name == "file1.txt" && *blob == hash)
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_touch()
+        .with(eq(hash))
+        .times(1)
+        .returning(|_| Ok(()));
+    blob_repository
+        .expect_get_reference()
+        .returning(|_, _, _| Ok(None));
+    blob_repository
+        .expect_add_reference()
+        .withf(move |reference| {
+            reference.hash == hash
+                && reference.upload_id == upload_id
+                && reference.owner == "client-1"
+                && reference.name == "file1.txt"
+                && reference.content_type == "text/plain"
+        })
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut usage_repository = MockUsageRepositoryImpl::new();
+    usage_repository
+        .expect_add()
+        .with(eq(UsageRow {
+            owner: "client-1".to_string(),
+            stored_bytes: 0,
+            open_uploads: 1,
+        }))
+        .times(1)
+        .returning(|_| Ok(()));
+    let quotas = Arc::new(Quotas::new(
+        QuotaConfig::default(),
+        Arc::new(usage_repository),
+    ));
+
+    let migration = migration(
+        file_repository,
+        file_storage,
+        blob_repository,
+        Some("client-1"),
+    )
+    .with_quotas(quotas);
+
+    assert_eq!(
+        migration.migrate().await.unwrap(),
+        MigrationReport {
+            migrated: 1,
+            failed: 0,
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_migration_of_files_moved_before_references_them_and_assigns_owner() {
+    let upload_id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+    let row = legacy_row(upload_id, UploadState::Completed, "file1.txt", hash);
+
+    let mut file_repository = listing(vec![row]);
+    file_repository
+        .expect_update()
+        .withf(move |row| row.id == upload_id && row.owner == "client-1")
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_get_file_metadata()
+        .returning(|_, _| Ok(None));
+    file_storage.expect_move_to_blob().never();
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_get_reference()
+        .returning(|_, _, _| Ok(None));
+    blob_repository
+        .expect_add_reference()
+        .withf(move |reference| {
+            reference.hash == hash
+                && reference.owner == "client-1"
+                && reference.content_type == "application/octet-stream"
+        })
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let migration = migration(
+        file_repository,
+        file_storage,
+        blob_repository,
+        Some("client-1"),
+    );
+
+    assert_eq!(
+        migration.migrate().await.unwrap(),
+        MigrationReport {
+            migrated: 1,
+            failed: 0,
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_migration_keeps_uploads_failing_to_move_without_owner() {
+    let upload_id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+    let row = legacy_row(upload_id, UploadState::Completed, "file1.txt", hash);
+
+    let mut file_repository = listing(vec![row]);
+    file_repository.expect_update().never();
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_get_file_metadata()
+        .returning(|_, _| Ok(Some(stored_metadata())));
+    file_storage
+        .expect_move_to_blob()
+        .returning(|_, _, _| Err(anyhow::anyhow!("storage unavailable")));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository.expect_touch().returning(|_| Ok(()));
+    blob_repository.expect_add_reference().never();
+
+    let migration = migration(
+        file_repository,
+        file_storage,
+        blob_repository,
+        Some("client-1"),
+    );
+
+    assert_eq!(
+        migration.migrate().await.unwrap(),
+        MigrationReport {
+            migrated: 0,
+            failed: 1,
+        }
+    );
+}
@@ -0,0 +1,224 @@
+mod helpers;
+
+use crate::helpers::{
+    mocks::{MockFileRepositoryImpl, MockFileStorageImpl, MockUploadSessionRepositoryImpl},
+    tree_fixtures::{listed_once, tree_row, with_contents},
+};
+use chrono::{DateTime, Duration, Utc};
+use file_server_server::{
+    models::{UploadCursor, UploadState},
+    repositories::{FileMerkleTreeRow, StoredObject, UploadSessionRow},
+    services::{OrphanCollector, OrphanGcConfig},
+};
+use mockall::predicate::eq;
+use std::sync::Arc;
+use uuid::Uuid;
+
+fn upload_row(state: UploadState, order: &[&str]) -> FileMerkleTreeRow {
+    order
+        .iter()
+        .fold(tree_row(Uuid::new_v4(), state), |row, name| {
+            with_contents(row, name, name.as_bytes())
+        })
+}
+
+fn object(name: &str, last_modified: DateTime<Utc>) -> StoredObject {
+    StoredObject {
+        name: name.to_string(),
+        size: 15,
+        last_modified,
+    }
+}
+
+fn session_row(id: Uuid, upload_id: Uuid) -> UploadSessionRow {
+    UploadSessionRow {
+        id,
+        upload_id,
+        name: "file2.txt".to_string(),
+        index: 1,
+        content_type: None,
+        length: None,
+        offset: 0,
+        chunk_offsets: vec![],
+        chunk_ids: vec![],
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    }
+}
+
+fn listing(rows: Vec<FileMerkleTreeRow>, limit: usize) -> MockFileRepositoryImpl {
+    let mut next_page = listed_once(rows);
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_list_all()
+        .with(eq(None), eq(limit))
+        .times(1)
+        .returning(move |_, _| Ok(next_page()));
+    file_repository
+}
+
+fn collector(
+    file_repository: MockFileRepositoryImpl,
+    file_storage: MockFileStorageImpl,
+    upload_session_repository: MockUploadSessionRepositoryImpl,
+) -> OrphanCollector {
+    OrphanCollector::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(upload_session_repository),
+        OrphanGcConfig::default(),
+    )
+}
+
+#[tokio::test]
+async fn test_collect_deletes_orphans_and_keeps_referenced_objects() {
+    let row = upload_row(UploadState::Initiated, &["file1.txt"]);
+    let id = row.id;
+    let live_session = Uuid::new_v4();
+    let gone_session = Uuid::new_v4();
+    let old = Utc::now() - Duration::hours(2);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_list()
+        .with(eq(id))
+        .times(1)
+        .returning(move |_| {
+            Ok(vec![
+                object("file1.txt", old),
+                object(".staging/1", old),
+                object(".staging/2", Utc::now()),
+                object(
+                    &format!(".sessions/{live_session}/00000000000000000000"),
+                    old,
+                ),
+                object(
+                    &format!(".sessions/{gone_session}/00000000000000000000"),
+                    old,
+                ),
+                object(
+                    &format!(".sessions/{gone_session}/00000000000000000015"),
+                    old,
+                ),
+            ])
+        });
+    for name in [
+        ".staging/1".to_string(),
+        format!(".sessions/{gone_session}/00000000000000000000"),
+        format!(".sessions/{gone_session}/00000000000000000015"),
+    ] {
+        file_storage
+            .expect_delete_file_content()
+            .with(eq(id), eq(name))
+            .times(1)
+            .returning(|_, _| Ok(()));
+    }
+
+    // Sessions are looked up once however many chunks they hold.
+    let mut upload_session_repository = MockUploadSessionRepositoryImpl::new();
+    upload_session_repository
+        .expect_get()
+        .with(eq(live_session))
+        .times(1)
+        .returning(move |session_id| Ok(Some(session_row(session_id, id))));
+    upload_session_repository
+        .expect_get()
+        .with(eq(gone_session))
+        .times(1)
+        .returning(|_| Ok(None));
+
+    let report = collector(
+        listing(vec![row], 100),
+        file_storage,
+        upload_session_repository,
+    )
+    .collect(None, 100, false)
+    .await
+    .unwrap();
+
+    let orphans: Vec<_> = report.orphans.iter().map(|o| o.name.as_str()).collect();
+    assert_eq!(
+        orphans,
+        vec![
+            ".staging/1".to_string(),
+            format!(".sessions/{gone_session}/00000000000000000000"),
+            format!(".sessions/{gone_session}/00000000000000000015"),
+        ]
+    );
+    assert_eq!(report.uploads_checked, 1);
+    assert_eq!(report.deleted, 3);
+    assert_eq!(report.failed, 0);
+    assert_eq!(report.next_cursor, None);
+}
+
+#[tokio::test]
+async fn test_collect_on_dry_run_reports_objects_of_expired_uploads() {
+    let row = upload_row(UploadState::Expired, &["file1.txt"]);
+    let id = row.id;
+    let old = Utc::now() - Duration::hours(2);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_list()
+        .with(eq(id))
+        .times(1)
+        .returning(move |_| Ok(vec![object("file1.txt", old)]));
+    file_storage.expect_delete_file_content().times(0);
+
+    let report = collector(
+        listing(vec![row], 100),
+        file_storage,
+        MockUploadSessionRepositoryImpl::new(),
+    )
+    .collect(None, 100, true)
+    .await
+    .unwrap();
+
+    assert_eq!(report.orphans.len(), 1);
+    assert_eq!(report.orphans[0].upload_id, id);
+    assert_eq!(report.orphans[0].name, "file1.txt");
+    assert_eq!(report.deleted, 0);
+}
+
+#[tokio::test]
+async fn test_collect_goes_on_when_an_orphan_fails_to_delete() {
+    let first = upload_row(UploadState::Completed, &[]);
+    let second = upload_row(UploadState::Completed, &[]);
+    let (first_id, second_id) = (first.id, second.id);
+    let cursor = UploadCursor {
+        created_at: second.created_at,
+        id: second_id,
+    };
+    let old = Utc::now() - Duration::hours(2);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_list()
+        .times(2)
+        .returning(move |_| Ok(vec![object(".staging/1", old)]));
+    file_storage
+        .expect_delete_file_content()
+        .with(eq(first_id), eq(".staging/1"))
+        .times(1)
+        .returning(|_, _| Err(anyhow::anyhow!("storage is unavailable")));
+    file_storage
+        .expect_delete_file_content()
+        .with(eq(second_id), eq(".staging/1"))
+        .times(1)
+        .returning(|_, _| Ok(()));
+
+    let report = collector(
+        listing(vec![first, second], 2),
+        file_storage,
+        MockUploadSessionRepositoryImpl::new(),
+    )
+    .collect(None, 2, false)
+    .await
+    .unwrap();
+
+    assert_eq!(report.orphans.len(), 2);
+    assert_eq!(report.deleted, 1);
+    assert_eq!(report.failed, 1);
+    // The batch was full, there may be more uploads after it.
+    assert_eq!(report.next_cursor, Some(cursor));
+}
@@ -1,13 +1,34 @@
 #![cfg(feature = "persistent")]
 
+use chrono::{DateTime, Duration, Utc};
 use clickhouse::Client;
 use file_server_library::models::Hash32;
-use file_server_server::repositories::{
-    ClickhouseConfig, ClickhouseFileRepository, FileMerkleTreeRow, FileRepository,
+use file_server_server::{
+    models::{
+        AuditFilter, DeliveryState, ScrubFindingKind, UploadCursor, UploadFilter, UploadState,
+        WebhookEvent,
+    },
+    repositories::{
+        AuditEntryRow, AuditRepository, BlobReferenceRow, BlobRepository,
+        ClickhouseAuditRepository, ClickhouseBlobRepository, ClickhouseConfig,
+        ClickhouseFileRepository, ClickhouseIdempotencyRepository,
+        ClickhousePendingWriteRepository, ClickhouseScrubRepository,
+        ClickhouseTransparencyLogRepository, ClickhouseUploadSessionRepository,
+        ClickhouseUsageRepository, ClickhouseWebhookRepository, FileMerkleTreeRow, FileRepository,
+        IdempotencyRepository, IdempotencyRow, LogEntryRow, PendingWriteRepository,
+        PendingWriteRow, ScrubFindingRow, ScrubRepository, TransparencyLogRepository,
+        UploadSessionRepository, UploadSessionRow, UsageRepository, UsageRow, WebhookDeliveryRow,
+        WebhookRepository,
+    },
 };
 use std::collections::HashMap;
 use uuid::Uuid;
 
+// ClickHouse stores timestamps with millisecond precision.
+fn now_millis() -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(Utc::now().timestamp_millis()).unwrap()
+}
+
 const TABLE_NAME: &str = "files";
 
 async fn clear_files_table(config: &ClickhouseConfig) {
@@ -34,8 +55,12 @@ async fn test_full_operations_for_clickhouse_repository() {
 
     let repo = ClickhouseFileRepository::new(config);
 
+    let created_at = now_millis();
     let row = FileMerkleTreeRow {
         id: Uuid::new_v4(),
+        version: 1,
+        previous_version: None,
+        owner: "client-1".to_string(),
         order: vec!["file1.txt".to_string(), "file2.txt".to_string()],
         files: HashMap::from([
             (
@@ -51,7 +76,13 @@ async fn test_full_operations_for_clickhouse_repository() {
             Hash32::hash("contents_file_1".as_bytes()),
             Hash32::hash("contents_file_2".as_bytes()),
         ],
+        sizes: vec![15, 15],
+        content_types: vec!["text/plain".to_string(), "text/plain".to_string()],
+        uploaded_at: vec![created_at, created_at],
         root: None,
+        state: UploadState::Initiated,
+        created_at,
+        updated_at: created_at,
     };
 
     let insert_result = repo.insert(row.clone()).await;
@@ -65,6 +96,9 @@ async fn test_full_operations_for_clickhouse_repository() {
 
     let updated_row = FileMerkleTreeRow {
         id: row.id,
+        version: 1,
+        previous_version: None,
+        owner: "client-1".to_string(),
         order: vec![
             "file1.txt".to_string(),
             "file2.txt".to_string(),
@@ -89,7 +123,17 @@ async fn test_full_operations_for_clickhouse_repository() {
             Hash32::hash("contents_file_2".as_bytes()),
             Hash32::hash("contents_file_3".as_bytes()),
         ],
+        sizes: vec![15, 15, 15],
+        content_types: vec![
+            "text/plain".to_string(),
+            "text/plain".to_string(),
+            "text/csv".to_string(),
+        ],
+        uploaded_at: vec![created_at, created_at, now_millis()],
         root: None,
+        state: UploadState::Initiated,
+        created_at,
+        updated_at: now_millis(),
     };
     let update_result = repo.update(updated_row.clone()).await;
     assert!(update_result.is_ok());
@@ -99,4 +143,730 @@ async fn test_full_operations_for_clickhouse_repository() {
 
     let fetched_updated_row = get_updated_result.unwrap().unwrap();
     assert_eq!(fetched_updated_row, updated_row);
+
+    // Conditional updates only apply over the row as it was read.
+    let expired_row = FileMerkleTreeRow {
+        state: UploadState::Expired,
+        updated_at: updated_row.updated_at + Duration::milliseconds(1),
+        ..updated_row.clone()
+    };
+    let stale_updated_at = updated_row.updated_at - Duration::seconds(1);
+    assert!(
+        !repo
+            .update_if_unchanged(expired_row.clone(), stale_updated_at)
+            .await
+            .unwrap()
+    );
+    assert_eq!(repo.get(row.id).await.unwrap().unwrap(), updated_row);
+
+    assert!(
+        repo.update_if_unchanged(expired_row.clone(), updated_row.updated_at)
+            .await
+            .unwrap()
+    );
+    assert_eq!(repo.get(row.id).await.unwrap().unwrap(), expired_row);
+
+    // A racing update made against the same row loses to the one stored first.
+    let racing_row = FileMerkleTreeRow {
+        updated_at: expired_row.updated_at + Duration::milliseconds(1),
+        ..updated_row.clone()
+    };
+    assert!(
+        !repo
+            .update_if_unchanged(racing_row, updated_row.updated_at)
+            .await
+            .unwrap()
+    );
+    assert_eq!(repo.get(row.id).await.unwrap().unwrap(), expired_row);
+
+    // Deletions are conditional as well.
+    assert!(
+        !repo
+            .delete_if_unchanged(row.id, updated_row.updated_at)
+            .await
+            .unwrap()
+    );
+    assert_eq!(repo.get(row.id).await.unwrap().unwrap(), expired_row);
+
+    let delete_result = repo
+        .delete_if_unchanged(row.id, expired_row.updated_at)
+        .await;
+    assert!(delete_result.unwrap());
+
+    // Updates made over the tree as it was before it was deleted do not bring it back.
+    assert!(
+        !repo
+            .update_if_unchanged(expired_row.clone(), expired_row.updated_at)
+            .await
+            .unwrap()
+    );
+
+    let get_deleted_result = repo.get(row.id).await;
+    assert!(matches!(get_deleted_result, Ok(None)));
+}
+
+#[tokio::test]
+async fn test_versions_for_clickhouse_repository() {
+    dotenv::dotenv().ok();
+
+    let config = ClickhouseConfig::load_from_env().unwrap();
+    let repo = ClickhouseFileRepository::new(config);
+
+    let created_at = now_millis();
+    let first = FileMerkleTreeRow {
+        id: Uuid::new_v4(),
+        version: 1,
+        previous_version: None,
+        owner: "client-1".to_string(),
+        order: vec!["file1.txt".to_string()],
+        files: HashMap::new(),
+        leaf_hashes: vec![Hash32::hash("contents_file_1".as_bytes())],
+        sizes: vec![15],
+        content_types: vec!["text/plain".to_string()],
+        uploaded_at: vec![created_at],
+        root: Some(Hash32::hash("root_1".as_bytes())),
+        state: UploadState::Completed,
+        created_at,
+        updated_at: created_at,
+    };
+    let second = FileMerkleTreeRow {
+        version: 2,
+        previous_version: Some(1),
+        leaf_hashes: vec![Hash32::hash("contents_file_1_v2".as_bytes())],
+        root: Some(Hash32::hash("root_2".as_bytes())),
+        updated_at: now_millis(),
+        ..first.clone()
+    };
+
+    repo.insert(second.clone()).await.unwrap();
+    repo.insert_version(first.clone()).await.unwrap();
+    repo.insert_version(second.clone()).await.unwrap();
+
+    assert_eq!(repo.get(first.id).await.unwrap(), Some(second.clone()));
+    assert_eq!(
+        repo.get_version(first.id, 1).await.unwrap(),
+        Some(first.clone())
+    );
+    assert_eq!(
+        repo.get_version(first.id, 2).await.unwrap(),
+        Some(second.clone())
+    );
+    assert_eq!(repo.get_version(first.id, 3).await.unwrap(), None);
+
+    assert!(
+        repo.delete_if_unchanged(first.id, second.updated_at)
+            .await
+            .unwrap()
+    );
+
+    assert_eq!(repo.get(first.id).await.unwrap(), None);
+    assert_eq!(repo.get_version(first.id, 1).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_list_stale_for_clickhouse_repository() {
+    dotenv::dotenv().ok();
+
+    let config = ClickhouseConfig::load_from_env().unwrap();
+
+    clear_files_table(&config).await;
+
+    let repo = ClickhouseFileRepository::new(config);
+
+    let old = now_millis() - Duration::days(2);
+    let stale_row = FileMerkleTreeRow {
+        id: Uuid::new_v4(),
+        version: 1,
+        previous_version: None,
+        owner: "client-1".to_string(),
+        order: vec![],
+        files: HashMap::new(),
+        leaf_hashes: vec![],
+        sizes: vec![],
+        content_types: vec![],
+        uploaded_at: vec![],
+        root: None,
+        state: UploadState::Initiated,
+        created_at: old,
+        updated_at: old,
+    };
+    let fresh_row = FileMerkleTreeRow {
+        id: Uuid::new_v4(),
+        version: 1,
+        previous_version: None,
+        created_at: now_millis(),
+        updated_at: now_millis(),
+        ..stale_row.clone()
+    };
+
+    repo.insert(stale_row.clone()).await.unwrap();
+    repo.insert(fresh_row).await.unwrap();
+
+    let cutoff = Utc::now() - Duration::days(1);
+
+    let stale = repo
+        .list_stale(UploadState::Initiated, cutoff, 10)
+        .await
+        .unwrap();
+    assert_eq!(stale, vec![stale_row]);
+
+    let completed = repo
+        .list_stale(UploadState::Completed, cutoff, 10)
+        .await
+        .unwrap();
+    assert!(completed.is_empty());
+}
+
+#[tokio::test]
+async fn test_list_for_clickhouse_repository() {
+    dotenv::dotenv().ok();
+
+    let config = ClickhouseConfig::load_from_env().unwrap();
+
+    clear_files_table(&config).await;
+
+    let repo = ClickhouseFileRepository::new(config);
+
+    let now = now_millis();
+    let rows: Vec<_> = (0..3)
+        .map(|i| FileMerkleTreeRow {
+            id: Uuid::new_v4(),
+            version: 1,
+            previous_version: None,
+            owner: "client-1".to_string(),
+            order: vec![],
+            files: HashMap::new(),
+            leaf_hashes: vec![],
+            sizes: vec![],
+            content_types: vec![],
+            uploaded_at: vec![],
+            root: None,
+            state: UploadState::Initiated,
+            created_at: now - Duration::hours(i),
+            updated_at: now,
+        })
+        .collect();
+    let other_owner_row = FileMerkleTreeRow {
+        id: Uuid::new_v4(),
+        version: 1,
+        previous_version: None,
+        owner: "client-2".to_string(),
+        root: Some(Hash32::hash(b"root")),
+        state: UploadState::Completed,
+        ..rows[0].clone()
+    };
+
+    for row in rows.iter().chain([&other_owner_row]) {
+        repo.insert(row.clone()).await.unwrap();
+    }
+
+    let first_page = repo
+        .list("client-1", &UploadFilter::default(), None, 2)
+        .await
+        .unwrap();
+    assert_eq!(first_page, rows[..2].to_vec());
+
+    let cursor = UploadCursor {
+        created_at: rows[1].created_at,
+        id: rows[1].id,
+    };
+    let second_page = repo
+        .list("client-1", &UploadFilter::default(), Some(cursor), 2)
+        .await
+        .unwrap();
+    assert_eq!(second_page, rows[2..].to_vec());
+
+    let filter = UploadFilter {
+        state: Some(UploadState::Initiated),
+        created_after: Some(now - Duration::minutes(90)),
+        created_before: Some(now),
+    };
+    let filtered = repo.list("client-1", &filter, None, 10).await.unwrap();
+    assert_eq!(filtered, vec![rows[1].clone()]);
+
+    let completed = repo.list_completed(None, 10).await.unwrap();
+    assert_eq!(completed, vec![other_owner_row.clone()]);
+
+    let cursor = UploadCursor {
+        created_at: other_owner_row.created_at,
+        id: other_owner_row.id,
+    };
+    let after_last = repo.list_completed(Some(cursor), 10).await.unwrap();
+    assert!(after_last.is_empty());
+
+    let all = repo.list_all(None, 10).await.unwrap();
+    assert_eq!(all.len(), 4);
+    assert_eq!(all[0], rows[2]);
+
+    let cursor = UploadCursor {
+        created_at: rows[2].created_at,
+        id: rows[2].id,
+    };
+    let after_oldest = repo.list_all(Some(cursor), 1).await.unwrap();
+    assert_eq!(after_oldest, vec![rows[1].clone()]);
+}
+
+#[tokio::test]
+async fn test_blob_operations_for_clickhouse_repository() {
+    dotenv::dotenv().ok();
+
+    let config = ClickhouseConfig::load_from_env().unwrap();
+
+    let repo = ClickhouseBlobRepository::new(config);
+
+    let hash = Hash32::hash(Uuid::new_v4().as_bytes());
+    let upload_id = Uuid::new_v4();
+
+    let missing_result = repo.get(hash).await;
+    assert!(matches!(missing_result, Ok(None)));
+
+    repo.touch(hash).await.unwrap();
+    // Adding the same reference again, as retries do, keeps counting it once.
+    for name in ["file1.txt", "copy_of_file1.txt", "file1.txt"] {
+        repo.add_reference(BlobReferenceRow {
+            hash,
+            upload_id,
+            owner: "client-1".to_string(),
+            name: name.to_string(),
+            content_type: "text/plain".to_string(),
+            created_at: now_millis(),
+        })
+        .await
+        .unwrap();
+    }
+
+    let blob = repo.get(hash).await.unwrap().unwrap();
+    assert_eq!(blob.references, 2);
+    assert!(repo.is_referenced_by(hash, "client-1").await.unwrap());
+    assert!(!repo.is_referenced_by(hash, "client-2").await.unwrap());
+
+    let reference = repo
+        .get_reference(upload_id, "file1.txt", hash)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(reference.hash, hash);
+    assert_eq!(reference.content_type, "text/plain");
+
+    let cutoff = Utc::now() + Duration::days(1);
+    let referenced = repo.list_unreferenced(cutoff, 1000).await.unwrap();
+    assert!(referenced.iter().all(|blob| blob.hash != hash));
+
+    repo.remove_reference(upload_id, "copy_of_file1.txt", hash)
+        .await
+        .unwrap();
+
+    let blob = repo.get(hash).await.unwrap().unwrap();
+    assert_eq!(blob.references, 1);
+
+    repo.remove_references(upload_id).await.unwrap();
+
+    let blob = repo.get(hash).await.unwrap().unwrap();
+    assert_eq!(blob.references, 0);
+
+    let unreferenced = repo.list_unreferenced(cutoff, 1000).await.unwrap();
+    assert!(unreferenced.iter().any(|blob| blob.hash == hash));
+
+    repo.delete(hash).await.unwrap();
+
+    let deleted_result = repo.get(hash).await;
+    assert!(matches!(deleted_result, Ok(None)));
+}
+
+#[tokio::test]
+async fn test_scrub_operations_for_clickhouse_repository() {
+    dotenv::dotenv().ok();
+
+    let config = ClickhouseConfig::load_from_env().unwrap();
+
+    let repo = ClickhouseScrubRepository::new(config);
+
+    let finding = ScrubFindingRow {
+        upload_id: Uuid::new_v4(),
+        index: 3,
+        name: "file3.txt".to_string(),
+        kind: ScrubFindingKind::Corrupted,
+        expected_hash: Hash32::hash(b"contents_file_3"),
+        actual_hash: Some(Hash32::hash(b"rotten_file_3")),
+        detected_at: now_millis() + Duration::days(1),
+    };
+    repo.add_finding(finding.clone()).await.unwrap();
+
+    let newest = repo.list_findings(0, 1).await.unwrap();
+    assert_eq!(newest, vec![finding]);
+
+    let cursor = UploadCursor {
+        created_at: now_millis(),
+        id: Uuid::new_v4(),
+    };
+    repo.set_cursor(Some(cursor)).await.unwrap();
+    assert_eq!(repo.get_cursor().await.unwrap(), Some(cursor));
+
+    repo.set_cursor(None).await.unwrap();
+    assert_eq!(repo.get_cursor().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_transparency_log_operations_for_clickhouse_repository() {
+    dotenv::dotenv().ok();
+
+    let config = ClickhouseConfig::load_from_env().unwrap();
+
+    let client = Client::default()
+        .with_url(config.database_url.to_owned())
+        .with_user(config.username.to_owned())
+        .with_password(config.password.to_owned())
+        .with_database(config.database_name.to_owned());
+    client
+        .query("TRUNCATE TABLE transparency_log")
+        .execute()
+        .await
+        .unwrap();
+
+    let repo = ClickhouseTransparencyLogRepository::new(config);
+    let upload_id = Uuid::new_v4();
+
+    let entries: Vec<LogEntryRow> = (0..3)
+        .map(|index| LogEntryRow {
+            index,
+            upload_id: if index == 1 {
+                Uuid::new_v4()
+            } else {
+                upload_id
+            },
+            owner: "client-1".to_string(),
+            root: Hash32::hash(format!("root_{index}").as_bytes()),
+            appended_at: now_millis(),
+            leaf_hash: Hash32::hash(format!("leaf_{index}").as_bytes()),
+        })
+        .collect();
+    for entry in &entries {
+        assert!(repo.append(entry.clone()).await.unwrap());
+    }
+
+    // Another instance appending at an index already taken is not stored.
+    let late = LogEntryRow {
+        leaf_hash: Hash32::hash(b"late leaf"),
+        ..entries[2].clone()
+    };
+    assert!(!repo.append(late).await.unwrap());
+
+    assert_eq!(repo.size().await.unwrap(), 3);
+    assert_eq!(
+        repo.latest_for_upload(upload_id).await.unwrap(),
+        Some(entries[2].clone())
+    );
+    assert_eq!(repo.latest_for_upload(Uuid::new_v4()).await.unwrap(), None);
+    assert_eq!(
+        repo.leaf_hashes(2).await.unwrap(),
+        vec![entries[0].leaf_hash, entries[1].leaf_hash]
+    );
+}
+
+#[tokio::test]
+async fn test_usage_operations_for_clickhouse_repository() {
+    dotenv::dotenv().ok();
+
+    let config = ClickhouseConfig::load_from_env().unwrap();
+    let repo = ClickhouseUsageRepository::new(config);
+    let owner = Uuid::new_v4().to_string();
+
+    for (stored_bytes, open_uploads) in [(0, 1), (150, 0), (-50, -1)] {
+        repo.add(UsageRow {
+            owner: owner.clone(),
+            stored_bytes,
+            open_uploads,
+        })
+        .await
+        .unwrap();
+    }
+
+    assert_eq!(
+        repo.get(&owner).await.unwrap(),
+        UsageRow {
+            owner: owner.clone(),
+            stored_bytes: 100,
+            open_uploads: 0,
+        }
+    );
+    assert_eq!(
+        repo.get("nobody").await.unwrap(),
+        UsageRow {
+            owner: "nobody".to_string(),
+            ..Default::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_idempotency_operations_for_clickhouse_repository() {
+    dotenv::dotenv().ok();
+
+    let config = ClickhouseConfig::load_from_env().unwrap();
+    let repo = ClickhouseIdempotencyRepository::new(config);
+    let owner = Uuid::new_v4().to_string();
+
+    let row = IdempotencyRow {
+        owner: owner.clone(),
+        key: "initiate-1".to_string(),
+        fingerprint: "fingerprint".to_string(),
+        status: 201,
+        content_type: Some("application/json".to_string()),
+        headers: vec![("location".to_string(), "/api/v1/1".to_string())],
+        body: r#"{"id":"1"}"#.to_string(),
+        expires_at: now_millis() + Duration::hours(1),
+    };
+    repo.insert(row.clone()).await.unwrap();
+
+    assert_eq!(
+        repo.get(&owner, "initiate-1").await.unwrap(),
+        Some(row.clone())
+    );
+    assert_eq!(repo.get("nobody", "initiate-1").await.unwrap(), None);
+
+    repo.insert(IdempotencyRow {
+        key: "expired".to_string(),
+        expires_at: now_millis() - Duration::seconds(1),
+        ..row
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(repo.get(&owner, "expired").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_pending_write_operations_for_clickhouse_repository() {
+    dotenv::dotenv().ok();
+
+    let config = ClickhouseConfig::load_from_env().unwrap();
+    let repo = ClickhousePendingWriteRepository::new(config);
+
+    let row = PendingWriteRow {
+        id: Uuid::new_v4(),
+        upload_id: Uuid::new_v4(),
+        index: 3,
+        name: "file1.txt".to_string(),
+        content_type: "text/plain".to_string(),
+        staging_name: Some(".staging/1".to_string()),
+        hash: None,
+        size: 0,
+        created_at: now_millis() - Duration::days(3650),
+    };
+    repo.insert(row.clone()).await.unwrap();
+
+    let pending = repo
+        .list_before(now_millis() - Duration::days(3649), 1000)
+        .await
+        .unwrap();
+    assert!(pending.contains(&row));
+
+    let hash = Hash32::hash(b"contents_file_1");
+    repo.mark_stored(row.id, hash, 15).await.unwrap();
+
+    let pending = repo
+        .list_before(now_millis() - Duration::days(3649), 1000)
+        .await
+        .unwrap();
+    let stored = pending.iter().find(|write| write.id == row.id).unwrap();
+    assert_eq!(stored.hash, Some(hash));
+    assert_eq!(stored.size, 15);
+
+    repo.delete(row.id).await.unwrap();
+
+    let pending = repo
+        .list_before(now_millis() - Duration::days(3649), 1000)
+        .await
+        .unwrap();
+    assert!(pending.iter().all(|write| write.id != row.id));
+}
+
+#[tokio::test]
+async fn test_upload_session_operations_for_clickhouse_repository() {
+    dotenv::dotenv().ok();
+
+    let config = ClickhouseConfig::load_from_env().unwrap();
+    let repo = ClickhouseUploadSessionRepository::new(config);
+
+    let row = UploadSessionRow {
+        id: Uuid::new_v4(),
+        upload_id: Uuid::new_v4(),
+        name: "file1.txt".to_string(),
+        index: 0,
+        content_type: None,
+        length: Some(10),
+        offset: 0,
+        chunk_offsets: vec![],
+        chunk_ids: vec![],
+        created_at: now_millis(),
+        updated_at: now_millis(),
+    };
+    repo.insert(row.clone()).await.unwrap();
+    assert_eq!(repo.get(row.id).await.unwrap(), Some(row.clone()));
+
+    // Of two appends at the same offset only the first one is committed.
+    let appended = |chunk_id: Uuid| UploadSessionRow {
+        offset: 5,
+        chunk_offsets: vec![0],
+        chunk_ids: vec![chunk_id],
+        ..row.clone()
+    };
+    let first = appended(Uuid::new_v4());
+    assert!(repo.update_if_offset(first.clone(), 0).await.unwrap());
+    assert!(
+        !repo
+            .update_if_offset(appended(Uuid::new_v4()), 0)
+            .await
+            .unwrap()
+    );
+    assert_eq!(repo.get(row.id).await.unwrap(), Some(first.clone()));
+
+    let second = UploadSessionRow {
+        offset: 10,
+        chunk_offsets: vec![0, 5],
+        chunk_ids: vec![first.chunk_ids[0], Uuid::new_v4()],
+        ..first.clone()
+    };
+    assert!(repo.update_if_offset(second.clone(), 5).await.unwrap());
+    assert_eq!(repo.get(row.id).await.unwrap(), Some(second));
+
+    repo.delete_by_upload(row.upload_id).await.unwrap();
+    assert_eq!(repo.get(row.id).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_webhook_delivery_operations_for_clickhouse_repository() {
+    dotenv::dotenv().ok();
+
+    let config = ClickhouseConfig::load_from_env().unwrap();
+    let repo = ClickhouseWebhookRepository::new(config);
+
+    // A fresh owner keeps deliveries of previous runs out of the listing.
+    let owner = format!("client-{}", Uuid::new_v4());
+    let created_at = now_millis() - Duration::days(3650);
+    let row = WebhookDeliveryRow {
+        id: Uuid::new_v4(),
+        owner: owner.clone(),
+        event: WebhookEvent::Completed,
+        upload_id: Uuid::new_v4(),
+        url: "http://localhost:9000/hooks".to_string(),
+        payload: r#"{"event":"upload.completed"}"#.to_string(),
+        state: DeliveryState::Pending,
+        attempts: 0,
+        next_attempt_at: created_at,
+        last_status: None,
+        last_error: None,
+        created_at,
+        updated_at: created_at,
+    };
+    repo.insert(row.clone()).await.unwrap();
+
+    let due = repo
+        .list_due(now_millis() - Duration::days(3649), 1000)
+        .await
+        .unwrap();
+    assert!(due.contains(&row));
+
+    let delivered = WebhookDeliveryRow {
+        state: DeliveryState::Delivered,
+        attempts: 1,
+        last_status: Some(204),
+        updated_at: now_millis(),
+        ..row.clone()
+    };
+    repo.update(delivered.clone()).await.unwrap();
+
+    let due = repo
+        .list_due(now_millis() - Duration::days(3649), 1000)
+        .await
+        .unwrap();
+    assert!(due.iter().all(|delivery| delivery.id != row.id));
+
+    let deliveries = repo.list_by_owner(&owner, 0, 10).await.unwrap();
+    assert_eq!(deliveries, vec![delivered]);
+    assert!(repo.list_by_owner(&owner, 1, 10).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_audit_operations_for_clickhouse_repository() {
+    dotenv::dotenv().ok();
+
+    let config = ClickhouseConfig::load_from_env().unwrap();
+    let repo = ClickhouseAuditRepository::new(config);
+
+    // A fresh owner keeps entries of previous runs out of the chain.
+    let owner = format!("client-{}", Uuid::new_v4());
+    assert_eq!(repo.latest(&owner).await.unwrap(), None);
+
+    let upload_id = Uuid::new_v4();
+    let initiated = AuditEntryRow {
+        owner: owner.clone(),
+        sequence: 0,
+        action: "POST /api/v1/initiate".to_string(),
+        upload_id: Some(upload_id),
+        index: None,
+        name: None,
+        hash: None,
+        status: 201,
+        request_id: Some("request-1".to_string()),
+        correlation_id: None,
+        occurred_at: now_millis(),
+        previous_hash: Hash32::empty(),
+        entry_hash: Hash32::hash(b"initiated"),
+    };
+    let uploaded = AuditEntryRow {
+        sequence: 1,
+        action: "POST /api/v1/{id}/upload".to_string(),
+        index: Some(0),
+        name: Some("file1.txt".to_string()),
+        hash: Some(Hash32::hash(b"contents").to_hex()),
+        status: 200,
+        request_id: Some("request-2".to_string()),
+        previous_hash: initiated.entry_hash,
+        entry_hash: Hash32::hash(b"uploaded"),
+        ..initiated.clone()
+    };
+    let listed = AuditEntryRow {
+        sequence: 2,
+        action: "GET /api/v1/uploads".to_string(),
+        upload_id: None,
+        status: 200,
+        request_id: Some("request-3".to_string()),
+        previous_hash: uploaded.entry_hash,
+        entry_hash: Hash32::hash(b"listed"),
+        ..initiated.clone()
+    };
+    for entry in [&initiated, &uploaded, &listed] {
+        assert!(repo.append(entry.clone()).await.unwrap());
+    }
+
+    // Another instance chaining after the same entry is not stored.
+    let late = AuditEntryRow {
+        entry_hash: Hash32::hash(b"late"),
+        ..listed.clone()
+    };
+    assert!(!repo.append(late).await.unwrap());
+
+    assert_eq!(repo.latest(&owner).await.unwrap(), Some(listed.clone()));
+
+    let entries = repo
+        .list(&owner, &AuditFilter::default(), None, 10)
+        .await
+        .unwrap();
+    assert_eq!(entries, vec![initiated.clone(), uploaded.clone(), listed]);
+
+    let filter = AuditFilter {
+        upload_id: Some(upload_id),
+        ..Default::default()
+    };
+    let entries = repo.list(&owner, &filter, Some(0), 10).await.unwrap();
+    assert_eq!(entries, vec![uploaded.clone()]);
+
+    let filter = AuditFilter {
+        action: Some("POST /api/v1/initiate".to_string()),
+        occurred_before: Some(now_millis() + Duration::minutes(1)),
+        ..Default::default()
+    };
+    let entries = repo.list(&owner, &filter, None, 10).await.unwrap();
+    assert_eq!(entries, vec![initiated]);
 }
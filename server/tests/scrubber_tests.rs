@@ -0,0 +1,230 @@
+mod helpers;
+
+use crate::helpers::{
+    mocks::{MockFileRepositoryImpl, MockFileStorageImpl, MockScrubRepositoryImpl},
+    tree_fixtures::{tree_row, with_contents},
+};
+use bytes::Bytes;
+use chrono::Utc;
+use file_server_library::models::Hash32;
+use file_server_server::{
+    models::{ScrubFindingKind, UploadCursor, UploadState},
+    repositories::{FileMerkleTreeRow, FileStream},
+    services::{Scrubber, ScrubberConfig},
+};
+use futures::stream;
+use mockall::predicate::eq;
+use std::{io, sync::Arc};
+use uuid::Uuid;
+
+fn completed_row(files: &[(&str, &[u8])]) -> FileMerkleTreeRow {
+    let row = tree_row(Uuid::new_v4(), UploadState::Completed);
+    let mut row = files.iter().fold(row, |row, (name, contents)| {
+        with_contents(row, name, contents)
+    });
+    row.root = Some(Hash32::hash(b"root"));
+    row
+}
+
+fn stored(contents: &'static [u8]) -> FileStream {
+    Box::pin(stream::iter(vec![Ok::<_, io::Error>(Bytes::from_static(
+        contents,
+    ))]))
+}
+
+fn scrubber(
+    file_repository: MockFileRepositoryImpl,
+    file_storage: MockFileStorageImpl,
+    scrub_repository: MockScrubRepositoryImpl,
+) -> Scrubber {
+    Scrubber::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(scrub_repository),
+        ScrubberConfig::default(),
+    )
+}
+
+#[tokio::test]
+async fn test_scrub_records_missing_and_corrupted_contents() {
+    let row = completed_row(&[
+        ("file_1.txt", b"contents_file_1"),
+        ("file_2.txt", b"contents_file_2"),
+        ("file_3.txt", b"contents_file_3"),
+    ]);
+    let id = row.id;
+    let cursor = UploadCursor {
+        created_at: row.created_at,
+        id,
+    };
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_list_completed()
+        .with(eq(None), eq(10))
+        .times(1)
+        .returning(move |_, _| Ok(vec![row.clone()]));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_get_blob_content().returning(|hash, _| {
+        if hash == Hash32::hash(b"contents_file_1") {
+            Ok(Some(stored(b"contents_file_1")))
+        } else if hash == Hash32::hash(b"contents_file_2") {
+            Ok(Some(stored(b"rotten_file_2")))
+        } else {
+            Ok(None)
+        }
+    });
+
+    let mut scrub_repository = MockScrubRepositoryImpl::new();
+    scrub_repository.expect_get_cursor().returning(|| Ok(None));
+    scrub_repository
+        .expect_add_finding()
+        .withf(move |finding| {
+            finding.upload_id == id
+                && finding.index == 1
+                && finding.name == "file_2.txt"
+                && finding.kind == ScrubFindingKind::Corrupted
+                && finding.expected_hash == Hash32::hash(b"contents_file_2")
+                && finding.actual_hash == Some(Hash32::hash(b"rotten_file_2"))
+        })
+        .times(1)
+        .returning(|_| Ok(()));
+    scrub_repository
+        .expect_add_finding()
+        .withf(move |finding| {
+            finding.upload_id == id
+                && finding.index == 2
+                && finding.kind == ScrubFindingKind::Missing
+                && finding.actual_hash.is_none()
+        })
+        .times(1)
+        .returning(|_| Ok(()));
+    scrub_repository
+        .expect_set_cursor()
+        .with(eq(Some(cursor)))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let findings = scrubber(file_repository, file_storage, scrub_repository)
+        .scrub()
+        .await
+        .unwrap();
+    assert_eq!(findings, 2);
+}
+
+#[tokio::test]
+async fn test_scrub_reads_shared_contents_once() {
+    let first = completed_row(&[("file_1.txt", b"contents_file_1")]);
+    let second = completed_row(&[("copy_1.txt", b"contents_file_1")]);
+    let previous = UploadCursor {
+        created_at: Utc::now(),
+        id: Uuid::new_v4(),
+    };
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_list_completed()
+        .with(eq(Some(previous)), eq(10))
+        .times(1)
+        .returning(move |_, _| Ok(vec![first.clone(), second.clone()]));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_get_blob_content()
+        .times(1)
+        .returning(|_, _| Ok(Some(stored(b"contents_file_1"))));
+
+    let mut scrub_repository = MockScrubRepositoryImpl::new();
+    scrub_repository
+        .expect_get_cursor()
+        .returning(move || Ok(Some(previous)));
+    scrub_repository.expect_add_finding().times(0);
+    scrub_repository
+        .expect_set_cursor()
+        .times(2)
+        .returning(|_| Ok(()));
+
+    let findings = scrubber(file_repository, file_storage, scrub_repository)
+        .scrub()
+        .await
+        .unwrap();
+    assert_eq!(findings, 0);
+}
+
+#[tokio::test]
+async fn test_scrub_starts_over_after_the_last_upload() {
+    let previous = UploadCursor {
+        created_at: Utc::now(),
+        id: Uuid::new_v4(),
+    };
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_list_completed()
+        .times(1)
+        .returning(|_, _| Ok(vec![]));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_get_blob_content().times(0);
+
+    let mut scrub_repository = MockScrubRepositoryImpl::new();
+    scrub_repository
+        .expect_get_cursor()
+        .returning(move || Ok(Some(previous)));
+    scrub_repository
+        .expect_set_cursor()
+        .with(eq(None))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let findings = scrubber(file_repository, file_storage, scrub_repository)
+        .scrub()
+        .await
+        .unwrap();
+    assert_eq!(findings, 0);
+}
+
+#[tokio::test]
+async fn test_scrub_records_contents_failing_decryption_as_corrupted() {
+    let row = completed_row(&[("file_1.txt", b"contents_file_1")]);
+    let id = row.id;
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_list_completed()
+        .times(1)
+        .returning(move |_, _| Ok(vec![row.clone()]));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_get_blob_content().returning(|_, _| {
+        let contents: FileStream = Box::pin(stream::iter(vec![Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "failed to decrypt stored contents",
+        ))]));
+        Ok(Some(contents))
+    });
+
+    let mut scrub_repository = MockScrubRepositoryImpl::new();
+    scrub_repository.expect_get_cursor().returning(|| Ok(None));
+    scrub_repository
+        .expect_add_finding()
+        .withf(move |finding| {
+            finding.upload_id == id
+                && finding.index == 0
+                && finding.kind == ScrubFindingKind::Corrupted
+                && finding.actual_hash.is_none()
+        })
+        .times(1)
+        .returning(|_| Ok(()));
+    scrub_repository
+        .expect_set_cursor()
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let findings = scrubber(file_repository, file_storage, scrub_repository)
+        .scrub()
+        .await
+        .unwrap();
+    assert_eq!(findings, 1);
+}
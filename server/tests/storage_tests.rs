@@ -3,9 +3,30 @@
 use aws_config::{BehaviorVersion, Region};
 use aws_sdk_s3::{Client, config::Builder};
 use bytes::Bytes;
-use file_server_server::repositories::{FileStorage, S3FileStorage};
+use file_server_library::models::Hash32;
+use file_server_server::repositories::{FileStorage, FileStream, S3FileStorage};
+use futures::{TryStreamExt, stream};
+use std::ops::RangeInclusive;
 use uuid::Uuid;
 
+const PART_SIZE_BYTES: usize = 5 * 1024 * 1024;
+
+fn single_chunk(contents: &Bytes) -> FileStream {
+    Box::pin(stream::iter([Ok(contents.clone())]))
+}
+
+async fn read_all(
+    storage: &S3FileStorage,
+    id: Uuid,
+    name: &str,
+    range: Option<RangeInclusive<u64>>,
+) -> Option<Vec<u8>> {
+    let content = storage.get_file_content(id, name, range).await.unwrap()?;
+    let content = content.map_ok(|chunk| chunk.to_vec()).try_concat();
+
+    Some(content.await.unwrap())
+}
+
 async fn setup_s3_storage() -> S3FileStorage {
     let s3_region = std::env::var("AWS_REGION").unwrap();
     let s3_endpoint = std::env::var("AWS_ENDPOINT_URL").unwrap();
@@ -41,26 +62,181 @@ async fn test_full_operations_for_s3_file_storage() {
 
     let id = Uuid::new_v4();
 
-    let get_result = storage.get_file_content(id, "invalid_file.txt").await;
+    let get_result = storage.get_file_content(id, "invalid_file.txt", None).await;
     assert!(matches!(get_result, Ok(None)));
 
+    let metadata_result = storage.get_file_metadata(id, "invalid_file.txt").await;
+    assert!(matches!(metadata_result, Ok(None)));
+
     let file_1 = "file1.txt";
     let ccontents_file_1 = Bytes::from_static(b"contents_file_1");
     let first_insert_result = storage
-        .insert_file_content(id, file_1, &ccontents_file_1)
+        .insert_file_content(
+            id,
+            file_1,
+            "text/plain",
+            None,
+            single_chunk(&ccontents_file_1),
+        )
         .await;
     assert!(first_insert_result.is_ok());
 
     let file_2 = "file2.txt";
     let ccontents_file_2 = Bytes::from_static(b"contents_file_2");
     let second_insert_result = storage
-        .insert_file_content(id, file_2, &ccontents_file_2)
+        .insert_file_content(
+            id,
+            file_2,
+            "text/plain",
+            None,
+            single_chunk(&ccontents_file_2),
+        )
         .await;
     assert!(second_insert_result.is_ok());
 
-    let file_1_get_result = storage.get_file_content(id, file_1).await;
-    assert!(matches!(file_1_get_result, Ok(Some(contents)) if contents == ccontents_file_1));
+    let file_1_get_result = read_all(&storage, id, file_1, None).await;
+    assert_eq!(file_1_get_result, Some(ccontents_file_1.to_vec()));
+
+    let file_2_get_result = read_all(&storage, id, file_2, None).await;
+    assert_eq!(file_2_get_result, Some(ccontents_file_2.to_vec()));
+
+    let file_1_range_result = read_all(&storage, id, file_1, Some(9..=14)).await;
+    assert_eq!(file_1_range_result, Some(b"file_1".to_vec()));
+
+    let file_1_metadata = storage
+        .get_file_metadata(id, file_1)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(file_1_metadata.size, ccontents_file_1.len() as u64);
+    assert_eq!(file_1_metadata.content_type, "text/plain");
+}
+
+#[tokio::test]
+async fn test_delete_operations_for_s3_file_storage() {
+    dotenv::dotenv().ok();
+
+    let storage = setup_s3_storage().await;
+
+    let id = Uuid::new_v4();
+    let contents = Bytes::from_static(b"contents");
+
+    storage
+        .insert_file_content(id, "file1.txt", "text/plain", None, single_chunk(&contents))
+        .await
+        .unwrap();
+    storage
+        .insert_file_content(id, "file2.txt", "text/plain", None, single_chunk(&contents))
+        .await
+        .unwrap();
+
+    let mut listed: Vec<_> = storage
+        .list(id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|object| (object.name, object.size))
+        .collect();
+    listed.sort();
+    assert_eq!(
+        listed,
+        vec![("file1.txt".to_string(), 8), ("file2.txt".to_string(), 8)]
+    );
+
+    let delete_result = storage.delete_file_content(id, "file1.txt").await;
+    assert!(delete_result.is_ok());
+
+    let file_1_get_result = read_all(&storage, id, "file1.txt", None).await;
+    assert_eq!(file_1_get_result, None);
+
+    let delete_prefix_result = storage.delete_prefix(id).await;
+    assert!(delete_prefix_result.is_ok());
+
+    let file_2_get_result = read_all(&storage, id, "file2.txt", None).await;
+    assert_eq!(file_2_get_result, None);
+    assert!(storage.list(id).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_multipart_upload_for_s3_file_storage() {
+    dotenv::dotenv().ok();
+
+    let storage = setup_s3_storage().await.with_part_size(PART_SIZE_BYTES);
+
+    let id = Uuid::new_v4();
+    let chunk = Bytes::from(vec![7u8; 1024 * 1024]);
+    let chunks = (0..12).map(|_| Ok(chunk.clone())).collect::<Vec<_>>();
+
+    let insert_result = storage
+        .insert_file_content(
+            id,
+            "big_file.bin",
+            "application/octet-stream",
+            None,
+            Box::pin(stream::iter(chunks)),
+        )
+        .await;
+    assert!(insert_result.is_ok());
+
+    let contents = read_all(&storage, id, "big_file.bin", None).await.unwrap();
+    assert_eq!(contents.len(), 12 * chunk.len());
+    assert!(contents.iter().all(|b| *b == 7));
+}
+
+#[tokio::test]
+async fn test_blob_operations_for_s3_file_storage() {
+    dotenv::dotenv().ok();
+
+    let storage = setup_s3_storage().await;
+
+    let contents = Bytes::from(Uuid::new_v4().to_string());
+    let hash = Hash32::hash(&contents);
+
+    let missing_result = storage.get_blob_metadata(hash).await;
+    assert!(matches!(missing_result, Ok(None)));
+
+    // The same contents uploaded twice end up in a single blob.
+    let first_id = Uuid::new_v4();
+    let second_id = Uuid::new_v4();
+    for id in [first_id, second_id] {
+        storage
+            .insert_file_content(
+                id,
+                ".staging/file",
+                "text/plain",
+                None,
+                single_chunk(&contents),
+            )
+            .await
+            .unwrap();
+        storage
+            .move_to_blob(id, ".staging/file", hash)
+            .await
+            .unwrap();
+
+        let staged = read_all(&storage, id, ".staging/file", None).await;
+        assert_eq!(staged, None);
+    }
+
+    let metadata = storage.get_blob_metadata(hash).await.unwrap().unwrap();
+    assert_eq!(metadata.size, contents.len() as u64);
+    assert_eq!(metadata.content_type, "text/plain");
+
+    let blob = storage
+        .get_blob_content(hash, Some(0..=3))
+        .await
+        .unwrap()
+        .unwrap();
+    let blob = blob
+        .map_ok(|chunk| chunk.to_vec())
+        .try_concat()
+        .await
+        .unwrap();
+    assert_eq!(blob, contents[0..=3].to_vec());
+
+    let delete_result = storage.delete_blob(hash).await;
+    assert!(delete_result.is_ok());
 
-    let file_2_get_result = storage.get_file_content(id, file_2).await;
-    assert!(matches!(file_2_get_result, Ok(Some(contents)) if contents == ccontents_file_2));
+    let deleted_result = storage.get_blob_metadata(hash).await;
+    assert!(matches!(deleted_result, Ok(None)));
 }
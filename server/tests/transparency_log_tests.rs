@@ -0,0 +1,307 @@
+mod helpers;
+
+use crate::helpers::mocks::MockTransparencyLogRepositoryImpl;
+use chrono::DateTime;
+use ed25519_dalek::{Signature, SigningKey};
+use file_server_library::{
+    models::Hash32,
+    transparency::{LogLeaf, LogTree, log_head_message},
+};
+use file_server_server::{
+    infrastructure::ServerSigner,
+    models::LogHead,
+    repositories::LogEntryRow,
+    services::{FileServiceError, TransparencyLogService, TransparencyLogServiceImpl},
+};
+use mockall::predicate::eq;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+use uuid::Uuid;
+
+const SIGNING_SEED: [u8; 32] = [9; 32];
+
+fn entry(index: u64) -> LogEntryRow {
+    let upload_id = Uuid::new_v4();
+    let root = Hash32::hash(format!("root_{index}").as_bytes());
+    let appended_at = DateTime::from_timestamp_millis(1_700_000_000_000 + index as i64).unwrap();
+    let leaf = LogLeaf {
+        upload_id: upload_id.to_string(),
+        owner: "client-1".to_string(),
+        root: root.to_hex(),
+        appended_at: appended_at.timestamp_millis(),
+    };
+
+    LogEntryRow {
+        index,
+        upload_id,
+        owner: "client-1".to_string(),
+        root,
+        appended_at,
+        leaf_hash: leaf.leaf_hash(),
+    }
+}
+
+fn entries(size: u64) -> Vec<LogEntryRow> {
+    (0..size).map(entry).collect()
+}
+
+/// Serves `entries` as the whole log.
+fn repository(entries: &[LogEntryRow]) -> MockTransparencyLogRepositoryImpl {
+    let mut repository = MockTransparencyLogRepositoryImpl::new();
+
+    let size = entries.len() as u64;
+    repository.expect_size().returning(move || Ok(size));
+
+    let leaves: Vec<Hash32> = entries.iter().map(|entry| entry.leaf_hash).collect();
+    repository
+        .expect_leaf_hashes()
+        .returning(move |size| Ok(leaves[..size as usize].to_vec()));
+
+    let rows = entries.to_vec();
+    repository.expect_latest_for_upload().returning(move |id| {
+        Ok(rows
+            .iter()
+            .rev()
+            .find(|entry| entry.upload_id == id)
+            .cloned())
+    });
+
+    repository
+}
+
+fn service(repository: MockTransparencyLogRepositoryImpl) -> TransparencyLogServiceImpl {
+    let signer = ServerSigner::new(SigningKey::from_bytes(&SIGNING_SEED));
+    TransparencyLogServiceImpl::new(Arc::new(repository), Arc::new(signer))
+}
+
+fn verify_head(head: &LogHead) {
+    let message = log_head_message(head.size, &head.root, head.timestamp.timestamp_millis());
+    let signature = Signature::from_slice(&hex::decode(&head.signature).unwrap()).unwrap();
+
+    SigningKey::from_bytes(&SIGNING_SEED)
+        .verifying_key()
+        .verify_strict(&message, &signature)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_append_stores_entry_at_end_of_log() {
+    let upload_id = Uuid::new_v4();
+    let root = Hash32::hash(b"root");
+
+    let mut repository = repository(&entries(3));
+    repository
+        .expect_append()
+        .withf(move |entry| {
+            let leaf = LogLeaf {
+                upload_id: upload_id.to_string(),
+                owner: "client-2".to_string(),
+                root: root.to_hex(),
+                appended_at: entry.appended_at.timestamp_millis(),
+            };
+
+            entry.index == 3
+                && entry.upload_id == upload_id
+                && entry.owner == "client-2"
+                && entry.root == root
+                && entry.leaf_hash == leaf.leaf_hash()
+        })
+        .times(1)
+        .returning(|_| Ok(true));
+
+    let entry = service(repository)
+        .append(upload_id, "client-2", root)
+        .await
+        .unwrap();
+
+    assert_eq!(entry.index, 3);
+}
+
+#[tokio::test]
+async fn test_append_losing_index_to_another_instance_appends_at_next_one() {
+    let upload_id = Uuid::new_v4();
+
+    let mut repository = MockTransparencyLogRepositoryImpl::new();
+    repository
+        .expect_latest_for_upload()
+        .returning(|_| Ok(None));
+    // Another instance appends right after the size is read.
+    let size = AtomicU64::new(3);
+    repository
+        .expect_size()
+        .times(2)
+        .returning(move || Ok(size.fetch_add(1, Ordering::Relaxed)));
+    repository
+        .expect_append()
+        .withf(|entry| entry.index == 3)
+        .times(1)
+        .returning(|_| Ok(false));
+    repository
+        .expect_append()
+        .withf(move |entry| entry.index == 4 && entry.upload_id == upload_id)
+        .times(1)
+        .returning(|_| Ok(true));
+
+    let entry = service(repository)
+        .append(upload_id, "client-1", Hash32::hash(b"root"))
+        .await
+        .unwrap();
+
+    assert_eq!(entry.index, 4);
+}
+
+#[tokio::test]
+async fn test_append_same_root_again_returns_existing_entry() {
+    let log = entries(4);
+    let existing = log[1].clone();
+
+    let mut repository = repository(&log);
+    repository.expect_append().never();
+
+    let entry = service(repository)
+        .append(existing.upload_id, &existing.owner, existing.root)
+        .await
+        .unwrap();
+
+    assert_eq!(entry, existing);
+}
+
+#[tokio::test]
+async fn test_append_new_root_of_logged_upload_appends_again() {
+    let log = entries(2);
+    let upload_id = log[0].upload_id;
+
+    let mut repository = repository(&log);
+    repository
+        .expect_append()
+        .withf(move |entry| entry.index == 2 && entry.upload_id == upload_id)
+        .times(1)
+        .returning(|_| Ok(true));
+
+    service(repository)
+        .append(upload_id, "client-1", Hash32::hash(b"other root"))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_head_is_signed_root_of_whole_log() {
+    let log = entries(5);
+    let leaves: Vec<Hash32> = log.iter().map(|entry| entry.leaf_hash).collect();
+
+    let head = service(repository(&log)).head().await.unwrap();
+
+    assert_eq!(head.size, 5);
+    assert_eq!(head.root, LogTree::new(leaves).root());
+    verify_head(&head);
+}
+
+#[tokio::test]
+async fn test_inclusion_proof_verifies_against_signed_head() {
+    let log = entries(7);
+    let service = service(repository(&log));
+
+    for size in [None, Some(3)] {
+        let proof = service
+            .inclusion_proof("client-1", log[2].upload_id, size)
+            .await
+            .unwrap();
+
+        verify_head(&proof.head);
+        assert_eq!(proof.head.size, size.unwrap_or(7));
+        assert_eq!(proof.entry, log[2]);
+        assert!(LogTree::verify_inclusion(
+            &proof.entry.leaf_hash,
+            proof.entry.index,
+            proof.head.size,
+            &proof.proof,
+            &proof.head.root
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_inclusion_proof_rejects_sizes_not_holding_entry() {
+    let log = entries(4);
+    let service = service(repository(&log));
+
+    for size in [2, 5] {
+        let result = service
+            .inclusion_proof("client-1", log[2].upload_id, Some(size))
+            .await;
+
+        assert!(matches!(result, Err(FileServiceError::InvalidLogSize)));
+    }
+}
+
+#[tokio::test]
+async fn test_inclusion_proof_of_unlogged_upload_returns_not_found() {
+    let result = service(repository(&entries(2)))
+        .inclusion_proof("client-1", Uuid::new_v4(), None)
+        .await;
+
+    assert!(matches!(result, Err(FileServiceError::LogEntryNotFound)));
+}
+
+#[tokio::test]
+async fn test_inclusion_proof_of_upload_of_another_owner_returns_not_found() {
+    let log = entries(2);
+
+    let result = service(repository(&log))
+        .inclusion_proof("client-2", log[1].upload_id, None)
+        .await;
+
+    assert!(matches!(result, Err(FileServiceError::LogEntryNotFound)));
+}
+
+#[tokio::test]
+async fn test_consistency_proof_verifies_between_signed_heads() {
+    let log = entries(7);
+    let service = service(repository(&log));
+
+    for (first, second) in [(3, None), (1, Some(6)), (4, Some(4))] {
+        let proof = service.consistency_proof(first, second).await.unwrap();
+
+        verify_head(&proof.first);
+        verify_head(&proof.second);
+        assert_eq!(proof.first.size, first);
+        assert_eq!(proof.second.size, second.unwrap_or(7));
+        assert!(LogTree::verify_consistency(
+            proof.first.size,
+            proof.second.size,
+            &proof.first.root,
+            &proof.second.root,
+            &proof.proof
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_consistency_proof_rejects_invalid_sizes() {
+    let service = service(repository(&entries(4)));
+
+    for (first, second) in [(3, Some(2)), (1, Some(5)), (5, None)] {
+        let result = service.consistency_proof(first, second).await;
+
+        assert!(matches!(result, Err(FileServiceError::InvalidLogSize)));
+    }
+}
+
+#[tokio::test]
+async fn test_proofs_fail_when_log_has_missing_entries() {
+    let log = entries(4);
+
+    let mut repository = MockTransparencyLogRepositoryImpl::new();
+    repository.expect_size().returning(|| Ok(4));
+    let leaves: Vec<Hash32> = log[..3].iter().map(|entry| entry.leaf_hash).collect();
+    repository
+        .expect_leaf_hashes()
+        .with(eq(4))
+        .returning(move |_| Ok(leaves.clone()));
+
+    let result = service(repository).head().await;
+
+    assert!(matches!(result, Err(FileServiceError::StorageError(_))));
+}
@@ -0,0 +1,84 @@
+use file_server_library::{CustomMerkleTree, models::Hash32};
+use file_server_server::services::TreeCache;
+use std::{cell::Cell, sync::Arc};
+use uuid::Uuid;
+
+fn leaves(count: usize) -> Vec<Hash32> {
+    (0..count)
+        .map(|i| Hash32::hash(format!("contents_file_{i}").as_bytes()))
+        .collect()
+}
+
+#[test]
+fn test_get_or_build_builds_each_tree_once() {
+    let cache = TreeCache::new(4);
+    let id = Uuid::new_v4();
+    let leaves = leaves(3);
+    let root = CustomMerkleTree::new(leaves.clone()).root();
+    let builds = Cell::new(0);
+
+    for _ in 0..3 {
+        let tree = cache.get_or_build(id, root, || {
+            builds.set(builds.get() + 1);
+            leaves.clone()
+        });
+        assert_eq!(tree.root(), root);
+    }
+
+    assert_eq!(builds.get(), 1);
+}
+
+#[test]
+fn test_get_or_build_rebuilds_when_root_changes() {
+    let cache = TreeCache::new(4);
+    let id = Uuid::new_v4();
+    let first = leaves(2);
+    let second = leaves(3);
+    let first_root = CustomMerkleTree::new(first.clone()).root();
+    let second_root = CustomMerkleTree::new(second.clone()).root();
+    let builds = Cell::new(0);
+
+    cache.get_or_build(id, first_root, || {
+        builds.set(builds.get() + 1);
+        first.clone()
+    });
+    let tree = cache.get_or_build(id, second_root, || {
+        builds.set(builds.get() + 1);
+        second.clone()
+    });
+
+    assert_eq!(builds.get(), 2);
+    assert_eq!(tree.root(), second_root);
+}
+
+#[test]
+fn test_inserted_tree_is_returned_without_building() {
+    let cache = TreeCache::new(4);
+    let id = Uuid::new_v4();
+    let tree = Arc::new(CustomMerkleTree::new(leaves(2)));
+    let root = tree.root();
+
+    cache.insert(id, Arc::clone(&tree));
+    let cached = cache.get_or_build(id, root, || panic!("tree should be cached"));
+
+    assert!(Arc::ptr_eq(&tree, &cached));
+}
+
+#[test]
+fn test_least_recently_used_tree_is_evicted() {
+    let cache = TreeCache::new(1);
+    let first_id = Uuid::new_v4();
+    let second_id = Uuid::new_v4();
+    let leaves = leaves(2);
+    let root = CustomMerkleTree::new(leaves.clone()).root();
+    let builds = Cell::new(0);
+
+    for id in [first_id, second_id, first_id] {
+        cache.get_or_build(id, root, || {
+            builds.set(builds.get() + 1);
+            leaves.clone()
+        });
+    }
+
+    assert_eq!(builds.get(), 3);
+}
@@ -0,0 +1,339 @@
+mod helpers;
+
+use crate::helpers::{
+    mocks::{
+        MockBlobRepositoryImpl, MockFileRepositoryImpl, MockFileStorageImpl,
+        MockUploadSessionRepositoryImpl, MockUsageRepositoryImpl,
+    },
+    tree_fixtures::{last_updated_at, tree_row, with_contents},
+};
+use chrono::{Duration, Utc};
+use file_server_library::models::Hash32;
+use file_server_server::{
+    models::{UploadEvent, UploadState},
+    repositories::{FileMerkleTreeRow, UsageRow},
+    services::{ExpiryConfig, QuotaConfig, Quotas, UploadEvents, UploadReaper},
+};
+use futures::StreamExt;
+use mockall::predicate::eq;
+use std::sync::Arc;
+use uuid::Uuid;
+
+fn stale_row(state: UploadState) -> FileMerkleTreeRow {
+    let row = tree_row(Uuid::new_v4(), state);
+    let row = with_contents(row, "file1.txt", b"contents_file_1");
+    let row = with_contents(row, "file2.txt", b"contents_file_2");
+    last_updated_at(row, Utc::now() - Duration::days(2))
+}
+
+#[tokio::test]
+async fn test_reaper_expires_stale_initiated_uploads() {
+    let row = stale_row(UploadState::Initiated);
+    let id = row.id;
+    let listed_at = row.updated_at;
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    let listed = row.clone();
+    file_repository
+        .expect_list_stale()
+        .withf(|state, _, _| *state == UploadState::Initiated)
+        .times(1)
+        .returning(move |_, _, _| Ok(vec![listed.clone()]));
+    file_repository
+        .expect_update_if_unchanged()
+        .withf(move |tree, updated_at| {
+            tree.id == id && tree.state == UploadState::Expired && *updated_at == listed_at
+        })
+        .times(1)
+        .returning(|_, _| Ok(true));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_delete_prefix()
+        .with(eq(id))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_remove_references()
+        .with(eq(id))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut upload_session_repository = MockUploadSessionRepositoryImpl::new();
+    upload_session_repository
+        .expect_delete_by_upload()
+        .with(eq(id))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let reaper = UploadReaper::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(upload_session_repository),
+        Arc::new(blob_repository),
+        ExpiryConfig::default(),
+    );
+
+    let expired = reaper.reap().await.unwrap();
+    assert_eq!(expired, 1);
+}
+
+#[tokio::test]
+async fn test_reaper_takes_expired_upload_off_usage_of_owner() {
+    let mut row = stale_row(UploadState::Initiated);
+    row.sizes = vec![10, 20];
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_list_stale()
+        .times(1)
+        .returning(move |_, _, _| Ok(vec![row.clone()]));
+    file_repository
+        .expect_update_if_unchanged()
+        .returning(|_, _| Ok(true));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_delete_prefix().returning(|_| Ok(()));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_remove_references()
+        .returning(|_| Ok(()));
+
+    let mut usage_repository = MockUsageRepositoryImpl::new();
+    usage_repository
+        .expect_add()
+        .with(eq(UsageRow {
+            owner: "client-1".to_string(),
+            stored_bytes: -30,
+            open_uploads: -1,
+        }))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut upload_session_repository = MockUploadSessionRepositoryImpl::new();
+    upload_session_repository
+        .expect_delete_by_upload()
+        .returning(|_| Ok(()));
+
+    let reaper = UploadReaper::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(upload_session_repository),
+        Arc::new(blob_repository),
+        ExpiryConfig::default(),
+    )
+    .with_quotas(Arc::new(Quotas::new(
+        QuotaConfig::default(),
+        Arc::new(usage_repository),
+    )));
+
+    let expired = reaper.reap().await.unwrap();
+    assert_eq!(expired, 1);
+}
+
+#[tokio::test]
+async fn test_reaper_publishes_expiry_of_upload() {
+    let row = stale_row(UploadState::Initiated);
+    let id = row.id;
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_list_stale()
+        .times(1)
+        .returning(move |_, _, _| Ok(vec![row.clone()]));
+    file_repository
+        .expect_update_if_unchanged()
+        .returning(|_, _| Ok(true));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_delete_prefix().returning(|_| Ok(()));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_remove_references()
+        .returning(|_| Ok(()));
+
+    let upload_events = UploadEvents::default();
+    let events = upload_events.subscribe(id);
+
+    let mut upload_session_repository = MockUploadSessionRepositoryImpl::new();
+    upload_session_repository
+        .expect_delete_by_upload()
+        .returning(|_| Ok(()));
+
+    let reaper = UploadReaper::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(upload_session_repository),
+        Arc::new(blob_repository),
+        ExpiryConfig::default(),
+    )
+    .with_upload_events(upload_events);
+
+    assert_eq!(reaper.reap().await.unwrap(), 1);
+
+    let events: Vec<_> = events.collect().await;
+    assert_eq!(events, vec![UploadEvent::Expired]);
+}
+
+#[tokio::test]
+async fn test_reaper_keeps_upload_changed_since_listed() {
+    let row = stale_row(UploadState::Initiated);
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_list_stale()
+        .times(1)
+        .returning(move |_, _, _| Ok(vec![row.clone()]));
+    file_repository
+        .expect_update_if_unchanged()
+        .times(1)
+        .returning(|_, _| Ok(false));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_delete_prefix().times(0);
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository.expect_remove_references().times(0);
+
+    let mut upload_session_repository = MockUploadSessionRepositoryImpl::new();
+    upload_session_repository.expect_delete_by_upload().times(0);
+
+    let reaper = UploadReaper::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(upload_session_repository),
+        Arc::new(blob_repository),
+        ExpiryConfig::default(),
+    );
+
+    let expired = reaper.reap().await.unwrap();
+    assert_eq!(expired, 0);
+}
+
+#[tokio::test]
+async fn test_reaper_expires_upload_when_storage_delete_fails() {
+    let row = stale_row(UploadState::Initiated);
+    let id = row.id;
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_list_stale()
+        .times(1)
+        .returning(move |_, _, _| Ok(vec![row.clone()]));
+    file_repository
+        .expect_update_if_unchanged()
+        .times(1)
+        .returning(|_, _| Ok(true));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_delete_prefix()
+        .returning(|_| Err(anyhow::anyhow!("storage unavailable")));
+
+    // Objects left behind are the orphan collector's, the rest is still removed.
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_remove_references()
+        .with(eq(id))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut upload_session_repository = MockUploadSessionRepositoryImpl::new();
+    upload_session_repository
+        .expect_delete_by_upload()
+        .with(eq(id))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let reaper = UploadReaper::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(upload_session_repository),
+        Arc::new(blob_repository),
+        ExpiryConfig::default(),
+    );
+
+    let expired = reaper.reap().await.unwrap();
+    assert_eq!(expired, 1);
+}
+
+#[tokio::test]
+async fn test_reaper_checks_completed_uploads_only_when_ttl_is_configured() {
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_list_stale()
+        .withf(|state, _, _| *state == UploadState::Initiated)
+        .times(1)
+        .returning(|_, _, _| Ok(vec![]));
+    file_repository
+        .expect_list_stale()
+        .withf(|state, _, _| *state == UploadState::Completed)
+        .times(0);
+
+    let upload_session_repository = MockUploadSessionRepositoryImpl::new();
+
+    let reaper = UploadReaper::new(
+        Arc::new(file_repository),
+        Arc::new(MockFileStorageImpl::new()),
+        Arc::new(upload_session_repository),
+        Arc::new(MockBlobRepositoryImpl::new()),
+        ExpiryConfig::default(),
+    );
+
+    let expired = reaper.reap().await.unwrap();
+    assert_eq!(expired, 0);
+}
+
+#[tokio::test]
+async fn test_reaper_discards_stale_version_instead_of_expiring_upload() {
+    let mut row = stale_row(UploadState::Initiated);
+    row.version = 2;
+    row.previous_version = Some(1);
+    let id = row.id;
+    let listed_at = row.updated_at;
+
+    let mut previous = stale_row(UploadState::Completed);
+    previous.id = id;
+    previous.root = Some(Hash32::hash("root".as_bytes()));
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_list_stale()
+        .withf(|state, _, _| *state == UploadState::Initiated)
+        .times(1)
+        .returning(move |_, _, _| Ok(vec![row.clone()]));
+    let restored = previous.clone();
+    file_repository
+        .expect_get_version()
+        .with(eq(id), eq(1))
+        .times(1)
+        .returning(move |_, _| Ok(Some(restored.clone())));
+    file_repository
+        .expect_update_if_unchanged()
+        .with(eq(previous), eq(listed_at))
+        .times(1)
+        .returning(|_, _| Ok(true));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_delete_prefix().times(0);
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository.expect_remove_references().times(0);
+
+    let upload_session_repository = MockUploadSessionRepositoryImpl::new();
+
+    let reaper = UploadReaper::new(
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(upload_session_repository),
+        Arc::new(blob_repository),
+        ExpiryConfig::default(),
+    );
+
+    let expired = reaper.reap().await.unwrap();
+    assert_eq!(expired, 0);
+}
@@ -0,0 +1,507 @@
+mod helpers;
+
+use crate::helpers::{
+    mocks::{
+        MockFileRepositoryImpl, MockFileServiceImpl, MockFileStorageImpl,
+        MockUploadSessionRepositoryImpl, MockUsageRepositoryImpl,
+    },
+    tree_fixtures::tree_row,
+};
+use bytes::Bytes;
+use chrono::Utc;
+use file_server_server::{
+    models::{FileMetadata, Quota, QuotaLimits, QuotaViolation, UploadSession, UploadState},
+    repositories::{FileStream, UploadSessionRow, UsageRow},
+    services::{
+        FileServiceError, QuotaConfig, Quotas, UploadSessionService, UploadSessionServiceImpl,
+    },
+};
+use futures::{TryStreamExt, executor::block_on, stream};
+use mockall::predicate::eq;
+use std::{
+    io,
+    sync::{
+        Arc, Barrier, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+use uuid::Uuid;
+
+fn session_row(upload_id: Uuid, length: Option<u64>, chunk_offsets: Vec<u64>) -> UploadSessionRow {
+    let now = Utc::now();
+
+    UploadSessionRow {
+        id: Uuid::new_v4(),
+        upload_id,
+        name: "file1.txt".to_string(),
+        index: 0,
+        content_type: None,
+        length,
+        offset: chunk_offsets.last().map(|offset| offset + 5).unwrap_or(0),
+        chunk_offsets,
+        chunk_ids: vec![],
+        created_at: now,
+        updated_at: now,
+    }
+}
+
+fn chunk(contents: &'static [u8]) -> FileStream {
+    Box::pin(stream::iter([Ok::<_, io::Error>(Bytes::from_static(
+        contents,
+    ))]))
+}
+
+fn file_repository_with_upload() -> MockFileRepositoryImpl {
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(|id| Ok(Some(tree_row(id, UploadState::Initiated))));
+    file_repository
+}
+
+fn session_repository_with(row: UploadSessionRow) -> MockUploadSessionRepositoryImpl {
+    let mut upload_session_repository = MockUploadSessionRepositoryImpl::new();
+    upload_session_repository
+        .expect_get()
+        .with(eq(row.id))
+        .returning(move |_| Ok(Some(row.clone())));
+    upload_session_repository
+}
+
+fn service(
+    file_service: MockFileServiceImpl,
+    file_storage: MockFileStorageImpl,
+    upload_session_repository: MockUploadSessionRepositoryImpl,
+) -> UploadSessionServiceImpl {
+    UploadSessionServiceImpl::new(
+        Arc::new(file_service),
+        Arc::new(file_repository_with_upload()),
+        Arc::new(file_storage),
+        Arc::new(upload_session_repository),
+    )
+}
+
+// `client-1` stores 10 bytes already.
+fn quotas(limits: QuotaLimits) -> Arc<Quotas> {
+    let mut usage_repository = MockUsageRepositoryImpl::new();
+    usage_repository.expect_get().returning(|owner| {
+        Ok(UsageRow {
+            owner: owner.to_string(),
+            stored_bytes: 10,
+            open_uploads: 1,
+        })
+    });
+
+    Arc::new(Quotas::new(
+        QuotaConfig {
+            default: limits,
+            ..Default::default()
+        },
+        Arc::new(usage_repository),
+    ))
+}
+
+#[tokio::test]
+async fn test_append_commits_chunk_and_advances_offset() {
+    let upload_id = Uuid::new_v4();
+    let row = session_row(upload_id, Some(10), vec![]);
+    let session_id = row.id;
+
+    let mut upload_session_repository = session_repository_with(row);
+    upload_session_repository
+        .expect_update_if_offset()
+        .withf(|session, offset| {
+            session.offset == 5 && session.chunk_offsets == vec![0] && *offset == 0
+        })
+        .times(1)
+        .returning(|_, _| Ok(true));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    let expected_chunk_prefix = format!(".sessions/{}/{:020}-", session_id, 0);
+    file_storage
+        .expect_insert_file_content()
+        .withf(move |id, name, _, _, _| {
+            *id == upload_id && name.starts_with(&expected_chunk_prefix)
+        })
+        .times(1)
+        .returning(|_, _, _, _, content| {
+            block_on(content.try_collect::<Vec<_>>())?;
+            Ok(())
+        });
+
+    let service = service(
+        MockFileServiceImpl::new(),
+        file_storage,
+        upload_session_repository,
+    );
+
+    let session = service
+        .append("client-1", upload_id, session_id, 0, chunk(b"01234"))
+        .await
+        .unwrap();
+
+    assert_eq!(session.offset(), 5);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_concurrent_appends_at_same_offset_commit_only_one_chunk() {
+    let upload_id = Uuid::new_v4();
+    let row = session_row(upload_id, Some(10), vec![]);
+    let session_id = row.id;
+
+    // Compare and set over the stored session, as the repositories do.
+    let stored = Arc::new(Mutex::new(row));
+    let mut upload_session_repository = MockUploadSessionRepositoryImpl::new();
+    let current = Arc::clone(&stored);
+    upload_session_repository
+        .expect_get()
+        .returning(move |_| Ok(Some(current.lock().unwrap().clone())));
+    let current = Arc::clone(&stored);
+    upload_session_repository
+        .expect_update_if_offset()
+        .times(2)
+        .returning(move |session, offset| {
+            let mut current = current.lock().unwrap();
+            if current.offset != offset {
+                return Ok(false);
+            }
+            *current = session;
+            Ok(true)
+        });
+
+    // Both appends store their chunk only once both passed the offset check. Each one gets its
+    // own expectation, a single one would let the first append hold it while waiting.
+    let barrier = Arc::new(Barrier::new(2));
+    let claimed = Arc::new(AtomicBool::new(false));
+    let inserted = Arc::new(Mutex::new(Vec::new()));
+    let deleted = Arc::new(Mutex::new(Vec::new()));
+    let mut file_storage = MockFileStorageImpl::new();
+    for first in [true, false] {
+        let claimed = Arc::clone(&claimed);
+        let barrier = Arc::clone(&barrier);
+        let chunks = Arc::clone(&inserted);
+        file_storage
+            .expect_insert_file_content()
+            .withf(move |_, _, _, _, _| !first || !claimed.swap(true, Ordering::SeqCst))
+            .times(1)
+            .returning(move |_, name, _, _, content| {
+                barrier.wait();
+                block_on(content.try_collect::<Vec<_>>())?;
+                chunks.lock().unwrap().push(name.to_string());
+                Ok(())
+            });
+    }
+    let chunks = Arc::clone(&deleted);
+    file_storage
+        .expect_delete_file_content()
+        .times(1)
+        .returning(move |_, name| {
+            chunks.lock().unwrap().push(name.to_string());
+            Ok(())
+        });
+
+    let service = Arc::new(service(
+        MockFileServiceImpl::new(),
+        file_storage,
+        upload_session_repository,
+    ));
+
+    let appends = [b"01234", b"abcde"].map(|contents: &'static [u8; 5]| {
+        let service = Arc::clone(&service);
+        tokio::spawn(async move {
+            service
+                .append("client-1", upload_id, session_id, 0, chunk(contents))
+                .await
+        })
+    });
+    let mut results = Vec::new();
+    for append in appends {
+        results.push(append.await.unwrap());
+    }
+
+    assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+    assert!(
+        results
+            .iter()
+            .any(|result| matches!(result, Err(FileServiceError::UploadOffsetMismatch(5))))
+    );
+
+    let committed = UploadSession::from(stored.lock().unwrap().clone());
+    assert_eq!(committed.offset(), 5);
+    let committed = committed.chunk_names();
+    let inserted = inserted.lock().unwrap().clone();
+    let deleted = deleted.lock().unwrap().clone();
+    assert_eq!(committed.len(), 1);
+    assert_ne!(inserted[0], inserted[1]);
+    assert!(inserted.contains(&committed[0]));
+    assert!(inserted.contains(&deleted[0]));
+    assert_ne!(committed[0], deleted[0]);
+}
+
+#[tokio::test]
+async fn test_append_with_wrong_offset_returns_committed_offset() {
+    let upload_id = Uuid::new_v4();
+    let row = session_row(upload_id, None, vec![0]);
+    let session_id = row.id;
+
+    let mut upload_session_repository = session_repository_with(row);
+    upload_session_repository.expect_update_if_offset().times(0);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_insert_file_content().times(0);
+
+    let service = service(
+        MockFileServiceImpl::new(),
+        file_storage,
+        upload_session_repository,
+    );
+
+    let result = service
+        .append("client-1", upload_id, session_id, 0, chunk(b"01234"))
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(FileServiceError::UploadOffsetMismatch(5))
+    ));
+}
+
+#[tokio::test]
+async fn test_append_exceeding_length_is_rejected() {
+    let upload_id = Uuid::new_v4();
+    let row = session_row(upload_id, Some(3), vec![]);
+    let session_id = row.id;
+
+    let mut upload_session_repository = session_repository_with(row);
+    upload_session_repository.expect_update_if_offset().times(0);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_insert_file_content()
+        .times(1)
+        .returning(|_, _, _, _, content| {
+            block_on(content.try_collect::<Vec<_>>())?;
+            Ok(())
+        });
+    file_storage
+        .expect_delete_file_content()
+        .times(1)
+        .returning(|_, _| Ok(()));
+
+    let service = service(
+        MockFileServiceImpl::new(),
+        file_storage,
+        upload_session_repository,
+    );
+
+    let result = service
+        .append("client-1", upload_id, session_id, 0, chunk(b"01234"))
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(FileServiceError::UploadLengthExceeded)
+    ));
+}
+
+#[tokio::test]
+async fn test_create_with_length_beyond_quota_returns_quota_exceeded() {
+    let mut upload_session_repository = MockUploadSessionRepositoryImpl::new();
+    upload_session_repository.expect_insert().times(0);
+
+    let service = service(
+        MockFileServiceImpl::new(),
+        MockFileStorageImpl::new(),
+        upload_session_repository,
+    )
+    .with_quotas(quotas(QuotaLimits {
+        max_stored_bytes: Some(20),
+        ..Default::default()
+    }));
+
+    let metadata = FileMetadata {
+        name: "file1.txt".to_string(),
+        index: 0,
+        content_type: None,
+    };
+    let result = service
+        .create("client-1", Uuid::new_v4(), metadata, Some(11))
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(FileServiceError::QuotaExceeded(QuotaViolation {
+            quota: Quota::StoredBytes,
+            limit: 20,
+            used: 21,
+        }))
+    ));
+}
+
+#[tokio::test]
+async fn test_append_beyond_quota_counting_committed_chunks_returns_quota_exceeded() {
+    let upload_id = Uuid::new_v4();
+    let row = session_row(upload_id, None, vec![0]);
+    let session_id = row.id;
+
+    let mut upload_session_repository = session_repository_with(row);
+    upload_session_repository.expect_update_if_offset().times(0);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_insert_file_content()
+        .times(1)
+        .returning(|_, _, _, _, content| {
+            block_on(content.try_collect::<Vec<_>>())?;
+            Ok(())
+        });
+    file_storage
+        .expect_delete_file_content()
+        .times(1)
+        .returning(|_, _| Ok(()));
+
+    let service = service(
+        MockFileServiceImpl::new(),
+        file_storage,
+        upload_session_repository,
+    )
+    .with_quotas(quotas(QuotaLimits {
+        max_file_size_bytes: Some(8),
+        ..Default::default()
+    }));
+
+    let result = service
+        .append("client-1", upload_id, session_id, 5, chunk(b"56789"))
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(FileServiceError::QuotaExceeded(QuotaViolation {
+            quota: Quota::FileSize,
+            limit: 8,
+            used: 10,
+        }))
+    ));
+}
+
+#[tokio::test]
+async fn test_finalize_uploads_chunks_in_order_and_cleans_up() {
+    let upload_id = Uuid::new_v4();
+    let row = session_row(upload_id, Some(10), vec![0, 5]);
+    let session_id = row.id;
+
+    let mut upload_session_repository = session_repository_with(row);
+    upload_session_repository
+        .expect_delete()
+        .with(eq(session_id))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_get_file_content()
+        .withf(|_, name, _| name.ends_with(&format!("{:020}", 0)))
+        .returning(|_, _, _| Ok(Some(chunk(b"01234"))));
+    file_storage
+        .expect_get_file_content()
+        .withf(|_, name, _| name.ends_with(&format!("{:020}", 5)))
+        .returning(|_, _, _| Ok(Some(chunk(b"56789"))));
+    file_storage
+        .expect_delete_file_content()
+        .times(2)
+        .returning(|_, _| Ok(()));
+
+    let mut file_service = MockFileServiceImpl::new();
+    file_service
+        .expect_upload_file()
+        .withf(move |owner, id, metadata, _| {
+            owner == "client-1" && *id == upload_id && metadata.name == "file1.txt"
+        })
+        .times(1)
+        .returning(|_, _, _, content| {
+            let content = block_on(content.map_ok(|chunk| chunk.to_vec()).try_concat()).unwrap();
+            assert_eq!(content, b"0123456789".to_vec());
+            Ok("encoded_hash".to_string())
+        });
+
+    let service = service(file_service, file_storage, upload_session_repository);
+
+    let encoded_hash = service
+        .finalize("client-1", upload_id, session_id)
+        .await
+        .unwrap();
+
+    assert_eq!(encoded_hash, "encoded_hash");
+}
+
+#[tokio::test]
+async fn test_finalize_incomplete_session_is_rejected() {
+    let upload_id = Uuid::new_v4();
+    let row = session_row(upload_id, Some(20), vec![0]);
+    let session_id = row.id;
+
+    let mut file_service = MockFileServiceImpl::new();
+    file_service.expect_upload_file().times(0);
+
+    let service = service(
+        file_service,
+        MockFileStorageImpl::new(),
+        session_repository_with(row),
+    );
+
+    let result = service.finalize("client-1", upload_id, session_id).await;
+
+    assert!(matches!(
+        result,
+        Err(FileServiceError::UploadSessionIncomplete)
+    ));
+}
+
+#[tokio::test]
+async fn test_session_of_another_upload_is_not_found() {
+    let row = session_row(Uuid::new_v4(), None, vec![]);
+    let session_id = row.id;
+
+    let service = service(
+        MockFileServiceImpl::new(),
+        MockFileStorageImpl::new(),
+        session_repository_with(row),
+    );
+
+    let result = service.get("client-1", Uuid::new_v4(), session_id).await;
+
+    assert!(matches!(
+        result,
+        Err(FileServiceError::UploadSessionNotFound)
+    ));
+}
+
+#[tokio::test]
+async fn test_session_of_upload_of_another_owner_is_not_found() {
+    let upload_id = Uuid::new_v4();
+    let row = session_row(upload_id, Some(5), vec![0]);
+    let session_id = row.id;
+
+    let mut file_service = MockFileServiceImpl::new();
+    file_service.expect_upload_file().times(0);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage.expect_insert_file_content().times(0);
+
+    let service = service(file_service, file_storage, session_repository_with(row));
+
+    assert!(matches!(
+        service.get("client-2", upload_id, session_id).await,
+        Err(FileServiceError::FileNotFound)
+    ));
+    assert!(matches!(
+        service
+            .append("client-2", upload_id, session_id, 5, chunk(b"56789"))
+            .await,
+        Err(FileServiceError::FileNotFound)
+    ));
+    assert!(matches!(
+        service.finalize("client-2", upload_id, session_id).await,
+        Err(FileServiceError::FileNotFound)
+    ));
+}
@@ -0,0 +1,154 @@
+mod helpers;
+
+use crate::helpers::mocks::MockWebhookRepositoryImpl;
+use axum::{Router, body::Bytes, extract::State, http::HeaderMap, http::StatusCode, routing::post};
+use chrono::{Duration, Utc};
+use file_server_server::{
+    models::{DeliveryState, WebhookEvent},
+    repositories::WebhookDeliveryRow,
+    services::{WebhookConfig, WebhookDispatcher},
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+/// Serves a webhook endpoint answering every request with `status` and `body`, and returns its
+/// url along with the requests it received.
+async fn endpoint(status: StatusCode, body: &'static str) -> (String, Received) {
+    let received = Received::default();
+    let app = Router::new()
+        .route(
+            "/hooks",
+            post(
+                move |State(received): State<Received>, headers: HeaderMap, payload: Bytes| async move {
+                    let payload = String::from_utf8(payload.to_vec()).unwrap();
+                    received.lock().unwrap().push((headers, payload));
+                    (status, body)
+                },
+            ),
+        )
+        .with_state(Arc::clone(&received));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    (format!("http://{address}/hooks"), received)
+}
+
+fn delivery(url: &str, attempts: u32) -> WebhookDeliveryRow {
+    let created_at = Utc::now() - Duration::minutes(5);
+
+    WebhookDeliveryRow {
+        id: Uuid::new_v4(),
+        owner: "client-1".to_string(),
+        event: WebhookEvent::Completed,
+        upload_id: Uuid::new_v4(),
+        url: url.to_string(),
+        payload: r#"{"event":"upload.completed"}"#.to_string(),
+        state: DeliveryState::Pending,
+        attempts,
+        next_attempt_at: created_at,
+        last_status: None,
+        last_error: None,
+        created_at,
+        updated_at: created_at,
+    }
+}
+
+/// Dispatches `delivery` once and returns it as it was updated.
+async fn dispatch(delivery: WebhookDeliveryRow) -> WebhookDeliveryRow {
+    let updated = Arc::new(Mutex::new(None));
+
+    let mut webhook_repository = MockWebhookRepositoryImpl::new();
+    webhook_repository
+        .expect_list_due()
+        .times(1)
+        .returning(move |_, _| Ok(vec![delivery.clone()]));
+    let tracked = Arc::clone(&updated);
+    webhook_repository
+        .expect_update()
+        .times(1)
+        .returning(move |delivery| {
+            *tracked.lock().unwrap() = Some(delivery);
+            Ok(())
+        });
+
+    let config = WebhookConfig {
+        max_attempts: 4,
+        base_delay_secs: 10,
+        ..Default::default()
+    };
+    let secrets = HashMap::from([("client-1".to_string(), "secret-1".to_string())]);
+
+    WebhookDispatcher::new(Arc::new(webhook_repository), config, secrets)
+        .unwrap()
+        .dispatch()
+        .await
+        .unwrap();
+
+    updated.lock().unwrap().take().unwrap()
+}
+
+#[tokio::test]
+async fn test_dispatch_signs_payload_with_client_secret() {
+    let (url, received) = endpoint(StatusCode::NO_CONTENT, "").await;
+
+    let updated = dispatch(delivery(&url, 0)).await;
+
+    assert_eq!(updated.state, DeliveryState::Delivered);
+    assert_eq!(updated.attempts, 1);
+    assert_eq!(updated.last_status, Some(204));
+    assert_eq!(updated.last_error, None);
+
+    let received = received.lock().unwrap();
+    let (headers, payload) = &received[0];
+    assert_eq!(payload, r#"{"event":"upload.completed"}"#);
+    assert_eq!(headers["x-auth-key"], "client-1");
+    assert_eq!(headers["content-type"], "application/json");
+
+    let timestamp = headers["x-auth-ts"].to_str().unwrap();
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"secret-1").unwrap();
+    mac.update(format!("{timestamp}.{payload}").as_bytes());
+    assert_eq!(
+        headers["x-auth-signature"].to_str().unwrap(),
+        hex::encode(mac.finalize().into_bytes())
+    );
+}
+
+#[tokio::test]
+async fn test_dispatch_backs_off_after_failed_attempt() {
+    let (url, _) = endpoint(StatusCode::INTERNAL_SERVER_ERROR, "not now").await;
+
+    let updated = dispatch(delivery(&url, 2)).await;
+
+    assert_eq!(updated.state, DeliveryState::Pending);
+    assert_eq!(updated.attempts, 3);
+    assert_eq!(updated.last_status, Some(500));
+    assert_eq!(updated.last_error.as_deref(), Some("not now"));
+    // Third failed attempt, twice the delay of the second one.
+    let delay = updated.next_attempt_at - updated.updated_at;
+    assert_eq!(delay, Duration::seconds(40));
+}
+
+#[tokio::test]
+async fn test_dispatch_fails_delivery_once_out_of_attempts() {
+    // Nothing listens on the port once the listener is dropped.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+    drop(listener);
+
+    let updated = dispatch(delivery(&url, 3)).await;
+
+    assert_eq!(updated.state, DeliveryState::Failed);
+    assert_eq!(updated.attempts, 4);
+    assert_eq!(updated.last_status, None);
+    assert!(updated.last_error.is_some());
+}
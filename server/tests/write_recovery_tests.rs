@@ -0,0 +1,405 @@
+mod helpers;
+
+use crate::helpers::{
+    mocks::{
+        MockBlobRepositoryImpl, MockFileRepositoryImpl, MockFileStorageImpl,
+        MockPendingWriteRepositoryImpl, MockUsageRepositoryImpl,
+    },
+    tree_fixtures::{listed_once, tree_row, with_file},
+};
+use chrono::{Duration, Utc};
+use file_server_library::models::Hash32;
+use file_server_server::{
+    models::UploadState,
+    repositories::{BlobReferenceRow, PendingWriteRow, StoredFileMetadata, UsageRow},
+    services::{QuotaConfig, Quotas, RecoveryReport, WriteRecovery, WriteRecoveryConfig},
+};
+use mockall::predicate::eq;
+use std::sync::Arc;
+use uuid::Uuid;
+
+fn pending_write(upload_id: Uuid, hash: Option<Hash32>) -> PendingWriteRow {
+    PendingWriteRow {
+        id: Uuid::new_v4(),
+        upload_id,
+        index: 0,
+        name: "file1.txt".to_string(),
+        content_type: "text/plain".to_string(),
+        staging_name: Some(".staging/1".to_string()),
+        hash,
+        size: 15,
+        created_at: Utc::now() - Duration::hours(2),
+    }
+}
+
+// The journal is empty once listed, as recovery removed the writes.
+fn listing(writes: Vec<PendingWriteRow>) -> MockPendingWriteRepositoryImpl {
+    let mut next_page = listed_once(writes);
+    let mut pending_write_repository = MockPendingWriteRepositoryImpl::new();
+    pending_write_repository
+        .expect_list_before()
+        .withf(|created_before, limit| {
+            *created_before < Utc::now() - Duration::minutes(59) && *limit == 100
+        })
+        .returning(move |_, _| Ok(next_page()));
+    pending_write_repository
+}
+
+fn recovery(
+    pending_write_repository: MockPendingWriteRepositoryImpl,
+    file_repository: MockFileRepositoryImpl,
+    file_storage: MockFileStorageImpl,
+    blob_repository: MockBlobRepositoryImpl,
+) -> WriteRecovery {
+    WriteRecovery::new(
+        Arc::new(pending_write_repository),
+        Arc::new(file_repository),
+        Arc::new(file_storage),
+        Arc::new(blob_repository),
+        WriteRecoveryConfig::default(),
+    )
+}
+
+#[tokio::test]
+async fn test_recovery_rolls_back_writes_cut_before_storing_contents() {
+    let upload_id = Uuid::new_v4();
+    let write = pending_write(upload_id, None);
+    let write_id = write.id;
+
+    let mut pending_write_repository = MockPendingWriteRepositoryImpl::new();
+    pending_write_repository
+        .expect_list_before()
+        .times(1)
+        .returning(move |_, _| Ok(vec![write.clone()]));
+    pending_write_repository
+        .expect_list_before()
+        .returning(|_, _| Ok(vec![]));
+    pending_write_repository
+        .expect_delete()
+        .with(eq(write_id))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_delete_file_content()
+        .withf(move |id, name| *id == upload_id && name == ".staging/1")
+        .times(1)
+        .returning(|_, _| Ok(()));
+
+    let recovery = recovery(
+        pending_write_repository,
+        MockFileRepositoryImpl::new(),
+        file_storage,
+        MockBlobRepositoryImpl::new(),
+    );
+
+    assert_eq!(
+        recovery.recover().await.unwrap(),
+        RecoveryReport {
+            rolled_back: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_recovery_finishes_stored_writes_of_open_uploads() {
+    let upload_id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+    let write = pending_write(upload_id, Some(hash));
+
+    let mut pending_write_repository = listing(vec![write.clone()]);
+    pending_write_repository
+        .expect_delete()
+        .with(eq(write.id))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .with(eq(upload_id))
+        .returning(|id| Ok(Some(tree_row(id, UploadState::Initiated))));
+    file_repository
+        .expect_update()
+        .withf(move |row| {
+            row.order == vec!["file1.txt"] && row.leaf_hashes == vec![hash] && row.sizes == vec![15]
+        })
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_get_blob_metadata()
+        .with(eq(hash))
+        .returning(|_| {
+            Ok(Some(StoredFileMetadata {
+                size: 15,
+                content_type: "application/octet-stream".to_string(),
+                content_encoding: None,
+            }))
+        });
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_get_reference()
+        .returning(|_, _, _| Ok(None));
+    blob_repository
+        .expect_add_reference()
+        .withf(move |reference| {
+            reference.hash == hash
+                && reference.upload_id == upload_id
+                && reference.name == "file1.txt"
+                && reference.content_type == "text/plain"
+        })
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut usage_repository = MockUsageRepositoryImpl::new();
+    usage_repository
+        .expect_add()
+        .with(eq(UsageRow {
+            owner: "client-1".to_string(),
+            stored_bytes: 15,
+            open_uploads: 0,
+        }))
+        .times(1)
+        .returning(|_| Ok(()));
+    let quotas = Arc::new(Quotas::new(
+        QuotaConfig::default(),
+        Arc::new(usage_repository),
+    ));
+
+    let recovery = recovery(
+        pending_write_repository,
+        file_repository,
+        file_storage,
+        blob_repository,
+    )
+    .with_quotas(quotas);
+
+    assert_eq!(
+        recovery.recover().await.unwrap(),
+        RecoveryReport {
+            finished: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_recovery_drops_writes_the_upload_already_holds() {
+    let upload_id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+    let write = pending_write(upload_id, Some(hash));
+
+    let mut pending_write_repository = listing(vec![write.clone()]);
+    pending_write_repository
+        .expect_delete()
+        .with(eq(write.id))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(move |id| {
+        Ok(Some(with_file(
+            tree_row(id, UploadState::Completed),
+            "file1.txt",
+            hash,
+        )))
+    });
+    file_repository.expect_update().times(0);
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_get_reference()
+        .with(eq(upload_id), eq("file1.txt"), eq(hash))
+        .returning(move |upload_id, name, hash| {
+            Ok(Some(BlobReferenceRow {
+                hash,
+                upload_id,
+                owner: "client-1".to_string(),
+                name: name.to_string(),
+                content_type: "text/plain".to_string(),
+                created_at: Utc::now(),
+            }))
+        });
+    blob_repository.expect_add_reference().times(0);
+
+    let recovery = recovery(
+        pending_write_repository,
+        file_repository,
+        MockFileStorageImpl::new(),
+        blob_repository,
+    );
+
+    assert_eq!(
+        recovery.recover().await.unwrap(),
+        RecoveryReport {
+            finished: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_recovery_rolls_back_stored_writes_of_completed_uploads() {
+    let upload_id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+    let write = pending_write(upload_id, Some(hash));
+
+    let mut pending_write_repository = listing(vec![write.clone()]);
+    pending_write_repository
+        .expect_delete()
+        .with(eq(write.id))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository
+        .expect_get()
+        .returning(|id| Ok(Some(tree_row(id, UploadState::Completed))));
+    file_repository.expect_update().times(0);
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_delete_file_content()
+        .times(1)
+        .returning(|_, _| Ok(()));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository
+        .expect_remove_reference()
+        .with(eq(upload_id), eq("file1.txt"), eq(hash))
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+
+    let recovery = recovery(
+        pending_write_repository,
+        file_repository,
+        file_storage,
+        blob_repository,
+    );
+
+    assert_eq!(
+        recovery.recover().await.unwrap(),
+        RecoveryReport {
+            rolled_back: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_recovery_keeps_references_held_by_previous_versions() {
+    let upload_id = Uuid::new_v4();
+    let hash = Hash32::hash(b"contents_file_1");
+    let write = pending_write(upload_id, Some(hash));
+
+    let mut pending_write_repository = listing(vec![write.clone()]);
+    pending_write_repository
+        .expect_delete()
+        .times(1)
+        .returning(|_| Ok(()));
+
+    // The second version replaced the file, the first one still points at the blob.
+    let mut file_repository = MockFileRepositoryImpl::new();
+    file_repository.expect_get().returning(|id| {
+        let mut row = with_file(
+            tree_row(id, UploadState::Initiated),
+            "file1.txt",
+            Hash32::hash(b"contents_file_2"),
+        );
+        row.version = 2;
+        row.previous_version = Some(1);
+        Ok(Some(row))
+    });
+    file_repository
+        .expect_get_version()
+        .with(eq(upload_id), eq(1))
+        .times(1)
+        .returning(move |id, _| {
+            Ok(Some(with_file(
+                tree_row(id, UploadState::Completed),
+                "file1.txt",
+                hash,
+            )))
+        });
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_delete_file_content()
+        .returning(|_, _| Ok(()));
+
+    let mut blob_repository = MockBlobRepositoryImpl::new();
+    blob_repository.expect_remove_reference().times(0);
+
+    let recovery = recovery(
+        pending_write_repository,
+        file_repository,
+        file_storage,
+        blob_repository,
+    );
+
+    assert_eq!(
+        recovery.recover().await.unwrap(),
+        RecoveryReport {
+            rolled_back: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_recovery_goes_on_when_a_write_fails_to_recover() {
+    let upload_id = Uuid::new_v4();
+    let failing = pending_write(upload_id, None);
+    let mut recovered = pending_write(upload_id, None);
+    recovered.staging_name = Some(".staging/2".to_string());
+
+    let mut pending_write_repository = MockPendingWriteRepositoryImpl::new();
+    let listed = vec![failing.clone(), recovered.clone()];
+    pending_write_repository
+        .expect_list_before()
+        .withf(|_, limit| *limit == 100)
+        .times(1)
+        .returning(move |_, _| Ok(listed.clone()));
+    // The failed write is still in the journal and is skipped past.
+    pending_write_repository
+        .expect_list_before()
+        .withf(|_, limit| *limit == 101)
+        .times(1)
+        .returning(move |_, _| Ok(vec![failing.clone()]));
+    pending_write_repository
+        .expect_delete()
+        .with(eq(recovered.id))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let mut file_storage = MockFileStorageImpl::new();
+    file_storage
+        .expect_delete_file_content()
+        .withf(|_, name| name == ".staging/1")
+        .returning(|_, _| Err(anyhow::anyhow!("storage is unavailable")));
+    file_storage
+        .expect_delete_file_content()
+        .withf(|_, name| name == ".staging/2")
+        .returning(|_, _| Ok(()));
+
+    let recovery = recovery(
+        pending_write_repository,
+        MockFileRepositoryImpl::new(),
+        file_storage,
+        MockBlobRepositoryImpl::new(),
+    );
+
+    assert_eq!(
+        recovery.recover().await.unwrap(),
+        RecoveryReport {
+            rolled_back: 1,
+            failed: 1,
+            ..Default::default()
+        }
+    );
+}